@@ -0,0 +1,232 @@
+use {
+    crate::world::{Entity, Position, Velocity},
+    koto::{
+        bytecode::{Chunk, Loader, LoaderError},
+        runtime::{
+            runtime_error, BinaryOp, CallArgs, RuntimeError, Value, ValueList, ValueMap, Vm,
+        },
+    },
+    std::{collections::HashMap, sync::Arc},
+};
+
+/// A component that can be exposed to scripts as a map
+///
+/// Each of the component's fields becomes an entry in the map.
+pub trait ScriptComponent {
+    /// The component's fields, along with their current values
+    fn fields(&self) -> Vec<(&'static str, Value)>;
+
+    /// Updates a field with a value that was assigned by a script
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<(), RuntimeError>;
+}
+
+impl ScriptComponent for Position {
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![("x", self.x.into()), ("y", self.y.into())]
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<(), RuntimeError> {
+        match (name, value) {
+            ("x", Value::Number(n)) => self.x = n.into(),
+            ("y", Value::Number(n)) => self.y = n.into(),
+            _ => return runtime_error!("Position: invalid value for '{}': {}", name, value),
+        }
+        Ok(())
+    }
+}
+
+impl ScriptComponent for Velocity {
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![("x", self.x.into()), ("y", self.y.into())]
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<(), RuntimeError> {
+        match (name, value) {
+            ("x", Value::Number(n)) => self.x = n.into(),
+            ("y", Value::Number(n)) => self.y = n.into(),
+            _ => return runtime_error!("Velocity: invalid value for '{}': {}", name, value),
+        }
+        Ok(())
+    }
+}
+
+/// A component's data exposed as a map, with changes made by scripts tracked
+///
+/// A snapshot of the component's fields is taken when the map is created, after running a
+/// script the map is compared against the snapshot, and only the fields that were changed are
+/// written back to the component.
+pub struct ComponentMap {
+    map: ValueMap,
+    snapshot: Vec<(&'static str, Value)>,
+}
+
+impl ComponentMap {
+    pub fn new(component: &impl ScriptComponent) -> Self {
+        let snapshot = component.fields();
+
+        let mut map = ValueMap::with_capacity(snapshot.len());
+        for (name, value) in snapshot.iter() {
+            map.add_value(name, value.clone());
+        }
+
+        Self { map, snapshot }
+    }
+
+    /// Writes fields that were changed by scripts back to the component
+    ///
+    /// The names of the changed fields are returned, which the application can use to decide
+    /// what needs to be updated, e.g. only re-rendering entities that have moved.
+    pub fn apply_changes(
+        &self,
+        vm: &mut Vm,
+        component: &mut impl ScriptComponent,
+    ) -> Result<Vec<&'static str>, RuntimeError> {
+        let mut changed = Vec::new();
+
+        for (name, previous) in self.snapshot.iter() {
+            let current = match self.map.data().get_with_string(name) {
+                Some(current) => current.clone(),
+                None => return runtime_error!("The field '{}' was removed by a script", name),
+            };
+
+            match vm.run_binary_op(BinaryOp::Equal, current.clone(), previous.clone())? {
+                Value::Bool(true) => {}
+                _ => {
+                    component.set_field(name, &current)?;
+                    changed.push(*name);
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+/// An entity exposed to scripts as a map, containing its id and a map for each of its components
+pub struct EntityMap {
+    pub entity: Entity,
+    map: ValueMap,
+    components: Vec<(&'static str, ComponentMap)>,
+}
+
+impl EntityMap {
+    pub fn new(entity: Entity, name: &str) -> Self {
+        let mut map = ValueMap::new();
+        map.add_value("id", entity.0.into());
+        map.add_value("name", name.into());
+
+        Self {
+            entity,
+            map,
+            components: Vec::new(),
+        }
+    }
+
+    /// Adds a component to the entity's map, with the given name as its key
+    pub fn with_component(mut self, name: &'static str, component: &impl ScriptComponent) -> Self {
+        let component_map = ComponentMap::new(component);
+        self.map.add_map(name, component_map.map.clone());
+        self.components.push((name, component_map));
+        self
+    }
+
+    /// The value that gets passed to scripts
+    pub fn value(&self) -> Value {
+        Value::Map(self.map.clone())
+    }
+
+    /// Writes changes made by scripts back to the named component, see [ComponentMap]
+    pub fn apply_changes(
+        &self,
+        vm: &mut Vm,
+        name: &str,
+        component: &mut impl ScriptComponent,
+    ) -> Result<Vec<&'static str>, RuntimeError> {
+        match self
+            .components
+            .iter()
+            .find(|(component_name, _)| *component_name == name)
+        {
+            Some((_, component_map)) => component_map.apply_changes(vm, component),
+            None => runtime_error!("The entity doesn't have a '{}' component", name),
+        }
+    }
+}
+
+/// Calls a system's script function once with a batch of entities
+///
+/// Calling the function once per system rather than once per entity keeps the overhead of
+/// switching between Rust and Koto low, with the script iterating over the entities itself.
+/// The function is called with a list of entity maps, followed by any additional arguments.
+pub fn run_system(
+    vm: &mut Vm,
+    system: &str,
+    batch: &[EntityMap],
+    extra_args: &[Value],
+) -> Result<(), RuntimeError> {
+    let function = match vm.get_exported_function(system) {
+        Some(function) => function,
+        None => return runtime_error!("Missing system function '{}'", system),
+    };
+
+    let entities = batch.iter().map(EntityMap::value).collect::<Vec<_>>();
+    let mut args = vec![Value::List(ValueList::from_slice(&entities))];
+    args.extend_from_slice(extra_args);
+
+    vm.run_function(function, CallArgs::Separate(&args))?;
+    Ok(())
+}
+
+/// Compiles scripts once, so that the resulting chunks can be shared by many script instances
+#[derive(Default)]
+pub struct ScriptCache {
+    loader: Loader,
+    chunks: HashMap<String, Arc<Chunk>>,
+}
+
+impl ScriptCache {
+    pub fn get_or_compile(&mut self, name: &str, script: &str) -> Result<Arc<Chunk>, LoaderError> {
+        if let Some(chunk) = self.chunks.get(name) {
+            return Ok(chunk.clone());
+        }
+
+        let chunk = self.loader.compile_script(script, &None)?;
+        self.chunks.insert(name.to_string(), chunk.clone());
+        Ok(chunk)
+    }
+}
+
+/// An instance of a script that's attached to an entity
+///
+/// Each instance runs in its own VM with its own exports, so that script state isn't shared
+/// between entities, while the prelude and the compiled chunk are shared by all instances.
+pub struct ScriptInstance {
+    vm: Vm,
+}
+
+impl ScriptInstance {
+    /// Runs the chunk in a new VM that shares its prelude with the parent VM
+    pub fn new(parent: &Vm, chunk: Arc<Chunk>) -> Result<Self, RuntimeError> {
+        let mut vm = parent.spawn_new_vm();
+        vm.run(chunk)?;
+        Ok(Self { vm })
+    }
+
+    /// Calls a function that was exported by the script instance
+    ///
+    /// Scripts don't need to implement every callback, so `None` is returned if the script
+    /// doesn't export a function with a matching name.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Option<Value>, RuntimeError> {
+        match self.vm.get_exported_function(name) {
+            Some(function) => self
+                .vm
+                .run_function(function, CallArgs::Separate(args))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn vm_mut(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+}
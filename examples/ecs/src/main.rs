@@ -0,0 +1,155 @@
+mod koto_bindings;
+mod world;
+
+use {
+    koto::runtime::{Value, Vm},
+    koto_bindings::{run_system, EntityMap, ScriptCache, ScriptInstance},
+    std::{
+        collections::{HashMap, HashSet},
+        error::Error,
+    },
+    world::{Behaviour, Entity, Position, Velocity, World},
+};
+
+const SYSTEMS_SCRIPT: &str = include_str!("../scripts/systems.koto");
+const BOUNCER_SCRIPT: &str = include_str!("../scripts/bouncer.koto");
+
+const FRAME_COUNT: usize = 8;
+const DT: f64 = 0.25;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut world = make_world();
+
+    let vm = Vm::default();
+    let mut scripts = ScriptCache::default();
+
+    // The systems script is run once in its own VM, with its exported functions called each frame
+    let mut systems = ScriptInstance::new(&vm, scripts.get_or_compile("systems", SYSTEMS_SCRIPT)?)?;
+
+    // Each entity with a behaviour gets its own script instance,
+    // with the compiled behaviour script shared between instances.
+    let mut behaviours = HashMap::new();
+    for entity in world.entities() {
+        if let Some(Behaviour(name)) = &world.behaviours[entity.0] {
+            let chunk = scripts.get_or_compile(name, behaviour_script(name))?;
+            behaviours.insert(entity, ScriptInstance::new(&vm, chunk)?);
+        }
+    }
+
+    for frame in 0..FRAME_COUNT {
+        let mut changes: HashMap<Entity, HashSet<String>> = HashMap::new();
+
+        // Run the systems, with each system being called once for all of the moving entities
+        let batch = world
+            .moving_entities()
+            .into_iter()
+            .map(|entity| make_entity_map(&world, entity))
+            .collect::<Vec<_>>();
+        for system in &["gravity", "movement"] {
+            run_system(systems.vm_mut(), system, &batch, &[DT.into()])?;
+        }
+        for entity_map in batch.iter() {
+            let changed = apply_changes(systems.vm_mut(), &mut world, entity_map)?;
+            changes
+                .entry(entity_map.entity)
+                .or_default()
+                .extend(changed);
+        }
+
+        // Run the per-entity behaviours
+        for entity in world.entities() {
+            if let Some(behaviour) = behaviours.get_mut(&entity) {
+                let entity_map = make_entity_map(&world, entity);
+                behaviour.call("on_update", &[entity_map.value(), DT.into()])?;
+                let changed = apply_changes(behaviour.vm_mut(), &mut world, &entity_map)?;
+                changes.entry(entity).or_default().extend(changed);
+            }
+        }
+
+        println!("Frame {}", frame);
+        for entity in world.entities() {
+            let changed = changes.get(&entity).map_or(0, |changed| changed.len());
+            match &world.positions[entity.0] {
+                Some(position) => println!(
+                    "  {:<8} x: {:>6.2} y: {:>6.2} ({} changed fields)",
+                    world.names[entity.0], position.x, position.y, changed
+                ),
+                None => println!("  {:<8} (no position)", world.names[entity.0]),
+            }
+        }
+    }
+
+    println!("\nBounces");
+    for entity in world.entities() {
+        if let Some(behaviour) = behaviours.get_mut(&entity) {
+            if let Some(Value::Number(bounces)) = behaviour.call("bounces", &[])? {
+                println!("  {:<8} {}", world.names[entity.0], bounces);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn make_world() -> World {
+    let mut world = World::default();
+
+    let rock = world.spawn("rock");
+    world.positions[rock.0] = Some(Position { x: 5.0, y: 8.0 });
+    world.velocities[rock.0] = Some(Velocity { x: 0.0, y: 0.0 });
+
+    for (name, x, velocity_x) in &[("ball_a", 1.0, -6.0), ("ball_b", 9.0, 12.0)] {
+        let ball = world.spawn(name);
+        world.positions[ball.0] = Some(Position { x: *x, y: 2.0 });
+        world.velocities[ball.0] = Some(Velocity {
+            x: *velocity_x,
+            y: 0.0,
+        });
+        world.behaviours[ball.0] = Some(Behaviour("bouncer".into()));
+    }
+
+    world.spawn("marker");
+
+    world
+}
+
+fn behaviour_script(name: &str) -> &'static str {
+    match name {
+        "bouncer" => BOUNCER_SCRIPT,
+        _ => panic!("Unknown behaviour '{}'", name),
+    }
+}
+
+fn make_entity_map(world: &World, entity: Entity) -> EntityMap {
+    let mut entity_map = EntityMap::new(entity, &world.names[entity.0]);
+    if let Some(position) = &world.positions[entity.0] {
+        entity_map = entity_map.with_component("position", position);
+    }
+    if let Some(velocity) = &world.velocities[entity.0] {
+        entity_map = entity_map.with_component("velocity", velocity);
+    }
+    entity_map
+}
+
+// Writes changes made by scripts back into the world, returning the names of the changed fields
+fn apply_changes(
+    vm: &mut Vm,
+    world: &mut World,
+    entity_map: &EntityMap,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let entity = entity_map.entity;
+    let mut changed = Vec::new();
+
+    if let Some(position) = world.positions[entity.0].as_mut() {
+        for field in entity_map.apply_changes(vm, "position", position)? {
+            changed.push(format!("position.{}", field));
+        }
+    }
+    if let Some(velocity) = world.velocities[entity.0].as_mut() {
+        for field in entity_map.apply_changes(vm, "velocity", velocity)? {
+            changed.push(format!("velocity.{}", field));
+        }
+    }
+
+    Ok(changed)
+}
@@ -0,0 +1,51 @@
+// A minimal entity-component-system, standing in for the ECS that an application would use
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Entity(pub usize);
+
+#[derive(Clone, Debug, Default)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64,
+}
+
+// The name of the behaviour script that's attached to an entity
+#[derive(Clone, Debug)]
+pub struct Behaviour(pub String);
+
+#[derive(Default)]
+pub struct World {
+    pub names: Vec<String>,
+    pub positions: Vec<Option<Position>>,
+    pub velocities: Vec<Option<Velocity>>,
+    pub behaviours: Vec<Option<Behaviour>>,
+}
+
+impl World {
+    pub fn spawn(&mut self, name: &str) -> Entity {
+        self.names.push(name.to_string());
+        self.positions.push(None);
+        self.velocities.push(None);
+        self.behaviours.push(None);
+        Entity(self.names.len() - 1)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> {
+        (0..self.names.len()).map(Entity)
+    }
+
+    // Returns the entities that have both a position and a velocity
+    pub fn moving_entities(&self) -> Vec<Entity> {
+        self.entities()
+            .filter(|entity| {
+                self.positions[entity.0].is_some() && self.velocities[entity.0].is_some()
+            })
+            .collect()
+    }
+}
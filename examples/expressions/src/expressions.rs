@@ -0,0 +1,553 @@
+//! A small expression language that's compiled directly into Koto bytecode
+//!
+//! Expressions can contain numbers, arithmetic and comparison operators, parentheses, and
+//! identifiers. Identifiers are looked up in the Koto runtime's prelude, so any value that's
+//! shared with Koto scripts is also available to expressions, e.g. `number.sqrt(x * 2)`. Functions
+//! that are accessed from a value are called as instance functions, e.g. `x.max(y)`.
+
+use {
+    koto::{
+        bytecode::{Chunk, ChunkBuilder, ChunkBuilderError, Instruction},
+        parser::{Position, Span},
+    },
+    std::{error::Error, fmt, iter::Peekable, str::CharIndices},
+};
+
+/// An error that occurred while compiling an expression
+#[derive(Debug)]
+pub struct ExpressionError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl ExpressionError {
+    fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.span.start.column)
+    }
+}
+
+impl Error for ExpressionError {}
+
+impl From<ChunkBuilderError> for ExpressionError {
+    fn from(error: ChunkBuilderError) -> Self {
+        Self::new(format!("Internal error: {}", error), Span::default())
+    }
+}
+
+/// Compiles an expression into a [Chunk] that returns the expression's result when run
+pub fn compile(source: &str) -> Result<Chunk, ExpressionError> {
+    let expression = Parser::new(source)?.parse()?;
+
+    let mut compiler = Compiler {
+        builder: ChunkBuilder::new(),
+        next_register: 1,
+    };
+    // The expression's source is provided to the builder so that runtime errors can show the
+    // part of the expression that caused the error.
+    compiler.builder.set_source(source);
+    compiler.compile(&expression, 0)?;
+    compiler.builder.push(Instruction::Return { register: 0 })?;
+
+    Ok(compiler.builder.build()?)
+}
+
+#[derive(Debug)]
+enum Node {
+    Number(f64),
+    Id(String),
+    Negate(Box<Expression>),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Access {
+        value: Box<Expression>,
+        key: String,
+    },
+    Call {
+        function: Box<Expression>,
+        args: Vec<Expression>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug)]
+struct Expression {
+    node: Node,
+    span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Id(String),
+    Symbol(&'static str),
+}
+
+// A recursive descent parser for expressions
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    end: Span,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let end_column = source.chars().count() as u32 + 1;
+        let end = Position {
+            line: 1,
+            column: end_column,
+            ..Default::default()
+        };
+        Ok(Self {
+            tokens,
+            position: 0,
+            end: Span { start: end, end },
+        })
+    }
+
+    fn parse(mut self) -> Result<Expression, ExpressionError> {
+        let result = self.parse_comparison()?;
+        match self.tokens.get(self.position) {
+            Some((_, span)) => Err(ExpressionError::new("Unexpected token", *span)),
+            None => Ok(result),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, ExpressionError> {
+        let lhs = self.parse_sum()?;
+        let op = match self.peek_symbol() {
+            Some("<") => BinaryOp::Less,
+            Some("<=") => BinaryOp::LessOrEqual,
+            Some(">") => BinaryOp::Greater,
+            Some(">=") => BinaryOp::GreaterOrEqual,
+            Some("==") => BinaryOp::Equal,
+            Some("!=") => BinaryOp::NotEqual,
+            _ => return Ok(lhs),
+        };
+        self.position += 1;
+        let rhs = self.parse_sum()?;
+        Ok(binary(op, lhs, rhs))
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, ExpressionError> {
+        let mut result = self.parse_product()?;
+        loop {
+            let op = match self.peek_symbol() {
+                Some("+") => BinaryOp::Add,
+                Some("-") => BinaryOp::Subtract,
+                _ => return Ok(result),
+            };
+            self.position += 1;
+            let rhs = self.parse_product()?;
+            result = binary(op, result, rhs);
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expression, ExpressionError> {
+        let mut result = self.parse_unary()?;
+        loop {
+            let op = match self.peek_symbol() {
+                Some("*") => BinaryOp::Multiply,
+                Some("/") => BinaryOp::Divide,
+                Some("%") => BinaryOp::Modulo,
+                _ => return Ok(result),
+            };
+            self.position += 1;
+            let rhs = self.parse_unary()?;
+            result = binary(op, result, rhs);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.peek_symbol() == Some("-") {
+            let span = self.tokens[self.position].1;
+            self.position += 1;
+            let value = self.parse_unary()?;
+            Ok(Expression {
+                span: join_spans(span, value.span),
+                node: Node::Negate(Box::new(value)),
+            })
+        } else {
+            self.parse_postfix()
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expression, ExpressionError> {
+        let mut result = self.parse_primary()?;
+        loop {
+            match self.peek_symbol() {
+                Some(".") => {
+                    self.position += 1;
+                    match self.next() {
+                        Some((Token::Id(key), span)) => {
+                            result = Expression {
+                                span: join_spans(result.span, span),
+                                node: Node::Access {
+                                    value: Box::new(result),
+                                    key,
+                                },
+                            };
+                        }
+                        Some((_, span)) => {
+                            return Err(ExpressionError::new("Expected a key after '.'", span))
+                        }
+                        None => {
+                            return Err(ExpressionError::new("Expected a key after '.'", self.end))
+                        }
+                    }
+                }
+                Some("(") => {
+                    self.position += 1;
+                    let mut args = Vec::new();
+                    let end_span = loop {
+                        if self.peek_symbol() == Some(")") {
+                            break self.next().unwrap().1;
+                        }
+                        args.push(self.parse_comparison()?);
+                        match self.next() {
+                            Some((Token::Symbol(","), _)) => {}
+                            Some((Token::Symbol(")"), span)) => break span,
+                            Some((_, span)) => {
+                                return Err(ExpressionError::new("Expected ',' or ')'", span))
+                            }
+                            None => return Err(ExpressionError::new("Expected ')'", self.end)),
+                        }
+                    };
+                    result = Expression {
+                        span: join_spans(result.span, end_span),
+                        node: Node::Call {
+                            function: Box::new(result),
+                            args,
+                        },
+                    };
+                }
+                _ => return Ok(result),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ExpressionError> {
+        match self.next() {
+            Some((Token::Number(n), span)) => Ok(Expression {
+                node: Node::Number(n),
+                span,
+            }),
+            Some((Token::Id(id), span)) => Ok(Expression {
+                node: Node::Id(id),
+                span,
+            }),
+            Some((Token::Symbol("("), _)) => {
+                let result = self.parse_comparison()?;
+                match self.next() {
+                    Some((Token::Symbol(")"), _)) => Ok(result),
+                    Some((_, span)) => Err(ExpressionError::new("Expected ')'", span)),
+                    None => Err(ExpressionError::new("Expected ')'", self.end)),
+                }
+            }
+            Some((_, span)) => Err(ExpressionError::new("Expected a value", span)),
+            None => Err(ExpressionError::new("Expected a value", self.end)),
+        }
+    }
+
+    fn peek_symbol(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some((Token::Symbol(symbol), _)) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<(Token, Span)> {
+        let result = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        result
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Expression, rhs: Expression) -> Expression {
+    Expression {
+        span: join_spans(lhs.span, rhs.span),
+        node: Node::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+    }
+}
+
+fn join_spans(start: Span, end: Span) -> Span {
+    Span {
+        start: start.start,
+        end: end.end,
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Span)>, ExpressionError> {
+    const SYMBOLS: &[&str] = &[
+        "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "%", "(", ")", ",", ".",
+    ];
+
+    let mut result = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let mut column = 1;
+
+    while let Some(&(start, c)) = chars.peek() {
+        let start_column = column;
+
+        let token = if c.is_whitespace() {
+            chars.next();
+            column += 1;
+            continue;
+        } else if c.is_ascii_digit() {
+            let end = consume_while(&mut chars, &mut column, |c| c.is_ascii_digit() || c == '.')
+                .unwrap_or(source.len());
+            let text = &source[start..end];
+            let n = text.parse::<f64>().map_err(|_| {
+                ExpressionError::new(
+                    format!("Invalid number '{}'", text),
+                    span(start_column, column),
+                )
+            })?;
+            Token::Number(n)
+        } else if c.is_alphabetic() || c == '_' {
+            let end = consume_while(&mut chars, &mut column, |c| c.is_alphanumeric() || c == '_')
+                .unwrap_or(source.len());
+            Token::Id(source[start..end].to_string())
+        } else {
+            match SYMBOLS
+                .iter()
+                .find(|symbol| source[start..].starts_with(*symbol))
+            {
+                Some(symbol) => {
+                    for _ in 0..symbol.len() {
+                        chars.next();
+                    }
+                    column += symbol.len() as u32;
+                    Token::Symbol(symbol)
+                }
+                None => {
+                    return Err(ExpressionError::new(
+                        format!("Unexpected character '{}'", c),
+                        span(column, column + 1),
+                    ))
+                }
+            }
+        };
+
+        result.push((token, span(start_column, column)));
+    }
+
+    Ok(result)
+}
+
+// Consumes characters while the predicate returns true
+//
+// The end position in bytes is returned, or None if the end of the source was reached.
+fn consume_while(
+    chars: &mut Peekable<CharIndices>,
+    column: &mut u32,
+    predicate: impl Fn(char) -> bool,
+) -> Option<usize> {
+    while let Some(&(position, c)) = chars.peek() {
+        if predicate(c) {
+            chars.next();
+            *column += 1;
+        } else {
+            return Some(position);
+        }
+    }
+    None
+}
+
+fn span(start_column: u32, end_column: u32) -> Span {
+    Span {
+        start: Position {
+            line: 1,
+            column: start_column,
+            ..Default::default()
+        },
+        end: Position {
+            line: 1,
+            column: end_column,
+            ..Default::default()
+        },
+    }
+}
+
+// Compiles expressions into bytecode, with each expression placing its result in a register
+struct Compiler {
+    builder: ChunkBuilder,
+    // Registers below next_register are in use by expressions that are being compiled
+    next_register: u8,
+}
+
+impl Compiler {
+    fn compile(&mut self, expression: &Expression, register: u8) -> Result<(), ExpressionError> {
+        use Instruction::*;
+
+        match &expression.node {
+            Node::Number(n) => {
+                if n.fract() == 0.0 && *n >= 0.0 && *n <= 255.0 {
+                    self.push(
+                        SetNumber {
+                            register,
+                            value: *n as i64,
+                        },
+                        expression,
+                    )?;
+                } else {
+                    let constant = self.builder.add_f64(*n)?;
+                    self.push(LoadFloat { register, constant }, expression)?;
+                }
+            }
+            Node::Id(id) => {
+                let constant = self.builder.add_string(id)?;
+                self.push(LoadNonLocal { register, constant }, expression)?;
+            }
+            Node::Negate(value) => {
+                self.compile(value, register)?;
+                self.push(
+                    Negate {
+                        register,
+                        value: register,
+                    },
+                    expression,
+                )?;
+            }
+            Node::Binary { op, lhs, rhs } => {
+                self.compile(lhs, register)?;
+                let rhs_register = self.push_register(expression)?;
+                self.compile(rhs, rhs_register)?;
+
+                let (lhs, rhs) = (register, rhs_register);
+                let instruction = match op {
+                    BinaryOp::Add => Add { register, lhs, rhs },
+                    BinaryOp::Subtract => Subtract { register, lhs, rhs },
+                    BinaryOp::Multiply => Multiply { register, lhs, rhs },
+                    BinaryOp::Divide => Divide { register, lhs, rhs },
+                    BinaryOp::Modulo => Modulo { register, lhs, rhs },
+                    BinaryOp::Less => Less { register, lhs, rhs },
+                    BinaryOp::LessOrEqual => LessOrEqual { register, lhs, rhs },
+                    BinaryOp::Greater => Greater { register, lhs, rhs },
+                    BinaryOp::GreaterOrEqual => GreaterOrEqual { register, lhs, rhs },
+                    BinaryOp::Equal => Equal { register, lhs, rhs },
+                    BinaryOp::NotEqual => NotEqual { register, lhs, rhs },
+                };
+                self.push(instruction, expression)?;
+
+                self.next_register = rhs_register;
+            }
+            Node::Access { value, key } => {
+                self.compile(value, register)?;
+                let key = self.builder.add_string(key)?;
+                self.push(
+                    Access {
+                        register,
+                        value: register,
+                        key,
+                    },
+                    expression,
+                )?;
+            }
+            Node::Call { function, args } => {
+                let function_register = self.push_register(expression)?;
+
+                // Functions that are accessed from a value are called with the value as the
+                // instance, e.g. in `x.max(y)`, `x` is passed to `max` as `self`.
+                let instance = match &function.node {
+                    Node::Access { value, key } => {
+                        let instance_register = self.push_register(expression)?;
+                        self.compile(value, instance_register)?;
+                        let key = self.builder.add_string(key)?;
+                        self.push(
+                            Access {
+                                register: function_register,
+                                value: instance_register,
+                                key,
+                            },
+                            function,
+                        )?;
+                        Some(instance_register)
+                    }
+                    _ => {
+                        self.compile(function, function_register)?;
+                        None
+                    }
+                };
+
+                // Arguments are placed in the registers following the frame base
+                let frame_base = self.push_register(expression)?;
+                for arg in args.iter() {
+                    let arg_register = self.push_register(arg)?;
+                    self.compile(arg, arg_register)?;
+                }
+
+                let result = register;
+                let function = function_register;
+                let arg_count = args.len() as u8;
+                let call = match instance {
+                    Some(instance) => CallInstance {
+                        result,
+                        function,
+                        frame_base,
+                        arg_count,
+                        instance,
+                    },
+                    None => Call {
+                        result,
+                        function,
+                        frame_base,
+                        arg_count,
+                    },
+                };
+                self.push(call, expression)?;
+
+                self.next_register = function_register;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        instruction: Instruction,
+        expression: &Expression,
+    ) -> Result<(), ExpressionError> {
+        self.builder.set_span(expression.span);
+        Ok(self.builder.push(instruction)?)
+    }
+
+    fn push_register(&mut self, expression: &Expression) -> Result<u8, ExpressionError> {
+        let result = self.next_register;
+        self.next_register = self.next_register.checked_add(1).ok_or_else(|| {
+            ExpressionError::new("The expression is too complex", expression.span)
+        })?;
+        Ok(result)
+    }
+}
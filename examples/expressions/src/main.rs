@@ -0,0 +1,63 @@
+//! An example of using Koto as the runtime for a different language
+//!
+//! Expressions are compiled directly into Koto bytecode by the `expressions` module, and then run
+//! in a Koto runtime that's shared with a Koto script. Values defined in Rust, and functions
+//! defined in Koto, are both made available to expressions via the runtime's prelude.
+
+mod expressions;
+
+use {
+    koto::{runtime::Value, Koto},
+    std::{env, error::Error, sync::Arc},
+};
+
+// A Koto script that provides helper functions for expressions
+const HELPERS: &str = "
+export circle_area = |r| number.pi * r * r
+export clamp = |x, min, max| x.max(min).min(max)
+";
+
+const DEFAULT_EXPRESSIONS: &[&str] = &[
+    "width * height",
+    "circle_area(radius) + 1",
+    "clamp(width - height * 3, 0, 100)",
+    "number.sqrt(width * width + height * height)",
+    "(width + height) % 7 == 3",
+    "width.max(height * 3) - 1.5",
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut koto = Koto::default();
+
+    // Values added to the prelude are available both to Koto scripts and to expressions
+    let mut prelude = koto.prelude();
+    prelude.add_value("width", Value::Number(12.into()));
+    prelude.add_value("height", Value::Number(5.into()));
+    prelude.add_value("radius", Value::Number(1.5.into()));
+
+    // The helpers script is run first so that its exported functions can be added to the prelude
+    koto.compile(HELPERS)?;
+    koto.run()?;
+    for (key, value) in koto.exports().data().iter() {
+        prelude.insert(key.clone(), value.clone());
+    }
+
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let expressions = if args.is_empty() {
+        DEFAULT_EXPRESSIONS.iter().map(|s| s.to_string()).collect()
+    } else {
+        args
+    };
+
+    for expression in expressions.iter() {
+        match expressions::compile(expression) {
+            Ok(chunk) => match koto.run_chunk(Arc::new(chunk)) {
+                Ok(result) => println!("{} = {}", expression, result),
+                Err(error) => println!("{}\nRuntime error: {}", expression, error),
+            },
+            Err(error) => println!("{}\nError: {}", expression, error),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use {
+    crate::{
+        widgets::{Input, Response, Responses, Widget, WidgetId},
+        Backend,
+    },
+    egui::{pos2, vec2, Event, Id, Pos2, Rect},
+    std::{collections::HashMap, ops::RangeInclusive},
+};
+
+// A backend that renders the UI with egui
+//
+// egui is run without a window, with input provided by the application being converted into
+// pointer events. An application with a windowing integration would pass the user's input to egui
+// instead, with the rest of the backend remaining the same.
+#[derive(Default)]
+pub struct EguiBackend {
+    ctx: egui::Context,
+    // The positions of widgets from the previous frame, used to place simulated pointer events
+    targets: HashMap<WidgetId, Target>,
+}
+
+#[derive(Clone, Debug)]
+enum Target {
+    Point(Pos2),
+    Slider {
+        rail: RangeInclusive<f32>,
+        y: f32,
+        min: f64,
+        max: f64,
+    },
+}
+
+impl Backend for EguiBackend {
+    fn render(&mut self, widgets: &[Widget], input: &[Input]) -> Responses {
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(1024.0, 768.0))),
+            ..Default::default()
+        };
+        for input in input {
+            match self.pointer_position(input) {
+                Some(position) => raw_input.events.extend(click_events(position)),
+                None => println!("  > Unable to find a position for {:?}", input),
+            }
+        }
+
+        let mut frame = Frame {
+            responses: Responses::new(),
+            targets: HashMap::new(),
+        };
+
+        let output = self.ctx.run(raw_input, |ctx| {
+            let (windows, root_widgets): (Vec<_>, Vec<_>) = widgets
+                .iter()
+                .partition(|widget| matches!(widget, Widget::Window { .. }));
+
+            egui::CentralPanel::default().show(ctx, |ui| {
+                for widget in root_widgets {
+                    frame.add_widget(ui, widget);
+                }
+            });
+
+            for (index, window) in windows.into_iter().enumerate() {
+                if let Widget::Window {
+                    id,
+                    title,
+                    children,
+                } = window
+                {
+                    egui::Window::new(title)
+                        .id(Id::new(id))
+                        .default_pos(pos2(20.0 + index as f32 * 320.0, 20.0))
+                        .show(ctx, |ui| {
+                            for child in children {
+                                frame.add_widget(ui, child);
+                            }
+                        });
+                }
+            }
+        });
+
+        let shape_count = output.shapes.len();
+        println!(
+            "  egui: {} widgets, {} shapes",
+            frame.targets.len(),
+            shape_count
+        );
+        for (id, response) in frame.responses.iter() {
+            println!("  > '{}': {:?}", id, response);
+        }
+
+        self.targets = frame.targets;
+        frame.responses
+    }
+}
+
+impl EguiBackend {
+    fn pointer_position(&self, input: &Input) -> Option<Pos2> {
+        match input {
+            Input::Click(id) => match self.targets.get(id)? {
+                Target::Point(position) => Some(*position),
+                Target::Slider { .. } => None,
+            },
+            Input::SetValue(id, value) => match self.targets.get(id)? {
+                Target::Slider { rail, y, min, max } => {
+                    let t = ((value - min) / (max - min)).clamp(0.0, 1.0) as f32;
+                    Some(pos2(egui::lerp(rail.clone(), t), *y))
+                }
+                Target::Point(_) => None,
+            },
+        }
+    }
+}
+
+// Pressing and releasing the primary button within a single frame is registered as a click
+fn click_events(position: Pos2) -> Vec<Event> {
+    let button = |pressed| Event::PointerButton {
+        pos: position,
+        button: egui::PointerButton::Primary,
+        pressed,
+        modifiers: Default::default(),
+    };
+
+    vec![Event::PointerMoved(position), button(true), button(false)]
+}
+
+// The state that's gathered while rendering a frame
+struct Frame {
+    responses: Responses,
+    targets: HashMap<WidgetId, Target>,
+}
+
+impl Frame {
+    fn add_widget(&mut self, ui: &mut egui::Ui, widget: &Widget) {
+        match widget {
+            Widget::Label { text } => {
+                ui.label(text);
+            }
+            Widget::Button { id, label } => {
+                let response = ui.button(label);
+                if response.clicked() {
+                    self.responses.insert(id.clone(), Response::Clicked);
+                }
+                self.add_target(id, Target::Point(response.rect.center()));
+            }
+            Widget::Slider {
+                id,
+                label,
+                value,
+                min,
+                max,
+                integer,
+            } => {
+                let mut value = *value;
+                let mut slider = egui::Slider::new(&mut value, *min..=*max).text(label);
+                if *integer {
+                    slider = slider.integer();
+                }
+                let response = ui.add(slider);
+                if response.changed() {
+                    self.responses.insert(id.clone(), Response::Value(value));
+                }
+
+                // The response covers the slider along with its value and label,
+                // the slider's rail is placed at the start of the response's rect.
+                let rect = response.rect;
+                let handle_radius = rect.height() / 2.5;
+                let rail_end = rect.left() + ui.spacing().slider_width - handle_radius;
+                self.add_target(
+                    id,
+                    Target::Slider {
+                        rail: (rect.left() + handle_radius)..=rail_end,
+                        y: rect.center().y,
+                        min: *min,
+                        max: *max,
+                    },
+                );
+            }
+            Widget::Checkbox { id, label, checked } => {
+                let mut checked = *checked;
+                let response = ui.checkbox(&mut checked, label);
+                if response.changed() {
+                    self.responses
+                        .insert(id.clone(), Response::Checked(checked));
+                }
+                self.add_target(id, Target::Point(response.rect.center()));
+            }
+            Widget::Horizontal { children } => {
+                ui.horizontal(|ui| {
+                    for child in children {
+                        self.add_widget(ui, child);
+                    }
+                });
+            }
+            Widget::Window { title, .. } => {
+                ui.label(format!("Unable to show nested window '{}'", title));
+            }
+        }
+    }
+
+    fn add_target(&mut self, id: &str, target: Target) {
+        self.targets.insert(id.to_string(), target);
+    }
+}
@@ -0,0 +1,234 @@
+use {
+    crate::widgets::{Response, Responses, UiFrame, Widget},
+    koto::runtime::{
+        expect_arg, runtime_error, CallArgs, ExternalData, ExternalValue, MetaKey, MetaMap,
+        RuntimeError, RuntimeResult, RwLock, Value, ValueNumber, Vm,
+    },
+    lazy_static::lazy_static,
+    std::{fmt, sync::Arc},
+};
+
+/// The `ui` value that's passed to a script's draw function
+///
+/// The value records the widgets that are declared by the script, and provides the responses
+/// from the previously rendered frame so that the script can react to user input.
+pub struct ScriptUi {
+    value: ExternalValue,
+}
+
+impl ScriptUi {
+    pub fn new() -> Self {
+        Self {
+            value: ExternalValue::with_shared_meta_map(
+                KotoUi(UiFrame::new(Responses::new())),
+                UI_BINDINGS.clone(),
+            ),
+        }
+    }
+
+    /// Calls the script's draw function, returning the widgets that were declared
+    pub fn draw(
+        &self,
+        vm: &mut Vm,
+        draw_fn: &Value,
+        responses: Responses,
+    ) -> Result<Vec<Widget>, RuntimeError> {
+        with_frame(&self.value, |frame| *frame = UiFrame::new(responses))?;
+
+        vm.run_function(
+            draw_fn.clone(),
+            CallArgs::Single(Value::ExternalValue(self.value.clone())),
+        )?;
+
+        with_frame(&self.value, |frame| {
+            std::mem::replace(frame, UiFrame::new(Responses::new())).finish()
+        })
+    }
+}
+
+lazy_static! {
+    static ref UI_BINDINGS: Arc<RwLock<MetaMap>> = {
+        use Value::{Bool, Empty, Number};
+
+        let mut bindings = MetaMap::with_type_name("Ui");
+
+        add_ui_fn(&mut bindings, "label", |_, ui, args| {
+            let text = expect_arg::<String>("ui.label", args, 0)?;
+            with_frame(ui, |frame| frame.add(Widget::Label { text }))?;
+            Ok(Empty)
+        });
+
+        add_ui_fn(&mut bindings, "button", |_, ui, args| {
+            let label = expect_arg::<String>("ui.button", args, 0)?;
+            with_frame(ui, |frame| {
+                let id = frame.make_id(&label);
+                let clicked = frame.response(&id) == Some(Response::Clicked);
+                frame.add(Widget::Button { id, label });
+                Bool(clicked)
+            })
+        });
+
+        add_ui_fn(&mut bindings, "slider", |_, ui, args| {
+            let label = expect_arg::<String>("ui.slider", args, 0)?;
+            let value = expect_arg::<ValueNumber>("ui.slider", args, 1)?;
+            let min = expect_arg::<Option<ValueNumber>>("ui.slider", args, 2)?;
+            let max = expect_arg::<Option<ValueNumber>>("ui.slider", args, 3)?;
+
+            let min = min.unwrap_or(ValueNumber::I64(0));
+            let max = max.unwrap_or(ValueNumber::I64(1));
+            if min > max {
+                return runtime_error!("ui.slider - The min value is larger than the max value");
+            }
+            // The slider produces integers when it's given integers
+            let integer = !(value.is_f64() || min.is_f64() || max.is_f64());
+
+            with_frame(ui, |frame| {
+                let id = frame.make_id(&label);
+                let value = match frame.response(&id) {
+                    Some(Response::Value(new_value)) => new_value,
+                    _ => value.into(),
+                };
+                let (min, max) = (f64::from(min), f64::from(max));
+                let value = if integer {
+                    value.round().clamp(min, max)
+                } else {
+                    value.clamp(min, max)
+                };
+
+                frame.add(Widget::Slider {
+                    id,
+                    label,
+                    value,
+                    min,
+                    max,
+                    integer,
+                });
+
+                if integer {
+                    Number((value as i64).into())
+                } else {
+                    Number(value.into())
+                }
+            })
+        });
+
+        add_ui_fn(&mut bindings, "checkbox", |_, ui, args| {
+            let label = expect_arg::<String>("ui.checkbox", args, 0)?;
+            let checked = expect_arg::<bool>("ui.checkbox", args, 1)?;
+            with_frame(ui, |frame| {
+                let id = frame.make_id(&label);
+                let checked = match frame.response(&id) {
+                    Some(Response::Checked(new_checked)) => new_checked,
+                    _ => checked,
+                };
+                frame.add(Widget::Checkbox { id, label, checked });
+                Bool(checked)
+            })
+        });
+
+        add_ui_fn(&mut bindings, "window", |vm, ui, args| {
+            let title = expect_arg::<String>("ui.window", args, 0)?;
+            let contents = expect_arg::<Value>("ui.window", args, 1)?;
+
+            let id = with_frame(ui, |frame| {
+                if frame.is_at_root() {
+                    Ok(frame.make_id(&title))
+                } else {
+                    runtime_error!("ui.window - Windows can't be nested in other widgets")
+                }
+            })??;
+
+            let (result, children) = run_in_container(vm, ui, id.clone(), contents)?;
+            with_frame(ui, |frame| {
+                frame.add(Widget::Window {
+                    id,
+                    title,
+                    children,
+                })
+            })?;
+            Ok(result)
+        });
+
+        add_ui_fn(&mut bindings, "horizontal", |vm, ui, args| {
+            let contents = expect_arg::<Value>("ui.horizontal", args, 0)?;
+
+            // Widgets in the layout are identified as if they were added to the parent container
+            let id = with_frame(ui, |frame| frame.parent_id().to_string())?;
+
+            let (result, children) = run_in_container(vm, ui, id, contents)?;
+            with_frame(ui, |frame| frame.add(Widget::Horizontal { children }))?;
+            Ok(result)
+        });
+
+        Arc::new(RwLock::new(bindings))
+    };
+}
+
+// Adds a function to the Ui bindings
+//
+// The function is called with the VM so that it can call back into the script,
+// along with the `ui` instance and the function's remaining arguments.
+fn add_ui_fn(
+    bindings: &mut MetaMap,
+    name: &'static str,
+    f: impl Fn(&mut Vm, &ExternalValue, &[Value]) -> RuntimeResult + Send + Sync + 'static,
+) {
+    bindings.add_instance_fn(MetaKey::Named(name.into()), move |vm, args| {
+        // The args are copied out of the VM's registers, allowing the VM to be used by f
+        let args = vm.get_args(args).to_vec();
+        match args.as_slice() {
+            [Value::ExternalValue(ui), args @ ..] => f(vm, ui, args),
+            _ => runtime_error!("ui.{} - Expected a Ui instance", name),
+        }
+    });
+}
+
+// Provides access to the frame that's being recorded by the `ui` instance
+//
+// The frame is locked while f is running, so f mustn't call back into the script.
+fn with_frame<T>(ui: &ExternalValue, f: impl FnOnce(&mut UiFrame) -> T) -> Result<T, RuntimeError> {
+    match ui.data_mut().downcast_mut::<KotoUi>() {
+        Some(KotoUi(frame)) => Ok(f(frame)),
+        None => runtime_error!("ui - Unexpected external data type"),
+    }
+}
+
+// Calls a script function that adds widgets to a new container
+//
+// The function's result is returned along with the widgets that were added to the container.
+fn run_in_container(
+    vm: &mut Vm,
+    ui: &ExternalValue,
+    id: String,
+    contents: Value,
+) -> Result<(Value, Vec<Widget>), RuntimeError> {
+    if !contents.is_callable() {
+        return runtime_error!(
+            "ui - Expected a Function for the container's contents, found {}",
+            contents.type_as_string()
+        );
+    }
+
+    with_frame(ui, |frame| frame.begin_container(id))?;
+    // The frame isn't locked while the function is running, allowing it to use the `ui` instance
+    let result = vm.run_function(contents, CallArgs::Single(Value::ExternalValue(ui.clone())));
+    // The container is ended even if the function failed so that the frame remains consistent
+    let children = with_frame(ui, |frame| frame.end_container())?;
+
+    Ok((result?, children))
+}
+
+#[derive(Debug)]
+struct KotoUi(UiFrame);
+
+impl ExternalData for KotoUi {
+    fn value_type(&self) -> String {
+        "Ui".to_string()
+    }
+}
+
+impl fmt::Display for KotoUi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ui")
+    }
+}
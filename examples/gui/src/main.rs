@@ -0,0 +1,65 @@
+#[cfg(feature = "egui")]
+mod egui_backend;
+mod koto_bindings;
+#[cfg(not(feature = "egui"))]
+mod text_backend;
+mod widgets;
+
+use {
+    koto::{bytecode::Loader, runtime::Vm},
+    koto_bindings::ScriptUi,
+    std::error::Error,
+    widgets::{Input, Responses, Widget},
+};
+
+const COUNTER_SCRIPT: &str = include_str!("../scripts/counter.koto");
+
+const FRAME_COUNT: usize = 11;
+
+// Renders the widgets that were declared by a script
+pub trait Backend {
+    // Renders a frame, returning the responses to the input that was received while rendering
+    //
+    // The responses are made available to the script when it draws the next frame.
+    fn render(&mut self, widgets: &[Widget], input: &[Input]) -> Responses;
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "egui")]
+    let mut backend = egui_backend::EguiBackend::default();
+    #[cfg(not(feature = "egui"))]
+    let mut backend = text_backend::TextBackend;
+
+    let mut vm = Vm::default();
+    let chunk = Loader::default().compile_script(COUNTER_SCRIPT, &None)?;
+    vm.run(chunk)?;
+    let draw = vm
+        .get_exported_function("draw")
+        .ok_or("Missing draw function")?;
+
+    let ui = ScriptUi::new();
+    let mut responses = Responses::new();
+
+    for frame in 0..FRAME_COUNT {
+        println!("Frame {}", frame);
+        let widgets = ui.draw(&mut vm, &draw, responses)?;
+        responses = backend.render(&widgets, &simulated_input(frame));
+    }
+
+    Ok(())
+}
+
+// Input that stands in for a user interacting with the UI
+fn simulated_input(frame: usize) -> Vec<Input> {
+    let id = |id: &str| id.to_string();
+
+    match frame {
+        2 => vec![Input::Click(id("Counter/Add"))],
+        3 => vec![Input::SetValue(id("Counter/Step"), 5.0)],
+        4 => vec![Input::Click(id("Counter/Add"))],
+        5 => vec![Input::Click(id("Counter/Show details"))],
+        7 => vec![Input::Click(id("Details/Double"))],
+        8 => vec![Input::Click(id("Counter/Reset"))],
+        _ => vec![],
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    widgets::{Input, Response, Responses, Widget},
+    Backend,
+};
+
+// A backend that prints the UI as text, with input provided by the application
+pub struct TextBackend;
+
+impl Backend for TextBackend {
+    fn render(&mut self, widgets: &[Widget], input: &[Input]) -> Responses {
+        for widget in widgets {
+            print_widget(widget, 1);
+        }
+
+        let mut responses = Responses::new();
+
+        for input in input {
+            let (id, response) = match input {
+                Input::Click(id) => match Widget::find(widgets, id) {
+                    Some(Widget::Button { .. }) => (id, Response::Clicked),
+                    Some(Widget::Checkbox { checked, .. }) => (id, Response::Checked(!checked)),
+                    _ => {
+                        println!("  > '{}' can't be clicked", id);
+                        continue;
+                    }
+                },
+                Input::SetValue(id, value) => match Widget::find(widgets, id) {
+                    Some(Widget::Slider { .. }) => (id, Response::Value(*value)),
+                    _ => {
+                        println!("  > '{}' doesn't have a value", id);
+                        continue;
+                    }
+                },
+            };
+
+            match response {
+                Response::Value(value) => println!("  > Setting '{}' to {}", id, value),
+                _ => println!("  > Clicking '{}'", id),
+            }
+            responses.insert(id.clone(), response);
+        }
+
+        responses
+    }
+}
+
+fn print_widget(widget: &Widget, indent: usize) {
+    let padding = "  ".repeat(indent);
+
+    match widget {
+        Widget::Window {
+            title, children, ..
+        } => {
+            println!("{}Window '{}'", padding, title);
+            for child in children {
+                print_widget(child, indent + 1);
+            }
+        }
+        _ => println!("{}{}", padding, describe(widget)),
+    }
+}
+
+// Describes a widget on a single line
+fn describe(widget: &Widget) -> String {
+    match widget {
+        Widget::Label { text } => text.clone(),
+        Widget::Button { label, .. } => format!("[{}]", label),
+        Widget::Slider {
+            label,
+            value,
+            min,
+            max,
+            integer,
+            ..
+        } => {
+            if *integer {
+                format!("{}: {} ({}..={})", label, value, min, max)
+            } else {
+                format!("{}: {:.2} ({:.2}..={:.2})", label, value, min, max)
+            }
+        }
+        Widget::Checkbox { label, checked, .. } => {
+            format!("[{}] {}", if *checked { "x" } else { " " }, label)
+        }
+        Widget::Horizontal { children } => {
+            children.iter().map(describe).collect::<Vec<_>>().join("  ")
+        }
+        Widget::Window { title, .. } => format!("Window '{}'", title),
+    }
+}
@@ -0,0 +1,178 @@
+// The widgets that are recorded while a script draws its UI, independent of the rendering backend
+
+use std::collections::HashMap;
+
+// Widgets are identified by their label, prefixed with the ids of their containers,
+// e.g. a button labeled 'Add' in a window titled 'Counter' has the id 'Counter/Add'.
+pub type WidgetId = String;
+
+#[derive(Clone, Debug)]
+pub enum Widget {
+    Label {
+        text: String,
+    },
+    Button {
+        id: WidgetId,
+        label: String,
+    },
+    Slider {
+        id: WidgetId,
+        label: String,
+        value: f64,
+        min: f64,
+        max: f64,
+        // Integer sliders only produce whole numbers
+        integer: bool,
+    },
+    Checkbox {
+        id: WidgetId,
+        label: String,
+        checked: bool,
+    },
+    Window {
+        id: WidgetId,
+        title: String,
+        children: Vec<Widget>,
+    },
+    // Widgets in a horizontal layout don't include the layout in their ids
+    Horizontal {
+        children: Vec<Widget>,
+    },
+}
+
+impl Widget {
+    pub fn id(&self) -> Option<&str> {
+        use Widget::*;
+
+        match self {
+            Label { .. } | Horizontal { .. } => None,
+            Button { id, .. } | Slider { id, .. } | Checkbox { id, .. } | Window { id, .. } => {
+                Some(id)
+            }
+        }
+    }
+
+    pub fn children(&self) -> &[Widget] {
+        match self {
+            Widget::Window { children, .. } | Widget::Horizontal { children, .. } => children,
+            _ => &[],
+        }
+    }
+
+    // Finds a widget with a matching id, searching through containers
+    pub fn find<'a>(widgets: &'a [Widget], id: &str) -> Option<&'a Widget> {
+        widgets.iter().find_map(|widget| {
+            if widget.id() == Some(id) {
+                Some(widget)
+            } else {
+                Widget::find(widget.children(), id)
+            }
+        })
+    }
+}
+
+// The result of the user interacting with a widget
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Response {
+    Clicked,
+    Value(f64),
+    Checked(bool),
+}
+
+pub type Responses = HashMap<WidgetId, Response>;
+
+// Input that's provided to a backend, standing in for a user interacting with the UI
+#[derive(Clone, Debug)]
+pub enum Input {
+    Click(WidgetId),
+    SetValue(WidgetId, f64),
+}
+
+// A container that's in the process of being recorded
+#[derive(Debug)]
+struct Container {
+    id: WidgetId,
+    children: Vec<Widget>,
+}
+
+// Records the widgets that are drawn by a script during a single frame
+//
+// Rendering happens after the script has finished drawing the frame, so the responses that are
+// available to the script are the ones that were produced when the previous frame was rendered.
+#[derive(Debug)]
+pub struct UiFrame {
+    containers: Vec<Container>,
+    responses: Responses,
+    // Used to give widgets with matching labels unique ids
+    id_counts: HashMap<WidgetId, usize>,
+}
+
+impl UiFrame {
+    pub fn new(responses: Responses) -> Self {
+        Self {
+            containers: vec![Container {
+                id: WidgetId::new(),
+                children: Vec::new(),
+            }],
+            responses,
+            id_counts: HashMap::new(),
+        }
+    }
+
+    pub fn make_id(&mut self, label: &str) -> WidgetId {
+        let parent = self.parent_id();
+        let id = if parent.is_empty() {
+            label.to_string()
+        } else {
+            format!("{}/{}", parent, label)
+        };
+
+        let count = self.id_counts.entry(id.clone()).or_default();
+        *count += 1;
+        if *count == 1 {
+            id
+        } else {
+            format!("{}#{}", id, count)
+        }
+    }
+
+    // The id of the container that widgets are currently being added to
+    pub fn parent_id(&self) -> &str {
+        &self.containers.last().unwrap().id
+    }
+
+    pub fn response(&self, id: &str) -> Option<Response> {
+        self.responses.get(id).copied()
+    }
+
+    pub fn add(&mut self, widget: Widget) {
+        self.containers.last_mut().unwrap().children.push(widget);
+    }
+
+    pub fn is_at_root(&self) -> bool {
+        self.containers.len() == 1
+    }
+
+    // Starts a container, with widgets being added to it until end_container is called
+    pub fn begin_container(&mut self, id: WidgetId) {
+        self.containers.push(Container {
+            id,
+            children: Vec::new(),
+        });
+    }
+
+    // Ends the current container, returning the widgets that were added to it
+    pub fn end_container(&mut self) -> Vec<Widget> {
+        assert!(
+            !self.is_at_root(),
+            "end_container called without a matching begin"
+        );
+        self.containers.pop().unwrap().children
+    }
+
+    // Returns the widgets that were recorded for the frame
+    pub fn finish(mut self) -> Vec<Widget> {
+        self.containers.truncate(1);
+        self.containers.pop().unwrap().children
+    }
+}
@@ -1,13 +1,15 @@
 use {
-    crate::InstructionReader,
-    koto_parser::{ConstantPool, Span},
-    std::{path::PathBuf, sync::Arc},
+    crate::{ChunkStats, Disassembler, Instruction, InstructionReader, JumpTableKey},
+    koto_parser::{Constant, ConstantIndex, ConstantPool, Span},
+    std::{collections::HashSet, ops::Range, path::PathBuf, sync::Arc},
 };
 
 /// Debug information for a Koto program
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct DebugInfo {
-    source_map: Vec<(usize, Span)>,
+    pub(crate) source_map: Vec<(usize, Span)>,
+    pub(crate) coverage_points: Vec<Span>,
+    pub(crate) functions: Vec<(Range<usize>, Option<String>)>,
     /// The source of the program that the debug info was derived from
     pub source: String,
 }
@@ -30,19 +32,60 @@ impl DebugInfo {
     }
 
     /// Returns a source span for a given instruction pointer
+    ///
+    /// The position can refer to any byte of an instruction, not only the instruction's start.
     pub fn get_source_span(&self, ip: usize) -> Option<Span> {
         // Find the last entry with an ip less than or equal to the input
-        // an upper_bound would nice here, but this isn't currently a performance sensitive function
-        // so a scan through the entries will do.
-        let mut result = None;
-        for entry in self.source_map.iter() {
-            if entry.0 <= ip {
-                result = Some(entry.1);
-            } else {
-                break;
-            }
-        }
-        result
+        let entry_count = self
+            .source_map
+            .partition_point(|(entry_ip, _)| *entry_ip <= ip);
+        entry_count
+            .checked_sub(1)
+            .map(|last_entry| self.source_map[last_entry].1)
+    }
+
+    /// Returns the entries of the source map
+    ///
+    /// Each entry contains the position of the first instruction that the span applies to, with
+    /// the span applying to each following instruction up until the next entry.
+    /// Entries are ordered by instruction position.
+    pub fn source_map(&self) -> &[(usize, Span)] {
+        &self.source_map
+    }
+
+    /// Adds a coverage point with the given span, returning the point's index
+    pub fn push_coverage_point(&mut self, span: Span) -> usize {
+        self.coverage_points.push(span);
+        self.coverage_points.len() - 1
+    }
+
+    /// Returns the spans of the chunk's coverage points
+    ///
+    /// Coverage points are only present when the chunk was compiled with
+    /// [CompilerSettings::coverage](crate::CompilerSettings::coverage), with each point being
+    /// recorded by a [Coverage](crate::Instruction::Coverage) instruction.
+    pub fn coverage_points(&self) -> &[Span] {
+        &self.coverage_points
+    }
+
+    /// Adds the position of a function's body, along with the function's name if it's known
+    pub fn push_function(&mut self, body: Range<usize>, name: Option<String>) {
+        self.functions.push((body, name));
+    }
+
+    /// Returns the name of the function that contains the given instruction position
+    ///
+    /// Functions are named after the ID that they're assigned to, e.g. `f = || ...`, or the key of
+    /// their map entry, e.g. `{foo: || ...}`. `None` is returned for anonymous functions,
+    /// and for instructions in the chunk's top-level code.
+    pub fn function_name(&self, ip: usize) -> Option<&str> {
+        // Nested functions are contained in the bodies of their parents,
+        // so the innermost function is the one with the latest start
+        self.functions
+            .iter()
+            .filter(|(body, _)| body.contains(&ip))
+            .max_by_key(|(body, _)| body.start)
+            .and_then(|(_, name)| name.as_deref())
     }
 }
 
@@ -86,6 +129,80 @@ impl Chunk {
         }
     }
 
+    // Replaces the strings that are only used to display expressions in `debug` and failed
+    // assertions with empty strings, see CompilerSettings::strip_debug_info
+    //
+    // Constant indices are unchanged, so the chunk's bytecode doesn't need to be updated.
+    pub(crate) fn remove_expression_strings(&mut self) {
+        let mut expression_strings = HashSet::new();
+        let mut other_constants = HashSet::new();
+
+        let bytecode = Chunk::new(
+            self.bytes.clone(),
+            ConstantPool::default(),
+            None,
+            DebugInfo::default(),
+        );
+        for disassembled in Disassembler::new(Arc::new(bytecode)) {
+            match disassembled.instruction {
+                Instruction::Debug { constant, .. }
+                | Instruction::Assert { constant, .. }
+                | Instruction::AssertComparison { constant, .. } => {
+                    expression_strings.insert(usize::from(constant));
+                }
+                Instruction::JumpTable {
+                    key_type: JumpTableKey::Str,
+                    entry_count,
+                    table_ip,
+                    ..
+                } => {
+                    // String keys are stored as constant indices at the start of each entry
+                    for i in 0..entry_count {
+                        let entry = table_ip + i * JumpTableKey::Str.entry_size();
+                        other_constants.insert(usize::from(ConstantIndex::from([
+                            self.bytes[entry],
+                            self.bytes[entry + 1],
+                            self.bytes[entry + 2],
+                            self.bytes[entry + 3],
+                        ])));
+                    }
+                }
+                _ => other_constants.extend(disassembled.constants().map(usize::from)),
+            }
+        }
+
+        let constants = self.constants.iter().enumerate().map(|(i, constant)| {
+            if expression_strings.contains(&i) && !other_constants.contains(&i) {
+                Constant::Str("")
+            } else {
+                constant
+            }
+        });
+
+        // The pool's size is unchanged, so rebuilding it can't fail
+        if let Ok(constants) = ConstantPool::from_constants(constants) {
+            self.constants = constants;
+        }
+    }
+
+    /// Returns the span of the source that produced the instruction at the given position
+    ///
+    /// None is returned if the position is outside of the chunk's bytecode, or if the chunk
+    /// doesn't have debug info. The span can be used with [Span::byte_range] to extract the
+    /// instruction's source from the chunk's [DebugInfo::source].
+    pub fn source_span(&self, ip: usize) -> Option<Span> {
+        if ip < self.bytes.len() {
+            self.debug_info.get_source_span(ip)
+        } else {
+            None
+        }
+    }
+
+    /// Returns statistics describing the chunk's size and the resources used by its functions
+    pub fn stats(chunk: Arc<Chunk>) -> ChunkStats {
+        ChunkStats::new(chunk)
+    }
+
     /// Returns a [String] displaying the instructions contained in the compiled [Chunk]
     pub fn bytes_as_string(chunk: Arc<Chunk>) -> String {
         let mut iter = chunk.bytes.iter();
@@ -0,0 +1,813 @@
+use {
+    crate::{Chunk, DebugInfo, FunctionFlags, Instruction, InstructionReader, Op},
+    koto_parser::{Constant, ConstantIndex, ConstantPoolBuilder, Span},
+    std::{convert::TryFrom, error, fmt, path::PathBuf, sync::Arc},
+};
+
+/// The error type used by the [ChunkBuilder]
+#[derive(Clone, Debug)]
+pub struct ChunkBuilderError {
+    /// The error's message
+    pub message: String,
+    /// The position in the bytecode of the instruction that caused the error
+    pub ip: usize,
+}
+
+impl fmt::Display for ChunkBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at ip {})", self.message, self.ip)
+    }
+}
+
+impl error::Error for ChunkBuilderError {}
+
+/// A position in the bytecode that's being prepared by a [ChunkBuilder]
+///
+/// Labels are created with [ChunkBuilder::new_label], and then placed at the current position in
+/// the bytecode with [ChunkBuilder::place_label]. Instructions that jump can refer to a label with
+/// [ChunkBuilder::push_with_target], before or after the label has been placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// A builder of [Chunk]s, allowing front-ends to target the Koto VM without producing Koto source
+///
+/// [Instruction]s are added to the chunk with [ChunkBuilder::push], and are encoded into bytecode
+/// using the most compact form available for the instruction's values.
+///
+/// The chunk is validated when [ChunkBuilder::build] is called:
+///   - Every label that's referred to by an instruction must have been placed.
+///   - Jump offsets and function sizes must land at the start of an instruction, or at the end of
+///     the chunk.
+///   - Constant indices must refer to constants of the expected type.
+///
+/// Registers aren't validated, the runtime makes space for registers as they're used.
+///
+/// # Example
+///
+/// ```
+/// use {
+///     koto_bytecode::{ChunkBuilder, Instruction},
+///     std::sync::Arc,
+/// };
+///
+/// // Produces the equivalent of: `if x then 'yes' else 'no'`
+/// let mut builder = ChunkBuilder::new();
+/// let x = builder.add_string("x").unwrap();
+/// let yes = builder.add_string("yes").unwrap();
+/// let no = builder.add_string("no").unwrap();
+/// let else_label = builder.new_label();
+/// let end_label = builder.new_label();
+///
+/// builder.push(Instruction::LoadNonLocal { register: 0, constant: x }).unwrap();
+/// builder
+///     .push_with_target(
+///         Instruction::JumpIf { register: 0, offset: 0, jump_condition: false },
+///         else_label,
+///     )
+///     .unwrap();
+/// builder.push(Instruction::LoadString { register: 1, constant: yes }).unwrap();
+/// builder
+///     .push_with_target(Instruction::Jump { offset: 0 }, end_label)
+///     .unwrap();
+/// builder.place_label(else_label).unwrap();
+/// builder.push(Instruction::LoadString { register: 1, constant: no }).unwrap();
+/// builder.place_label(end_label).unwrap();
+/// builder.push(Instruction::Return { register: 1 }).unwrap();
+///
+/// let chunk = Arc::new(builder.build().unwrap());
+/// ```
+#[derive(Default)]
+pub struct ChunkBuilder {
+    bytes: Vec<u8>,
+    constants: ConstantPoolBuilder,
+    debug_info: DebugInfo,
+    span: Span,
+    source_path: Option<PathBuf>,
+    // The positions of the builder's labels, or None for labels that haven't been placed yet
+    labels: Vec<Option<usize>>,
+    // Instructions with offsets that will be set once their target labels have been placed
+    pending_targets: Vec<PendingTarget>,
+    // The start position of each instruction, used to validate jump targets
+    instruction_ips: Vec<usize>,
+    // The (instruction ip, target ip) pairs of each jump, validated in build()
+    jumps: Vec<(usize, usize)>,
+}
+
+impl ChunkBuilder {
+    /// Initializes an empty ChunkBuilder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a string constant to the chunk, returning the constant's index
+    pub fn add_string(&mut self, s: &str) -> Result<ConstantIndex, ChunkBuilderError> {
+        self.constants
+            .add_string(s)
+            .map_err(|_| self.make_error("Too many constants".into()))
+    }
+
+    /// Adds an f64 constant to the chunk, returning the constant's index
+    pub fn add_f64(&mut self, n: f64) -> Result<ConstantIndex, ChunkBuilderError> {
+        self.constants
+            .add_f64(n)
+            .map_err(|_| self.make_error("Too many constants".into()))
+    }
+
+    /// Adds an i64 constant to the chunk, returning the constant's index
+    pub fn add_i64(&mut self, n: i64) -> Result<ConstantIndex, ChunkBuilderError> {
+        self.constants
+            .add_i64(n)
+            .map_err(|_| self.make_error("Too many constants".into()))
+    }
+
+    /// Sets the span that will be associated with the instructions that are pushed next
+    ///
+    /// The span is used when reporting runtime errors. By default instructions are associated
+    /// with the start of the source.
+    pub fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
+    /// Sets the source that the chunk's spans refer to
+    ///
+    /// The source is used to show excerpts when reporting runtime errors, so front-ends with their
+    /// own syntax can provide their source here, along with matching spans via
+    /// [ChunkBuilder::set_span].
+    pub fn set_source(&mut self, source: &str) {
+        self.debug_info.source = source.to_string();
+    }
+
+    /// Sets the path of the chunk's source, which is included in runtime errors
+    pub fn set_source_path(&mut self, source_path: Option<PathBuf>) {
+        self.source_path = source_path;
+    }
+
+    /// The current position in the chunk's bytecode
+    ///
+    /// The next instruction to be pushed will start at this position.
+    pub fn ip(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Creates a new [Label], which can then be placed with [ChunkBuilder::place_label]
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places the label at the current position in the bytecode
+    pub fn place_label(&mut self, label: Label) -> Result<(), ChunkBuilderError> {
+        let ip = self.ip();
+        match self.labels.get_mut(label.0) {
+            Some(position @ None) => {
+                *position = Some(ip);
+                Ok(())
+            }
+            Some(Some(_)) => {
+                Err(self.make_error(format!("Label {} has already been placed", label.0)))
+            }
+            None => Err(self.make_error(format!("Label {} is unknown", label.0))),
+        }
+    }
+
+    /// Encodes an instruction and adds it to the end of the chunk's bytecode
+    pub fn push(&mut self, instruction: Instruction) -> Result<(), ChunkBuilderError> {
+        self.push_instruction(instruction, None)
+    }
+
+    /// Encodes an instruction that jumps to a label, and adds it to the end of the bytecode
+    ///
+    /// The instruction's offset (or size, for functions) is ignored, and is instead derived from
+    /// the label's position once it's known. For functions, the label should be placed at the end
+    /// of the function's body.
+    ///
+    /// Returns an error if the instruction doesn't have an offset.
+    pub fn push_with_target(
+        &mut self,
+        instruction: Instruction,
+        label: Label,
+    ) -> Result<(), ChunkBuilderError> {
+        if label.0 >= self.labels.len() {
+            return Err(self.make_error(format!("Label {} is unknown", label.0)));
+        }
+        self.push_instruction(instruction, Some(label))
+    }
+
+    /// Validates the bytecode and produces a [Chunk]
+    pub fn build(mut self) -> Result<Chunk, ChunkBuilderError> {
+        for pending in self.pending_targets.iter() {
+            let error = |message: String| ChunkBuilderError {
+                message,
+                ip: pending.instruction_ip,
+            };
+
+            let target_ip = self.labels[pending.label.0].ok_or_else(|| {
+                error(format!("Label {} was used but not placed", pending.label.0))
+            })?;
+
+            let offset = if pending.backwards {
+                pending.instruction_end.checked_sub(target_ip)
+            } else {
+                target_ip.checked_sub(pending.instruction_end)
+            }
+            .ok_or_else(|| {
+                error(format!(
+                    "Label {} is in the wrong direction for the instruction",
+                    pending.label.0
+                ))
+            })?;
+
+            let offset = u16::try_from(offset).map_err(|_| {
+                error(format!(
+                    "The offset to label {} is too large ({}, the maximum is {})",
+                    pending.label.0,
+                    offset,
+                    u16::MAX
+                ))
+            })?;
+
+            self.bytes[pending.offset_ip..pending.offset_ip + 2]
+                .copy_from_slice(&offset.to_le_bytes());
+            self.jumps.push((pending.instruction_ip, target_ip));
+        }
+
+        for (instruction_ip, target_ip) in self.jumps.iter() {
+            if *target_ip != self.bytes.len()
+                && self.instruction_ips.binary_search(target_ip).is_err()
+            {
+                return Err(ChunkBuilderError {
+                    message: format!(
+                        "The target of the instruction ({}) isn't at the start of an instruction",
+                        target_ip
+                    ),
+                    ip: *instruction_ip,
+                });
+            }
+        }
+
+        let chunk = Arc::new(Chunk::new(
+            self.bytes,
+            self.constants.build(),
+            self.source_path,
+            self.debug_info,
+        ));
+
+        validate_constants(chunk.clone())?;
+
+        // The chunk was only shared with the reader in validate_constants, which has been dropped
+        Ok(Arc::try_unwrap(chunk).expect("Chunk is still shared"))
+    }
+
+    fn push_instruction(
+        &mut self,
+        instruction: Instruction,
+        label: Option<Label>,
+    ) -> Result<(), ChunkBuilderError> {
+        let ip = self.ip();
+        let mut bytes = Vec::new();
+        let jump = encode_instruction(instruction, &mut bytes).map_err(|e| self.make_error(e))?;
+        let instruction_end = ip + bytes.len();
+
+        match (label, jump) {
+            (Some(label), Some(jump)) => self.pending_targets.push(PendingTarget {
+                label,
+                offset_ip: ip + jump.offset_position,
+                instruction_ip: ip,
+                instruction_end,
+                backwards: jump.backwards,
+            }),
+            (Some(_), None) => {
+                return Err(self.make_error("The instruction doesn't have an offset".into()));
+            }
+            (None, Some(jump)) => {
+                let target_ip = if jump.backwards {
+                    instruction_end.checked_sub(jump.offset).ok_or_else(|| {
+                        self.make_error("The offset jumps past the start of the chunk".into())
+                    })?
+                } else {
+                    instruction_end + jump.offset
+                };
+                self.jumps.push((ip, target_ip));
+            }
+            (None, None) => {}
+        }
+
+        self.debug_info.push(ip, self.span);
+        self.instruction_ips.push(ip);
+        self.bytes.extend_from_slice(&bytes);
+
+        Ok(())
+    }
+
+    fn make_error(&self, message: String) -> ChunkBuilderError {
+        ChunkBuilderError {
+            message,
+            ip: self.ip(),
+        }
+    }
+}
+
+// An instruction whose offset will be set once its target label has been placed
+struct PendingTarget {
+    label: Label,
+    // The position in the bytecode of the instruction's u16 offset
+    offset_ip: usize,
+    instruction_ip: usize,
+    instruction_end: usize,
+    backwards: bool,
+}
+
+// The offset of an encoded instruction that jumps, or that defines a function's body
+struct JumpOffset {
+    // The position of the u16 offset in the encoded instruction
+    offset_position: usize,
+    offset: usize,
+    backwards: bool,
+}
+
+// Encodes the instruction into bytes, returning the instruction's offset if it has one
+fn encode_instruction(
+    instruction: Instruction,
+    bytes: &mut Vec<u8>,
+) -> Result<Option<JumpOffset>, String> {
+    use Instruction::*;
+
+    // Pushes an op followed by the given bytes
+    macro_rules! op {
+        ($op:expr, $($byte:expr),* $(,)?) => {{
+            bytes.push($op as u8);
+            $(bytes.push($byte);)*
+        }};
+    }
+
+    // Pushes the op that matches the size of the constant index
+    macro_rules! constant_op {
+        ($op8:expr, $op16:expr, $op24:expr, $op32:expr, $register:expr, $constant:expr) => {{
+            let constant_bytes = $constant.bytes();
+            match $constant.byte_count() {
+                1 => op!($op8, $register),
+                2 => op!($op16, $register),
+                3 => op!($op24, $register),
+                _ => op!($op32, $register),
+            }
+            bytes.extend_from_slice(&constant_bytes[..$constant.byte_count()]);
+        }};
+    }
+
+    // Pushes the op that matches the size of the size hint
+    macro_rules! size_hint_op {
+        ($op8:expr, $op32:expr, $register:expr, $size_hint:expr) => {{
+            if let Ok(size_hint) = u8::try_from($size_hint) {
+                op!($op8, $register, size_hint);
+            } else if let Ok(size_hint) = u32::try_from($size_hint) {
+                op!($op32, $register);
+                bytes.extend_from_slice(&size_hint.to_le_bytes());
+            } else {
+                return Err(format!("The size hint {} is too large", $size_hint));
+            }
+        }};
+    }
+
+    // Pushes a u16 offset and returns it as the instruction's JumpOffset
+    macro_rules! offset {
+        ($offset:expr, $backwards:expr) => {{
+            let offset_position = bytes.len();
+            match u16::try_from($offset) {
+                Ok(offset) => bytes.extend_from_slice(&offset.to_le_bytes()),
+                Err(_) => {
+                    return Err(format!(
+                        "The offset {} is too large, the maximum is {}",
+                        $offset,
+                        u16::MAX
+                    ))
+                }
+            }
+            return Ok(Some(JumpOffset {
+                offset_position,
+                offset: $offset,
+                backwards: $backwards,
+            }));
+        }};
+    }
+
+    match instruction {
+        Error { .. } => return Err("Error instructions can't be encoded".into()),
+        Copy { target, source } => op!(Op::Copy, target, source),
+        SetEmpty { register } => op!(Op::SetEmpty, register),
+        SetBool { register, value } => {
+            op!(if value { Op::SetTrue } else { Op::SetFalse }, register)
+        }
+        SetNumber { register, value } => match value {
+            0 => op!(Op::Set0, register),
+            1 => op!(Op::Set1, register),
+            _ => match u8::try_from(value) {
+                Ok(n) => op!(Op::SetNumberU8, register, n),
+                Err(_) => {
+                    return Err(format!(
+                        "SetNumber only supports values from 0 to 255, \
+                         use LoadInt for other values (found {})",
+                        value
+                    ))
+                }
+            },
+        },
+        LoadFloat { register, constant } => constant_op!(
+            Op::LoadFloat,
+            Op::LoadFloat16,
+            Op::LoadFloat24,
+            Op::LoadFloat32,
+            register,
+            constant
+        ),
+        LoadInt { register, constant } => {
+            constant_op!(
+                Op::LoadInt,
+                Op::LoadInt16,
+                Op::LoadInt24,
+                Op::LoadInt32,
+                register,
+                constant
+            )
+        }
+        LoadString { register, constant } => constant_op!(
+            Op::LoadString,
+            Op::LoadString16,
+            Op::LoadString24,
+            Op::LoadString32,
+            register,
+            constant
+        ),
+        LoadNonLocal { register, constant } => constant_op!(
+            Op::LoadNonLocal,
+            Op::LoadNonLocal16,
+            Op::LoadNonLocal24,
+            Op::LoadNonLocal32,
+            register,
+            constant
+        ),
+        ValueExport { name, value } => op!(Op::ValueExport, name, value),
+        Import { register } => op!(Op::Import, register),
+        MakeTempTuple {
+            register,
+            start,
+            count,
+        } => op!(Op::MakeTempTuple, register, start, count),
+        MakeMap {
+            register,
+            size_hint,
+        } => size_hint_op!(Op::MakeMap, Op::MakeMap32, register, size_hint),
+        MakeNum2 {
+            register,
+            element_register,
+            count,
+        } => op!(Op::MakeNum2, register, element_register, count),
+        MakeNum4 {
+            register,
+            element_register,
+            count,
+        } => op!(Op::MakeNum4, register, element_register, count),
+        SequenceStart {
+            register,
+            size_hint,
+        } => size_hint_op!(Op::SequenceStart, Op::SequenceStart32, register, size_hint),
+        SequencePush { sequence, value } => op!(Op::SequencePush, sequence, value),
+        SequencePushN {
+            sequence,
+            start,
+            count,
+        } => op!(Op::SequencePushN, sequence, start, count),
+        SequenceToList { sequence } => op!(Op::SequenceToList, sequence),
+        SequenceToTuple { sequence } => op!(Op::SequenceToTuple, sequence),
+        Range {
+            register,
+            start,
+            end,
+        } => op!(Op::Range, register, start, end),
+        RangeInclusive {
+            register,
+            start,
+            end,
+        } => op!(Op::RangeInclusive, register, start, end),
+        RangeTo { register, end } => op!(Op::RangeTo, register, end),
+        RangeToInclusive { register, end } => op!(Op::RangeToInclusive, register, end),
+        RangeFrom { register, start } => op!(Op::RangeFrom, register, start),
+        RangeFull { register } => op!(Op::RangeFull, register),
+        MakeIterator { register, iterable } => op!(Op::MakeIterator, register, iterable),
+        SimpleFunction {
+            register,
+            arg_count,
+            size,
+        } => {
+            op!(Op::SimpleFunction, register, arg_count);
+            offset!(size, false);
+        }
+        Function {
+            register,
+            arg_count,
+            capture_count,
+            instance_function,
+            variadic,
+            generator,
+            arg_is_unpacked_tuple,
+            size,
+        } => {
+            let flags = FunctionFlags {
+                instance_function,
+                variadic,
+                generator,
+                arg_is_unpacked_tuple,
+            };
+            op!(
+                Op::Function,
+                register,
+                arg_count,
+                capture_count,
+                flags.as_byte()
+            );
+            offset!(size, false);
+        }
+        Capture {
+            function,
+            target,
+            source,
+        } => op!(Op::Capture, function, target, source),
+        Negate { register, value } => op!(Op::Negate, register, value),
+        Not { register, value } => op!(Op::Not, register, value),
+        Add { register, lhs, rhs } => op!(Op::Add, register, lhs, rhs),
+        Subtract { register, lhs, rhs } => op!(Op::Subtract, register, lhs, rhs),
+        Multiply { register, lhs, rhs } => op!(Op::Multiply, register, lhs, rhs),
+        Divide { register, lhs, rhs } => op!(Op::Divide, register, lhs, rhs),
+        Modulo { register, lhs, rhs } => op!(Op::Modulo, register, lhs, rhs),
+        Less { register, lhs, rhs } => op!(Op::Less, register, lhs, rhs),
+        LessOrEqual { register, lhs, rhs } => op!(Op::LessOrEqual, register, lhs, rhs),
+        Greater { register, lhs, rhs } => op!(Op::Greater, register, lhs, rhs),
+        GreaterOrEqual { register, lhs, rhs } => op!(Op::GreaterOrEqual, register, lhs, rhs),
+        Equal { register, lhs, rhs } => op!(Op::Equal, register, lhs, rhs),
+        NotEqual { register, lhs, rhs } => op!(Op::NotEqual, register, lhs, rhs),
+        In { register, lhs, rhs } => op!(Op::In, register, lhs, rhs),
+        AddU8 { register, lhs, rhs } => op!(Op::AddU8, register, lhs, rhs),
+        SubtractU8 { register, lhs, rhs } => op!(Op::SubtractU8, register, lhs, rhs),
+        LessU8 { register, lhs, rhs } => op!(Op::LessU8, register, lhs, rhs),
+        LessOrEqualU8 { register, lhs, rhs } => op!(Op::LessOrEqualU8, register, lhs, rhs),
+        GreaterU8 { register, lhs, rhs } => op!(Op::GreaterU8, register, lhs, rhs),
+        GreaterOrEqualU8 { register, lhs, rhs } => op!(Op::GreaterOrEqualU8, register, lhs, rhs),
+        EqualU8 { register, lhs, rhs } => op!(Op::EqualU8, register, lhs, rhs),
+        NotEqualU8 { register, lhs, rhs } => op!(Op::NotEqualU8, register, lhs, rhs),
+        Jump { offset } => {
+            op!(Op::Jump,);
+            offset!(offset, false);
+        }
+        JumpIf {
+            register,
+            offset,
+            jump_condition,
+        } => {
+            op!(
+                if jump_condition {
+                    Op::JumpTrue
+                } else {
+                    Op::JumpFalse
+                },
+                register
+            );
+            offset!(offset, false);
+        }
+        JumpBack { offset } => {
+            op!(Op::JumpBack,);
+            offset!(offset, true);
+        }
+        Call {
+            result,
+            function,
+            frame_base,
+            arg_count,
+        } => op!(Op::Call, result, function, frame_base, arg_count),
+        CallInstance {
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance,
+        } => op!(
+            Op::CallInstance,
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance
+        ),
+        TailCall {
+            result,
+            function,
+            frame_base,
+            arg_count,
+        } => op!(Op::TailCall, result, function, frame_base, arg_count),
+        TailCallInstance {
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance,
+        } => op!(
+            Op::TailCallInstance,
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance
+        ),
+        Return { register } => op!(Op::Return, register),
+        Yield { register } => op!(Op::Yield, register),
+        Throw { register } => op!(Op::Throw, register),
+        Size { register, value } => op!(Op::Size, register, value),
+        IterNext {
+            register,
+            iterator,
+            jump_offset,
+        } => {
+            op!(Op::IterNext, register, iterator);
+            offset!(jump_offset, false);
+        }
+        IterNextTemp {
+            register,
+            iterator,
+            jump_offset,
+        } => {
+            op!(Op::IterNextTemp, register, iterator);
+            offset!(jump_offset, false);
+        }
+        IterNextQuiet {
+            iterator,
+            jump_offset,
+        } => {
+            op!(Op::IterNextQuiet, iterator);
+            offset!(jump_offset, false);
+        }
+        TempIndex {
+            register,
+            value,
+            index,
+        } => op!(Op::TempIndex, register, value, index as u8),
+        SliceFrom {
+            register,
+            value,
+            index,
+        } => op!(Op::SliceFrom, register, value, index as u8),
+        SliceTo {
+            register,
+            value,
+            index,
+        } => op!(Op::SliceTo, register, value, index as u8),
+        IsTuple { register, value } => op!(Op::IsTuple, register, value),
+        IsList { register, value } => op!(Op::IsList, register, value),
+        Index {
+            register,
+            value,
+            index,
+        } => op!(Op::Index, register, value, index),
+        SetIndex {
+            register,
+            index,
+            value,
+        } => op!(Op::SetIndex, register, index, value),
+        MapInsert {
+            register,
+            key,
+            value,
+        } => op!(Op::MapInsert, register, key, value),
+        MetaInsert {
+            register,
+            value,
+            id,
+        } => op!(Op::MetaInsert, register, id as u8, value),
+        MetaInsertNamed {
+            register,
+            value,
+            id,
+            name,
+        } => op!(Op::MetaInsertNamed, register, id as u8, name, value),
+        MetaExport { id, value } => op!(Op::MetaExport, id as u8, value),
+        MetaExportNamed { id, name, value } => op!(Op::MetaExportNamed, id as u8, name, value),
+        Access {
+            register,
+            value,
+            key,
+        } => {
+            match key.byte_count() {
+                1 => op!(Op::Access, register, value),
+                2 => op!(Op::Access16, register, value),
+                3 => op!(Op::Access24, register, value),
+                _ => op!(Op::Access32, register, value),
+            }
+            bytes.extend_from_slice(&key.bytes()[..key.byte_count()]);
+        }
+        AccessString {
+            register,
+            value,
+            key,
+        } => op!(Op::AccessString, register, value, key),
+        TryStart {
+            arg_register,
+            catch_offset,
+        } => {
+            op!(Op::TryStart, arg_register);
+            offset!(catch_offset, false);
+        }
+        TryEnd => op!(Op::TryEnd,),
+        Coverage { index } => {
+            op!(Op::Coverage,);
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        Debug { register, constant } => {
+            op!(Op::Debug, register);
+            bytes.extend_from_slice(&constant.bytes());
+        }
+        Assert { register, constant } => {
+            op!(Op::Assert, register);
+            bytes.extend_from_slice(&constant.bytes());
+        }
+        AssertComparison {
+            register,
+            lhs,
+            rhs,
+            constant,
+        } => {
+            op!(Op::AssertComparison, register, lhs, rhs);
+            bytes.extend_from_slice(&constant.bytes());
+        }
+        CheckType { register, type_id } => op!(Op::CheckType, register, type_id as u8),
+        CheckSize { register, size } => match u8::try_from(size) {
+            Ok(size) => op!(Op::CheckSize, register, size),
+            Err(_) => return Err(format!("The size {} is too large for CheckSize", size)),
+        },
+        JumpTable { .. } => return Err("JumpTable instructions can't be encoded".into()),
+        CheckTypeAnnotation {
+            register,
+            type_name,
+        } => {
+            op!(Op::CheckTypeAnnotation, register);
+            bytes.extend_from_slice(&type_name.bytes());
+        }
+        StringStart {
+            register,
+            size_hint,
+        } => size_hint_op!(Op::StringStart, Op::StringStart32, register, size_hint),
+        StringPush { register, value } => op!(Op::StringPush, register, value),
+        StringFinish { register } => op!(Op::StringFinish, register),
+    }
+
+    Ok(None)
+}
+
+// Checks that the constants referred to by the chunk's instructions have the expected types
+fn validate_constants(chunk: Arc<Chunk>) -> Result<(), ChunkBuilderError> {
+    #[derive(Clone, Copy, Debug)]
+    enum ConstantKind {
+        Float,
+        Int,
+        String,
+    }
+
+    let mut reader = InstructionReader::new(chunk.clone());
+    let mut ip = reader.ip;
+
+    while let Some(instruction) = reader.next() {
+        use Instruction::*;
+
+        let expected = match instruction {
+            LoadFloat { constant, .. } => Some((constant, ConstantKind::Float)),
+            LoadInt { constant, .. } => Some((constant, ConstantKind::Int)),
+            LoadString { constant, .. }
+            | LoadNonLocal { constant, .. }
+            | Debug { constant, .. }
+            | Assert { constant, .. }
+            | AssertComparison { constant, .. }
+            | Access { key: constant, .. }
+            | CheckTypeAnnotation {
+                type_name: constant,
+                ..
+            } => Some((constant, ConstantKind::String)),
+            _ => None,
+        };
+
+        if let Some((index, kind)) = expected {
+            let is_valid = matches!(
+                (chunk.constants.get(usize::from(index)), kind),
+                (Some(Constant::F64(_)), ConstantKind::Float)
+                    | (Some(Constant::I64(_)), ConstantKind::Int)
+                    | (Some(Constant::Str(_)), ConstantKind::String)
+            );
+
+            if !is_valid {
+                return Err(ChunkBuilderError {
+                    message: format!("Constant {} isn't a {:?} constant", index, kind),
+                    ip,
+                });
+            }
+        }
+
+        ip = reader.ip;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,348 @@
+use {
+    crate::{Chunk, DebugInfo},
+    koto_parser::{Constant, ConstantPool, Position, Span},
+    std::{convert::TryFrom, error, fmt, path::PathBuf},
+};
+
+// The header that starts each serialized chunk
+//
+// The leading null byte ensures that serialized chunks can't be mistaken for Koto source.
+const MAGIC: &[u8] = b"\0KOTO";
+
+// The version of the serialization format, incremented when the format or the bytecode changes
+pub(crate) const FORMAT_VERSION: u16 = 5;
+
+// Tags that identify the type of each serialized constant
+const CONSTANT_F64: u8 = 0;
+const CONSTANT_I64: u8 = 1;
+const CONSTANT_STR: u8 = 2;
+
+/// The error type returned by [Chunk::deserialize]
+#[derive(Clone, Debug)]
+pub struct ChunkFormatError {
+    /// The error's message
+    pub message: String,
+    /// The position in the serialized data where the error was encountered
+    pub offset: usize,
+}
+
+impl fmt::Display for ChunkFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.offset)
+    }
+}
+
+impl error::Error for ChunkFormatError {}
+
+impl Chunk {
+    /// Serializes the chunk, allowing it to be loaded later without recompiling its source
+    ///
+    /// The serialized data starts with a header containing the format's version, followed by
+    /// sections containing the chunk's bytecode, constants, source path, and debug info.
+    ///
+    /// Numbers are encoded in little-endian order with fixed sizes, and compiling the same
+    /// source with the same settings produces identical chunks, so serialized chunks are
+    /// reproducible and can be shared between platforms.
+    ///
+    /// See [Chunk::deserialize].
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut writer = ChunkWriter::default();
+
+        writer.bytes.extend_from_slice(MAGIC);
+        writer
+            .bytes
+            .extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+        writer.section(|writer| writer.bytes.extend_from_slice(&self.bytes));
+
+        writer.section(|writer| {
+            writer.write_len(self.constants.size());
+            for constant in self.constants.iter() {
+                match constant {
+                    Constant::F64(n) => {
+                        writer.bytes.push(CONSTANT_F64);
+                        writer.bytes.extend_from_slice(&n.to_le_bytes());
+                    }
+                    Constant::I64(n) => {
+                        writer.bytes.push(CONSTANT_I64);
+                        writer.bytes.extend_from_slice(&n.to_le_bytes());
+                    }
+                    Constant::Str(s) => {
+                        writer.bytes.push(CONSTANT_STR);
+                        writer.write_str(s);
+                    }
+                }
+            }
+        });
+
+        writer.section(|writer| match &self.source_path {
+            Some(path) => {
+                writer.bytes.push(1);
+                writer.write_str(&path.to_string_lossy());
+            }
+            None => writer.bytes.push(0),
+        });
+
+        writer.section(|writer| {
+            let debug_info = &self.debug_info;
+            writer.write_len(debug_info.source_map.len());
+            for (ip, span) in debug_info.source_map.iter() {
+                writer.write_len(*ip);
+                writer.write_position(span.start);
+                writer.write_position(span.end);
+            }
+            writer.write_str(&debug_info.source);
+            writer.write_len(debug_info.coverage_points.len());
+            for span in debug_info.coverage_points.iter() {
+                writer.write_position(span.start);
+                writer.write_position(span.end);
+            }
+            writer.write_len(debug_info.functions.len());
+            for (body, name) in debug_info.functions.iter() {
+                writer.write_len(body.start);
+                writer.write_len(body.end);
+                match name {
+                    Some(name) => {
+                        writer.bytes.push(1);
+                        writer.write_str(name);
+                    }
+                    None => writer.bytes.push(0),
+                }
+            }
+        });
+
+        writer.bytes
+    }
+
+    /// Deserializes a chunk that was serialized with [Chunk::serialize]
+    ///
+    /// An error is returned if the data is malformed, or if it was serialized with an
+    /// incompatible version of Koto.
+    ///
+    /// The chunk's bytecode isn't validated, so chunks that are deserialized from untrusted
+    /// sources should be checked with [Chunk::verify] before being run.
+    pub fn deserialize(data: &[u8]) -> Result<Self, ChunkFormatError> {
+        let mut reader = ChunkReader { data, offset: 0 };
+
+        if !Self::is_serialized(data) {
+            return reader.error("Missing chunk header");
+        }
+        reader.offset = MAGIC.len();
+
+        let version = u16::from_le_bytes(reader.read_array()?);
+        if version != FORMAT_VERSION {
+            return reader.error(format!(
+                "Unsupported chunk format version {} (expected {})",
+                version, FORMAT_VERSION
+            ));
+        }
+
+        let bytes = reader.section(|reader| Ok(reader.read_bytes(reader.remaining())?.to_vec()))?;
+
+        let constants = reader.section(|reader| {
+            let count = reader.read_len()?;
+            let mut constants = Vec::new();
+            for _ in 0..count {
+                let constant = match reader.read_array::<1>()?[0] {
+                    CONSTANT_F64 => OwnedConstant::F64(f64::from_le_bytes(reader.read_array()?)),
+                    CONSTANT_I64 => OwnedConstant::I64(i64::from_le_bytes(reader.read_array()?)),
+                    CONSTANT_STR => OwnedConstant::Str(reader.read_str()?),
+                    other => return reader.error(format!("Unexpected constant type {}", other)),
+                };
+                constants.push(constant);
+            }
+
+            match ConstantPool::from_constants(constants.iter().map(|constant| match constant {
+                OwnedConstant::F64(n) => Constant::F64(*n),
+                OwnedConstant::I64(n) => Constant::I64(*n),
+                OwnedConstant::Str(s) => Constant::Str(s),
+            })) {
+                Ok(pool) => Ok(pool),
+                Err(_) => reader.error("Too many constants"),
+            }
+        })?;
+
+        let source_path = reader.section(|reader| match reader.read_array::<1>()?[0] {
+            0 => Ok(None),
+            1 => Ok(Some(PathBuf::from(reader.read_str()?))),
+            other => reader.error(format!("Unexpected source path flag {}", other)),
+        })?;
+
+        let debug_info = reader.section(|reader| {
+            let count = reader.read_len()?;
+            let mut source_map = Vec::new();
+            for _ in 0..count {
+                let ip = reader.read_len()?;
+                let start = reader.read_position()?;
+                let end = reader.read_position()?;
+                source_map.push((ip, Span { start, end }));
+            }
+            let source = reader.read_str()?;
+            let count = reader.read_len()?;
+            let mut coverage_points = Vec::new();
+            for _ in 0..count {
+                let start = reader.read_position()?;
+                let end = reader.read_position()?;
+                coverage_points.push(Span { start, end });
+            }
+            let count = reader.read_len()?;
+            let mut functions = Vec::new();
+            for _ in 0..count {
+                let start = reader.read_len()?;
+                let end = reader.read_len()?;
+                let name = match reader.read_array::<1>()?[0] {
+                    0 => None,
+                    1 => Some(reader.read_str()?),
+                    other => {
+                        return reader.error(format!("Unexpected function name flag {}", other))
+                    }
+                };
+                functions.push((start..end, name));
+            }
+            Ok(DebugInfo {
+                source_map,
+                coverage_points,
+                functions,
+                source,
+            })
+        })?;
+
+        if reader.remaining() > 0 {
+            return reader.error("Unexpected data at the end of the chunk");
+        }
+
+        Ok(Self::new(bytes, constants, source_path, debug_info))
+    }
+
+    /// Returns true if the data starts with the header of a serialized chunk
+    ///
+    /// This can be used to check whether a script needs to be compiled or deserialized,
+    /// see [Chunk::serialize].
+    pub fn is_serialized(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+}
+
+// An owned equivalent of [Constant], used while deserializing a chunk's constants
+enum OwnedConstant {
+    F64(f64),
+    I64(i64),
+    Str(String),
+}
+
+#[derive(Default)]
+struct ChunkWriter {
+    bytes: Vec<u8>,
+}
+
+impl ChunkWriter {
+    // Writes a section prefixed by its length in bytes
+    fn section(&mut self, write_contents: impl FnOnce(&mut Self)) {
+        let length_offset = self.bytes.len();
+        self.write_len(0);
+        write_contents(self);
+        let length = self.bytes.len() - length_offset - 4;
+        self.bytes[length_offset..length_offset + 4].copy_from_slice(&to_u32(length).to_le_bytes());
+    }
+
+    fn write_len(&mut self, len: usize) {
+        self.bytes.extend_from_slice(&to_u32(len).to_le_bytes());
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write_len(s.len());
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn write_position(&mut self, position: Position) {
+        self.bytes.extend_from_slice(&position.line.to_le_bytes());
+        self.bytes.extend_from_slice(&position.column.to_le_bytes());
+        self.bytes.extend_from_slice(&position.offset.to_le_bytes());
+    }
+}
+
+// Chunk sizes are limited by the u32 positions used in spans, so lengths are stored as u32s
+fn to_u32(len: usize) -> u32 {
+    u32::try_from(len).expect("Chunk data is too large to be serialized")
+}
+
+struct ChunkReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ChunkReader<'a> {
+    // Reads a section prefixed by its length in bytes
+    //
+    // The section's contents must be fully consumed by read_contents.
+    fn section<T>(
+        &mut self,
+        read_contents: impl FnOnce(&mut ChunkReader<'a>) -> Result<T, ChunkFormatError>,
+    ) -> Result<T, ChunkFormatError> {
+        let length = self.read_len()?;
+        let start = self.offset;
+        let section_data = self.read_bytes(length)?;
+
+        let mut section_reader = ChunkReader {
+            data: &self.data[..start + section_data.len()],
+            offset: start,
+        };
+        let result = read_contents(&mut section_reader)?;
+
+        if section_reader.remaining() > 0 {
+            return section_reader.error("Unexpected data at the end of the section");
+        }
+
+        Ok(result)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], ChunkFormatError> {
+        if count > self.remaining() {
+            return self.error("Unexpected end of data");
+        }
+        let result = &self.data[self.offset..self.offset + count];
+        self.offset += count;
+        Ok(result)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ChunkFormatError> {
+        let mut result = [0; N];
+        result.copy_from_slice(self.read_bytes(N)?);
+        Ok(result)
+    }
+
+    fn read_len(&mut self) -> Result<usize, ChunkFormatError> {
+        Ok(u32::from_le_bytes(self.read_array()?) as usize)
+    }
+
+    fn read_str(&mut self) -> Result<String, ChunkFormatError> {
+        let len = self.read_len()?;
+        let start = self.offset;
+        match std::str::from_utf8(self.read_bytes(len)?) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => {
+                self.offset = start;
+                self.error("Invalid UTF-8 in string")
+            }
+        }
+    }
+
+    fn read_position(&mut self) -> Result<Position, ChunkFormatError> {
+        Ok(Position {
+            line: u32::from_le_bytes(self.read_array()?),
+            column: u32::from_le_bytes(self.read_array()?),
+            offset: u32::from_le_bytes(self.read_array()?),
+        })
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ChunkFormatError> {
+        Err(ChunkFormatError {
+            message: message.into(),
+            offset: self.offset,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use {
+    crate::{chunk_verifier, Chunk, Disassembler, Instruction},
+    koto_parser::{ConstantPoolStats, Span},
+    std::{fmt, sync::Arc},
+};
+
+/// Statistics describing one of the functions in a [Chunk], see [ChunkStats]
+///
+/// The chunk's top-level code is treated as a function, with instructions in nested functions
+/// being counted in the stats of the function that contains them rather than in its parent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionStats {
+    /// The position in the chunk's bytecode of the instruction that creates the function
+    ///
+    /// This is `None` for the chunk's top-level code.
+    pub ip: Option<usize>,
+    /// The span of the source that defines the function, if available
+    pub span: Option<Span>,
+    /// The number of functions that the function is nested in
+    pub depth: usize,
+    /// The number of instructions in the function's body
+    pub instruction_count: usize,
+    /// The size in bytes of the function's body
+    pub byte_size: usize,
+    /// The number of registers needed by the function
+    ///
+    /// This is the function's register high-water mark, i.e. the highest register that's
+    /// referred to by the function's instructions, plus one.
+    pub register_count: usize,
+    /// The number of references to constants that are made by the function's instructions
+    pub constant_references: usize,
+}
+
+impl fmt::Display for FunctionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.ip, self.span) {
+            (None, _) => write!(f, "top level")?,
+            (Some(ip), Some(span)) => {
+                write!(f, "function at line {} (ip {})", span.start.line, ip)?
+            }
+            (Some(ip), None) => write!(f, "function at ip {}", ip)?,
+        }
+
+        write!(
+            f,
+            ": {} instructions, {} bytes, {} registers, {} constant references",
+            self.instruction_count, self.byte_size, self.register_count, self.constant_references
+        )
+    }
+}
+
+/// Statistics describing the contents of a compiled [Chunk], see [Chunk::stats]
+///
+/// # Example
+///
+/// ```
+/// use koto_bytecode::{Chunk, Loader};
+///
+/// let chunk = Loader::default()
+///     .compile_script("f = |x| x + 1\nf 42", &None)
+///     .unwrap();
+/// let stats = Chunk::stats(chunk);
+///
+/// assert_eq!(stats.functions.len(), 2);
+/// assert!(stats.max_register_count() > 0);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkStats {
+    /// The size in bytes of the chunk's bytecode
+    pub byte_size: usize,
+    /// Statistics describing the chunk's constant pool
+    pub constants: ConstantPoolStats,
+    /// Statistics for each of the chunk's functions, in the order they appear in the bytecode
+    ///
+    /// The first entry describes the chunk's top-level code.
+    pub functions: Vec<FunctionStats>,
+}
+
+impl ChunkStats {
+    pub(crate) fn new(chunk: Arc<Chunk>) -> Self {
+        let mut functions = vec![FunctionStats::default()];
+        // The indices in `functions` of the function that contains the current instruction,
+        // along with the functions that it's nested in
+        let mut function_stack = vec![0];
+
+        let byte_size = chunk.bytes.len();
+        let constants = chunk.constants.stats();
+
+        for disassembled in Disassembler::new(chunk) {
+            function_stack.truncate(disassembled.depth + 1);
+
+            let function = &mut functions[*function_stack.last().unwrap()];
+            function.instruction_count += 1;
+            function.byte_size += disassembled.size;
+            function.register_count = function
+                .register_count
+                .max(chunk_verifier::register_count(&disassembled.instruction));
+            function.constant_references += disassembled.constants().count();
+
+            if matches!(
+                disassembled.instruction,
+                Instruction::SimpleFunction { .. } | Instruction::Function { .. }
+            ) {
+                function_stack.push(functions.len());
+                functions.push(FunctionStats {
+                    ip: Some(disassembled.ip),
+                    span: disassembled.span,
+                    depth: disassembled.depth + 1,
+                    ..Default::default()
+                });
+            }
+        }
+
+        Self {
+            byte_size,
+            constants,
+            functions,
+        }
+    }
+
+    /// The total number of instructions in the chunk
+    pub fn instruction_count(&self) -> usize {
+        self.functions
+            .iter()
+            .map(|function| function.instruction_count)
+            .sum()
+    }
+
+    /// The highest number of registers that's needed by any of the chunk's functions
+    pub fn max_register_count(&self) -> usize {
+        self.functions
+            .iter()
+            .map(|function| function.register_count)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl fmt::Display for ChunkStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} bytes, {} instructions, {} functions",
+            self.byte_size,
+            self.instruction_count(),
+            self.functions.len().saturating_sub(1)
+        )?;
+        writeln!(f, "{}", self.constants)?;
+
+        for function in self.functions.iter() {
+            writeln!(f, "{}{}", "  ".repeat(function.depth), function)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,785 @@
+use {
+    crate::{Chunk, DebugInfo, Instruction, InstructionReader, JumpTableKey, MAX_SIZE_HINT},
+    koto_parser::{Constant, ConstantIndex, ConstantPool},
+    std::{error, fmt, sync::Arc},
+};
+
+// The number of zeroed bytes that are appended to the bytecode while it's being decoded
+//
+// The instruction reader doesn't perform bounds checks in release builds, so the padding ensures
+// that the operands of a truncated instruction can be read safely. It needs to be at least as
+// large as the largest instruction, excluding the entries of jump tables.
+const DECODING_PADDING: usize = 16;
+
+/// The error type returned by [Chunk::verify]
+#[derive(Clone, Debug)]
+pub struct ChunkVerifierError {
+    /// The error's message
+    pub message: String,
+    /// The position in the bytecode of the instruction that caused the error
+    pub ip: usize,
+}
+
+impl fmt::Display for ChunkVerifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at ip {})", self.message, self.ip)
+    }
+}
+
+impl error::Error for ChunkVerifierError {}
+
+impl Chunk {
+    /// Checks that the chunk's bytecode is well-formed
+    ///
+    /// Chunks produced by the compiler are always well-formed, while chunks from other sources
+    /// (e.g. chunks loaded with [Chunk::deserialize]) should be verified before being run.
+    ///
+    /// The following checks are performed:
+    ///   - Each instruction can be decoded, and fits within the bytecode.
+    ///   - Constant indices refer to constants of the expected type.
+    ///   - Coverage instructions refer to the chunk's coverage points.
+    ///   - Size hints are no larger than [MAX_SIZE_HINT].
+    ///   - Jump targets are at the start of an instruction in the same function.
+    ///   - Function bodies are nested within their enclosing function, and don't allow execution
+    ///     to continue past the end of the body.
+    ///   - Registers are assigned before they're read, taking every path through the function
+    ///     into account, along with the registers that are removed by calls.
+    pub fn verify(&self) -> Result<(), ChunkVerifierError> {
+        let mut verifier = Verifier::new(self)?;
+        verifier.check_structure()?;
+        verifier.find_assigned_registers();
+        verifier.check_registers()
+    }
+}
+
+struct Entry {
+    ip: usize,
+    end: usize,
+    instruction: Instruction,
+    // The index of the function that contains the instruction
+    function: usize,
+}
+
+struct Function {
+    // The end of the function's body
+    end: usize,
+    // The number of registers that are assigned when the function is called
+    initial_registers: u16,
+}
+
+struct TryBlock {
+    start: usize,
+    catch_ip: usize,
+    arg_register: u8,
+    function: usize,
+}
+
+struct Verifier<'a> {
+    chunk: &'a Chunk,
+    entries: Vec<Entry>,
+    // The chunk's top-level code is treated as the first function
+    functions: Vec<Function>,
+    try_blocks: Vec<TryBlock>,
+    // The number of registers that are guaranteed to have been assigned before each instruction,
+    // or None if the instruction is unreachable.
+    assigned_registers: Vec<Option<u16>>,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(chunk: &'a Chunk) -> Result<Self, ChunkVerifierError> {
+        let len = chunk.bytes.len();
+        let mut padded_bytes = Vec::with_capacity(len + DECODING_PADDING);
+        padded_bytes.extend_from_slice(&chunk.bytes);
+        padded_bytes.resize(len + DECODING_PADDING, 0);
+        let mut reader = InstructionReader::new(Arc::new(Chunk::new(
+            padded_bytes,
+            ConstantPool::default(),
+            None,
+            DebugInfo::default(),
+        )));
+
+        let mut entries = Vec::new();
+        let mut functions = vec![Function {
+            end: len,
+            initial_registers: 0,
+        }];
+        // The indices of the functions that contain the current instruction
+        let mut function_stack = vec![0];
+
+        while reader.ip < len {
+            let ip = reader.ip;
+            let instruction = reader.next().expect("Missing instruction");
+            let end = reader.ip;
+
+            let error = |message: String| Err(ChunkVerifierError { message, ip });
+
+            if let Instruction::Error { message } = instruction {
+                return error(message);
+            }
+            if end > len {
+                return error("The instruction extends past the end of the bytecode".into());
+            }
+
+            while functions[*function_stack.last().unwrap()].end <= ip {
+                function_stack.pop();
+            }
+            let function = *function_stack.last().unwrap();
+
+            let nested_function = match &instruction {
+                Instruction::SimpleFunction {
+                    arg_count, size, ..
+                } => Some((*size, *arg_count as u16)),
+                Instruction::Function {
+                    arg_count,
+                    capture_count,
+                    size,
+                    ..
+                } => Some((*size, *arg_count as u16 + *capture_count as u16)),
+                _ => None,
+            };
+
+            if let Some((size, initial_registers)) = nested_function {
+                if size == 0 {
+                    return error("The function has an empty body".into());
+                }
+                if end + size > functions[function].end {
+                    return error(
+                        "The function's body extends past the end of the enclosing function".into(),
+                    );
+                }
+                function_stack.push(functions.len());
+                functions.push(Function {
+                    end: end + size,
+                    initial_registers,
+                });
+            }
+
+            entries.push(Entry {
+                ip,
+                end,
+                instruction,
+                function,
+            });
+        }
+
+        let assigned_registers = vec![None; entries.len()];
+
+        Ok(Self {
+            chunk,
+            entries,
+            functions,
+            try_blocks: Vec::new(),
+            assigned_registers,
+        })
+    }
+
+    // Checks constants, jump targets, and function boundaries
+    fn check_structure(&mut self) -> Result<(), ChunkVerifierError> {
+        let mut try_blocks = Vec::new();
+
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            let ip = entry.ip;
+            let error = |message: String| Err(ChunkVerifierError { message, ip });
+
+            for (constant, kind) in expected_constants(&self.chunk.bytes, entry) {
+                let is_valid = matches!(
+                    (self.chunk.constants.get(usize::from(constant)), kind),
+                    (Some(Constant::F64(_)), ConstantKind::Float)
+                        | (Some(Constant::I64(_)), ConstantKind::Int)
+                        | (Some(Constant::Str(_)), ConstantKind::String)
+                );
+                if !is_valid {
+                    return error(format!("Constant {} isn't a {:?} constant", constant, kind));
+                }
+            }
+
+            for target in self.successors(index)? {
+                self.check_target(entry, target)?;
+            }
+
+            if let Instruction::Coverage { index } = entry.instruction {
+                if index as usize >= self.chunk.debug_info.coverage_points().len() {
+                    return error(format!("Coverage point {} doesn't exist", index));
+                }
+            }
+
+            if let Instruction::MakeMap { size_hint, .. }
+            | Instruction::SequenceStart { size_hint, .. }
+            | Instruction::StringStart { size_hint, .. } = entry.instruction
+            {
+                if size_hint > MAX_SIZE_HINT {
+                    return error(format!(
+                        "The size hint {} is greater than the maximum of {}",
+                        size_hint, MAX_SIZE_HINT
+                    ));
+                }
+            }
+
+            if let Instruction::TryStart {
+                arg_register,
+                catch_offset,
+            } = entry.instruction
+            {
+                try_blocks.push(TryBlock {
+                    start: ip,
+                    catch_ip: entry.end + catch_offset,
+                    arg_register,
+                    function: entry.function,
+                });
+            }
+        }
+
+        self.try_blocks = try_blocks;
+        Ok(())
+    }
+
+    // Returns the positions that execution can continue from after the instruction
+    //
+    // The position of the catch block is included for TryStart.
+    fn successors(&self, index: usize) -> Result<Vec<usize>, ChunkVerifierError> {
+        use Instruction::*;
+
+        let entry = &self.entries[index];
+        let end = entry.end;
+
+        let result = match &entry.instruction {
+            Return { .. } | Throw { .. } => vec![],
+            Jump { offset } => vec![end + offset],
+            JumpBack { offset } => match end.checked_sub(*offset) {
+                Some(target) => vec![target],
+                None => {
+                    return Err(ChunkVerifierError {
+                        message: "The jump target is before the start of the bytecode".into(),
+                        ip: entry.ip,
+                    })
+                }
+            },
+            JumpIf { offset, .. } => vec![end, end + offset],
+            IterNext { jump_offset, .. }
+            | IterNextTemp { jump_offset, .. }
+            | IterNextQuiet { jump_offset, .. } => vec![end, end + jump_offset],
+            TryStart { catch_offset, .. } => vec![end, end + catch_offset],
+            SimpleFunction { size, .. } | Function { size, .. } => vec![end + size],
+            JumpTable {
+                key_type,
+                entry_count,
+                default_offset,
+                table_ip,
+                ..
+            } => {
+                let mut targets = vec![end, end + default_offset];
+                let entry_size = key_type.entry_size();
+                for i in 0..*entry_count {
+                    let offset_ip = table_ip + (i + 1) * entry_size - 2;
+                    let offset = u16::from_le_bytes([
+                        self.chunk.bytes[offset_ip],
+                        self.chunk.bytes[offset_ip + 1],
+                    ]);
+                    targets.push(end + offset as usize);
+                }
+                targets
+            }
+            _ => vec![end],
+        };
+
+        Ok(result)
+    }
+
+    fn check_target(&self, entry: &Entry, target: usize) -> Result<(), ChunkVerifierError> {
+        let error = |message: &str| {
+            Err(ChunkVerifierError {
+                message: format!("{} ({})", message, target),
+                ip: entry.ip,
+            })
+        };
+
+        let function_end = self.functions[entry.function].end;
+
+        if target == function_end {
+            // Reaching the end of the top-level code ends execution of the chunk,
+            // whereas nested functions need to return explicitly.
+            if entry.function == 0 {
+                Ok(())
+            } else {
+                error("Execution continues past the end of the function")
+            }
+        } else if target > function_end {
+            error("The jump target is outside of the function")
+        } else {
+            match self.entry_index(target) {
+                Some(index) if self.entries[index].function == entry.function => Ok(()),
+                Some(_) => error("The jump target is outside of the function"),
+                None => error("The jump target isn't at the start of an instruction"),
+            }
+        }
+    }
+
+    fn entry_index(&self, ip: usize) -> Option<usize> {
+        self.entries
+            .binary_search_by_key(&ip, |entry| entry.ip)
+            .ok()
+    }
+
+    // Finds the number of registers that are guaranteed to be assigned before each instruction
+    //
+    // Starting from the beginning of each function, the register counts are propagated through
+    // the instructions until they stop changing, with the lowest count being kept when multiple
+    // paths lead to the same instruction.
+    fn find_assigned_registers(&mut self) {
+        let mut pending = Vec::new();
+
+        let mut start_function = |verifier: &mut Self, ip: usize, registers: u16| {
+            if let Some(index) = verifier.entry_index(ip) {
+                verifier.assigned_registers[index] = Some(registers);
+                pending.push(index);
+            }
+        };
+
+        start_function(self, 0, 0);
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            if let Instruction::SimpleFunction { .. } | Instruction::Function { .. } =
+                entry.instruction
+            {
+                let function = self.entries[index + 1].function;
+                let registers = self.functions[function].initial_registers;
+                start_function(self, entry.end, registers);
+            }
+        }
+
+        while let Some(index) = pending.pop() {
+            let registers = self.assigned_registers[index].expect("Missing register count");
+            let entry = &self.entries[index];
+            let usage = register_usage(&entry.instruction);
+
+            // The call's frame is removed from the value stack when the call is complete.
+            // External functions assign their result before the frame is removed, so a result
+            // register at or above the frame base can't be relied on after the call.
+            let after_call = match usage.call_frame_base {
+                Some(frame_base) => registers.min(frame_base as u16),
+                None => registers,
+            };
+            let after = usage
+                .writes
+                .iter()
+                .filter(|register| match usage.call_frame_base {
+                    Some(frame_base) => **register < frame_base,
+                    None => true,
+                })
+                .fold(after_call, |after, register| {
+                    after.max(*register as u16 + 1)
+                });
+
+            let mut updates = Vec::new();
+
+            for target in self.successors(index).unwrap_or_default() {
+                let target_registers = match &entry.instruction {
+                    // The iterator's output isn't assigned when the loop is finished
+                    Instruction::IterNext { jump_offset, .. }
+                    | Instruction::IterNextTemp { jump_offset, .. }
+                        if target == entry.end + jump_offset =>
+                    {
+                        registers
+                    }
+                    // The catch block is handled below
+                    Instruction::TryStart { catch_offset, .. }
+                        if target == entry.end + catch_offset =>
+                    {
+                        continue;
+                    }
+                    _ => after,
+                };
+                updates.push((target, target_registers));
+            }
+
+            // Any instruction in a try block can throw an error, so the catch block starts with
+            // the registers that are assigned throughout the try block.
+            for try_block in self.try_blocks.iter() {
+                if try_block.function == entry.function
+                    && (try_block.start..try_block.catch_ip).contains(&entry.ip)
+                {
+                    let catch_registers = after_call.max(try_block.arg_register as u16 + 1);
+                    updates.push((try_block.catch_ip, catch_registers));
+                }
+            }
+
+            for (target, target_registers) in updates {
+                if let Some(target_index) = self.entry_index(target) {
+                    let assigned = &mut self.assigned_registers[target_index];
+                    let is_lower = match assigned {
+                        Some(assigned) => target_registers < *assigned,
+                        None => true,
+                    };
+                    if is_lower {
+                        *assigned = Some(target_registers);
+                        pending.push(target_index);
+                    }
+                }
+            }
+        }
+    }
+
+    // Checks that the registers read by each reachable instruction have been assigned
+    fn check_registers(&self) -> Result<(), ChunkVerifierError> {
+        for (entry, assigned) in self.entries.iter().zip(self.assigned_registers.iter()) {
+            let assigned = match assigned {
+                Some(assigned) => assigned,
+                None => continue,
+            };
+
+            for (start, count) in register_usage(&entry.instruction).reads {
+                let end = start + count;
+                if count > 0 && end > *assigned {
+                    let message = if count == 1 {
+                        format!("Register {} is read before being assigned", start)
+                    } else {
+                        format!(
+                            "Registers {}..{} are read before being assigned",
+                            start, end
+                        )
+                    };
+                    return Err(ChunkVerifierError {
+                        message,
+                        ip: entry.ip,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ConstantKind {
+    Float,
+    Int,
+    String,
+}
+
+fn expected_constants(bytes: &[u8], entry: &Entry) -> Vec<(ConstantIndex, ConstantKind)> {
+    use Instruction::*;
+
+    match &entry.instruction {
+        LoadFloat { constant, .. } => vec![(*constant, ConstantKind::Float)],
+        LoadInt { constant, .. } => vec![(*constant, ConstantKind::Int)],
+        LoadString { constant, .. }
+        | LoadNonLocal { constant, .. }
+        | Debug { constant, .. }
+        | Assert { constant, .. }
+        | AssertComparison { constant, .. }
+        | Access { key: constant, .. }
+        | CheckTypeAnnotation {
+            type_name: constant,
+            ..
+        } => vec![(*constant, ConstantKind::String)],
+        JumpTable {
+            key_type: JumpTableKey::Str,
+            entry_count,
+            table_ip,
+            ..
+        } => (0..*entry_count)
+            .map(|i| {
+                let start = table_ip + i * JumpTableKey::Str.entry_size();
+                let constant = ConstantIndex::from([
+                    bytes[start],
+                    bytes[start + 1],
+                    bytes[start + 2],
+                    bytes[start + 3],
+                ]);
+                (constant, ConstantKind::String)
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// Returns the number of registers that the instruction needs in its frame,
+// i.e. the highest register that it refers to, plus one
+pub(crate) fn register_count(instruction: &Instruction) -> usize {
+    let usage = register_usage(instruction);
+    let reads = usage
+        .reads
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(start, count)| (start + count) as usize);
+    let writes = usage.writes.iter().map(|register| *register as usize + 1);
+    reads.chain(writes).max().unwrap_or(0)
+}
+
+#[derive(Default)]
+struct RegisterUsage {
+    // Ranges of registers that are read by the instruction, as (start, count) pairs
+    reads: Vec<(u16, u16)>,
+    // Registers that are assigned by the instruction
+    writes: Vec<u8>,
+    // The frame base of a call, registers from the frame base onwards are removed by the call
+    call_frame_base: Option<u8>,
+}
+
+fn register_usage(instruction: &Instruction) -> RegisterUsage {
+    use Instruction::*;
+
+    macro_rules! usage {
+        (reads: [$($read:expr),*], writes: [$($write:expr),*]) => {
+            RegisterUsage {
+                reads: vec![$((*$read as u16, 1)),*],
+                writes: vec![$(*$write),*],
+                call_frame_base: None,
+            }
+        };
+    }
+
+    match instruction {
+        Error { .. }
+        | Jump { .. }
+        | JumpBack { .. }
+        | TryStart { .. }
+        | TryEnd
+        | Coverage { .. } => RegisterUsage::default(),
+        Copy { target, source } => usage!(reads: [source], writes: [target]),
+        SetEmpty { register }
+        | RangeFull { register }
+        | SetBool { register, .. }
+        | SetNumber { register, .. }
+        | LoadFloat { register, .. }
+        | LoadInt { register, .. }
+        | LoadString { register, .. }
+        | LoadNonLocal { register, .. }
+        | MakeMap { register, .. }
+        | SequenceStart { register, .. }
+        | StringStart { register, .. }
+        | SimpleFunction { register, .. }
+        | Function { register, .. } => usage!(reads: [], writes: [register]),
+        Import { register }
+        | StringFinish { register }
+        | SequenceToList { sequence: register }
+        | SequenceToTuple { sequence: register }
+        // The resumed value of a coroutine is placed in the yielded register
+        | Yield { register } => usage!(reads: [register], writes: [register]),
+        Return { register }
+        | Throw { register }
+        | Debug { register, .. }
+        | Assert { register, .. }
+        | JumpIf { register, .. }
+        | CheckType { register, .. }
+        | CheckSize { register, .. }
+        | CheckTypeAnnotation { register, .. }
+        | JumpTable { register, .. }
+        | IterNextQuiet {
+            iterator: register, ..
+        }
+        | MetaExport {
+            value: register, ..
+        } => usage!(reads: [register], writes: []),
+        ValueExport { name, value }
+        | SequencePush {
+            sequence: name,
+            value,
+        }
+        | StringPush {
+            register: name,
+            value,
+        }
+        | MetaExportNamed { name, value, .. }
+        | MetaInsert {
+            register: name,
+            value,
+            ..
+        } => usage!(reads: [name, value], writes: []),
+        MetaInsertNamed {
+            register,
+            value,
+            name,
+            ..
+        }
+        | SetIndex {
+            register,
+            index: name,
+            value,
+        }
+        | MapInsert {
+            register,
+            key: name,
+            value,
+        } => usage!(reads: [register, name, value], writes: []),
+        AssertComparison {
+            register, lhs, rhs, ..
+        } => usage!(reads: [register, lhs, rhs], writes: []),
+        Capture {
+            function, source, ..
+        } => usage!(reads: [function, source], writes: []),
+        MakeTempTuple {
+            register,
+            start,
+            count,
+        }
+        | MakeNum2 {
+            register,
+            element_register: start,
+            count,
+        }
+        | MakeNum4 {
+            register,
+            element_register: start,
+            count,
+        } => RegisterUsage {
+            reads: vec![(*start as u16, *count as u16)],
+            writes: vec![*register],
+            call_frame_base: None,
+        },
+        SequencePushN {
+            sequence,
+            start,
+            count,
+        } => RegisterUsage {
+            reads: vec![(*sequence as u16, 1), (*start as u16, *count as u16)],
+            ..Default::default()
+        },
+        RangeTo { register, end }
+        | RangeToInclusive { register, end }
+        | RangeFrom {
+            register,
+            start: end,
+        }
+        | MakeIterator {
+            register,
+            iterable: end,
+        }
+        | Negate {
+            register,
+            value: end,
+        }
+        | Not {
+            register,
+            value: end,
+        }
+        | Size {
+            register,
+            value: end,
+        }
+        | IsTuple {
+            register,
+            value: end,
+        }
+        | IsList {
+            register,
+            value: end,
+        }
+        | IterNext {
+            register,
+            iterator: end,
+            ..
+        }
+        | IterNextTemp {
+            register,
+            iterator: end,
+            ..
+        }
+        | TempIndex {
+            register,
+            value: end,
+            ..
+        }
+        | SliceFrom {
+            register,
+            value: end,
+            ..
+        }
+        | SliceTo {
+            register,
+            value: end,
+            ..
+        }
+        | Access {
+            register,
+            value: end,
+            ..
+        } => usage!(reads: [end], writes: [register]),
+        AddU8 { register, lhs, .. }
+        | SubtractU8 { register, lhs, .. }
+        | LessU8 { register, lhs, .. }
+        | LessOrEqualU8 { register, lhs, .. }
+        | GreaterU8 { register, lhs, .. }
+        | GreaterOrEqualU8 { register, lhs, .. }
+        | EqualU8 { register, lhs, .. }
+        | NotEqualU8 { register, lhs, .. } => {
+            usage!(reads: [lhs], writes: [register])
+        }
+        Range {
+            register,
+            start: lhs,
+            end: rhs,
+        }
+        | RangeInclusive {
+            register,
+            start: lhs,
+            end: rhs,
+        }
+        | Add { register, lhs, rhs }
+        | Subtract { register, lhs, rhs }
+        | Multiply { register, lhs, rhs }
+        | Divide { register, lhs, rhs }
+        | Modulo { register, lhs, rhs }
+        | Less { register, lhs, rhs }
+        | LessOrEqual { register, lhs, rhs }
+        | Greater { register, lhs, rhs }
+        | GreaterOrEqual { register, lhs, rhs }
+        | Equal { register, lhs, rhs }
+        | NotEqual { register, lhs, rhs }
+        | In { register, lhs, rhs }
+        | Index {
+            register,
+            value: lhs,
+            index: rhs,
+        }
+        | AccessString {
+            register,
+            value: lhs,
+            key: rhs,
+        } => usage!(reads: [lhs, rhs], writes: [register]),
+        Call {
+            result,
+            function,
+            frame_base,
+            arg_count,
+        }
+        | TailCall {
+            result,
+            function,
+            frame_base,
+            arg_count,
+        } => RegisterUsage {
+            // The call's arguments follow the frame base
+            reads: vec![
+                (*function as u16, 1),
+                (*frame_base as u16 + 1, *arg_count as u16),
+            ],
+            writes: vec![*result],
+            call_frame_base: Some(*frame_base),
+        },
+        CallInstance {
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance,
+        }
+        | TailCallInstance {
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance,
+        } => RegisterUsage {
+            reads: vec![
+                (*function as u16, 1),
+                (*instance as u16, 1),
+                (*frame_base as u16 + 1, *arg_count as u16),
+            ],
+            writes: vec![*result],
+            call_frame_base: Some(*frame_base),
+        },
+    }
+}
@@ -1,12 +1,12 @@
 use {
-    crate::{DebugInfo, FunctionFlags, Op, TypeId},
+    crate::{peephole, DebugInfo, FunctionFlags, JumpTableKey, Op, TypeId, MAX_SIZE_HINT},
     koto_parser::{
         AssignOp, AssignTarget, Ast, AstBinaryOp, AstFor, AstIf, AstIndex, AstNode, AstTry,
         AstUnaryOp, ConstantIndex, Function, ImportItemNode, LookupNode, MapKey, MatchArm,
         MetaKeyId, Node, Scope, Span, StringNode, SwitchArm,
     },
     smallvec::SmallVec,
-    std::{convert::TryFrom, error, fmt},
+    std::{collections::HashSet, convert::TryFrom, error, fmt},
 };
 
 /// The error type used to report errors during compilation
@@ -16,6 +16,31 @@ pub struct CompilerError {
     pub message: String,
     /// The span in the source where the error occurred
     pub span: Span,
+    /// Errors that were found in the script after this error
+    ///
+    /// When an expression in a script's main block fails to compile, the compiler continues
+    /// with the following expressions, so that independent errors can be reported together.
+    /// Only the first error's `message` and `span` are displayed, with any further errors
+    /// being listed here in the order that they were found.
+    pub additional_errors: Vec<CompilerError>,
+}
+
+impl CompilerError {
+    /// Returns an iterator over the error, followed by any additional errors
+    pub fn errors(&self) -> impl Iterator<Item = &CompilerError> {
+        std::iter::once(self).chain(self.additional_errors.iter())
+    }
+
+    // Combines a series of errors into a single error, with the first error being reported
+    fn from_errors(mut errors: Vec<CompilerError>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+
+        let mut result = errors.remove(0);
+        result.additional_errors.extend(errors);
+        Some(result)
+    }
 }
 
 impl fmt::Display for CompilerError {
@@ -26,11 +51,30 @@ impl fmt::Display for CompilerError {
 
 impl error::Error for CompilerError {}
 
+/// A warning produced during compilation
+///
+/// Warnings don't prevent a script from being compiled, but point out code that is likely to be
+/// a mistake.
+#[derive(Clone, Debug)]
+pub struct CompilerWarning {
+    /// The warning's message
+    pub message: String,
+    /// The span in the source that the warning refers to
+    pub span: Span,
+}
+
+impl fmt::Display for CompilerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", &self.message)
+    }
+}
+
 macro_rules! make_compiler_error {
     ($span:expr, $message:expr) => {{
         CompilerError {
             message: $message,
             span: $span,
+            additional_errors: Vec::new(),
         }
     }};
 }
@@ -86,6 +130,28 @@ struct Frame {
     temporary_base: u8,
     temporary_count: u8,
     last_op: Option<Op>, // used to decide if an additional return instruction is needed
+    // The position of the last op, used when converting a returned call into a tail call
+    last_op_ip: usize,
+    // True when calls in return position can be converted into tail calls,
+    // i.e. when compiling a function that isn't a generator.
+    allow_tail_calls: bool,
+    // Functions that are defined later in the current block,
+    // which can be referred to by functions that are defined earlier in the block.
+    pending_forward_definitions: Vec<ConstantIndex>,
+    // Captures of forward definitions that will be updated once the definition is assigned,
+    // (definition id, function register, capture index)
+    forward_captures: Vec<(ConstantIndex, u8, u8)>,
+    // Set while compiling a function that refers to forward definitions,
+    // the function is copied into the register so that its captures can be updated later.
+    forward_capture_register: Option<u8>,
+    // The annotated return type of the frame's function, checked before returning
+    return_type: Option<ConstantIndex>,
+    // Locals that have been assigned but not yet accessed, along with the span of their first
+    // assignment. Any remaining entries produce warnings once the frame has been compiled.
+    unused_locals: Vec<(ConstantIndex, Span)>,
+    // The span of the frame's function, used for all of the frame's instructions when debug info
+    // is being stripped.
+    span: Span,
 }
 
 impl Frame {
@@ -151,6 +217,26 @@ impl Frame {
             .map(|position| position as u8)
     }
 
+    // Returns the register assigned to a local, marking the local as having been accessed
+    fn access_local_register(&mut self, index: ConstantIndex) -> Option<u8> {
+        let result = self.get_local_assigned_register(index);
+        if result.is_some() {
+            self.mark_local_as_accessed(index);
+        }
+        result
+    }
+
+    fn mark_local_as_accessed(&mut self, index: ConstantIndex) {
+        self.unused_locals.retain(|(unused, _)| *unused != index);
+    }
+
+    fn is_local_register_reserved(&self, register: u8) -> bool {
+        matches!(
+            self.local_registers.get(register as usize),
+            Some(LocalRegister::Reserved(_, _))
+        )
+    }
+
     fn get_local_reserved_register(&self, index: ConstantIndex) -> Option<u8> {
         self.local_registers
             .iter()
@@ -346,10 +432,49 @@ impl CompileResult {
 type CompileNodeResult = Result<Option<CompileResult>, CompilerError>;
 
 /// The settings used by the [Compiler]
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CompilerSettings {
     /// Causes all top level identifiers to be exported
     pub repl_mode: bool,
+    /// Enables a peephole optimization pass over the compiled bytecode
+    ///
+    /// Redundant instructions are removed, and jumps to other jumps are redirected to their
+    /// final target. Defaults to true.
+    ///
+    /// When compiling with the [Loader](crate::Loader), constant expressions in module-level
+    /// assignments are also evaluated at compile time, e.g. `x = 60 * 60` is compiled as `x = 3600`.
+    pub optimize: bool,
+    /// Reduces the debug info that's included with the compiled bytecode
+    ///
+    /// Instead of each expression having its own source span, all instructions in a function
+    /// share the function's span, so errors only refer to the function where they occurred.
+    ///
+    /// When compiling with the [Loader](crate::Loader), the script's source is also omitted
+    /// from the chunk, along with the expression strings that are displayed by `debug` and
+    /// failed assertions.
+    pub strip_debug_info: bool,
+    /// Instruments the compiled bytecode with coverage points
+    ///
+    /// A coverage point is added for each expression in a block, and for each branch body that
+    /// isn't a block (e.g. in `if x then a else b`), with the runtime counting how many times
+    /// each point is reached.
+    pub coverage: bool,
+    /// Causes compilation to fail if any warnings are produced
+    ///
+    /// The first warning is returned as a [CompilerError].
+    pub warnings_as_errors: bool,
+}
+
+impl Default for CompilerSettings {
+    fn default() -> Self {
+        Self {
+            repl_mode: false,
+            optimize: true,
+            strip_debug_info: false,
+            coverage: false,
+            warnings_as_errors: false,
+        }
+    }
 }
 
 /// The compiler used by the Koto language
@@ -359,27 +484,72 @@ pub struct Compiler {
     debug_info: DebugInfo,
     frame_stack: Vec<Frame>,
     span_stack: Vec<Span>,
+    warnings: Vec<CompilerWarning>,
+    // Errors from main block expressions that failed to compile, see compile_block_expression
+    errors: Vec<CompilerError>,
+    // The name that the next compiled function is being assigned to, see compile_function
+    function_name: Option<ConstantIndex>,
     settings: CompilerSettings,
 }
 
 impl Compiler {
     /// Compiles an [Ast]
     ///
-    /// Returns compiled bytecode along with corresponding debug information
+    /// If the Ast's entry point is a single expression rather than a main block
+    /// (see [Parser::parse_expression](koto_parser::Parser::parse_expression)),
+    /// then the compiled bytecode returns the expression's result.
+    ///
+    /// Returns compiled bytecode along with corresponding debug information, and any warnings
+    /// that were produced during compilation.
+    ///
+    /// If errors are found in more than one of the main block's expressions, then the first error
+    /// is returned with the others available in [CompilerError::additional_errors].
     pub fn compile(
         ast: &Ast,
         settings: CompilerSettings,
-    ) -> Result<(Vec<u8>, DebugInfo), CompilerError> {
+    ) -> Result<(Vec<u8>, DebugInfo, Vec<CompilerWarning>), CompilerError> {
         let mut compiler = Compiler {
             settings,
             ..Default::default()
         };
 
-        if let Some(entry_point) = ast.entry_point() {
-            compiler.compile_node(ResultRegister::None, entry_point, ast)?;
+        let result = match ast.entry_point() {
+            Some(entry_point) if matches!(entry_point.node, Node::MainBlock { .. }) => compiler
+                .compile_node(ResultRegister::None, entry_point, ast)
+                .map(|_| ()),
+            Some(_) => {
+                // The Ast was produced by Parser::parse_expression, so the expression is compiled
+                // in a frame of its own, with the expression's result being returned.
+                compiler.compile_frame(0, &[ast.entry_point_index()], &[], &[], ast, None)
+            }
+            None => Ok(()),
+        };
+
+        let mut errors = std::mem::take(&mut compiler.errors);
+        if let Err(error) = result {
+            errors.push(error);
+        }
+        if let Some(error) = CompilerError::from_errors(errors) {
+            return Err(error);
         }
 
-        Ok((compiler.bytes, compiler.debug_info))
+        if compiler.settings.warnings_as_errors {
+            if let Some(warning) = compiler.warnings.first() {
+                return Err(CompilerError {
+                    message: warning.message.clone(),
+                    span: warning.span,
+                    additional_errors: Vec::new(),
+                });
+            }
+        }
+
+        let (bytes, debug_info) = if compiler.settings.optimize {
+            peephole::optimize(compiler.bytes, compiler.debug_info)
+        } else {
+            (compiler.bytes, compiler.debug_info)
+        };
+
+        Ok((bytes, debug_info, compiler.warnings))
     }
 
     fn compile_node(
@@ -442,6 +612,7 @@ impl Compiler {
                         LoadFloat,
                         LoadFloat16,
                         LoadFloat24,
+                        LoadFloat32,
                     );
                 }
                 result
@@ -455,6 +626,7 @@ impl Compiler {
                         LoadInt,
                         LoadInt16,
                         LoadInt24,
+                        LoadInt32,
                     );
                 }
                 result
@@ -549,7 +721,7 @@ impl Compiler {
                 result
             }
             Node::MainBlock { body, local_count } => {
-                self.compile_frame(*local_count as u8, body, &[], &[], ast, true)?;
+                self.compile_frame(*local_count as u8, body, &[], &[], ast, None)?;
                 None
             }
             Node::Block(expressions) => self.compile_block(result_register, expressions, ast)?,
@@ -560,6 +732,10 @@ impl Compiler {
                 self.compile_make_temp_tuple(result_register, elements, ast)?
             }
             Node::Function(f) => self.compile_function(result_register, f, ast)?,
+            Node::Decorated {
+                decorators,
+                function,
+            } => self.compile_decorated_function(result_register, decorators, *function, ast)?,
             Node::NamedCall { id, args } => {
                 self.compile_named_call(result_register, *id, args, None, ast)?
             }
@@ -587,9 +763,18 @@ impl Compiler {
             }
             Node::Switch(arms) => self.compile_switch(result_register, arms, ast)?,
             Node::Ellipsis(_) => {
-                return compiler_error!(self, "Ellipsis found outside of match patterns")
+                return compiler_error!(
+                    self,
+                    "Ellipsis found outside of match patterns or multi-assignment"
+                )
             }
             Node::Wildcard => None,
+            Node::Error => {
+                return compiler_error!(
+                    self,
+                    "Unable to compile an expression that failed to parse"
+                )
+            }
             Node::For(ast_for) => self.compile_for(result_register, ast_for, ast)?,
             Node::While { condition, body } => {
                 self.compile_loop(result_register, Some((*condition, false)), *body, ast)?
@@ -620,12 +805,14 @@ impl Compiler {
             Node::Return(None) => match self.get_result_register(result_register)? {
                 Some(result) => {
                     self.push_op(SetEmpty, &[result.register]);
+                    self.compile_return_type_check(result.register);
                     self.push_op(Return, &[result.register]);
                     Some(result)
                 }
                 None => {
                     let register = self.push_register()?;
                     self.push_op(SetEmpty, &[register]);
+                    self.compile_return_type_check(register);
                     self.push_op(Return, &[register]);
                     self.pop_register()?;
                     None
@@ -636,9 +823,11 @@ impl Compiler {
                     .compile_node(ResultRegister::Any, ast.node(*expression), ast)?
                     .unwrap();
 
+                self.compile_return_type_check(expression_register.register);
+
                 match result_register {
                     ResultRegister::Any => {
-                        self.push_op(Return, &[expression_register.register]);
+                        self.push_return_op(expression_register.register, true);
                         Some(expression_register)
                     }
                     ResultRegister::Fixed(result) => {
@@ -650,7 +839,7 @@ impl Compiler {
                         Some(CompileResult::with_assigned(result))
                     }
                     ResultRegister::None => {
-                        self.push_op(Return, &[expression_register.register]);
+                        self.push_return_op(expression_register.register, true);
                         if expression_register.is_temporary {
                             self.pop_register()?;
                         }
@@ -661,17 +850,23 @@ impl Compiler {
             Node::Yield(expression) => {
                 let result = self.get_result_register(result_register)?;
 
-                let expression_register = self
-                    .compile_node(ResultRegister::Any, ast.node(*expression), ast)?
-                    .unwrap();
+                // When a coroutine is resumed, the resumed value is placed in the yield register,
+                // so the yielded value is compiled into the result register (or a temporary
+                // register if the result isn't used) rather than into a local value's register.
+                let yield_register = match result {
+                    Some(result) => result.register,
+                    None => self.push_register()?,
+                };
 
-                self.push_op(Yield, &[expression_register.register]);
+                self.compile_node(
+                    ResultRegister::Fixed(yield_register),
+                    ast.node(*expression),
+                    ast,
+                )?;
 
-                if let Some(result) = result {
-                    self.push_op(Copy, &[result.register, expression_register.register]);
-                }
+                self.push_op(Yield, &[yield_register]);
 
-                if expression_register.is_temporary {
+                if result.is_none() {
                     self.pop_register()?;
                 }
 
@@ -716,6 +911,10 @@ impl Compiler {
 
                 result
             }
+            Node::Assert {
+                expression_string,
+                expression,
+            } => self.compile_assert(result_register, *expression_string, *expression, ast)?,
             Node::Meta(_, _) => {
                 // Meta nodes are currently only compiled in the context of an export assignment,
                 // see compile_assign().
@@ -745,7 +944,7 @@ impl Compiler {
         args: &[AstIndex],
         captures: &[ConstantIndex],
         ast: &Ast,
-        allow_implicit_return: bool,
+        function: Option<&Function>,
     ) -> Result<(), CompilerError> {
         self.frame_stack.push(Frame::new(
             local_count,
@@ -753,6 +952,35 @@ impl Compiler {
             captures,
         ));
 
+        // Expressions compiled with Parser::parse_expression don't have a parent node,
+        // so the frame uses the span of its first expression.
+        let frame_span = self.span_stack.last().or_else(|| {
+            expressions
+                .first()
+                .map(|first| ast.span(ast.node(*first).span))
+        });
+        if let Some(frame_span) = frame_span {
+            self.frame_mut().span = *frame_span;
+        }
+
+        // check annotated arg types
+        if let Some(function) = function {
+            for (arg_index, arg_type) in function.arg_types.iter().enumerate() {
+                if let Some(arg_type) = arg_type {
+                    let arg_node = ast.node(args[arg_index]);
+                    self.span_stack.push(*ast.span(arg_node.span));
+                    self.push_op(Op::CheckTypeAnnotation, &[arg_index as u8]);
+                    self.push_bytes(&arg_type.bytes());
+                    self.span_stack.pop();
+                }
+            }
+
+            if !function.is_generator {
+                self.frame_mut().return_type = function.return_type;
+                self.frame_mut().allow_tail_calls = true;
+            }
+        }
+
         // unpack nested args
         for (arg_index, arg) in args.iter().enumerate() {
             match &ast.node(*arg).node {
@@ -772,6 +1000,9 @@ impl Compiler {
             }
         }
 
+        // Generators don't have implicit return values
+        let allow_implicit_return = !matches!(function, Some(function) if function.is_generator);
+
         let result_register = if allow_implicit_return {
             ResultRegister::Any
         } else {
@@ -782,7 +1013,14 @@ impl Compiler {
 
         if let Some(result) = block_result {
             if self.frame().last_op != Some(Op::Return) {
-                self.push_op_without_span(Op::Return, &[result.register]);
+                if let Some(last_expression) = expressions.last() {
+                    // Type errors for the implicit return value refer to the last expression
+                    let last_node = ast.node(*last_expression);
+                    self.span_stack.push(*ast.span(last_node.span));
+                    self.compile_return_type_check(result.register);
+                    self.span_stack.pop();
+                }
+                self.push_return_op(result.register, false);
             }
             if result.is_temporary {
                 self.pop_register()?;
@@ -794,6 +1032,7 @@ impl Compiler {
             self.pop_register()?;
         }
 
+        self.check_for_unused_locals(ast);
         self.frame_stack.pop();
 
         Ok(())
@@ -914,6 +1153,9 @@ impl Compiler {
     ) -> CompileNodeResult {
         use Op::SetEmpty;
 
+        self.check_for_discarded_values(expressions, ast);
+        self.check_for_unreachable_code(expressions, ast);
+
         let result = match expressions {
             [] => match self.get_result_register(result_register)? {
                 Some(result) => {
@@ -927,19 +1169,382 @@ impl Compiler {
                     Some(CompileResult::with_temporary(register))
                 }
             },
-            [expression] => self.compile_node(result_register, ast.node(*expression), ast)?,
+            [expression] => self.compile_block_expression(result_register, *expression, ast)?,
+            _ if self.block_has_forward_definitions(expressions, ast) => {
+                self.compile_block_with_forward_definitions(result_register, expressions, ast)?
+            }
             [expressions @ .., last_expression] => {
                 for expression in expressions.iter() {
-                    self.compile_node(ResultRegister::None, ast.node(*expression), ast)?;
+                    self.compile_block_expression(ResultRegister::None, *expression, ast)?;
                 }
 
-                self.compile_node(result_register, ast.node(*last_expression), ast)?
+                self.compile_block_expression(result_register, *last_expression, ast)?
             }
         };
 
         Ok(result)
     }
 
+    // Compiles one of a block's expressions
+    //
+    // Errors in the main block's expressions are recorded rather than returned, with the
+    // compiler's state being reset so that compilation can continue with the next expression.
+    // The recorded errors are then returned together once the main block has been compiled.
+    fn compile_block_expression(
+        &mut self,
+        result_register: ResultRegister,
+        expression: AstIndex,
+        ast: &Ast,
+    ) -> CompileNodeResult {
+        let node = ast.node(expression);
+        self.compile_coverage_point(node, ast)?;
+
+        // The main block's expressions are compiled in the first frame, with only the
+        // main block's span on the span stack.
+        if self.frame_stack.len() != 1 || self.span_stack.len() > 1 {
+            return self.compile_node(result_register, node, ast);
+        }
+
+        let span_count = self.span_stack.len();
+        let frame = self.frame();
+        let register_count = frame.register_stack.len();
+        let loop_count = frame.loop_stack.len();
+
+        match self.compile_node(result_register, node, ast) {
+            Ok(result) => Ok(result),
+            Err(error) => {
+                self.frame_stack.truncate(1);
+                self.span_stack.truncate(span_count);
+                self.frame_mut().loop_stack.truncate(loop_count);
+                self.frame_mut().forward_capture_register = None;
+                // If the frame can't be restored then the error is returned immediately
+                if self.truncate_register_stack(register_count).is_err() {
+                    return Err(error);
+                }
+                self.errors.push(error);
+
+                match result_register {
+                    ResultRegister::None => Ok(None),
+                    _ => self.get_result_register(result_register),
+                }
+            }
+        }
+    }
+
+    // Adds a warning for each expression in a block that has its value discarded without having
+    // any effect, e.g. a function that was defined without being assigned or called.
+    fn check_for_discarded_values(&mut self, expressions: &[AstIndex], ast: &Ast) {
+        if self.settings.repl_mode {
+            return;
+        }
+
+        if let [discarded @ .., _] = expressions {
+            for expression in discarded {
+                let node = ast.node(*expression);
+                if let Some(message) = Self::discarded_value_message(&node.node, ast) {
+                    self.warnings.push(CompilerWarning {
+                        message,
+                        span: *ast.span(node.span),
+                    });
+                }
+            }
+        }
+    }
+
+    // Adds a warning for the first expression in a block that follows an expression that
+    // unconditionally leaves the block, e.g. `return` or `break`.
+    fn check_for_unreachable_code(&mut self, expressions: &[AstIndex], ast: &Ast) {
+        let exit_position = expressions
+            .iter()
+            .position(|expression| is_unconditional_exit(&ast.node(*expression).node));
+
+        if let Some(unreachable) = exit_position.and_then(|position| expressions.get(position + 1))
+        {
+            self.warnings.push(CompilerWarning {
+                message: "Unreachable code".into(),
+                span: *ast.span(ast.node(*unreachable).span),
+            });
+        }
+    }
+
+    // Adds a warning for match arms that follow an arm that matches any value
+    fn check_for_unreachable_match_arms(&mut self, arms: &[MatchArm], ast: &Ast) {
+        let catch_all_position = arms.iter().position(|arm| is_catch_all_match_arm(arm, ast));
+
+        if let Some(unreachable) = catch_all_position.and_then(|position| arms.get(position + 1)) {
+            let first_node = unreachable
+                .patterns
+                .first()
+                .unwrap_or(&unreachable.expression);
+            self.warnings.push(CompilerWarning {
+                message: "Unreachable match arm, the previous arm matches all values".into(),
+                span: *ast.span(ast.node(*first_node).span),
+            });
+        }
+    }
+
+    // Called when a local is assigned for the first time in the current frame
+    fn add_new_local(&mut self, id: ConstantIndex, span: Span, ast: &Ast) {
+        self.check_for_shadowed_value(id, span, ast);
+
+        let frame = self.frame_mut();
+        // Functions that refer to forward definitions have already accessed the local
+        let has_forward_capture = frame
+            .forward_captures
+            .iter()
+            .any(|(capture_id, _, _)| *capture_id == id);
+        if !has_forward_capture {
+            frame.unused_locals.push((id, span));
+        }
+    }
+
+    // Adds a warning if a function's local has the same name as a local in an enclosing function
+    //
+    // Values from enclosing functions are captured when they're accessed, but assigning to a
+    // value with the same name creates a new local, which can be surprising.
+    fn check_for_shadowed_value(&mut self, id: ConstantIndex, span: Span, ast: &Ast) {
+        let (_, enclosing_frames) = match self.frame_stack.split_last() {
+            Some(frames) => frames,
+            None => return,
+        };
+
+        let is_shadowed = enclosing_frames.iter().any(|frame| {
+            frame.get_local_assigned_register(id).is_some()
+                || frame.get_local_reserved_register(id).is_some()
+        });
+
+        if is_shadowed {
+            self.warnings.push(CompilerWarning {
+                message: format!(
+                    "'{}' shadows a value with the same name in an enclosing function",
+                    ast.constants().get_str(id)
+                ),
+                span,
+            });
+        }
+    }
+
+    // Adds a warning for each local in the current frame that was assigned without being accessed
+    fn check_for_unused_locals(&mut self, ast: &Ast) {
+        let unused_locals = std::mem::take(&mut self.frame_mut().unused_locals);
+
+        for (id, span) in unused_locals {
+            self.warnings.push(CompilerWarning {
+                message: format!(
+                    "'{}' is assigned but never used",
+                    ast.constants().get_str(id)
+                ),
+                span,
+            });
+        }
+    }
+
+    fn discarded_value_message(node: &Node, ast: &Ast) -> Option<String> {
+        match node {
+            Node::Nested(nested) => Self::discarded_value_message(&ast.node(*nested).node, ast),
+            Node::Function(_) => {
+                Some("Function has no effect, did you mean to call or assign it?".into())
+            }
+            Node::Id(id) => {
+                let id = ast.constants().get_str(*id);
+                Some(format!(
+                    "'{}' has no effect, did you mean to call it with '{}()'?",
+                    id, id
+                ))
+            }
+            Node::Str(string)
+                if string
+                    .nodes
+                    .iter()
+                    .all(|node| matches!(node, StringNode::Literal(_))) =>
+            {
+                Some("Value has no effect".into())
+            }
+            Node::Empty
+            | Node::BoolTrue
+            | Node::BoolFalse
+            | Node::Number0
+            | Node::Number1
+            | Node::Int(_)
+            | Node::Float(_) => Some("Value has no effect".into()),
+            _ => None,
+        }
+    }
+
+    // Returns the assigned id and function if the expression assigns a function to a local,
+    // e.g. `f = |x| x * x`
+    fn local_function_definition<'a>(
+        &self,
+        expression: AstIndex,
+        ast: &'a Ast,
+    ) -> Option<(ConstantIndex, &'a Function)> {
+        match &ast.node(expression).node {
+            Node::Assign {
+                target,
+                op: AssignOp::Equal,
+                expression,
+            } if self.scope_for_assign_target(target) == Scope::Local => {
+                let id = match &ast.node(target.target_index).node {
+                    Node::Id(id) => *id,
+                    _ => return None,
+                };
+
+                let function = match &ast.node(*expression).node {
+                    Node::Decorated { function, .. } => &ast.node(*function).node,
+                    other => other,
+                };
+
+                match function {
+                    Node::Function(function) => Some((id, function)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the ids of functions defined in the block that haven't been assigned yet,
+    // along with flags for each expression indicating if the expression defines a function
+    // that refers to a function defined later in the block.
+    fn collect_forward_definitions(
+        &self,
+        expressions: &[AstIndex],
+        ast: &Ast,
+    ) -> (Vec<ConstantIndex>, Vec<bool>) {
+        let definitions = expressions
+            .iter()
+            .map(|expression| self.local_function_definition(*expression, ast))
+            .collect::<Vec<_>>();
+
+        let mut forward_ids = Vec::new();
+        for (id, _) in definitions.iter().flatten() {
+            if !forward_ids.contains(id) && self.frame().get_local_assigned_register(*id).is_none()
+            {
+                forward_ids.push(*id);
+            }
+        }
+
+        let refers_to_forward_definition = definitions
+            .iter()
+            .enumerate()
+            .map(|(i, definition)| match definition {
+                Some((_, function)) => function.accessed_non_locals.iter().any(|accessed| {
+                    forward_ids.contains(accessed)
+                        && !definitions[..=i]
+                            .iter()
+                            .flatten()
+                            .any(|(id, _)| id == accessed)
+                }),
+                None => false,
+            })
+            .collect();
+
+        (forward_ids, refers_to_forward_definition)
+    }
+
+    fn block_has_forward_definitions(&self, expressions: &[AstIndex], ast: &Ast) -> bool {
+        let (_, refers_to_forward_definition) = self.collect_forward_definitions(expressions, ast);
+        refers_to_forward_definition.contains(&true)
+    }
+
+    // Compiles a block containing functions that refer to functions defined later in the block
+    //
+    // e.g.
+    //   is_even = |n| if n == 0 then true else is_odd n - 1
+    //   is_odd = |n| if n == 0 then false else is_even n - 1
+    //
+    // Functions that refer to later definitions are copied into registers that are kept for the
+    // duration of the block, and the forward definitions are captured once they're assigned.
+    fn compile_block_with_forward_definitions(
+        &mut self,
+        result_register: ResultRegister,
+        expressions: &[AstIndex],
+        ast: &Ast,
+    ) -> CompileNodeResult {
+        let (forward_ids, refers_to_forward_definition) =
+            self.collect_forward_definitions(expressions, ast);
+
+        let result = self.get_result_register(result_register)?;
+        let stack_count = self.frame().register_stack.len();
+
+        let mut forward_capture_registers = Vec::new();
+        for refers_to_forward in refers_to_forward_definition.iter() {
+            if *refers_to_forward {
+                forward_capture_registers.push(self.push_register()?);
+            }
+        }
+        let mut forward_capture_registers = forward_capture_registers.into_iter();
+
+        let outer_definitions = std::mem::replace(
+            &mut self.frame_mut().pending_forward_definitions,
+            forward_ids,
+        );
+        let outer_captures = std::mem::take(&mut self.frame_mut().forward_captures);
+
+        for (i, expression) in expressions.iter().enumerate() {
+            if refers_to_forward_definition[i] {
+                self.frame_mut().forward_capture_register = forward_capture_registers.next();
+            }
+
+            let expression_result_register = match result {
+                Some(result) if i == expressions.len() - 1 => {
+                    ResultRegister::Fixed(result.register)
+                }
+                _ => ResultRegister::None,
+            };
+
+            self.compile_block_expression(expression_result_register, *expression, ast)?;
+
+            self.frame_mut().forward_capture_register = None;
+
+            if let Some((id, _)) = self.local_function_definition(*expression, ast) {
+                self.compile_forward_captures(id);
+            }
+        }
+
+        let frame = self.frame_mut();
+        frame.pending_forward_definitions = outer_definitions;
+        frame.forward_captures = outer_captures;
+
+        self.truncate_register_stack(stack_count)?;
+
+        Ok(result)
+    }
+
+    fn compile_return_type_check(&mut self, register: u8) {
+        if let Some(return_type) = self.frame().return_type {
+            self.push_op(Op::CheckTypeAnnotation, &[register]);
+            self.push_bytes(&return_type.bytes());
+        }
+    }
+
+    // Updates the captures of functions that refer to a forward definition that has now been
+    // assigned.
+    fn compile_forward_captures(&mut self, id: ConstantIndex) {
+        let frame = self.frame_mut();
+        frame
+            .pending_forward_definitions
+            .retain(|pending| *pending != id);
+
+        let definition_register = match frame.get_local_assigned_register(id) {
+            Some(register) => register,
+            None => return,
+        };
+
+        let (captures, remaining): (Vec<_>, Vec<_>) = frame
+            .forward_captures
+            .drain(..)
+            .partition(|(capture_id, _, _)| *capture_id == id);
+        frame.forward_captures = remaining;
+
+        for (_, function_register, capture_index) in captures {
+            self.push_op(
+                Op::Capture,
+                &[function_register, capture_index, definition_register],
+            );
+        }
+    }
+
     fn scope_for_assign_target(&self, target: &AssignTarget) -> Scope {
         if self.settings.repl_mode && self.frame_stack.len() == 1 {
             Scope::Export
@@ -955,7 +1560,16 @@ impl Compiler {
     ) -> Result<Option<u8>, CompilerError> {
         let result = match self.scope_for_assign_target(target) {
             Scope::Local => match &ast.node(target.target_index).node {
-                Node::Id(constant_index) => Some(self.reserve_local_register(*constant_index)?),
+                Node::Id(constant_index) | Node::Ellipsis(Some(constant_index)) => {
+                    let frame = self.frame();
+                    if frame.get_local_assigned_register(*constant_index).is_none()
+                        && frame.get_local_reserved_register(*constant_index).is_none()
+                    {
+                        let span = *ast.span(ast.node(target.target_index).span);
+                        self.add_new_local(*constant_index, span, ast);
+                    }
+                    Some(self.reserve_local_register(*constant_index)?)
+                }
                 Node::Lookup(_) | Node::Wildcard => None,
                 unexpected => {
                     return compiler_error!(self, "Expected Id in AST, found {}", unexpected)
@@ -985,7 +1599,13 @@ impl Compiler {
 
         let value_register = match op {
             AssignOp::Equal => {
-                self.compile_node(value_result_register, ast.node(expression), ast)?
+                let expression_node = ast.node(expression);
+                if let (Node::Function(_), Node::Id(id)) =
+                    (&expression_node.node, &ast.node(target.target_index).node)
+                {
+                    self.function_name = Some(*id);
+                }
+                self.compile_node(value_result_register, expression_node, ast)?
             }
             AssignOp::Add => self.compile_binary_op(
                 value_result_register,
@@ -1087,11 +1707,13 @@ impl Compiler {
     ) -> CompileNodeResult {
         use Op::*;
 
-        if targets.len() >= u8::MAX as usize {
+        // The targets are assigned with signed 8 bit indices
+        if targets.len() > i8::MAX as usize {
             return compiler_error!(
                 self,
-                "Too many targets in multi-assignment, ({})",
-                targets.len()
+                "Too many targets in multi-assignment, {} is greater than the maximum of {}",
+                targets.len(),
+                i8::MAX
             );
         }
 
@@ -1107,21 +1729,60 @@ impl Compiler {
             .compile_node(ResultRegister::Any, ast.node(expression), ast)?
             .unwrap();
 
+        // If the first target is an ellipsis then the remaining targets are indexed from the end
+        // e.g. `rest..., x, y = foo`
+        //   - rest is assigned to `foo[..-2]`
+        //   - x is assigned to `foo[-2]`, y is assigned to `foo[-1]`
+        let index_from_end = matches!(
+            targets
+                .first()
+                .map(|target| &ast.node(target.target_index).node),
+            Some(Node::Ellipsis(_))
+        );
+
         for (i, (target, target_register)) in
             targets.iter().zip(target_registers.iter()).enumerate()
         {
+            let index = if index_from_end {
+                (i as i8 - targets.len() as i8) as u8
+            } else {
+                i as u8
+            };
+
             match &ast.node(target.target_index).node {
+                Node::Ellipsis(Some(id_index)) => {
+                    // The parser only allows ellipses in first or last position
+                    let (slice_op, slice_index) = if index_from_end {
+                        (SliceTo, -(targets.len() as i8 - 1) as u8)
+                    } else {
+                        (SliceFrom, index)
+                    };
+
+                    match (target_register, self.scope_for_assign_target(target)) {
+                        (Some(target_register), Scope::Local) => {
+                            self.push_op(slice_op, &[*target_register, rhs.register, slice_index]);
+                            self.commit_local_register(*target_register)?;
+                        }
+                        (None, Scope::Export) => {
+                            let slice_register = self.push_register()?;
+                            self.push_op(slice_op, &[slice_register, rhs.register, slice_index]);
+                            self.compile_value_export(*id_index, slice_register)?;
+                            self.pop_register()?; // slice_register
+                        }
+                        _ => unreachable!(),
+                    }
+                }
                 Node::Id(id_index) => {
                     match (target_register, self.scope_for_assign_target(target)) {
                         (Some(target_register), Scope::Local) => {
-                            self.push_op(TempIndex, &[*target_register, rhs.register, i as u8]);
+                            self.push_op(TempIndex, &[*target_register, rhs.register, index]);
                             // The register was reserved before the RHS was compiled, and now it
                             // needs to be committed.
                             self.commit_local_register(*target_register)?;
                         }
                         (None, Scope::Export) => {
                             let index_register = self.push_register()?;
-                            self.push_op(TempIndex, &[index_register, rhs.register, i as u8]);
+                            self.push_op(TempIndex, &[index_register, rhs.register, index]);
                             self.compile_value_export(*id_index, index_register)?;
                             self.pop_register()?; // index_register
                         }
@@ -1136,7 +1797,7 @@ impl Compiler {
                 Node::Lookup(lookup) => {
                     let register = self.push_register()?;
 
-                    self.push_op(TempIndex, &[register, rhs.register, i as u8]);
+                    self.push_op(TempIndex, &[register, rhs.register, index]);
                     self.compile_lookup(ResultRegister::None, lookup, None, Some(register), ast)?;
 
                     self.pop_register()?;
@@ -1168,7 +1829,7 @@ impl Compiler {
         result_register: ResultRegister,
         id: ConstantIndex,
     ) -> CompileNodeResult {
-        let result = if let Some(local_register) = self.frame().get_local_assigned_register(id) {
+        let result = if let Some(local_register) = self.frame_mut().access_local_register(id) {
             match result_register {
                 ResultRegister::None => None,
                 ResultRegister::Any => Some(CompileResult::with_assigned(local_register)),
@@ -1229,6 +1890,7 @@ impl Compiler {
             Op::LoadString,
             Op::LoadString16,
             Op::LoadString24,
+            Op::LoadString32,
         );
     }
 
@@ -1239,6 +1901,7 @@ impl Compiler {
             Op::LoadNonLocal,
             Op::LoadNonLocal16,
             Op::LoadNonLocal24,
+            Op::LoadNonLocal32,
         );
     }
 
@@ -1249,11 +1912,16 @@ impl Compiler {
         op8: Op,
         op16: Op,
         op24: Op,
+        op32: Op,
     ) {
         match id.bytes() {
-            [byte1, 0, 0] => self.push_op(op8, &[result_register, byte1]),
-            [byte1, byte2, 0] => self.push_op(op16, &[result_register, byte1, byte2]),
-            [byte1, byte2, byte3] => self.push_op(op24, &[result_register, byte1, byte2, byte3]),
+            [byte1, 0, 0, 0] => self.push_op(op8, &[result_register, byte1]),
+            [byte1, byte2, 0, 0] => self.push_op(op16, &[result_register, byte1, byte2]),
+            [byte1, byte2, byte3, 0] => self.push_op(op24, &[result_register, byte1, byte2, byte3]),
+            bytes => {
+                self.push_op(op32, &[result_register]);
+                self.push_bytes(&bytes);
+            }
         }
     }
 
@@ -1429,7 +2097,7 @@ impl Compiler {
 
         match root {
             ImportItemNode::Id(id) => {
-                if let Some(local_register) = self.frame().get_local_assigned_register(*id) {
+                if let Some(local_register) = self.frame_mut().access_local_register(*id) {
                     if local_register != import_register {
                         self.push_op(Copy, &[import_register, local_register]);
                     }
@@ -1566,7 +2234,7 @@ impl Compiler {
         let rhs_node = ast.node(rhs);
 
         match op {
-            Add | Subtract | Multiply | Divide | Modulo => {
+            Add | Subtract | Multiply | Divide | Modulo | In => {
                 self.compile_arithmetic_op(result_register, op, lhs_node, rhs_node, ast)
             }
             Less | LessOrEqual | Greater | GreaterOrEqual | Equal | NotEqual => {
@@ -1587,12 +2255,13 @@ impl Compiler {
     ) -> CompileNodeResult {
         use AstBinaryOp::*;
 
-        let op = match op {
-            Add => Op::Add,
-            Subtract => Op::Subtract,
-            Multiply => Op::Multiply,
-            Divide => Op::Divide,
-            Modulo => Op::Modulo,
+        let (op, small_int_op) = match op {
+            Add => (Op::Add, Some(Op::AddU8)),
+            Subtract => (Op::Subtract, Some(Op::SubtractU8)),
+            Multiply => (Op::Multiply, None),
+            Divide => (Op::Divide, None),
+            Modulo => (Op::Modulo, None),
+            In => (Op::In, None),
             _ => return compiler_error!(self, "Internal error: invalid op"),
         };
 
@@ -1601,16 +2270,25 @@ impl Compiler {
                 let lhs = self
                     .compile_node(ResultRegister::Any, lhs_node, ast)?
                     .ok_or_else(|| self.make_error("Missing lhs for binary op".into()))?;
-                let rhs = self
-                    .compile_node(ResultRegister::Any, rhs_node, ast)?
-                    .ok_or_else(|| self.make_error("Missing rhs for binary op".into()))?;
 
-                self.push_op(op, &[result.register, lhs.register, rhs.register]);
+                match small_int_op.zip(small_int_literal(rhs_node, ast)) {
+                    Some((small_int_op, n)) => {
+                        self.push_op(small_int_op, &[result.register, lhs.register, n]);
+                    }
+                    None => {
+                        let rhs = self
+                            .compile_node(ResultRegister::Any, rhs_node, ast)?
+                            .ok_or_else(|| self.make_error("Missing rhs for binary op".into()))?;
 
-                if lhs.is_temporary {
-                    self.pop_register()?;
+                        self.push_op(op, &[result.register, lhs.register, rhs.register]);
+
+                        if rhs.is_temporary {
+                            self.pop_register()?;
+                        }
+                    }
                 }
-                if rhs.is_temporary {
+
+                if lhs.is_temporary {
                     self.pop_register()?;
                 }
 
@@ -1648,6 +2326,16 @@ impl Compiler {
             })
         };
 
+        let get_small_int_comparison_op = |ast_op| match ast_op {
+            Less => Some(Op::LessU8),
+            LessOrEqual => Some(Op::LessOrEqualU8),
+            Greater => Some(Op::GreaterU8),
+            GreaterOrEqual => Some(Op::GreaterOrEqualU8),
+            Equal => Some(Op::EqualU8),
+            NotEqual => Some(Op::NotEqualU8),
+            _ => None,
+        };
+
         let result = self.get_result_register(result_register)?;
 
         let stack_count = self.frame().register_stack.len();
@@ -1705,16 +2393,28 @@ impl Compiler {
             }
         }
 
-        // Compile the rhs for the final rhs in the comparison chain
-        let rhs_register = self
-            .compile_node(ResultRegister::Any, rhs, ast)?
-            .unwrap()
-            .register;
+        // Small integer literals in the final comparison are used directly as the rhs
+        let small_int_comparison = match (result, small_int_literal(rhs, ast)) {
+            (Some(result), Some(n)) => {
+                get_small_int_comparison_op(ast_op).map(|op| (result, op, n))
+            }
+            _ => None,
+        };
 
-        // We only need to perform the final comparison if there's a result register
-        if let Some(result) = result {
-            let op = get_comparision_op(ast_op).map_err(|e| self.make_error(e))?;
-            self.push_op(op, &[result.register, lhs_register, rhs_register]);
+        if let Some((result, op, n)) = small_int_comparison {
+            self.push_op(op, &[result.register, lhs_register, n]);
+        } else {
+            // Compile the rhs for the final rhs in the comparison chain
+            let rhs_register = self
+                .compile_node(ResultRegister::Any, rhs, ast)?
+                .unwrap()
+                .register;
+
+            // We only need to perform the final comparison if there's a result register
+            if let Some(result) = result {
+                let op = get_comparision_op(ast_op).map_err(|e| self.make_error(e))?;
+                self.push_op(op, &[result.register, lhs_register, rhs_register]);
+            }
         }
 
         for jump_offset in jump_offsets.iter() {
@@ -1726,6 +2426,80 @@ impl Compiler {
         Ok(result)
     }
 
+    fn compile_assert(
+        &mut self,
+        result_register: ResultRegister,
+        expression_string: ConstantIndex,
+        expression: AstIndex,
+        ast: &Ast,
+    ) -> CompileNodeResult {
+        use AstBinaryOp::*;
+
+        let comparison_op = |ast_op| match ast_op {
+            Less => Some(Op::Less),
+            LessOrEqual => Some(Op::LessOrEqual),
+            Greater => Some(Op::Greater),
+            GreaterOrEqual => Some(Op::GreaterOrEqual),
+            Equal => Some(Op::Equal),
+            NotEqual => Some(Op::NotEqual),
+            _ => None,
+        };
+
+        let result = self.get_result_register(result_register)?;
+
+        let stack_count = self.frame().register_stack.len();
+
+        let expression_node = ast.node(expression);
+
+        // Single comparisons are asserted with their compared values so that they can be included
+        // in the error message. Chained comparisons are asserted as a single expression.
+        let comparison = match expression_node.node {
+            Node::BinaryOp { op, lhs, rhs } => match (comparison_op(op), &ast.node(rhs).node) {
+                (Some(_), Node::BinaryOp { op: rhs_op, .. })
+                    if comparison_op(*rhs_op).is_some() =>
+                {
+                    None
+                }
+                (Some(op), _) => Some((op, lhs, rhs)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some((op, lhs, rhs)) = comparison {
+            let lhs_register = self
+                .compile_node(ResultRegister::Any, ast.node(lhs), ast)?
+                .unwrap()
+                .register;
+            let rhs_register = self
+                .compile_node(ResultRegister::Any, ast.node(rhs), ast)?
+                .unwrap()
+                .register;
+            let comparison_register = self.push_register()?;
+
+            self.push_op(op, &[comparison_register, lhs_register, rhs_register]);
+            self.push_op(
+                Op::AssertComparison,
+                &[comparison_register, lhs_register, rhs_register],
+            );
+        } else {
+            let expression_register = self
+                .compile_node(ResultRegister::Any, expression_node, ast)?
+                .unwrap();
+
+            self.push_op(Op::Assert, &[expression_register.register]);
+        }
+        self.push_bytes(&expression_string.bytes());
+
+        self.truncate_register_stack(stack_count)?;
+
+        if let Some(result) = result {
+            self.push_op(Op::SetEmpty, &[result.register]);
+        }
+
+        Ok(result)
+    }
+
     fn compile_logic_op(
         &mut self,
         result_register: ResultRegister,
@@ -1800,9 +2574,7 @@ impl Compiler {
                     if size_hint <= u8::MAX as usize {
                         self.push_op(Op::StringStart, &[result.register, size_hint as u8]);
                     } else {
-                        // Limit the size hint to u32::MAX, u64 size hinting can be added later if
-                        // it would be useful in practice.
-                        let size_hint = size_hint.min(u32::MAX as usize) as u32;
+                        let size_hint = size_hint.min(MAX_SIZE_HINT) as u32;
                         self.push_op(Op::StringStart32, &[result.register]);
                         self.push_bytes(&size_hint.to_le_bytes());
                     }
@@ -2028,7 +2800,7 @@ impl Compiler {
                     }
                     size_hint if size_hint <= u32::MAX as usize => {
                         self.push_op(SequenceStart32, &[result.register]);
-                        self.push_bytes(&(size_hint as u32).to_le_bytes());
+                        self.push_bytes(&(size_hint.min(MAX_SIZE_HINT) as u32).to_le_bytes());
                     }
                     overflow => {
                         return compiler_error!(
@@ -2113,7 +2885,7 @@ impl Compiler {
                     }
                     size_hint if size_hint <= u32::MAX as usize => {
                         self.push_op(MakeMap32, &[result.register]);
-                        self.push_bytes(&(size_hint as u32).to_le_bytes());
+                        self.push_bytes(&(size_hint.min(MAX_SIZE_HINT) as u32).to_le_bytes());
                     }
                     overflow => {
                         return compiler_error!(
@@ -2129,11 +2901,14 @@ impl Compiler {
                     let value = match (key, maybe_value_node) {
                         (_, Some(value_node)) => {
                             let value_node = ast.node(*value_node);
+                            if let (MapKey::Id(id), Node::Function(_)) = (key, &value_node.node) {
+                                self.function_name = Some(*id);
+                            }
                             self.compile_node(ResultRegister::Any, value_node, ast)?
                                 .unwrap()
                         }
                         (MapKey::Id(id), None) => {
-                            match self.frame().get_local_assigned_register(*id) {
+                            match self.frame_mut().access_local_register(*id) {
                                 Some(register) => CompileResult::with_assigned(register),
                                 None => {
                                     let register = self.push_register()?;
@@ -2177,6 +2952,12 @@ impl Compiler {
     ) -> CompileNodeResult {
         use Op::*;
 
+        let forward_capture_register = self.frame_mut().forward_capture_register.take();
+        let function_name = self
+            .function_name
+            .take()
+            .map(|id| ast.constants().get_str(id).to_string());
+
         if let Some(result) = self.get_result_register(result_register)? {
             let arg_count = match u8::try_from(function.args.len()) {
                 Ok(x) => x,
@@ -2189,9 +2970,22 @@ impl Compiler {
                 }
             };
 
-            let captures = self
-                .frame()
-                .captures_for_nested_frame(&function.accessed_non_locals);
+            let accessed_non_locals = live_non_locals(function, ast);
+            let mut captures = self.frame().captures_for_nested_frame(&accessed_non_locals);
+            if forward_capture_register.is_some() {
+                // Functions defined later in the block are also captured,
+                // with the captures being updated once the functions have been assigned.
+                for accessed in accessed_non_locals.iter() {
+                    if self.frame().pending_forward_definitions.contains(accessed)
+                        && !captures.contains(accessed)
+                    {
+                        captures.push(*accessed);
+                    }
+                }
+            }
+            for capture in captures.iter() {
+                self.frame_mut().mark_local_as_accessed(*capture);
+            }
             if captures.len() > u8::MAX as usize {
                 return compiler_error!(
                     self,
@@ -2224,6 +3018,7 @@ impl Compiler {
             }
 
             let function_size_ip = self.push_offset_placeholder();
+            let body_start_ip = self.bytes.len();
 
             let local_count = match u8::try_from(function.local_count) {
                 Ok(x) => x,
@@ -2236,8 +3031,6 @@ impl Compiler {
                 }
             };
 
-            let allow_implicit_return = !function.is_generator;
-
             let body_as_slice = [function.body];
 
             let function_body = match &ast.node(function.body).node {
@@ -2251,10 +3044,16 @@ impl Compiler {
                 &function.args,
                 &captures,
                 ast,
-                allow_implicit_return,
+                Some(function),
             )?;
 
             self.update_offset_placeholder(function_size_ip)?;
+            self.debug_info
+                .push_function(body_start_ip..self.bytes.len(), function_name);
+
+            if let Some(forward_capture_register) = forward_capture_register {
+                self.push_op(Copy, &[forward_capture_register, result.register]);
+            }
 
             for (i, capture) in captures.iter().enumerate() {
                 if let Some(local_register) = self.frame().get_local_reserved_register(*capture) {
@@ -2268,6 +3067,15 @@ impl Compiler {
                     self.frame().get_local_assigned_register(*capture)
                 {
                     self.push_op(Capture, &[result.register, i as u8, local_register]);
+                } else if let (Some(forward_capture_register), true) = (
+                    forward_capture_register,
+                    self.frame().pending_forward_definitions.contains(capture),
+                ) {
+                    self.frame_mut().forward_captures.push((
+                        *capture,
+                        forward_capture_register,
+                        i as u8,
+                    ));
                 } else {
                     let capture_register = self.push_register()?;
                     self.compile_load_non_local(capture_register, *capture);
@@ -2282,6 +3090,66 @@ impl Compiler {
         }
     }
 
+    // Compiles a decorated function as a chain of calls, e.g. `@a @b |x| ...` is compiled as
+    // `a(b(|x| ...))`.
+    fn compile_decorated_function(
+        &mut self,
+        result_register: ResultRegister,
+        decorators: &[AstIndex],
+        function: AstIndex,
+        ast: &Ast,
+    ) -> CompileNodeResult {
+        let result = self.get_result_register(result_register)?;
+        let stack_count = self.frame().register_stack.len();
+
+        // The undecorated function is kept in its own register while the decorators are applied,
+        // so that captures which are deferred until the end of an assignment (e.g. when the
+        // function refers to itself) are applied to the function rather than to the result.
+        let function_register = self.push_register()?;
+        self.compile_node(
+            ResultRegister::Fixed(function_register),
+            ast.node(function),
+            ast,
+        )?;
+
+        let mut value_register = function_register;
+        for (i, decorator) in decorators.iter().enumerate().rev() {
+            let decorator_register = self
+                .compile_node(ResultRegister::Any, ast.node(*decorator), ast)?
+                .unwrap();
+
+            let call_result_register = match result {
+                Some(result) if i == 0 => ResultRegister::Fixed(result.register),
+                None if i == 0 => ResultRegister::None,
+                _ => ResultRegister::Any,
+            };
+
+            if let Some(call_result) = self.compile_call(
+                call_result_register,
+                decorator_register.register,
+                &[],
+                Some(value_register),
+                None,
+                ast,
+            )? {
+                value_register = call_result.register;
+            }
+        }
+
+        if let Some(result) = result {
+            if self.frame().is_local_register_reserved(result.register) {
+                // The decorated function is being assigned to a local, so the local can be
+                // committed now to resolve any deferred captures while the undecorated function
+                // is still available.
+                self.commit_local_register(result.register)?;
+            }
+        }
+
+        self.truncate_register_stack(stack_count)?;
+
+        Ok(result)
+    }
+
     // Works through a lookup chain.
     //
     // The lookup chain is a linked list of LookupNodes stored as AST indices.
@@ -2332,11 +3200,13 @@ impl Compiler {
         };
 
         let mut lookup_node = root_node.clone();
+        let mut previous_node_was_call = false;
 
         loop {
             let mut piped_call_args = Vec::new();
 
             let is_last_node = next_node_index.is_none();
+            let is_call = matches!(lookup_node, LookupNode::Call { .. });
 
             match lookup_node {
                 LookupNode::Root(root_node) => {
@@ -2464,6 +3334,10 @@ impl Compiler {
                 LookupNode::Call { args, with_parens } => {
                     // Function call on a lookup result
 
+                    if previous_node_was_call && !with_parens {
+                        self.check_for_misleading_call_indentation(&args, ast);
+                    }
+
                     if is_last_node && set_value.is_some() {
                         return compiler_error!(self, "Assigning to temporary value");
                     }
@@ -2524,6 +3398,8 @@ impl Compiler {
                 }
             }
 
+            previous_node_was_call = is_call;
+
             // Is the lookup chain complete?
             if let Some(next) = next_node_index {
                 let next_lookup_node = ast.node(next);
@@ -2635,9 +3511,15 @@ impl Compiler {
         use Op::*;
 
         match key.bytes() {
-            [byte1, 0, 0] => self.push_op(Access, &[result, value, byte1]),
-            [byte1, byte2, 0] => self.push_op(Access16, &[result, value, byte1, byte2]),
-            [byte1, byte2, byte3] => self.push_op(Access24, &[result, value, byte1, byte2, byte3]),
+            [byte1, 0, 0, 0] => self.push_op(Access, &[result, value, byte1]),
+            [byte1, byte2, 0, 0] => self.push_op(Access16, &[result, value, byte1, byte2]),
+            [byte1, byte2, byte3, 0] => {
+                self.push_op(Access24, &[result, value, byte1, byte2, byte3])
+            }
+            bytes => {
+                self.push_op(Access32, &[result, value]);
+                self.push_bytes(&bytes);
+            }
         }
 
         Ok(())
@@ -2743,7 +3625,7 @@ impl Compiler {
         piped_arg: Option<u8>,
         ast: &Ast,
     ) -> CompileNodeResult {
-        if let Some(function_register) = self.frame().get_local_assigned_register(function_id) {
+        if let Some(function_register) = self.frame_mut().access_local_register(function_id) {
             self.compile_call(
                 result_register,
                 function_register,
@@ -2777,6 +3659,32 @@ impl Compiler {
         }
     }
 
+    // Adds a warning if a call without parentheses on the result of another call has its
+    // arguments on an indented line, e.g.
+    //
+    // foo.bar 1
+    //   foo.bar 2
+    //
+    // ...which is parsed as `foo.bar(1)(foo.bar 2)`, rather than as two separate calls.
+    fn check_for_misleading_call_indentation(&mut self, args: &[AstIndex], ast: &Ast) {
+        let call_line = match self.span_stack.last() {
+            Some(span) => span.start.line,
+            None => return,
+        };
+
+        if let Some(first_arg) = args.first() {
+            let arg_span = *ast.span(ast.node(*first_arg).span);
+            if arg_span.start.line > call_line {
+                self.warnings.push(CompilerWarning {
+                    message: "Indented expression is passed as an argument to the result of the \
+                              previous call, did you mean to unindent it?"
+                        .into(),
+                    span: arg_span,
+                });
+            }
+        }
+    }
+
     fn compile_call(
         &mut self,
         result_register: ResultRegister,
@@ -2882,7 +3790,7 @@ impl Compiler {
             self.pop_register()?;
         }
 
-        self.compile_node(expression_result_register, ast.node(*then_node), ast)?;
+        self.compile_branch(expression_result_register, *then_node, ast)?;
 
         let if_jump_ip = {
             if !else_if_blocks.is_empty() || else_node.is_some() || result.is_some() {
@@ -2912,7 +3820,7 @@ impl Compiler {
                         self.pop_register()?;
                     }
 
-                    self.compile_node(expression_result_register, ast.node(*else_if_node), ast)?;
+                    self.compile_branch(expression_result_register, *else_if_node, ast)?;
 
                     self.push_op_without_span(Jump, &[]);
                     let else_if_jump_ip = self.push_offset_placeholder();
@@ -2926,7 +3834,7 @@ impl Compiler {
 
         // Else - either compile the else block, or set the result to empty
         if let Some(else_node) = else_node {
-            self.compile_node(expression_result_register, ast.node(*else_node), ast)?;
+            self.compile_branch(expression_result_register, *else_node, ast)?;
         } else if let Some(result) = result {
             self.push_op_without_span(SetEmpty, &[result.register]);
         }
@@ -2980,7 +3888,7 @@ impl Compiler {
                 ResultRegister::None
             };
 
-            self.compile_node(body_result_register, ast.node(arm.expression), ast)?;
+            self.compile_branch(body_result_register, arm.expression, ast)?;
 
             if !is_last_arm {
                 self.push_op_without_span(Op::Jump, &[]);
@@ -3021,11 +3929,22 @@ impl Compiler {
             _ => 1,
         };
 
+        let jump_table = if match_len == 1 {
+            self.compile_match_jump_table(match_register.register, arms, ast)?
+        } else {
+            None
+        };
+
+        self.check_for_unreachable_match_arms(arms, ast);
+
         let mut result_jump_placeholders = Vec::new();
+        let mut arm_ips = Vec::with_capacity(arms.len());
 
         for (arm_index, arm) in arms.iter().enumerate() {
             let is_last_arm = arm_index == arms.len() - 1;
 
+            arm_ips.push(self.bytes.len());
+
             if let Some(placeholder) = self.compile_match_arm(
                 result,
                 match_register.register,
@@ -3038,6 +3957,10 @@ impl Compiler {
             }
         }
 
+        if let Some(jump_table) = jump_table {
+            self.update_match_jump_table(jump_table, &arm_ips)?;
+        }
+
         for jump_placeholder in result_jump_placeholders.iter() {
             self.update_offset_placeholder(*jump_placeholder)?;
         }
@@ -3047,6 +3970,163 @@ impl Compiler {
         Ok(result)
     }
 
+    // Emits a jump table for match expressions that start with arms containing literal patterns
+    //
+    // e.g.
+    //   match x
+    //     0 then "zero"
+    //     1 or 2 then "small"
+    //     100 if big_enough then "big"
+    //     n if n < 0 then "negative"
+    //     else "other"
+    //
+    // The table's entries point to the start of each arm, so matching values still run through
+    // the arm's pattern checks and condition, but only from the first arm that's known to match.
+    // Values that aren't in the table jump to the first arm that wasn't included in the table,
+    // and values of other types fall through to the sequential pattern checks.
+    //
+    // Returns None if the match expression isn't suitable for a jump table.
+    fn compile_match_jump_table(
+        &mut self,
+        match_register: u8,
+        arms: &[MatchArm],
+        ast: &Ast,
+    ) -> Result<Option<MatchJumpTable>, CompilerError> {
+        let mut entries = Vec::new();
+        let mut key_type = None;
+        let mut table_arm_count = 0;
+
+        'arms: for arm in arms.iter() {
+            if arm.patterns.is_empty() {
+                break;
+            }
+
+            let mut arm_entries = Vec::with_capacity(arm.patterns.len());
+            for pattern in arm.patterns.iter() {
+                let (key, pattern_key_type) = match &ast.node(*pattern).node {
+                    Node::Number0 => (JumpTableLiteral::Int(0), JumpTableKey::Int),
+                    Node::Number1 => (JumpTableLiteral::Int(1), JumpTableKey::Int),
+                    Node::Int(constant) => (
+                        JumpTableLiteral::Int(ast.constants().get_i64(*constant)),
+                        JumpTableKey::Int,
+                    ),
+                    Node::Str(string) => match string.nodes.as_slice() {
+                        [StringNode::Literal(constant)] => {
+                            (JumpTableLiteral::Str(*constant), JumpTableKey::Str)
+                        }
+                        _ => break 'arms,
+                    },
+                    _ => break 'arms,
+                };
+
+                if *key_type.get_or_insert(pattern_key_type) != pattern_key_type {
+                    break 'arms;
+                }
+
+                arm_entries.push((key, table_arm_count));
+            }
+
+            entries.extend(arm_entries);
+            table_arm_count += 1;
+        }
+
+        let key_type = match key_type {
+            Some(key_type) if table_arm_count > 0 => key_type,
+            _ => return Ok(None),
+        };
+
+        // Values that don't match any entries jump to the first arm following the table's arms.
+        // If every arm is in the table then unmatched values are sent to the last arm,
+        // where they'll fail to match in the same way as they would without the jump table.
+        let default_arm = table_arm_count.min(arms.len() - 1);
+
+        // Entries are sorted so that the runtime can perform a binary search.
+        // The sort is stable, so when keys are repeated the entry for the first arm is kept.
+        let constants = ast.constants();
+        entries.sort_by(|(a, _), (b, _)| match (a, b) {
+            (JumpTableLiteral::Int(a), JumpTableLiteral::Int(b)) => a.cmp(b),
+            (JumpTableLiteral::Str(a), JumpTableLiteral::Str(b)) => {
+                constants.get_str(*a).cmp(constants.get_str(*b))
+            }
+            _ => unreachable!(),
+        });
+        entries.dedup_by(|(a, _), (b, _)| match (&a, &b) {
+            (JumpTableLiteral::Int(a), JumpTableLiteral::Int(b)) => a == b,
+            (JumpTableLiteral::Str(a), JumpTableLiteral::Str(b)) => {
+                constants.get_str(*a) == constants.get_str(*b)
+            }
+            _ => false,
+        });
+
+        if entries.len() < MIN_JUMP_TABLE_ENTRIES || entries.len() > u16::MAX as usize {
+            return Ok(None);
+        }
+
+        self.push_op(Op::JumpTable, &[match_register, key_type as u8]);
+        self.push_bytes(&(entries.len() as u16).to_le_bytes());
+        let default_offset_ip = self.push_offset_placeholder();
+
+        let mut entry_offsets = Vec::with_capacity(entries.len());
+        for (key, arm_index) in entries.iter() {
+            match key {
+                JumpTableLiteral::Int(n) => self.push_bytes(&n.to_le_bytes()),
+                JumpTableLiteral::Str(constant) => self.push_bytes(&constant.bytes()),
+            }
+            entry_offsets.push((self.push_offset_placeholder(), *arm_index));
+        }
+
+        Ok(Some(MatchJumpTable {
+            end_ip: self.bytes.len(),
+            default_offset_ip,
+            default_arm,
+            entry_offsets,
+        }))
+    }
+
+    // Updates a jump table's offsets once the match arms have been compiled
+    //
+    // The arm ips are the start positions of each match arm.
+    fn update_match_jump_table(
+        &mut self,
+        jump_table: MatchJumpTable,
+        arm_ips: &[usize],
+    ) -> Result<(), CompilerError> {
+        self.update_jump_table_offset(
+            jump_table.default_offset_ip,
+            jump_table.end_ip,
+            arm_ips[jump_table.default_arm],
+        )?;
+
+        for (offset_ip, arm_index) in jump_table.entry_offsets.iter() {
+            self.update_jump_table_offset(*offset_ip, jump_table.end_ip, arm_ips[*arm_index])?;
+        }
+
+        Ok(())
+    }
+
+    fn update_jump_table_offset(
+        &mut self,
+        offset_ip: usize,
+        table_end_ip: usize,
+        target_ip: usize,
+    ) -> Result<(), CompilerError> {
+        match u16::try_from(target_ip - table_end_ip) {
+            Ok(offset) => {
+                let offset_bytes = offset.to_le_bytes();
+                self.bytes[offset_ip] = offset_bytes[0];
+                self.bytes[offset_ip + 1] = offset_bytes[1];
+                Ok(())
+            }
+            Err(_) => compiler_error!(
+                self,
+                "Jump table offset is too large, {} is larger than the maximum of {}.
+                 Try breaking up this part of the program a bit.",
+                target_ip - table_end_ip,
+                u16::MAX
+            ),
+        }
+    }
+
     fn compile_match_arm(
         &mut self,
         result: Option<CompileResult>,
@@ -3056,6 +4136,8 @@ impl Compiler {
         is_last_arm: bool,
         ast: &Ast,
     ) -> Result<Option<usize>, CompilerError> {
+        self.check_match_arm_alternative_bindings(arm, ast)?;
+
         let mut jumps = MatchJumpPlaceholders::default();
 
         for (alternative_index, arm_pattern) in arm.patterns.iter().enumerate() {
@@ -3169,7 +4251,7 @@ impl Compiler {
             ResultRegister::None
         };
 
-        self.compile_node(body_result_register, ast.node(arm.expression), ast)?;
+        self.compile_branch(body_result_register, arm.expression, ast)?;
 
         let result_jump_placeholder = if !is_last_arm {
             self.push_op_without_span(Op::Jump, &[]);
@@ -3185,6 +4267,61 @@ impl Compiler {
         Ok(result_jump_placeholder)
     }
 
+    // Checks that each alternative in a match arm binds the same set of ids
+    //
+    // The arm's condition and body can refer to any ids that are bound in the arm's patterns,
+    // so each alternative needs to provide the same bindings.
+    //
+    // e.g.
+    //   match x
+    //     (y, 0) or (0, y) if y > 0 then y
+    //     ^~~~~~~~~~~~~~~~ y is bound in both alternatives, so it can be used in the condition
+    //                      and in the body.
+    //     (a, 1) or (1, b) then a
+    //     ^~~~~~~~~~~~~~~~ a and b are only bound in one alternative each, which is an error.
+    fn check_match_arm_alternative_bindings(
+        &mut self,
+        arm: &MatchArm,
+        ast: &Ast,
+    ) -> Result<(), CompilerError> {
+        let (first, other_alternatives) = match arm.patterns.split_first() {
+            Some((first, others)) if !others.is_empty() => (first, others),
+            _ => return Ok(()),
+        };
+
+        let mut expected_bindings = Vec::new();
+        collect_match_pattern_bindings(*first, ast, &mut expected_bindings);
+        expected_bindings.sort_by_key(|id| usize::from(*id));
+        expected_bindings.dedup();
+
+        for alternative in other_alternatives.iter() {
+            let mut bindings = Vec::new();
+            collect_match_pattern_bindings(*alternative, ast, &mut bindings);
+            bindings.sort_by_key(|id| usize::from(*id));
+            bindings.dedup();
+
+            if bindings != expected_bindings {
+                let missing_id = expected_bindings
+                    .iter()
+                    .find(|id| !bindings.contains(id))
+                    .or_else(|| bindings.iter().find(|id| !expected_bindings.contains(id)))
+                    .unwrap();
+
+                self.span_stack.push(*ast.span(ast.node(*alternative).span));
+                let error = compiler_error!(
+                    self,
+                    "Inconsistent bindings in match arm alternatives, \
+                     '{}' needs to be bound in each alternative",
+                    ast.constants().get_str(*missing_id)
+                );
+                self.span_stack.pop();
+                return error;
+            }
+        }
+
+        Ok(())
+    }
+
     fn compile_match_arm_patterns(
         &mut self,
         params: MatchArmParameters,
@@ -3547,7 +4684,7 @@ impl Compiler {
             ResultRegister::None
         };
 
-        self.compile_node(body_result_register, ast.node(*body), ast)?;
+        self.compile_branch(body_result_register, *body, ast)?;
 
         self.push_jump_back_op(JumpBack, &[], loop_start_ip);
 
@@ -3612,7 +4749,7 @@ impl Compiler {
             ResultRegister::None
         };
 
-        let body_result = self.compile_node(body_result_register, ast.node(body), ast)?;
+        let body_result = self.compile_branch(body_result_register, body, ast)?;
 
         self.push_jump_back_op(JumpBack, &[], loop_start_ip);
 
@@ -3694,14 +4831,83 @@ impl Compiler {
     }
 
     fn push_op(&mut self, op: Op, bytes: &[u8]) {
-        self.debug_info.push(self.bytes.len(), self.span());
+        let span = if self.settings.strip_debug_info {
+            self.frame().span
+        } else {
+            self.span()
+        };
+        self.debug_info.push(self.bytes.len(), span);
         self.push_op_without_span(op, bytes);
     }
 
     fn push_op_without_span(&mut self, op: Op, bytes: &[u8]) {
+        let ip = self.bytes.len();
         self.bytes.push(op as u8);
         self.bytes.extend_from_slice(bytes);
-        self.frame_mut().last_op = Some(op);
+        let frame = self.frame_mut();
+        frame.last_op = Some(op);
+        frame.last_op_ip = ip;
+    }
+
+    // Pushes a Return op for the given register
+    //
+    // If the returned value was produced by the preceding op, and the op is a call,
+    // then the call is converted into a tail call.
+    fn push_return_op(&mut self, register: u8, with_span: bool) {
+        let frame = self.frame();
+        if frame.allow_tail_calls {
+            let call_ip = frame.last_op_ip;
+            let tail_call_op = match frame.last_op {
+                Some(Op::Call) if self.bytes.len() == call_ip + 5 => Some(Op::TailCall),
+                Some(Op::CallInstance) if self.bytes.len() == call_ip + 6 => {
+                    Some(Op::TailCallInstance)
+                }
+                _ => None,
+            };
+
+            if let Some(tail_call_op) = tail_call_op {
+                // The call's result register follows the op
+                if self.bytes[call_ip + 1] == register {
+                    self.bytes[call_ip] = tail_call_op as u8;
+                }
+            }
+        }
+
+        if with_span {
+            self.push_op(Op::Return, &[register]);
+        } else {
+            self.push_op_without_span(Op::Return, &[register]);
+        }
+    }
+
+    // Adds a coverage point for the node, when compiling with coverage enabled
+    fn compile_coverage_point(&mut self, node: &AstNode, ast: &Ast) -> Result<(), CompilerError> {
+        if self.settings.coverage {
+            let index = self.debug_info.push_coverage_point(*ast.span(node.span));
+            let index = match u32::try_from(index) {
+                Ok(index) => index,
+                Err(_) => return compiler_error!(self, "Too many coverage points"),
+            };
+            self.push_op_without_span(Op::Coverage, &[]);
+            self.push_bytes(&index.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    // Compiles the body of a branch, with a coverage point if the body isn't a block
+    //
+    // Blocks add coverage points for each of their expressions, see compile_block.
+    fn compile_branch(
+        &mut self,
+        result_register: ResultRegister,
+        body: AstIndex,
+        ast: &Ast,
+    ) -> CompileNodeResult {
+        let body = ast.node(body);
+        if !matches!(body.node, Node::Block(_)) {
+            self.compile_coverage_point(body, ast)?;
+        }
+        self.compile_node(result_register, body, ast)
     }
 
     fn push_bytes(&mut self, bytes: &[u8]) {
@@ -3767,6 +4973,7 @@ impl Compiler {
         CompilerError {
             message,
             span: self.span(),
+            additional_errors: Vec::new(),
         }
     }
 
@@ -3790,6 +4997,159 @@ struct MatchJumpPlaceholders {
     alternative_end: Vec<usize>,
 }
 
+// The minimum number of literal patterns needed in a match expression to make a jump table
+const MIN_JUMP_TABLE_ENTRIES: usize = 4;
+
+enum JumpTableLiteral {
+    Int(i64),
+    Str(ConstantIndex),
+}
+
+struct MatchJumpTable {
+    // The position of the end of the table, used as the base for the table's offsets
+    end_ip: usize,
+    // The position of the default offset placeholder
+    default_offset_ip: usize,
+    // The arm that should be jumped to when no entries match
+    default_arm: usize,
+    // The positions of each entry's offset placeholder, along with the entry's arm index
+    entry_offsets: Vec<(usize, usize)>,
+}
+
+// Collects the ids that are bound by a match pattern, including those in nested patterns
+fn collect_match_pattern_bindings(pattern: AstIndex, ast: &Ast, bindings: &mut Vec<ConstantIndex>) {
+    match &ast.node(pattern).node {
+        Node::Id(id) | Node::Ellipsis(Some(id)) => bindings.push(*id),
+        Node::List(patterns) | Node::Tuple(patterns) | Node::TempTuple(patterns) => {
+            for nested in patterns.iter() {
+                collect_match_pattern_bindings(*nested, ast, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Returns true if the match arm matches any value without a condition,
+// e.g. `else`, `_`, or an id
+// Returns the value of an integer literal that fits in the rhs byte of ops like AddU8
+fn small_int_literal(node: &AstNode, ast: &Ast) -> Option<u8> {
+    match node.node {
+        Node::Number0 => Some(0),
+        Node::Number1 => Some(1),
+        Node::Int(constant) => u8::try_from(ast.constants().get_i64(constant)).ok(),
+        _ => None,
+    }
+}
+
+// Returns true if the expression unconditionally leaves its block, e.g. `return` or `break`
+fn is_unconditional_exit(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Return(_) | Node::Throw(_) | Node::Break | Node::Continue
+    )
+}
+
+// Returns the non-locals accessed by a function that are accessed in reachable code
+//
+// Non-locals that are only accessed in code that can't be reached, e.g. following a `return`, or
+// in a branch of an `if` with a constant condition, don't need to be captured by the function.
+// Code that can't be reached is still compiled, with accesses of the uncaptured non-locals
+// falling back to non-local lookups that will never be executed.
+fn live_non_locals(function: &Function, ast: &Ast) -> Vec<ConstantIndex> {
+    // Constants referred to by reachable nodes. String literals are included too, which can only
+    // cause extra captures, rather than missing captures.
+    let mut constants = HashSet::new();
+
+    let mut pending = vec![function.body];
+    pending.extend(function.args.iter());
+
+    while let Some(index) = pending.pop() {
+        let node = &ast.node(index).node;
+
+        match node {
+            // The nested function's accesses are found when visiting its body,
+            // so that its unreachable code is also ignored.
+            Node::Function(_) => {}
+            _ => {
+                let mut node = node.clone();
+                node.map_constants(|constant| {
+                    constants.insert(usize::from(constant));
+                    constant
+                });
+            }
+        }
+
+        pending.extend(reachable_children(node, ast));
+    }
+
+    function
+        .accessed_non_locals
+        .iter()
+        .filter(|id| constants.contains(&usize::from(**id)))
+        .cloned()
+        .collect()
+}
+
+// Returns the children of a node that can be reached when the node is executed
+fn reachable_children(node: &Node, ast: &Ast) -> Vec<AstIndex> {
+    let is_constant = |condition: &AstIndex, value: bool| match ast.node(*condition).node {
+        Node::BoolTrue => value,
+        Node::BoolFalse => !value,
+        _ => false,
+    };
+
+    match node {
+        Node::Block(expressions) => {
+            let end = expressions
+                .iter()
+                .position(|expression| is_unconditional_exit(&ast.node(*expression).node))
+                .map_or(expressions.len(), |position| position + 1);
+            expressions[..end].to_vec()
+        }
+        Node::If(AstIf {
+            condition,
+            then_node,
+            ..
+        }) if is_constant(condition, true) => vec![*condition, *then_node],
+        Node::If(AstIf {
+            condition,
+            then_node,
+            ..
+        }) if is_constant(condition, false) => node
+            .children()
+            .into_iter()
+            .filter(|child| child != then_node)
+            .collect(),
+        Node::While { condition, .. } if is_constant(condition, false) => vec![*condition],
+        Node::Match { expression, arms } => {
+            let end = arms
+                .iter()
+                .position(|arm| is_catch_all_match_arm(arm, ast))
+                .map_or(arms.len(), |position| position + 1);
+            let mut result = vec![*expression];
+            for arm in arms[..end].iter() {
+                result.extend(arm.patterns.iter());
+                result.extend(arm.condition.iter());
+                result.push(arm.expression);
+            }
+            result
+        }
+        _ => node.children(),
+    }
+}
+
+fn is_catch_all_match_arm(arm: &MatchArm, ast: &Ast) -> bool {
+    if arm.condition.is_some() {
+        return false;
+    }
+
+    match arm.patterns.as_slice() {
+        [] => true,
+        [pattern] => matches!(ast.node(*pattern).node, Node::Wildcard | Node::Id(_)),
+        _ => false,
+    }
+}
+
 struct MatchArmParameters<'a> {
     match_register: u8,
     is_last_alternative: bool,
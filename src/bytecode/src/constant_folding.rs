@@ -0,0 +1,241 @@
+//! Evaluates module-level constant expressions at compile time
+//!
+//! The expressions that are assigned to values at the top level of a module are searched for
+//! operations that only involve constants, which are then replaced by the operation's result:
+//!
+//!   - Arithmetic on numbers, e.g. `x = 60 * 60 * 24`
+//!   - Negation of numbers, e.g. `x = -(1 + 2)`
+//!   - Concatenation of string literals, e.g. `x = 'foo' + 'bar'`
+//!
+//! The elements of list, tuple, and map literals are folded individually,
+//! e.g. `x = [1 + 1, 2 + 2]` becomes `x = [2, 4]`.
+//!
+//! Results are only folded when they match what the runtime would produce, so integer
+//! operations that would overflow, or integer division by zero, are left to the runtime.
+//! The results are appended to the Ast's constant pool, leaving existing constants unchanged.
+
+use {
+    koto_parser::{
+        Ast, AstBinaryOp, AstIndex, AstString, AstUnaryOp, Constant, ConstantIndex, ConstantPool,
+        Node, StringNode,
+    },
+    std::convert::TryFrom,
+};
+
+/// Folds the constant expressions in the Ast's module-level assignments
+pub(crate) fn fold_constants(ast: &mut Ast) {
+    let body = match ast.entry_point() {
+        Some(entry_point) => match &entry_point.node {
+            Node::MainBlock { body, .. } => body.clone(),
+            _ => return,
+        },
+        None => return,
+    };
+
+    let mut folder = ConstantFolder::default();
+
+    for expression in body {
+        let assigned = match &ast.node(expression).node {
+            Node::Assign { expression, .. } | Node::MultiAssign { expression, .. } => *expression,
+            _ => continue,
+        };
+        folder.fold_node(ast, assigned);
+    }
+
+    if !folder.constants.is_empty() {
+        let constants = ast
+            .constants()
+            .iter()
+            .chain(folder.constants.iter().map(FoldedValue::as_constant))
+            .collect::<Vec<_>>();
+
+        // The pool's capacity is checked before each folded constant is added
+        let pool = ConstantPool::from_constants(constants).expect("Too many constants");
+        ast.set_constants(pool);
+    }
+}
+
+// A value that's known at compile time
+#[derive(Clone, Debug, PartialEq)]
+enum FoldedValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl FoldedValue {
+    // Floats are compared by their bits, so that e.g. `0.0` and `-0.0` are kept separate
+    fn is_same_constant(&self, other: &FoldedValue) -> bool {
+        match (self, other) {
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => self == other,
+        }
+    }
+
+    fn as_constant(&self) -> Constant<'_> {
+        match self {
+            Self::Int(n) => Constant::I64(*n),
+            Self::Float(n) => Constant::F64(*n),
+            Self::Str(s) => Constant::Str(s),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ConstantFolder {
+    // The constants that have been produced by folding,
+    // to be appended to the Ast's existing constants
+    constants: Vec<FoldedValue>,
+}
+
+impl ConstantFolder {
+    // Folds the node and its children, returning the node's value if it's a constant
+    fn fold_node(&mut self, ast: &mut Ast, index: AstIndex) -> Option<FoldedValue> {
+        match ast.node(index).node.clone() {
+            Node::Number0 => Some(FoldedValue::Int(0)),
+            Node::Number1 => Some(FoldedValue::Int(1)),
+            Node::Int(constant) => Some(FoldedValue::Int(ast.constants().get_i64(constant))),
+            Node::Float(constant) => Some(FoldedValue::Float(ast.constants().get_f64(constant))),
+            Node::Str(string) => {
+                let mut result = String::new();
+                for node in string.nodes.iter() {
+                    match node {
+                        StringNode::Literal(constant) => {
+                            result.push_str(ast.constants().get_str(*constant))
+                        }
+                        StringNode::Expr(expression) => {
+                            self.fold_node(ast, *expression);
+                            return None;
+                        }
+                    }
+                }
+                Some(FoldedValue::Str(result))
+            }
+            Node::Nested(nested) => self.fold_node(ast, nested),
+            Node::UnaryOp {
+                op: AstUnaryOp::Negate,
+                value,
+            } => {
+                let result = match self.fold_node(ast, value)? {
+                    FoldedValue::Int(n) => FoldedValue::Int(n.checked_neg()?),
+                    FoldedValue::Float(n) => FoldedValue::Float(-n),
+                    FoldedValue::Str(_) => return None,
+                };
+                self.replace_node(ast, index, result)
+            }
+            Node::BinaryOp { op, lhs, rhs } => {
+                let lhs = self.fold_node(ast, lhs);
+                let rhs = self.fold_node(ast, rhs);
+                let result = binary_op(op, lhs?, rhs?)?;
+                self.replace_node(ast, index, result)
+            }
+            Node::List(elements) | Node::Tuple(elements) | Node::TempTuple(elements) => {
+                for element in elements {
+                    self.fold_node(ast, element);
+                }
+                None
+            }
+            Node::Map(entries) => {
+                for value in entries.iter().filter_map(|(_, value)| *value) {
+                    self.fold_node(ast, value);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    // Replaces the node with a node that loads the folded value
+    fn replace_node(
+        &mut self,
+        ast: &mut Ast,
+        index: AstIndex,
+        value: FoldedValue,
+    ) -> Option<FoldedValue> {
+        let node = match &value {
+            FoldedValue::Int(0) => Node::Number0,
+            FoldedValue::Int(1) => Node::Number1,
+            FoldedValue::Int(_) => Node::Int(self.add_constant(ast, value.clone())?),
+            FoldedValue::Float(_) => Node::Float(self.add_constant(ast, value.clone())?),
+            FoldedValue::Str(_) => {
+                // The folded string uses the quotation mark of the operation's first string
+                let mut first = index;
+                let quotation_mark = loop {
+                    match &ast.node(first).node {
+                        Node::BinaryOp { lhs, .. } => first = *lhs,
+                        Node::Nested(nested) => first = *nested,
+                        Node::Str(string) => break string.quotation_mark,
+                        _ => return None,
+                    }
+                };
+                Node::Str(AstString {
+                    quotation_mark,
+                    nodes: vec![StringNode::Literal(self.add_constant(ast, value.clone())?)],
+                })
+            }
+        };
+
+        ast.replace_node(index, node);
+        Some(value)
+    }
+
+    fn add_constant(&mut self, ast: &Ast, value: FoldedValue) -> Option<ConstantIndex> {
+        let index = match self
+            .constants
+            .iter()
+            .position(|constant| constant.is_same_constant(&value))
+        {
+            Some(position) => ast.constants().size() + position,
+            None => {
+                self.constants.push(value);
+                ast.constants().size() + self.constants.len() - 1
+            }
+        };
+
+        match ConstantIndex::try_from(index) {
+            Ok(index) => Some(index),
+            Err(_) => {
+                self.constants.pop();
+                None
+            }
+        }
+    }
+}
+
+// Evaluates a binary operation in the same way as the runtime, see koto_runtime::ValueNumber
+fn binary_op(op: AstBinaryOp, lhs: FoldedValue, rhs: FoldedValue) -> Option<FoldedValue> {
+    use {AstBinaryOp::*, FoldedValue::*};
+
+    let result = match (lhs, rhs) {
+        (Int(a), Int(b)) => match op {
+            Add => Int(a.checked_add(b)?),
+            Subtract => Int(a.checked_sub(b)?),
+            Multiply => Int(a.checked_mul(b)?),
+            Divide => Float(a as f64 / b as f64),
+            Modulo => Int(a.checked_rem(b)?),
+            _ => return None,
+        },
+        (Int(a), Float(b)) => Float(float_op(op, a as f64, b)?),
+        (Float(a), Int(b)) => Float(float_op(op, a, b as f64)?),
+        (Float(a), Float(b)) => Float(float_op(op, a, b)?),
+        (Str(a), Str(b)) if op == Add => Str(a + &b),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn float_op(op: AstBinaryOp, a: f64, b: f64) -> Option<f64> {
+    use AstBinaryOp::*;
+
+    let result = match op {
+        Add => a + b,
+        Subtract => a - b,
+        Multiply => a * b,
+        Divide => a / b,
+        Modulo => a % b,
+        _ => return None,
+    };
+
+    Some(result)
+}
@@ -0,0 +1,613 @@
+use {
+    crate::{Chunk, Instruction, InstructionReader, JumpTableKey, TypeId},
+    koto_parser::{Constant, ConstantIndex, MetaKeyId, Span},
+    std::{fmt::Write, sync::Arc},
+};
+
+/// The value of an [Operand]
+#[derive(Clone, Debug, PartialEq)]
+pub enum OperandValue {
+    /// A register
+    Register(u8),
+    /// An index into the chunk's [ConstantPool](koto_parser::ConstantPool)
+    Constant(ConstantIndex),
+    /// A bool value
+    Bool(bool),
+    /// An integer value, e.g. the value of a [SetNumber](Instruction::SetNumber)
+    Int(i64),
+    /// A count or size, e.g. an argument count, or a size hint
+    Count(usize),
+    /// The position in the chunk's bytecode that the instruction jumps to
+    JumpTarget(usize),
+    /// A meta key
+    MetaKey(MetaKeyId),
+    /// A type that's checked by a [CheckType](Instruction::CheckType)
+    Type(TypeId),
+    /// The type of key used in a [JumpTable](Instruction::JumpTable)
+    JumpTableKey(JumpTableKey),
+    /// The message of an [Error](Instruction::Error)
+    Message(String),
+    /// An index into the chunk's [coverage points](crate::DebugInfo::coverage_points)
+    CoveragePoint(u32),
+}
+
+/// An operand of a [DisassembledInstruction]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operand {
+    /// The operand's name, matching the name of the corresponding [Instruction] field
+    ///
+    /// Jump offsets are converted into absolute positions, with the name `target`.
+    pub name: &'static str,
+    /// The operand's value
+    pub value: OperandValue,
+}
+
+/// An instruction produced by a [Disassembler]
+#[derive(Debug)]
+pub struct DisassembledInstruction {
+    /// The position of the instruction in the chunk's bytecode
+    pub ip: usize,
+    /// The size of the instruction in bytes, including any inline data like jump table entries
+    pub size: usize,
+    /// The decoded instruction
+    pub instruction: Instruction,
+    /// The instruction's operands
+    pub operands: Vec<Operand>,
+    /// The span of the source that produced the instruction, if available
+    pub span: Option<Span>,
+    /// The number of functions that the instruction is nested in
+    pub depth: usize,
+}
+
+impl DisassembledInstruction {
+    /// Returns the indices of the constants that are referred to by the instruction
+    pub fn constants(&self) -> impl Iterator<Item = ConstantIndex> + '_ {
+        self.operands
+            .iter()
+            .filter_map(|operand| match operand.value {
+                OperandValue::Constant(index) => Some(index),
+                _ => None,
+            })
+    }
+}
+
+/// Produces a [DisassembledInstruction] for each instruction in a [Chunk]
+///
+/// Instructions are decoded with an [InstructionReader], with each instruction's operands
+/// and source span made available for inspection. [Disassembler::render] produces a text listing
+/// of the chunk's instructions.
+///
+/// # Example
+///
+/// ```
+/// use koto_bytecode::{Disassembler, Loader, OperandValue};
+///
+/// let chunk = Loader::default().compile_script("x = 'hello'", &None).unwrap();
+/// let instructions: Vec<_> = Disassembler::new(chunk.clone()).collect();
+///
+/// assert_eq!(instructions[0].instruction.to_string(), "LoadString");
+/// let constant = instructions[0].constants().next().unwrap();
+/// assert_eq!(chunk.constants.get_str(constant), "hello");
+/// assert_eq!(instructions[0].span.unwrap().start.column, 5);
+/// ```
+pub struct Disassembler {
+    reader: InstructionReader,
+    // The end positions of the functions that contain the current instruction
+    function_ends: Vec<usize>,
+}
+
+impl Disassembler {
+    /// Initializes a disassembler for the given chunk
+    pub fn new(chunk: Arc<Chunk>) -> Self {
+        Self {
+            reader: InstructionReader::new(chunk),
+            function_ends: Vec::new(),
+        }
+    }
+
+    /// Renders the chunk's instructions as text
+    ///
+    /// Each instruction is displayed with its position in the bytecode and its operands, with
+    /// constants shown along with their values. Function bodies are indented, and the source
+    /// lines that produced the instructions are included when the chunk has debug source.
+    pub fn render(self) -> String {
+        let chunk = self.reader.chunk.clone();
+        let source_lines = chunk.debug_info.source.lines().collect::<Vec<_>>();
+        let mut result = String::new();
+        let mut previous_line = None;
+
+        for disassembled in self {
+            let indent = "  ".repeat(disassembled.depth);
+
+            if let Some(span) = disassembled.span {
+                let line = span.start.line;
+                if previous_line != Some(line) && !source_lines.is_empty() {
+                    if previous_line.is_some() {
+                        result.push('\n');
+                    }
+                    let source_line = source_lines
+                        .get(line.saturating_sub(1) as usize)
+                        .unwrap_or(&"");
+                    writeln!(result, "{}|{}| {}", indent, line, source_line.trim_end()).ok();
+                    previous_line = Some(line);
+                }
+            }
+
+            write!(
+                result,
+                "{}{:>5}  {:<20}",
+                indent,
+                disassembled.ip,
+                disassembled.instruction.to_string()
+            )
+            .ok();
+
+            for (i, operand) in disassembled.operands.iter().enumerate() {
+                if i > 0 {
+                    result.push_str(", ");
+                }
+                write!(result, "{}: ", operand.name).ok();
+                render_operand_value(&mut result, &operand.value, &chunk);
+            }
+
+            let line_len = result.trim_end_matches(' ').len();
+            result.truncate(line_len);
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+impl Iterator for Disassembler {
+    type Item = DisassembledInstruction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ip = self.reader.ip;
+        let instruction = self.reader.next()?;
+        let end = self.reader.ip;
+
+        while matches!(self.function_ends.last(), Some(function_end) if *function_end <= ip) {
+            self.function_ends.pop();
+        }
+        let depth = self.function_ends.len();
+
+        let operands = operands(&instruction, end);
+
+        match instruction {
+            Instruction::SimpleFunction { size, .. } | Instruction::Function { size, .. } => {
+                self.function_ends.push(end + size)
+            }
+            // Stop after an error, given that the following bytes can't be decoded reliably
+            Instruction::Error { .. } => self.reader.ip = self.reader.chunk.bytes.len(),
+            _ => {}
+        }
+
+        Some(DisassembledInstruction {
+            ip,
+            size: end - ip,
+            span: self.reader.chunk.debug_info.get_source_span(ip),
+            instruction,
+            operands,
+            depth,
+        })
+    }
+}
+
+fn render_operand_value(result: &mut String, value: &OperandValue, chunk: &Chunk) {
+    match value {
+        OperandValue::Register(register) => write!(result, "r{}", register),
+        OperandValue::Constant(index) => match chunk.constants.get(usize::from(*index)) {
+            Some(Constant::F64(n)) => write!(result, "{} ({})", index, n),
+            Some(Constant::I64(n)) => write!(result, "{} ({})", index, n),
+            Some(Constant::Str(s)) => write!(result, "{} ({:?})", index, s),
+            None => write!(result, "{} (missing)", index),
+        },
+        OperandValue::Bool(b) => write!(result, "{}", b),
+        OperandValue::Int(n) => write!(result, "{}", n),
+        OperandValue::Count(n) => write!(result, "{}", n),
+        OperandValue::JumpTarget(target) => write!(result, "{}", target),
+        OperandValue::MetaKey(key) => write!(result, "{:?}", key),
+        OperandValue::Type(type_id) => write!(result, "{:?}", type_id),
+        OperandValue::JumpTableKey(key) => write!(result, "{:?}", key),
+        OperandValue::Message(message) => write!(result, "{:?}", message),
+        OperandValue::CoveragePoint(index) => {
+            match chunk.debug_info.coverage_points().get(*index as usize) {
+                Some(span) => write!(result, "{} (line {})", index, span.start.line),
+                None => write!(result, "{} (missing)", index),
+            }
+        }
+    }
+    .ok();
+}
+
+// Returns the operands of an instruction that ends at the given position
+pub(crate) fn operands(instruction: &Instruction, end: usize) -> Vec<Operand> {
+    use {Instruction::*, OperandValue::*};
+
+    macro_rules! operands {
+        ($($name:ident: $value:expr),* $(,)?) => {
+            vec![$(Operand {
+                name: stringify!($name),
+                value: $value,
+            }),*]
+        };
+    }
+
+    match instruction {
+        Error { message } => operands![message: Message(message.clone())],
+        Copy { target, source } => operands![target: Register(*target), source: Register(*source)],
+        SetEmpty { register }
+        | Import { register }
+        | RangeFull { register }
+        | Return { register }
+        | Yield { register }
+        | Throw { register }
+        | StringFinish { register } => operands![register: Register(*register)],
+        SetBool { register, value } => {
+            operands![register: Register(*register), value: Bool(*value)]
+        }
+        SetNumber { register, value } => {
+            operands![register: Register(*register), value: Int(*value)]
+        }
+        LoadFloat { register, constant }
+        | LoadInt { register, constant }
+        | LoadString { register, constant }
+        | LoadNonLocal { register, constant }
+        | Debug { register, constant }
+        | Assert { register, constant } => {
+            operands![register: Register(*register), constant: Constant(*constant)]
+        }
+        ValueExport { name, value } => operands![name: Register(*name), value: Register(*value)],
+        MakeTempTuple {
+            register,
+            start,
+            count,
+        } => operands![
+            register: Register(*register),
+            start: Register(*start),
+            count: Count(*count as usize),
+        ],
+        MakeMap {
+            register,
+            size_hint,
+        }
+        | SequenceStart {
+            register,
+            size_hint,
+        }
+        | StringStart {
+            register,
+            size_hint,
+        } => operands![register: Register(*register), size_hint: Count(*size_hint)],
+        MakeNum2 {
+            register,
+            element_register,
+            count,
+        }
+        | MakeNum4 {
+            register,
+            element_register,
+            count,
+        } => operands![
+            register: Register(*register),
+            element_register: Register(*element_register),
+            count: Count(*count as usize),
+        ],
+        SequencePush { sequence, value } => {
+            operands![sequence: Register(*sequence), value: Register(*value)]
+        }
+        SequencePushN {
+            sequence,
+            start,
+            count,
+        } => operands![
+            sequence: Register(*sequence),
+            start: Register(*start),
+            count: Count(*count as usize),
+        ],
+        SequenceToList { sequence } | SequenceToTuple { sequence } => {
+            operands![sequence: Register(*sequence)]
+        }
+        Range {
+            register,
+            start,
+            end,
+        }
+        | RangeInclusive {
+            register,
+            start,
+            end,
+        } => operands![
+            register: Register(*register),
+            start: Register(*start),
+            end: Register(*end),
+        ],
+        RangeTo { register, end } | RangeToInclusive { register, end } => {
+            operands![register: Register(*register), end: Register(*end)]
+        }
+        RangeFrom { register, start } => {
+            operands![register: Register(*register), start: Register(*start)]
+        }
+        MakeIterator { register, iterable } => {
+            operands![register: Register(*register), iterable: Register(*iterable)]
+        }
+        SimpleFunction {
+            register,
+            arg_count,
+            size,
+        } => operands![
+            register: Register(*register),
+            arg_count: Count(*arg_count as usize),
+            size: Count(*size),
+        ],
+        Function {
+            register,
+            arg_count,
+            capture_count,
+            instance_function,
+            variadic,
+            generator,
+            arg_is_unpacked_tuple,
+            size,
+        } => operands![
+            register: Register(*register),
+            arg_count: Count(*arg_count as usize),
+            capture_count: Count(*capture_count as usize),
+            instance_function: Bool(*instance_function),
+            variadic: Bool(*variadic),
+            generator: Bool(*generator),
+            arg_is_unpacked_tuple: Bool(*arg_is_unpacked_tuple),
+            size: Count(*size),
+        ],
+        Capture {
+            function,
+            target,
+            source,
+        } => operands![
+            function: Register(*function),
+            target: Count(*target as usize),
+            source: Register(*source),
+        ],
+        Negate { register, value }
+        | Not { register, value }
+        | Size { register, value }
+        | IsTuple { register, value }
+        | IsList { register, value }
+        | StringPush { register, value } => {
+            operands![register: Register(*register), value: Register(*value)]
+        }
+        Add { register, lhs, rhs }
+        | Subtract { register, lhs, rhs }
+        | Multiply { register, lhs, rhs }
+        | Divide { register, lhs, rhs }
+        | Modulo { register, lhs, rhs }
+        | Less { register, lhs, rhs }
+        | LessOrEqual { register, lhs, rhs }
+        | Greater { register, lhs, rhs }
+        | GreaterOrEqual { register, lhs, rhs }
+        | Equal { register, lhs, rhs }
+        | NotEqual { register, lhs, rhs }
+        | In { register, lhs, rhs } => operands![
+            register: Register(*register),
+            lhs: Register(*lhs),
+            rhs: Register(*rhs),
+        ],
+        AddU8 { register, lhs, rhs }
+        | SubtractU8 { register, lhs, rhs }
+        | LessU8 { register, lhs, rhs }
+        | LessOrEqualU8 { register, lhs, rhs }
+        | GreaterU8 { register, lhs, rhs }
+        | GreaterOrEqualU8 { register, lhs, rhs }
+        | EqualU8 { register, lhs, rhs }
+        | NotEqualU8 { register, lhs, rhs } => operands![
+            register: Register(*register),
+            lhs: Register(*lhs),
+            rhs: Int(*rhs as i64),
+        ],
+        Jump { offset } => operands![target: JumpTarget(end + offset)],
+        JumpIf {
+            register,
+            offset,
+            jump_condition,
+        } => operands![
+            register: Register(*register),
+            target: JumpTarget(end + offset),
+            jump_condition: Bool(*jump_condition),
+        ],
+        JumpBack { offset } => operands![target: JumpTarget(end.saturating_sub(*offset))],
+        Call {
+            result,
+            function,
+            frame_base,
+            arg_count,
+        }
+        | TailCall {
+            result,
+            function,
+            frame_base,
+            arg_count,
+        } => operands![
+            result: Register(*result),
+            function: Register(*function),
+            frame_base: Register(*frame_base),
+            arg_count: Count(*arg_count as usize),
+        ],
+        CallInstance {
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance,
+        }
+        | TailCallInstance {
+            result,
+            function,
+            frame_base,
+            arg_count,
+            instance,
+        } => operands![
+            result: Register(*result),
+            function: Register(*function),
+            frame_base: Register(*frame_base),
+            arg_count: Count(*arg_count as usize),
+            instance: Register(*instance),
+        ],
+        IterNext {
+            register,
+            iterator,
+            jump_offset,
+        }
+        | IterNextTemp {
+            register,
+            iterator,
+            jump_offset,
+        } => operands![
+            register: Register(*register),
+            iterator: Register(*iterator),
+            target: JumpTarget(end + jump_offset),
+        ],
+        IterNextQuiet {
+            iterator,
+            jump_offset,
+        } => operands![
+            iterator: Register(*iterator),
+            target: JumpTarget(end + jump_offset),
+        ],
+        TempIndex {
+            register,
+            value,
+            index,
+        }
+        | SliceFrom {
+            register,
+            value,
+            index,
+        }
+        | SliceTo {
+            register,
+            value,
+            index,
+        } => operands![
+            register: Register(*register),
+            value: Register(*value),
+            index: Int(*index as i64),
+        ],
+        Index {
+            register,
+            value,
+            index,
+        } => operands![
+            register: Register(*register),
+            value: Register(*value),
+            index: Register(*index),
+        ],
+        SetIndex {
+            register,
+            index,
+            value,
+        } => operands![
+            register: Register(*register),
+            index: Register(*index),
+            value: Register(*value),
+        ],
+        MapInsert {
+            register,
+            key,
+            value,
+        } => operands![
+            register: Register(*register),
+            key: Register(*key),
+            value: Register(*value),
+        ],
+        MetaInsert {
+            register,
+            value,
+            id,
+        } => operands![
+            register: Register(*register),
+            value: Register(*value),
+            id: MetaKey(*id),
+        ],
+        MetaInsertNamed {
+            register,
+            value,
+            id,
+            name,
+        } => operands![
+            register: Register(*register),
+            value: Register(*value),
+            id: MetaKey(*id),
+            name: Register(*name),
+        ],
+        MetaExport { id, value } => operands![id: MetaKey(*id), value: Register(*value)],
+        MetaExportNamed { id, name, value } => operands![
+            id: MetaKey(*id),
+            name: Register(*name),
+            value: Register(*value),
+        ],
+        Access {
+            register,
+            value,
+            key,
+        } => operands![
+            register: Register(*register),
+            value: Register(*value),
+            key: Constant(*key),
+        ],
+        AccessString {
+            register,
+            value,
+            key,
+        } => operands![
+            register: Register(*register),
+            value: Register(*value),
+            key: Register(*key),
+        ],
+        TryStart {
+            arg_register,
+            catch_offset,
+        } => operands![
+            arg_register: Register(*arg_register),
+            target: JumpTarget(end + catch_offset),
+        ],
+        TryEnd => operands![],
+        Coverage { index } => operands![index: CoveragePoint(*index)],
+        AssertComparison {
+            register,
+            lhs,
+            rhs,
+            constant,
+        } => operands![
+            register: Register(*register),
+            lhs: Register(*lhs),
+            rhs: Register(*rhs),
+            constant: Constant(*constant),
+        ],
+        CheckType { register, type_id } => {
+            operands![register: Register(*register), type_id: Type(*type_id)]
+        }
+        CheckSize { register, size } => {
+            operands![register: Register(*register), size: Count(*size)]
+        }
+        JumpTable {
+            register,
+            key_type,
+            entry_count,
+            default_offset,
+            ..
+        } => operands![
+            register: Register(*register),
+            key_type: JumpTableKey(*key_type),
+            entry_count: Count(*entry_count),
+            target: JumpTarget(end + default_offset),
+        ],
+        CheckTypeAnnotation {
+            register,
+            type_name,
+        } => operands![
+            register: Register(*register),
+            type_name: Constant(*type_name),
+        ],
+    }
+}
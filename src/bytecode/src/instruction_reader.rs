@@ -4,7 +4,14 @@ use {
     std::{convert::TryInto, fmt, sync::Arc},
 };
 
-#[derive(Debug)]
+/// The largest size hint that's used by the `MakeMap`, `SequenceStart`, and `StringStart`
+/// instructions
+///
+/// Size hints are only used to preallocate capacity, so the compiler limits them to this value,
+/// and [Chunk::verify] rejects chunks that contain larger hints.
+pub const MAX_SIZE_HINT: usize = u16::MAX as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 #[allow(missing_docs)]
 pub enum TypeId {
@@ -24,6 +31,35 @@ impl TypeId {
     }
 }
 
+/// The type of key used in a [JumpTable](crate::Op::JumpTable)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum JumpTableKey {
+    Int,
+    Str,
+}
+
+impl JumpTableKey {
+    /// The size in bytes of a table entry, including the entry's u16 offset
+    pub fn entry_size(&self) -> usize {
+        match self {
+            Self::Int => 8 + 2,
+            Self::Str => 4 + 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, u8> {
+        if byte == Self::Int as u8 {
+            Ok(Self::Int)
+        } else if byte == Self::Str as u8 {
+            Ok(Self::Str)
+        } else {
+            Err(byte)
+        }
+    }
+}
+
 /// Flags used to define the properties of a Function
 pub struct FunctionFlags {
     /// True if the function is an instance function
@@ -75,124 +111,117 @@ impl FunctionFlags {
     }
 }
 
-/// Decoded instructions produced by an [InstructionReader] for execution in the runtime
+/// The instructions that make up the Koto VM's instruction set
 ///
-/// For descriptions of each instruction's purpose, see corresponding [Op] entries.
-#[allow(missing_docs)]
+/// Instructions are decoded from bytecode by an [InstructionReader] for execution in the
+/// runtime, and can be encoded into bytecode with a [ChunkBuilder](crate::ChunkBuilder).
+///
+/// Each instruction corresponds to one or more [Op]s, with the encoded form being chosen based on
+/// the instruction's values, e.g. a [LoadInt](Instruction::LoadInt) with a constant index larger
+/// than 255 is encoded as [Op::LoadInt16].
+///
+/// Fields share the following naming conventions:
+///   - `register` is the register that receives the instruction's result, or for instructions
+///     that don't produce a result, the register containing the value that's being operated on.
+///   - `value`, `lhs`, `rhs`, `start`, `end`, etc. are registers containing the instruction's
+///     input values.
+///   - `constant`, `key`, and `type_name` are indices into the chunk's
+///     [ConstantPool](koto_parser::ConstantPool).
+///   - Offsets and sizes are in bytes, relative to the end of the instruction.
+#[allow(missing_docs)] // Fields are described by the conventions above and the variant docs
 pub enum Instruction {
-    Error {
-        message: String,
-    },
-    Copy {
-        target: u8,
-        source: u8,
-    },
-    SetEmpty {
-        register: u8,
-    },
-    SetBool {
-        register: u8,
-        value: bool,
-    },
-    SetNumber {
-        register: u8,
-        value: i64,
-    },
+    /// An error that occurred while decoding the bytecode
+    ///
+    /// This is only produced by the [InstructionReader], and can't be added to a
+    /// [ChunkBuilder](crate::ChunkBuilder).
+    Error { message: String },
+    /// Copies the value in the `source` register to the `target` register
+    Copy { target: u8, source: u8 },
+    /// Sets the register to contain Empty
+    SetEmpty { register: u8 },
+    /// Sets the register to contain a Bool
+    SetBool { register: u8, value: bool },
+    /// Sets the register to contain an Int
+    ///
+    /// Only values in the range `0..=255` can be encoded, larger values are loaded with
+    /// [LoadInt](Instruction::LoadInt).
+    SetNumber { register: u8, value: i64 },
+    /// Loads an f64 constant into the register
     LoadFloat {
         register: u8,
         constant: ConstantIndex,
     },
+    /// Loads an i64 constant into the register
     LoadInt {
         register: u8,
         constant: ConstantIndex,
     },
+    /// Loads a string constant into the register
     LoadString {
         register: u8,
         constant: ConstantIndex,
     },
+    /// Loads the non-local value with the name in the string constant into the register
     LoadNonLocal {
         register: u8,
         constant: ConstantIndex,
     },
-    ValueExport {
-        name: u8,
-        value: u8,
-    },
-    Import {
-        register: u8,
-    },
-    MakeTempTuple {
-        register: u8,
-        start: u8,
-        count: u8,
-    },
-    MakeMap {
-        register: u8,
-        size_hint: usize,
-    },
+    /// Exports the value, using the string in the `name` register as the export's name
+    ValueExport { name: u8, value: u8 },
+    /// Imports the value named by the string in the register, replacing the name with the result
+    Import { register: u8 },
+    /// Makes a temporary tuple from `count` values in consecutive registers from `start`
+    MakeTempTuple { register: u8, start: u8, count: u8 },
+    /// Makes an empty map with the given size hint
+    MakeMap { register: u8, size_hint: usize },
+    /// Makes a Num2 from `count` values in consecutive registers from `element_register`
     MakeNum2 {
         register: u8,
         element_register: u8,
         count: u8,
     },
+    /// Makes a Num4 from `count` values in consecutive registers from `element_register`
     MakeNum4 {
         register: u8,
         element_register: u8,
         count: u8,
     },
-    SequenceStart {
-        register: u8,
-        size_hint: usize,
-    },
-    SequencePush {
-        sequence: u8,
-        value: u8,
-    },
-    SequencePushN {
-        sequence: u8,
-        start: u8,
-        count: u8,
-    },
-    SequenceToList {
-        sequence: u8,
-    },
-    SequenceToTuple {
-        sequence: u8,
-    },
-    Range {
-        register: u8,
-        start: u8,
-        end: u8,
-    },
-    RangeInclusive {
-        register: u8,
-        start: u8,
-        end: u8,
-    },
-    RangeTo {
-        register: u8,
-        end: u8,
-    },
-    RangeToInclusive {
-        register: u8,
-        end: u8,
-    },
-    RangeFrom {
-        register: u8,
-        start: u8,
-    },
-    RangeFull {
-        register: u8,
-    },
-    MakeIterator {
-        register: u8,
-        iterable: u8,
-    },
+    /// Starts a sequence builder (for a List or Tuple) with the given size hint
+    SequenceStart { register: u8, size_hint: usize },
+    /// Pushes the value to the end of the sequence builder
+    SequencePush { sequence: u8, value: u8 },
+    /// Pushes `count` values in consecutive registers from `start` to the sequence builder
+    SequencePushN { sequence: u8, start: u8, count: u8 },
+    /// Converts the sequence builder into a List
+    SequenceToList { sequence: u8 },
+    /// Converts the sequence builder into a Tuple
+    SequenceToTuple { sequence: u8 },
+    /// Makes a Range from `start` to `end`
+    Range { register: u8, start: u8, end: u8 },
+    /// Makes an inclusive Range from `start` to `end`
+    RangeInclusive { register: u8, start: u8, end: u8 },
+    /// Makes a Range with no start, up to `end`
+    RangeTo { register: u8, end: u8 },
+    /// Makes an inclusive Range with no start, up to `end`
+    RangeToInclusive { register: u8, end: u8 },
+    /// Makes a Range with no end, starting from `start`
+    RangeFrom { register: u8, start: u8 },
+    /// Makes a Range with no start or end
+    RangeFull { register: u8 },
+    /// Makes an Iterator from the iterable value
+    MakeIterator { register: u8, iterable: u8 },
+    /// Makes a function that has no captures or special properties
+    ///
+    /// The `size` bytes following the instruction make up the function's body.
     SimpleFunction {
         register: u8,
         arg_count: u8,
         size: usize,
     },
+    /// Makes a function with captures and extended properties
+    ///
+    /// The `size` bytes following the instruction make up the function's body.
+    /// Captures are added to the function with [Capture](Instruction::Capture).
     Function {
         register: u8,
         arg_count: u8,
@@ -203,91 +232,74 @@ pub enum Instruction {
         arg_is_unpacked_tuple: bool,
         size: usize,
     },
+    /// Copies the value in the `source` register into the function's captures at index `target`
     Capture {
         function: u8,
         target: u8,
         source: u8,
     },
-    Negate {
-        register: u8,
-        value: u8,
-    },
-    Not {
-        register: u8,
-        value: u8,
-    },
-    Add {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Subtract {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Multiply {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Divide {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Modulo {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Less {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    LessOrEqual {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Greater {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    GreaterOrEqual {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Equal {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    NotEqual {
-        register: u8,
-        lhs: u8,
-        rhs: u8,
-    },
-    Jump {
-        offset: usize,
-    },
+    /// Negates the value
+    Negate { register: u8, value: u8 },
+    /// Flips the value of a boolean
+    Not { register: u8, value: u8 },
+    /// Adds `lhs` and `rhs` together
+    Add { register: u8, lhs: u8, rhs: u8 },
+    /// Subtracts `rhs` from `lhs`
+    Subtract { register: u8, lhs: u8, rhs: u8 },
+    /// Multiplies `lhs` and `rhs` together
+    Multiply { register: u8, lhs: u8, rhs: u8 },
+    /// Divides `lhs` by `rhs`
+    Divide { register: u8, lhs: u8, rhs: u8 },
+    /// Performs the modulo operation with `lhs` and `rhs`
+    Modulo { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and `rhs` using the `<` operator
+    Less { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and `rhs` using the `<=` operator
+    LessOrEqual { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and `rhs` using the `>` operator
+    Greater { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and `rhs` using the `>=` operator
+    GreaterOrEqual { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and `rhs` using the `==` operator
+    Equal { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and `rhs` using the `!=` operator
+    NotEqual { register: u8, lhs: u8, rhs: u8 },
+    /// Checks if `lhs` is contained in `rhs` using the `in` operator
+    In { register: u8, lhs: u8, rhs: u8 },
+    /// Adds the small integer `rhs` to `lhs`
+    AddU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Subtracts the small integer `rhs` from `lhs`
+    SubtractU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and the small integer `rhs` using the `<` operator
+    LessU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and the small integer `rhs` using the `<=` operator
+    LessOrEqualU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and the small integer `rhs` using the `>` operator
+    GreaterU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and the small integer `rhs` using the `>=` operator
+    GreaterOrEqualU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and the small integer `rhs` using the `==` operator
+    EqualU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Compares `lhs` and the small integer `rhs` using the `!=` operator
+    NotEqualU8 { register: u8, lhs: u8, rhs: u8 },
+    /// Jumps forward by `offset` bytes
+    Jump { offset: usize },
+    /// Jumps forward by `offset` bytes if the value's truthiness matches `jump_condition`
     JumpIf {
         register: u8,
         offset: usize,
         jump_condition: bool,
     },
-    JumpBack {
-        offset: usize,
-    },
+    /// Jumps back by `offset` bytes
+    JumpBack { offset: usize },
+    /// Calls the function with `arg_count` arguments, starting in the `frame_base` register
     Call {
         result: u8,
         function: u8,
         frame_base: u8,
         arg_count: u8,
     },
+    /// Calls the function as an instance function, with `instance` as `self`
     CallInstance {
         result: u8,
         function: u8,
@@ -295,136 +307,147 @@ pub enum Instruction {
         arg_count: u8,
         instance: u8,
     },
-    Return {
-        register: u8,
-    },
-    Yield {
-        register: u8,
-    },
-    Throw {
-        register: u8,
+    /// Calls the function in return position, see [Op::TailCall](crate::Op::TailCall)
+    TailCall {
+        result: u8,
+        function: u8,
+        frame_base: u8,
+        arg_count: u8,
     },
-    Size {
-        register: u8,
-        value: u8,
+    /// Calls the function in return position as an instance function, with `instance` as `self`
+    TailCallInstance {
+        result: u8,
+        function: u8,
+        frame_base: u8,
+        arg_count: u8,
+        instance: u8,
     },
+    /// Returns the value from the current frame
+    Return { register: u8 },
+    /// Yields the value from the current generator
+    Yield { register: u8 },
+    /// Throws the value as an error
+    Throw { register: u8 },
+    /// Gets the size of the value
+    Size { register: u8, value: u8 },
+    /// Gets the next value from the iterator, or jumps forward by `jump_offset` when finished
     IterNext {
         register: u8,
         iterator: u8,
         jump_offset: usize,
     },
+    /// Like [IterNext](Instruction::IterNext), with the output being treated as temporary
     IterNextTemp {
         register: u8,
         iterator: u8,
         jump_offset: usize,
     },
-    IterNextQuiet {
-        iterator: u8,
-        jump_offset: usize,
-    },
-    TempIndex {
-        register: u8,
-        value: u8,
-        index: i8,
-    },
-    SliceFrom {
-        register: u8,
-        value: u8,
-        index: i8,
-    },
-    SliceTo {
-        register: u8,
-        value: u8,
-        index: i8,
-    },
-    IsTuple {
-        register: u8,
-        value: u8,
-    },
-    IsList {
-        register: u8,
-        value: u8,
-    },
-    Index {
-        register: u8,
-        value: u8,
-        index: u8,
-    },
-    SetIndex {
-        register: u8,
-        index: u8,
-        value: u8,
-    },
-    MapInsert {
-        register: u8,
-        key: u8,
-        value: u8,
-    },
+    /// Like [IterNext](Instruction::IterNext), with the iterator's output being discarded
+    IterNextQuiet { iterator: u8, jump_offset: usize },
+    /// Accesses a contained value in a temporary value using a fixed index
+    TempIndex { register: u8, value: u8, index: i8 },
+    /// Takes a slice from a List or Tuple, starting from the index
+    SliceFrom { register: u8, value: u8, index: i8 },
+    /// Takes a slice from a List or Tuple, ending at the index
+    SliceTo { register: u8, value: u8, index: i8 },
+    /// Sets the register to true if the value is a Tuple
+    IsTuple { register: u8, value: u8 },
+    /// Sets the register to true if the value is a List
+    IsList { register: u8, value: u8 },
+    /// Accesses a contained value via the value in the `index` register
+    Index { register: u8, value: u8, index: u8 },
+    /// Sets a contained value in the indexable value in `register`
+    SetIndex { register: u8, index: u8, value: u8 },
+    /// Inserts a key/value entry into the map
+    MapInsert { register: u8, key: u8, value: u8 },
+    /// Inserts an entry into the map's metamap
     MetaInsert {
         register: u8,
         value: u8,
         id: MetaKeyId,
     },
+    /// Inserts a named entry into the map's metamap, e.g. `@test foo`
     MetaInsertNamed {
         register: u8,
         value: u8,
         id: MetaKeyId,
         name: u8,
     },
-    MetaExport {
-        id: MetaKeyId,
-        value: u8,
-    },
-    MetaExportNamed {
-        id: MetaKeyId,
-        name: u8,
-        value: u8,
-    },
+    /// Adds an entry to the module's exported metamap
+    MetaExport { id: MetaKeyId, value: u8 },
+    /// Adds a named entry to the module's exported metamap
+    MetaExportNamed { id: MetaKeyId, name: u8, value: u8 },
+    /// Accesses a contained value via a string constant key
     Access {
         register: u8,
         value: u8,
         key: ConstantIndex,
     },
-    AccessString {
-        register: u8,
-        value: u8,
-        key: u8,
-    },
+    /// Accesses a contained value via the string in the `key` register
+    AccessString { register: u8, value: u8, key: u8 },
+    /// Starts a try block
+    ///
+    /// If an error is thrown, the error is placed in `arg_register` and execution jumps forward
+    /// by `catch_offset` bytes.
     TryStart {
         arg_register: u8,
         catch_offset: usize,
     },
+    /// Ends the current try block
     TryEnd,
+    /// Records that the coverage point with the given index has been reached
+    Coverage { index: u32 },
+    /// Displays the value along with the expression in the string constant
     Debug {
         register: u8,
         constant: ConstantIndex,
     },
-    CheckType {
+    /// Throws an error if the value is false, using the expression in the string constant
+    Assert {
         register: u8,
-        type_id: TypeId,
+        constant: ConstantIndex,
     },
-    CheckSize {
+    /// Throws an error if the comparison's result is false, including the compared values
+    AssertComparison {
         register: u8,
-        size: usize,
+        lhs: u8,
+        rhs: u8,
+        constant: ConstantIndex,
     },
-    StringStart {
+    /// Throws an error if the value doesn't have the expected type
+    CheckType { register: u8, type_id: TypeId },
+    /// Throws an error if the value doesn't have the expected size
+    CheckSize { register: u8, size: usize },
+    /// Jumps to the entry in a table of literal keys that matches the value
+    ///
+    /// The table's entries are stored in the bytecode starting at `table_ip`.
+    /// This is only produced by the compiler, and can't be added to a
+    /// [ChunkBuilder](crate::ChunkBuilder).
+    JumpTable {
         register: u8,
-        size_hint: usize,
+        key_type: JumpTableKey,
+        entry_count: usize,
+        default_offset: usize,
+        table_ip: usize,
     },
-    StringPush {
+    /// Throws an error if the value doesn't match the type named in the string constant
+    CheckTypeAnnotation {
         register: u8,
-        value: u8,
-    },
-    StringFinish {
-        register: u8,
-    },
+        type_name: ConstantIndex,
+    },
+    /// Starts a string builder with the given size hint
+    StringStart { register: u8, size_hint: usize },
+    /// Pushes the value to the end of the string builder
+    StringPush { register: u8, value: u8 },
+    /// Replaces the string builder with the finished string
+    StringFinish { register: u8 },
 }
 
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Instruction::*;
         match self {
-            Error { message } => unreachable!(message),
+            Error { .. } => write!(f, "Error"),
             Copy { .. } => write!(f, "Copy"),
             SetEmpty { .. } => write!(f, "SetEmpty"),
             SetBool { .. } => write!(f, "SetBool"),
@@ -470,11 +493,22 @@ impl fmt::Display for Instruction {
             GreaterOrEqual { .. } => write!(f, "GreaterOrEqual"),
             Equal { .. } => write!(f, "Equal"),
             NotEqual { .. } => write!(f, "NotEqual"),
+            In { .. } => write!(f, "In"),
+            AddU8 { .. } => write!(f, "AddU8"),
+            SubtractU8 { .. } => write!(f, "SubtractU8"),
+            LessU8 { .. } => write!(f, "LessU8"),
+            LessOrEqualU8 { .. } => write!(f, "LessOrEqualU8"),
+            GreaterU8 { .. } => write!(f, "GreaterU8"),
+            GreaterOrEqualU8 { .. } => write!(f, "GreaterOrEqualU8"),
+            EqualU8 { .. } => write!(f, "EqualU8"),
+            NotEqualU8 { .. } => write!(f, "NotEqualU8"),
             Jump { .. } => write!(f, "Jump"),
             JumpIf { .. } => write!(f, "JumpIf"),
             JumpBack { .. } => write!(f, "JumpBack"),
             Call { .. } => write!(f, "Call"),
             CallInstance { .. } => write!(f, "CallInstance"),
+            TailCall { .. } => write!(f, "TailCall"),
+            TailCallInstance { .. } => write!(f, "TailCallInstance"),
             Return { .. } => write!(f, "Return"),
             Yield { .. } => write!(f, "Yield"),
             Throw { .. } => write!(f, "Throw"),
@@ -498,9 +532,14 @@ impl fmt::Display for Instruction {
             AccessString { .. } => write!(f, "AccessString"),
             TryStart { .. } => write!(f, "TryStart"),
             TryEnd => write!(f, "TryEnd"),
+            Coverage { .. } => write!(f, "Coverage"),
             Debug { .. } => write!(f, "Debug"),
+            Assert { .. } => write!(f, "Assert"),
+            AssertComparison { .. } => write!(f, "AssertComparison"),
             CheckType { .. } => write!(f, "CheckType"),
             CheckSize { .. } => write!(f, "CheckSize"),
+            JumpTable { .. } => write!(f, "JumpTable"),
+            CheckTypeAnnotation { .. } => write!(f, "CheckTypeAnnotation"),
         }
     }
 }
@@ -509,7 +548,7 @@ impl fmt::Debug for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Instruction::*;
         match self {
-            Error { message } => unreachable!(message),
+            Error { message } => write!(f, "Error\t\tmessage: {}", message),
             Copy { target, source } => write!(f, "Copy\t\tresult: {}\tsource: {}", target, source),
             SetEmpty { register } => write!(f, "SetEmpty\tresult: {}", register),
             SetBool { register, value } => {
@@ -728,6 +767,51 @@ impl fmt::Debug for Instruction {
                 "NotEqual\tresult: {}\tlhs: {}\t\trhs: {}",
                 register, lhs, rhs
             ),
+            In { register, lhs, rhs } => write!(
+                f,
+                "In\t\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            AddU8 { register, lhs, rhs } => write!(
+                f,
+                "AddU8\t\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            SubtractU8 { register, lhs, rhs } => write!(
+                f,
+                "SubtractU8\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            LessU8 { register, lhs, rhs } => write!(
+                f,
+                "LessU8\t\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            LessOrEqualU8 { register, lhs, rhs } => write!(
+                f,
+                "LessOrEqualU8\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            GreaterU8 { register, lhs, rhs } => write!(
+                f,
+                "GreaterU8\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            GreaterOrEqualU8 { register, lhs, rhs } => write!(
+                f,
+                "GreaterOrEqualU8\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            EqualU8 { register, lhs, rhs } => write!(
+                f,
+                "EqualU8\t\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
+            NotEqualU8 { register, lhs, rhs } => write!(
+                f,
+                "NotEqualU8\tresult: {}\tlhs: {}\t\trhs: {}",
+                register, lhs, rhs
+            ),
             Jump { offset } => write!(f, "Jump\t\toffset: {}", offset),
             JumpIf {
                 register,
@@ -761,6 +845,28 @@ impl fmt::Debug for Instruction {
                  \t\t\targs: {}\t\tinstance: {}",
                 result, function, frame_base, arg_count, instance
             ),
+            TailCall {
+                result,
+                function,
+                frame_base,
+                arg_count,
+            } => write!(
+                f,
+                "TailCall\tresult: {}\tfunction: {}\tframe base: {}\targs: {}",
+                result, function, frame_base, arg_count
+            ),
+            TailCallInstance {
+                result,
+                function,
+                frame_base,
+                arg_count,
+                instance,
+            } => write!(
+                f,
+                "TailCallInstance\tresult: {}\tfunction: {}\tframe_base: {}
+                 \t\t\targs: {}\t\tinstance: {}",
+                result, function, frame_base, arg_count, instance
+            ),
             Return { register } => write!(f, "Return\t\tresult: {}", register),
             Yield { register } => write!(f, "Yield\t\tresult: {}", register),
             Throw { register } => write!(f, "Throw\t\tresult: {}", register),
@@ -903,15 +1009,52 @@ impl fmt::Debug for Instruction {
                 arg_register, catch_offset
             ),
             TryEnd => write!(f, "TryEnd"),
+            Coverage { index } => write!(f, "Coverage\tindex: {}", index),
             Debug { register, constant } => {
                 write!(f, "Debug\t\tregister: {}\tconstant: {}", register, constant)
             }
+            Assert { register, constant } => {
+                write!(
+                    f,
+                    "Assert\t\tregister: {}\tconstant: {}",
+                    register, constant
+                )
+            }
+            AssertComparison {
+                register,
+                lhs,
+                rhs,
+                constant,
+            } => write!(
+                f,
+                "AssertComparison\tregister: {}\tlhs: {}\trhs: {}\tconstant: {}",
+                register, lhs, rhs, constant
+            ),
             CheckType { register, type_id } => {
                 write!(f, "CheckType\tregister: {}\ttype: {:?}", register, type_id)
             }
             CheckSize { register, size } => {
                 write!(f, "CheckSize\tregister: {}\tsize: {}", register, size)
             }
+            JumpTable {
+                register,
+                key_type,
+                entry_count,
+                default_offset,
+                ..
+            } => write!(
+                f,
+                "JumpTable\tregister: {}\tkey type: {:?}\tentries: {}\tdefault offset: {}",
+                register, key_type, entry_count, default_offset
+            ),
+            CheckTypeAnnotation {
+                register,
+                type_name,
+            } => write!(
+                f,
+                "CheckTypeAnnotation\tregister: {}\ttype: {}",
+                register, type_name
+            ),
             StringStart {
                 register,
                 size_hint,
@@ -1134,6 +1277,10 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 constant: get_u24_constant!(),
             }),
+            Op::LoadFloat32 => Some(LoadFloat {
+                register: get_u8!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
             Op::LoadInt => Some(LoadInt {
                 register: get_u8!(),
                 constant: ConstantIndex::from(get_u8!()),
@@ -1146,6 +1293,10 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 constant: get_u24_constant!(),
             }),
+            Op::LoadInt32 => Some(LoadInt {
+                register: get_u8!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
             Op::LoadString => Some(LoadString {
                 register: get_u8!(),
                 constant: ConstantIndex::from(get_u8!()),
@@ -1158,6 +1309,10 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 constant: get_u24_constant!(),
             }),
+            Op::LoadString32 => Some(LoadString {
+                register: get_u8!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
             Op::LoadNonLocal => Some(LoadNonLocal {
                 register: get_u8!(),
                 constant: ConstantIndex::from(get_u8!()),
@@ -1170,6 +1325,10 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 constant: get_u24_constant!(),
             }),
+            Op::LoadNonLocal32 => Some(LoadNonLocal {
+                register: get_u8!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
             Op::ValueExport => Some(ValueExport {
                 name: get_u8!(),
                 value: get_u8!(),
@@ -1349,6 +1508,51 @@ impl Iterator for InstructionReader {
                 lhs: get_u8!(),
                 rhs: get_u8!(),
             }),
+            Op::In => Some(In {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::AddU8 => Some(AddU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::SubtractU8 => Some(SubtractU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::LessU8 => Some(LessU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::LessOrEqualU8 => Some(LessOrEqualU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::GreaterU8 => Some(GreaterU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::GreaterOrEqualU8 => Some(GreaterOrEqualU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::EqualU8 => Some(EqualU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
+            Op::NotEqualU8 => Some(NotEqualU8 {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+            }),
             Op::Jump => Some(Jump {
                 offset: get_u16!() as usize,
             }),
@@ -1378,6 +1582,19 @@ impl Iterator for InstructionReader {
                 arg_count: get_u8!(),
                 instance: get_u8!(),
             }),
+            Op::TailCall => Some(TailCall {
+                result: get_u8!(),
+                function: get_u8!(),
+                frame_base: get_u8!(),
+                arg_count: get_u8!(),
+            }),
+            Op::TailCallInstance => Some(TailCallInstance {
+                result: get_u8!(),
+                function: get_u8!(),
+                frame_base: get_u8!(),
+                arg_count: get_u8!(),
+                instance: get_u8!(),
+            }),
             Op::Return => Some(Return {
                 register: get_u8!(),
             }),
@@ -1527,6 +1744,11 @@ impl Iterator for InstructionReader {
                 value: get_u8!(),
                 key: get_u24_constant!(),
             }),
+            Op::Access32 => Some(Access {
+                register: get_u8!(),
+                value: get_u8!(),
+                key: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
             Op::AccessString => Some(AccessString {
                 register: get_u8!(),
                 value: get_u8!(),
@@ -1537,9 +1759,20 @@ impl Iterator for InstructionReader {
                 catch_offset: get_u16!() as usize,
             }),
             Op::TryEnd => Some(TryEnd),
+            Op::Coverage => Some(Coverage { index: get_u32!() }),
             Op::Debug => Some(Debug {
                 register: get_u8!(),
-                constant: get_u24_constant!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
+            Op::Assert => Some(Assert {
+                register: get_u8!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
+            Op::AssertComparison => Some(AssertComparison {
+                register: get_u8!(),
+                lhs: get_u8!(),
+                rhs: get_u8!(),
+                constant: ConstantIndex::from(get_u32!().to_le_bytes()),
             }),
             Op::CheckType => {
                 let register = get_u8!();
@@ -1554,6 +1787,33 @@ impl Iterator for InstructionReader {
                 register: get_u8!(),
                 size: get_u8!() as usize,
             }),
+            Op::JumpTable => {
+                let register = get_u8!();
+                match JumpTableKey::from_byte(get_u8!()) {
+                    Ok(key_type) => {
+                        let entry_count = get_u16!() as usize;
+                        let default_offset = get_u16!() as usize;
+                        let table_ip = self.ip;
+                        // Skip over the table's entries,
+                        // they're read by the runtime when the instruction is executed.
+                        self.ip += entry_count * key_type.entry_size();
+                        Some(JumpTable {
+                            register,
+                            key_type,
+                            entry_count,
+                            default_offset,
+                            table_ip,
+                        })
+                    }
+                    Err(byte) => Some(Error {
+                        message: format!("Unexpected value for JumpTable key type: {}", byte),
+                    }),
+                }
+            }
+            Op::CheckTypeAnnotation => Some(CheckTypeAnnotation {
+                register: get_u8!(),
+                type_name: ConstantIndex::from(get_u32!().to_le_bytes()),
+            }),
             Op::StringStart => Some(StringStart {
                 register: get_u8!(),
                 size_hint: get_u8!() as usize,
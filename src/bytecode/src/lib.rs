@@ -5,15 +5,29 @@
 #![warn(missing_docs)]
 
 mod chunk;
+mod chunk_builder;
+mod chunk_format;
+mod chunk_stats;
+mod chunk_verifier;
 mod compiler;
+mod constant_folding;
+mod disassembler;
 mod instruction_reader;
 mod loader;
 mod op;
+mod peephole;
 
 pub use {
     chunk::{Chunk, DebugInfo},
-    compiler::{Compiler, CompilerError, CompilerSettings},
-    instruction_reader::{FunctionFlags, Instruction, InstructionReader, TypeId},
-    loader::{Loader, LoaderError},
+    chunk_builder::{ChunkBuilder, ChunkBuilderError, Label},
+    chunk_format::ChunkFormatError,
+    chunk_stats::{ChunkStats, FunctionStats},
+    chunk_verifier::ChunkVerifierError,
+    compiler::{Compiler, CompilerError, CompilerSettings, CompilerWarning},
+    disassembler::{DisassembledInstruction, Disassembler, Operand, OperandValue},
+    instruction_reader::{
+        FunctionFlags, Instruction, InstructionReader, JumpTableKey, TypeId, MAX_SIZE_HINT,
+    },
+    loader::{Loader, LoaderError, LoaderWarning, ModuleResolver, ResolvedModule},
     op::Op,
 };
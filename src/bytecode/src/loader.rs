@@ -1,8 +1,16 @@
 use {
-    crate::{Chunk, Compiler, CompilerError, CompilerSettings},
+    crate::{
+        constant_folding, Chunk, ChunkFormatError, ChunkVerifierError, Compiler, CompilerError,
+        CompilerSettings, CompilerWarning,
+    },
     dunce::canonicalize,
-    koto_parser::{format_error_with_excerpt, Parser, ParserError},
-    std::{collections::HashMap, error, fmt, path::PathBuf, sync::Arc},
+    koto_parser::{format_error_with_excerpt, Ast, ExcerptOptions, Parser, ParserError},
+    std::{
+        collections::HashMap,
+        error, fmt,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
 };
 
 /// Errors that can be returned from [Loader] operations
@@ -11,6 +19,8 @@ use {
 pub enum LoaderErrorType {
     Parser(ParserError),
     Compiler(CompilerError),
+    Chunk(ChunkFormatError),
+    Verifier(ChunkVerifierError),
     Io(String),
 }
 
@@ -47,6 +57,28 @@ impl LoaderError {
         }
     }
 
+    pub(crate) fn from_chunk_format_error(
+        error: ChunkFormatError,
+        source_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            error: LoaderErrorType::Chunk(error),
+            source: "".into(),
+            source_path,
+        }
+    }
+
+    pub(crate) fn from_chunk_verifier_error(
+        error: ChunkVerifierError,
+        source_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            error: LoaderErrorType::Verifier(error),
+            source: "".into(),
+            source_path,
+        }
+    }
+
     pub(crate) fn io_error(error: String) -> Self {
         Self {
             error: LoaderErrorType::Io(error),
@@ -64,6 +96,43 @@ impl LoaderError {
     }
 }
 
+impl LoaderError {
+    /// Formats the error with a source excerpt, using the provided formatting options
+    ///
+    /// The error's `Display` implementation uses the default options.
+    pub fn format_with_options(&self, options: &ExcerptOptions) -> String {
+        use LoaderErrorType::*;
+
+        match &self.error {
+            Parser(koto_parser::ParserError { error, span, .. }) => format_error_with_excerpt(
+                Some(&error.to_string()),
+                &self.source_path,
+                &self.source,
+                span.start,
+                span.end,
+                options,
+            ),
+            Compiler(crate::CompilerError { message, span, .. }) => format_error_with_excerpt(
+                Some(message),
+                &self.source_path,
+                &self.source,
+                span.start,
+                span.end,
+                options,
+            ),
+            Chunk(e) => match &self.source_path {
+                Some(path) => format!("{} - {}", path.to_string_lossy(), e),
+                None => e.to_string(),
+            },
+            Verifier(e) => match &self.source_path {
+                Some(path) => format!("{} - Invalid bytecode: {}", path.to_string_lossy(), e),
+                None => format!("Invalid bytecode: {}", e),
+            },
+            Io(e) => e.clone(),
+        }
+    }
+}
+
 impl fmt::Display for LoaderError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use LoaderErrorType::*;
@@ -72,72 +141,310 @@ impl fmt::Display for LoaderError {
             match &self.error {
                 Parser(koto_parser::ParserError { error, .. }) => f.write_str(&error.to_string()),
                 Compiler(crate::CompilerError { message, .. }) => f.write_str(message),
+                Chunk(e) => write!(f, "{}", e),
+                Verifier(e) => write!(f, "Invalid bytecode: {}", e),
                 Io(e) => f.write_str(e),
             }
         } else {
-            match &self.error {
-                Parser(koto_parser::ParserError { error, span }) => {
-                    f.write_str(&format_error_with_excerpt(
-                        Some(&error.to_string()),
-                        &self.source_path,
-                        &self.source,
-                        span.start,
-                        span.end,
-                    ))
-                }
-                Compiler(crate::CompilerError { message, span }) => {
-                    f.write_str(&format_error_with_excerpt(
-                        Some(message),
-                        &self.source_path,
-                        &self.source,
-                        span.start,
-                        span.end,
-                    ))
-                }
-                Io(e) => f.write_str(e),
-            }
+            f.write_str(&self.format_with_options(&ExcerptOptions::default()))
         }
     }
 }
 
 impl error::Error for LoaderError {}
 
+/// A compiler warning produced while loading a script, see [Loader::take_warnings]
+#[derive(Clone, Debug)]
+pub struct LoaderWarning {
+    warning: CompilerWarning,
+    source: String,
+    source_path: Option<PathBuf>,
+}
+
+impl LoaderWarning {
+    /// The warning that was produced by the compiler
+    pub fn warning(&self) -> &CompilerWarning {
+        &self.warning
+    }
+
+    /// The path of the script that produced the warning, if available
+    pub fn source_path(&self) -> Option<&PathBuf> {
+        self.source_path.as_ref()
+    }
+
+    /// Formats the warning with a source excerpt, using the provided formatting options
+    ///
+    /// The warning's `Display` implementation uses the default options.
+    pub fn format_with_options(&self, options: &ExcerptOptions) -> String {
+        format_error_with_excerpt(
+            Some(&self.warning.message),
+            &self.source_path,
+            &self.source,
+            self.warning.span.start,
+            self.warning.span.end,
+            options,
+        )
+    }
+}
+
+impl fmt::Display for LoaderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(&self.warning.message)
+        } else {
+            f.write_str(&self.format_with_options(&ExcerptOptions::default()))
+        }
+    }
+}
+
+/// A module's source, provided by a [ModuleResolver]
+#[derive(Clone, Debug)]
+pub struct ResolvedModule {
+    /// The path that identifies the module
+    ///
+    /// The path is used as the module's source path in error messages, and is passed to the
+    /// resolver when the module imports other modules. Modules are only loaded once for each
+    /// path. The path doesn't need to exist in the filesystem.
+    pub path: PathBuf,
+    /// The module's source, which can be either Koto source or a serialized chunk
+    pub source: Vec<u8>,
+}
+
+/// Resolves imported modules to their source, see [Loader::set_module_resolver]
+///
+/// Resolvers allow modules to be loaded from places other than the filesystem,
+/// e.g. from a database, from assets embedded in the application, or from a virtual filesystem.
+pub trait ModuleResolver: Send + Sync {
+    /// Resolves a module from the name that it's imported with
+    ///
+    /// `importing_path` is the path of the script that's importing the module, if available.
+    ///
+    /// If the resolver doesn't provide the module then `Ok(None)` should be returned,
+    /// and the module will be searched for in the filesystem (unless filesystem imports have
+    /// been disabled, see [Loader::set_filesystem_imports]).
+    fn resolve(
+        &self,
+        name: &str,
+        importing_path: Option<&Path>,
+    ) -> Result<Option<ResolvedModule>, String>;
+}
+
+// The file extension used for Koto modules
+const MODULE_EXTENSION: &str = "koto";
+
+// The file extension used for compiled chunks in the cache directory
+const CACHED_CHUNK_EXTENSION: &str = "kotoc";
+
 /// Helper for loading, compiling, and caching Koto modules
 #[derive(Clone, Default)]
 pub struct Loader {
     chunks: HashMap<PathBuf, Arc<Chunk>>,
+    warnings: Vec<LoaderWarning>,
+    cache_dir: Option<PathBuf>,
+    compiler_settings: CompilerSettings,
+    module_resolver: Option<Arc<dyn ModuleResolver>>,
+    filesystem_imports_disabled: bool,
 }
 
 impl Loader {
+    /// Sets the settings that are used when compiling scripts and modules
+    ///
+    /// The settings are used by [Loader::compile_script] and when loading modules.
+    /// [Loader::compile_repl] always enables `repl_mode`, and [Loader::compile_expression]
+    /// always disables it.
+    pub fn set_compiler_settings(&mut self, settings: CompilerSettings) {
+        self.compiler_settings = settings;
+    }
+
+    /// The settings that are used when compiling scripts and modules
+    pub fn compiler_settings(&self) -> CompilerSettings {
+        self.compiler_settings
+    }
+
+    /// Sets the directory that's used to cache compiled scripts and modules
+    ///
+    /// When a cache directory is set, scripts with a source path that are compiled with
+    /// [Loader::compile_script] (including imported modules) are serialized into the directory,
+    /// keyed by a hash of their source and the compiler version. Subsequent compilations of the
+    /// same source with the same compiler settings then load the serialized chunk rather than
+    /// recompiling.
+    ///
+    /// Scripts that produce compiler warnings aren't cached so that their warnings are reported
+    /// each time they're compiled. The cache is best-effort; failures while reading or writing
+    /// cached chunks cause the script to be compiled as normal.
+    pub fn set_cache_dir(&mut self, cache_dir: Option<PathBuf>) {
+        self.cache_dir = cache_dir;
+    }
+
+    /// The directory used to cache compiled scripts, see [Loader::set_cache_dir]
+    pub fn cache_dir(&self) -> Option<&PathBuf> {
+        self.cache_dir.as_ref()
+    }
+
+    /// Sets the resolver that's used to find imported modules
+    ///
+    /// The resolver is asked for each module before the filesystem is searched,
+    /// see [ModuleResolver::resolve]. Modules imported with [Loader::compile_modules_in_dir]
+    /// are always loaded from the filesystem.
+    pub fn set_module_resolver(&mut self, module_resolver: Option<Arc<dyn ModuleResolver>>) {
+        self.module_resolver = module_resolver;
+    }
+
+    /// The resolver used to find imported modules, see [Loader::set_module_resolver]
+    pub fn module_resolver(&self) -> Option<&Arc<dyn ModuleResolver>> {
+        self.module_resolver.as_ref()
+    }
+
+    /// Enables or disables the loading of modules from the filesystem
+    ///
+    /// When disabled, only modules that are provided by the module resolver can be loaded,
+    /// and attempts to load other modules fail without accessing the filesystem.
+    /// Filesystem imports are enabled by default.
+    pub fn set_filesystem_imports(&mut self, enabled: bool) {
+        self.filesystem_imports_disabled = !enabled;
+    }
+
+    /// True if modules can be loaded from the filesystem, see [Loader::set_filesystem_imports]
+    pub fn filesystem_imports(&self) -> bool {
+        !self.filesystem_imports_disabled
+    }
+
+    // Compiles a script, using a chunk from the cache directory if one is available
+    fn compile_with_cache(
+        &mut self,
+        script: &str,
+        script_path: &Option<PathBuf>,
+        cache_dir: &Path,
+    ) -> Result<Arc<Chunk>, LoaderError> {
+        let cached_path = cache_dir
+            .join(format!(
+                "{:016x}",
+                cache_key(script, self.compiler_settings)
+            ))
+            .with_extension(CACHED_CHUNK_EXTENSION);
+
+        if let Some(mut chunk) = std::fs::read(&cached_path)
+            .ok()
+            .and_then(|data| Chunk::deserialize(&data).ok())
+            .filter(|chunk| {
+                // The source is compared to rule out hash collisions, when it's available
+                chunk.verify().is_ok()
+                    && (self.compiler_settings.strip_debug_info
+                        || chunk.debug_info.source == script)
+            })
+        {
+            chunk.source_path = script_path.clone();
+            return Ok(Arc::new(chunk));
+        }
+
+        let warning_count = self.warnings.len();
+        let chunk = self.compile(script, script_path.clone(), self.compiler_settings)?;
+
+        if self.warnings.len() == warning_count {
+            // Write to a temporary file first so that other processes sharing the cache don't
+            // see partially written chunks. Errors are ignored, the cache is only an optimization.
+            let temp_path = cached_path.with_extension(format!("{}.tmp", std::process::id()));
+            let write_result = std::fs::create_dir_all(cache_dir)
+                .and_then(|_| std::fs::write(&temp_path, chunk.serialize()))
+                .and_then(|_| std::fs::rename(&temp_path, &cached_path));
+            if write_result.is_err() {
+                let _ = std::fs::remove_file(&temp_path);
+            }
+        }
+
+        Ok(chunk)
+    }
+
     fn compile(
         &mut self,
         script: &str,
         script_path: Option<PathBuf>,
         compiler_settings: CompilerSettings,
     ) -> Result<Arc<Chunk>, LoaderError> {
-        match Parser::parse(script) {
-            Ok(ast) => {
-                let (bytes, mut debug_info) = match Compiler::compile(&ast, compiler_settings) {
-                    Ok((bytes, debug_info)) => (bytes, debug_info),
-                    Err(e) => return Err(LoaderError::from_compiler_error(e, script, script_path)),
-                };
-
-                debug_info.source = script.to_string();
-
-                Ok(Arc::new(Chunk::new(
-                    bytes,
-                    ast.consume_constants(),
-                    script_path,
-                    debug_info,
-                )))
+        self.compile_ast(
+            Parser::parse(script),
+            script,
+            script_path,
+            compiler_settings,
+        )
+    }
+
+    fn compile_ast(
+        &mut self,
+        parse_result: Result<Ast, ParserError>,
+        script: &str,
+        script_path: Option<PathBuf>,
+        compiler_settings: CompilerSettings,
+    ) -> Result<Arc<Chunk>, LoaderError> {
+        match parse_result {
+            Ok(mut ast) => {
+                if compiler_settings.optimize {
+                    constant_folding::fold_constants(&mut ast);
+                }
+
+                let strip_debug_info = compiler_settings.strip_debug_info;
+                let (bytes, mut debug_info, warnings) =
+                    match Compiler::compile(&ast, compiler_settings) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return Err(LoaderError::from_compiler_error(e, script, script_path))
+                        }
+                    };
+
+                self.warnings
+                    .extend(warnings.into_iter().map(|warning| LoaderWarning {
+                        warning,
+                        source: script.into(),
+                        source_path: script_path.clone(),
+                    }));
+
+                if !strip_debug_info {
+                    debug_info.source = script.to_string();
+                }
+
+                let mut chunk = Chunk::new(bytes, ast.consume_constants(), script_path, debug_info);
+                if strip_debug_info {
+                    chunk.remove_expression_strings();
+                }
+
+                Ok(Arc::new(chunk))
             }
             Err(e) => Err(LoaderError::from_parser_error(e, script, script_path)),
         }
     }
 
+    /// Returns the warnings that were produced while compiling scripts and modules
+    ///
+    /// The warnings are removed from the loader, so each warning is only returned once.
+    pub fn take_warnings(&mut self) -> Vec<LoaderWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
     /// Compiles a script in REPL mode
     pub fn compile_repl(&mut self, script: &str) -> Result<Arc<Chunk>, LoaderError> {
-        self.compile(script, None, CompilerSettings { repl_mode: true })
+        self.compile(
+            script,
+            None,
+            CompilerSettings {
+                repl_mode: true,
+                ..self.compiler_settings
+            },
+        )
+    }
+
+    /// Compiles a source string containing a single expression
+    ///
+    /// See [Parser::parse_expression]. Running the resulting chunk returns the expression's result.
+    pub fn compile_expression(&mut self, expression: &str) -> Result<Arc<Chunk>, LoaderError> {
+        self.compile_ast(
+            Parser::parse_expression(expression),
+            expression,
+            None,
+            CompilerSettings {
+                repl_mode: false,
+                ..self.compiler_settings
+            },
+        )
     }
 
     /// Compiles a script
@@ -146,7 +453,110 @@ impl Loader {
         script: &str,
         script_path: &Option<PathBuf>,
     ) -> Result<Arc<Chunk>, LoaderError> {
-        self.compile(script, script_path.clone(), CompilerSettings::default())
+        match (self.cache_dir.clone(), script_path) {
+            (Some(cache_dir), Some(_)) => self.compile_with_cache(script, script_path, &cache_dir),
+            _ => self.compile(script, script_path.clone(), self.compiler_settings),
+        }
+    }
+
+    /// Checks a script for errors, without producing a compiled chunk
+    ///
+    /// Rather than stopping at the first error as [Loader::compile_script] does, the script is
+    /// parsed with [Parser::parse_with_recovery], and the errors found by both the parser and
+    /// the compiler are returned, ordered by their position in the script.
+    /// An empty list is returned if no errors are found.
+    ///
+    /// The compiler's warnings are available from [Loader::take_warnings] when the script
+    /// parses successfully.
+    pub fn check_script(
+        &mut self,
+        script: &str,
+        script_path: &Option<PathBuf>,
+    ) -> Vec<LoaderError> {
+        let (ast, parser_errors) = Parser::parse_with_recovery(script);
+
+        let mut errors = parser_errors
+            .iter()
+            .map(|error| (error.span, LoaderErrorType::Parser(error.clone())))
+            .collect::<Vec<_>>();
+
+        match Compiler::compile(&ast, self.compiler_settings) {
+            Ok((_, _, warnings)) => {
+                if parser_errors.is_empty() {
+                    self.warnings
+                        .extend(warnings.into_iter().map(|warning| LoaderWarning {
+                            warning,
+                            source: script.into(),
+                            source_path: script_path.clone(),
+                        }));
+                }
+            }
+            Err(error) => {
+                // Expressions that failed to parse produce compiler errors that refer to the
+                // parser error's span, these are skipped to avoid reporting the error twice.
+                errors.extend(
+                    error
+                        .errors()
+                        .filter(|error| {
+                            !parser_errors
+                                .iter()
+                                .any(|parser_error| parser_error.span == error.span)
+                        })
+                        .map(|error| {
+                            let error = CompilerError {
+                                additional_errors: Vec::new(),
+                                ..error.clone()
+                            };
+                            (error.span, LoaderErrorType::Compiler(error))
+                        }),
+                );
+            }
+        }
+
+        errors.sort_by_key(|(span, _)| span.start.offset);
+
+        errors
+            .into_iter()
+            .map(|(_, error)| LoaderError {
+                error,
+                source: script.into(),
+                source_path: script_path.clone(),
+            })
+            .collect()
+    }
+
+    /// Loads a script that's either Koto source or a serialized chunk
+    ///
+    /// Serialized chunks (see [Chunk::serialize]) are deserialized without being recompiled,
+    /// and are checked with [Chunk::verify] before being returned. Otherwise the script is
+    /// compiled as with [Loader::compile_script].
+    ///
+    /// If a path is provided then it's used as the deserialized chunk's source path.
+    pub fn load_script(
+        &mut self,
+        script: &[u8],
+        script_path: &Option<PathBuf>,
+    ) -> Result<Arc<Chunk>, LoaderError> {
+        if Chunk::is_serialized(script) {
+            let mut chunk = Chunk::deserialize(script).map_err(|error| {
+                LoaderError::from_chunk_format_error(error, script_path.clone())
+            })?;
+            chunk.verify().map_err(|error| {
+                LoaderError::from_chunk_verifier_error(error, script_path.clone())
+            })?;
+            if script_path.is_some() {
+                chunk.source_path = script_path.clone();
+            }
+            Ok(Arc::new(chunk))
+        } else {
+            match std::str::from_utf8(script) {
+                Ok(script) => self.compile_script(script, script_path),
+                Err(_) => Err(LoaderError::io_error(match script_path {
+                    Some(path) => format!("Invalid UTF-8 in '{}'", path.to_string_lossy()),
+                    None => "Invalid UTF-8 in script".into(),
+                })),
+            }
+        }
     }
 
     /// Finds a module from its name, and then compiles it
@@ -155,35 +565,188 @@ impl Loader {
         name: &str,
         load_from_path: Option<PathBuf>,
     ) -> Result<(Arc<Chunk>, PathBuf), LoaderError> {
-        // Get either the directory of the provided path, or the current working directory
-        let path = match &load_from_path {
+        if let Some(module) = self.resolve_module(name, &load_from_path)? {
+            return self.load_resolved_module(module);
+        }
+
+        self.check_filesystem_imports(name)?;
+        let module_path = Self::find_module(name, load_from_path)?;
+        self.load_module_from_path(module_path)
+    }
+
+    /// Finds a module from its name, and then recompiles it from its source file
+    ///
+    /// Unlike [Loader::compile_module], a previously compiled chunk for the module isn't reused,
+    /// so changes to the module's source file are picked up.
+    pub fn recompile_module(
+        &mut self,
+        name: &str,
+        load_from_path: Option<PathBuf>,
+    ) -> Result<(Arc<Chunk>, PathBuf), LoaderError> {
+        if let Some(module) = self.resolve_module(name, &load_from_path)? {
+            self.chunks.remove(&module.path);
+            return self.load_resolved_module(module);
+        }
+
+        self.check_filesystem_imports(name)?;
+        let module_path = Self::find_module(name, load_from_path)?;
+        self.chunks.remove(&module_path);
+        self.load_module_from_path(module_path)
+    }
+
+    // Asks the module resolver for a module's source, if a resolver has been set
+    fn resolve_module(
+        &self,
+        name: &str,
+        load_from_path: &Option<PathBuf>,
+    ) -> Result<Option<ResolvedModule>, LoaderError> {
+        match &self.module_resolver {
+            Some(resolver) => resolver
+                .resolve(name, load_from_path.as_deref())
+                .map_err(LoaderError::io_error),
+            None => Ok(None),
+        }
+    }
+
+    // Returns an error if modules can't be loaded from the filesystem
+    fn check_filesystem_imports(&self, name: &str) -> Result<(), LoaderError> {
+        if self.filesystem_imports_disabled {
+            Err(LoaderError::io_error(format!(
+                "Unable to import '{}', importing modules from the filesystem is disabled",
+                name
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Finds the path of a module's source file
+    fn find_module(name: &str, load_from_path: Option<PathBuf>) -> Result<PathBuf, LoaderError> {
+        let path = Self::module_search_dir(load_from_path)?;
+
+        let named_path = path.join(name);
+
+        // First, check for a neighbouring file with a matching name.
+        let module_path = named_path.with_extension(MODULE_EXTENSION);
+        if module_path.exists() {
+            Ok(module_path)
+        } else {
+            // Alternatively, check for a neighbouring directory with a matching name,
+            // that also contains a main file.
+            let module_path = named_path.join("main").with_extension(MODULE_EXTENSION);
+            if module_path.exists() {
+                Ok(module_path)
+            } else {
+                Err(LoaderError::io_error(format!(
+                    "Unable to find module '{}'",
+                    name
+                )))
+            }
+        }
+    }
+
+    /// Compiles all of the modules contained in a directory
+    ///
+    /// Each file in the directory with a `.koto` extension is compiled, and returned along with
+    /// its module name and path. The modules are sorted by name.
+    pub fn compile_modules_in_dir(
+        &mut self,
+        dir: &str,
+        load_from_path: Option<PathBuf>,
+    ) -> Result<Vec<(String, Arc<Chunk>, PathBuf)>, LoaderError> {
+        self.check_filesystem_imports(dir)?;
+        let dir_path = Self::module_search_dir(load_from_path)?.join(dir);
+
+        let entries = match std::fs::read_dir(&dir_path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                return Err(LoaderError::io_error(format!(
+                    "Unable to read directory '{}': {}",
+                    dir_path.to_string_lossy(),
+                    e
+                )))
+            }
+        };
+
+        let mut modules = Vec::new();
+
+        for entry in entries {
+            let module_path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => return Err(LoaderError::io_error(e.to_string())),
+            };
+
+            if !module_path.is_file()
+                || module_path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    != Some(MODULE_EXTENSION)
+            {
+                continue;
+            }
+
+            let name = match module_path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+
+            modules.push((name, module_path));
+        }
+
+        modules.sort_by(|(name_a, _), (name_b, _)| name_a.cmp(name_b));
+
+        modules
+            .into_iter()
+            .map(|(name, module_path)| {
+                let (chunk, module_path) = self.load_module_from_path(module_path)?;
+                Ok((name, chunk, module_path))
+            })
+            .collect()
+    }
+
+    // Gets either the directory of the provided path, or the current working directory
+    fn module_search_dir(load_from_path: Option<PathBuf>) -> Result<PathBuf, LoaderError> {
+        match &load_from_path {
             Some(path) => match canonicalize(path) {
                 Ok(canonicalized) if canonicalized.is_file() => match canonicalized.parent() {
-                    Some(parent_dir) => parent_dir.to_path_buf(),
-                    None => {
-                        return Err(LoaderError::io_error(
-                            "Failed to get parent of provided path".to_string(),
-                        ))
-                    }
+                    Some(parent_dir) => Ok(parent_dir.to_path_buf()),
+                    None => Err(LoaderError::io_error(
+                        "Failed to get parent of provided path".to_string(),
+                    )),
                 },
-                Ok(canonicalized) => canonicalized,
-                Err(e) => return Err(LoaderError::io_error(e.to_string())),
+                Ok(canonicalized) => Ok(canonicalized),
+                Err(e) => Err(LoaderError::io_error(e.to_string())),
             },
             None => match std::env::current_dir() {
-                Ok(path) => path,
-                Err(e) => return Err(LoaderError::io_error(e.to_string())),
+                Ok(path) => Ok(path),
+                Err(e) => Err(LoaderError::io_error(e.to_string())),
             },
-        };
+        }
+    }
 
-        let mut load_module_from_path = |module_path: PathBuf| match self.chunks.get(&module_path) {
-            Some(chunk) => Ok((chunk.clone(), module_path.clone())),
-            None => match std::fs::read_to_string(&module_path) {
+    fn load_resolved_module(
+        &mut self,
+        module: ResolvedModule,
+    ) -> Result<(Arc<Chunk>, PathBuf), LoaderError> {
+        match self.chunks.get(&module.path) {
+            Some(chunk) => Ok((chunk.clone(), module.path)),
+            None => {
+                let chunk = self.load_script(&module.source, &Some(module.path.clone()))?;
+                self.chunks.insert(module.path.clone(), chunk.clone());
+                Ok((chunk, module.path))
+            }
+        }
+    }
+
+    fn load_module_from_path(
+        &mut self,
+        module_path: PathBuf,
+    ) -> Result<(Arc<Chunk>, PathBuf), LoaderError> {
+        match self.chunks.get(&module_path) {
+            Some(chunk) => Ok((chunk.clone(), module_path)),
+            None => match std::fs::read(&module_path) {
                 Ok(script) => {
-                    let chunk = self.compile(
-                        &script,
-                        Some(module_path.clone()),
-                        CompilerSettings::default(),
-                    )?;
+                    let chunk = self.load_script(&script, &Some(module_path.clone()))?;
 
                     self.chunks.insert(module_path.clone(), chunk.clone());
                     Ok((chunk, module_path))
@@ -193,27 +756,35 @@ impl Loader {
                     module_path.to_string_lossy()
                 ))),
             },
-        };
+        }
+    }
+}
 
-        let extension = "koto";
-        let named_path = path.join(name);
+// Produces the key used to identify a script's compiled chunk in the cache directory
+//
+// FNV-1a is used rather than std's hasher because the key needs to be stable between runs.
+fn cache_key(script: &str, settings: CompilerSettings) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
 
-        // First, check for a neighbouring file with a matching name.
-        let module_path = named_path.with_extension(extension);
-        if module_path.exists() {
-            load_module_from_path(module_path)
-        } else {
-            // Alternatively, check for a neighbouring directory with a matching name,
-            // that also contains a main file.
-            let module_path = named_path.join("main").with_extension(extension);
-            if module_path.exists() {
-                load_module_from_path(module_path)
-            } else {
-                Err(LoaderError::io_error(format!(
-                    "Unable to find module '{}'",
-                    name
-                )))
-            }
+    // Warnings prevent chunks from being cached, so warnings_as_errors doesn't affect the key
+    let settings = [
+        settings.repl_mode as u8,
+        settings.optimize as u8,
+        settings.strip_debug_info as u8,
+        settings.coverage as u8,
+    ];
+
+    for bytes in [
+        env!("CARGO_PKG_VERSION").as_bytes(),
+        &crate::chunk_format::FORMAT_VERSION.to_le_bytes(),
+        &settings,
+        script.as_bytes(),
+    ] {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
         }
     }
+
+    hash
 }
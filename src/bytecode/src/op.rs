@@ -60,6 +60,11 @@ pub enum Op {
     /// `[*target, constant[3]]`
     LoadFloat24,
 
+    /// Loads an f64 constant with a u32 index into a register
+    ///
+    /// `[*target, constant[4]]`
+    LoadFloat32,
+
     /// Loads an i64 constant into a register
     ///
     /// `[*target, constant]`
@@ -75,6 +80,11 @@ pub enum Op {
     /// `[*target, constant[3]]`
     LoadInt24,
 
+    /// Loads an i64 constant with a u32 index into a register
+    ///
+    /// `[*target, constant[4]]`
+    LoadInt32,
+
     /// Loads a string constant into a register
     ///
     /// `[*target, constant]`
@@ -90,6 +100,11 @@ pub enum Op {
     /// `[*target, constant[3]]`
     LoadString24,
 
+    /// Loads a string constant with a u32 index into a register
+    ///
+    /// `[*target, constant[4]]`
+    LoadString32,
+
     /// Loads a non-local value into a register
     ///
     /// `[*target, constant]`
@@ -105,6 +120,11 @@ pub enum Op {
     /// `[*target, constant[3]]`
     LoadNonLocal24,
 
+    /// Loads a non-local value with a u32 id index into a register
+    ///
+    /// `[*target, constant[4]]`
+    LoadNonLocal32,
+
     /// Imports a value
     ///
     /// The name of the value to be imported will be placed in the register before running this op,
@@ -475,6 +495,11 @@ pub enum Op {
     /// `[*target, constant[3]]`
     Access24,
 
+    /// Accesses a contained value via a u32 constant key
+    ///
+    /// `[*target, constant[4]]`
+    Access32,
+
     /// Access a contained value via a string key
     ///
     /// Used in '.' access operations that use a quoted string, e.g. `foo."bar"`.
@@ -513,7 +538,7 @@ pub enum Op {
 
     /// Displays the contents of a value along with the source expression that produced it
     ///
-    /// `[*value, expression constant[3]]`
+    /// `[*value, expression constant[4]]`
     Debug,
 
     /// Throws an error if the value doesn't match the expected type
@@ -532,28 +557,129 @@ pub enum Op {
     /// `[*value, size]`
     CheckSize,
 
+    /// Jumps to a match arm by looking up a value in a table of literal keys
+    ///
+    /// If the value has the table's key type then the offset of the entry with a matching key is
+    /// used, or the default offset if there's no matching entry. Values of other types fall
+    /// through to the next instruction.
+    ///
+    /// Entries are sorted by key, with each entry containing a key followed by a u16 offset.
+    /// Offsets are relative to the end of the table.
+    ///   - Int keys are stored as 8 byte i64s.
+    ///   - Str keys are stored as u32 constants.
+    ///
+    /// See [JumpTableKey](crate::JumpTableKey) for the list of key types.
+    ///
+    /// `[*value, key type, entry count[2], default offset[2], entries...]`
+    JumpTable,
+
+    /// Throws an error if the value doesn't match an annotated type
+    ///
+    /// Used when checking the annotated types of function arguments and return values.
+    /// The check is skipped unless it has been enabled in the runtime's settings.
+    ///
+    /// `[*value, type constant[4]]`
+    CheckTypeAnnotation,
+
+    /// Checks if the lhs value is contained in the rhs value using the 'in' operator
+    ///
+    /// `[*result, *lhs, *rhs]`
+    In,
+
+    /// Throws an error if the value is false, including the source of the asserted expression
+    ///
+    /// `[*value, expression constant[4]]`
+    Assert,
+
+    /// Throws an error if the result of a comparison is false
+    ///
+    /// The error includes the source of the asserted expression, along with the compared values.
+    ///
+    /// `[*value, *lhs, *rhs, expression constant[4]]`
+    AssertComparison,
+
+    /// Adds a small integer to lhs
+    ///
+    /// Used when the rhs is an integer literal in the range 0..=255, e.g. `x + 1`.
+    /// Integer lhs values take a fast path, other values fall back to the behaviour of [Op::Add].
+    ///
+    /// `[*result, *lhs, n]`
+    AddU8,
+
+    /// Subtracts a small integer from lhs
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    SubtractU8,
+
+    /// Compares lhs and a small integer using the '<' operator
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    LessU8,
+
+    /// Compares lhs and a small integer using the '<=' operator
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    LessOrEqualU8,
+
+    /// Compares lhs and a small integer using the '>' operator
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    GreaterU8,
+
+    /// Compares lhs and a small integer using the '>=' operator
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    GreaterOrEqualU8,
+
+    /// Compares lhs and a small integer using the '==' operator
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    EqualU8,
+
+    /// Compares lhs and a small integer using the '!=' operator
+    ///
+    /// See [Op::AddU8].
+    ///
+    /// `[*result, *lhs, n]`
+    NotEqualU8,
+
+    /// Calls a function in return position
+    ///
+    /// If enabled in the runtime's settings, the called function replaces the current frame.
+    /// Otherwise this behaves like [Op::Call], and the following [Op::Return] returns the result.
+    ///
+    /// `[*result, *function, *first arg, arg count]`
+    TailCall,
+
+    /// Calls an instance function in return position
+    ///
+    /// See [Op::TailCall].
+    ///
+    /// `[*result, *function, *first arg, arg count, *instance]`
+    TailCallInstance,
+
+    /// Records that a coverage point has been reached
+    ///
+    /// Only produced when compiling with
+    /// [CompilerSettings::coverage](crate::CompilerSettings::coverage), the index refers to the
+    /// chunk's [coverage points](crate::DebugInfo::coverage_points).
+    ///
+    /// `[index[4]]`
+    Coverage,
+
     // Unused opcodes, allowing for a direct transmutation from a byte to an Op.
-    Unused93,
-    Unused94,
-    Unused95,
-    Unused96,
-    Unused97,
-    Unused98,
-    Unused99,
-    Unused100,
-    Unused101,
-    Unused102,
-    Unused103,
-    Unused104,
-    Unused105,
-    Unused106,
-    Unused107,
-    Unused108,
-    Unused109,
-    Unused110,
-    Unused111,
-    Unused112,
-    Unused113,
     Unused114,
     Unused115,
     Unused116,
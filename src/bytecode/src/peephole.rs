@@ -0,0 +1,440 @@
+//! A peephole optimizer that simplifies the bytecode produced by the [Compiler](crate::Compiler)
+//!
+//! The compiled bytecode is decoded into a list of instructions, with each jump's target being
+//! converted into an absolute position. The following simplifications are then made:
+//!
+//!   - Jumps that land on an unconditional jump are redirected to the final target.
+//!   - Jumps to the following instruction are removed.
+//!   - Copies to the same register are removed.
+//!   - A copy that's immediately returned is replaced by returning the copy's source.
+//!   - A copy that's immediately followed by a copy back to the original register has the second
+//!     copy removed.
+//!   - Loads of a constant into a register that already contains the constant are removed.
+//!
+//! Finally, the remaining instructions are reassembled, with jump offsets, function sizes, and
+//! debug info positions being updated to match the new instruction positions.
+
+use {
+    crate::{Chunk, DebugInfo, Disassembler, Instruction, Op, OperandValue},
+    koto_parser::ConstantPool,
+    std::{collections::HashSet, convert::TryFrom, sync::Arc},
+};
+
+// The maximum number of jumps that will be followed while looking for a jump's final target
+const MAX_JUMP_CHAIN: usize = 16;
+
+/// Optimizes the compiled bytecode, updating the debug info to match
+pub(crate) fn optimize(bytes: Vec<u8>, debug_info: DebugInfo) -> (Vec<u8>, DebugInfo) {
+    let mut optimizer = match PeepholeOptimizer::new(&bytes) {
+        Some(optimizer) => optimizer,
+        // The bytecode couldn't be decoded, so it's left unchanged
+        None => return (bytes, debug_info),
+    };
+
+    optimizer.thread_jumps();
+    optimizer.simplify_copies();
+    optimizer.remove_redundant_loads();
+    optimizer.remove_jumps_to_next_instruction();
+    optimizer.assemble(debug_info)
+}
+
+// A jump target within an instruction
+#[derive(Clone, Copy)]
+struct Target {
+    // The position of the target's u16 offset within the instruction's bytes
+    offset_position: usize,
+    // The absolute position in the original bytecode that the offset refers to
+    ip: usize,
+    // True if the offset is relative to the end of the instruction going backwards
+    backwards: bool,
+}
+
+struct Entry {
+    // The instruction's position in the original bytecode
+    ip: usize,
+    // The instruction's encoded bytes, including any inline data
+    bytes: Vec<u8>,
+    instruction: Instruction,
+    // The function depth of the instruction, see DisassembledInstruction::depth
+    depth: usize,
+    targets: Vec<Target>,
+    removed: bool,
+}
+
+impl Entry {
+    fn end(&self) -> usize {
+        self.ip + self.bytes.len()
+    }
+
+    fn set_target(&mut self, index: usize, ip: usize, backwards: bool) {
+        let target = &mut self.targets[index];
+        target.ip = ip;
+        target.backwards = backwards;
+    }
+}
+
+struct PeepholeOptimizer {
+    entries: Vec<Entry>,
+    // The length of the original bytecode
+    end_ip: usize,
+}
+
+impl PeepholeOptimizer {
+    fn new(bytes: &[u8]) -> Option<Self> {
+        let chunk = Arc::new(Chunk::new(
+            bytes.to_vec(),
+            ConstantPool::default(),
+            None,
+            DebugInfo::default(),
+        ));
+
+        let mut entries = Vec::new();
+
+        for disassembled in Disassembler::new(chunk) {
+            if matches!(disassembled.instruction, Instruction::Error { .. }) {
+                return None;
+            }
+
+            let ip = disassembled.ip;
+            let end = ip + disassembled.size;
+            let instruction_bytes = bytes[ip..end].to_vec();
+
+            let forward = |offset_position: usize, offset: usize| Target {
+                offset_position,
+                ip: end + offset,
+                backwards: false,
+            };
+            // Most instructions that refer to a target have their offset in their final two bytes
+            let size = disassembled.size;
+            let last_offset = || size - 2;
+
+            use Instruction::*;
+            let targets = match &disassembled.instruction {
+                Jump { offset } | JumpIf { offset, .. } => vec![forward(last_offset(), *offset)],
+                IterNext { jump_offset, .. }
+                | IterNextTemp { jump_offset, .. }
+                | IterNextQuiet { jump_offset, .. } => vec![forward(last_offset(), *jump_offset)],
+                TryStart { catch_offset, .. } => vec![forward(last_offset(), *catch_offset)],
+                SimpleFunction { size, .. } | Function { size, .. } => {
+                    vec![forward(last_offset(), *size)]
+                }
+                JumpBack { offset } => vec![Target {
+                    offset_position: last_offset(),
+                    ip: end - offset,
+                    backwards: true,
+                }],
+                JumpTable {
+                    key_type,
+                    entry_count,
+                    default_offset,
+                    table_ip,
+                    ..
+                } => {
+                    // The default offset precedes the table's entries
+                    let table_start = table_ip - ip;
+                    let mut targets = vec![forward(table_start - 2, *default_offset)];
+                    for i in 0..*entry_count {
+                        let offset_position = table_start + (i + 1) * key_type.entry_size() - 2;
+                        let offset = u16::from_le_bytes([
+                            instruction_bytes[offset_position],
+                            instruction_bytes[offset_position + 1],
+                        ]);
+                        targets.push(forward(offset_position, offset as usize));
+                    }
+                    targets
+                }
+                _ => vec![],
+            };
+
+            entries.push(Entry {
+                ip,
+                bytes: instruction_bytes,
+                instruction: disassembled.instruction,
+                depth: disassembled.depth,
+                targets,
+                removed: false,
+            });
+        }
+
+        Some(Self {
+            entries,
+            end_ip: bytes.len(),
+        })
+    }
+
+    fn entry_index(&self, ip: usize) -> Option<usize> {
+        self.entries
+            .binary_search_by_key(&ip, |entry| entry.ip)
+            .ok()
+    }
+
+    // Returns the positions of all instructions that are the target of a jump
+    fn jump_targets(&self) -> HashSet<usize> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.removed)
+            .flat_map(|entry| entry.targets.iter().map(|target| target.ip))
+            .collect()
+    }
+
+    // Redirects jumps that land on an unconditional jump to the final target
+    fn thread_jumps(&mut self) {
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            let threadable = matches!(
+                entry.instruction,
+                Instruction::Jump { .. }
+                    | Instruction::JumpIf { .. }
+                    | Instruction::IterNext { .. }
+                    | Instruction::IterNextTemp { .. }
+                    | Instruction::IterNextQuiet { .. }
+            );
+            if !threadable {
+                continue;
+            }
+
+            let start_target = entry.targets[0].ip;
+            let mut target = start_target;
+            let mut backwards = false;
+
+            for _ in 0..MAX_JUMP_CHAIN {
+                let next = match self.entry_index(target).map(|i| &self.entries[i]) {
+                    Some(
+                        next @ Entry {
+                            instruction: Instruction::Jump { .. } | Instruction::JumpBack { .. },
+                            ..
+                        },
+                    ) => next,
+                    _ => break,
+                };
+                if next.ip == entry.ip {
+                    // The jump is part of a cycle
+                    break;
+                }
+                target = next.targets[0].ip;
+                backwards = next.targets[0].backwards;
+            }
+
+            if target == start_target {
+                continue;
+            }
+
+            let entry_end = entry.end();
+            if backwards || target < entry_end {
+                // Only unconditional jumps can be converted into backwards jumps,
+                // with the same encoded size.
+                if !matches!(entry.instruction, Instruction::Jump { .. }) {
+                    continue;
+                }
+                if u16::try_from(entry_end - target).is_err() {
+                    continue;
+                }
+                let entry = &mut self.entries[index];
+                entry.bytes[0] = Op::JumpBack as u8;
+                entry.instruction = Instruction::JumpBack {
+                    offset: entry_end - target,
+                };
+                entry.set_target(0, target, true);
+            } else {
+                if u16::try_from(target - entry_end).is_err() {
+                    continue;
+                }
+                self.entries[index].set_target(0, target, false);
+            }
+        }
+    }
+
+    fn simplify_copies(&mut self) {
+        let jump_targets = self.jump_targets();
+
+        for index in 0..self.entries.len() {
+            let (target, source) = match self.entries[index].instruction {
+                Instruction::Copy { target, source } => (target, source),
+                _ => continue,
+            };
+
+            if target == source {
+                self.entries[index].removed = true;
+                continue;
+            }
+
+            let next_index = index + 1;
+            let next = match self.entries.get(next_index) {
+                Some(next)
+                    if next.depth == self.entries[index].depth
+                        && !jump_targets.contains(&next.ip) =>
+                {
+                    next
+                }
+                _ => continue,
+            };
+
+            match next.instruction {
+                Instruction::Return { register } if register == target => {
+                    // The returned register isn't used after the return, so the copy's source can
+                    // be returned directly.
+                    self.entries[index].removed = true;
+                    let next = &mut self.entries[next_index];
+                    next.bytes[1] = source;
+                    next.instruction = Instruction::Return { register: source };
+                }
+                Instruction::Copy {
+                    target: next_target,
+                    source: next_source,
+                } if next_target == source && next_source == target => {
+                    self.entries[next_index].removed = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Removes loads of constants into registers that already contain the constant
+    //
+    // Loaded registers are tracked within a straight line of instructions in a single function.
+    // Any instruction that refers to a loaded register is treated as potentially modifying it.
+    fn remove_redundant_loads(&mut self) {
+        let jump_targets = self.jump_targets();
+        // The loaded registers, along with the encoded bytes of the instruction that loaded them
+        let mut loaded: Vec<(u8, Vec<u8>)> = Vec::new();
+        let mut depth = 0;
+
+        for entry in self.entries.iter_mut() {
+            if entry.removed {
+                continue;
+            }
+
+            if entry.depth != depth || jump_targets.contains(&entry.ip) {
+                loaded.clear();
+                depth = entry.depth;
+            }
+
+            use Instruction::*;
+            match entry.instruction {
+                SetEmpty { register }
+                | SetBool { register, .. }
+                | SetNumber { register, .. }
+                | LoadFloat { register, .. }
+                | LoadInt { register, .. }
+                | LoadString { register, .. } => {
+                    if loaded.iter().any(|(loaded_register, bytes)| {
+                        *loaded_register == register && *bytes == entry.bytes
+                    }) {
+                        entry.removed = true;
+                    } else {
+                        loaded.retain(|(loaded_register, _)| *loaded_register != register);
+                        loaded.push((register, entry.bytes.clone()));
+                    }
+                }
+                Call { frame_base, .. }
+                | CallInstance { frame_base, .. }
+                | TailCall { frame_base, .. }
+                | TailCallInstance { frame_base, .. } => {
+                    // The called function's frame starts at the frame base,
+                    // so registers from there onwards are overwritten.
+                    loaded.retain(|(register, _)| *register < frame_base);
+                    let operands = operand_registers(&entry.instruction);
+                    loaded.retain(|(register, _)| !operands.contains(register));
+                }
+                SimpleFunction { .. } | Function { .. } => loaded.clear(),
+                _ => {
+                    let operands = operand_registers(&entry.instruction);
+                    loaded.retain(|(register, _)| !operands.contains(register));
+                }
+            }
+        }
+    }
+
+    fn remove_jumps_to_next_instruction(&mut self) {
+        for index in 0..self.entries.len() {
+            let entry = &self.entries[index];
+            if entry.removed || !matches!(entry.instruction, Instruction::Jump { .. }) {
+                continue;
+            }
+
+            let target = entry.targets[0].ip;
+            let skipped_instructions_removed = self.entries[index + 1..]
+                .iter()
+                .take_while(|skipped| skipped.ip < target)
+                .all(|skipped| skipped.removed);
+
+            if skipped_instructions_removed {
+                self.entries[index].removed = true;
+            }
+        }
+    }
+
+    fn assemble(self, debug_info: DebugInfo) -> (Vec<u8>, DebugInfo) {
+        // Map each original instruction position to its new position,
+        // with removed instructions mapping to the position of the following instruction.
+        let mut new_ips = Vec::with_capacity(self.entries.len());
+        let mut new_ip = 0;
+        for entry in self.entries.iter() {
+            new_ips.push(new_ip);
+            if !entry.removed {
+                new_ip += entry.bytes.len();
+            }
+        }
+        let new_end_ip = new_ip;
+
+        let map_ip = |ip: usize| -> usize {
+            if ip >= self.end_ip {
+                new_end_ip
+            } else {
+                match self.entries.binary_search_by_key(&ip, |entry| entry.ip) {
+                    Ok(index) => new_ips[index],
+                    Err(index) => new_ips.get(index).copied().unwrap_or(new_end_ip),
+                }
+            }
+        };
+
+        let mut bytes = Vec::with_capacity(new_end_ip);
+        for (entry, entry_ip) in self.entries.iter().zip(new_ips.iter()) {
+            if entry.removed {
+                continue;
+            }
+
+            let mut entry_bytes = entry.bytes.clone();
+            let entry_end = entry_ip + entry_bytes.len();
+            for target in entry.targets.iter() {
+                let target_ip = map_ip(target.ip);
+                // Removing instructions only brings targets closer, so offsets always fit in a u16
+                let offset = if target.backwards {
+                    entry_end - target_ip
+                } else {
+                    target_ip - entry_end
+                } as u16;
+                entry_bytes[target.offset_position..target.offset_position + 2]
+                    .copy_from_slice(&offset.to_le_bytes());
+            }
+            bytes.extend_from_slice(&entry_bytes);
+        }
+
+        let mut new_debug_info = DebugInfo {
+            coverage_points: debug_info.coverage_points,
+            source: debug_info.source,
+            ..Default::default()
+        };
+        for (ip, span) in debug_info.source_map.iter() {
+            new_debug_info.source_map.push((map_ip(*ip), *span));
+        }
+        for (body, name) in debug_info.functions.into_iter() {
+            new_debug_info
+                .functions
+                .push((map_ip(body.start)..map_ip(body.end), name));
+        }
+
+        (bytes, new_debug_info)
+    }
+}
+
+// Returns the registers that are referred to by an instruction
+fn operand_registers(instruction: &Instruction) -> Vec<u8> {
+    crate::disassembler::operands(instruction, 0)
+        .into_iter()
+        .filter_map(|operand| match operand.value {
+            OperandValue::Register(register) => Some(register),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,11 @@
+use {
+    koto_bytecode::{Chunk, Loader},
+    std::sync::Arc,
+};
+
+pub fn compile(script: &str) -> Arc<Chunk> {
+    match Loader::default().compile_script(script, &None) {
+        Ok(chunk) => chunk,
+        Err(error) => panic!("Failure while compiling:\n{}\n{}", script, error),
+    }
+}
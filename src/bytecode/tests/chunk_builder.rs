@@ -0,0 +1,310 @@
+mod bytecode {
+    use {
+        koto_bytecode::{Chunk, ChunkBuilder, Instruction, InstructionReader, Op},
+        std::sync::Arc,
+    };
+
+    // Reads the instructions from the built chunk, returning them in their debug format
+    fn read_instructions(builder: ChunkBuilder) -> Vec<String> {
+        let chunk = Arc::new(builder.build().unwrap());
+        InstructionReader::new(chunk)
+            .map(|instruction| format!("{:?}", instruction))
+            .collect()
+    }
+
+    fn check_round_trip(instructions: Vec<Instruction>) {
+        let expected = instructions
+            .iter()
+            .map(|instruction| format!("{:?}", instruction))
+            .collect::<Vec<_>>();
+
+        let mut builder = ChunkBuilder::new();
+        for instruction in instructions {
+            builder.push(instruction).unwrap();
+        }
+
+        assert_eq!(read_instructions(builder), expected);
+    }
+
+    mod encoding {
+        use {super::*, Instruction::*};
+
+        #[test]
+        fn values() {
+            check_round_trip(vec![
+                SetEmpty { register: 0 },
+                SetBool {
+                    register: 1,
+                    value: true,
+                },
+                SetNumber {
+                    register: 2,
+                    value: 0,
+                },
+                SetNumber {
+                    register: 3,
+                    value: 1,
+                },
+                SetNumber {
+                    register: 4,
+                    value: 255,
+                },
+                MakeMap {
+                    register: 5,
+                    size_hint: 1000,
+                },
+                SequenceStart {
+                    register: 6,
+                    size_hint: 3,
+                },
+                SequenceToTuple { sequence: 6 },
+                Add {
+                    register: 7,
+                    lhs: 2,
+                    rhs: 3,
+                },
+                SliceFrom {
+                    register: 8,
+                    value: 6,
+                    index: -1,
+                },
+                Return { register: 7 },
+            ]);
+        }
+
+        #[test]
+        fn constants_use_the_smallest_index_size() {
+            let mut builder = ChunkBuilder::new();
+            let mut last = None;
+            for i in 0..300 {
+                last = Some(builder.add_i64(i).unwrap());
+            }
+            let first = builder.add_i64(0).unwrap();
+            let last = last.unwrap();
+
+            builder
+                .push(LoadInt {
+                    register: 0,
+                    constant: first,
+                })
+                .unwrap();
+            let ip_after_first = builder.ip();
+            builder
+                .push(LoadInt {
+                    register: 1,
+                    constant: last,
+                })
+                .unwrap();
+
+            assert_eq!(ip_after_first, 3);
+            assert_eq!(builder.ip(), 7);
+            assert_eq!(
+                read_instructions(builder),
+                &[
+                    "LoadInt\t\tresult: 0\tconstant: 0",
+                    "LoadInt\t\tresult: 1\tconstant: 299"
+                ]
+            );
+        }
+
+        #[test]
+        fn u32_constant_indices() {
+            let bytes = [
+                [Op::LoadString24 as u8, 0, 0x56, 0x34, 0x12].as_ref(),
+                &[Op::LoadString32 as u8, 1, 0x78, 0x56, 0x34, 0x12],
+                &[Op::Access32 as u8, 2, 1, 0xff, 0xff, 0xff, 0xff],
+                &[Op::Assert as u8, 3, 0x00, 0x00, 0x00, 0x01],
+            ]
+            .concat();
+            let chunk = Chunk {
+                bytes,
+                ..Default::default()
+            };
+
+            let instructions = InstructionReader::new(Arc::new(chunk))
+                .map(|instruction| format!("{:?}", instruction))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                instructions,
+                &[
+                    "LoadString\tresult: 0\tconstant: 1193046",
+                    "LoadString\tresult: 1\tconstant: 305419896",
+                    "Access\t\tresult: 2\tvalue: 1\tkey: 4294967295",
+                    "Assert\t\tregister: 3\tconstant: 16777216",
+                ]
+            );
+        }
+    }
+
+    mod labels {
+        use {super::*, Instruction::*};
+
+        #[test]
+        fn forward_and_backward_jumps() {
+            let mut builder = ChunkBuilder::new();
+            let loop_start = builder.new_label();
+            let loop_end = builder.new_label();
+
+            builder.place_label(loop_start).unwrap();
+            builder
+                .push_with_target(
+                    JumpIf {
+                        register: 0,
+                        offset: 0,
+                        jump_condition: false,
+                    },
+                    loop_end,
+                )
+                .unwrap();
+            builder.push(SetEmpty { register: 1 }).unwrap();
+            builder
+                .push_with_target(JumpBack { offset: 0 }, loop_start)
+                .unwrap();
+            builder.place_label(loop_end).unwrap();
+            builder.push(Return { register: 1 }).unwrap();
+
+            assert_eq!(
+                read_instructions(builder),
+                &[
+                    "JumpIf\t\tresult: 0\toffset: 5\tcondition: false",
+                    "SetEmpty\tresult: 1",
+                    "JumpBack\toffset: 9",
+                    "Return\t\tresult: 1",
+                ]
+            );
+        }
+
+        #[test]
+        fn function_size() {
+            let mut builder = ChunkBuilder::new();
+            let function_end = builder.new_label();
+
+            builder
+                .push_with_target(
+                    SimpleFunction {
+                        register: 0,
+                        arg_count: 1,
+                        size: 0,
+                    },
+                    function_end,
+                )
+                .unwrap();
+            builder.push(Return { register: 0 }).unwrap();
+            builder.place_label(function_end).unwrap();
+
+            assert_eq!(
+                read_instructions(builder),
+                &[
+                    "SimpleFunction\tresult: 0\targs: 1\t\tsize: 2",
+                    "Return\t\tresult: 0"
+                ]
+            );
+        }
+    }
+
+    mod validation {
+        use {super::*, Instruction::*};
+
+        fn check_build_error(builder: ChunkBuilder, expected_ip: usize) {
+            match builder.build() {
+                Ok(_) => panic!("Expected an error while building the chunk"),
+                Err(error) => assert_eq!(error.ip, expected_ip, "{}", error),
+            }
+        }
+
+        #[test]
+        fn label_not_placed() {
+            let mut builder = ChunkBuilder::new();
+            let label = builder.new_label();
+            builder.push(SetEmpty { register: 0 }).unwrap();
+            builder.push_with_target(Jump { offset: 0 }, label).unwrap();
+            check_build_error(builder, 2);
+        }
+
+        #[test]
+        fn label_placed_twice() {
+            let mut builder = ChunkBuilder::new();
+            let label = builder.new_label();
+            builder.place_label(label).unwrap();
+            assert!(builder.place_label(label).is_err());
+        }
+
+        #[test]
+        fn backward_jump_to_later_label() {
+            let mut builder = ChunkBuilder::new();
+            let label = builder.new_label();
+            builder
+                .push_with_target(JumpBack { offset: 0 }, label)
+                .unwrap();
+            builder.push(SetEmpty { register: 0 }).unwrap();
+            builder.place_label(label).unwrap();
+            check_build_error(builder, 0);
+        }
+
+        #[test]
+        fn jump_into_the_middle_of_an_instruction() {
+            let mut builder = ChunkBuilder::new();
+            builder.push(Jump { offset: 1 }).unwrap();
+            builder.push(SetEmpty { register: 0 }).unwrap();
+            check_build_error(builder, 0);
+        }
+
+        #[test]
+        fn target_without_offset() {
+            let mut builder = ChunkBuilder::new();
+            let label = builder.new_label();
+            assert!(builder
+                .push_with_target(SetEmpty { register: 0 }, label)
+                .is_err());
+        }
+
+        #[test]
+        fn constant_with_wrong_type() {
+            let mut builder = ChunkBuilder::new();
+            let n = builder.add_i64(42).unwrap();
+            builder.push(SetEmpty { register: 0 }).unwrap();
+            builder
+                .push(LoadString {
+                    register: 0,
+                    constant: n,
+                })
+                .unwrap();
+            check_build_error(builder, 2);
+        }
+
+        #[test]
+        fn missing_constant() {
+            let mut builder = ChunkBuilder::new();
+            builder
+                .push(LoadFloat {
+                    register: 0,
+                    constant: 0_u32.into(),
+                })
+                .unwrap();
+            check_build_error(builder, 0);
+        }
+
+        #[test]
+        fn unencodable_instructions() {
+            let mut builder = ChunkBuilder::new();
+            assert!(builder
+                .push(SetNumber {
+                    register: 0,
+                    value: 256
+                })
+                .is_err());
+            assert!(builder
+                .push(CheckSize {
+                    register: 0,
+                    size: 1000
+                })
+                .is_err());
+            assert!(builder
+                .push(Error {
+                    message: "oops".into()
+                })
+                .is_err());
+            assert_eq!(builder.ip(), 0);
+        }
+    }
+}
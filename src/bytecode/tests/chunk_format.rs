@@ -0,0 +1,285 @@
+mod bytecode {
+    use {
+        koto_bytecode::{Chunk, CompilerSettings, Loader},
+        std::path::PathBuf,
+    };
+
+    const SCRIPT: &str = "\
+x = 1.5
+f = |n| n * x
+export y = f 42
+'hello ${y}'
+";
+
+    fn compile(script: &str, path: Option<PathBuf>) -> Chunk {
+        let mut loader = Loader::default();
+        match loader.compile_script(script, &path) {
+            Ok(chunk) => (*chunk).clone(),
+            Err(error) => panic!("Failure while compiling:\n{}\n{}", script, error),
+        }
+    }
+
+    fn deserialize_error(data: &[u8]) -> String {
+        match Chunk::deserialize(data) {
+            Ok(_) => panic!("Unexpected success"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let chunk = compile(SCRIPT, Some(PathBuf::from("foo/bar.koto")));
+        let data = chunk.serialize();
+
+        assert!(Chunk::is_serialized(&data));
+        assert!(!Chunk::is_serialized(SCRIPT.as_bytes()));
+
+        let deserialized = Chunk::deserialize(&data).unwrap();
+        assert_eq!(deserialized, chunk);
+        assert_eq!(deserialized.source_path, chunk.source_path);
+        assert_eq!(
+            deserialized.constants.iter().collect::<Vec<_>>(),
+            chunk.constants.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(deserialized.debug_info.source, SCRIPT);
+        for ip in 0..chunk.bytes.len() {
+            assert_eq!(
+                deserialized.debug_info.get_source_span(ip),
+                chunk.debug_info.get_source_span(ip)
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip_with_coverage_points() {
+        let mut loader = Loader::default();
+        loader.set_compiler_settings(CompilerSettings {
+            coverage: true,
+            ..Default::default()
+        });
+        let chunk = loader.compile_script(SCRIPT, &None).unwrap();
+        assert!(!chunk.debug_info.coverage_points().is_empty());
+
+        let deserialized = Chunk::deserialize(&chunk.serialize()).unwrap();
+        assert_eq!(deserialized, *chunk);
+        assert_eq!(
+            deserialized.debug_info.coverage_points(),
+            chunk.debug_info.coverage_points()
+        );
+    }
+
+    #[test]
+    fn round_trip_without_source_path() {
+        let chunk = compile(SCRIPT, None);
+        let deserialized = Chunk::deserialize(&chunk.serialize()).unwrap();
+        assert_eq!(deserialized, chunk);
+        assert_eq!(deserialized.source_path, None);
+    }
+
+    #[test]
+    fn missing_header() {
+        assert_eq!(
+            deserialize_error(SCRIPT.as_bytes()),
+            "Missing chunk header (at byte 0)"
+        );
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut data = compile(SCRIPT, None).serialize();
+        data[5] = 99;
+        data[6] = 0;
+        assert_eq!(
+            deserialize_error(&data),
+            "Unsupported chunk format version 99 (expected 5) (at byte 7)"
+        );
+    }
+
+    #[test]
+    fn truncated_data() {
+        let data = compile(SCRIPT, None).serialize();
+        for len in 0..data.len() {
+            assert!(Chunk::deserialize(&data[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut data = compile(SCRIPT, None).serialize();
+        data.push(0);
+        assert_eq!(
+            deserialize_error(&data),
+            format!(
+                "Unexpected data at the end of the chunk (at byte {})",
+                data.len() - 1
+            )
+        );
+    }
+
+    #[test]
+    fn compilation_is_deterministic() {
+        // Captures were previously ordered by a set with randomized iteration order
+        let script = "\
+a, b, c, d, e, f, g, h = 1, 2, 3, 4, 5, 6, 7, 8
+sum = || a + b + c + d + e + f + g + h
+sum()
+";
+        let expected = compile(script, None).serialize();
+        for _ in 0..20 {
+            assert_eq!(compile(script, None).serialize(), expected);
+        }
+    }
+
+    #[test]
+    fn loader_accepts_source_or_serialized_chunks() {
+        let path = Some(PathBuf::from("baz.koto"));
+        let chunk = compile(SCRIPT, None);
+
+        let mut loader = Loader::default();
+        let from_source = loader.load_script(SCRIPT.as_bytes(), &path).unwrap();
+        let from_chunk = loader.load_script(&chunk.serialize(), &path).unwrap();
+
+        assert_eq!(*from_source, *from_chunk);
+        // The provided path is used as the deserialized chunk's source path
+        assert_eq!(from_chunk.source_path, path);
+    }
+
+    #[test]
+    fn loader_error_for_malformed_chunk() {
+        let mut data = compile(SCRIPT, None).serialize();
+        data.truncate(data.len() - 1);
+
+        let mut loader = Loader::default();
+        match loader.load_script(&data, &None) {
+            Ok(_) => panic!("Unexpected success"),
+            Err(error) => assert!(error.to_string().starts_with("Unexpected end of data")),
+        }
+    }
+
+    mod cache_dir {
+        use super::*;
+
+        // Each test uses its own cache directory, which is removed when the test ends
+        struct CacheDir(PathBuf);
+
+        impl CacheDir {
+            fn new(name: &str) -> Self {
+                let dir = std::env::temp_dir().join(format!(
+                    "koto_chunk_cache_{}_{}",
+                    name,
+                    std::process::id()
+                ));
+                let _ = std::fs::remove_dir_all(&dir);
+                Self(dir)
+            }
+
+            fn cached_chunks(&self) -> Vec<PathBuf> {
+                match std::fs::read_dir(&self.0) {
+                    Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+                    Err(_) => vec![],
+                }
+            }
+
+            fn loader(&self) -> Loader {
+                let mut loader = Loader::default();
+                loader.set_cache_dir(Some(self.0.clone()));
+                loader
+            }
+        }
+
+        impl Drop for CacheDir {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+        }
+
+        #[test]
+        fn compiled_scripts_are_cached() {
+            let cache = CacheDir::new("cached");
+            let path = Some(PathBuf::from("foo.koto"));
+
+            let compiled = cache.loader().compile_script(SCRIPT, &path).unwrap();
+            let cached_chunks = cache.cached_chunks();
+            assert_eq!(cached_chunks.len(), 1);
+
+            // A new loader finds the cached chunk, with the provided path as its source path
+            let other_path = Some(PathBuf::from("bar.koto"));
+            let from_cache = cache.loader().compile_script(SCRIPT, &other_path).unwrap();
+            assert_eq!(from_cache.bytes, compiled.bytes);
+            assert_eq!(from_cache.source_path, other_path);
+            assert_eq!(cache.cached_chunks(), cached_chunks);
+
+            // Changing the script produces a new entry in the cache
+            let changed = SCRIPT.replace("42", "99");
+            cache.loader().compile_script(&changed, &path).unwrap();
+            assert_eq!(cache.cached_chunks().len(), 2);
+        }
+
+        #[test]
+        fn scripts_without_paths_are_not_cached() {
+            let cache = CacheDir::new("no_path");
+
+            cache.loader().compile_script(SCRIPT, &None).unwrap();
+            assert!(cache.cached_chunks().is_empty());
+        }
+
+        #[test]
+        fn scripts_with_warnings_are_not_cached() {
+            let cache = CacheDir::new("warnings");
+            let path = Some(PathBuf::from("foo.koto"));
+            let script = "\
+f = || 42
+f
+f()
+";
+
+            for _ in 0..2 {
+                let mut loader = cache.loader();
+                loader.compile_script(script, &path).unwrap();
+                assert_eq!(loader.take_warnings().len(), 1);
+            }
+            assert!(cache.cached_chunks().is_empty());
+        }
+
+        #[test]
+        fn invalid_cached_chunks_are_replaced() {
+            let cache = CacheDir::new("invalid");
+            let path = Some(PathBuf::from("foo.koto"));
+
+            let compiled = cache.loader().compile_script(SCRIPT, &path).unwrap();
+            let cached_chunk = cache.cached_chunks().pop().unwrap();
+            std::fs::write(&cached_chunk, b"\0KOTO").unwrap();
+
+            let recompiled = cache.loader().compile_script(SCRIPT, &path).unwrap();
+            assert_eq!(*recompiled, *compiled);
+            assert_eq!(
+                Chunk::deserialize(&std::fs::read(&cached_chunk).unwrap()).unwrap(),
+                *compiled
+            );
+        }
+
+        #[test]
+        fn compiler_settings_are_part_of_the_cache_key() {
+            let cache = CacheDir::new("settings");
+            let path = Some(PathBuf::from("foo.koto"));
+
+            let optimized = cache.loader().compile_script(SCRIPT, &path).unwrap();
+
+            let mut loader = cache.loader();
+            loader.set_compiler_settings(CompilerSettings {
+                optimize: false,
+                strip_debug_info: true,
+                ..Default::default()
+            });
+            let unoptimized = loader.compile_script(SCRIPT, &path).unwrap();
+            assert_eq!(cache.cached_chunks().len(), 2);
+            assert!(unoptimized.debug_info.source.is_empty());
+
+            // Chunks without source are still found in the cache
+            let from_cache = loader.compile_script(SCRIPT, &path).unwrap();
+            assert_eq!(*from_cache, *unoptimized);
+            assert_ne!(*from_cache, *optimized);
+            assert_eq!(cache.cached_chunks().len(), 2);
+        }
+    }
+}
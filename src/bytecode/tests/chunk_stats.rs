@@ -0,0 +1,81 @@
+mod bytecode_test_utils;
+
+mod bytecode {
+    use {
+        crate::bytecode_test_utils::compile,
+        koto_bytecode::{Chunk, ChunkStats, Disassembler},
+    };
+
+    fn stats(script: &str) -> ChunkStats {
+        Chunk::stats(compile(script))
+    }
+
+    #[test]
+    fn top_level_only() {
+        let chunk = compile("x = 'hello'\ny = x.size()");
+        let stats = Chunk::stats(chunk.clone());
+
+        assert_eq!(stats.byte_size, chunk.bytes.len());
+        assert_eq!(stats.constants, chunk.constants.stats());
+        assert_eq!(stats.functions.len(), 1);
+
+        let top_level = &stats.functions[0];
+        assert_eq!(top_level.ip, None);
+        assert_eq!(top_level.depth, 0);
+        assert_eq!(top_level.byte_size, chunk.bytes.len());
+        assert_eq!(
+            top_level.instruction_count,
+            Disassembler::new(chunk).count()
+        );
+        // 'hello', and the 'size' access
+        assert_eq!(top_level.constant_references, 2);
+        assert_eq!(top_level.register_count, stats.max_register_count());
+    }
+
+    #[test]
+    fn nested_functions() {
+        let script = "\
+f = |x|
+  g = |y| y * x
+  g 42
+f 99
+";
+        let stats = stats(script);
+        assert_eq!(stats.functions.len(), 3);
+
+        let depths_and_lines = stats
+            .functions
+            .iter()
+            .map(|function| (function.depth, function.span.map(|span| span.start.line)))
+            .collect::<Vec<_>>();
+        assert_eq!(depths_and_lines, &[(0, None), (1, Some(1)), (2, Some(2))]);
+
+        // Each instruction is counted in exactly one function
+        assert_eq!(
+            stats
+                .functions
+                .iter()
+                .map(|function| function.byte_size)
+                .sum::<usize>(),
+            stats.byte_size
+        );
+        assert_eq!(
+            stats.instruction_count(),
+            Disassembler::new(compile(script)).count()
+        );
+
+        // g only refers to its argument and its capture
+        let g = &stats.functions[2];
+        assert_eq!(g.register_count, 3);
+        assert_eq!(g.constant_references, 0);
+    }
+
+    #[test]
+    fn register_high_water_mark() {
+        let few = stats("f = |a| a + 1");
+        let many = stats("f = |a, b, c, d, e| [a, b, c, d, e]");
+
+        assert!(few.functions[1].register_count < many.functions[1].register_count);
+        assert!(many.functions[1].register_count >= 5);
+    }
+}
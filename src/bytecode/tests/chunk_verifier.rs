@@ -0,0 +1,455 @@
+mod bytecode_test_utils;
+
+mod bytecode {
+    use {
+        crate::bytecode_test_utils::compile,
+        koto_bytecode::{Chunk, ChunkBuilder, DebugInfo, Instruction, Loader, Op, MAX_SIZE_HINT},
+        koto_parser::ConstantPool,
+    };
+
+    fn build(instructions: Vec<Instruction>) -> Chunk {
+        let mut builder = ChunkBuilder::new();
+        for instruction in instructions {
+            builder.push(instruction).unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Chunk {
+        Chunk::new(bytes, ConstantPool::default(), None, DebugInfo::default())
+    }
+
+    fn check_verifier_error(chunk: &Chunk, expected_ip: usize) {
+        match chunk.verify() {
+            Ok(_) => panic!("Expected an error while verifying the chunk"),
+            Err(error) => assert_eq!(error.ip, expected_ip, "{}", error),
+        }
+    }
+
+    #[test]
+    fn compiled_scripts_are_valid() {
+        let chunk = compile(
+            "\
+f = |n, rest...|
+  match n
+    0 then 'zero'
+    1 or 2 then 'small'
+    x if x < 0 then 'negative'
+    else 'large'
+g = ||
+  for i in 0..10
+    yield f i
+result = try
+  for x in g()
+    if x == 'large'
+      throw x
+catch e
+  e
+finally
+  debug result
+",
+        );
+        assert!(chunk.verify().is_ok());
+    }
+
+    #[test]
+    fn empty_chunk() {
+        assert!(from_bytes(vec![]).verify().is_ok());
+    }
+
+    mod decoding {
+        use super::*;
+
+        #[test]
+        fn unknown_op() {
+            let mut chunk = build(vec![Instruction::SetEmpty { register: 0 }]);
+            chunk.bytes.push(Op::Unused255 as u8);
+            check_verifier_error(&chunk, 2);
+        }
+
+        #[test]
+        fn truncated_instruction() {
+            let mut chunk = build(vec![
+                Instruction::SetEmpty { register: 0 },
+                Instruction::Return { register: 0 },
+            ]);
+            chunk.bytes.pop();
+            check_verifier_error(&chunk, 2);
+        }
+
+        #[test]
+        fn truncated_jump_table() {
+            let mut chunk = compile(
+                "\
+x = 'foo'
+match x
+  'a' then 0
+  'b' then 1
+  'c' then 2
+  'd' then 3
+",
+            )
+            .as_ref()
+            .clone();
+            let jump_table_ip = chunk
+                .bytes
+                .iter()
+                .position(|byte| *byte == Op::JumpTable as u8)
+                .unwrap();
+            chunk.bytes.truncate(jump_table_ip + 8);
+            check_verifier_error(&chunk, jump_table_ip);
+        }
+    }
+
+    mod constants {
+        use super::*;
+
+        #[test]
+        fn missing_constant() {
+            let mut chunk = compile("x = 'hello'").as_ref().clone();
+            chunk.constants = ConstantPool::default();
+            check_verifier_error(&chunk, 0);
+        }
+
+        #[test]
+        fn constant_with_wrong_type() {
+            let mut chunk = compile("x = 42.0").as_ref().clone();
+            assert_eq!(chunk.bytes[0], Op::LoadFloat as u8);
+            // Load the float constant as a string
+            chunk.bytes[0] = Op::LoadString as u8;
+            check_verifier_error(&chunk, 0);
+        }
+
+        #[test]
+        fn size_hint_is_too_large() {
+            let chunk = build(vec![
+                Instruction::SequenceStart {
+                    register: 0,
+                    size_hint: MAX_SIZE_HINT,
+                },
+                Instruction::Return { register: 0 },
+            ]);
+            assert!(chunk.verify().is_ok());
+
+            for instruction in [
+                Instruction::MakeMap {
+                    register: 0,
+                    size_hint: MAX_SIZE_HINT + 1,
+                },
+                Instruction::SequenceStart {
+                    register: 0,
+                    size_hint: u32::MAX as usize,
+                },
+                Instruction::StringStart {
+                    register: 0,
+                    size_hint: u32::MAX as usize,
+                },
+            ] {
+                let chunk = build(vec![instruction, Instruction::Return { register: 0 }]);
+                check_verifier_error(&chunk, 0);
+            }
+        }
+
+        #[test]
+        fn missing_coverage_point() {
+            let chunk = build(vec![
+                Instruction::Coverage { index: 0 },
+                Instruction::SetEmpty { register: 0 },
+                Instruction::Return { register: 0 },
+            ]);
+            check_verifier_error(&chunk, 0);
+        }
+    }
+
+    mod jumps {
+        use {super::*, Instruction::*};
+
+        #[test]
+        fn jump_into_the_middle_of_an_instruction() {
+            let mut chunk = build(vec![
+                Jump { offset: 0 },
+                SetEmpty { register: 0 },
+                Return { register: 0 },
+            ]);
+            // Jump: op + u16 offset
+            chunk.bytes[1] = 1;
+            check_verifier_error(&chunk, 0);
+        }
+
+        #[test]
+        fn jump_past_the_end_of_the_chunk() {
+            let chunk = build(vec![Jump { offset: 0 }]);
+            assert!(chunk.verify().is_ok());
+
+            let mut chunk = chunk;
+            chunk.bytes[1] = 1;
+            check_verifier_error(&chunk, 0);
+        }
+
+        #[test]
+        fn jump_back_before_the_start_of_the_chunk() {
+            let mut chunk = build(vec![SetEmpty { register: 0 }, JumpBack { offset: 0 }]);
+            chunk.bytes[3] = 10;
+            check_verifier_error(&chunk, 2);
+        }
+
+        #[test]
+        fn jump_out_of_a_function() {
+            let mut builder = ChunkBuilder::new();
+            let function_end = builder.new_label();
+            let outside = builder.new_label();
+            builder
+                .push_with_target(
+                    SimpleFunction {
+                        register: 0,
+                        arg_count: 0,
+                        size: 0,
+                    },
+                    function_end,
+                )
+                .unwrap();
+            builder
+                .push_with_target(Jump { offset: 0 }, outside)
+                .unwrap();
+            builder.place_label(function_end).unwrap();
+            builder.place_label(outside).unwrap();
+            builder.push(Return { register: 0 }).unwrap();
+            let chunk = builder.build().unwrap();
+
+            // The Jump follows the 5 byte SimpleFunction instruction
+            check_verifier_error(&chunk, 5);
+        }
+    }
+
+    mod functions {
+        use {super::*, Instruction::*};
+
+        fn function_chunk(body: Vec<Instruction>, arg_count: u8) -> Chunk {
+            let mut builder = ChunkBuilder::new();
+            let function_end = builder.new_label();
+            builder
+                .push_with_target(
+                    SimpleFunction {
+                        register: 0,
+                        arg_count,
+                        size: 0,
+                    },
+                    function_end,
+                )
+                .unwrap();
+            for instruction in body {
+                builder.push(instruction).unwrap();
+            }
+            builder.place_label(function_end).unwrap();
+            builder.push(Return { register: 0 }).unwrap();
+            builder.build().unwrap()
+        }
+
+        #[test]
+        fn function_with_return() {
+            let chunk = function_chunk(vec![Return { register: 0 }], 1);
+            assert!(chunk.verify().is_ok());
+        }
+
+        #[test]
+        fn execution_continues_past_the_end_of_a_function() {
+            let chunk = function_chunk(vec![SetEmpty { register: 0 }], 0);
+            check_verifier_error(&chunk, 5);
+        }
+
+        #[test]
+        fn function_larger_than_the_chunk() {
+            let mut chunk = function_chunk(vec![Return { register: 0 }], 1);
+            // SimpleFunction: op, register, arg count, u16 size
+            chunk.bytes[3] = 100;
+            check_verifier_error(&chunk, 0);
+        }
+
+        #[test]
+        fn empty_function() {
+            let chunk = function_chunk(vec![], 0);
+            check_verifier_error(&chunk, 0);
+        }
+    }
+
+    mod registers {
+        use {super::*, Instruction::*};
+
+        #[test]
+        fn register_read_before_assignment() {
+            let chunk = build(vec![
+                SetEmpty { register: 0 },
+                Add {
+                    register: 2,
+                    lhs: 0,
+                    rhs: 1,
+                },
+            ]);
+            check_verifier_error(&chunk, 2);
+        }
+
+        #[test]
+        fn register_assigned_on_one_branch() {
+            let mut builder = ChunkBuilder::new();
+            let skip = builder.new_label();
+            builder
+                .push(SetBool {
+                    register: 0,
+                    value: true,
+                })
+                .unwrap();
+            builder
+                .push_with_target(
+                    JumpIf {
+                        register: 0,
+                        offset: 0,
+                        jump_condition: true,
+                    },
+                    skip,
+                )
+                .unwrap();
+            builder.push(SetEmpty { register: 1 }).unwrap();
+            builder.place_label(skip).unwrap();
+            builder.push(Return { register: 1 }).unwrap();
+            let chunk = builder.build().unwrap();
+
+            // SetBool (2 bytes), JumpIf (4 bytes), SetEmpty (2 bytes)
+            check_verifier_error(&chunk, 8);
+        }
+
+        #[test]
+        fn unreachable_instructions_are_ignored() {
+            let chunk = build(vec![
+                SetEmpty { register: 0 },
+                Return { register: 0 },
+                Return { register: 42 },
+            ]);
+            assert!(chunk.verify().is_ok());
+        }
+
+        #[test]
+        fn function_args_are_assigned() {
+            let mut builder = ChunkBuilder::new();
+            let function_end = builder.new_label();
+            builder
+                .push_with_target(
+                    SimpleFunction {
+                        register: 0,
+                        arg_count: 2,
+                        size: 0,
+                    },
+                    function_end,
+                )
+                .unwrap();
+            builder
+                .push(Add {
+                    register: 2,
+                    lhs: 0,
+                    rhs: 1,
+                })
+                .unwrap();
+            builder.push(Return { register: 2 }).unwrap();
+            builder.place_label(function_end).unwrap();
+            builder.push(Return { register: 0 }).unwrap();
+            assert!(builder.build().unwrap().verify().is_ok());
+        }
+
+        #[test]
+        fn registers_above_the_frame_base_are_removed_by_calls() {
+            let chunk = build(vec![
+                SetEmpty { register: 0 },
+                SetEmpty { register: 1 },
+                SetEmpty { register: 2 },
+                Call {
+                    result: 0,
+                    function: 0,
+                    frame_base: 1,
+                    arg_count: 1,
+                },
+                Return { register: 2 },
+            ]);
+            // 3 SetEmpty instructions, followed by Call (5 bytes)
+            check_verifier_error(&chunk, 11);
+        }
+
+        #[test]
+        fn call_results_in_the_removed_frame_are_unassigned() {
+            let mut chunk = compile(
+                "\
+x = [1, 2, 3]
+y = x.first()
+y
+",
+            )
+            .as_ref()
+            .clone();
+            // CallInstance: op, result, function, frame base, arg count, instance
+            let call_ip = chunk
+                .bytes
+                .iter()
+                .position(|byte| *byte == Op::CallInstance as u8)
+                .unwrap();
+            assert!(chunk.verify().is_ok());
+
+            // Place the call's result in the frame base, which is removed after the call,
+            // and then return the result.
+            let frame_base = chunk.bytes[call_ip + 3];
+            chunk.bytes[call_ip + 1] = frame_base;
+            let return_ip = chunk.bytes.len() - 2;
+            assert_eq!(chunk.bytes[return_ip], Op::Return as u8);
+            chunk.bytes[return_ip + 1] = frame_base;
+            check_verifier_error(&chunk, return_ip);
+        }
+
+        #[test]
+        fn call_args_must_be_assigned() {
+            let chunk = build(vec![
+                SetEmpty { register: 0 },
+                Call {
+                    result: 0,
+                    function: 0,
+                    frame_base: 1,
+                    arg_count: 2,
+                },
+            ]);
+            check_verifier_error(&chunk, 2);
+        }
+
+        #[test]
+        fn catch_block_registers() {
+            let script = "\
+x = try
+  y = 1
+  z = 2
+catch e
+  e
+";
+            assert!(compile(script).verify().is_ok());
+        }
+    }
+
+    mod loader {
+        use super::*;
+
+        #[test]
+        fn invalid_serialized_chunks_are_rejected() {
+            let mut chunk = compile("x = 'hello'").as_ref().clone();
+            chunk.constants = ConstantPool::default();
+
+            let mut loader = Loader::default();
+            let error = loader.load_script(&chunk.serialize(), &None).unwrap_err();
+            assert!(
+                error.to_string().starts_with("Invalid bytecode"),
+                "{}",
+                error
+            );
+        }
+
+        #[test]
+        fn valid_serialized_chunks_are_loaded() {
+            let chunk = compile("x = 'hello'");
+            let mut loader = Loader::default();
+            assert!(loader.load_script(&chunk.serialize(), &None).is_ok());
+        }
+    }
+}
@@ -1,6 +1,6 @@
 mod bytecode {
     use {
-        koto_bytecode::{Compiler, CompilerSettings},
+        koto_bytecode::{Compiler, CompilerSettings, Loader},
         koto_parser::Parser,
     };
 
@@ -28,6 +28,16 @@ x = 1 + _
             check_compilation_fails(source);
         }
 
+        #[test]
+        fn multi_assignment_with_too_many_targets() {
+            let targets = (0..128)
+                .map(|i| format!("x{}", i))
+                .collect::<Vec<_>>()
+                .join(", ");
+            check_compilation_fails(&format!("{} = foo", targets));
+            check_compilation_fails(&format!("rest..., {} = foo", targets));
+        }
+
         #[test]
         fn match_insufficient_patterns() {
             let source = "
@@ -54,5 +64,80 @@ match [1, 2, 3]
 ";
             check_compilation_fails(source);
         }
+
+        #[test]
+        fn match_alternatives_with_inconsistent_bindings() {
+            let source = "
+match (1, 0)
+  (a, 1) or (1, b) then a
+  else 0
+";
+            check_compilation_fails(source);
+        }
+    }
+
+    mod multiple_errors {
+        use super::*;
+
+        fn error_lines(source: &str) -> Vec<u32> {
+            let ast = Parser::parse(source).unwrap();
+            match Compiler::compile(&ast, CompilerSettings::default()) {
+                Ok(_) => panic!("\nUnexpected success while compiling: {}", source),
+                Err(error) => error.errors().map(|error| error.span.start.line).collect(),
+            }
+        }
+
+        #[test]
+        fn errors_in_independent_expressions() {
+            let source = "
+x = 1 + _
+y = 2
+z = y + _
+";
+            assert_eq!(error_lines(source), &[2, 4]);
+        }
+
+        #[test]
+        fn errors_in_functions() {
+            let source = "
+f = ||
+  a = 1 + _
+  b = 2 + _
+g = || 1 + _
+";
+            // Compilation of a function stops at the first error in its body
+            assert_eq!(error_lines(source), &[3, 5]);
+        }
+
+        #[test]
+        fn single_error() {
+            let error = Compiler::compile(&Parser::parse("x = 1 + _").unwrap(), Default::default())
+                .unwrap_err();
+            assert!(error.additional_errors.is_empty());
+        }
+
+        #[test]
+        fn check_script_includes_parser_errors() {
+            let source = "
+x = 1 + _
+y = (1 +
+z = x + _
+";
+            let errors = Loader::default().check_script(source, &None);
+            let messages = errors
+                .iter()
+                .map(|error| format!("{:#}", error))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                messages,
+                &[
+                    "Missing rhs for binary op",
+                    "Expected expression",
+                    "Missing rhs for binary op"
+                ]
+            );
+
+            assert!(Loader::default().check_script("x = 1", &None).is_empty());
+        }
     }
 }
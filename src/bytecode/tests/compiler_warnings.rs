@@ -0,0 +1,375 @@
+mod bytecode {
+    use {
+        koto_bytecode::{Compiler, CompilerSettings},
+        koto_parser::Parser,
+    };
+
+    // Compiles the source and checks that the warnings match the expected (line, column) pairs
+    fn check_warnings(source: &str, expected: &[(u32, u32)]) {
+        let ast = match Parser::parse(source) {
+            Ok(ast) => ast,
+            Err(parser_error) => {
+                panic!("Failure while parsing:\n{}\n{}", source, parser_error);
+            }
+        };
+
+        let warnings = match Compiler::compile(&ast, CompilerSettings::default()) {
+            Ok((_, _, warnings)) => warnings,
+            Err(error) => panic!("Failure while compiling:\n{}\n{}", source, error),
+        };
+
+        let positions = warnings
+            .iter()
+            .map(|warning| (warning.span.start.line, warning.span.start.column))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            positions, expected,
+            "\nUnexpected warnings while compiling: {}\n{:#?}",
+            source, warnings
+        );
+    }
+
+    mod discarded_values {
+        use super::*;
+
+        #[test]
+        fn uncalled_function() {
+            let source = "
+|x| x * x
+42
+";
+            check_warnings(source, &[(2, 1)]);
+        }
+
+        #[test]
+        fn uncalled_id() {
+            let source = "
+f = || 42
+f
+f()
+";
+            check_warnings(source, &[(3, 1)]);
+        }
+
+        #[test]
+        fn literals_in_function_body() {
+            let source = "
+f = ||
+  1
+  'hello'
+  (true)
+  ()
+f()
+";
+            check_warnings(source, &[(3, 3), (4, 3), (5, 3)]);
+        }
+
+        #[test]
+        fn last_expression_is_not_discarded() {
+            let source = "
+f = |x| x
+f
+";
+            check_warnings(source, &[]);
+        }
+
+        #[test]
+        fn expressions_with_effects() {
+            let source = "
+f = |x| x
+f 1
+f()
+x = f
+'$x'
+x
+";
+            check_warnings(source, &[]);
+        }
+
+        #[test]
+        fn no_warnings_in_repl_mode() {
+            let source = "
+|x| x
+42
+";
+            let ast = Parser::parse(source).unwrap();
+            let (_, _, warnings) = Compiler::compile(
+                &ast,
+                CompilerSettings {
+                    repl_mode: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert!(warnings.is_empty());
+        }
+    }
+
+    mod misleading_indentation {
+        use super::*;
+
+        #[test]
+        fn indented_call_after_call() {
+            let source = "
+io.print 1
+  io.print 2
+";
+            check_warnings(source, &[(3, 3)]);
+        }
+
+        #[test]
+        fn indented_args_after_call_with_parens() {
+            let source = "
+foo.bar(1)
+  2
+";
+            check_warnings(source, &[(3, 3)]);
+        }
+
+        #[test]
+        fn indented_call_args() {
+            let source = "
+foo.bar
+  1, 2
+";
+            check_warnings(source, &[]);
+        }
+
+        #[test]
+        fn chained_calls_on_one_line() {
+            let source = "
+foo.bar(1) 2
+";
+            check_warnings(source, &[]);
+        }
+    }
+
+    mod unused_locals {
+        use super::*;
+
+        #[test]
+        fn unused_top_level_value() {
+            let source = "
+x = 1
+y = 2
+y
+";
+            check_warnings(source, &[(2, 1)]);
+        }
+
+        #[test]
+        fn unused_function_local() {
+            let source = "
+f = |a|
+  b = a * 2
+  a
+f 1
+";
+            check_warnings(source, &[(3, 3)]);
+        }
+
+        #[test]
+        fn unused_value_in_multi_assignment() {
+            let source = "
+a, b = 1, 2
+b
+";
+            check_warnings(source, &[(2, 1)]);
+        }
+
+        #[test]
+        fn reassigned_value_is_only_reported_once() {
+            let source = "
+x = 1
+x = 2
+";
+            check_warnings(source, &[(2, 1)]);
+        }
+
+        #[test]
+        fn values_used_in_various_ways() {
+            let source = "
+a = 1
+b = 2
+c = 3
+d = || 4
+e = 5
+f = 'hello'
+g = || a
+x = {b}
+y = '$c ${d()}'
+z = e + f.size()
+export foo = x, y, z, g
+";
+            check_warnings(source, &[]);
+        }
+
+        #[test]
+        fn recursive_and_forward_definitions() {
+            let source = "
+is_even = |n| if n == 0 then true else is_odd n - 1
+is_odd = |n| if n == 0 then false else is_even n - 1
+is_even 42
+";
+            check_warnings(source, &[]);
+        }
+
+        #[test]
+        fn exports_and_args_are_ignored() {
+            let source = "
+export x = 1
+export f = |a, b| a
+";
+            check_warnings(source, &[]);
+        }
+    }
+
+    mod shadowing {
+        use super::*;
+
+        #[test]
+        fn local_shadows_value_in_enclosing_function() {
+            let source = "
+x = 1
+f = ||
+  x = 2
+  x
+f() + x
+";
+            check_warnings(source, &[(4, 3)]);
+        }
+
+        #[test]
+        fn value_assigned_after_function() {
+            let source = "
+f = ||
+  x = 2
+  x
+x = 1
+f() + x
+";
+            check_warnings(source, &[]);
+        }
+    }
+
+    mod unreachable_code {
+        use super::*;
+
+        #[test]
+        fn expression_after_return() {
+            let source = "
+f = |x|
+  return x
+  io.print x
+f 1
+";
+            check_warnings(source, &[(4, 3)]);
+        }
+
+        #[test]
+        fn expressions_after_break_and_throw() {
+            let source = "
+for x in 0..10
+  if x > 5
+    break
+    io.print x
+  throw 'error'
+  io.print x
+";
+            // The outer block is checked before the nested block is compiled
+            check_warnings(source, &[(7, 3), (5, 5)]);
+        }
+
+        #[test]
+        fn conditional_exit() {
+            let source = "
+f = |x|
+  if x then return 1
+  2
+f true
+";
+            check_warnings(source, &[]);
+        }
+    }
+
+    mod unreachable_match_arms {
+        use super::*;
+
+        #[test]
+        fn arm_after_id_pattern() {
+            let source = "
+match 42
+  0 then 'zero'
+  x then x
+  1 then 'one'
+";
+            check_warnings(source, &[(5, 3)]);
+        }
+
+        #[test]
+        fn arm_after_wildcard_pattern() {
+            let source = "
+match 42
+  _ then 'any'
+  1 then 'one'
+";
+            check_warnings(source, &[(4, 3)]);
+        }
+
+        #[test]
+        fn conditional_catch_all() {
+            let source = "
+match 42
+  x if x > 10 then 'big'
+  1 then 'one'
+  else 'other'
+";
+            check_warnings(source, &[]);
+        }
+    }
+    mod warnings_as_errors {
+        use super::*;
+
+        #[test]
+        fn first_warning_is_returned_as_error() {
+            let source = "
+f = || 42
+f
+42
+";
+            let ast = Parser::parse(source).unwrap();
+            let settings = CompilerSettings {
+                warnings_as_errors: true,
+                ..Default::default()
+            };
+
+            match Compiler::compile(&ast, settings) {
+                Ok(_) => panic!("Unexpected success while compiling:\n{}", source),
+                Err(error) => {
+                    assert_eq!(
+                        (error.span.start.line, error.span.start.column),
+                        (3, 1),
+                        "Unexpected error: {}",
+                        error
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn no_error_without_warnings() {
+            let source = "
+f = || 42
+f()
+";
+            let ast = Parser::parse(source).unwrap();
+            let settings = CompilerSettings {
+                warnings_as_errors: true,
+                ..Default::default()
+            };
+
+            assert!(Compiler::compile(&ast, settings).is_ok());
+        }
+    }
+}
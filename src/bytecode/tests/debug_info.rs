@@ -0,0 +1,158 @@
+mod bytecode {
+    use {
+        koto_bytecode::{Chunk, Compiler, CompilerSettings, DisassembledInstruction, Disassembler},
+        koto_parser::Parser,
+        std::sync::Arc,
+    };
+
+    fn disassemble(script: &str, strip_debug_info: bool) -> Vec<DisassembledInstruction> {
+        let ast = match Parser::parse(script) {
+            Ok(ast) => ast,
+            Err(error) => panic!("Failure while parsing:\n{}\n{}", script, error),
+        };
+
+        let settings = CompilerSettings {
+            strip_debug_info,
+            ..Default::default()
+        };
+        let (bytes, debug_info, _) = match Compiler::compile(&ast, settings) {
+            Ok(result) => result,
+            Err(error) => panic!("Failure while compiling:\n{}\n{}", script, error),
+        };
+
+        let chunk = Chunk::new(bytes, ast.consume_constants(), None, debug_info);
+        Disassembler::new(Arc::new(chunk)).collect()
+    }
+
+    fn lines(instructions: &[DisassembledInstruction]) -> Vec<(usize, u32)> {
+        instructions
+            .iter()
+            .map(|instruction| (instruction.depth, instruction.span.unwrap().start.line))
+            .collect()
+    }
+
+    const SCRIPT: &str = "\
+x = 1
+f = |n|
+  y = n * 2
+  y + x
+z = f 42
+debug z
+";
+
+    #[test]
+    fn expression_spans_are_kept_by_default() {
+        let instructions = disassemble(SCRIPT, false);
+        let function_lines = lines(&instructions)
+            .into_iter()
+            .filter(|(depth, _)| *depth == 1)
+            .map(|(_, line)| line)
+            .collect::<Vec<_>>();
+        assert!(function_lines.contains(&3));
+        assert!(function_lines.contains(&4));
+    }
+
+    #[test]
+    fn stripped_spans_refer_to_functions() {
+        let instructions = disassemble(SCRIPT, true);
+
+        for (depth, line) in lines(&instructions) {
+            match depth {
+                // The main block's span starts on the script's first line
+                0 => assert_eq!(line, 1),
+                // Instructions in the function refer to the start of the function
+                1 => assert_eq!(line, 2),
+                _ => unreachable!(),
+            }
+        }
+
+        // The bytecode is unaffected
+        let unstripped = disassemble(SCRIPT, false);
+        assert_eq!(
+            instructions
+                .iter()
+                .map(|instruction| instruction.instruction.to_string())
+                .collect::<Vec<_>>(),
+            unstripped
+                .iter()
+                .map(|instruction| instruction.instruction.to_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn source_spans_for_instruction_positions() {
+        let mut loader = koto_bytecode::Loader::default();
+        let chunk = loader.compile_script(SCRIPT, &None).unwrap();
+
+        for instruction in Disassembler::new(chunk.clone()) {
+            // Each byte of an instruction maps to the instruction's span
+            for ip in instruction.ip..instruction.ip + instruction.size {
+                assert_eq!(chunk.source_span(ip), instruction.span);
+            }
+        }
+        assert_eq!(chunk.source_span(chunk.bytes.len()), None);
+
+        // The span can be used to find the instruction's source
+        let debug = Disassembler::new(chunk.clone())
+            .find(|instruction| instruction.instruction.to_string() == "Debug")
+            .unwrap();
+        let span = chunk.source_span(debug.ip).unwrap();
+        assert_eq!(&chunk.debug_info.source[span.byte_range()], "debug z");
+    }
+
+    #[test]
+    fn source_map_entries() {
+        let mut loader = koto_bytecode::Loader::default();
+        let chunk = loader.compile_script(SCRIPT, &None).unwrap();
+        let source_map = chunk.debug_info.source_map();
+
+        assert_eq!(source_map.first().unwrap().0, 0);
+        for pair in source_map.windows(2) {
+            assert!(pair[0].0 < pair[1].0);
+        }
+        for (ip, span) in source_map {
+            assert_eq!(chunk.source_span(*ip), Some(*span));
+        }
+    }
+
+    #[test]
+    fn function_names() {
+        let script = "\
+outer = ||
+  g = |x| x * 2
+  (1..3).each(|x| g x).to_tuple()
+m = {foo: |n| n + 1}
+outer()
+";
+        let mut loader = koto_bytecode::Loader::default();
+        let chunk = loader.compile_script(script, &None).unwrap();
+
+        // Each line's instructions are attributed to the innermost function that contains them
+        let mut names = Disassembler::new(chunk.clone())
+            .map(|instruction| {
+                (
+                    instruction.span.unwrap().start.line,
+                    chunk.debug_info.function_name(instruction.ip),
+                )
+            })
+            .collect::<Vec<_>>();
+        names.dedup();
+
+        assert_eq!(
+            names,
+            &[
+                (1, None),
+                (2, Some("outer")),
+                (2, Some("g")),
+                (3, Some("outer")),
+                (3, None),
+                (3, Some("outer")),
+                (4, None),
+                (4, Some("foo")),
+                (4, None),
+                (5, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+mod bytecode_test_utils;
+
+mod bytecode {
+    use {
+        crate::bytecode_test_utils::compile,
+        koto_bytecode::{
+            Chunk, ChunkBuilder, CompilerSettings, Disassembler, Instruction, Loader, Operand,
+            OperandValue,
+        },
+        koto_parser::Constant,
+        std::sync::Arc,
+    };
+
+    fn operand(name: &'static str, value: OperandValue) -> Operand {
+        Operand { name, value }
+    }
+
+    #[test]
+    fn operands_and_constants() {
+        let chunk = compile("x = 'hello'\ny = x.size()");
+        let instructions = Disassembler::new(chunk.clone()).collect::<Vec<_>>();
+
+        let load = &instructions[0];
+        assert_eq!(load.ip, 0);
+        assert_eq!(load.instruction.to_string(), "LoadString");
+        assert_eq!(
+            load.operands,
+            &[
+                operand("register", OperandValue::Register(0)),
+                operand("constant", OperandValue::Constant(1_u32.into())),
+            ]
+        );
+        assert_eq!(
+            load.constants()
+                .map(|constant| chunk.constants.get_str(constant))
+                .collect::<Vec<_>>(),
+            &["hello"]
+        );
+
+        let access = instructions
+            .iter()
+            .find(|instruction| matches!(instruction.instruction, Instruction::Access { .. }))
+            .unwrap();
+        assert_eq!(
+            access
+                .constants()
+                .map(|constant| chunk.constants.get_str(constant))
+                .collect::<Vec<_>>(),
+            &["size"]
+        );
+    }
+
+    #[test]
+    fn small_int_rhs() {
+        let chunk = compile("x = 42\ny = x + 1\nz = x < 300");
+        let instructions = Disassembler::new(chunk).collect::<Vec<_>>();
+
+        let add = instructions
+            .iter()
+            .find(|instruction| matches!(instruction.instruction, Instruction::AddU8 { .. }))
+            .unwrap();
+        assert_eq!(
+            add.operands,
+            &[
+                operand("register", OperandValue::Register(1)),
+                operand("lhs", OperandValue::Register(0)),
+                operand("rhs", OperandValue::Int(1)),
+            ]
+        );
+
+        // Literals that don't fit in a byte use the generic op
+        assert!(instructions
+            .iter()
+            .any(|instruction| matches!(instruction.instruction, Instruction::Less { .. })));
+    }
+
+    #[test]
+    fn calls_in_return_position_are_tail_calls() {
+        let count_tail_calls = |script| {
+            Disassembler::new(compile(script))
+                .filter(|instruction| {
+                    matches!(
+                        instruction.instruction,
+                        Instruction::TailCall { .. } | Instruction::TailCallInstance { .. }
+                    )
+                })
+                .count()
+        };
+
+        assert_eq!(count_tail_calls("f = |n| f n - 1"), 1);
+        assert_eq!(count_tail_calls("f = |n|\n  return f n - 1"), 1);
+        assert_eq!(count_tail_calls("f = |n| if n == 0 then n else f n - 1"), 1);
+        assert_eq!(count_tail_calls("f = |m| m.foo 42"), 1);
+        // Calls that aren't returned, or at the top level of a script
+        assert_eq!(count_tail_calls("f = |n| (f n) + 1"), 0);
+        assert_eq!(count_tail_calls("f 42"), 0);
+        // Generators and functions with annotated return types
+        assert_eq!(count_tail_calls("f = |n|\n  yield n\n  return f n"), 0);
+        assert_eq!(count_tail_calls("f = |n| -> Number f n"), 0);
+    }
+
+    #[test]
+    fn unreachable_accesses_are_not_captured() {
+        // Returns the capture counts of the functions defined with the given body
+        let captures = |body: &str| {
+            let script = format!("a, b, c = 1, 2, 3\nf = ||\n{}\nf()", body);
+            Disassembler::new(compile(&script))
+                .filter_map(|instruction| match instruction.instruction {
+                    Instruction::Function { capture_count, .. } => Some(capture_count),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(captures("  a + b + c"), &[3]);
+        // Following an unconditional exit
+        assert_eq!(captures("  return a\n  b + c"), &[1]);
+        // Branches with constant conditions
+        assert_eq!(captures("  if false then a else b"), &[1]);
+        assert_eq!(captures("  if true then a else b"), &[1]);
+        assert_eq!(captures("  while false\n    a\n  b"), &[1]);
+        // Match arms following a catch-all arm
+        assert_eq!(captures("  match a\n    x then b\n    _ then c"), &[2]);
+        // Unreachable code in nested functions
+        assert_eq!(captures("  g = ||\n    return a\n    b\n  g()"), &[1, 1]);
+        // A non-constant condition keeps both branches
+        assert_eq!(captures("  if a then b else c"), &[3]);
+    }
+
+    #[test]
+    fn coverage_points() {
+        let script = "\
+f = |x| if x then 1 else 2
+for i in 0..2
+  f i == 1
+";
+        let mut loader = Loader::default();
+        loader.set_compiler_settings(CompilerSettings {
+            coverage: true,
+            ..Default::default()
+        });
+        let chunk = loader.compile_script(script, &None).unwrap();
+
+        let indices = Disassembler::new(chunk.clone())
+            .filter_map(|instruction| match instruction.instruction {
+                Instruction::Coverage { index } => Some(index),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Each statement has a point, along with the function body and each of the if's branches
+        assert_eq!(indices, (0..6).collect::<Vec<_>>());
+
+        let lines = chunk
+            .debug_info
+            .coverage_points()
+            .iter()
+            .map(|span| span.start.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, &[1, 1, 1, 1, 2, 3]);
+
+        // Coverage isn't instrumented by default
+        assert!(!Disassembler::new(compile(script))
+            .any(|instruction| matches!(instruction.instruction, Instruction::Coverage { .. })));
+    }
+
+    #[test]
+    fn module_level_constants_are_folded() {
+        let script = "\
+a = 60 * 60 * 24
+b = 'foo' + ('bar' + 'baz')
+c = [1 + 1, -(2.5 * 2)], {x: 10 / 4}
+d = 1 % 0
+f = || 1 + 2
+";
+        let arithmetic_ops = |chunk: Arc<Chunk>| {
+            Disassembler::new(chunk)
+                .filter(|instruction| {
+                    matches!(
+                        instruction.instruction,
+                        Instruction::Add { .. }
+                            | Instruction::AddU8 { .. }
+                            | Instruction::Multiply { .. }
+                            | Instruction::Divide { .. }
+                            | Instruction::Modulo { .. }
+                            | Instruction::Negate { .. }
+                    )
+                })
+                .map(|instruction| instruction.instruction.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let chunk = compile(script);
+        // Integer division by zero is left to the runtime, along with expressions in functions
+        assert_eq!(arithmetic_ops(chunk.clone()), &["Modulo", "AddU8"]);
+
+        let constants = chunk.constants.iter().collect::<Vec<_>>();
+        for expected in &[
+            Constant::I64(86400),
+            Constant::Str("foobarbaz"),
+            Constant::F64(-5.0),
+            Constant::F64(2.5),
+        ] {
+            assert!(
+                constants.contains(expected),
+                "Missing constant {:?}",
+                expected
+            );
+        }
+
+        // Constants aren't folded when optimizations are disabled
+        let mut loader = Loader::default();
+        loader.set_compiler_settings(CompilerSettings {
+            optimize: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            arithmetic_ops(loader.compile_script(script, &None).unwrap()).len(),
+            10
+        );
+    }
+
+    #[test]
+    fn sizes_match_positions() {
+        let chunk = compile(
+            "\
+f = |n|
+  if n > 0
+    return n * 2
+  -1
+for i in 0..10
+  f i
+",
+        );
+        let instructions = Disassembler::new(chunk.clone()).collect::<Vec<_>>();
+
+        for pair in instructions.windows(2) {
+            assert_eq!(pair[0].ip + pair[0].size, pair[1].ip);
+        }
+        let last = instructions.last().unwrap();
+        assert_eq!(last.ip + last.size, chunk.bytes.len());
+    }
+
+    #[test]
+    fn spans() {
+        let chunk = compile("x = 1\n\ny = 'foo'");
+        let instructions = Disassembler::new(chunk).collect::<Vec<_>>();
+        let lines = instructions
+            .iter()
+            .map(|instruction| instruction.span.unwrap().start.line)
+            .collect::<Vec<_>>();
+        // The script's implicit return is associated with the final expression
+        assert_eq!(lines, &[1, 3, 3]);
+    }
+
+    #[test]
+    fn jump_targets_are_absolute() {
+        let chunk = compile("for x in y\n  z = x");
+        let instructions = Disassembler::new(chunk.clone()).collect::<Vec<_>>();
+
+        let jump_target = |name: &str| {
+            instructions
+                .iter()
+                .find(|instruction| instruction.instruction.to_string() == name)
+                .and_then(|instruction| {
+                    instruction
+                        .operands
+                        .iter()
+                        .find_map(|operand| match operand.value {
+                            OperandValue::JumpTarget(target) => Some(target),
+                            _ => None,
+                        })
+                })
+                .unwrap()
+        };
+
+        let iter_next = instructions
+            .iter()
+            .position(|instruction| matches!(instruction.instruction, Instruction::IterNext { .. }))
+            .unwrap();
+        let jump_back = instructions
+            .iter()
+            .position(|instruction| matches!(instruction.instruction, Instruction::JumpBack { .. }))
+            .unwrap();
+
+        // IterNext jumps to the end of the loop, and the loop body jumps back to the IterNext
+        assert_eq!(jump_target("IterNext"), instructions[jump_back + 1].ip);
+        assert_eq!(jump_target("JumpBack"), instructions[iter_next].ip);
+    }
+
+    #[test]
+    fn function_depth() {
+        let chunk = compile(
+            "\
+f = ||
+  g = ||
+    42
+  g
+x = f()
+",
+        );
+        let depths = Disassembler::new(chunk)
+            .map(|instruction| (instruction.instruction.to_string(), instruction.depth))
+            .collect::<Vec<_>>();
+
+        assert_eq!(depths.first().unwrap(), &("SimpleFunction".to_string(), 0));
+        assert!(depths.contains(&("SimpleFunction".to_string(), 1)));
+        assert!(depths.contains(&("LoadInt".to_string(), 2)));
+        assert_eq!(depths.last().unwrap().1, 0);
+    }
+
+    #[test]
+    fn render() {
+        let chunk = compile("x = 'hello'\ndebug x");
+        assert_eq!(
+            Disassembler::new(chunk).render(),
+            "\
+|1| x = 'hello'
+    0  LoadString          register: r0, constant: 1 (\"hello\")
+
+|2| debug x
+    3  Debug               register: r0, constant: 0 (\"x\")
+    9  Return              register: r0
+"
+        );
+    }
+
+    #[test]
+    fn render_without_source() {
+        let mut builder = ChunkBuilder::new();
+        builder
+            .push(Instruction::SetNumber {
+                register: 0,
+                value: 42,
+            })
+            .unwrap();
+        builder.push(Instruction::Return { register: 0 }).unwrap();
+        let chunk = Arc::new(builder.build().unwrap());
+
+        assert_eq!(
+            Disassembler::new(chunk).render(),
+            "    0  SetNumber           register: r0, value: 42
+    3  Return              register: r0
+"
+        );
+    }
+}
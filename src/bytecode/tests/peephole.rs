@@ -0,0 +1,174 @@
+mod bytecode {
+    use {
+        koto_bytecode::{
+            Chunk, Compiler, CompilerSettings, DisassembledInstruction, Disassembler, Instruction,
+            OperandValue,
+        },
+        koto_parser::Parser,
+        std::sync::Arc,
+    };
+
+    fn disassemble(script: &str, optimize: bool) -> Vec<DisassembledInstruction> {
+        let ast = match Parser::parse(script) {
+            Ok(ast) => ast,
+            Err(error) => panic!("Failure while parsing:\n{}\n{}", script, error),
+        };
+
+        let settings = CompilerSettings {
+            optimize,
+            ..Default::default()
+        };
+        let (bytes, debug_info, _) = match Compiler::compile(&ast, settings) {
+            Ok(result) => result,
+            Err(error) => panic!("Failure while compiling:\n{}\n{}", script, error),
+        };
+
+        let chunk = Chunk::new(bytes, ast.consume_constants(), None, debug_info);
+        Disassembler::new(Arc::new(chunk)).collect()
+    }
+
+    fn instruction_names(instructions: &[DisassembledInstruction]) -> Vec<String> {
+        instructions
+            .iter()
+            .map(|instruction| instruction.instruction.to_string())
+            .collect()
+    }
+
+    fn jump_target(instruction: &DisassembledInstruction) -> Option<usize> {
+        instruction
+            .operands
+            .iter()
+            .find_map(|operand| match operand.value {
+                OperandValue::JumpTarget(target) => Some(target),
+                _ => None,
+            })
+    }
+
+    #[test]
+    fn copy_then_return() {
+        let script = "x = 'hello'\ndebug x";
+
+        let unoptimized = instruction_names(&disassemble(script, false));
+        assert!(unoptimized.contains(&"Copy".to_string()));
+
+        let optimized = disassemble(script, true);
+        assert!(!instruction_names(&optimized).contains(&"Copy".to_string()));
+        assert!(matches!(
+            optimized.last().unwrap().instruction,
+            Instruction::Return { register: 0 }
+        ));
+    }
+
+    #[test]
+    fn redundant_loads() {
+        let script = "\
+x = 99
+x = 99
+x = 99
+";
+        let count_loads = |instructions: &[DisassembledInstruction]| {
+            instructions
+                .iter()
+                .filter(|instruction| {
+                    matches!(instruction.instruction, Instruction::LoadInt { .. })
+                })
+                .count()
+        };
+
+        assert_eq!(count_loads(&disassemble(script, false)), 3);
+        assert_eq!(count_loads(&disassemble(script, true)), 1);
+    }
+
+    #[test]
+    fn loads_after_modification_are_kept() {
+        let script = "\
+x = 99
+x += 1
+x = 99
+";
+        let loads = disassemble(script, true)
+            .iter()
+            .filter(|instruction| matches!(instruction.instruction, Instruction::LoadInt { .. }))
+            .count();
+        assert_eq!(loads, 2);
+    }
+
+    #[test]
+    fn jumps_to_jumps_are_threaded() {
+        let script = "\
+x = 0
+for i in 0..10
+  if i > 5
+    x += 1
+  else
+    x += 2
+x
+";
+        let instructions = disassemble(script, true);
+
+        for instruction in instructions.iter() {
+            if let Some(target) = jump_target(instruction) {
+                let target_instruction = instructions.iter().find(|other| other.ip == target);
+                if let Some(target_instruction) = target_instruction {
+                    assert!(
+                        !matches!(target_instruction.instruction, Instruction::Jump { .. }),
+                        "{} at ip {} jumps to a Jump at ip {}",
+                        instruction.instruction,
+                        instruction.ip,
+                        target
+                    );
+                }
+            }
+        }
+
+        // The jump over the else branch now jumps straight back to the start of the loop
+        let jump_backs = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction.instruction, Instruction::JumpBack { .. }))
+            .count();
+        assert_eq!(jump_backs, 2);
+    }
+
+    #[test]
+    fn instructions_and_spans_are_consistent() {
+        let script = "\
+f = |n|
+  match n
+    0 then 'zero'
+    1 or 2 then 'small'
+    x if x < 0 then 'negative'
+    else 'large'
+result = try
+  for i in 0..10
+    if i == 5
+      throw 'five'
+    f i
+catch e
+  e
+";
+        let optimized = disassemble(script, true);
+
+        for pair in optimized.windows(2) {
+            assert_eq!(pair[0].ip + pair[0].size, pair[1].ip);
+        }
+
+        let end = optimized.last().map_or(0, |last| last.ip + last.size);
+        for instruction in optimized.iter() {
+            assert!(!matches!(
+                instruction.instruction,
+                Instruction::Error { .. }
+            ));
+            assert!(instruction.span.is_some());
+
+            if let Some(target) = jump_target(instruction) {
+                assert!(
+                    target == end || optimized.iter().any(|other| other.ip == target),
+                    "Invalid jump target {} for {} at ip {}",
+                    target,
+                    instruction.instruction,
+                    instruction.ip
+                );
+            }
+        }
+    }
+}
@@ -16,6 +16,7 @@ impl Help {
         };
 
         let help_modules = [
+            include_str!("docs/reference/core_lib/coroutine.md"),
             include_str!("docs/reference/core_lib/io.md"),
             include_str!("docs/reference/core_lib/iterator.md"),
             include_str!("docs/reference/core_lib/koto.md"),
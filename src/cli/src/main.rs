@@ -3,7 +3,11 @@ mod repl;
 
 use {
     crossterm::tty::IsTty,
-    koto::{bytecode::Chunk, Koto, KotoSettings},
+    koto::{
+        bytecode::{Chunk, CompilerSettings, Disassembler},
+        runtime::{ChunkCoverage, TestOutcome, TestResult},
+        Koto, KotoSettings,
+    },
     repl::{Repl, ReplSettings},
     std::{
         fs,
@@ -28,16 +32,30 @@ USAGE:
 
 FLAGS:
     -e, --eval               Evaluate the script directly (rather than reading it from disk)
+    --check                  Check the script for errors without running it
     -i, --show_instructions  Show compiled instructions annotated with source lines
     -b, --show_bytecode      Show the script's compiled bytecode
+    --stats                  Show the size and register usage of the script's compiled functions
     -t, --tests              Run the script's tests before running the script
     -T, --import_tests       Run tests when importing modules
+    -p, --parallel_tests     Run each module's tests in parallel
+    -s, --shared_tests       Run tests with shared module state rather than isolated instances
+    -u, --update_snapshots   Update the snapshots used by test.snapshot rather than checking them
+    -c, --check_types        Check the annotated types of function arguments and return values
+    -r, --test_report <path> Write a JSON report of the test results to the given path
+    --cache_dir <path>       Cache compiled scripts and modules in the given directory
+    --coverage               Show the coverage of the script and its modules after running
+    --profile                Show the calls, timings, and allocations of functions after running
     -h, --help               Prints help information
     -v, --version            Prints version information
 
 ARGS:
     <script>     The koto script to run, as a file path, or as a string when --eval is set
     <args>...    Arguments to pass into the script
+
+ENVIRONMENT:
+    KOTO_UPDATE_SNAPSHOTS    Setting this to 1 has the same effect as --update_snapshots
+    KOTO_CACHE_DIR           Used as the cache directory when --cache_dir isn't provided
 ",
         version = version_string()
     )
@@ -48,10 +66,20 @@ struct KotoArgs {
     help: bool,
     version: bool,
     eval_script: bool,
+    check: bool,
     run_tests: bool,
     run_import_tests: bool,
+    parallel_tests: bool,
+    shared_tests: bool,
+    update_snapshots: bool,
+    test_report: Option<String>,
+    cache_dir: Option<String>,
+    coverage: bool,
+    profile: bool,
+    check_types: bool,
     show_bytecode: bool,
     show_instructions: bool,
+    show_stats: bool,
     script: Option<String>,
     script_args: Vec<String>,
 }
@@ -60,10 +88,26 @@ fn parse_arguments() -> Result<KotoArgs, String> {
     let mut args = pico_args::Arguments::from_env();
 
     let eval_script = args.contains(["-e", "--eval"]);
+    let check = args.contains("--check");
     let show_instructions = args.contains(["-i", "--show_instructions"]);
     let show_bytecode = args.contains(["-b", "--show_bytecode"]);
+    let show_stats = args.contains("--stats");
     let run_tests = args.contains(["-t", "--tests"]);
     let run_import_tests = args.contains(["-T", "--import_tests"]);
+    let parallel_tests = args.contains(["-p", "--parallel_tests"]);
+    let shared_tests = args.contains(["-s", "--shared_tests"]);
+    let update_snapshots = args.contains(["-u", "--update_snapshots"])
+        || matches!(std::env::var("KOTO_UPDATE_SNAPSHOTS").as_deref(), Ok("1"));
+    let check_types = args.contains(["-c", "--check_types"]);
+    let test_report = args
+        .opt_value_from_str(["-r", "--test_report"])
+        .map_err(|e| format!("Error while parsing arguments: {}", e))?;
+    let cache_dir = args
+        .opt_value_from_str("--cache_dir")
+        .map_err(|e| format!("Error while parsing arguments: {}", e))?
+        .or_else(|| std::env::var("KOTO_CACHE_DIR").ok());
+    let coverage = args.contains("--coverage");
+    let profile = args.contains("--profile");
     let help = args.contains(["-h", "--help"]);
     let version = args.contains(["-v", "--version"]);
 
@@ -87,10 +131,20 @@ fn parse_arguments() -> Result<KotoArgs, String> {
         help,
         version,
         eval_script,
+        check,
         run_tests,
         run_import_tests,
+        parallel_tests,
+        shared_tests,
+        update_snapshots,
+        test_report,
+        cache_dir,
+        coverage,
+        profile,
+        check_types,
         show_bytecode,
         show_instructions,
+        show_stats,
         script,
         script_args,
     })
@@ -125,6 +179,16 @@ fn run() -> Result<(), ()> {
     let koto_settings = KotoSettings {
         run_tests: args.run_tests,
         run_import_tests: args.run_import_tests,
+        isolate_tests: !args.shared_tests,
+        parallel_tests: args.parallel_tests,
+        update_snapshots: args.update_snapshots,
+        check_type_annotations: args.check_types,
+        cache_dir: args.cache_dir.map(|dir| dir.into()),
+        compiler_settings: CompilerSettings {
+            coverage: args.coverage,
+            ..Default::default()
+        },
+        profile: args.profile,
         ..Default::default()
     };
 
@@ -165,27 +229,60 @@ fn run() -> Result<(), ()> {
         prelude.add_map("toml", koto_toml::make_module());
         prelude.add_map("yaml", koto_yaml::make_module());
 
+        if args.check {
+            let errors = koto.check(&script);
+            print_warnings(&mut koto);
+            for error in errors.iter() {
+                eprintln!("Error: {}", error);
+            }
+            return if errors.is_empty() { Ok(()) } else { Err(()) };
+        }
+
         match koto.compile(&script) {
             Ok(chunk) => {
+                print_warnings(&mut koto);
+
                 if args.show_bytecode {
                     println!("{}\n", &Chunk::bytes_as_string(chunk.clone()));
                 }
                 if args.show_instructions {
-                    println!("Constants\n---------\n{}\n", chunk.constants.to_string());
+                    println!(
+                        "Constants\n---------\n{}{}\n",
+                        chunk.constants,
+                        chunk.constants.stats()
+                    );
 
-                    let script_lines = script.lines().collect::<Vec<_>>();
                     println!(
                         "Instructions\n------------\n{}",
-                        Chunk::instructions_as_string(chunk, &script_lines)
+                        Disassembler::new(chunk.clone()).render()
                     );
                 }
-                match koto.run_with_args(&args.script_args) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!("Error: {}", e);
+                if args.show_stats {
+                    println!("Stats\n-----\n{}", Chunk::stats(chunk));
+                }
+                let result = koto.run_with_args(&args.script_args);
+                // Warnings may have been produced by modules that were imported by the script
+                print_warnings(&mut koto);
+
+                let test_results = koto.take_test_results();
+                print_test_results(&test_results);
+                if args.coverage {
+                    print_coverage(&koto.take_coverage());
+                }
+                if args.profile {
+                    println!("Profile\n-------\n{}", koto.take_profile());
+                }
+                if let Some(report_path) = &args.test_report {
+                    if let Err(e) = write_test_report(report_path, &test_results) {
+                        eprintln!("Error while writing test report: {}", e);
                         return Err(());
                     }
                 }
+
+                if let Err(e) = result {
+                    eprintln!("Error: {}", e);
+                    return Err(());
+                }
             }
             Err(e) => {
                 eprintln!("Error: {}", e);
@@ -205,3 +302,100 @@ fn run() -> Result<(), ()> {
         repl.run().map_err(|_| ())
     }
 }
+
+fn print_warnings(koto: &mut Koto) {
+    for warning in koto.take_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+// Tests that passed aren't mentioned, and failures are reported as errors
+fn print_test_results(results: &[TestResult]) {
+    for result in results {
+        match &result.outcome {
+            TestOutcome::Skipped(_) | TestOutcome::ExpectedFailure(_) => {
+                eprintln!("Test {}", result)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn print_coverage(coverage: &[ChunkCoverage]) {
+    println!("Coverage\n--------");
+
+    for chunk_coverage in coverage {
+        let path = match &chunk_coverage.chunk.source_path {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => "<script>".into(),
+        };
+        let total = chunk_coverage.hits.len();
+        let covered = chunk_coverage.covered_count();
+        let percentage = if total > 0 {
+            covered as f64 / total as f64 * 100.0
+        } else {
+            100.0
+        };
+        println!("{}: {}/{} ({:.1}%)", path, covered, total, percentage);
+
+        let mut uncovered_lines = chunk_coverage
+            .points()
+            .filter(|(_, hits)| *hits == 0)
+            .map(|(span, _)| span.start.line)
+            .collect::<Vec<_>>();
+        uncovered_lines.sort_unstable();
+        uncovered_lines.dedup();
+        if !uncovered_lines.is_empty() {
+            let lines = uncovered_lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>();
+            println!("  Uncovered lines: {}", lines.join(", "));
+        }
+    }
+}
+
+fn write_test_report(path: &str, results: &[TestResult]) -> io::Result<()> {
+    let tests = results
+        .iter()
+        .map(|result| {
+            let message = match &result.outcome {
+                TestOutcome::Passed | TestOutcome::UnexpectedPass => None,
+                TestOutcome::Failed(message)
+                | TestOutcome::ExpectedFailure(message)
+                | TestOutcome::Skipped(message) => Some(message.clone()),
+                TestOutcome::TimedOut(_) => Some(result.outcome.to_string()),
+            };
+
+            serde_json::json!({
+                "module": result.module_path.as_ref().map(|path| path.to_string_lossy()),
+                "name": result.name,
+                "outcome": result.outcome.name(),
+                "message": message,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut summary = serde_json::Map::new();
+    for outcome in &[
+        "passed",
+        "failed",
+        "expected_failure",
+        "unexpected_pass",
+        "skipped",
+        "timed_out",
+    ] {
+        let count = results
+            .iter()
+            .filter(|result| result.outcome.name() == *outcome)
+            .count();
+        summary.insert(outcome.to_string(), count.into());
+    }
+
+    let report = serde_json::json!({
+        "summary": summary,
+        "tests": tests,
+    });
+
+    fs::write(path, serde_json::to_string_pretty(&report)?)
+}
@@ -8,7 +8,10 @@ use {
         tty::IsTty,
         Result,
     },
-    koto::{bytecode::Chunk, Koto, KotoSettings},
+    koto::{
+        bytecode::{Chunk, Disassembler},
+        Koto, KotoSettings,
+    },
     std::{
         fmt,
         io::{self, Stdout, Write},
@@ -247,10 +250,9 @@ impl Repl {
                     if self.settings.show_instructions {
                         println!("Constants\n---------\n{}\n", chunk.constants.to_string());
 
-                        let script_lines = input.lines().collect::<Vec<_>>();
                         println!(
                             "Instructions\n------------\n{}",
-                            Chunk::instructions_as_string(chunk, &script_lines)
+                            Disassembler::new(chunk).render()
                         );
                     }
                     match self.koto.run() {
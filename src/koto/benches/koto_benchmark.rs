@@ -97,6 +97,29 @@ pub fn koto_benchmark(c: &mut Criterion) {
             runner.run();
         })
     });
+    c.bench_function("value_copies", |b| {
+        let mut runner = BenchmarkRunner::new(
+            "value_copies.koto",
+            &["100".to_string(), "quiet".to_string()],
+        );
+        b.iter(|| {
+            runner.run();
+        })
+    });
+    c.bench_function("map_access", |b| {
+        let mut runner =
+            BenchmarkRunner::new("map_access.koto", &["100".to_string(), "quiet".to_string()]);
+        b.iter(|| {
+            runner.run();
+        })
+    });
+    c.bench_function("map_keys", |b| {
+        let mut runner =
+            BenchmarkRunner::new("map_keys.koto", &["100".to_string(), "quiet".to_string()]);
+        b.iter(|| {
+            runner.run();
+        })
+    });
 }
 
 criterion_group!(benches, koto_benchmark);
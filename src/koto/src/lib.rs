@@ -30,9 +30,12 @@ pub use {koto_bytecode as bytecode, koto_parser as parser, koto_runtime as runti
 
 use {
     dunce::canonicalize,
-    koto_bytecode::{Chunk, LoaderError},
+    koto_bytecode::{Chunk, CompilerSettings, LoaderError, LoaderWarning},
+    koto_parser::ExcerptOptions,
     koto_runtime::{
-        CallArgs, KotoFile, Loader, MetaKey, RuntimeError, Value, ValueMap, Vm, VmSettings,
+        CallArgs, ChunkCoverage, DebugHook, InterruptHandle, KotoClock, KotoFile, Loader, MetaKey,
+        ModuleResolver, Profile, RuntimeError, SandboxSettings, TestResult, Value, ValueMap, Vm,
+        VmSettings,
     },
     std::{error::Error, fmt, path::PathBuf, sync::Arc},
 };
@@ -53,6 +56,15 @@ impl KotoError {
             _ => false,
         }
     }
+
+    /// Formats the error using the provided options for rendering source excerpts
+    pub fn format_with_options(&self, options: &ExcerptOptions) -> String {
+        match &self {
+            Self::CompileError(e) => e.format_with_options(options),
+            Self::RuntimeError(e) => e.format_with_options(options),
+            other => other.to_string(),
+        }
+    }
 }
 
 impl fmt::Display for KotoError {
@@ -90,10 +102,37 @@ pub type KotoResult = Result<Value, KotoError>;
 pub struct KotoSettings {
     pub run_tests: bool,
     pub run_import_tests: bool,
+    pub isolate_tests: bool,
+    pub parallel_tests: bool,
+    pub update_snapshots: bool,
     pub repl_mode: bool,
     pub stdin: Arc<dyn KotoFile>,
     pub stdout: Arc<dyn KotoFile>,
     pub stderr: Arc<dyn KotoFile>,
+    pub clock: Arc<dyn KotoClock>,
+    pub float_epsilon: f64,
+    pub approximate_num_equality: bool,
+    pub check_type_annotations: bool,
+    pub eliminate_tail_calls: bool,
+    pub cache_dir: Option<PathBuf>,
+    pub compiler_settings: CompilerSettings,
+    pub module_resolver: Option<Arc<dyn ModuleResolver>>,
+    pub sandbox: SandboxSettings,
+    pub instruction_budget: Option<u64>,
+    pub memory_limit: Option<usize>,
+    pub max_call_depth: Option<usize>,
+    pub max_nested_call_depth: Option<usize>,
+    pub debug_hook: Option<Arc<dyn DebugHook>>,
+    pub profile: bool,
+    pub trace_sample_interval: u64,
+    /// When enabled, reference cycles are broken when the runtime is dropped
+    ///
+    /// See [VmSettings::break_cycles_on_drop].
+    pub break_cycles_on_drop: bool,
+    /// When set, reference cycles are freed by a garbage collector
+    ///
+    /// See [VmSettings::gc_interval].
+    pub gc_interval: Option<usize>,
 }
 
 impl Default for KotoSettings {
@@ -102,10 +141,31 @@ impl Default for KotoSettings {
         Self {
             run_tests: true,
             run_import_tests: true,
+            isolate_tests: default_vm_settings.isolate_tests,
+            parallel_tests: default_vm_settings.parallel_tests,
+            update_snapshots: default_vm_settings.update_snapshots,
             repl_mode: false,
             stdin: default_vm_settings.stdin,
             stdout: default_vm_settings.stdout,
             stderr: default_vm_settings.stderr,
+            clock: default_vm_settings.clock,
+            float_epsilon: default_vm_settings.float_epsilon,
+            approximate_num_equality: default_vm_settings.approximate_num_equality,
+            check_type_annotations: default_vm_settings.check_type_annotations,
+            eliminate_tail_calls: default_vm_settings.eliminate_tail_calls,
+            cache_dir: default_vm_settings.module_cache_dir,
+            compiler_settings: default_vm_settings.compiler_settings,
+            module_resolver: default_vm_settings.module_resolver,
+            sandbox: default_vm_settings.sandbox,
+            instruction_budget: default_vm_settings.instruction_budget,
+            memory_limit: default_vm_settings.memory_limit,
+            max_call_depth: default_vm_settings.max_call_depth,
+            max_nested_call_depth: default_vm_settings.max_nested_call_depth,
+            debug_hook: default_vm_settings.debug_hook,
+            profile: default_vm_settings.profile,
+            trace_sample_interval: default_vm_settings.trace_sample_interval,
+            break_cycles_on_drop: default_vm_settings.break_cycles_on_drop,
+            gc_interval: default_vm_settings.gc_interval,
         }
     }
 }
@@ -133,18 +193,47 @@ impl Koto {
     }
 
     pub fn with_settings(settings: KotoSettings) -> Self {
-        Self {
+        let mut result = Self {
             settings: settings.clone(),
             runtime: Vm::with_settings(VmSettings {
                 stdin: settings.stdin,
                 stdout: settings.stdout,
                 stderr: settings.stderr,
+                clock: settings.clock,
                 run_import_tests: settings.run_import_tests,
+                isolate_tests: settings.isolate_tests,
+                parallel_tests: settings.parallel_tests,
+                update_snapshots: settings.update_snapshots,
+                float_epsilon: settings.float_epsilon,
+                approximate_num_equality: settings.approximate_num_equality,
+                check_type_annotations: settings.check_type_annotations,
+                eliminate_tail_calls: settings.eliminate_tail_calls,
+                module_cache_dir: settings.cache_dir.clone(),
+                compiler_settings: settings.compiler_settings,
+                module_resolver: settings.module_resolver,
+                sandbox: settings.sandbox,
+                instruction_budget: settings.instruction_budget,
+                memory_limit: settings.memory_limit,
+                max_call_depth: settings.max_call_depth,
+                max_nested_call_depth: settings.max_nested_call_depth,
+                debug_hook: settings.debug_hook,
+                profile: settings.profile,
+                trace_sample_interval: settings.trace_sample_interval,
+                break_cycles_on_drop: settings.break_cycles_on_drop,
+                gc_interval: settings.gc_interval,
             }),
             loader: Loader::default(),
             chunk: None,
             script_path: None,
-        }
+        };
+        result
+            .loader
+            .set_cache_dir(result.settings.cache_dir.clone());
+        result
+            .loader
+            .set_compiler_settings(result.settings.compiler_settings);
+        result.update_script_settings();
+        result
     }
 
     pub fn compile(&mut self, script: &str) -> Result<Arc<Chunk>, KotoError> {
@@ -163,6 +252,26 @@ impl Koto {
         }
     }
 
+    /// Checks a script for errors without compiling it for running
+    ///
+    /// All of the errors that can be found in the script are returned, see
+    /// [Loader::check_script](koto_bytecode::Loader::check_script).
+    pub fn check(&mut self, script: &str) -> Vec<LoaderError> {
+        self.loader.check_script(script, &self.script_path)
+    }
+
+    /// Compiles and runs a source string containing a single expression, returning its result
+    ///
+    /// The expression has access to the prelude and to any values exported by previously run
+    /// scripts. See [Parser::parse_expression](koto_parser::Parser::parse_expression).
+    pub fn run_expression(&mut self, expression: &str) -> KotoResult {
+        let chunk = self
+            .loader
+            .compile_expression(expression)
+            .map_err(KotoError::CompileError)?;
+        self.runtime.run(chunk).map_err(|e| e.into())
+    }
+
     pub fn run_with_args(&mut self, args: &[String]) -> KotoResult {
         self.set_args(args);
         self.run()
@@ -177,29 +286,15 @@ impl Koto {
     }
 
     pub fn run_chunk(&mut self, chunk: Arc<Chunk>) -> KotoResult {
-        let result = self.runtime.run(chunk)?;
+        // The settings and the prelude may have been modified since the last run
+        self.update_script_settings();
+
+        let result = self.runtime.run(chunk.clone())?;
 
         if self.settings.repl_mode {
             Ok(result)
         } else {
-            if self.settings.run_tests {
-                let maybe_tests = self
-                    .runtime
-                    .context()
-                    .exports
-                    .meta()
-                    .get(&MetaKey::Tests)
-                    .cloned();
-                match maybe_tests {
-                    Some(Value::Map(tests)) => {
-                        self.runtime.run_tests(tests)?;
-                    }
-                    Some(other) => {
-                        return Err(KotoError::InvalidTestsType(other.type_as_string()));
-                    }
-                    None => {}
-                }
-            }
+            self.run_exported_tests(chunk)?;
 
             if let Some(main) = self.runtime.get_exported_function("main") {
                 self.runtime
@@ -211,6 +306,98 @@ impl Koto {
         }
     }
 
+    /// Runs the compiled script, allowing async external functions to be awaited
+    ///
+    /// Tests are run synchronously, while the script and its exported `main` function are run
+    /// with [Vm::run_async], see [Pending](koto_runtime::Pending).
+    pub async fn run_async(&mut self) -> KotoResult {
+        let chunk = match self.chunk.clone() {
+            Some(chunk) => chunk,
+            None => return Err(KotoError::NothingToRun),
+        };
+
+        self.update_script_settings();
+
+        let result = self.runtime.run_async(chunk.clone()).await?;
+
+        if self.settings.repl_mode {
+            Ok(result)
+        } else {
+            self.run_exported_tests(chunk)?;
+
+            if let Some(main) = self.runtime.get_exported_function("main") {
+                self.runtime
+                    .run_function_async(main, CallArgs::None)
+                    .await
+                    .map_err(|e| e.into())
+            } else {
+                Ok(result)
+            }
+        }
+    }
+
+    // Runs the tests that were exported by the chunk, if enabled in the settings
+    fn run_exported_tests(&mut self, chunk: Arc<Chunk>) -> Result<(), KotoError> {
+        if !self.settings.run_tests {
+            return Ok(());
+        }
+
+        let maybe_tests = self
+            .runtime
+            .context()
+            .exports
+            .meta()
+            .get(&MetaKey::Tests)
+            .cloned();
+        match maybe_tests {
+            Some(Value::Map(tests)) => {
+                self.runtime.run_module_tests(chunk, tests)?;
+                Ok(())
+            }
+            Some(other) => Err(KotoError::InvalidTestsType(other.type_as_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the compiler warnings that have been produced since the last call
+    ///
+    /// Warnings from the compiled script are included along with warnings from imported modules.
+    pub fn take_warnings(&mut self) -> Vec<LoaderWarning> {
+        let mut warnings = self.loader.take_warnings();
+        warnings.extend(self.runtime.context_mut().take_warnings());
+        warnings
+    }
+
+    /// Returns the results of the tests that have been run since the last call
+    ///
+    /// Results for tests in imported modules are included.
+    pub fn take_test_results(&mut self) -> Vec<TestResult> {
+        self.runtime.take_test_results()
+    }
+
+    /// Returns the coverage that has been recorded since the last call
+    ///
+    /// Coverage is only recorded when `compiler_settings.coverage` is enabled,
+    /// and includes the coverage of imported modules.
+    pub fn take_coverage(&mut self) -> Vec<ChunkCoverage> {
+        self.runtime.take_coverage()
+    }
+
+    /// Returns the profile that has been recorded since the last call
+    ///
+    /// Functions are only profiled when `settings.profile` is enabled,
+    /// and the profile includes the functions of imported modules.
+    pub fn take_profile(&mut self) -> Profile {
+        self.runtime.take_profile()
+    }
+
+    /// Returns a handle that can be used to interrupt running scripts from another thread
+    ///
+    /// See [Vm::interrupt_handle].
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.runtime.interrupt_handle()
+    }
+
     pub fn prelude(&self) -> ValueMap {
         self.runtime.prelude()
     }
@@ -279,6 +466,52 @@ impl Koto {
             }
             _ => unreachable!(),
         }
+
+        self.update_script_settings();
+    }
+
+    // Updates the `koto.settings` value that's visible to scripts
+    //
+    // Only settings that are useful to scripts are included, e.g. the IO handles are left out.
+    // The names of the modules in the prelude are provided as the available capabilities.
+    fn update_script_settings(&mut self) {
+        use Value::{Bool, Empty, Map, Str, Tuple};
+
+        let prelude = self.runtime.prelude();
+        let koto_module = match prelude.data().get_with_string("koto").unwrap() {
+            Map(map) => map.clone(),
+            _ => unreachable!(),
+        };
+        // The path has already been canonicalized in set_script_path
+        let script_path = koto_module
+            .data()
+            .get_with_string("script_path")
+            .cloned()
+            .unwrap_or(Empty);
+
+        let mut capabilities = prelude
+            .data()
+            .keys()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>();
+        capabilities.sort();
+        let capabilities = capabilities
+            .into_iter()
+            .map(|name| Str(name.into()))
+            .collect::<Vec<_>>();
+
+        let settings = koto_runtime::core::koto::make_settings(&[
+            ("run_tests", Bool(self.settings.run_tests)),
+            ("run_import_tests", Bool(self.settings.run_import_tests)),
+            (
+                "check_type_annotations",
+                Bool(self.settings.check_type_annotations),
+            ),
+            ("script_path", script_path),
+            ("capabilities", Tuple(capabilities.into())),
+        ]);
+
+        koto_module.data_mut().add_value("settings", settings);
     }
 
     pub fn run_function_by_name(&mut self, function_name: &str, args: CallArgs) -> KotoResult {
@@ -293,4 +526,26 @@ impl Koto {
             .run_function(function, args)
             .map_err(|e| e.into())
     }
+
+    /// Runs a function with its output redirected to the provided stdout and stderr
+    ///
+    /// See [Vm::run_function_with_output].
+    pub fn run_function_with_output(
+        &mut self,
+        function: Value,
+        args: CallArgs,
+        stdout: Arc<dyn KotoFile>,
+        stderr: Arc<dyn KotoFile>,
+    ) -> KotoResult {
+        self.runtime
+            .run_function_with_output(function, args, stdout, stderr)
+            .map_err(|e| e.into())
+    }
+
+    /// Reloads a module that's been imported by the script, updating its exports in place
+    ///
+    /// See [Vm::reload_module].
+    pub fn reload_module(&mut self, name: &str) -> KotoResult {
+        self.runtime.reload_module(name).map_err(|e| e.into())
+    }
 }
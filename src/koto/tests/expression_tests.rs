@@ -0,0 +1,32 @@
+use koto::{runtime::Value, Koto};
+
+fn check_expression(koto: &mut Koto, expression: &str, expected: f64) {
+    match koto.run_expression(expression) {
+        Ok(Value::Number(n)) => assert_eq!(n, expected),
+        Ok(other) => panic!("Unexpected result for '{}': {}", expression, other),
+        Err(error) => panic!("Error while running '{}': {}", expression, error),
+    }
+}
+
+#[test]
+fn arithmetic() {
+    let mut koto = Koto::default();
+    check_expression(&mut koto, "1 + 2 * 3", 7.0);
+}
+
+#[test]
+fn prelude_and_exports() {
+    let mut koto = Koto::default();
+    koto.compile("export scale = 3").unwrap();
+    koto.run().unwrap();
+
+    check_expression(&mut koto, "number.max 2, scale", 3.0);
+    check_expression(&mut koto, "[1, 2, 3].fold 0, |n, x| n + x * scale", 18.0);
+}
+
+#[test]
+fn statements_are_rejected() {
+    let mut koto = Koto::default();
+    assert!(koto.run_expression("export x = 1").is_err());
+    assert!(koto.run_expression("x = 1").is_err());
+}
@@ -1,13 +1,26 @@
 use {
-    koto::{Koto, KotoSettings},
-    std::{fs::read_to_string, path::PathBuf},
+    koto::{bytecode::CompilerSettings, Koto, KotoSettings},
+    std::{
+        fs::{self, read_to_string},
+        path::PathBuf,
+    },
 };
 
 fn run_script(script: &str, path: Option<PathBuf>, should_fail_at_runtime: bool) {
-    let mut koto = Koto::with_settings(KotoSettings {
+    let settings = KotoSettings {
         run_tests: true,
         ..Default::default()
-    });
+    };
+    run_script_with_settings(script, path, should_fail_at_runtime, settings);
+}
+
+fn run_script_with_settings(
+    script: &str,
+    path: Option<PathBuf>,
+    should_fail_at_runtime: bool,
+    settings: KotoSettings,
+) {
+    let mut koto = Koto::with_settings(settings);
     koto.set_script_path(path);
 
     match koto.compile(script) {
@@ -30,6 +43,14 @@ fn run_script(script: &str, path: Option<PathBuf>, should_fail_at_runtime: bool)
 }
 
 fn load_and_run_script(script_path: &str) {
+    let settings = KotoSettings {
+        run_tests: true,
+        ..Default::default()
+    };
+    load_and_run_script_with_settings(script_path, false, settings);
+}
+
+fn test_script_path(script_path: &str) -> PathBuf {
     let mut path = PathBuf::new();
     path.push(env!("CARGO_MANIFEST_DIR"));
     path.push("..");
@@ -37,13 +58,22 @@ fn load_and_run_script(script_path: &str) {
     path.push("koto");
     path.push("tests");
     path.push(script_path);
+    path
+}
+
+fn load_and_run_script_with_settings(
+    script_path: &str,
+    should_fail_at_runtime: bool,
+    settings: KotoSettings,
+) {
+    let path = test_script_path(script_path);
     if !path.exists() {
         panic!("Path doesn't exist: {:?}", path);
     }
     let script =
         read_to_string(&path).unwrap_or_else(|_| panic!("Unable to load path '{:?}'", &path));
 
-    run_script(&script, Some(path), false);
+    run_script_with_settings(&script, Some(path), should_fail_at_runtime, settings);
 }
 
 macro_rules! koto_test {
@@ -67,6 +97,72 @@ test.assert false
         run_script(script, None, true);
     }
 
+    #[test]
+    fn crlf_line_endings_and_byte_order_mark() {
+        for script_name in &["line_breaks.koto", "strings.koto"] {
+            let path = test_script_path(script_name);
+            let script = read_to_string(&path).unwrap().replace("\r\n", "\n");
+            let crlf_script = script.replace('\n', "\r\n");
+            let bom_script = format!("\u{feff}{}", crlf_script);
+
+            run_script(&crlf_script, Some(path.clone()), false);
+            run_script(&bom_script, Some(path), false);
+        }
+    }
+
+    #[test]
+    fn serialized_chunks() {
+        for script_name in &["import.koto", "strings.koto"] {
+            let path = test_script_path(script_name);
+            let script = read_to_string(&path).unwrap();
+            let settings = KotoSettings {
+                run_tests: true,
+                ..Default::default()
+            };
+
+            let mut koto = Koto::with_settings(settings.clone());
+            koto.set_script_path(Some(path.clone()));
+            let data = koto.compile(&script).unwrap().serialize();
+
+            let chunk = koto::bytecode::Chunk::deserialize(&data).unwrap();
+            let mut koto = Koto::with_settings(settings);
+            koto.set_script_path(Some(path));
+            if let Err(error) = koto.run_chunk(chunk.into()) {
+                panic!("{}", error);
+            }
+        }
+    }
+
+    #[test]
+    fn compiled_chunks_pass_verification() {
+        fn koto_files(dir: PathBuf, files: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    koto_files(path, files);
+                } else if path.extension().and_then(|extension| extension.to_str()) == Some("koto")
+                {
+                    files.push(path);
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+        koto_files(test_script_path(""), &mut files);
+        assert!(!files.is_empty());
+
+        for path in files {
+            let script = read_to_string(&path).unwrap();
+            let mut loader = koto::bytecode::Loader::default();
+            let chunk = loader
+                .compile_script(&script, &Some(path.clone()))
+                .unwrap_or_else(|error| panic!("{}", error));
+            if let Err(error) = chunk.verify() {
+                panic!("{:?} - {}", path, error);
+            }
+        }
+    }
+
     #[test]
     fn check_assert_eq() {
         let script = "
@@ -94,9 +190,266 @@ assert_near 1, 2, 0.1
         run_script(script, None, true);
     }
 
+    #[test]
+    fn parallel_tests() {
+        for script in &["tests.koto", "import.koto"] {
+            let settings = KotoSettings {
+                run_tests: true,
+                parallel_tests: true,
+                ..Default::default()
+            };
+            load_and_run_script_with_settings(script, false, settings);
+        }
+    }
+
+    #[test]
+    fn parallel_test_failures_are_reported_in_order() {
+        // There are more tests than threads, and the failure of the first failing test in the
+        // map should be reported, regardless of which thread finishes first
+        let mut script = String::from("export @tests =\n");
+        for i in 0..64 {
+            script.push_str(&format!("  @test test_{}: || assert {} % 20 != 19\n", i, i));
+        }
+
+        let mut koto = Koto::with_settings(KotoSettings {
+            run_tests: true,
+            parallel_tests: true,
+            ..Default::default()
+        });
+        koto.compile(&script).unwrap();
+        match koto.run() {
+            Ok(_) => panic!("Expected failure"),
+            Err(error) => assert!(error.to_string().contains("'test_19'"), "{}", error),
+        }
+    }
+
+    #[test]
+    fn shared_module_state_in_tests() {
+        // tests.koto checks that changes to the module's state aren't shared between tests
+        let settings = KotoSettings {
+            run_tests: true,
+            isolate_tests: false,
+            ..Default::default()
+        };
+        load_and_run_script_with_settings("tests.koto", true, settings);
+    }
+
+    #[test]
+    fn compiler_settings() {
+        let script = "
+f = || 42
+f
+f()
+";
+        let settings = |warnings_as_errors| KotoSettings {
+            compiler_settings: CompilerSettings {
+                warnings_as_errors,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut koto = Koto::with_settings(settings(false));
+        assert!(koto.compile(script).is_ok());
+        assert_eq!(koto.take_warnings().len(), 1);
+
+        let mut koto = Koto::with_settings(settings(true));
+        assert!(koto.compile(script).is_err());
+    }
+
+    #[test]
+    fn coverage() {
+        let script = "
+f = |x|
+  if x > 5
+    'big'
+  else
+    'small'
+for i in 0..3
+  f i
+";
+        let mut koto = Koto::with_settings(KotoSettings {
+            compiler_settings: CompilerSettings {
+                coverage: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        koto.compile(script).unwrap();
+        koto.run().unwrap();
+
+        let coverage = koto.take_coverage();
+        assert_eq!(coverage.len(), 1);
+        // The assignment of f, the if expression, 'big', 'small', the for loop, and the call of f
+        assert_eq!(coverage[0].hits, &[1, 3, 0, 3, 1, 3]);
+        assert_eq!(coverage[0].covered_count(), 5);
+
+        // The recorded coverage is cleared once it's been taken
+        assert!(koto.take_coverage().is_empty());
+    }
+
+    #[test]
+    fn settings_are_read_only() {
+        let script = "
+koto.settings.run_tests = false
+";
+        run_script(script, None, true);
+    }
+
+    mod snapshots {
+        use super::*;
+
+        fn snapshot_script(value: &str) -> String {
+            format!("import test.snapshot\nsnapshot 'example', {}\n", value)
+        }
+
+        #[test]
+        fn mismatched_snapshot() {
+            let script = "
+import test.snapshot
+snapshot 'values', 'not the stored snapshot'
+";
+            run_script(script, Some(test_script_path("snapshots.koto")), true);
+        }
+
+        #[test]
+        fn invalid_snapshot_name() {
+            let script = "
+import test.snapshot
+snapshot '../values', 42
+";
+            run_script(script, Some(test_script_path("snapshots.koto")), true);
+        }
+
+        #[test]
+        fn script_without_path() {
+            run_script(&snapshot_script("42"), None, true);
+        }
+
+        #[test]
+        fn creating_and_updating_snapshots() {
+            let dir = std::env::temp_dir().join(format!("koto_snapshots_{}", std::process::id()));
+            let script_path = dir.join("script.koto");
+            let snapshot_path = dir.join("snapshots").join("script").join("example.snap");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&script_path, "").unwrap();
+
+            // Missing snapshots are created
+            run_script(&snapshot_script("42"), Some(script_path.clone()), false);
+            assert_eq!(read_to_string(&snapshot_path).unwrap(), "42\n");
+
+            // Snapshots that don't match cause an error, unless update_snapshots is enabled
+            run_script(&snapshot_script("99"), Some(script_path.clone()), true);
+            let settings = KotoSettings {
+                run_tests: true,
+                update_snapshots: true,
+                ..Default::default()
+            };
+            run_script_with_settings(
+                &snapshot_script("99"),
+                Some(script_path.clone()),
+                false,
+                settings,
+            );
+            assert_eq!(read_to_string(&snapshot_path).unwrap(), "99\n");
+            run_script(&snapshot_script("99"), Some(script_path), false);
+
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    mod test_annotations {
+        use {super::*, koto::runtime::TestOutcome, std::time::Duration};
+
+        fn run_tests(script: &str) -> (bool, Vec<TestOutcome>) {
+            let mut koto = Koto::with_settings(KotoSettings {
+                run_tests: true,
+                ..Default::default()
+            });
+            koto.compile(script).unwrap();
+            let succeeded = koto.run().is_ok();
+            let outcomes = koto
+                .take_test_results()
+                .into_iter()
+                .map(|result| result.outcome)
+                .collect();
+            (succeeded, outcomes)
+        }
+
+        #[test]
+        fn skipped_and_expected_failures() {
+            let script = "
+export @tests =
+  @test_expected_to_fail fails: || assert false
+  @test skipped: || assert false
+  @test_skip skipped: 'not ready yet'
+  @test passes: || assert true
+";
+            let (succeeded, outcomes) = run_tests(script);
+            assert!(succeeded);
+            assert!(matches!(outcomes[0], TestOutcome::ExpectedFailure(_)));
+            assert_eq!(outcomes[1], TestOutcome::Skipped("not ready yet".into()));
+            assert_eq!(outcomes[2], TestOutcome::Passed);
+        }
+
+        #[test]
+        fn unexpected_pass() {
+            let script = "
+export @tests =
+  @test_expected_to_fail passes: || assert true
+";
+            let (succeeded, outcomes) = run_tests(script);
+            assert!(!succeeded);
+            assert_eq!(outcomes, [TestOutcome::UnexpectedPass]);
+        }
+
+        #[test]
+        fn timeout_cant_be_caught() {
+            let script = "
+export @tests =
+  @test loops_forever: ||
+    try
+      while true
+        x = 1
+    catch _
+      ()
+  @test_timeout loops_forever: 0.1
+";
+            let (succeeded, outcomes) = run_tests(script);
+            assert!(!succeeded);
+            assert_eq!(
+                outcomes,
+                [TestOutcome::TimedOut(Duration::from_secs_f64(0.1))]
+            );
+        }
+
+        #[test]
+        fn invalid_skip_reason() {
+            let script = "
+export @tests =
+  @test foo: || assert true
+  @test_skip foo: 42
+";
+            let (succeeded, _) = run_tests(script);
+            assert!(!succeeded);
+        }
+
+        #[test]
+        fn invalid_timeout() {
+            let script = "
+export @tests =
+  @test foo: || assert true
+  @test_timeout foo: -1
+";
+            let (succeeded, _) = run_tests(script);
+            assert!(!succeeded);
+        }
+    }
+
     koto_test!(assignment);
     koto_test!(comments);
     koto_test!(control_flow);
+    koto_test!(coroutines);
     koto_test!(enums);
     koto_test!(error_handling);
     koto_test!(function_closures);
@@ -114,12 +467,15 @@ assert_near 1, 2, 0.1
     koto_test!(maps);
     koto_test!(maps_and_lists);
     koto_test!(meta_maps);
+    koto_test!(mocking);
     koto_test!(number_ops);
     koto_test!(numbers);
     koto_test!(num2_4);
     koto_test!(os);
     koto_test!(primes);
     koto_test!(ranges);
+    koto_test!(settings);
+    koto_test!(snapshots);
     koto_test!(strings);
     koto_test!(string_formatting);
     koto_test!(tests);
@@ -1,98 +1,182 @@
 use {
-    crate::{Position, Span},
-    std::{iter::Peekable, str::Chars},
-    unicode_width::UnicodeWidthChar,
+    crate::{span::column_width, Position, Span},
+    std::{iter::Peekable, ops::Range, str::Chars},
     unicode_xid::UnicodeXID,
 };
 
 /// The tokens that can emerge from the lexer
+///
+/// Whitespace, newlines, and comments are emitted as tokens rather than being discarded, so the
+/// token stream covers every byte of the input source, see [TokenStream].
 #[derive(Copy, Clone, Debug, PartialEq)]
-#[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
+    /// An unexpected or malformed sequence of characters
     Error,
+    /// Spaces or tabs that aren't at the start of a line
     Whitespace,
+    /// A line ending that's followed by an unindented line
     NewLine,
+    /// A line ending along with the indentation of the following line
     NewLineIndented,
+    /// A comment that runs to the end of the line, starting with `#`
     CommentSingle,
+    /// A comment that can span multiple lines, enclosed by `#-` and `-#`
     CommentMulti,
+    /// A number literal, e.g. `42`, `1.5e-3`, `0xff`
     Number,
+    /// An identifier, e.g. `foo`, `x_1`
+    ///
+    /// Keywords can be used as identifiers by enclosing them in backticks, e.g. `` `match` ``,
+    /// see [id_name].
     Id,
 
+    /// `'`, or `'''` when opening or closing a triple-quoted string
     SingleQuote,
+    /// `"`, or `"""` when opening or closing a triple-quoted string
     DoubleQuote,
+    /// The contents of a string, excluding quotes and template expressions
     StringLiteral,
 
-    // Symbols
+    /// `->`
+    Arrow,
+    /// `@`
     At,
+    /// `:`
     Colon,
+    /// `,`
     Comma,
+    /// `$`, used to start a string template expression
     Dollar,
+    /// `.`
     Dot,
+    /// `...`
     Ellipsis,
+    /// `|`, used to start and end a function's arguments
     Function,
+    /// `(`
     RoundOpen,
+    /// `)`
     RoundClose,
+    /// `[`
     SquareOpen,
+    /// `]`
     SquareClose,
+    /// `{`
     CurlyOpen,
+    /// `}`
     CurlyClose,
+    /// `_`
     Wildcard,
+    /// `..`
     Range,
+    /// `..=`
     RangeInclusive,
 
-    // operators
+    /// `+`
     Add,
+    /// `-`
     Subtract,
+    /// `*`
     Multiply,
+    /// `/`
     Divide,
+    /// `%`
     Modulo,
 
+    /// `=`
     Assign,
+    /// `+=`
     AssignAdd,
+    /// `-=`
     AssignSubtract,
+    /// `*=`
     AssignMultiply,
+    /// `/=`
     AssignDivide,
+    /// `%=`
     AssignModulo,
 
+    /// `==`
     Equal,
+    /// `!=`
     NotEqual,
+    /// `>`
     Greater,
+    /// `>=`
     GreaterOrEqual,
+    /// `<`
     Less,
+    /// `<=`
     LessOrEqual,
 
+    /// `>>`
     Pipe,
 
     // Keywords
+    /// `and`
     And,
+    /// `assert`
+    Assert,
+    /// `break`
     Break,
+    /// `catch`
     Catch,
+    /// `continue`
     Continue,
+    /// `debug`
     Debug,
+    /// `else`
     Else,
+    /// `else if`
     ElseIf,
+    /// `export`
     Export,
+    /// `false`
     False,
+    /// `finally`
     Finally,
+    /// `for`
     For,
+    /// `from`
     From,
+    /// `guard`
+    Guard,
+    /// `if`
     If,
+    /// `import`
     Import,
+    /// `in`
     In,
+    /// `loop`
     Loop,
+    /// `match`
     Match,
+    /// `not`
     Not,
+    /// `num2`
     Num2,
+    /// `num4`
     Num4,
+    /// `or`
     Or,
+    /// `return`
     Return,
+    /// `switch`
     Switch,
+    /// `then`
     Then,
+    /// `throw`
     Throw,
+    /// `true`
     True,
+    /// `try`
     Try,
+    /// `until`
     Until,
+    /// `while`
     While,
+    /// `yield`
     Yield,
 }
 
@@ -108,22 +192,136 @@ impl Token {
         use Token::*;
         matches!(self, NewLine | NewLineIndented)
     }
+
+    /// Returns true if the token has no effect on the meaning of a script
+    ///
+    /// Trivia tokens are whitespace, comments, and newlines, which are preserved in the token
+    /// stream so that tools like formatters and highlighters can reproduce the input source.
+    pub fn is_trivia(&self) -> bool {
+        self.is_whitespace() || self.is_newline()
+    }
+
+    /// Returns the token's [TokenCategory]
+    pub fn category(&self) -> TokenCategory {
+        use Token::*;
+
+        match self {
+            Error => TokenCategory::Error,
+            Whitespace | NewLine | NewLineIndented => TokenCategory::Whitespace,
+            CommentSingle | CommentMulti => TokenCategory::Comment,
+            Number => TokenCategory::Number,
+            SingleQuote | DoubleQuote | StringLiteral | Dollar => TokenCategory::String,
+            Id | Wildcard => TokenCategory::Id,
+            Add | Subtract | Multiply | Divide | Modulo | Assign | AssignAdd | AssignSubtract
+            | AssignMultiply | AssignDivide | AssignModulo | Equal | NotEqual | Greater
+            | GreaterOrEqual | Less | LessOrEqual | Pipe | Range | RangeInclusive | Ellipsis => {
+                TokenCategory::Operator
+            }
+            Arrow | At | Colon | Comma | Dot | Function | RoundOpen | RoundClose | SquareOpen
+            | SquareClose | CurlyOpen | CurlyClose => TokenCategory::Punctuation,
+            And | Assert | Break | Catch | Continue | Debug | Else | ElseIf | Export | False
+            | Finally | For | From | Guard | If | Import | In | Loop | Match | Not | Num2
+            | Num4 | Or | Return | Switch | Then | Throw | True | Try | Until | While | Yield => {
+                TokenCategory::Keyword
+            }
+        }
+    }
+}
+
+/// A broad classification of [Token]s, useful for tools like syntax highlighters
+///
+/// See [Token::category].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TokenCategory {
+    /// Whitespace and newlines
+    Whitespace,
+    /// Single-line and multi-line comments
+    Comment,
+    /// Number literals
+    Number,
+    /// The quotation marks and literal parts of strings, along with the `$` that starts a
+    /// template expression
+    String,
+    /// Identifiers, including wildcards like `_` or `_foo`
+    Id,
+    /// Keywords, e.g. `if`, `match`, or `true`
+    Keyword,
+    /// Operators, e.g. `+`, `==`, `..`, or `...`
+    Operator,
+    /// Punctuation, e.g. brackets, commas, `.`, `@`, or the `|` that surrounds function args
+    Punctuation,
+    /// A token that couldn't be lexed
+    Error,
+}
+
+/// The reason that a [Token::Error] was produced by the lexer
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LexerError {
+    /// A character that isn't valid at this position in the script
+    UnexpectedCharacter,
+    /// The end of the script was reached before a string was terminated
+    UnterminatedString {
+        /// The span of the string's opening quote
+        quote: Span,
+    },
+    /// The end of the script was reached before a multi-line comment was terminated
+    UnterminatedComment,
+    /// A number literal that's malformed, e.g. `0x`, `1e`, or `0b102`
+    InvalidNumber,
+    /// A line's indentation mixes tabs and spaces, see [LexerSettings::reject_mixed_indentation]
+    MixedIndentation,
 }
 
 // Used to keep track of different lexing modes while working through a string
 #[derive(Clone)]
 enum StringMode {
     // Inside a string literal, expecting an end quote
-    Literal(char),
+    //
+    // Strings delimited by triple quotes (e.g. '"""') are ended by a matching triple quote.
+    // The span of the opening quote is used when reporting unterminated strings.
+    Literal {
+        quote: char,
+        triple: bool,
+        start: Span,
+    },
     // Just after a $ symbol, either an id or a '{' will follow
     TemplateStart,
     // Inside a string template, e.g. '${...}'
     TemplateExpression,
     // Inside an inline map in a template expression, e.g. '${foo({bar: 42})}'
     // A closing '}' will end the map rather than the template expression.
+    // Inline maps can be nested, with each map pushing a new mode onto the stack.
     TemplateExpressionInlineMap,
 }
 
+/// Settings that control how the lexer interprets a script's indentation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LexerSettings {
+    /// The number of columns of indentation that a tab counts as
+    ///
+    /// Default: 1
+    pub tab_width: usize,
+    /// Rejects scripts that mix tabs and spaces in their indentation
+    ///
+    /// When enabled, a line's indentation is expected to consist entirely of tabs or spaces,
+    /// matching the first indented line in the script. A [Token::Error] is emitted in place of
+    /// the newline token when a line's indentation doesn't match.
+    ///
+    /// Default: false
+    pub reject_mixed_indentation: bool,
+}
+
+impl Default for LexerSettings {
+    fn default() -> Self {
+        Self {
+            tab_width: 1,
+            reject_mixed_indentation: false,
+        }
+    }
+}
+
 // Separates the input source into Tokens
 //
 // TokenLexer is the internal implementation, KotoLexer provides the external interface.
@@ -137,27 +335,41 @@ struct TokenLexer<'a> {
     previous_byte: usize,
     // A cache of the previous token that was emitted
     previous_token: Option<Token>,
+    // The reason for the previous token being an error
+    previous_error: Option<LexerError>,
     // The span represented by the current token
     span: Span,
     // The indentation of the current line
     indent: usize,
+    // The character used for indentation, see LexerSettings::reject_mixed_indentation
+    indent_char: Option<char>,
+    settings: LexerSettings,
     // A stack of string modes, allowing for nested mode changes while parsing strings
     string_mode_stack: Vec<StringMode>,
 }
 
 impl<'a> TokenLexer<'a> {
-    fn new(source: &'a str) -> Self {
+    fn new(source: &'a str, settings: LexerSettings) -> Self {
         Self {
             source,
             previous_byte: 0,
             current_byte: 0,
             indent: 0,
+            indent_char: None,
+            settings,
             previous_token: None,
+            previous_error: None,
             span: Span::default(),
             string_mode_stack: vec![],
         }
     }
 
+    // Records the reason for an error, returning Token::Error
+    fn error(&mut self, error: LexerError) -> Token {
+        self.previous_error = Some(error);
+        Token::Error
+    }
+
     // The slice associated with the token that was just emitted
     fn slice(&self) -> &'a str {
         &self.source[self.previous_byte..self.current_byte]
@@ -181,17 +393,24 @@ impl<'a> TokenLexer<'a> {
             end: Position {
                 line: previous_end.line,
                 column: previous_end.column + char_count as u32,
+                offset: self.current_byte as u32,
             },
         };
     }
 
+    // Advances to the given line and column position
+    //
+    // The position's byte offset is derived from the number of consumed bytes.
     fn advance_to_position(&mut self, char_bytes: usize, position: Position) {
         self.previous_byte = self.current_byte;
         self.current_byte += char_bytes;
 
         self.span = Span {
             start: self.span.end,
-            end: position,
+            end: Position {
+                offset: self.current_byte as u32,
+                ..position
+            },
         };
     }
 
@@ -209,21 +428,37 @@ impl<'a> TokenLexer<'a> {
 
         match chars.next() {
             Some('\n') => {}
-            _ => return Error,
+            _ => return self.error(LexerError::UnexpectedCharacter),
         }
 
-        consumed_bytes += consume_and_count(&mut chars, is_whitespace);
+        let mut indent = 0;
+        let mut mixed_indentation = false;
+        while let Some(c) = chars.peek().copied().filter(|c| is_whitespace(*c)) {
+            consumed_bytes += 1;
+            indent += if c == '\t' {
+                self.settings.tab_width
+            } else {
+                1
+            };
+            if self.settings.reject_mixed_indentation {
+                mixed_indentation |= *self.indent_char.get_or_insert(c) != c;
+            }
+            chars.next();
+        }
 
-        self.indent = consumed_bytes - newline_bytes;
+        self.indent = indent;
         self.advance_to_position(
             consumed_bytes,
             Position {
                 line: self.current_position().line + 1,
                 column: (consumed_bytes - newline_bytes + 1) as u32, // indexing from 1 for column
+                ..Default::default()
             },
         );
 
-        if self.indent == 0 {
+        if mixed_indentation {
+            self.error(LexerError::MixedIndentation)
+        } else if self.indent == 0 {
             NewLine
         } else {
             NewLineIndented
@@ -243,7 +478,7 @@ impl<'a> TokenLexer<'a> {
             let mut position = self.current_position();
             while let Some(c) = chars.next() {
                 char_bytes += c.len_utf8();
-                position.column += c.width().unwrap_or(0) as u32;
+                position.column += column_width(c) as u32;
                 match c {
                     '#' => {
                         if chars.peek() == Some(&'-') {
@@ -266,7 +501,7 @@ impl<'a> TokenLexer<'a> {
                     }
                     '\r' => {
                         if chars.next() != Some('\n') {
-                            return Error;
+                            return self.error(LexerError::UnexpectedCharacter);
                         }
                         char_bytes += 1;
                         position.line += 1;
@@ -285,7 +520,7 @@ impl<'a> TokenLexer<'a> {
             if nest_count == 0 {
                 CommentMulti
             } else {
-                Error
+                self.error(LexerError::UnterminatedComment)
             }
         } else {
             // single-line comment
@@ -299,9 +534,13 @@ impl<'a> TokenLexer<'a> {
     fn consume_string_literal(&mut self, mut chars: Peekable<Chars>) -> Token {
         use Token::*;
 
-        let string_quote = match self.string_mode_stack.last() {
-            Some(StringMode::Literal(quote)) => *quote,
-            _ => return Error,
+        let (string_quote, triple_quote, string_start) = match self.string_mode_stack.last() {
+            Some(StringMode::Literal {
+                quote,
+                triple,
+                start,
+            }) => (*quote, *triple, *start),
+            _ => return self.error(LexerError::UnexpectedCharacter),
         };
 
         let mut string_bytes = 0;
@@ -309,7 +548,10 @@ impl<'a> TokenLexer<'a> {
 
         while let Some(c) = chars.peek().cloned() {
             match c {
-                _ if c == string_quote => {
+                _ if c == string_quote
+                    && (!triple_quote
+                        || self.is_triple_quote(self.current_byte + string_bytes, c)) =>
+                {
                     self.advance_to_position(string_bytes, position);
                     return StringLiteral;
                 }
@@ -338,7 +580,7 @@ impl<'a> TokenLexer<'a> {
                 '\r' => {
                     chars.next();
                     if chars.next() != Some('\n') {
-                        return Error;
+                        return self.error(LexerError::UnexpectedCharacter);
                     }
                     string_bytes += 2;
                     position.line += 1;
@@ -353,77 +595,82 @@ impl<'a> TokenLexer<'a> {
                 _ => {
                     chars.next();
                     string_bytes += c.len_utf8();
-                    position.column += c.width().unwrap_or(0) as u32;
+                    position.column += column_width(c) as u32;
                 }
             }
         }
 
-        Error
+        // The end of the source was reached before the string was terminated
+        self.advance_to_position(string_bytes, position);
+        self.error(LexerError::UnterminatedString {
+            quote: string_start,
+        })
+    }
+
+    // Returns true if the source contains three of the given quote character at the given position
+    fn is_triple_quote(&self, byte: usize, quote: char) -> bool {
+        matches!(
+            self.source.get(byte..byte + 3),
+            Some(quotes) if quotes.chars().all(|c| c == quote)
+        )
     }
 
     fn consume_number(&mut self, mut chars: Peekable<Chars>) -> Token {
         use Token::*;
 
         let has_leading_zero = chars.peek() == Some(&'0');
-        let mut char_bytes = consume_and_count(&mut chars, is_digit);
+        let mut char_bytes = consume_digits(&mut chars, is_digit);
         let mut allow_exponent = true;
+        let mut missing_digits = false;
 
         match chars.peek() {
-            Some(&'b') if has_leading_zero && char_bytes == 1 => {
+            Some(&c @ ('b' | 'o' | 'x')) if has_leading_zero && char_bytes == 1 => {
                 chars.next();
-                char_bytes += 1 + consume_and_count(&mut chars, is_binary_digit);
-                allow_exponent = false;
-            }
-            Some(&'o') if has_leading_zero && char_bytes == 1 => {
-                chars.next();
-                char_bytes += 1 + consume_and_count(&mut chars, is_octal_digit);
-                allow_exponent = false;
-            }
-            Some(&'x') if has_leading_zero && char_bytes == 1 => {
-                chars.next();
-                char_bytes += 1 + consume_and_count(&mut chars, is_hex_digit);
+                let digit_bytes = match c {
+                    'b' => consume_digits(&mut chars, is_binary_digit),
+                    'o' => consume_digits(&mut chars, is_octal_digit),
+                    _ => consume_digits(&mut chars, is_hex_digit),
+                };
+                char_bytes += 1 + digit_bytes;
                 allow_exponent = false;
+                missing_digits = digit_bytes == 0;
             }
             Some(&'.') => {
-                chars.next();
+                // lookahead to check that this isn't a function call, e.g. 1.exp()
+                let mut lookahead = chars.clone();
+                lookahead.next();
 
-                match chars.peek() {
-                    Some(c) if is_digit(*c) => {}
-                    Some(&'e') => {
-                        // lookahead to check that this isn't a function call starting with 'e'
-                        // e.g. 1.exp()
-                        let mut lookahead = chars.clone();
-                        lookahead.next();
-                        match lookahead.peek() {
-                            Some(c) if is_digit(*c) => {}
-                            Some(&'+') | Some(&'-') => {}
-                            _ => {
-                                self.advance_line(char_bytes);
-                                return Number;
-                            }
-                        }
-                    }
-                    _ => {
-                        self.advance_line(char_bytes);
-                        return Number;
-                    }
+                let is_fraction = matches!(lookahead.peek(), Some(&c) if is_digit(c))
+                    || exponent_bytes(&lookahead).is_some();
+
+                if !is_fraction {
+                    self.advance_line(char_bytes);
+                    return Number;
                 }
 
-                char_bytes += 1 + consume_and_count(&mut chars, is_digit);
+                chars.next();
+                char_bytes += 1 + consume_digits(&mut chars, is_digit);
             }
             _ => {}
         }
 
-        if chars.peek() == Some(&'e') && allow_exponent {
-            chars.next();
-            char_bytes += 1;
-
-            if matches!(chars.peek(), Some(&'+') | Some(&'-')) {
-                chars.next();
-                char_bytes += 1;
+        if allow_exponent {
+            // The exponent is only included when it's followed by digits, e.g. `1e3` or `1e-3`,
+            // otherwise the number ends before the `e`.
+            if let Some(exponent_bytes) = exponent_bytes(&chars) {
+                for _ in 0..exponent_bytes {
+                    chars.next();
+                }
+                char_bytes += exponent_bytes + consume_digits(&mut chars, is_digit);
             }
+        }
 
-            char_bytes += consume_and_count(&mut chars, is_digit);
+        // Numbers that are directly followed by ID characters are malformed,
+        // e.g. `1e`, `0b102`, or `42abc`
+        if missing_digits || matches!(chars.peek(), Some(&c) if is_id_continue(c)) {
+            let (invalid_bytes, invalid_count) = consume_and_count_utf8(&mut chars, is_id_continue);
+            self.advance_line_utf8(char_bytes + invalid_bytes, char_bytes + invalid_count);
+            return self.error(LexerError::InvalidNumber);
         }
 
         self.advance_line(char_bytes);
@@ -467,6 +714,7 @@ impl<'a> TokenLexer<'a> {
 
         if !matches!(self.previous_token, Some(Token::Dot)) {
             check_keyword!("and", And);
+            check_keyword!("assert", Assert);
             check_keyword!("break", Break);
             check_keyword!("catch", Catch);
             check_keyword!("continue", Continue);
@@ -476,6 +724,7 @@ impl<'a> TokenLexer<'a> {
             check_keyword!("finally", Finally);
             check_keyword!("for", For);
             check_keyword!("from", From);
+            check_keyword!("guard", Guard);
             check_keyword!("if", If);
             check_keyword!("import", Import);
             check_keyword!("in", In);
@@ -501,6 +750,27 @@ impl<'a> TokenLexer<'a> {
         Token::Id
     }
 
+    // Raw identifiers are enclosed in backticks, allowing keywords to be used as IDs,
+    // e.g. `` `match` ``
+    fn consume_raw_id(&mut self, mut chars: Peekable<Chars>) -> Token {
+        // The opening backtick has already been matched
+        chars.next();
+
+        if !matches!(chars.peek(), Some(&c) if is_id_start(c)) {
+            return self.error(LexerError::UnexpectedCharacter);
+        }
+
+        let (id_bytes, id_count) = consume_and_count_utf8(&mut chars, is_id_continue);
+
+        if chars.next() == Some('`') {
+            self.advance_line_utf8(id_bytes + 2, id_count + 2);
+            Token::Id
+        } else {
+            self.advance_line_utf8(id_bytes + 1, id_count + 1);
+            self.error(LexerError::UnexpectedCharacter)
+        }
+    }
+
     fn consume_symbol(&mut self, remaining: &str) -> Option<Token> {
         use Token::*;
 
@@ -527,6 +797,8 @@ impl<'a> TokenLexer<'a> {
         check_symbol!(">", Greater);
         check_symbol!("<", Less);
 
+        check_symbol!("->", Arrow);
+
         check_symbol!("+=", AssignAdd);
         check_symbol!("-=", AssignSubtract);
         check_symbol!("*=", AssignMultiply);
@@ -559,6 +831,9 @@ impl<'a> TokenLexer<'a> {
     fn get_next_token(&mut self) -> Option<Token> {
         use Token::*;
 
+        let start_byte = self.current_byte;
+        self.previous_error = None;
+
         let result = match self.source.get(self.current_byte..) {
             Some(remaining) if !remaining.is_empty() => {
                 let mut chars = remaining.chars().peekable();
@@ -567,14 +842,18 @@ impl<'a> TokenLexer<'a> {
                 let string_mode = self.string_mode_stack.last().cloned();
 
                 match string_mode {
-                    Some(StringMode::Literal(quote)) => match next_char {
-                        '"' if quote == '"' => {
-                            self.advance_line(1);
+                    Some(StringMode::Literal { quote, triple, .. }) => match next_char {
+                        '"' if quote == '"'
+                            && (!triple || self.is_triple_quote(self.current_byte, '"')) =>
+                        {
+                            self.advance_line(if triple { 3 } else { 1 });
                             self.string_mode_stack.pop();
                             Some(DoubleQuote)
                         }
-                        '\'' if quote == '\'' => {
-                            self.advance_line(1);
+                        '\'' if quote == '\''
+                            && (!triple || self.is_triple_quote(self.current_byte, '\'')) =>
+                        {
+                            self.advance_line(if triple { 3 } else { 1 });
                             self.string_mode_stack.pop();
                             Some(SingleQuote)
                         }
@@ -591,7 +870,7 @@ impl<'a> TokenLexer<'a> {
                                 self.string_mode_stack.pop();
                                 Some(Id)
                             }
-                            _ => Some(Error),
+                            _ => Some(self.error(LexerError::UnexpectedCharacter)),
                         },
                         '{' => {
                             self.advance_line(1);
@@ -599,9 +878,14 @@ impl<'a> TokenLexer<'a> {
                             self.string_mode_stack.push(StringMode::TemplateExpression);
                             Some(CurlyOpen)
                         }
-                        _ => Some(Error),
+                        _ => Some(self.error(LexerError::UnexpectedCharacter)),
                     },
                     _ => match next_char {
+                        // A byte order mark at the start of the source is treated as whitespace
+                        BYTE_ORDER_MARK if self.current_byte == 0 => {
+                            self.advance_line_utf8(BYTE_ORDER_MARK.len_utf8(), 0);
+                            Some(Whitespace)
+                        }
                         c if is_whitespace(c) => {
                             let count = consume_and_count(&mut chars, is_whitespace);
                             self.advance_line(count);
@@ -610,24 +894,41 @@ impl<'a> TokenLexer<'a> {
                         '\r' | '\n' => Some(self.consume_newline(chars)),
                         '#' => Some(self.consume_comment(chars)),
                         '"' => {
-                            self.advance_line(1);
-                            self.string_mode_stack.push(StringMode::Literal('"'));
+                            let triple = self.is_triple_quote(self.current_byte, '"');
+                            self.advance_line(if triple { 3 } else { 1 });
+                            self.string_mode_stack.push(StringMode::Literal {
+                                quote: '"',
+                                triple,
+                                start: self.span,
+                            });
                             Some(DoubleQuote)
                         }
                         '\'' => {
-                            self.advance_line(1);
-                            self.string_mode_stack.push(StringMode::Literal('\''));
+                            let triple = self.is_triple_quote(self.current_byte, '\'');
+                            self.advance_line(if triple { 3 } else { 1 });
+                            self.string_mode_stack.push(StringMode::Literal {
+                                quote: '\'',
+                                triple,
+                                start: self.span,
+                            });
                             Some(SingleQuote)
                         }
                         '0'..='9' => Some(self.consume_number(chars)),
                         c if is_id_start(c) => Some(self.consume_id_or_keyword(chars)),
+                        '`' => Some(self.consume_raw_id(chars)),
                         _ => {
-                            let result = self.consume_symbol(remaining).unwrap_or(Error);
+                            let result = match self.consume_symbol(remaining) {
+                                Some(token) => token,
+                                None => self.error(LexerError::UnexpectedCharacter),
+                            };
 
                             use StringMode::*;
                             match result {
                                 CurlyOpen => {
-                                    if matches!(string_mode, Some(TemplateExpression)) {
+                                    if matches!(
+                                        string_mode,
+                                        Some(TemplateExpression | TemplateExpressionInlineMap)
+                                    ) {
                                         self.string_mode_stack.push(TemplateExpressionInlineMap);
                                     }
                                 }
@@ -650,6 +951,13 @@ impl<'a> TokenLexer<'a> {
             _ => None,
         };
 
+        // Make sure that errors always consume some input, so that the lexer can't get stuck
+        if result == Some(Token::Error) && self.current_byte == start_byte {
+            if let Some(c) = self.source[self.current_byte..].chars().next() {
+                self.advance_line_utf8(c.len_utf8(), column_width(c));
+            }
+        }
+
         self.previous_token = result;
         result
     }
@@ -679,10 +987,23 @@ fn is_hex_digit(c: char) -> bool {
     matches!(c, '0'..='9' | 'a'..='f' | 'A'..='F')
 }
 
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
 fn is_whitespace(c: char) -> bool {
     matches!(c, ' ' | '\t')
 }
 
+/// Returns the name of an identifier from its source slice
+///
+/// Raw identifiers are enclosed in backticks, e.g. `` `match` ``, which are removed from the
+/// returned name.
+pub fn id_name(slice: &str) -> &str {
+    slice
+        .strip_prefix('`')
+        .and_then(|id| id.strip_suffix('`'))
+        .unwrap_or(slice)
+}
+
 /// Returns true if the character matches the XID_Start Unicode property
 pub fn is_id_start(c: char) -> bool {
     UnicodeXID::is_xid_start(c)
@@ -693,6 +1014,63 @@ pub fn is_id_continue(c: char) -> bool {
     UnicodeXID::is_xid_continue(c)
 }
 
+// Consumes the digits of a number, which can be separated by underscores, e.g. `1_000_000`
+//
+// Underscores are only consumed when they're followed by a digit.
+fn consume_digits(chars: &mut Peekable<Chars>, is_valid_digit: impl Fn(char) -> bool) -> usize {
+    let mut char_bytes = 0;
+
+    loop {
+        match chars.peek() {
+            Some(&c) if is_valid_digit(c) => {
+                char_bytes += 1;
+                chars.next();
+            }
+            Some(&'_') if char_bytes > 0 => {
+                let mut lookahead = chars.clone();
+                let underscores = consume_and_count(&mut lookahead, |c| c == '_');
+                match lookahead.peek() {
+                    Some(&c) if is_valid_digit(c) => {
+                        char_bytes += underscores;
+                        for _ in 0..underscores {
+                            chars.next();
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            _ => break,
+        }
+    }
+
+    char_bytes
+}
+
+// Returns the number of bytes used by the start of an exponent, e.g. `e` or `E-`
+//
+// None is returned if the chars don't start with an exponent that's followed by a digit.
+fn exponent_bytes(chars: &Peekable<Chars>) -> Option<usize> {
+    let mut lookahead = chars.clone();
+
+    match lookahead.next() {
+        Some('e') | Some('E') => {}
+        _ => return None,
+    }
+
+    let sign_bytes = match lookahead.peek() {
+        Some(&'+') | Some(&'-') => {
+            lookahead.next();
+            1
+        }
+        _ => 0,
+    };
+
+    match lookahead.peek() {
+        Some(&c) if is_digit(c) => Some(1 + sign_bytes),
+        _ => None,
+    }
+}
+
 fn consume_and_count(chars: &mut Peekable<Chars>, predicate: impl Fn(char) -> bool) -> usize {
     let mut char_bytes = 0;
 
@@ -719,7 +1097,7 @@ fn consume_and_count_utf8(
             break;
         }
         char_bytes += c.len_utf8();
-        char_count += c.width().unwrap_or(0);
+        char_count += column_width(*c);
         chars.next();
     }
 
@@ -729,6 +1107,10 @@ fn consume_and_count_utf8(
 #[derive(Clone)]
 struct PeekedToken<'a> {
     token: Option<Token>,
+    // The token that was emitted before this token, i.e. the token associated with the span
+    current_token: Option<Token>,
+    // The reason for current_token being an error
+    current_error: Option<LexerError>,
     slice: &'a str,
     span: Span,
     indent: usize,
@@ -748,8 +1130,13 @@ pub struct KotoLexer<'a> {
 impl<'a> KotoLexer<'a> {
     /// Initializes a lexer with the given input script
     pub fn new(source: &'a str) -> Self {
+        Self::with_settings(source, LexerSettings::default())
+    }
+
+    /// Initializes a lexer with the given input script and settings
+    pub fn with_settings(source: &'a str, settings: LexerSettings) -> Self {
         Self {
-            lexer: TokenLexer::new(source),
+            lexer: TokenLexer::new(source, settings),
             peeked_tokens: Vec::new(),
             current_peek_index: 0,
         }
@@ -785,6 +1172,8 @@ impl<'a> KotoLexer<'a> {
     pub fn peek_n(&mut self, n: usize) -> Option<Token> {
         while self.peeked_tokens.len() - self.current_peek_index <= n {
             let span = self.lexer.span;
+            let current_token = self.lexer.previous_token;
+            let current_error = self.lexer.previous_error;
             let slice = self.lexer.slice();
             let indent = self.lexer.indent;
             let source_position = self.lexer.current_byte;
@@ -792,6 +1181,8 @@ impl<'a> KotoLexer<'a> {
             let token = self.lexer.next();
             self.peeked_tokens.push(PeekedToken {
                 token,
+                current_token,
+                current_error,
                 slice,
                 span,
                 indent,
@@ -810,6 +1201,28 @@ impl<'a> KotoLexer<'a> {
         }
     }
 
+    /// Returns the current token, i.e. the token that was most recently emitted
+    ///
+    /// The current token is the token associated with [KotoLexer::span] and [KotoLexer::slice].
+    pub fn current_token(&self) -> Option<Token> {
+        if self.peeked_tokens.is_empty() {
+            self.lexer.previous_token
+        } else {
+            self.peeked_tokens[self.current_peek_index].current_token
+        }
+    }
+
+    /// Returns the reason for the current token being a [Token::Error]
+    ///
+    /// None is returned if the current token isn't an error.
+    pub fn current_error(&self) -> Option<LexerError> {
+        if self.peeked_tokens.is_empty() {
+            self.lexer.previous_error
+        } else {
+            self.peeked_tokens[self.current_peek_index].current_error
+        }
+    }
+
     /// Returns the string slice of the input associated with the current token
     pub fn slice(&self) -> &'a str {
         if self.peeked_tokens.is_empty() {
@@ -819,6 +1232,11 @@ impl<'a> KotoLexer<'a> {
         }
     }
 
+    /// Returns the name of the current [Token::Id], without a raw identifier's backticks
+    pub fn id_name(&self) -> &'a str {
+        id_name(self.slice())
+    }
+
     /// Returns the indent associated with the current token
     pub fn current_indent(&self) -> usize {
         if self.peeked_tokens.is_empty() {
@@ -865,6 +1283,81 @@ impl<'a> Iterator for KotoLexer<'a> {
     }
 }
 
+/// A token along with the details of where it appears in the input source
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexedToken<'a> {
+    /// The kind of token
+    pub token: Token,
+    /// The slice of the input source that the token covers
+    pub slice: &'a str,
+    /// The line and column positions of the start and end of the token
+    pub span: Span,
+    /// The byte range of the token in the input source
+    pub source_bytes: Range<usize>,
+    /// The indentation of the line that the token appears on
+    ///
+    /// For [Token::NewLineIndented], this is the indentation of the line that follows.
+    pub indent: usize,
+    /// The reason for the token being a [Token::Error]
+    pub error: Option<LexerError>,
+}
+
+/// An iterator that produces each [LexedToken] in a script
+///
+/// All tokens are produced, including whitespace, comments, and newlines, so the token slices
+/// can be concatenated to reproduce the input source.
+///
+/// # Example
+///
+/// ```
+/// use koto_lexer::{Token, TokenStream};
+///
+/// let source = "x = 42 # the answer";
+/// let tokens: Vec<_> = TokenStream::new(source).collect();
+///
+/// assert_eq!(tokens[0].token, Token::Id);
+/// assert_eq!(tokens[4].slice, "42");
+/// assert_eq!(tokens.last().unwrap().token, Token::CommentSingle);
+///
+/// let round_trip: String = tokens.iter().map(|token| token.slice).collect();
+/// assert_eq!(round_trip, source);
+/// ```
+#[derive(Clone)]
+pub struct TokenStream<'a> {
+    lexer: TokenLexer<'a>,
+}
+
+impl<'a> TokenStream<'a> {
+    /// Initializes a token stream with the given input script
+    pub fn new(source: &'a str) -> Self {
+        Self::with_settings(source, LexerSettings::default())
+    }
+
+    /// Initializes a token stream with the given input script and settings
+    pub fn with_settings(source: &'a str, settings: LexerSettings) -> Self {
+        Self {
+            lexer: TokenLexer::new(source, settings),
+        }
+    }
+}
+
+impl<'a> Iterator for TokenStream<'a> {
+    type Item = LexedToken<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.lexer.next()?;
+
+        Some(LexedToken {
+            token,
+            slice: self.lexer.slice(),
+            span: self.lexer.span,
+            source_bytes: self.lexer.previous_byte..self.lexer.current_byte,
+            indent: self.lexer.indent,
+            error: self.lexer.previous_error,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Token::*, *};
@@ -878,6 +1371,7 @@ mod tests {
                     Whitespace => continue,
                     output => {
                         assert_eq!(&output, token, "Token mismatch at position {}", i);
+                        assert_eq!(lex.current_token(), Some(output));
                         if let Some(slice) = maybe_slice {
                             assert_eq!(&lex.slice(), slice, "Slice mismatch at position {}", i);
                         }
@@ -975,6 +1469,56 @@ y";
         );
     }
 
+    #[test]
+    fn tab_width() {
+        let source = "x\n\ty\n\t  z";
+        let indents = |settings| {
+            TokenStream::with_settings(source, settings)
+                .filter(|lexed| lexed.token == Id)
+                .map(|lexed| lexed.indent)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(indents(LexerSettings::default()), [0, 1, 3]);
+        assert_eq!(
+            indents(LexerSettings {
+                tab_width: 4,
+                ..Default::default()
+            }),
+            [0, 4, 6]
+        );
+    }
+
+    #[test]
+    fn reject_mixed_indentation() {
+        let settings = LexerSettings {
+            reject_mixed_indentation: true,
+            ..Default::default()
+        };
+        let newlines = |source| {
+            TokenStream::with_settings(source, settings)
+                .filter(|lexed| !matches!(lexed.token, Id | Whitespace))
+                .map(|lexed| lexed.token)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            newlines("a\n  b\n    c\nd"),
+            [NewLineIndented, NewLineIndented, NewLine]
+        );
+        assert_eq!(
+            newlines("a\n\tb\n\t\tc"),
+            [NewLineIndented, NewLineIndented]
+        );
+        // Tabs and spaces mixed on the same line
+        assert_eq!(newlines("a\n \tb"), [Error]);
+        // Tabs after spaces were used for earlier indentation
+        assert_eq!(
+            newlines("a\n  b\nc\n\td"),
+            [NewLineIndented, NewLine, Error]
+        );
+    }
+
     #[test]
     fn comments() {
         let input = "\
@@ -1041,6 +1585,30 @@ false #
         );
     }
 
+    #[test]
+    fn triple_quoted_strings() {
+        let input = r#"
+"""
+  "hello"
+  """
+''''''
+"#;
+
+        check_lexer_output(
+            input,
+            &[
+                (NewLine, None, 2),
+                (DoubleQuote, None, 2),
+                (StringLiteral, Some("\n  \"hello\"\n  "), 4),
+                (DoubleQuote, None, 4),
+                (NewLine, None, 5),
+                (SingleQuote, None, 5),
+                (SingleQuote, None, 5),
+                (NewLine, None, 6),
+            ],
+        );
+    }
+
     #[test]
     fn interpolated_string_ids() {
         let input = r#"
@@ -1105,10 +1673,160 @@ false #
         );
     }
 
+    #[test]
+    fn interpolated_string_with_nested_braces() {
+        let input = r#"
+"${{a: {b: '}'}}.a.b}!"
+"#;
+        check_lexer_output(
+            input,
+            &[
+                (NewLine, None, 2),
+                (DoubleQuote, None, 2),
+                (Dollar, None, 2),
+                (CurlyOpen, None, 2),
+                (CurlyOpen, None, 2),
+                (Id, Some("a"), 2),
+                (Colon, None, 2),
+                (CurlyOpen, None, 2),
+                (Id, Some("b"), 2),
+                (Colon, None, 2),
+                (SingleQuote, None, 2),
+                (StringLiteral, Some("}"), 2),
+                (SingleQuote, None, 2),
+                (CurlyClose, None, 2),
+                (CurlyClose, None, 2),
+                (Dot, None, 2),
+                (Id, Some("a"), 2),
+                (Dot, None, 2),
+                (Id, Some("b"), 2),
+                (CurlyClose, None, 2),
+                (StringLiteral, Some("!"), 2),
+                (DoubleQuote, None, 2),
+                (NewLine, None, 3),
+            ],
+        );
+    }
+
+    #[test]
+    fn span_offsets() {
+        let source = "'👋' é";
+        let tokens = TokenStream::new(source).collect::<Vec<_>>();
+
+        let id = tokens.last().unwrap();
+        assert_eq!(id.slice, "é");
+        assert_eq!(id.span.start.column, 6);
+        assert_eq!(id.span.byte_range(), 7..9);
+        assert_eq!(id.span.start.utf16_offset(source), Some(5));
+        assert_eq!(id.span.end.utf16_offset(source), Some(6));
+    }
+
+    #[test]
+    fn lexer_errors() {
+        let check_error = |source: &str, expected: LexerError, expected_slice: &str| {
+            let error = TokenStream::new(source)
+                .find(|lexed| lexed.token == Error)
+                .unwrap_or_else(|| panic!("Missing error while lexing '{}'", source));
+            assert_eq!(error.error, Some(expected), "{}", source);
+            assert_eq!(error.slice, expected_slice, "{}", source);
+        };
+
+        check_error("x = ~", LexerError::UnexpectedCharacter, "~");
+        check_error("#- abc", LexerError::UnterminatedComment, "#- abc");
+        check_error("0x + 1", LexerError::InvalidNumber, "0x");
+        check_error("1.5e", LexerError::InvalidNumber, "1.5e");
+        check_error(
+            "x = 'abc",
+            LexerError::UnterminatedString {
+                quote: Span {
+                    start: Position {
+                        line: 1,
+                        column: 5,
+                        offset: 4,
+                    },
+                    end: Position {
+                        line: 1,
+                        column: 6,
+                        offset: 5,
+                    },
+                },
+            },
+            "abc",
+        );
+
+        // Tokens that aren't errors don't have an associated error
+        assert!(TokenStream::new("x = 1").all(|lexed| lexed.error.is_none()));
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let source = "\
+f = |x|
+  if x > 0
+    '''
+    ${x}
+    '''
+  else
+    \"x: ${ {a: x}.a }\" # comment
+#- multi
+line -#
+";
+        let crlf_source = source.replace('\n', "\r\n");
+
+        let tokens = TokenStream::new(source).collect::<Vec<_>>();
+        let crlf_tokens = TokenStream::new(&crlf_source).collect::<Vec<_>>();
+        assert_eq!(tokens.len(), crlf_tokens.len());
+
+        for (lf, crlf) in tokens.iter().zip(crlf_tokens.iter()) {
+            assert_eq!(lf.token, crlf.token);
+            assert_eq!(lf.indent, crlf.indent);
+            assert_eq!(lf.slice.replace('\n', "\r\n"), crlf.slice);
+            assert_eq!(
+                (lf.span.start.line, lf.span.start.column),
+                (crlf.span.start.line, crlf.span.start.column),
+                "Mismatched span start for {:?}",
+                lf.token
+            );
+            assert_eq!(
+                (lf.span.end.line, lf.span.end.column),
+                (crlf.span.end.line, crlf.span.end.column),
+                "Mismatched span end for {:?}",
+                lf.token
+            );
+            assert_eq!(crlf.span.byte_range(), crlf.source_bytes);
+        }
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let source = "x = 1\ny";
+        let bom_source = format!("\u{feff}{}", source);
+
+        let tokens = TokenStream::new(source).collect::<Vec<_>>();
+        let bom_tokens = TokenStream::new(&bom_source).collect::<Vec<_>>();
+
+        // The byte order mark is emitted as whitespace with a zero-width span
+        let bom = &bom_tokens[0];
+        assert_eq!(bom.token, Whitespace);
+        assert_eq!(bom.slice, "\u{feff}");
+        assert_eq!(bom.span.byte_range(), 0..3);
+        assert_eq!((bom.span.end.line, bom.span.end.column), (1, 1),);
+
+        for (token, bom_token) in tokens.iter().zip(bom_tokens.iter().skip(1)) {
+            assert_eq!(token.token, bom_token.token);
+            assert_eq!(token.slice, bom_token.slice);
+            assert_eq!(
+                (token.span.start.line, token.span.start.column),
+                (bom_token.span.start.line, bom_token.span.start.column),
+            );
+            assert_eq!(bom_token.span.source_slice(&bom_source), Some(token.slice));
+        }
+    }
+
     #[test]
     fn operators() {
         let input = r#"
-> >= >> < <=
+> >= >> < <= ->
 "#;
         check_lexer_output(
             input,
@@ -1119,6 +1837,7 @@ false #
                 (Pipe, None, 2),
                 (Less, None, 2),
                 (LessOrEqual, None, 2),
+                (Arrow, None, 2),
                 (NewLine, None, 3),
             ],
         );
@@ -1162,6 +1881,42 @@ false #
         );
     }
 
+    #[test]
+    fn numbers_with_separators_and_exponents() {
+        let input = "\
+1_000_000
+0xFF_FF
+1_0.0_1e1_0
+2.5E6
+1.E-3
+1e
+1_ 2
+1.e";
+        check_lexer_output(
+            input,
+            &[
+                (Number, Some("1_000_000"), 1),
+                (NewLine, None, 2),
+                (Number, Some("0xFF_FF"), 2),
+                (NewLine, None, 3),
+                (Number, Some("1_0.0_1e1_0"), 3),
+                (NewLine, None, 4),
+                (Number, Some("2.5E6"), 4),
+                (NewLine, None, 5),
+                (Number, Some("1.E-3"), 5),
+                (NewLine, None, 6),
+                (Error, Some("1e"), 6),
+                (NewLine, None, 7),
+                (Error, Some("1_"), 7),
+                (Number, Some("2"), 7),
+                (NewLine, None, 8),
+                (Number, Some("1"), 8),
+                (Dot, None, 8),
+                (Id, Some("e"), 8),
+            ],
+        );
+    }
+
     #[test]
     fn lookups_on_numbers() {
         let input = "\
@@ -1377,6 +2132,61 @@ else
         );
     }
 
+    #[test]
+    fn raw_ids() {
+        let input = "`match` = {`if`: x.`else`}";
+
+        check_lexer_output(
+            input,
+            &[
+                (Id, Some("`match`"), 1),
+                (Assign, None, 1),
+                (CurlyOpen, None, 1),
+                (Id, Some("`if`"), 1),
+                (Colon, None, 1),
+                (Id, Some("x"), 1),
+                (Dot, None, 1),
+                (Id, Some("`else`"), 1),
+                (CurlyClose, None, 1),
+            ],
+        );
+
+        assert_eq!(id_name("`match`"), "match");
+        assert_eq!(id_name("foo"), "foo");
+    }
+
+    #[test]
+    fn invalid_raw_ids() {
+        for source in &["`", "``", "`match", "`1`", "`foo bar`"] {
+            let error = TokenStream::new(source)
+                .find(|lexed| lexed.token == Error)
+                .unwrap_or_else(|| panic!("Missing error while lexing '{}'", source));
+            assert_eq!(error.error, Some(LexerError::UnexpectedCharacter));
+        }
+    }
+
+    #[test]
+    fn errors_consume_input() {
+        let check_tokens = |source: &str, expected: &[(Token, &str)]| {
+            let tokens: Vec<_> = TokenStream::new(source)
+                .map(|token| (token.token, token.slice))
+                .collect();
+            assert_eq!(tokens, expected);
+        };
+
+        check_tokens("'abc\nx", &[(SingleQuote, "'"), (Error, "abc\nx")]);
+        check_tokens(
+            "x ~ y",
+            &[
+                (Id, "x"),
+                (Whitespace, " "),
+                (Error, "~"),
+                (Whitespace, " "),
+                (Id, "y"),
+            ],
+        );
+    }
+
     #[test]
     fn windows_line_endings() {
         let input = "123\r\n456\r\n789";
@@ -1392,4 +2202,137 @@ else
             ],
         );
     }
+
+    #[test]
+    fn token_stream_with_trivia() {
+        let source = "\
+x = 1 # one
+if x
+  #- multi
+  line -#
+  'a $x'
+";
+
+        let tokens: Vec<_> = TokenStream::new(source).collect();
+
+        let kinds: Vec<_> = tokens.iter().map(|token| token.token).collect();
+        assert_eq!(
+            kinds,
+            &[
+                Id,
+                Whitespace,
+                Assign,
+                Whitespace,
+                Number,
+                Whitespace,
+                CommentSingle,
+                NewLine,
+                If,
+                Whitespace,
+                Id,
+                NewLineIndented,
+                CommentMulti,
+                NewLineIndented,
+                SingleQuote,
+                StringLiteral,
+                Dollar,
+                Id,
+                SingleQuote,
+                NewLine,
+            ]
+        );
+
+        let comment = &tokens[12];
+        assert_eq!(comment.slice, "#- multi\n  line -#");
+        assert_eq!(comment.source_bytes, 19..37);
+        assert_eq!(
+            comment.span.start,
+            Position {
+                line: 3,
+                column: 3,
+                offset: 19
+            }
+        );
+        assert_eq!(
+            comment.span.end,
+            Position {
+                line: 4,
+                column: 10,
+                offset: 37
+            }
+        );
+        assert_eq!(comment.indent, 2);
+
+        // The span offsets match the token's byte range
+        for token in tokens.iter() {
+            assert_eq!(token.span.byte_range(), token.source_bytes);
+        }
+
+        let round_trip: String = tokens.iter().map(|token| token.slice).collect();
+        assert_eq!(round_trip, source);
+
+        for token in tokens.iter() {
+            assert_eq!(&source[token.source_bytes.clone()], token.slice);
+        }
+    }
+
+    #[test]
+    fn token_categories() {
+        use TokenCategory as C;
+
+        let source = "f = |x, _| x + 1 # add\nif f(1) >= 2 then '$x' else @main";
+        let categories: Vec<_> = TokenStream::new(source)
+            .map(|token| (token.slice, token.token.category()))
+            .filter(|(_, category)| *category != C::Whitespace)
+            .collect();
+
+        assert_eq!(
+            categories,
+            &[
+                ("f", C::Id),
+                ("=", C::Operator),
+                ("|", C::Punctuation),
+                ("x", C::Id),
+                (",", C::Punctuation),
+                ("_", C::Id),
+                ("|", C::Punctuation),
+                ("x", C::Id),
+                ("+", C::Operator),
+                ("1", C::Number),
+                ("# add", C::Comment),
+                ("if", C::Keyword),
+                ("f", C::Id),
+                ("(", C::Punctuation),
+                ("1", C::Number),
+                (")", C::Punctuation),
+                (">=", C::Operator),
+                ("2", C::Number),
+                ("then", C::Keyword),
+                ("'", C::String),
+                ("$", C::String),
+                ("x", C::Id),
+                ("'", C::String),
+                ("else", C::Keyword),
+                ("@", C::Punctuation),
+                ("main", C::Id),
+            ]
+        );
+    }
+
+    #[test]
+    fn current_token_with_lookahead() {
+        let mut lex = KotoLexer::new("x = 42");
+
+        assert_eq!(lex.current_token(), None);
+        assert_eq!(lex.next(), Some(Id));
+        assert_eq!(lex.peek_n(2), Some(Whitespace));
+        assert_eq!(lex.current_token(), Some(Id));
+        assert_eq!(lex.slice(), "x");
+        assert_eq!(lex.next(), Some(Whitespace));
+        assert_eq!(lex.next(), Some(Assign));
+        assert_eq!(lex.current_token(), Some(Assign));
+        assert_eq!(lex.slice(), "=");
+        assert_eq!(lex.peek(), Some(Whitespace));
+        assert_eq!(lex.current_token(), Some(Assign));
+    }
 }
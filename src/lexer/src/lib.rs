@@ -1,9 +1,29 @@
 //! Contains the lexer used by the Koto language
+//!
+//! The lexer is part of Koto's public interface, and can be used by tools that need to work with
+//! Koto source at the token level, like formatters and syntax highlighters.
+//!
+//! [TokenStream] produces every token in a script along with its [Span] and byte range.
+//! Whitespace, newlines, and comments are preserved as trivia tokens (see [Token::is_trivia]),
+//! so concatenating the token slices always reproduces the input source.
+//! [Token::category] provides a broad classification of each token for syntax highlighters.
+//!
+//! [Lexer] is the interface used by the Koto parser, providing lookahead via
+//! [Lexer::peek_n].
+//!
+//! # Stability
+//!
+//! The types exported from this crate follow semantic versioning along with the rest of Koto.
+//! Adding or removing a [Token] variant, or changing how a piece of source is split into tokens,
+//! is considered to be a breaking change, and will be listed in the changelog.
 
 #![warn(missing_docs)]
 
 mod lexer;
 mod span;
 
-pub use lexer::{is_id_continue, is_id_start, KotoLexer as Lexer, Token};
+pub use lexer::{
+    id_name, is_id_continue, is_id_start, KotoLexer as Lexer, LexedToken, LexerError,
+    LexerSettings, Token, TokenCategory, TokenStream,
+};
 pub use span::{Position, Span};
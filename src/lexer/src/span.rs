@@ -1,17 +1,42 @@
-use std::fmt;
+use {
+    std::{fmt, ops::Range},
+    unicode_width::UnicodeWidthChar,
+};
 
 /// Represents a line/column position in a script
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     /// The position's line, counting from 1
     pub line: u32,
     /// The position's column, counting from 1.
     pub column: u32,
+    /// The position's byte offset in the source, counting from 0
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub offset: u32,
+}
+
+impl Position {
+    /// Returns the position's offset in the source in UTF-16 code units
+    ///
+    /// This is useful for editors that index documents with UTF-16 offsets.
+    ///
+    /// None is returned if the position's byte offset doesn't fit within the source,
+    /// or if it isn't on a character boundary.
+    pub fn utf16_offset(&self, source: &str) -> Option<usize> {
+        source
+            .get(..self.offset as usize)
+            .map(|prefix| prefix.encode_utf16().count())
+    }
 }
 
 impl Default for Position {
     fn default() -> Self {
-        Self { line: 1, column: 1 }
+        Self {
+            line: 1,
+            column: 1,
+            offset: 0,
+        }
     }
 }
 
@@ -23,9 +48,37 @@ impl fmt::Display for Position {
 
 /// A span is a range in the source code, represented by a start and end position
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     /// The span's start position
     pub start: Position,
     /// The span's end position
     pub end: Position,
 }
+
+impl Span {
+    /// Returns the slice of the source that's covered by the span
+    ///
+    /// The source should be the same source that was used to produce the span.
+    ///
+    /// None is returned if the span's byte range doesn't fit within the source,
+    /// or if it doesn't start and end on character boundaries.
+    pub fn source_slice<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.byte_range())
+    }
+
+    /// Returns the range of bytes in the source that's covered by the span
+    pub fn byte_range(&self) -> Range<usize> {
+        self.start.offset as usize..self.end.offset as usize
+    }
+}
+
+// Returns the number of columns that a character occupies in a line
+//
+// Characters are counted by their display width, with tabs counting as a single column.
+pub(crate) fn column_width(c: char) -> usize {
+    match c {
+        '\t' => 1,
+        _ => c.width().unwrap_or(0),
+    }
+}
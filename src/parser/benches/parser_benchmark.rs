@@ -0,0 +1,60 @@
+use {
+    criterion::{criterion_group, criterion_main, Criterion},
+    koto_parser::Parser,
+    std::{fs::read_to_string, path::PathBuf},
+};
+
+// Produces a script where most tokens are repeated identifiers
+fn identifier_heavy_script() -> String {
+    let mut script = String::new();
+
+    for i in 0..2000 {
+        script.push_str(&format!(
+            "value_{} = foo_{}.bar(some_long_identifier, qux_{}) + another_identifier * value_{}\n",
+            i % 50,
+            i % 20,
+            i % 30,
+            (i + 1) % 50
+        ));
+    }
+
+    script
+}
+
+fn benchmark_scripts() -> Vec<String> {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("..");
+    dir.push("..");
+    dir.push("koto");
+    dir.push("benches");
+
+    [
+        "fannkuch.koto",
+        "n_body.koto",
+        "spectral_norm.koto",
+        "string_formatting.koto",
+    ]
+    .iter()
+    .map(|script| read_to_string(dir.join(script)).expect("Unable to load script"))
+    .collect()
+}
+
+pub fn parser_benchmark(c: &mut Criterion) {
+    c.bench_function("parse_identifiers", |b| {
+        let script = identifier_heavy_script();
+        b.iter(|| {
+            Parser::parse(&script).unwrap();
+        })
+    });
+    c.bench_function("parse_benchmark_scripts", |b| {
+        let scripts = benchmark_scripts();
+        b.iter(|| {
+            for script in scripts.iter() {
+                Parser::parse(script).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, parser_benchmark);
+criterion_main!(benches);
@@ -1,14 +1,37 @@
 use {
-    crate::{error::*, ConstantPool, Node},
-    koto_lexer::Span,
-    std::convert::TryFrom,
+    crate::{error::*, Constant, ConstantIndex, ConstantPool, Node},
+    koto_lexer::{Position, Span},
+    std::{
+        collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+        convert::TryFrom,
+        hash::{Hash, Hasher},
+    },
 };
 
 /// The index type used by nodes in the [Ast]
 pub type AstIndex = u32;
 
+/// A stable identifier for a node in an [Ast]
+///
+/// Node IDs are assigned as nodes are pushed onto the tree. Unlike an [AstIndex], which refers to
+/// a node's position in the tree, a node's ID can be carried over to the tree that's produced
+/// when an edited script is reparsed, see [Ast::preserve_node_ids].
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(pub u64);
+
+// Hashes that describe a node's contents, see Ast::structural_hashes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct NodeHash {
+    // A hash of the node's own contents, excluding its children
+    pub contents: u64,
+    // A hash of the node's contents along with the contents of all of its children
+    pub full: u64,
+}
+
 /// A [Node] in the [Ast], along with its corresponding [Span]
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AstNode {
     /// The node itself
     pub node: Node,
@@ -22,11 +45,16 @@ pub struct AstNode {
 ///
 /// This is produced by the parser, and consumed by the compiler.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ast {
     nodes: Vec<AstNode>,
     spans: Vec<Span>,
     constants: ConstantPool,
     entry_point: u32,
+    // The ID of each node in the nodes list
+    node_ids: Vec<NodeId>,
+    // The ID that will be assigned to the next node that's pushed
+    next_node_id: u64,
 }
 
 impl Ast {
@@ -37,6 +65,8 @@ impl Ast {
             spans: Vec::with_capacity(capacity),
             constants: ConstantPool::default(),
             entry_point: 0,
+            node_ids: Vec::with_capacity(capacity),
+            next_node_id: 0,
         }
     }
 
@@ -52,6 +82,7 @@ impl Ast {
             node,
             span: span_index,
         });
+        self.push_node_id();
         AstIndex::try_from(self.nodes.len() - 1)
             .map_err(|_| ParserError::new(InternalError::AstCapacityOverflow.into(), span))
     }
@@ -66,6 +97,7 @@ impl Ast {
             node,
             span: span_index,
         });
+        self.push_node_id();
         AstIndex::try_from(self.nodes.len() - 1).map_err(|_| {
             ParserError::new(
                 InternalError::AstCapacityOverflow.into(),
@@ -79,11 +111,255 @@ impl Ast {
         &self.nodes[index as usize]
     }
 
+    /// Replaces the node at the given index, keeping the node's span and ID
+    pub fn replace_node(&mut self, index: AstIndex, node: Node) {
+        self.nodes[index as usize].node = node;
+    }
+
+    /// Returns the ID of the node with the given index
+    pub fn node_id(&self, index: AstIndex) -> NodeId {
+        self.node_ids[index as usize]
+    }
+
+    /// Returns the index of the node with the given ID, if it's contained in the tree
+    ///
+    /// Note that the tree's nodes are searched linearly.
+    pub fn find_node(&self, id: NodeId) -> Option<AstIndex> {
+        self.node_ids
+            .iter()
+            .position(|node_id| *node_id == id)
+            .map(|index| index as AstIndex)
+    }
+
+    /// Carries over node IDs from a previous version of the tree
+    ///
+    /// This is useful after reparsing an edited script, allowing data that's associated with
+    /// the nodes of the previous tree (e.g. editor annotations, or cached compilation results)
+    /// to be reused for nodes that haven't changed.
+    ///
+    /// A node keeps the ID of a node in the previous tree when both nodes have the same contents,
+    /// including the contents of their children and the values of any constants they refer to.
+    /// Node positions aren't taken into account, so unchanged nodes that have been moved by an
+    /// edit keep their IDs. When there are multiple candidates with the same contents then they're
+    /// matched in the order in which they appear in the trees.
+    ///
+    /// Nodes that have changed are assigned new IDs that aren't used in the previous tree.
+    pub fn preserve_node_ids(&mut self, previous: &Ast) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let hashes = self.structural_hashes();
+        let previous_hashes = previous.structural_hashes();
+
+        // The previous tree's nodes, grouped by hash, in depth-first order
+        let mut candidates: HashMap<u64, VecDeque<AstIndex>> = HashMap::new();
+        for index in previous.depth_first_order() {
+            candidates
+                .entry(previous_hashes[index as usize].full)
+                .or_default()
+                .push_back(index);
+        }
+
+        let mut new_ids = vec![None; self.nodes.len()];
+        let mut used = HashSet::new();
+
+        // Match nodes starting from the entry point, with the children of a matched node being
+        // matched with the corresponding children of the previous node.
+        let mut pending = vec![self.entry_point];
+        while let Some(index) = pending.pop() {
+            let children = self.nodes[index as usize].node.children();
+
+            let matching =
+                candidates
+                    .get_mut(&hashes[index as usize].full)
+                    .and_then(|candidates| {
+                        while let Some(candidate) = candidates.pop_front() {
+                            if !used.contains(&candidate) {
+                                return Some(candidate);
+                            }
+                        }
+                        None
+                    });
+
+            match matching {
+                Some(previous_index) => {
+                    let mut matched = vec![(index, previous_index)];
+                    while let Some((index, previous_index)) = matched.pop() {
+                        if new_ids[index as usize].is_some() || !used.insert(previous_index) {
+                            continue;
+                        }
+                        new_ids[index as usize] = Some(previous.node_id(previous_index));
+                        let previous_children =
+                            previous.nodes[previous_index as usize].node.children();
+                        let children = self.nodes[index as usize].node.children();
+                        matched.extend(children.into_iter().zip(previous_children));
+                    }
+                }
+                // Children are visited in order, so they're pushed in reverse
+                None => pending.extend(children.into_iter().rev()),
+            }
+        }
+
+        let mut next_node_id = previous.next_node_id.max(self.next_node_id);
+        for (node_id, new_id) in self.node_ids.iter_mut().zip(new_ids) {
+            *node_id = new_id.unwrap_or_else(|| {
+                let result = NodeId(next_node_id);
+                next_node_id += 1;
+                result
+            });
+        }
+        self.next_node_id = next_node_id;
+    }
+
     /// Returns a span for a given span index
     pub fn span(&self, index: AstIndex) -> &Span {
         &self.spans[index as usize]
     }
 
+    /// Returns the slice of the source that corresponds to the node with the given index
+    ///
+    /// The slice covers the node along with all of its child nodes.
+    /// The source should be the same source that was used to produce the AST.
+    /// See [Span::source_slice].
+    pub fn source_slice<'a>(&self, index: AstIndex, source: &'a str) -> Option<&'a str> {
+        self.node_span_with_children(index)?.source_slice(source)
+    }
+
+    /// Returns the innermost node that contains the given position
+    ///
+    /// See [Ast::nodes_at].
+    pub fn node_at(&self, position: Position) -> Option<AstIndex> {
+        self.nodes_at(position).last().copied()
+    }
+
+    /// Returns the chain of nodes that contain the given position
+    ///
+    /// The chain starts with the entry point and ends with the innermost node that contains the
+    /// position, with each node's span including the spans of its children.
+    ///
+    /// A position at the end of a node is considered to be contained by the node,
+    /// e.g. the position immediately following an ID is considered to be part of the ID.
+    /// If the position is at the end of one child and the start of another,
+    /// then the child that starts at the position is preferred.
+    pub fn nodes_at(&self, position: Position) -> Vec<AstIndex> {
+        let mut result = Vec::new();
+        let position = (position.line, position.column);
+
+        let contains = |span: &Span| {
+            (span.start.line, span.start.column) <= position
+                && position <= (span.end.line, span.end.column)
+        };
+
+        let mut next = match self.node_span_with_children(self.entry_point) {
+            Some(span) if contains(&span) => Some(self.entry_point),
+            _ => None,
+        };
+
+        while let Some(index) = next {
+            result.push(index);
+
+            next = None;
+            for child in self.nodes[index as usize].node.children() {
+                if let Some(child_span) = self.node_span_with_children(child) {
+                    if contains(&child_span) {
+                        next = Some(child);
+                        if position < (child_span.end.line, child_span.end.column) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    // Returns a span that covers the node along with all of its children
+    //
+    // A node's own span doesn't necessarily cover its children,
+    // e.g. the span of a binary op refers to its operator and RHS.
+    pub(crate) fn node_span_with_children(&self, index: AstIndex) -> Option<Span> {
+        let node = self.nodes.get(index as usize)?;
+        let mut result = *self.spans.get(node.span as usize)?;
+
+        for child in node.node.children() {
+            if let Some(child_span) = self.node_span_with_children(child) {
+                if child_span.start.offset < result.start.offset {
+                    result.start = child_span.start;
+                }
+                if child_span.end.offset > result.end.offset {
+                    result.end = child_span.end;
+                }
+            }
+        }
+
+        Some(result)
+    }
+
+    // Returns hashes for each node that are derived from the node's contents and its children
+    //
+    // Child and constant indices are replaced with the hashes of the children and the values of
+    // the constants, so that the hashes can be compared between trees.
+    pub(crate) fn structural_hashes(&self) -> Vec<NodeHash> {
+        let mut result: Vec<NodeHash> = Vec::with_capacity(self.nodes.len());
+
+        for ast_node in self.nodes.iter() {
+            let mut contents_hasher = DefaultHasher::new();
+            let mut children_hasher = DefaultHasher::new();
+            let mut node = ast_node.node.clone();
+
+            // Children are pushed before their parents, so their hashes are already available
+            node.map_children(|child| {
+                result
+                    .get(child as usize)
+                    .map_or(0, |child_hash| child_hash.full)
+                    .hash(&mut children_hasher);
+                0
+            });
+            node.map_constants(|constant| {
+                match self.constants.get(usize::from(constant)) {
+                    Some(Constant::F64(n)) => n.to_bits().hash(&mut contents_hasher),
+                    Some(Constant::I64(n)) => n.hash(&mut contents_hasher),
+                    Some(Constant::Str(s)) => s.hash(&mut contents_hasher),
+                    None => {}
+                }
+                ConstantIndex::from(0_u32)
+            });
+            format!("{:?}", node).hash(&mut contents_hasher);
+
+            let contents = contents_hasher.finish();
+            contents.hash(&mut children_hasher);
+            result.push(NodeHash {
+                contents,
+                full: children_hasher.finish(),
+            });
+        }
+
+        result
+    }
+
+    // Returns the indices of the nodes that are reachable from the entry point, depth-first
+    fn depth_first_order(&self) -> Vec<AstIndex> {
+        let mut result = Vec::with_capacity(self.nodes.len());
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let mut pending = vec![self.entry_point];
+        while let Some(index) = pending.pop() {
+            result.push(index);
+            pending.extend(self.nodes[index as usize].node.children().into_iter().rev());
+        }
+
+        result
+    }
+
+    fn push_node_id(&mut self) {
+        self.node_ids.push(NodeId(self.next_node_id));
+        self.next_node_id += 1;
+    }
+
     /// Returns the constant pool referred to by the AST
     pub fn constants(&self) -> &ConstantPool {
         &self.constants
@@ -97,7 +373,11 @@ impl Ast {
         self.constants
     }
 
-    pub(crate) fn set_constants(&mut self, constants: ConstantPool) {
+    /// Replaces the AST's constant pool
+    ///
+    /// Nodes refer to constants by index, so the new pool needs to contain the existing
+    /// constants at their current positions.
+    pub fn set_constants(&mut self, constants: ConstantPool) {
         self.constants = constants
     }
 
@@ -106,6 +386,11 @@ impl Ast {
         self.nodes.get(self.entry_point as usize)
     }
 
+    /// Returns the index of the root node in the tree
+    pub fn entry_point_index(&self) -> AstIndex {
+        self.entry_point
+    }
+
     // Sets the entry point for the AST
     //
     // In practice this will always be the last node in the nodes list,
@@ -0,0 +1,260 @@
+use {
+    crate::{
+        Ast, AstIndex, AstString, Constant, ConstantIndex, ConstantPoolBuilder, ImportItemNode,
+        LookupNode, MapKey, Node, Span, StringNode,
+    },
+    std::{convert::TryFrom, error, fmt},
+};
+
+/// The error type used by the [AstBuilder]
+#[derive(Clone, Debug)]
+pub struct AstBuilderError {
+    /// The error's message
+    pub message: String,
+    /// The index of the node that caused the error
+    pub node: AstIndex,
+}
+
+impl fmt::Display for AstBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at node {})", self.message, self.node)
+    }
+}
+
+impl error::Error for AstBuilderError {}
+
+/// A builder of [Ast]s, allowing code generators and tools to produce ASTs without Koto source
+///
+/// Nodes are added to the tree with [AstBuilder::push], which returns the index of the new node.
+/// A node's children must be pushed before the node itself, and the last node that's pushed
+/// becomes the tree's entry point, which would typically be a [Node::MainBlock].
+///
+/// Nodes are validated as they're pushed:
+///   - Child indices must refer to nodes that have already been pushed.
+///   - Constant indices must refer to constants of the expected type,
+///     e.g. [Node::Id] must refer to a string, and [Node::Int] must refer to an i64.
+///
+/// Values that the parser would usually derive from the source aren't validated,
+/// e.g. `local_count` for [Node::MainBlock] and [Function](crate::Function)s, along with
+/// [Function::accessed_non_locals](crate::Function::accessed_non_locals), need to be provided
+/// correctly by the caller.
+///
+/// # Example
+///
+/// ```
+/// use koto_parser::{AssignOp, AssignTarget, AstBinaryOp, AstBuilder, Node, Scope};
+///
+/// // Produces the equivalent of: `x = 1 + 2`
+/// let mut builder = AstBuilder::new();
+/// let x = builder.add_string("x").unwrap();
+/// let two = builder.add_i64(2).unwrap();
+///
+/// let target = builder.push(Node::Id(x)).unwrap();
+/// let lhs = builder.push(Node::Number1).unwrap();
+/// let rhs = builder.push(Node::Int(two)).unwrap();
+/// let expression = builder
+///     .push(Node::BinaryOp { op: AstBinaryOp::Add, lhs, rhs })
+///     .unwrap();
+/// let assign = builder
+///     .push(Node::Assign {
+///         target: AssignTarget { target_index: target, scope: Scope::Local },
+///         op: AssignOp::Equal,
+///         expression,
+///     })
+///     .unwrap();
+/// builder
+///     .push(Node::MainBlock { body: vec![assign], local_count: 1 })
+///     .unwrap();
+///
+/// let ast = builder.build().unwrap();
+/// ```
+#[derive(Default)]
+pub struct AstBuilder {
+    ast: Ast,
+    constants: ConstantPoolBuilder,
+    span: Span,
+}
+
+impl AstBuilder {
+    /// Initializes an empty AstBuilder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a string constant to the tree, returning the constant's index
+    pub fn add_string(&mut self, s: &str) -> Result<ConstantIndex, AstBuilderError> {
+        self.constants
+            .add_string(s)
+            .map_err(|_| self.make_error("Too many constants".into()))
+    }
+
+    /// Adds an f64 constant to the tree, returning the constant's index
+    pub fn add_f64(&mut self, n: f64) -> Result<ConstantIndex, AstBuilderError> {
+        self.constants
+            .add_f64(n)
+            .map_err(|_| self.make_error("Too many constants".into()))
+    }
+
+    /// Adds an i64 constant to the tree, returning the constant's index
+    pub fn add_i64(&mut self, n: i64) -> Result<ConstantIndex, AstBuilderError> {
+        self.constants
+            .add_i64(n)
+            .map_err(|_| self.make_error("Too many constants".into()))
+    }
+
+    /// Sets the span that will be associated with the nodes that are pushed next
+    ///
+    /// Spans are used when reporting compilation and runtime errors.
+    /// By default nodes are associated with the start of the source.
+    pub fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
+    /// Returns the number of nodes that have been pushed
+    pub fn node_count(&self) -> usize {
+        self.ast.nodes().len()
+    }
+
+    /// Validates and pushes a node onto the tree, returning the node's index
+    pub fn push(&mut self, node: Node) -> Result<AstIndex, AstBuilderError> {
+        let node_count = self.node_count();
+
+        for child in node.children() {
+            if child as usize >= node_count {
+                return Err(self.make_error(format!(
+                    "Child node {} hasn't been added to the tree",
+                    child
+                )));
+            }
+        }
+
+        for (constant, expected) in node_constants(&node) {
+            let valid = matches!(
+                (self.constants.get(constant), expected),
+                (Some(Constant::Str(_)), ConstantType::Str)
+                    | (Some(Constant::I64(_)), ConstantType::I64)
+                    | (Some(Constant::F64(_)), ConstantType::F64)
+            );
+            if !valid {
+                return Err(
+                    self.make_error(format!("Expected constant {} to be {}", constant, expected))
+                );
+            }
+        }
+
+        let span = self.span;
+        self.ast
+            .push(node, span)
+            .map_err(|_| self.make_error("Too many nodes".into()))
+    }
+
+    /// Consumes the builder, producing the [Ast]
+    ///
+    /// The last node that was pushed becomes the tree's entry point.
+    pub fn build(mut self) -> Result<Ast, AstBuilderError> {
+        let entry_point = match self.node_count().checked_sub(1) {
+            Some(index) => {
+                AstIndex::try_from(index).map_err(|_| self.make_error("Too many nodes".into()))?
+            }
+            None => return Err(self.make_error("The tree doesn't contain any nodes".into())),
+        };
+
+        self.ast.set_entry_point(entry_point);
+        self.ast.set_constants(self.constants.build());
+        Ok(self.ast)
+    }
+
+    // Makes an error that refers to the next node to be pushed
+    fn make_error(&self, message: String) -> AstBuilderError {
+        AstBuilderError {
+            message,
+            node: AstIndex::try_from(self.node_count()).unwrap_or(AstIndex::MAX),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ConstantType {
+    Str,
+    I64,
+    F64,
+}
+
+impl fmt::Display for ConstantType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstantType::Str => write!(f, "a string"),
+            ConstantType::I64 => write!(f, "an i64"),
+            ConstantType::F64 => write!(f, "an f64"),
+        }
+    }
+}
+
+// Returns the constants that are referred to by a node, along with their expected types
+fn node_constants(node: &Node) -> Vec<(ConstantIndex, ConstantType)> {
+    use ConstantType::*;
+
+    let mut result = Vec::new();
+
+    match node {
+        Node::Id(id)
+        | Node::NamedCall { id, .. }
+        | Node::Meta(_, Some(id))
+        | Node::Ellipsis(Some(id))
+        | Node::Debug {
+            expression_string: id,
+            ..
+        }
+        | Node::Assert {
+            expression_string: id,
+            ..
+        } => result.push((*id, Str)),
+        Node::Int(constant) => result.push((*constant, I64)),
+        Node::Float(constant) => result.push((*constant, F64)),
+        Node::Str(string) => string_constants(string, &mut result),
+        Node::Lookup((lookup_node, _)) => match lookup_node {
+            LookupNode::Id(id) => result.push((*id, Str)),
+            LookupNode::Str(string) => string_constants(string, &mut result),
+            LookupNode::Root(_) | LookupNode::Index(_) | LookupNode::Call { .. } => {}
+        },
+        Node::Map(entries) => {
+            for (key, _) in entries.iter() {
+                match key {
+                    MapKey::Id(id) | MapKey::Meta(_, Some(id)) => result.push((*id, Str)),
+                    MapKey::Str(string) => string_constants(string, &mut result),
+                    MapKey::Meta(_, None) => {}
+                }
+            }
+        }
+        Node::Function(function) => {
+            let ids = function
+                .arg_types
+                .iter()
+                .flatten()
+                .chain(function.return_type.iter())
+                .chain(function.accessed_non_locals.iter());
+            result.extend(ids.map(|id| (*id, Str)));
+        }
+        Node::Import { items, from } => {
+            for item in from.iter().chain(items.iter().flatten()) {
+                match item {
+                    ImportItemNode::Id(id) => result.push((*id, Str)),
+                    ImportItemNode::Str(string) => string_constants(string, &mut result),
+                }
+            }
+        }
+        Node::For(for_loop) => result.extend(for_loop.args.iter().flatten().map(|id| (*id, Str))),
+        Node::Try(try_expression) => result.extend(try_expression.catch_arg.map(|id| (id, Str))),
+        _ => {}
+    }
+
+    result
+}
+
+fn string_constants(string: &AstString, result: &mut Vec<(ConstantIndex, ConstantType)>) {
+    for node in string.nodes.iter() {
+        if let StringNode::Literal(constant) = node {
+            result.push((*constant, ConstantType::Str));
+        }
+    }
+}
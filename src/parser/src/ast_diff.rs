@@ -0,0 +1,215 @@
+use crate::{ast::NodeHash, Ast, AstIndex, Span};
+
+/// A change between two [Ast]s, see [Ast::diff]
+#[derive(Clone, Debug, PartialEq)]
+pub enum AstChange {
+    /// A node was added in the updated tree
+    Inserted {
+        /// The index of the node in the updated tree
+        node: AstIndex,
+        /// The span of the node, including its children
+        span: Span,
+    },
+    /// A node from the previous tree is missing in the updated tree
+    Removed {
+        /// The index of the node in the previous tree
+        node: AstIndex,
+        /// The span of the node in the previous tree's source, including its children
+        span: Span,
+    },
+    /// A node's contents have changed, while keeping the same kind
+    ///
+    /// Changes to the node's children are reported separately.
+    Changed {
+        /// The index of the node in the previous tree
+        previous: AstIndex,
+        /// The span of the node in the previous tree's source, including its children
+        previous_span: Span,
+        /// The index of the node in the updated tree
+        node: AstIndex,
+        /// The span of the node in the updated tree's source, including its children
+        span: Span,
+    },
+}
+
+impl Ast {
+    /// Compares the tree with an updated version, returning the changes between them
+    ///
+    /// The trees are compared structurally starting from their entry points, so that nodes that
+    /// have been moved (e.g. by inserting lines above them) are considered to be unchanged.
+    ///
+    /// Nodes of the same kind at matching positions are reported as [AstChange::Changed] when
+    /// their own contents differ, e.g. a literal with a new value, or a list with an additional
+    /// entry. Nodes that can't be matched are reported as [AstChange::Removed] or
+    /// [AstChange::Inserted].
+    pub fn diff(&self, updated: &Ast) -> Vec<AstChange> {
+        let mut differ = AstDiffer {
+            previous: self,
+            updated,
+            previous_hashes: self.structural_hashes(),
+            updated_hashes: updated.structural_hashes(),
+            changes: Vec::new(),
+        };
+
+        match (self.entry_point(), updated.entry_point()) {
+            (Some(_), Some(_)) => {
+                differ.compare(self.entry_point_index(), updated.entry_point_index())
+            }
+            (Some(_), None) => differ.removed(self.entry_point_index()),
+            (None, Some(_)) => differ.inserted(updated.entry_point_index()),
+            (None, None) => {}
+        }
+
+        differ.changes
+    }
+}
+
+struct AstDiffer<'a> {
+    previous: &'a Ast,
+    updated: &'a Ast,
+    previous_hashes: Vec<NodeHash>,
+    updated_hashes: Vec<NodeHash>,
+    changes: Vec<AstChange>,
+}
+
+impl AstDiffer<'_> {
+    fn compare(&mut self, previous: AstIndex, updated: AstIndex) {
+        let previous_hash = self.previous_hashes[previous as usize];
+        let updated_hash = self.updated_hashes[updated as usize];
+
+        if previous_hash.full == updated_hash.full {
+            return;
+        }
+
+        let previous_node = &self.previous.node(previous).node;
+        let updated_node = &self.updated.node(updated).node;
+
+        if previous_node.kind() != updated_node.kind() {
+            self.removed(previous);
+            self.inserted(updated);
+            return;
+        }
+
+        if previous_hash.contents != updated_hash.contents {
+            self.changes.push(AstChange::Changed {
+                previous,
+                previous_span: span_or_default(self.previous, previous),
+                node: updated,
+                span: span_or_default(self.updated, updated),
+            });
+        }
+
+        self.compare_children(&previous_node.children(), &updated_node.children());
+    }
+
+    // Aligns the children of two matching nodes, comparing the children that line up
+    //
+    // Children with identical contents are aligned by finding their longest common subsequence,
+    // then the unaligned children in each gap are compared pairwise.
+    fn compare_children(&mut self, previous: &[AstIndex], updated: &[AstIndex]) {
+        let previous_hashes = previous
+            .iter()
+            .map(|child| self.previous_hashes[*child as usize].full)
+            .collect::<Vec<_>>();
+        let updated_hashes = updated
+            .iter()
+            .map(|child| self.updated_hashes[*child as usize].full)
+            .collect::<Vec<_>>();
+
+        // Skip over unchanged children at the start and end
+        let prefix = previous_hashes
+            .iter()
+            .zip(updated_hashes.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = previous_hashes[prefix..]
+            .iter()
+            .rev()
+            .zip(updated_hashes[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let previous_hashes = &previous_hashes[prefix..previous_hashes.len() - suffix];
+        let updated_hashes = &updated_hashes[prefix..updated_hashes.len() - suffix];
+
+        // lcs[i][j] contains the length of the common subsequence of previous[i..] and updated[j..]
+        let mut lcs = vec![vec![0usize; updated_hashes.len() + 1]; previous_hashes.len() + 1];
+        for i in (0..previous_hashes.len()).rev() {
+            for j in (0..updated_hashes.len()).rev() {
+                lcs[i][j] = if previous_hashes[i] == updated_hashes[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let previous = &previous[prefix..];
+        let updated = &updated[prefix..];
+        let mut removed = Vec::new();
+        let mut inserted = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        while i < previous_hashes.len() || j < updated_hashes.len() {
+            if i < previous_hashes.len()
+                && j < updated_hashes.len()
+                && previous_hashes[i] == updated_hashes[j]
+            {
+                self.compare_gap(&removed, &inserted);
+                removed.clear();
+                inserted.clear();
+                i += 1;
+                j += 1;
+                continue;
+            }
+
+            let skip_previous = if i == previous_hashes.len() {
+                false
+            } else if j == updated_hashes.len() {
+                true
+            } else {
+                lcs[i + 1][j] >= lcs[i][j + 1]
+            };
+
+            if skip_previous {
+                removed.push(previous[i]);
+                i += 1;
+            } else {
+                inserted.push(updated[j]);
+                j += 1;
+            }
+        }
+
+        self.compare_gap(&removed, &inserted);
+    }
+
+    // Compares the children in a gap between aligned children
+    fn compare_gap(&mut self, removed: &[AstIndex], inserted: &[AstIndex]) {
+        for (previous, updated) in removed.iter().zip(inserted.iter()) {
+            self.compare(*previous, *updated);
+        }
+        for previous in removed.iter().skip(inserted.len()) {
+            self.removed(*previous);
+        }
+        for updated in inserted.iter().skip(removed.len()) {
+            self.inserted(*updated);
+        }
+    }
+
+    fn removed(&mut self, node: AstIndex) {
+        self.changes.push(AstChange::Removed {
+            node,
+            span: span_or_default(self.previous, node),
+        });
+    }
+
+    fn inserted(&mut self, node: AstIndex) {
+        self.changes.push(AstChange::Inserted {
+            node,
+            span: span_or_default(self.updated, node),
+        });
+    }
+}
+
+fn span_or_default(ast: &Ast, node: AstIndex) -> Span {
+    ast.node_span_with_children(node).unwrap_or_default()
+}
@@ -1,44 +1,56 @@
 use std::{convert::TryFrom, fmt};
 
-const CONSTANT_INDEX_MAX: usize = 2_usize.pow(24) - 1;
-
-/// A 24 bit index for constants
-///
-/// Values are stored as little-endian 24 bit values.
+/// A 32 bit index for constants
 ///
-/// Q: Why not just use a u32?
-/// A: Minimizing the memory footprint for a complex script with lots of constants seems
-///    like a healthy idea. Having a dedicated u24 type also forces the validity of a
-///    value to be checked when its first created; after creation an in-range index is
-///    guaranteed.
-/// Q: What if we need more than 2^24 constants in a script?
-/// A: Let's wait and see, ConstantIndex can be transitioned to a u32 (along with the
-///    corresponding constant loading ops) if it really turns out to be necessary.
+/// Constant indices are encoded in bytecode using the smallest number of bytes that can
+/// represent the index, see [ConstantIndex::byte_count].
 #[derive(Clone, Copy, Hash, PartialEq)]
-pub struct ConstantIndex(pub u8, pub u8, pub u8);
+pub struct ConstantIndex(u32);
 
 impl ConstantIndex {
-    /// The raw bytes representing the constant index
-    pub fn bytes(&self) -> [u8; 3] {
-        [self.0, self.1, self.2]
+    /// The raw little-endian bytes representing the constant index
+    pub fn bytes(&self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+
+    /// The number of bytes needed to represent the index, from 1 to 4
+    pub fn byte_count(&self) -> usize {
+        match self.0 {
+            0..=0xff => 1,
+            0x100..=0xffff => 2,
+            0x1_0000..=0xff_ffff => 3,
+            _ => 4,
+        }
+    }
+}
+
+impl From<u32> for ConstantIndex {
+    fn from(x: u32) -> Self {
+        Self(x)
     }
 }
 
 impl From<u8> for ConstantIndex {
     fn from(x: u8) -> Self {
-        Self(x, 0, 0)
+        Self(x as u32)
     }
 }
 
 impl From<[u8; 2]> for ConstantIndex {
     fn from(x: [u8; 2]) -> Self {
-        Self(x[0], x[1], 0)
+        Self(u16::from_le_bytes(x) as u32)
     }
 }
 
 impl From<[u8; 3]> for ConstantIndex {
     fn from(x: [u8; 3]) -> Self {
-        Self(x[0], x[1], x[2])
+        Self(u32::from_le_bytes([x[0], x[1], x[2], 0]))
+    }
+}
+
+impl From<[u8; 4]> for ConstantIndex {
+    fn from(x: [u8; 4]) -> Self {
+        Self(u32::from_le_bytes(x))
     }
 }
 
@@ -46,18 +58,21 @@ impl TryFrom<usize> for ConstantIndex {
     type Error = ConstantIndexTryFromOutOfRange;
 
     fn try_from(x: usize) -> Result<Self, Self::Error> {
-        if x <= CONSTANT_INDEX_MAX {
-            let bytes = x.to_le_bytes();
-            Ok(Self(bytes[0], bytes[1], bytes[2]))
-        } else {
-            Err(ConstantIndexTryFromOutOfRange())
-        }
+        u32::try_from(x)
+            .map(Self)
+            .map_err(|_| ConstantIndexTryFromOutOfRange())
+    }
+}
+
+impl From<ConstantIndex> for u32 {
+    fn from(x: ConstantIndex) -> Self {
+        x.0
     }
 }
 
 impl From<ConstantIndex> for usize {
     fn from(x: ConstantIndex) -> Self {
-        (x.0 as usize) | (x.1 as usize) << 8 | (x.2 as usize) << 16
+        x.0 as usize
     }
 }
 
@@ -87,6 +102,21 @@ impl fmt::Debug for ConstantIndex {
     }
 }
 
+// Constant indices are serialized as plain integers
+#[cfg(feature = "serde")]
+impl serde::Serialize for ConstantIndex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ConstantIndex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Self)
+    }
+}
+
 /// The error returned from TryFrom implementations for ConstantIndex
 #[derive(Debug)]
 pub struct ConstantIndexTryFromOutOfRange();
@@ -105,6 +135,31 @@ mod tests {
     fn test_bytes_from_usize() {
         let x = usize::from_le_bytes([12, 34, 56, 0, 0, 0, 0, 0]);
         let constant = ConstantIndex::try_from(x).unwrap();
-        assert_eq!(constant.bytes(), [12, 34, 56]);
+        assert_eq!(constant.bytes(), [12, 34, 56, 0]);
+        assert_eq!(constant.byte_count(), 3);
+    }
+
+    #[test]
+    fn test_byte_count() {
+        assert_eq!(ConstantIndex::from(0_u8).byte_count(), 1);
+        assert_eq!(ConstantIndex::from(255_u8).byte_count(), 1);
+        assert_eq!(ConstantIndex::from([0, 1]).byte_count(), 2);
+        assert_eq!(ConstantIndex::from([0, 0, 1]).byte_count(), 3);
+        assert_eq!(ConstantIndex::from([0, 0, 0, 1]).byte_count(), 4);
+        assert_eq!(ConstantIndex::from([255; 4]).byte_count(), 4);
+    }
+
+    #[test]
+    fn test_u32_conversions() {
+        let constant = ConstantIndex::from(0x1234_5678_u32);
+        assert_eq!(u32::from(constant), 0x1234_5678);
+        assert_eq!(constant.bytes(), [0x78, 0x56, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        if let Ok(x) = usize::try_from(u32::MAX as u64 + 1) {
+            assert!(ConstantIndex::try_from(x).is_err());
+        }
     }
 }
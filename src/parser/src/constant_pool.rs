@@ -1,10 +1,14 @@
 use {
     crate::{ConstantIndex, ConstantIndexTryFromOutOfRange},
     std::{
-        collections::{hash_map::DefaultHasher, HashMap},
-        convert::TryFrom,
+        collections::{
+            hash_map::{DefaultHasher, Entry},
+            HashMap,
+        },
+        convert::{TryFrom, TryInto},
         fmt,
-        hash::{Hash, Hasher},
+        hash::{BuildHasherDefault, Hash, Hasher},
+        mem,
         ops::Range,
         sync::Arc,
     },
@@ -23,6 +27,7 @@ enum ConstantEntry {
 
 /// A constant provided by a [ConstantPool]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constant<'a> {
     /// An f64 constant
     F64(f64),
@@ -32,6 +37,56 @@ pub enum Constant<'a> {
     Str(&'a str),
 }
 
+/// Statistics describing the contents of a [ConstantPool]
+///
+/// See [ConstantPool::stats] and [ConstantPoolBuilder::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConstantPoolStats {
+    /// The total number of constants in the pool
+    pub entry_count: usize,
+    /// The number of string constants in the pool
+    pub string_count: usize,
+    /// The number of i64 constants in the pool
+    pub int_count: usize,
+    /// The number of f64 constants in the pool
+    pub float_count: usize,
+    /// The size in bytes of the pool's concatenated string data
+    pub string_data_size: usize,
+    /// The approximate size in bytes of the pool's contents, including its string data
+    pub byte_size: usize,
+}
+
+impl ConstantPoolStats {
+    fn new(constants: &[ConstantEntry], string_data: &str) -> Self {
+        let mut result = Self {
+            entry_count: constants.len(),
+            string_data_size: string_data.len(),
+            byte_size: mem::size_of_val(constants) + string_data.len(),
+            ..Default::default()
+        };
+
+        for constant in constants {
+            match constant {
+                ConstantEntry::F64(_) => result.float_count += 1,
+                ConstantEntry::I64(_) => result.int_count += 1,
+                ConstantEntry::Str(_) => result.string_count += 1,
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for ConstantPoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} constants (strings: {}, ints: {}, floats: {}), {} bytes",
+            self.entry_count, self.string_count, self.int_count, self.float_count, self.byte_size
+        )
+    }
+}
+
 /// A constant pool produced by the [Parser](crate::Parser) for a Koto script
 ///
 /// A `ConstantPoolBuilder` is used to prepare the pool.
@@ -124,6 +179,45 @@ impl ConstantPool {
     pub fn iter(&self) -> ConstantPoolIterator {
         ConstantPoolIterator::new(self)
     }
+
+    /// Returns statistics describing the pool's contents
+    pub fn stats(&self) -> ConstantPoolStats {
+        ConstantPoolStats::new(&self.constants, &self.string_data)
+    }
+
+    /// Creates a pool containing the provided constants
+    ///
+    /// Unlike [ConstantPoolBuilder], duplicate constants aren't merged, so the position of each
+    /// constant in the input is preserved as its index in the pool.
+    pub fn from_constants<'a>(
+        constants: impl IntoIterator<Item = Constant<'a>>,
+    ) -> Result<Self, ConstantIndexTryFromOutOfRange> {
+        let mut builder = ConstantPoolBuilder::default();
+
+        for constant in constants {
+            ConstantIndex::try_from(builder.constants.len())?;
+
+            let entry = match constant {
+                Constant::F64(n) => {
+                    n.to_bits().hash(&mut builder.hasher);
+                    ConstantEntry::F64(n)
+                }
+                Constant::I64(n) => {
+                    n.hash(&mut builder.hasher);
+                    ConstantEntry::I64(n)
+                }
+                Constant::Str(s) => {
+                    let start = builder.string_data.len();
+                    builder.string_data.push_str(s);
+                    s.hash(&mut builder.hasher);
+                    ConstantEntry::Str(start..builder.string_data.len())
+                }
+            };
+            builder.constants.push(entry);
+        }
+
+        Ok(builder.build())
+    }
 }
 
 /// An iterator that iterates over a [ConstantPool]'s constants
@@ -175,14 +269,55 @@ impl Hash for ConstantPool {
     }
 }
 
+// The pool is serialized as a sequence of constants.
+//
+// Deserialized pools are rebuilt from the sequence, which ensures that the string ranges are valid
+// and that the pool's hash matches the hash of the original pool.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ConstantPool {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ConstantPool {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // An owned equivalent of [Constant], allowing strings to be deserialized from any source
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Constant")]
+        enum OwnedConstant {
+            F64(f64),
+            I64(i64),
+            Str(String),
+        }
+
+        let constants = Vec::<OwnedConstant>::deserialize(deserializer)?;
+
+        ConstantPool::from_constants(constants.iter().map(|constant| match constant {
+            OwnedConstant::F64(n) => Constant::F64(*n),
+            OwnedConstant::I64(n) => Constant::I64(*n),
+            OwnedConstant::Str(s) => Constant::Str(s),
+        }))
+        .map_err(|_| serde::de::Error::custom("too many constants"))
+    }
+}
+
 /// A builder of [ConstantPool]s
 ///
-/// The parser uses this builder to build up a pool of constants.
+/// The parser uses this builder to build up a pool of constants, and it can also be used by
+/// front-ends that produce bytecode directly, see `koto_bytecode::ChunkBuilder`.
+///
+/// Constants are deduplicated, so adding a constant that's already in the pool returns the index
+/// of the existing constant, and each distinct string is only stored once in the pool's string
+/// data. Floats are compared by their bit patterns, with all NaNs sharing a single constant.
+/// `0.0` and `-0.0` are kept as separate constants given that they can produce different results,
+/// e.g. `1 / -0.0` is negative infinity.
 ///
 /// [ConstantPoolBuilder::build]() is called when parsing is finished to produce a finalized
 /// ConstantPool.
 #[derive(Default)]
-pub(crate) struct ConstantPoolBuilder {
+pub struct ConstantPoolBuilder {
     // The list of constants
     constants: Vec<ConstantEntry>,
     // The concatenated string constants
@@ -190,7 +325,12 @@ pub(crate) struct ConstantPoolBuilder {
     // A hash of the pool contents, incrementally built up as constants are added
     hasher: DefaultHasher,
     // A map that keeps track of which string constants have already been added
-    string_map: HashMap<String, ConstantIndex>,
+    //
+    // The map is keyed by each string's hash, with candidate strings being compared against the
+    // pool's string data. This avoids allocating a copy of each string for use as a key.
+    string_map: HashMap<u64, ConstantIndex, BuildHasherDefault<StringHashHasher>>,
+    // String constants that share a hash with an entry in string_map
+    string_hash_collisions: Vec<(u64, ConstantIndex)>,
     // A map that keeps track of which float constants have already been added
     float_map: HashMap<u64, ConstantIndex>,
     // A map that keeps track of which integer constants have already been added
@@ -198,9 +338,28 @@ pub(crate) struct ConstantPoolBuilder {
 }
 
 impl ConstantPoolBuilder {
+    /// Adds a string constant to the pool, returning the constant's index
     pub fn add_string(&mut self, s: &str) -> Result<ConstantIndex, ConstantIndexTryFromOutOfRange> {
-        match self.string_map.get(s) {
-            Some(index) => Ok(*index),
+        self.add_string_with_hash(s, hash_string(s))
+    }
+
+    fn add_string_with_hash(
+        &mut self,
+        s: &str,
+        string_hash: u64,
+    ) -> Result<ConstantIndex, ConstantIndexTryFromOutOfRange> {
+        let existing = match self.string_map.get(&string_hash) {
+            Some(index) if self.get_str(*index) == s => Some(*index),
+            Some(_) => self
+                .string_hash_collisions
+                .iter()
+                .find(|(hash, index)| *hash == string_hash && self.get_str(*index) == s)
+                .map(|(_, index)| *index),
+            None => None,
+        };
+
+        match existing {
+            Some(index) => Ok(index),
             None => {
                 let result = ConstantIndex::try_from(self.constants.len())?;
 
@@ -210,14 +369,22 @@ impl ConstantPoolBuilder {
                 self.constants.push(ConstantEntry::Str(start..end));
                 s.hash(&mut self.hasher);
 
-                self.string_map.insert(s.to_string(), result);
+                match self.string_map.entry(string_hash) {
+                    Entry::Occupied(_) => self.string_hash_collisions.push((string_hash, result)),
+                    Entry::Vacant(entry) => {
+                        entry.insert(result);
+                    }
+                }
 
                 Ok(result)
             }
         }
     }
 
+    /// Adds an f64 constant to the pool, returning the constant's index
     pub fn add_f64(&mut self, n: f64) -> Result<ConstantIndex, ConstantIndexTryFromOutOfRange> {
+        // NaNs can have many different bit patterns, so they're canonicalized to a single NaN
+        let n = if n.is_nan() { f64::NAN } else { n };
         let n_u64 = n.to_bits();
 
         match self.float_map.get(&n_u64) {
@@ -232,6 +399,7 @@ impl ConstantPoolBuilder {
         }
     }
 
+    /// Adds an i64 constant to the pool, returning the constant's index
     pub fn add_i64(&mut self, n: i64) -> Result<ConstantIndex, ConstantIndexTryFromOutOfRange> {
         match self.int_map.get(&n) {
             Some(index) => Ok(*index),
@@ -245,6 +413,9 @@ impl ConstantPoolBuilder {
         }
     }
 
+    /// Returns the string corresponding to the provided index
+    ///
+    /// Warning! Panics if there isn't a string at the provided index
     pub fn get_str(&self, index: ConstantIndex) -> &str {
         match self.constants.get(usize::from(index)) {
             Some(ConstantEntry::Str(range)) => {
@@ -255,6 +426,21 @@ impl ConstantPoolBuilder {
         }
     }
 
+    /// Returns the constant corresponding to the provided index
+    pub fn get(&self, index: ConstantIndex) -> Option<Constant<'_>> {
+        match self.constants.get(usize::from(index))? {
+            ConstantEntry::F64(n) => Some(Constant::F64(*n)),
+            ConstantEntry::I64(n) => Some(Constant::I64(*n)),
+            ConstantEntry::Str(range) => Some(Constant::Str(&self.string_data[range.clone()])),
+        }
+    }
+
+    /// Returns statistics describing the contents of the pool that's being built
+    pub fn stats(&self) -> ConstantPoolStats {
+        ConstantPoolStats::new(&self.constants, &self.string_data)
+    }
+
+    /// Consumes the builder, producing a finalized [ConstantPool]
     pub fn build(self) -> ConstantPool {
         ConstantPool {
             constants: self.constants,
@@ -264,6 +450,46 @@ impl ConstantPoolBuilder {
     }
 }
 
+// Produces a hash for a string constant
+//
+// This is the hash function used by rustc (FxHash), which is much faster than the default hasher
+// for the short strings that make up most constants.
+fn hash_string(s: &str) -> u64 {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    let add_to_hash = |hash: u64, word: u64| (hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+
+    let mut hash = add_to_hash(0, s.len() as u64);
+
+    let mut chunks = s.as_bytes().chunks_exact(8);
+    for chunk in &mut chunks {
+        hash = add_to_hash(hash, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    for byte in chunks.remainder() {
+        hash = add_to_hash(hash, *byte as u64);
+    }
+
+    hash
+}
+
+// A Hasher for the keys of the builder's string map, which are already hashed by hash_string
+#[derive(Default)]
+struct StringHashHasher(u64);
+
+impl Hasher for StringHashHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!("StringHashHasher only supports u64 keys");
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,6 +521,33 @@ mod tests {
         assert_eq!(2, pool.size());
     }
 
+    #[test]
+    fn test_adding_strings_with_colliding_hashes() {
+        let mut builder = ConstantPoolBuilder::default();
+
+        let hash = 42;
+        let s1 = "foo";
+        let s2 = "bar";
+        let s3 = "baz";
+
+        assert_eq!(0, builder.add_string_with_hash(s1, hash).unwrap());
+        assert_eq!(1, builder.add_string_with_hash(s2, hash).unwrap());
+        assert_eq!(2, builder.add_string_with_hash(s3, hash).unwrap());
+
+        // don't duplicate strings that share a hash
+        assert_eq!(0, builder.add_string_with_hash(s1, hash).unwrap());
+        assert_eq!(1, builder.add_string_with_hash(s2, hash).unwrap());
+        assert_eq!(2, builder.add_string_with_hash(s3, hash).unwrap());
+
+        let pool = builder.build();
+
+        assert_eq!(s1, pool.get_str(ConstantIndex::from(0_u8)));
+        assert_eq!(s2, pool.get_str(ConstantIndex::from(1_u8)));
+        assert_eq!(s3, pool.get_str(ConstantIndex::from(2_u8)));
+
+        assert_eq!(3, pool.size());
+    }
+
     #[test]
     fn test_adding_numbers() {
         let mut builder = ConstantPoolBuilder::default();
@@ -347,6 +600,55 @@ mod tests {
         assert_eq!(4, pool.size());
     }
 
+    #[test]
+    fn test_adding_floats_with_different_bit_patterns() {
+        let mut builder = ConstantPoolBuilder::default();
+
+        let nan = builder.add_f64(f64::NAN).unwrap();
+        let other_nan = builder
+            .add_f64(f64::from_bits(f64::NAN.to_bits() | 1))
+            .unwrap();
+        let negative_nan = builder.add_f64(-f64::NAN).unwrap();
+        let zero = builder.add_f64(0.0).unwrap();
+        let negative_zero = builder.add_f64(-0.0).unwrap();
+
+        // All NaNs share a single constant
+        assert_eq!(nan, other_nan);
+        assert_eq!(nan, negative_nan);
+        // 0.0 and -0.0 are distinct
+        assert_ne!(zero, negative_zero);
+        assert_eq!(zero, builder.add_f64(0.0).unwrap());
+        assert_eq!(negative_zero, builder.add_f64(-0.0).unwrap());
+
+        let pool = builder.build();
+        assert!(pool.get_f64(nan).is_nan());
+        assert!(pool.get_f64(negative_zero).is_sign_negative());
+        assert_eq!(3, pool.size());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut builder = ConstantPoolBuilder::default();
+
+        for _ in 0..10 {
+            builder.add_string("foo").unwrap();
+            builder.add_string("bar").unwrap();
+            builder.add_i64(42).unwrap();
+            builder.add_f64(1.5).unwrap();
+        }
+
+        let stats = builder.stats();
+        assert_eq!(stats.entry_count, 4);
+        assert_eq!(stats.string_count, 2);
+        assert_eq!(stats.int_count, 1);
+        assert_eq!(stats.float_count, 1);
+        assert_eq!(stats.string_data_size, 6);
+        assert!(stats.byte_size > stats.string_data_size);
+
+        let pool = builder.build();
+        assert_eq!(pool.stats(), stats);
+    }
+
     #[test]
     fn test_iter() {
         let mut builder = ConstantPoolBuilder::default();
@@ -1,10 +1,12 @@
 use {
-    koto_lexer::{Position, Span},
+    crate::NodeKind,
+    koto_lexer::{Position, Span, Token},
     std::{error, fmt, path::PathBuf},
 };
 
 /// An error that represents a problem with the Parser's internal logic, rather than a user error
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum InternalError {
     ArgumentsParseFailure,
@@ -29,7 +31,8 @@ pub enum InternalError {
 ///
 /// Having these errors separated out from [SyntaxError] is useful when working with interactive
 /// input, where an indented continuation can be started in response to an indentation error.
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum ExpectedIndentation {
     CatchBody,
@@ -49,15 +52,18 @@ pub enum ExpectedIndentation {
 }
 
 /// A syntax error encountered by the [Parser]
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum SyntaxError {
     AsciiEscapeCodeOutOfRange,
+    DisabledNode(NodeKind),
     ExpectedArgsEnd,
     ExpectedAssignmentTarget,
     ExpectedCatchArgument,
     ExpectedCatch,
     ExpectedCloseParen,
+    ExpectedDecoratorId,
     ExpectedElseExpression,
     ExpectedElseIfCondition,
     ExpectedEndOfLine,
@@ -68,7 +74,11 @@ pub enum SyntaxError {
     ExpectedForCondition,
     ExpectedForInKeyword,
     ExpectedForIterable,
+    ExpectedFunctionAfterDecorator,
     ExpectedFunctionArgsEnd,
+    ExpectedGuardCondition,
+    ExpectedGuardElse,
+    ExpectedGuardExit,
     ExpectedIdInImportExpression,
     ExpectedIfCondition,
     ExpectedImportKeywordAfterFrom,
@@ -90,23 +100,32 @@ pub enum SyntaxError {
     ExpectedMetaKey,
     ExpectedMetaId,
     ExpectedNegatableExpression,
+    ExpectedReturnType,
     ExpectedSwitchArmExpression,
     ExpectedSwitchArmExpressionAfterThen,
     ExpectedStringPlaceholderEnd,
     ExpectedThenExpression,
     ExpectedTestName,
+    ExpectedTypeAnnotation,
     ExpectedUntilCondition,
     ExpectedWhileCondition,
+    ExportNotAllowedInExpression,
     IfBlockNotAllowedInThisContext,
     ImportFromExpressionHasTooManyItems,
+    InvalidEllipsisInAssignment,
+    InvalidNumber,
     LexerError,
+    LocalAssignmentInExpression,
     MatchEllipsisOutsideOfNestedPatterns,
     MatchElseNotInLastArm,
+    MaxDepthExceeded,
+    MixedIndentation,
     SelfArgNotInFirstPosition,
     SwitchElseNotInLastArm,
     TooManyNum2Terms,
     TooManyNum4Terms,
     UnexpectedCharInNumericEscapeCode,
+    UnexpectedCharacter,
     UnexpectedElseIndentation,
     UnexpectedElseIfIndentation,
     UnexpectedEscapeInString,
@@ -119,12 +138,15 @@ pub enum SyntaxError {
     UnexpectedTokenAfterExportId,
     UnexpectedTokenInImportExpression,
     UnicodeEscapeCodeOutOfRange,
+    UnknownUnicodeEscapeName,
+    UnterminatedComment,
     UnterminatedNumericEscapeCode,
     UnterminatedString,
 }
 
 /// See [ParserError]
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum ErrorType {
     InternalError(InternalError),
@@ -132,6 +154,33 @@ pub enum ErrorType {
     SyntaxError(SyntaxError),
 }
 
+impl ErrorType {
+    /// Returns a code that identifies the error, e.g. `SyntaxError::ExpectedCloseParen`
+    ///
+    /// The code is made up of the error's category and the name of the specific error,
+    /// and is suitable for use by tooling that needs to identify errors without relying on
+    /// the error messages.
+    pub fn code(&self) -> String {
+        match self {
+            Self::InternalError(error) => format!("InternalError::{:?}", error),
+            Self::ExpectedIndentation(error) => format!("ExpectedIndentation::{:?}", error),
+            Self::SyntaxError(error) => format!("SyntaxError::{:?}", error),
+        }
+    }
+
+    /// Returns the tokens that would have been valid at the location of the error
+    ///
+    /// An empty slice is returned when the error doesn't correspond to a specific set of tokens,
+    /// e.g. when any expression would have been valid.
+    pub fn expected_tokens(&self) -> &'static [Token] {
+        match self {
+            Self::InternalError(_) => &[],
+            Self::ExpectedIndentation(error) => error.expected_tokens(),
+            Self::SyntaxError(error) => error.expected_tokens(),
+        }
+    }
+}
+
 impl From<InternalError> for ErrorType {
     fn from(e: InternalError) -> ErrorType {
         ErrorType::InternalError(e)
@@ -164,17 +213,36 @@ impl fmt::Display for ErrorType {
 
 /// An error that can be produced by the [Parser](crate::Parser)
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParserError {
     /// The error itself
     pub error: ErrorType,
     /// The span in the source string where the error occurred
     pub span: Span,
+    /// The token that the parser encountered when the error occurred, if known
+    pub token: Option<Token>,
 }
 
 impl ParserError {
     /// Initializes a parser error with the specific error type and its associated span
     pub fn new(error: ErrorType, span: Span) -> Self {
-        Self { error, span }
+        Self {
+            error,
+            span,
+            token: None,
+        }
+    }
+
+    /// Returns a code that identifies the error, see [ErrorType::code]
+    pub fn code(&self) -> String {
+        self.error.code()
+    }
+
+    /// Returns the tokens that would have been valid in place of the error's token
+    ///
+    /// See [ErrorType::expected_tokens].
+    pub fn expected_tokens(&self) -> &'static [Token] {
+        self.error.expected_tokens()
     }
 
     /// Returns true if the error was caused by the expectation of indentation
@@ -222,6 +290,18 @@ impl fmt::Display for InternalError {
     }
 }
 
+impl ExpectedIndentation {
+    fn expected_tokens(&self) -> &'static [Token] {
+        use ExpectedIndentation::*;
+
+        match self {
+            FunctionBody | RhsExpression => &[],
+            ThenKeywordOrBlock => &[Token::Then, Token::NewLineIndented],
+            _ => &[Token::NewLineIndented],
+        }
+    }
+}
+
 impl fmt::Display for ExpectedIndentation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use ExpectedIndentation::*;
@@ -247,6 +327,34 @@ impl fmt::Display for ExpectedIndentation {
     }
 }
 
+impl SyntaxError {
+    fn expected_tokens(&self) -> &'static [Token] {
+        use SyntaxError::*;
+
+        match self {
+            ExpectedArgsEnd | ExpectedCloseParen => &[Token::RoundClose],
+            ExpectedCatch => &[Token::Catch],
+            ExpectedCatchArgument | ExpectedForArgs => &[Token::Id, Token::Wildcard],
+            ExpectedDecoratorId
+            | ExpectedExportExpression
+            | ExpectedIdInImportExpression
+            | ExpectedMetaId
+            | ExpectedReturnType
+            | ExpectedTestName
+            | ExpectedTypeAnnotation => &[Token::Id],
+            ExpectedEndOfLine => &[Token::NewLine, Token::NewLineIndented],
+            ExpectedForInKeyword => &[Token::In],
+            ExpectedFunctionArgsEnd => &[Token::Function],
+            ExpectedGuardElse => &[Token::Else],
+            ExpectedImportKeywordAfterFrom => &[Token::Import],
+            ExpectedIndexEnd | ExpectedListEnd => &[Token::SquareClose],
+            ExpectedMapColon => &[Token::Colon],
+            ExpectedMapEnd | ExpectedStringPlaceholderEnd => &[Token::CurlyClose],
+            _ => &[],
+        }
+    }
+}
+
 impl fmt::Display for SyntaxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use SyntaxError::*;
@@ -255,11 +363,15 @@ impl fmt::Display for SyntaxError {
             AsciiEscapeCodeOutOfRange => {
                 f.write_str("Ascii value out of range, the maximum is \\x7f")
             }
+            DisabledNode(kind) => {
+                write!(f, "'{}' has been disabled in the parser's settings", kind)
+            }
             ExpectedArgsEnd => f.write_str("Expected end of arguments ')'"),
             ExpectedAssignmentTarget => f.write_str("Expected target for assignment"),
             ExpectedCatchArgument => f.write_str("Expected argument for catch expression"),
             ExpectedCatch => f.write_str("Expected catch expression after try"),
             ExpectedCloseParen => f.write_str("Expected closing parenthesis"),
+            ExpectedDecoratorId => f.write_str("Expected ID for decorator"),
             ExpectedElseExpression => f.write_str("Expected 'else' expression."),
             ExpectedElseIfCondition => f.write_str("Expected condition for 'else if'."),
             ExpectedEndOfLine => f.write_str("Expected end of line"),
@@ -270,7 +382,14 @@ impl fmt::Display for SyntaxError {
             ExpectedForCondition => f.write_str("Expected condition after 'if' in for loop"),
             ExpectedForInKeyword => f.write_str("Expected in keyword in for loop"),
             ExpectedForIterable => f.write_str("Expected iterable in for loop"),
+            ExpectedFunctionAfterDecorator => f.write_str("Expected function after decorator"),
             ExpectedFunctionArgsEnd => f.write_str("Expected end of function arguments '|'"),
+            ExpectedGuardCondition => f.write_str("Expected condition in guard expression"),
+            ExpectedGuardElse => f.write_str("Expected 'else' after guard condition"),
+            ExpectedGuardExit => f.write_str(
+                "Expected the guard's else branch to end with 'return', 'throw', 'break', \
+                 or 'continue'",
+            ),
             ExpectedIdInImportExpression => f.write_str("Expected ID in import expression"),
             ExpectedIfCondition => f.write_str("Expected condition in if expression"),
             ExpectedImportKeywordAfterFrom => f.write_str("Expected 'import' after 'from' ID"),
@@ -296,6 +415,7 @@ impl fmt::Display for SyntaxError {
             ExpectedMetaKey => f.write_str("Expected meta key after @"),
             ExpectedMetaId => f.write_str("Expected id after @meta"),
             ExpectedNegatableExpression => f.write_str("Expected negatable expression"),
+            ExpectedReturnType => f.write_str("Expected type after '->'"),
             ExpectedStringPlaceholderEnd => {
                 f.write_str("Expected '}' at end of string placeholder")
             }
@@ -305,21 +425,32 @@ impl fmt::Display for SyntaxError {
             }
             ExpectedTestName => f.write_str("Expected a test name"),
             ExpectedThenExpression => f.write_str("Expected 'then' expression."),
+            ExpectedTypeAnnotation => f.write_str("Expected type after ':'"),
             ExpectedUntilCondition => f.write_str("Expected condition in until loop"),
             ExpectedWhileCondition => f.write_str("Expected condition in while loop"),
+            ExportNotAllowedInExpression => f.write_str("export isn't allowed in an expression"),
             IfBlockNotAllowedInThisContext => {
                 f.write_str("Non-inline if expression isn't allowed in this context.")
             }
             ImportFromExpressionHasTooManyItems => {
                 f.write_str("Too many items listed after 'from' in import expression")
             }
+            InvalidEllipsisInAssignment => f.write_str(
+                "Ellipsis can only be used once in a multi-assignment, in first or last position",
+            ),
+            InvalidNumber => f.write_str("Invalid number"),
             LexerError => f.write_str("Found an unexpected token while lexing input"),
+            LocalAssignmentInExpression => {
+                f.write_str("Values can't be assigned to top-level IDs in an expression")
+            }
             MatchEllipsisOutsideOfNestedPatterns => {
                 f.write_str("Ellipsis found outside of nested match patterns")
             }
             MatchElseNotInLastArm => {
                 f.write_str("else can only be used in the last arm in a match expression")
             }
+            MaxDepthExceeded => f.write_str("The maximum nesting depth has been exceeded"),
+            MixedIndentation => f.write_str("Tabs and spaces are mixed in the line's indentation"),
             SwitchElseNotInLastArm => {
                 f.write_str("else can only be used in the last arm in a switch expression")
             }
@@ -329,6 +460,7 @@ impl fmt::Display for SyntaxError {
             UnexpectedCharInNumericEscapeCode => {
                 f.write_str("Unexpected character in numeric escape code")
             }
+            UnexpectedCharacter => f.write_str("Unexpected character"),
             UnexpectedElseIndentation => f.write_str("Unexpected indentation for else block"),
             UnexpectedElseIfIndentation => f.write_str("Unexpected indentation for else if block"),
             UnexpectedEscapeInString => f.write_str("Unexpected escape pattern in string"),
@@ -347,71 +479,148 @@ impl fmt::Display for SyntaxError {
             UnicodeEscapeCodeOutOfRange => {
                 f.write_str("Unicode value out of range, the maximum is \\u{10ffff}")
             }
+            UnknownUnicodeEscapeName => f.write_str("Unknown character name in unicode escape"),
+            UnterminatedComment => f.write_str("Unterminated multi-line comment"),
             UnterminatedNumericEscapeCode => f.write_str("Unterminated numeric escape code"),
             UnterminatedString => f.write_str("Unterminated string"),
         }
     }
 }
 
+/// Options that control how [format_error_with_excerpt] renders errors
+///
+/// The default options produce plain ASCII output without any context lines.
+#[derive(Clone, Debug, Default)]
+pub struct ExcerptOptions {
+    /// Enables ANSI color codes in the output
+    pub color: bool,
+    /// The number of lines to show before and after the lines covered by the error
+    pub context_lines: usize,
+    /// Uses unicode box-drawing characters for the excerpt's gutter instead of ASCII characters
+    pub unicode: bool,
+    /// The maximum number of characters to show for each line of the excerpt
+    ///
+    /// Longer lines are truncated, with the truncation indicated by an ellipsis.
+    pub max_width: Option<usize>,
+}
+
 /// Produces a formatted error string with a corresponding source excerpt
+///
+/// See [ExcerptOptions] for the available formatting options.
 pub fn format_error_with_excerpt(
     message: Option<&str>,
     source_path: &Option<PathBuf>,
     source: &str,
     start_pos: Position,
     end_pos: Position,
+    options: &ExcerptOptions,
 ) -> String {
-    let (excerpt, padding) = {
-        let excerpt_lines = source
-            .lines()
-            .skip((start_pos.line - 1) as usize)
-            .take((end_pos.line - start_pos.line + 1) as usize)
-            .collect::<Vec<_>>();
-
-        let line_numbers = (start_pos.line..=end_pos.line)
-            .map(|n| n.to_string())
-            .collect::<Vec<_>>();
-
-        let number_width = line_numbers.iter().max_by_key(|n| n.len()).unwrap().len();
-
-        let padding = " ".repeat(number_width + 2);
-
-        if start_pos.line == end_pos.line {
-            let mut excerpt = format!(
-                " {:>width$} | {}\n",
-                line_numbers.first().unwrap(),
-                excerpt_lines.first().unwrap(),
-                width = number_width
-            );
-
-            excerpt += &format!(
-                "{}|{}",
-                padding,
-                format!(
-                    "{}{}",
-                    " ".repeat(start_pos.column as usize),
-                    "^".repeat((end_pos.column - start_pos.column) as usize)
-                ),
-            );
-
-            (excerpt, padding)
+    format_frame_with_excerpt(
+        message,
+        None,
+        source_path,
+        source,
+        start_pos,
+        end_pos,
+        options,
+    )
+}
+
+/// Produces a formatted string for a frame in an error's call stack, with a source excerpt
+///
+/// This is the same as [format_error_with_excerpt], with the name of the function that
+/// contains the excerpt being included in the excerpt's position, e.g. `--- 2:3 in 'foo'`.
+pub fn format_frame_with_excerpt(
+    message: Option<&str>,
+    function_name: Option<&str>,
+    source_path: &Option<PathBuf>,
+    source: &str,
+    start_pos: Position,
+    end_pos: Position,
+    options: &ExcerptOptions,
+) -> String {
+    const BLUE: &str = "34";
+    const RED: &str = "31";
+    const BOLD_RED: &str = "1;31";
+
+    let style = |text: &str, code: &str| {
+        if options.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
         } else {
-            let mut excerpt = String::new();
-
-            for (excerpt_line, line_number) in excerpt_lines.iter().zip(line_numbers.iter()) {
-                excerpt += &format!(
-                    " {:>width$} | {}\n",
-                    line_number,
-                    excerpt_line,
-                    width = number_width
-                );
-            }
+            text.to_string()
+        }
+    };
 
-            (excerpt, padding)
+    let (gutter, dashes, ellipsis) = if options.unicode {
+        ("│", "───", "…")
+    } else {
+        ("|", "---", "...")
+    };
+
+    let truncate = |line: &str| match options.max_width {
+        Some(max_width) if line.chars().count() > max_width => {
+            let ellipsis_width = ellipsis.chars().count();
+            let mut result = line
+                .chars()
+                .take(max_width.saturating_sub(ellipsis_width))
+                .collect::<String>();
+            result.push_str(ellipsis);
+            result
         }
+        _ => line.to_string(),
     };
 
-    let position_info = if let Some(path) = source_path {
+    let context_lines = options.context_lines as u32;
+    let source_line_count = source.lines().count() as u32;
+    let first_line = start_pos.line.saturating_sub(context_lines).max(1);
+    let last_line = end_pos
+        .line
+        .max((end_pos.line + context_lines).min(source_line_count));
+
+    let number_width = last_line.to_string().len();
+    let padding = " ".repeat(number_width + 2);
+
+    let mut excerpt_lines = Vec::new();
+
+    for (line_number, line) in (first_line..=last_line).zip(
+        source
+            .lines()
+            .skip((first_line - 1) as usize)
+            .chain(std::iter::repeat("")),
+    ) {
+        excerpt_lines.push(format!(
+            " {} {}",
+            style(
+                &format!("{:>width$} {}", line_number, gutter, width = number_width),
+                BLUE
+            ),
+            truncate(line)
+        ));
+
+        if start_pos.line == end_pos.line && line_number == start_pos.line {
+            let mut start = start_pos.column.saturating_sub(1) as usize;
+            let mut end = end_pos.column.saturating_sub(1) as usize;
+            if let Some(max_width) = options.max_width {
+                end = end.min(max_width);
+                start = start.min(end.saturating_sub(1));
+            }
+
+            excerpt_lines.push(format!(
+                "{}{}",
+                style(&format!("{}{}", padding, gutter), BLUE),
+                style(
+                    &format!(
+                        "{}{}",
+                        " ".repeat(start + 1),
+                        "^".repeat(end.saturating_sub(start))
+                    ),
+                    RED
+                )
+            ));
+        }
+    }
+
+    let mut position_info = if let Some(path) = source_path {
         let display_path = if let Ok(current_dir) = std::env::current_dir() {
             if let Ok(stripped) = path.strip_prefix(current_dir) {
                 stripped.display()
@@ -426,12 +635,24 @@ pub fn format_error_with_excerpt(
     } else {
         format!("{}:{}", start_pos.line, start_pos.column)
     };
+    if let Some(function_name) = function_name {
+        position_info.push_str(&format!(" in '{}'", function_name));
+    }
+
+    // Sources can be unavailable, e.g. when a chunk was compiled without debug info
+    if source.is_empty() {
+        return format!(
+            "{message}\n {position_info}",
+            message = style(message.unwrap_or(""), BOLD_RED),
+            position_info = style(&format!("{} {}", dashes, position_info), BLUE),
+        );
+    }
 
     format!(
-        "{message}\n --- {position_info}\n{padding}|\n{excerpt}",
-        message = message.unwrap_or(""),
-        position_info = position_info,
-        padding = padding,
-        excerpt = excerpt,
+        "{message}\n {position_info}\n{gutter}\n{excerpt}",
+        message = style(message.unwrap_or(""), BOLD_RED),
+        position_info = style(&format!("{} {}", dashes, position_info), BLUE),
+        gutter = style(&format!("{}{}", padding, gutter), BLUE),
+        excerpt = excerpt_lines.join("\n"),
     )
 }
@@ -3,18 +3,31 @@
 #![warn(missing_docs)]
 
 mod ast;
+mod ast_builder;
+mod ast_diff;
 mod constant_index;
 mod constant_pool;
 mod error;
 mod node;
 mod parser;
+mod scope;
+mod syntax_tree;
+mod visitor;
 
 pub use {
     ast::*,
+    ast_builder::{AstBuilder, AstBuilderError},
+    ast_diff::AstChange,
     constant_index::{ConstantIndex, ConstantIndexTryFromOutOfRange},
-    constant_pool::{Constant, ConstantPool},
-    error::{format_error_with_excerpt, ParserError},
-    koto_lexer::{Position, Span},
+    constant_pool::{Constant, ConstantPool, ConstantPoolBuilder, ConstantPoolStats},
+    error::{
+        format_error_with_excerpt, format_frame_with_excerpt, ErrorType, ExcerptOptions,
+        ExpectedIndentation, InternalError, ParserError, SyntaxError,
+    },
+    koto_lexer::{LexedToken, LexerSettings, Position, Span, Token, TokenCategory, TokenStream},
     node::*,
-    parser::Parser,
+    parser::{NodeFilter, Parser, ParserSettings},
+    scope::{IdResolution, LocalId, Scopes},
+    syntax_tree::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, SyntaxTree},
+    visitor::{rewrite, walk, walk_node, Visitor},
 };
@@ -7,6 +7,7 @@ use {
 ///
 /// Nodes refer to each other via [AstIndex]s, see [AstNode](crate::AstNode).
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Node {
     /// An Empty node, used for `()` empty expressions
     Empty,
@@ -142,6 +143,17 @@ pub enum Node {
     /// A function node
     Function(Function),
 
+    /// A function with a list of decorators, e.g. `@memoize |n| n * n`
+    ///
+    /// The decorators are applied in reverse order, with each decorator being called with the
+    /// result of the previous one, starting with the function.
+    Decorated {
+        /// The decorators that should be applied to the function, in the order they appear
+        decorators: Vec<AstIndex>,
+        /// The function being decorated
+        function: AstIndex,
+    },
+
     /// An import expression
     ///
     /// Each import item is defined as a series of [ImportItemNode]s,
@@ -269,6 +281,19 @@ pub enum Node {
         /// The expression that should be debugged
         expression: AstIndex,
     },
+
+    /// An assert expression
+    Assert {
+        /// The stored string of the asserted expression to be used when reporting a failure
+        expression_string: ConstantIndex,
+        /// The expression that should be asserted
+        expression: AstIndex,
+    },
+
+    /// A placeholder for a top-level expression that failed to parse
+    ///
+    /// Only produced by [Parser::parse_with_recovery](crate::Parser::parse_with_recovery).
+    Error,
 }
 
 impl Default for Node {
@@ -277,66 +302,530 @@ impl Default for Node {
     }
 }
 
-impl fmt::Display for Node {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Node {
+    /// Returns the node's kind
+    pub fn kind(&self) -> NodeKind {
         use Node::*;
+
+        match self {
+            Empty => NodeKind::Empty,
+            Nested(_) => NodeKind::Nested,
+            Id(_) => NodeKind::Id,
+            Meta(_, _) => NodeKind::Meta,
+            Lookup(_) => NodeKind::Lookup,
+            BoolTrue => NodeKind::BoolTrue,
+            BoolFalse => NodeKind::BoolFalse,
+            Float(_) => NodeKind::Float,
+            Int(_) => NodeKind::Int,
+            Number0 => NodeKind::Number0,
+            Number1 => NodeKind::Number1,
+            Str(_) => NodeKind::Str,
+            Num2(_) => NodeKind::Num2,
+            Num4(_) => NodeKind::Num4,
+            List(_) => NodeKind::List,
+            Tuple(_) => NodeKind::Tuple,
+            TempTuple(_) => NodeKind::TempTuple,
+            Range { .. } => NodeKind::Range,
+            RangeFrom { .. } => NodeKind::RangeFrom,
+            RangeTo { .. } => NodeKind::RangeTo,
+            RangeFull => NodeKind::RangeFull,
+            Map(_) => NodeKind::Map,
+            MainBlock { .. } => NodeKind::MainBlock,
+            Block(_) => NodeKind::Block,
+            Function(_) => NodeKind::Function,
+            Decorated { .. } => NodeKind::Decorated,
+            NamedCall { .. } => NodeKind::NamedCall,
+            Import { .. } => NodeKind::Import,
+            Assign { .. } => NodeKind::Assign,
+            MultiAssign { .. } => NodeKind::MultiAssign,
+            UnaryOp { .. } => NodeKind::UnaryOp,
+            BinaryOp { .. } => NodeKind::BinaryOp,
+            If(_) => NodeKind::If,
+            Match { .. } => NodeKind::Match,
+            Switch { .. } => NodeKind::Switch,
+            Wildcard => NodeKind::Wildcard,
+            Ellipsis(_) => NodeKind::Ellipsis,
+            For(_) => NodeKind::For,
+            While { .. } => NodeKind::While,
+            Until { .. } => NodeKind::Until,
+            Loop { .. } => NodeKind::Loop,
+            Break => NodeKind::Break,
+            Continue => NodeKind::Continue,
+            Return(_) => NodeKind::Return,
+            Try { .. } => NodeKind::Try,
+            Throw(_) => NodeKind::Throw,
+            Yield { .. } => NodeKind::Yield,
+            Debug { .. } => NodeKind::Debug,
+            Assert { .. } => NodeKind::Assert,
+            Error => NodeKind::Error,
+        }
+    }
+
+    /// Returns the indices of the node's children, in the order that they appear in the source
+    pub fn children(&self) -> Vec<AstIndex> {
+        use Node::*;
+
+        let mut result = Vec::new();
+
+        match self {
+            Empty
+            | Id(_)
+            | Meta(_, _)
+            | BoolTrue
+            | BoolFalse
+            | Number0
+            | Number1
+            | Int(_)
+            | Float(_)
+            | RangeFull
+            | Wildcard
+            | Ellipsis(_)
+            | Break
+            | Continue
+            | Return(None)
+            | Error => {}
+            Nested(index)
+            | RangeFrom { start: index }
+            | RangeTo { end: index, .. }
+            | Loop { body: index }
+            | Return(Some(index))
+            | Throw(index)
+            | Yield(index)
+            | Debug {
+                expression: index, ..
+            }
+            | Assert {
+                expression: index, ..
+            } => result.push(*index),
+            Lookup((lookup_node, next)) => {
+                match lookup_node {
+                    LookupNode::Root(index) | LookupNode::Index(index) => result.push(*index),
+                    LookupNode::Id(_) => {}
+                    LookupNode::Str(string) => string_children(string, &mut result),
+                    LookupNode::Call { args, .. } => result.extend(args),
+                }
+                result.extend(next);
+            }
+            NamedCall { args: nodes, .. }
+            | Num2(nodes)
+            | Num4(nodes)
+            | List(nodes)
+            | Tuple(nodes)
+            | TempTuple(nodes)
+            | MainBlock { body: nodes, .. }
+            | Block(nodes) => result.extend(nodes),
+            Str(string) => string_children(string, &mut result),
+            Range { start, end, .. } => result.extend(&[*start, *end]),
+            Map(entries) => {
+                for (key, value) in entries.iter() {
+                    if let MapKey::Str(string) = key {
+                        string_children(string, &mut result);
+                    }
+                    result.extend(value);
+                }
+            }
+            Function(function) => {
+                result.extend(&function.args);
+                result.push(function.body);
+            }
+            Decorated {
+                decorators,
+                function,
+            } => {
+                result.extend(decorators);
+                result.push(*function);
+            }
+            Import { items, from } => {
+                for item in from.iter().chain(items.iter().flatten()) {
+                    if let ImportItemNode::Str(string) = item {
+                        string_children(string, &mut result);
+                    }
+                }
+            }
+            Assign {
+                target, expression, ..
+            } => result.extend(&[target.target_index, *expression]),
+            MultiAssign {
+                targets,
+                expression,
+            } => {
+                result.extend(targets.iter().map(|target| target.target_index));
+                result.push(*expression);
+            }
+            UnaryOp { value, .. } => result.push(*value),
+            BinaryOp { lhs, rhs, .. } => result.extend(&[*lhs, *rhs]),
+            If(AstIf {
+                condition,
+                then_node,
+                else_if_blocks,
+                else_node,
+            }) => {
+                result.extend(&[*condition, *then_node]);
+                for (else_if_condition, else_if_node) in else_if_blocks.iter() {
+                    result.extend(&[*else_if_condition, *else_if_node]);
+                }
+                result.extend(else_node);
+            }
+            Match { expression, arms } => {
+                result.push(*expression);
+                for arm in arms.iter() {
+                    result.extend(&arm.patterns);
+                    result.extend(arm.condition);
+                    result.push(arm.expression);
+                }
+            }
+            Switch(arms) => {
+                for arm in arms.iter() {
+                    result.extend(arm.condition);
+                    result.push(arm.expression);
+                }
+            }
+            For(AstFor { iterable, body, .. }) => result.extend(&[*iterable, *body]),
+            While { condition, body } | Until { condition, body } => {
+                result.extend(&[*condition, *body])
+            }
+            Try(AstTry {
+                try_block,
+                catch_block,
+                finally_block,
+                ..
+            }) => {
+                result.extend(&[*try_block, *catch_block]);
+                result.extend(finally_block);
+            }
+        }
+
+        result
+    }
+
+    /// Replaces each of the node's child indices with the result of calling `f`
+    ///
+    /// The children are visited in the same order as they're returned by [Node::children].
+    pub fn map_children(&mut self, mut f: impl FnMut(AstIndex) -> AstIndex) {
+        use Node::*;
+
+        let mut map = |index: &mut AstIndex| *index = f(*index);
+
+        match self {
+            Empty
+            | Id(_)
+            | Meta(_, _)
+            | BoolTrue
+            | BoolFalse
+            | Number0
+            | Number1
+            | Int(_)
+            | Float(_)
+            | RangeFull
+            | Wildcard
+            | Ellipsis(_)
+            | Break
+            | Continue
+            | Return(None)
+            | Error => {}
+            Nested(index)
+            | RangeFrom { start: index }
+            | RangeTo { end: index, .. }
+            | Loop { body: index }
+            | Return(Some(index))
+            | Throw(index)
+            | Yield(index)
+            | Debug {
+                expression: index, ..
+            }
+            | Assert {
+                expression: index, ..
+            } => map(index),
+            Lookup((lookup_node, next)) => {
+                match lookup_node {
+                    LookupNode::Root(index) | LookupNode::Index(index) => map(index),
+                    LookupNode::Id(_) => {}
+                    LookupNode::Str(string) => map_string_children(string, &mut map),
+                    LookupNode::Call { args, .. } => args.iter_mut().for_each(&mut map),
+                }
+                next.iter_mut().for_each(map);
+            }
+            NamedCall { args: nodes, .. }
+            | Num2(nodes)
+            | Num4(nodes)
+            | List(nodes)
+            | Tuple(nodes)
+            | TempTuple(nodes)
+            | MainBlock { body: nodes, .. }
+            | Block(nodes) => nodes.iter_mut().for_each(map),
+            Str(string) => map_string_children(string, &mut map),
+            Range { start, end, .. } => {
+                map(start);
+                map(end);
+            }
+            Map(entries) => {
+                for (key, value) in entries.iter_mut() {
+                    if let MapKey::Str(string) = key {
+                        map_string_children(string, &mut map);
+                    }
+                    value.iter_mut().for_each(&mut map);
+                }
+            }
+            Function(function) => {
+                function.args.iter_mut().for_each(&mut map);
+                map(&mut function.body);
+            }
+            Decorated {
+                decorators,
+                function,
+            } => {
+                decorators.iter_mut().for_each(&mut map);
+                map(function);
+            }
+            Import { items, from } => {
+                for item in from.iter_mut().chain(items.iter_mut().flatten()) {
+                    if let ImportItemNode::Str(string) = item {
+                        map_string_children(string, &mut map);
+                    }
+                }
+            }
+            Assign {
+                target, expression, ..
+            } => {
+                map(&mut target.target_index);
+                map(expression);
+            }
+            MultiAssign {
+                targets,
+                expression,
+            } => {
+                for target in targets.iter_mut() {
+                    map(&mut target.target_index);
+                }
+                map(expression);
+            }
+            UnaryOp { value, .. } => map(value),
+            BinaryOp { lhs, rhs, .. } => {
+                map(lhs);
+                map(rhs);
+            }
+            If(AstIf {
+                condition,
+                then_node,
+                else_if_blocks,
+                else_node,
+            }) => {
+                map(condition);
+                map(then_node);
+                for (else_if_condition, else_if_node) in else_if_blocks.iter_mut() {
+                    map(else_if_condition);
+                    map(else_if_node);
+                }
+                else_node.iter_mut().for_each(map);
+            }
+            Match { expression, arms } => {
+                map(expression);
+                for arm in arms.iter_mut() {
+                    arm.patterns.iter_mut().for_each(&mut map);
+                    arm.condition.iter_mut().for_each(&mut map);
+                    map(&mut arm.expression);
+                }
+            }
+            Switch(arms) => {
+                for arm in arms.iter_mut() {
+                    arm.condition.iter_mut().for_each(&mut map);
+                    map(&mut arm.expression);
+                }
+            }
+            For(AstFor { iterable, body, .. })
+            | While {
+                condition: iterable,
+                body,
+            }
+            | Until {
+                condition: iterable,
+                body,
+            } => {
+                map(iterable);
+                map(body);
+            }
+            Try(AstTry {
+                try_block,
+                catch_block,
+                finally_block,
+                ..
+            }) => {
+                map(try_block);
+                map(catch_block);
+                finally_block.iter_mut().for_each(map);
+            }
+        }
+    }
+
+    /// Replaces each of the node's constant indices with the result of calling `f`
+    ///
+    /// This visits the IDs and literals that the node refers to in the [ConstantPool],
+    /// but not the constants of the node's children.
+    ///
+    /// [ConstantPool]: crate::ConstantPool
+    pub fn map_constants(&mut self, mut f: impl FnMut(ConstantIndex) -> ConstantIndex) {
+        use Node::*;
+
+        let mut map = |constant: &mut ConstantIndex| *constant = f(*constant);
+
         match self {
-            Empty => write!(f, "Empty"),
-            Nested(_) => write!(f, "Nested"),
-            Id(_) => write!(f, "Id"),
-            Meta(_, _) => write!(f, "Meta"),
-            Lookup(_) => write!(f, "Lookup"),
-            BoolTrue => write!(f, "BoolTrue"),
-            BoolFalse => write!(f, "BoolFalse"),
-            Float(_) => write!(f, "Float"),
-            Int(_) => write!(f, "Int"),
-            Number0 => write!(f, "Number0"),
-            Number1 => write!(f, "Number1"),
-            Str(_) => write!(f, "Str"),
-            Num2(_) => write!(f, "Num2"),
-            Num4(_) => write!(f, "Num4"),
-            List(_) => write!(f, "List"),
-            Tuple(_) => write!(f, "Tuple"),
-            TempTuple(_) => write!(f, "TempTuple"),
-            Range { .. } => write!(f, "Range"),
-            RangeFrom { .. } => write!(f, "RangeFrom"),
-            RangeTo { .. } => write!(f, "RangeTo"),
-            RangeFull => write!(f, "RangeFull"),
-            Map(_) => write!(f, "Map"),
-            MainBlock { .. } => write!(f, "MainBlock"),
-            Block(_) => write!(f, "Block"),
-            Function(_) => write!(f, "Function"),
-            NamedCall { .. } => write!(f, "NamedCall"),
-            Import { .. } => write!(f, "Import"),
-            Assign { .. } => write!(f, "Assign"),
-            MultiAssign { .. } => write!(f, "MultiAssign"),
-            UnaryOp { .. } => write!(f, "UnaryOp"),
-            BinaryOp { .. } => write!(f, "BinaryOp"),
-            If(_) => write!(f, "If"),
-            Match { .. } => write!(f, "Match"),
-            Switch { .. } => write!(f, "Switch"),
-            Wildcard => write!(f, "Wildcard"),
-            Ellipsis(_) => write!(f, "Ellipsis"),
-            For(_) => write!(f, "For"),
-            While { .. } => write!(f, "While"),
-            Until { .. } => write!(f, "Until"),
-            Loop { .. } => write!(f, "Loop"),
-            Break => write!(f, "Break"),
-            Continue => write!(f, "Continue"),
-            Return(_) => write!(f, "Return"),
-            Try { .. } => write!(f, "Try"),
-            Throw(_) => write!(f, "Throw"),
-            Yield { .. } => write!(f, "Yield"),
-            Debug { .. } => write!(f, "Debug"),
+            Id(constant)
+            | Meta(_, Some(constant))
+            | Ellipsis(Some(constant))
+            | Int(constant)
+            | Float(constant)
+            | NamedCall { id: constant, .. }
+            | Debug {
+                expression_string: constant,
+                ..
+            }
+            | Assert {
+                expression_string: constant,
+                ..
+            } => map(constant),
+            Str(string) => map_string_constants(string, &mut map),
+            Lookup((lookup_node, _)) => match lookup_node {
+                LookupNode::Id(constant) => map(constant),
+                LookupNode::Str(string) => map_string_constants(string, &mut map),
+                LookupNode::Root(_) | LookupNode::Index(_) | LookupNode::Call { .. } => {}
+            },
+            Map(entries) => {
+                for (key, _) in entries.iter_mut() {
+                    match key {
+                        MapKey::Id(constant) | MapKey::Meta(_, Some(constant)) => map(constant),
+                        MapKey::Str(string) => map_string_constants(string, &mut map),
+                        MapKey::Meta(_, None) => {}
+                    }
+                }
+            }
+            Function(function) => {
+                function.arg_types.iter_mut().flatten().for_each(&mut map);
+                function.return_type.iter_mut().for_each(&mut map);
+                function.accessed_non_locals.iter_mut().for_each(&mut map);
+            }
+            Import { items, from } => {
+                for item in from.iter_mut().chain(items.iter_mut().flatten()) {
+                    match item {
+                        ImportItemNode::Id(constant) => map(constant),
+                        ImportItemNode::Str(string) => map_string_constants(string, &mut map),
+                    }
+                }
+            }
+            For(AstFor { args, .. }) => args.iter_mut().flatten().for_each(map),
+            Try(AstTry { catch_arg, .. }) => catch_arg.iter_mut().for_each(map),
+            _ => {}
         }
     }
 }
 
+fn string_children(string: &AstString, result: &mut Vec<AstIndex>) {
+    for node in string.nodes.iter() {
+        if let StringNode::Expr(index) = node {
+            result.push(*index);
+        }
+    }
+}
+
+fn map_string_constants(string: &mut AstString, map: &mut impl FnMut(&mut ConstantIndex)) {
+    for node in string.nodes.iter_mut() {
+        if let StringNode::Literal(constant) = node {
+            map(constant);
+        }
+    }
+}
+
+fn map_string_children(string: &mut AstString, map: &mut impl FnMut(&mut AstIndex)) {
+    for node in string.nodes.iter_mut() {
+        if let StringNode::Expr(index) = node {
+            map(index);
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind())
+    }
+}
+
+/// The kind of a [Node], without any of the node's data
+///
+/// See [ParserSettings::node_filter](crate::ParserSettings::node_filter).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(missing_docs)]
+pub enum NodeKind {
+    Empty,
+    Nested,
+    Id,
+    Meta,
+    Lookup,
+    BoolTrue,
+    BoolFalse,
+    Float,
+    Int,
+    Number0,
+    Number1,
+    Str,
+    Num2,
+    Num4,
+    List,
+    Tuple,
+    TempTuple,
+    Range,
+    RangeFrom,
+    RangeTo,
+    RangeFull,
+    Map,
+    MainBlock,
+    Block,
+    Function,
+    Decorated,
+    NamedCall,
+    Import,
+    Assign,
+    MultiAssign,
+    UnaryOp,
+    BinaryOp,
+    If,
+    Match,
+    Switch,
+    Wildcard,
+    Ellipsis,
+    For,
+    While,
+    Until,
+    Loop,
+    Break,
+    Continue,
+    Return,
+    Try,
+    Throw,
+    Yield,
+    Debug,
+    Assert,
+    Error,
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 /// A function definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     /// The function's arguments
     pub args: Vec<AstIndex>,
+    /// Type annotations for the function's arguments, e.g. `|x: Number|`
+    ///
+    /// If any of the arguments are annotated then there will be an entry for each argument,
+    /// otherwise the list will be empty.
+    pub arg_types: Vec<Option<ConstantIndex>>,
+    /// The function's annotated return type, e.g. `|x| -> String`
+    pub return_type: Option<ConstantIndex>,
     /// The number of locally assigned values
     ///
     /// Used by the compiler when reserving registers for local values at the start of the frame.
@@ -361,6 +850,7 @@ pub struct Function {
 
 /// A string definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AstString {
     /// Indicates if single or double quotation marks were used
     pub quotation_mark: QuotationMark,
@@ -373,6 +863,7 @@ pub struct AstString {
 
 /// A node in a string definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StringNode {
     /// A string literal
     Literal(ConstantIndex),
@@ -382,6 +873,7 @@ pub enum StringNode {
 
 /// A for loop definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AstFor {
     /// The optional arguments that capture each iteration's output values
     pub args: Vec<Option<ConstantIndex>>,
@@ -393,6 +885,7 @@ pub struct AstFor {
 
 /// An if expression definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AstIf {
     /// The if expression's condition
     pub condition: AstIndex,
@@ -406,6 +899,7 @@ pub struct AstIf {
 
 /// An operation used in UnaryOp expressions
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum AstUnaryOp {
     Negate,
@@ -414,6 +908,7 @@ pub enum AstUnaryOp {
 
 /// An operation used in BinaryOp expressions
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum AstBinaryOp {
     Add,
@@ -429,11 +924,13 @@ pub enum AstBinaryOp {
     GreaterOrEqual,
     And,
     Or,
+    In,
     Pipe,
 }
 
 /// A try expression definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AstTry {
     /// The block that's wrapped by the try
     pub try_block: AstIndex,
@@ -447,6 +944,7 @@ pub struct AstTry {
 
 /// The operation used in an assignment expression
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AssignOp {
     /// +=
     Add,
@@ -464,6 +962,7 @@ pub enum AssignOp {
 
 /// The scope for an assignment
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Scope {
     /// The export scope
     ///
@@ -490,6 +989,7 @@ pub enum Scope {
 ///  |  ^ Id (bar)
 ///  ^ Root (foo)
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LookupNode {
     /// The root of the lookup chain
     Root(AstIndex),
@@ -517,6 +1017,7 @@ pub enum LookupNode {
 
 /// An assignment target with its associated scope
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AssignTarget {
     /// The target of the assignment
     pub target_index: AstIndex,
@@ -526,6 +1027,7 @@ pub struct AssignTarget {
 
 /// An arm in a match expression
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MatchArm {
     /// A series of match patterns
     pub patterns: Vec<AstIndex>,
@@ -541,6 +1043,7 @@ pub struct MatchArm {
 
 /// An arm in a switch expression
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SwitchArm {
     /// An optional condition for the switch arm
     ///
@@ -552,6 +1055,7 @@ pub struct SwitchArm {
 
 /// A meta key
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum MetaKeyId {
     /// @+
@@ -588,10 +1092,23 @@ pub enum MetaKeyId {
     /// @type
     Type,
 
+    /// @version
+    Version,
+    /// @requires
+    Requires,
+    /// @reload
+    Reload,
+
     /// @tests
     Tests,
     /// @test test_name
     Test,
+    /// @test_expected_to_fail test_name
+    TestExpectedToFail,
+    /// @test_skip test_name
+    TestSkip,
+    /// @test_timeout test_name
+    TestTimeout,
     /// @pre_test
     PreTest,
     /// @post_test
@@ -621,6 +1138,7 @@ impl TryFrom<u8> for MetaKeyId {
 
 /// A map key definition
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MapKey {
     /// An identifier
     Id(ConstantIndex),
@@ -634,6 +1152,7 @@ pub enum MapKey {
 
 /// The type of quotation mark used in a string literal
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs)]
 pub enum QuotationMark {
     Double,
@@ -642,6 +1161,7 @@ pub enum QuotationMark {
 
 /// A node in an import item, see [Node::Import]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImportItemNode {
     /// An identifier node
     ///
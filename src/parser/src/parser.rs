@@ -1,9 +1,16 @@
 #![cfg_attr(feature = "panic_on_parser_error", allow(unreachable_code))]
 
 use {
-    crate::{constant_pool::ConstantPoolBuilder, error::*, *},
-    koto_lexer::{Lexer, Span, Token},
-    std::{collections::HashSet, iter::FromIterator, str::FromStr},
+    crate::{error::*, scope::FrameResolution, *},
+    koto_lexer::{Lexer, LexerError, Span, Token},
+    std::{
+        borrow::Cow,
+        collections::HashSet,
+        convert::TryFrom,
+        iter::{FromIterator, Peekable},
+        str::{Chars, FromStr},
+    },
+    unicode_width::UnicodeWidthChar,
 };
 
 macro_rules! make_internal_error {
@@ -26,7 +33,20 @@ macro_rules! internal_error {
 
 macro_rules! parser_error {
     ($error:ident, $parser:expr, $error_type:ident) => {{
-        let error = ParserError::new($error_type::$error.into(), $parser.current_span());
+        let mut error = ParserError::new($error_type::$error.into(), $parser.current_span());
+        error.token = $parser.lexer.current_token();
+
+        #[cfg(feature = "panic_on_parser_error")]
+        panic!("{}", error);
+
+        #[cfg(not(feature = "panic_on_parser_error"))]
+        Err(error)
+    }};
+}
+
+macro_rules! parser_error_with_span {
+    ($error:ident, $span:expr, $error_type:ident) => {{
+        let error = ParserError::new($error_type::$error.into(), $span);
 
         #[cfg(feature = "panic_on_parser_error")]
         panic!("{}", error);
@@ -48,6 +68,12 @@ macro_rules! syntax_error {
     }};
 }
 
+macro_rules! syntax_error_with_span {
+    ($error:ident, $span:expr) => {{
+        parser_error_with_span!($error, $span, SyntaxError)
+    }};
+}
+
 enum ConstantIndexOrWildcard {
     Index(ConstantIndex),
     Wildcard,
@@ -59,6 +85,9 @@ struct Frame {
     contains_yield: bool,
     // IDs that have been assigned within the current frame
     ids_assigned_in_scope: HashSet<ConstantIndex>,
+    // The frame's locals in the order that they were first assigned, along with their defining
+    // nodes, with a local's position in the list being its slot
+    locals: Vec<(ConstantIndex, Option<AstIndex>)>,
     // IDs and lookup roots which have been accessed without being locally assigned previously
     accessed_non_locals: HashSet<ConstantIndex>,
     // While an expression is being parsed we keep track of lhs assignments and rhs accesses.
@@ -66,6 +95,17 @@ struct Frame {
     // assigned are then counted as non-local accesses.
     pending_accesses: HashSet<ConstantIndex>,
     pending_assignments: HashSet<ConstantIndex>,
+    // When performing scope analysis (see `Parser::parse_with_scopes`), the nodes that refer to
+    // IDs are also tracked, and then resolved along with the IDs in `finish_expression`.
+    track_nodes: bool,
+    pending_access_nodes: Vec<(AstIndex, ConstantIndex)>,
+    pending_assignment_nodes: Vec<(AstIndex, ConstantIndex)>,
+    // Nodes in nested frames that refer to IDs that weren't assigned in the nested frame
+    pending_capture_nodes: Vec<(AstIndex, ConstantIndex)>,
+    // Nodes that refer to IDs that aren't assigned in this frame
+    non_local_nodes: Vec<(AstIndex, ConstantIndex)>,
+    // The resolutions of the nodes that refer to IDs in this frame
+    resolutions: Vec<(AstIndex, FrameResolution)>,
 }
 
 impl Frame {
@@ -73,15 +113,50 @@ impl Frame {
         self.ids_assigned_in_scope.len()
     }
 
+    // Returns the local's slot if the ID has been assigned in the frame
+    fn local_slot(&self, id: ConstantIndex) -> Option<usize> {
+        if self.ids_assigned_in_scope.contains(&id) {
+            self.locals.iter().position(|(local, _)| *local == id)
+        } else {
+            None
+        }
+    }
+
+    // Assigns an ID immediately rather than at the end of the current expression,
+    // returning the local's slot
+    //
+    // If the ID doesn't have its own node then the definition can be provided later with
+    // `set_definitions`, once the containing node has been added to the Ast.
+    fn add_local(&mut self, id: ConstantIndex, definition: Option<AstIndex>) -> usize {
+        if self.ids_assigned_in_scope.insert(id) {
+            self.locals.push((id, definition));
+            self.locals.len() - 1
+        } else {
+            self.local_slot(id).unwrap_or_default()
+        }
+    }
+
+    // Sets the definition for locals that were added without a node
+    fn set_definitions(&mut self, ids: &[ConstantIndex], node: AstIndex) {
+        for (id, definition) in self.locals.iter_mut() {
+            if definition.is_none() && ids.contains(id) {
+                *definition = Some(node);
+            }
+        }
+    }
+
     // Non-locals accessed in a nested frame need to be declared as also accessed in this
     // frame. This ensures that captures from the outer frame will be available when
     // creating the nested inner scope.
-    fn add_nested_accessed_non_locals(&mut self, nested_frame: &Frame) {
+    fn add_nested_accessed_non_locals(&mut self, nested_frame: &mut Frame) {
         for non_local in nested_frame.accessed_non_locals.iter() {
             if !self.pending_assignments.contains(non_local) {
                 self.add_id_access(*non_local);
             }
         }
+
+        self.pending_capture_nodes
+            .append(&mut nested_frame.non_local_nodes);
     }
 
     fn add_id_access(&mut self, id: ConstantIndex) {
@@ -96,15 +171,72 @@ impl Frame {
         self.pending_assignments.insert(id);
     }
 
+    fn add_access_node(&mut self, node: AstIndex, id: ConstantIndex) {
+        if self.track_nodes {
+            self.pending_access_nodes.push((node, id));
+        }
+    }
+
+    // The node will have previously been added as an access, e.g. when parsing `x = 1`, `x` is
+    // initially parsed as an access before the `=` is reached.
+    fn add_assignment_node(&mut self, node: AstIndex, id: ConstantIndex) {
+        if self.track_nodes {
+            self.pending_access_nodes
+                .retain(|(access_node, _)| *access_node != node);
+            self.pending_assignment_nodes.push((node, id));
+        }
+    }
+
+    // Assigns an ID immediately with its defining node, see `add_local`
+    fn add_local_node(&mut self, node: AstIndex, id: ConstantIndex) {
+        let slot = self.add_local(id, Some(node));
+        if self.track_nodes {
+            self.resolutions.push((node, FrameResolution::Local(slot)));
+        }
+    }
+
+    // Adds a node that refers to a global, e.g. an exported ID
+    fn add_global_node(&mut self, node: AstIndex) {
+        if self.track_nodes {
+            self.resolutions.push((node, FrameResolution::Global));
+        }
+    }
+
+    // Finishes the current expression, resolving the IDs (and nodes when performing scope
+    // analysis) that were accessed and assigned in the expression
     fn finish_expression(&mut self) {
+        // Accesses refer to locals that were assigned before the current expression
+        for (node, id) in std::mem::take(&mut self.pending_access_nodes) {
+            match self.local_slot(id) {
+                Some(slot) => self.resolutions.push((node, FrameResolution::Local(slot))),
+                None => self.non_local_nodes.push((node, id)),
+            }
+        }
+
         for id in self.pending_accesses.drain() {
             if !self.ids_assigned_in_scope.contains(&id) {
                 self.accessed_non_locals.insert(id);
             }
         }
 
-        self.ids_assigned_in_scope
-            .extend(self.pending_assignments.drain());
+        for (node, id) in std::mem::take(&mut self.pending_assignment_nodes) {
+            self.add_local_node(node, id);
+        }
+
+        for id in std::mem::take(&mut self.pending_assignments) {
+            self.add_local(id, None);
+        }
+
+        // Nested functions can capture values that are assigned in the current expression,
+        // e.g. `f = |n| if n > 0 then f n - 1`
+        for (node, id) in std::mem::take(&mut self.pending_capture_nodes) {
+            match self.local_slot(id) {
+                Some(slot) => self
+                    .resolutions
+                    .push((node, FrameResolution::Capture(slot))),
+                None => self.non_local_nodes.push((node, id)),
+            }
+        }
     }
 }
 
@@ -209,30 +341,265 @@ impl ExpressionContext {
     }
 }
 
+/// Settings used to control the behaviour of the [Parser], see [Parser::parse_with_settings]
+#[derive(Clone, Debug)]
+pub struct ParserSettings {
+    /// The maximum nesting depth of expressions
+    ///
+    /// The parser is recursive, so deeply nested expressions could otherwise exhaust the stack.
+    /// A [SyntaxError::MaxDepthExceeded] error is returned when the limit is exceeded.
+    ///
+    /// The depth is increased at each of the parser's recursive entry points, so a single level
+    /// of nesting in a script (e.g. a nested call or list) will typically count more than once.
+    /// The default limit keeps the parser's stack usage below 2MB (the default stack size for
+    /// spawned threads) in debug builds.
+    ///
+    /// Default: 128
+    pub max_depth: usize,
+    /// Controls which kinds of nodes are allowed in the script
+    ///
+    /// This allows language constructs to be disabled, e.g. when Koto is being used as a
+    /// restricted DSL. A [SyntaxError::DisabledNode] error is returned when a node is encountered
+    /// that isn't allowed.
+    ///
+    /// Default: [NodeFilter::AllowAll]
+    pub node_filter: NodeFilter,
+    /// The settings used by the lexer, controlling how tabs in indentation are handled
+    ///
+    /// When [LexerSettings::reject_mixed_indentation] is enabled, a
+    /// [SyntaxError::MixedIndentation] error is returned for lines that mix tabs and spaces.
+    ///
+    /// Default: [LexerSettings::default]
+    pub lexer: LexerSettings,
+}
+
+impl Default for ParserSettings {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            node_filter: NodeFilter::AllowAll,
+            lexer: LexerSettings::default(),
+        }
+    }
+}
+
+/// Describes the kinds of nodes that are allowed by the [Parser], see [ParserSettings::node_filter]
+///
+/// [NodeKind::MainBlock] is always allowed.
+#[derive(Clone, Debug)]
+pub enum NodeFilter {
+    /// All kinds of nodes are allowed
+    AllowAll,
+    /// Only the listed kinds of nodes are allowed
+    Allow(Vec<NodeKind>),
+    /// The listed kinds of nodes are disallowed
+    Deny(Vec<NodeKind>),
+}
+
+impl NodeFilter {
+    /// Returns true if the kind of node is allowed by the filter
+    pub fn allows(&self, kind: NodeKind) -> bool {
+        match self {
+            _ if kind == NodeKind::MainBlock => true,
+            Self::AllowAll => true,
+            Self::Allow(allowed) => allowed.contains(&kind),
+            Self::Deny(denied) => !denied.contains(&kind),
+        }
+    }
+}
+
 /// Koto's parser
 pub struct Parser<'source> {
     ast: Ast,
     constants: ConstantPoolBuilder,
     lexer: Lexer<'source>,
     frame_stack: Vec<Frame>,
+    // When recovering from errors, errors in the main block are collected here
+    // rather than ending the parse, see `parse_with_recovery`.
+    recovered_errors: Option<Vec<ParserError>>,
+    // The results of scope analysis, see `parse_with_scopes`
+    scopes: Option<Scopes>,
+    settings: ParserSettings,
+    // The current nesting depth, see ParserSettings::max_depth
+    depth: usize,
 }
 
 impl<'source> Parser<'source> {
     /// Takes in a source script, and produces an Ast
     pub fn parse(source: &'source str) -> Result<Ast, ParserError> {
+        Self::parse_with_settings(source, ParserSettings::default())
+    }
+
+    /// Takes in a source script and the settings that should be used while parsing,
+    /// and produces an Ast
+    pub fn parse_with_settings(
+        source: &'source str,
+        settings: ParserSettings,
+    ) -> Result<Ast, ParserError> {
+        let mut parser = Parser::new(source, false);
+        parser.lexer = Lexer::with_settings(source, settings.lexer);
+        parser.settings = settings;
+
+        let main_block = parser.parse_main_block()?;
+        parser.ast.set_entry_point(main_block);
+        parser.ast.set_constants(parser.constants.build());
+
+        Ok(parser.ast)
+    }
+
+    /// Takes in a source script, and produces an Ast along with all of the errors in the script
+    ///
+    /// When a top-level expression fails to parse, the error is recorded and parsing continues
+    /// from the next line that isn't indented. The failed expression is replaced in the main block
+    /// by a [Node::Error] placeholder, with the span of the error.
+    ///
+    /// If no errors are returned, then the Ast matches the Ast that would be produced by
+    /// [Parser::parse], otherwise the Ast is partial and isn't suitable for compilation.
+    pub fn parse_with_recovery(source: &'source str) -> (Ast, Vec<ParserError>) {
+        let mut parser = Parser::new(source, true);
+
+        match parser.parse_main_block() {
+            Ok(main_block) => parser.ast.set_entry_point(main_block),
+            Err(error) => parser
+                .recovered_errors
+                .get_or_insert_with(Vec::new)
+                .push(error),
+        }
+        parser.ast.set_constants(parser.constants.build());
+
+        (parser.ast, parser.recovered_errors.unwrap_or_default())
+    }
+
+    /// Takes in a source script, and produces an Ast along with the results of scope analysis
+    ///
+    /// The returned [Scopes] map each node that refers to an ID to the ID's resolution, which
+    /// allows tools to find the definition of a local value, or all of its references, without
+    /// needing to reimplement the parser's scoping rules.
+    pub fn parse_with_scopes(source: &'source str) -> Result<(Ast, Scopes), ParserError> {
+        let mut parser = Parser::new(source, false);
+        parser.scopes = Some(Scopes::default());
+
+        let main_block = parser.parse_main_block()?;
+        parser.ast.set_entry_point(main_block);
+        parser.ast.set_constants(parser.constants.build());
+
+        let scopes = parser.scopes.take().unwrap_or_default();
+        Ok((parser.ast, scopes))
+    }
+
+    /// Takes in a source string containing a single expression, and produces an Ast
+    ///
+    /// The Ast's entry point is the parsed expression (see [Ast::entry_point_index]) rather than
+    /// a main block, which is useful when evaluating short snippets of code,
+    /// e.g. `size.width * 2`.
+    ///
+    /// Statements like `export` are rejected, as are assignments to top-level IDs, given that
+    /// there's no main block to hold the values. IDs can still be assigned in nested functions.
+    pub fn parse_expression(source: &'source str) -> Result<Ast, ParserError> {
+        let mut parser = Parser::new(source, false);
+
+        let expression = parser
+            .parse_standalone_expression()
+            .map_err(|error| parser.check_for_lexer_error(error))?;
+        parser.ast.set_entry_point(expression);
+        parser.ast.set_constants(parser.constants.build());
+
+        Ok(parser.ast)
+    }
+
+    fn new(source: &'source str, recover_from_errors: bool) -> Self {
         let capacity_guess = source.len() / 4;
-        let mut parser = Parser {
+        Parser {
             ast: Ast::with_capacity(capacity_guess),
             constants: ConstantPoolBuilder::default(),
             lexer: Lexer::new(source),
             frame_stack: Vec::new(),
+            recovered_errors: if recover_from_errors {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            scopes: None,
+            settings: ParserSettings::default(),
+            depth: 0,
+        }
+    }
+
+    // Replaces an error with a more precise error if it was caused by a lexer error
+    //
+    // Input that the lexer can't make sense of is emitted as Token::Error, which the parser then
+    // fails to parse. When the failure happens the error token will either be the current token,
+    // or the next token after whitespace on the same line.
+    fn check_for_lexer_error(&mut self, error: ParserError) -> ParserError {
+        if self.lexer.current_token() != Some(Token::Error) {
+            let mut peek_count = 0;
+            while matches!(self.peek_token_n(peek_count), Some(token) if token.is_whitespace()) {
+                peek_count += 1;
+            }
+            if self.peek_token_n(peek_count) != Some(Token::Error) {
+                return error;
+            }
+            for _ in 0..=peek_count {
+                self.consume_token();
+            }
+        }
+
+        let span = self.current_span();
+        let (syntax_error, span) = match self.lexer.current_error() {
+            Some(LexerError::UnexpectedCharacter) => (SyntaxError::UnexpectedCharacter, span),
+            Some(LexerError::UnterminatedString { quote }) => {
+                (SyntaxError::UnterminatedString, quote)
+            }
+            Some(LexerError::UnterminatedComment) => {
+                // Refer to the comment's opening `#-`
+                let mut end = span.start;
+                end.column += 2;
+                end.offset += 2;
+                (
+                    SyntaxError::UnterminatedComment,
+                    Span {
+                        start: span.start,
+                        end,
+                    },
+                )
+            }
+            Some(LexerError::InvalidNumber) => (SyntaxError::InvalidNumber, span),
+            Some(LexerError::MixedIndentation) => {
+                // Refer to the indentation following the newline
+                let start = Position {
+                    line: span.end.line,
+                    column: 1,
+                    offset: span.end.offset - (span.end.column - 1),
+                };
+                (
+                    SyntaxError::MixedIndentation,
+                    Span {
+                        start,
+                        end: span.end,
+                    },
+                )
+            }
+            None => return error,
         };
 
-        let main_block = parser.parse_main_block()?;
-        parser.ast.set_entry_point(main_block);
-        parser.ast.set_constants(parser.constants.build());
+        let mut result = ParserError::new(syntax_error.into(), span);
+        result.token = Some(Token::Error);
+        result
+    }
 
-        Ok(parser.ast)
+    // Calls the function with the nesting depth increased, see ParserSettings::max_depth
+    fn with_nested_depth<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParserError>,
+    ) -> Result<T, ParserError> {
+        if self.depth >= self.settings.max_depth {
+            return syntax_error!(MaxDepthExceeded, self);
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
     }
 
     fn frame(&self) -> Result<&Frame, ParserError> {
@@ -255,8 +622,40 @@ impl<'source> Parser<'source> {
         }
     }
 
+    // Finishes the current expression, see Frame::finish_expression
+    fn finish_expression(&mut self) -> Result<(), ParserError> {
+        self.frame_mut()?.finish_expression();
+        Ok(())
+    }
+
+    fn new_frame(&self) -> Frame {
+        Frame {
+            track_nodes: self.scopes.is_some(),
+            ..Default::default()
+        }
+    }
+
+    // Adds the resolutions from a frame that has been popped from the frame stack to the results
+    // of scope analysis, once the node that contains the frame is known
+    //
+    // Nodes in the outermost frame that refer to IDs that weren't assigned are resolved as globals,
+    // otherwise they'll have been passed on to the enclosing frame as captures.
+    fn add_frame_to_scopes(&mut self, mut frame: Frame, frame_node: AstIndex) {
+        if let Some(scopes) = self.scopes.as_mut() {
+            if self.frame_stack.is_empty() {
+                frame.resolutions.extend(
+                    frame
+                        .non_local_nodes
+                        .drain(..)
+                        .map(|(node, _)| (node, FrameResolution::Global)),
+                );
+            }
+            scopes.add_frame(frame_node, &frame.locals, frame.resolutions);
+        }
+    }
+
     fn parse_main_block(&mut self) -> Result<AstIndex, ParserError> {
-        self.frame_stack.push(Frame::default());
+        self.frame_stack.push(self.new_frame());
 
         let start_span = self.current_span();
 
@@ -264,33 +663,28 @@ impl<'source> Parser<'source> {
         context.expected_indentation = Indentation::Equal(0);
 
         let mut body = Vec::new();
-        while self.peek_next_token(&context).is_some() {
-            self.consume_until_next_token(&mut context);
-
-            if let Some(expression) = self.parse_line(&mut ExpressionContext::permissive())? {
-                body.push(expression);
-
-                match self.peek_next_token_on_same_line() {
-                    Some(Token::NewLine) | Some(Token::NewLineIndented) => continue,
-                    None => break,
-                    _ => {
-                        self.consume_next_token_on_same_line();
-                        return syntax_error!(UnexpectedToken, self);
+        loop {
+            let frame_count = self.frame_stack.len();
+
+            match self.parse_main_block_expression(&mut context) {
+                Ok(Some(expression)) => body.push(expression),
+                Ok(None) => break,
+                Err(error) => {
+                    let error = self.check_for_lexer_error(error);
+                    match self.recovered_errors.as_mut() {
+                        Some(recovered_errors) => {
+                            let span = error.span;
+                            recovered_errors.push(error);
+                            self.frame_stack.truncate(frame_count);
+                            self.skip_to_next_unindented_line();
+                            body.push(self.push_node_with_span(Node::Error, span)?);
+                        }
+                        None => return Err(error),
                     }
                 }
-            } else {
-                self.lexer.next();
-                return syntax_error!(ExpectedExpressionInMainBlock, self);
             }
         }
 
-        // Check that all tokens were consumed
-        self.consume_until_next_token(&mut ExpressionContext::permissive());
-        if self.peek_token().is_some() {
-            self.consume_token(); // Consume the token so that the error span is correct
-            return syntax_error!(UnexpectedToken, self);
-        }
-
         let result = self.push_node_with_start_span(
             Node::MainBlock {
                 body,
@@ -299,13 +693,103 @@ impl<'source> Parser<'source> {
             start_span,
         )?;
 
-        self.frame_stack.pop();
+        if let Some(frame) = self.frame_stack.pop() {
+            self.add_frame_to_scopes(frame, result);
+        }
         Ok(result)
     }
 
+    // Parses an expression in the main block
+    //
+    // None is returned when the end of the script has been reached.
+    fn parse_main_block_expression(
+        &mut self,
+        context: &mut ExpressionContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        if self.peek_next_token(context).is_none() {
+            // Check that all tokens were consumed
+            self.consume_until_next_token(&mut ExpressionContext::permissive());
+            if self.peek_token().is_some() {
+                self.consume_token(); // Consume the token so that the error span is correct
+                return syntax_error!(UnexpectedToken, self);
+            }
+            return Ok(None);
+        }
+
+        self.consume_until_next_token(context);
+
+        if let Some(expression) = self.parse_line(&mut ExpressionContext::permissive())? {
+            match self.peek_next_token_on_same_line() {
+                Some(Token::NewLine) | Some(Token::NewLineIndented) | None => Ok(Some(expression)),
+                _ => {
+                    self.consume_next_token_on_same_line();
+                    syntax_error!(UnexpectedToken, self)
+                }
+            }
+        } else {
+            self.lexer.next();
+            syntax_error!(ExpectedExpressionInMainBlock, self)
+        }
+    }
+
+    // Parses a single expression, see `Parser::parse_expression`
+    fn parse_standalone_expression(&mut self) -> Result<AstIndex, ParserError> {
+        self.frame_stack.push(self.new_frame());
+
+        if self
+            .consume_until_next_token(&mut ExpressionContext::permissive())
+            .is_none()
+        {
+            return syntax_error!(ExpectedExpression, self);
+        }
+
+        if self.peek_token() == Some(Token::Export) {
+            self.consume_token();
+            return syntax_error!(ExportNotAllowedInExpression, self);
+        }
+
+        let expression = match self.parse_line(&mut ExpressionContext::permissive())? {
+            Some(expression) => expression,
+            None => {
+                self.consume_token();
+                return syntax_error!(ExpectedExpression, self);
+            }
+        };
+
+        if self.frame()?.local_count() > 0 {
+            let span = *self.ast.span(self.ast.node(expression).span);
+            return syntax_error_with_span!(LocalAssignmentInExpression, span);
+        }
+
+        // Check that the expression is the only thing in the source
+        self.consume_until_next_token(&mut ExpressionContext::permissive());
+        if self.peek_token().is_some() {
+            self.consume_token(); // Consume the token so that the error span is correct
+            return syntax_error!(UnexpectedToken, self);
+        }
+
+        if let Some(frame) = self.frame_stack.pop() {
+            self.add_frame_to_scopes(frame, expression);
+        }
+        Ok(expression)
+    }
+
+    // Skips over tokens until the next line that starts without indentation
+    //
+    // Used when recovering from errors, with the following line being the next possible start of
+    // a top-level expression.
+    fn skip_to_next_unindented_line(&mut self) {
+        while let Some(token) = self.peek_token() {
+            if token == Token::NewLine {
+                break;
+            }
+            self.consume_token();
+        }
+    }
+
     fn parse_nested_function_args(
         &mut self,
-        arg_ids: &mut Vec<ConstantIndex>,
+        arg_ids: &mut Vec<(ConstantIndex, AstIndex)>,
     ) -> Result<Vec<AstIndex>, ParserError> {
         let mut nested_args = Vec::new();
 
@@ -318,8 +802,9 @@ impl<'source> Parser<'source> {
                         return syntax_error!(SelfArgNotInFirstPosition, self);
                     }
 
-                    arg_ids.push(constant_index);
-                    nested_args.push(self.push_node(Node::Id(constant_index))?);
+                    let arg_node = self.push_node(Node::Id(constant_index))?;
+                    arg_ids.push((constant_index, arg_node));
+                    nested_args.push(arg_node);
                 }
                 Some(ConstantIndexOrWildcard::Wildcard) => {
                     nested_args.push(self.push_node(Node::Wildcard)?)
@@ -374,6 +859,7 @@ impl<'source> Parser<'source> {
         // Parse function's args
         let mut arg_nodes = Vec::new();
         let mut arg_ids = Vec::new();
+        let mut arg_types = Vec::new();
         let mut is_instance_function = false;
         let mut is_variadic = false;
 
@@ -389,8 +875,10 @@ impl<'source> Parser<'source> {
                         is_instance_function = true;
                     }
 
-                    arg_ids.push(constant_index);
-                    arg_nodes.push(self.push_node(Node::Id(constant_index))?);
+                    let arg_node = self.push_node(Node::Id(constant_index))?;
+                    arg_ids.push((constant_index, arg_node));
+                    arg_nodes.push(arg_node);
+                    arg_types.push(self.parse_type_annotation()?);
 
                     if self.peek_token() == Some(Token::Ellipsis) {
                         self.consume_token();
@@ -399,7 +887,8 @@ impl<'source> Parser<'source> {
                     }
                 }
                 Some(ConstantIndexOrWildcard::Wildcard) => {
-                    arg_nodes.push(self.push_node(Node::Wildcard)?)
+                    arg_nodes.push(self.push_node(Node::Wildcard)?);
+                    arg_types.push(self.parse_type_annotation()?);
                 }
                 None => match self.peek_token() {
                     Some(Token::SquareOpen) => {
@@ -407,6 +896,7 @@ impl<'source> Parser<'source> {
 
                         let list_args = self.parse_nested_function_args(&mut arg_ids)?;
                         arg_nodes.push(self.push_node(Node::List(list_args))?);
+                        arg_types.push(None);
 
                         if self.consume_next_token(&mut args_context) != Some(Token::SquareClose) {
                             return syntax_error!(ExpectedListEnd, self);
@@ -417,6 +907,7 @@ impl<'source> Parser<'source> {
 
                         let tuple_args = self.parse_nested_function_args(&mut arg_ids)?;
                         arg_nodes.push(self.push_node(Node::Tuple(tuple_args))?);
+                        arg_types.push(None);
 
                         if self.consume_next_token(&mut args_context) != Some(Token::RoundClose) {
                             return syntax_error!(ExpectedCloseParen, self);
@@ -440,16 +931,33 @@ impl<'source> Parser<'source> {
             return syntax_error!(ExpectedFunctionArgsEnd, self);
         }
 
+        if arg_types.iter().all(|arg_type| arg_type.is_none()) {
+            arg_types.clear();
+        }
+
+        // return type
+        let return_type = if self.peek_next_token_on_same_line() == Some(Token::Arrow) {
+            self.consume_next_token_on_same_line();
+            match self.consume_next_token_on_same_line() {
+                Some(Token::Id) => Some(self.add_string_constant(self.lexer.id_name())?),
+                _ => return syntax_error!(ExpectedReturnType, self),
+            }
+        } else {
+            None
+        };
+
         // body
-        let mut function_frame = Frame::default();
-        function_frame.ids_assigned_in_scope.extend(arg_ids.iter());
+        let mut function_frame = self.new_frame();
+        for (id, node) in arg_ids {
+            function_frame.add_local_node(node, id);
+        }
         self.frame_stack.push(function_frame);
 
         let body = if let Some(block) = self.parse_indented_block()? {
             // If the body is a Map block, then finish_expressions is needed here to finalise the
             // captures for the Map values. Normally parse_line takes care of calling
             // finish_expressions, but this is a situation where it can be bypassed.
-            self.frame_mut()?.finish_expression();
+            self.finish_expression()?;
             block
         } else {
             self.consume_until_next_token_on_same_line();
@@ -460,23 +968,31 @@ impl<'source> Parser<'source> {
             }
         };
 
-        let function_frame = self
+        let mut function_frame = self
             .frame_stack
             .pop()
             .ok_or_else(|| make_internal_error!(MissingScope, self))?;
 
         self.frame_mut()?
-            .add_nested_accessed_non_locals(&function_frame);
+            .add_nested_accessed_non_locals(&mut function_frame);
 
         let local_count = function_frame.local_count();
 
         let span_end = self.current_span().end;
 
-        let result = self.ast.push(
+        // The accessed non-locals determine the order of the function's captures, so they're
+        // sorted to ensure that the compiled output doesn't depend on the set's iteration order.
+        let mut accessed_non_locals =
+            Vec::from_iter(std::mem::take(&mut function_frame.accessed_non_locals));
+        accessed_non_locals.sort_by_key(|id| usize::from(*id));
+
+        let result = self.push_node_with_span(
             Node::Function(Function {
                 args: arg_nodes,
+                arg_types,
+                return_type,
                 local_count,
-                accessed_non_locals: Vec::from_iter(function_frame.accessed_non_locals),
+                accessed_non_locals,
                 body,
                 is_instance_function,
                 is_variadic,
@@ -488,9 +1004,94 @@ impl<'source> Parser<'source> {
             },
         )?;
 
+        self.add_frame_to_scopes(function_frame, result);
+
         Ok(Some(result))
     }
 
+    // Checks if the peeked `@` at the given peek position starts a function decorator,
+    // e.g. `@memoize |n| ...`, rather than a meta key.
+    fn peek_decorator_start(&mut self, at_peek_count: usize) -> bool {
+        if self.peek_token_n(at_peek_count + 1) != Some(Token::Id) {
+            return false;
+        }
+
+        let mut peek_count = at_peek_count + 2;
+        while let Some(peeked) = self.peek_token_n(peek_count) {
+            match peeked {
+                token if token.is_whitespace() => {}
+                Token::At | Token::Function => return true,
+                _ => return false,
+            }
+
+            peek_count += 1;
+        }
+
+        false
+    }
+
+    // Parses a function preceded by a list of decorators, e.g. `@memoize @log |n| ...`
+    fn parse_decorated_function(
+        &mut self,
+        context: &mut ExpressionContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        if self.consume_next_token(context) != Some(Token::At) {
+            return internal_error!(UnexpectedToken, self);
+        }
+
+        let start_span = self.current_span();
+        let mut decorators = Vec::new();
+
+        loop {
+            if self.consume_token() != Some(Token::Id) {
+                return syntax_error!(ExpectedDecoratorId, self);
+            }
+
+            let id = self.add_string_constant(self.lexer.id_name())?;
+            self.frame_mut()?.add_id_access(id);
+            let id_node = self.push_node(Node::Id(id))?;
+            self.frame_mut()?.add_access_node(id_node, id);
+            decorators.push(id_node);
+
+            match self.peek_next_token_on_same_line() {
+                Some(Token::At) => {
+                    self.consume_next_token_on_same_line();
+                }
+                Some(Token::Function) => break,
+                _ => return syntax_error!(ExpectedFunctionAfterDecorator, self),
+            }
+        }
+
+        let function = match self.parse_function(context)? {
+            Some(function) => function,
+            None => return syntax_error!(ExpectedFunctionAfterDecorator, self),
+        };
+
+        let result = self.push_node_with_start_span(
+            Node::Decorated {
+                decorators,
+                function,
+            },
+            start_span,
+        )?;
+
+        Ok(Some(result))
+    }
+
+    // Parses an optional type annotation following a function argument, e.g. `x: Number`
+    fn parse_type_annotation(&mut self) -> Result<Option<ConstantIndex>, ParserError> {
+        if self.peek_token() != Some(Token::Colon) {
+            return Ok(None);
+        }
+
+        self.consume_token();
+
+        match self.consume_next_token_on_same_line() {
+            Some(Token::Id) => Ok(Some(self.add_string_constant(self.lexer.id_name())?)),
+            _ => syntax_error!(ExpectedTypeAnnotation, self),
+        }
+    }
+
     fn parse_line(
         &mut self,
         context: &mut ExpressionContext,
@@ -509,7 +1110,7 @@ impl<'source> Parser<'source> {
             self.parse_expressions(context, TempResult::No)?
         };
 
-        self.frame_mut()?.finish_expression();
+        self.finish_expression()?;
 
         Ok(result)
     }
@@ -532,7 +1133,7 @@ impl<'source> Parser<'source> {
             ..*context
         };
 
-        if let Some(first) = self.parse_expression(&mut expression_context)? {
+        if let Some(first) = self.parse_expression_with_context(&mut expression_context)? {
             let mut expressions = vec![first];
             let mut encountered_comma = false;
 
@@ -586,7 +1187,7 @@ impl<'source> Parser<'source> {
         self.parse_expression_start(lhs, 0, context)
     }
 
-    fn parse_expression(
+    fn parse_expression_with_context(
         &mut self,
         context: &mut ExpressionContext,
     ) -> Result<Option<AstIndex>, ParserError> {
@@ -602,7 +1203,7 @@ impl<'source> Parser<'source> {
 
         let result = match self.peek_next_token_on_same_line() {
             Some(Token::Range) | Some(Token::RangeInclusive) => {
-                self.parse_range(result, context)?
+                self.parse_range(result, 0, context)?
             }
             _ => result,
         };
@@ -615,6 +1216,17 @@ impl<'source> Parser<'source> {
         lhs: Option<&[AstIndex]>,
         min_precedence: u8,
         context: &mut ExpressionContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        self.with_nested_depth(|parser| {
+            parser.parse_expression_start_unchecked(lhs, min_precedence, context)
+        })
+    }
+
+    fn parse_expression_start_unchecked(
+        &mut self,
+        lhs: Option<&[AstIndex]>,
+        min_precedence: u8,
+        context: &mut ExpressionContext,
     ) -> Result<Option<AstIndex>, ParserError> {
         let expression_start = match self.parse_term(context)? {
             Some(term) => term,
@@ -683,6 +1295,15 @@ impl<'source> Parser<'source> {
                                 return indentation_error!(RhsExpression, self);
                             };
 
+                            // Ranges bind more loosely than binary operators, apart from `in`,
+                            // which allows a range to be used as its RHS, e.g. `x in 0..10`
+                            let rhs = match self.peek_next_token_on_same_line() {
+                                Some(Range) | Some(RangeInclusive) if op == In => self
+                                    .parse_range(Some(rhs), right_priority, &mut context)?
+                                    .unwrap_or(rhs),
+                                _ => rhs,
+                            };
+
                             let op_node = self.push_ast_op(op, last_lhs, rhs)?;
                             return self.parse_expression_continued(
                                 &[op_node],
@@ -707,14 +1328,32 @@ impl<'source> Parser<'source> {
         self.consume_next_token(context); // The assign op has already been matched
 
         let mut targets = Vec::new();
+        let mut ellipsis_count = 0;
 
-        for lhs_expression in lhs.iter() {
+        for (i, lhs_expression) in lhs.iter().enumerate() {
             match self.ast.node(*lhs_expression).node.clone() {
                 Node::Id(id_index) => {
                     if matches!(assign_op, AssignOp::Equal) {
                         self.frame_mut()?.add_id_assignment(id_index);
                         self.frame_mut()?.remove_id_access(id_index);
+                        self.frame_mut()?
+                            .add_assignment_node(*lhs_expression, id_index);
+                    }
+                }
+                Node::Ellipsis(Some(id_index)) => {
+                    // e.g. `first, rest... = x`, or `rest..., last = x`
+                    ellipsis_count += 1;
+                    let is_first_or_last = i == 0 || i == lhs.len() - 1;
+                    if !matches!(assign_op, AssignOp::Equal)
+                        || lhs.len() == 1
+                        || !is_first_or_last
+                        || ellipsis_count > 1
+                    {
+                        return syntax_error!(InvalidEllipsisInAssignment, self);
                     }
+                    self.frame_mut()?.add_id_assignment(id_index);
+                    self.frame_mut()?
+                        .add_assignment_node(*lhs_expression, id_index);
                 }
                 Node::Lookup(_) | Node::Wildcard => {}
                 _ => return syntax_error!(ExpectedAssignmentTarget, self),
@@ -775,7 +1414,7 @@ impl<'source> Parser<'source> {
                 token: Token::Id, ..
             }) => {
                 self.consume_next_token(context);
-                let result = self.add_string_constant(self.lexer.slice())?;
+                let result = self.add_string_constant(self.lexer.id_name())?;
                 Ok(Some(result))
             }
             _ => Ok(None),
@@ -791,7 +1430,7 @@ impl<'source> Parser<'source> {
                 token: Token::Id, ..
             }) => {
                 self.consume_next_token(context);
-                let result = self.add_string_constant(self.lexer.slice())?;
+                let result = self.add_string_constant(self.lexer.id_name())?;
                 Ok(Some(ConstantIndexOrWildcard::Index(result)))
             }
             Some(PeekInfo {
@@ -829,29 +1468,40 @@ impl<'source> Parser<'source> {
             Some(Token::Equal) => MetaKeyId::Equal,
             Some(Token::NotEqual) => MetaKeyId::NotEqual,
             Some(Token::Not) => MetaKeyId::Not,
-            Some(Token::Id) => match self.lexer.slice() {
+            Some(Token::Id) => match self.lexer.id_name() {
                 "display" => MetaKeyId::Display,
                 "negate" => MetaKeyId::Negate,
                 "tests" => MetaKeyId::Tests,
                 "pre_test" => MetaKeyId::PreTest,
                 "post_test" => MetaKeyId::PostTest,
-                "test" => match self.consume_next_token_on_same_line() {
-                    Some(Token::Id) => {
-                        let test_name = self.add_string_constant(self.lexer.slice())?;
-                        meta_name = Some(test_name);
-                        MetaKeyId::Test
+                test_key @ ("test" | "test_expected_to_fail" | "test_skip" | "test_timeout") => {
+                    let meta_key_id = match test_key {
+                        "test" => MetaKeyId::Test,
+                        "test_expected_to_fail" => MetaKeyId::TestExpectedToFail,
+                        "test_skip" => MetaKeyId::TestSkip,
+                        _ => MetaKeyId::TestTimeout,
+                    };
+                    match self.consume_next_token_on_same_line() {
+                        Some(Token::Id) => {
+                            let test_name = self.add_string_constant(self.lexer.id_name())?;
+                            meta_name = Some(test_name);
+                            meta_key_id
+                        }
+                        _ => return syntax_error!(ExpectedTestName, self),
                     }
-                    _ => return syntax_error!(ExpectedTestName, self),
-                },
+                }
                 "meta" => match self.consume_next_token_on_same_line() {
                     Some(Token::Id) => {
-                        let id = self.add_string_constant(self.lexer.slice())?;
+                        let id = self.add_string_constant(self.lexer.id_name())?;
                         meta_name = Some(id);
                         MetaKeyId::Named
                     }
                     _ => return syntax_error!(ExpectedMetaId, self),
                 },
                 "type" => MetaKeyId::Type,
+                "version" => MetaKeyId::Version,
+                "requires" => MetaKeyId::Requires,
+                "reload" => MetaKeyId::Reload,
                 _ => return syntax_error!(UnexpectedMetaKey, self),
             },
             Some(Token::SquareOpen) => match self.consume_token() {
@@ -937,18 +1587,25 @@ impl<'source> Parser<'source> {
         if let Some(constant_index) = self.parse_id(context)? {
             if self.peek_token() == Some(Token::Colon) {
                 self.parse_braceless_map_start(MapKey::Id(constant_index), context)
+            } else if self.peek_token() == Some(Token::Ellipsis) {
+                // e.g. `first, rest... = x`
+                // The ellipsis is only valid as a target in a multi-assignment,
+                // which is checked in parse_assign_expression.
+                self.consume_token();
+                Ok(Some(self.push_node(Node::Ellipsis(Some(constant_index)))?))
             } else {
                 self.frame_mut()?.add_id_access(constant_index);
 
                 let mut context = context.start_new_expression();
                 let result = if self.next_token_is_lookup_start(&context) {
                     let id_index = self.push_node(Node::Id(constant_index))?;
+                    self.frame_mut()?.add_access_node(id_index, constant_index);
                     self.parse_lookup(id_index, &mut context)?
                 } else {
                     let start_span = self.current_span();
                     let args = self.parse_call_args(&mut context)?;
 
-                    if args.is_empty() {
+                    let result = if args.is_empty() {
                         self.push_node(Node::Id(constant_index))?
                     } else {
                         self.push_node_with_start_span(
@@ -958,7 +1615,9 @@ impl<'source> Parser<'source> {
                             },
                             start_span,
                         )?
-                    }
+                    };
+                    self.frame_mut()?.add_access_node(result, constant_index);
+                    result
                 };
 
                 Ok(Some(result))
@@ -1005,14 +1664,14 @@ impl<'source> Parser<'source> {
                     let mut index_context = ExpressionContext::restricted();
 
                     let index_expression = if let Some(index_expression) =
-                        self.parse_expression(&mut index_context)?
+                        self.parse_expression_with_context(&mut index_context)?
                     {
                         match self.peek_token() {
                             Some(Token::Range) => {
                                 self.consume_token();
 
                                 if let Some(end_expression) =
-                                    self.parse_expression(&mut index_context)?
+                                    self.parse_expression_with_context(&mut index_context)?
                                 {
                                     self.push_node(Node::Range {
                                         start: index_expression,
@@ -1029,7 +1688,7 @@ impl<'source> Parser<'source> {
                                 self.consume_token();
 
                                 if let Some(end_expression) =
-                                    self.parse_expression(&mut index_context)?
+                                    self.parse_expression_with_context(&mut index_context)?
                                 {
                                     self.push_node(Node::Range {
                                         start: index_expression,
@@ -1052,7 +1711,7 @@ impl<'source> Parser<'source> {
                                 self.consume_next_token_on_same_line();
 
                                 if let Some(end_expression) =
-                                    self.parse_expression(&mut index_context)?
+                                    self.parse_expression_with_context(&mut index_context)?
                                 {
                                     self.push_node(Node::RangeTo {
                                         end: end_expression,
@@ -1066,7 +1725,7 @@ impl<'source> Parser<'source> {
                                 self.consume_next_token_on_same_line();
 
                                 if let Some(end_expression) =
-                                    self.parse_expression(&mut index_context)?
+                                    self.parse_expression_with_context(&mut index_context)?
                                 {
                                     self.push_node(Node::RangeTo {
                                         end: end_expression,
@@ -1198,7 +1857,9 @@ impl<'source> Parser<'source> {
         while self.peek_next_token(&args_context).is_some() {
             self.consume_until_next_token(&mut args_context);
 
-            if let Some(expression) = self.parse_expression(&mut ExpressionContext::inline())? {
+            if let Some(expression) =
+                self.parse_expression_with_context(&mut ExpressionContext::inline())?
+            {
                 args.push(expression);
             } else {
                 break;
@@ -1227,9 +1888,13 @@ impl<'source> Parser<'source> {
         Ok(args)
     }
 
+    // Parses a range following the (optional) start expression
+    //
+    // The range's end is parsed with the provided minimum precedence.
     fn parse_range(
         &mut self,
         lhs: Option<AstIndex>,
+        end_min_precedence: u8,
         context: &mut ExpressionContext,
     ) -> Result<Option<AstIndex>, ParserError> {
         use Node::{Range, RangeFrom, RangeFull, RangeTo};
@@ -1242,7 +1907,12 @@ impl<'source> Parser<'source> {
 
         self.consume_next_token_on_same_line();
 
-        let rhs = self.parse_expression(&mut ExpressionContext::inline())?;
+        let rhs = self.with_nested_depth(|parser| {
+            parser.parse_expression_with_min_precedence(
+                end_min_precedence,
+                &mut ExpressionContext::inline(),
+            )
+        })?;
 
         let range_node = match (lhs, rhs) {
             (Some(start), Some(end)) => Range {
@@ -1270,28 +1940,59 @@ impl<'source> Parser<'source> {
 
         self.consume_next_token_on_same_line();
 
-        let export_id = if let Some(constant_index) = self.parse_id(context)? {
-            self.push_node(Node::Id(constant_index))?
-        } else if let Some((meta_key_id, name)) = self.parse_meta_key()? {
-            self.push_node(Node::Meta(meta_key_id, name))?
+        if matches!(
+            self.peek_next_token_on_same_line(),
+            None | Some(Token::NewLine) | Some(Token::NewLineIndented)
+        ) {
+            return self.parse_export_block();
+        }
+
+        let export_targets = if let Some((meta_key_id, name)) = self.parse_meta_key()? {
+            vec![self.push_node(Node::Meta(meta_key_id, name))?]
         } else {
-            return syntax_error!(ExpectedExportExpression, self);
+            let ids = self.parse_export_ids(context)?;
+            if ids.is_empty() {
+                return syntax_error!(ExpectedExportExpression, self);
+            }
+            let mut targets = Vec::with_capacity(ids.len());
+            for id in ids {
+                let target = self.push_node(Node::Id(id))?;
+                self.frame_mut()?.add_global_node(target);
+                targets.push(target);
+            }
+            targets
         };
 
         match self.peek_next_token_on_same_line() {
             Some(Token::Assign) => {
                 self.consume_next_token_on_same_line();
 
+                let single_target = export_targets.len() == 1;
+                let temp_result = if single_target {
+                    TempResult::No
+                } else {
+                    TempResult::Yes
+                };
+
                 if let Some(rhs) =
-                    self.parse_expressions(&mut ExpressionContext::permissive(), TempResult::No)?
+                    self.parse_expressions(&mut ExpressionContext::permissive(), temp_result)?
                 {
-                    let node = Node::Assign {
-                        target: AssignTarget {
-                            target_index: export_id,
-                            scope: Scope::Export,
-                        },
-                        op: AssignOp::Equal,
-                        expression: rhs,
+                    let mut targets = export_targets.into_iter().map(|target_index| AssignTarget {
+                        target_index,
+                        scope: Scope::Export,
+                    });
+
+                    let node = if single_target {
+                        Node::Assign {
+                            target: targets.next().unwrap(),
+                            op: AssignOp::Equal,
+                            expression: rhs,
+                        }
+                    } else {
+                        Node::MultiAssign {
+                            targets: targets.collect(),
+                            expression: rhs,
+                        }
                     };
 
                     Ok(Some(self.push_node(node)?))
@@ -1299,9 +2000,127 @@ impl<'source> Parser<'source> {
                     indentation_error!(RhsExpression, self)
                 }
             }
-            Some(Token::NewLine) | Some(Token::NewLineIndented) => Ok(Some(export_id)),
-            _ => syntax_error!(UnexpectedTokenAfterExportId, self),
-        }
+            None | Some(Token::NewLine) | Some(Token::NewLineIndented) => {
+                match self.ast.node(export_targets[0]).node {
+                    Node::Meta(_, _) => Ok(Some(export_targets[0])),
+                    _ => self.export_existing_values(export_targets),
+                }
+            }
+            _ => syntax_error!(UnexpectedTokenAfterExportId, self),
+        }
+    }
+
+    // Parses an indented block of IDs to be exported
+    //
+    // e.g.
+    //   export
+    //     foo, bar
+    //     baz
+    fn parse_export_block(&mut self) -> Result<Option<AstIndex>, ParserError> {
+        let mut block_context = ExpressionContext::permissive();
+
+        let start_indent = self.current_indent();
+        match self.peek_next_token(&block_context) {
+            Some(peeked) if peeked.indent > start_indent => {}
+            _ => return syntax_error!(ExpectedExportExpression, self),
+        }
+
+        let mut export_targets = Vec::new();
+
+        loop {
+            self.consume_until_next_token(&mut block_context);
+
+            let ids = self.parse_export_ids(&mut ExpressionContext::restricted())?;
+            if ids.is_empty() {
+                return syntax_error!(ExpectedExportExpression, self);
+            }
+            for id in ids {
+                let target = self.push_node(Node::Id(id))?;
+                self.frame_mut()?.add_global_node(target);
+                export_targets.push(target);
+            }
+
+            match self.peek_next_token_on_same_line() {
+                None | Some(Token::NewLine) | Some(Token::NewLineIndented) => {}
+                _ => return syntax_error!(UnexpectedTokenAfterExportId, self),
+            }
+
+            if self.peek_next_token(&block_context).is_none() {
+                break;
+            }
+        }
+
+        self.export_existing_values(export_targets)
+    }
+
+    // Parses a comma-separated list of IDs in an export expression
+    fn parse_export_ids(
+        &mut self,
+        context: &mut ExpressionContext,
+    ) -> Result<Vec<ConstantIndex>, ParserError> {
+        let mut ids = Vec::new();
+
+        if let Some(id) = self.parse_id(context)? {
+            ids.push(id);
+        } else {
+            return Ok(ids);
+        }
+
+        while self.peek_next_token_on_same_line() == Some(Token::Comma) {
+            self.consume_next_token_on_same_line();
+
+            if let Some(id) = self.parse_id(&mut ExpressionContext::restricted())? {
+                ids.push(id);
+            } else {
+                return syntax_error!(ExpectedExportExpression, self);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    // Exports the current values of previously defined IDs
+    //
+    // e.g. `export foo, bar` is equivalent to `export foo, bar = foo, bar`
+    fn export_existing_values(
+        &mut self,
+        export_targets: Vec<AstIndex>,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        let mut values = Vec::with_capacity(export_targets.len());
+
+        for target in export_targets.iter() {
+            let id = match self.ast.node(*target).node {
+                Node::Id(id) => id,
+                _ => return internal_error!(UnexpectedToken, self),
+            };
+            self.frame_mut()?.add_id_access(id);
+            let value = self.push_node(Node::Id(id))?;
+            self.frame_mut()?.add_access_node(value, id);
+            values.push(value);
+        }
+
+        let mut targets = export_targets
+            .into_iter()
+            .map(|target_index| AssignTarget {
+                target_index,
+                scope: Scope::Export,
+            })
+            .collect::<Vec<_>>();
+
+        let node = if targets.len() == 1 {
+            Node::Assign {
+                target: targets.remove(0),
+                op: AssignOp::Equal,
+                expression: values[0],
+            }
+        } else {
+            Node::MultiAssign {
+                targets,
+                expression: self.push_node(Node::TempTuple(values))?,
+            }
+        };
+
+        Ok(Some(self.push_node(node)?))
     }
 
     fn parse_throw_expression(&mut self) -> Result<Option<AstIndex>, ParserError> {
@@ -1314,7 +2133,7 @@ impl<'source> Parser<'source> {
         let start_span = self.current_span();
 
         let expression = if let Some(expression) =
-            self.parse_expression(&mut ExpressionContext::permissive())?
+            self.parse_expression_with_context(&mut ExpressionContext::permissive())?
         {
             expression
         } else {
@@ -1325,6 +2144,46 @@ impl<'source> Parser<'source> {
         Ok(Some(result))
     }
 
+    fn parse_assert_expression(&mut self) -> Result<Option<AstIndex>, ParserError> {
+        if self.peek_next_token_on_same_line() != Some(Token::Assert) {
+            return Ok(None);
+        }
+
+        self.consume_next_token_on_same_line();
+
+        let start_position = self.current_span().start;
+
+        self.consume_until_next_token_on_same_line();
+
+        let mut context = ExpressionContext::permissive();
+        let expression_source_start = self.lexer.source_position();
+        let expression =
+            if let Some(expression) = self.parse_expression_with_context(&mut context)? {
+                expression
+            } else {
+                return syntax_error!(ExpectedExpression, self);
+            };
+
+        let expression_source_end = self.lexer.source_position();
+
+        let expression_string = self.add_string_constant(
+            &self.lexer.source()[expression_source_start..expression_source_end],
+        )?;
+
+        let result = self.push_node_with_span(
+            Node::Assert {
+                expression_string,
+                expression,
+            },
+            Span {
+                start: start_position,
+                end: self.current_span().end,
+            },
+        )?;
+
+        Ok(Some(result))
+    }
+
     fn parse_debug_expression(&mut self) -> Result<Option<AstIndex>, ParserError> {
         if self.peek_next_token_on_same_line() != Some(Token::Debug) {
             return Ok(None);
@@ -1351,7 +2210,7 @@ impl<'source> Parser<'source> {
             &self.lexer.source()[expression_source_start..expression_source_end],
         )?;
 
-        let result = self.ast.push(
+        let result = self.push_node_with_span(
             Node::Debug {
                 expression_string,
                 expression,
@@ -1368,6 +2227,13 @@ impl<'source> Parser<'source> {
     fn parse_term(
         &mut self,
         context: &mut ExpressionContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        self.with_nested_depth(|parser| parser.parse_term_unchecked(context))
+    }
+
+    fn parse_term_unchecked(
+        &mut self,
+        context: &mut ExpressionContext,
     ) -> Result<Option<AstIndex>, ParserError> {
         use Node::*;
 
@@ -1395,6 +2261,9 @@ impl<'source> Parser<'source> {
                     }
                 }
                 Token::Id => self.parse_id_expression(context)?,
+                Token::At if self.peek_decorator_start(peeked.peek_count) => {
+                    self.parse_decorated_function(context)?
+                }
                 Token::At if context.allow_map_block || peeked.indent > start_indent => {
                     self.consume_until_next_token(context);
                     let (meta_key_id, meta_name) = self.parse_meta_key()?.unwrap();
@@ -1445,6 +2314,7 @@ impl<'source> Parser<'source> {
                     Some(self.check_for_lookup_after_node(node, context)?)
                 }
                 Token::If => self.parse_if_expression(context)?,
+                Token::Guard => self.parse_guard_expression(context)?,
                 Token::Match => self.parse_match_expression(context)?,
                 Token::Switch => self.parse_switch_expression(context)?,
                 Token::Function => self.parse_function(context)?,
@@ -1470,11 +2340,13 @@ impl<'source> Parser<'source> {
                 },
                 Token::Not => {
                     self.consume_next_token(context);
-                    if let Some(expression) = self.parse_expression(&mut ExpressionContext {
-                        allow_space_separated_call: true,
-                        expected_indentation: Indentation::Greater,
-                        ..*context
-                    })? {
+                    if let Some(expression) =
+                        self.parse_expression_with_context(&mut ExpressionContext {
+                            allow_space_separated_call: true,
+                            expected_indentation: Indentation::Greater,
+                            ..*context
+                        })?
+                    {
                         let result = self.push_node(Node::UnaryOp {
                             op: AstUnaryOp::Not,
                             value: expression,
@@ -1512,6 +2384,7 @@ impl<'source> Parser<'source> {
                 }
                 Token::Throw => self.parse_throw_expression()?,
                 Token::Debug => self.parse_debug_expression()?,
+                Token::Assert => self.parse_assert_expression()?,
                 Token::From | Token::Import => self.parse_import_expression(context)?,
                 Token::Try => self.parse_try_expression(context)?,
                 Token::Error => return syntax_error!(LexerError, self),
@@ -1549,32 +2422,51 @@ impl<'source> Parser<'source> {
 
         self.consume_next_token(context);
 
+        // Underscores can be used as digit separators, e.g. `1_000_000`
         let slice = self.lexer.slice();
+        let number = if slice.contains('_') {
+            Cow::Owned(slice.replace('_', ""))
+        } else {
+            Cow::Borrowed(slice)
+        };
 
-        let maybe_integer = if let Some(hex) = slice.strip_prefix("0x") {
-            i64::from_str_radix(hex, 16)
-        } else if let Some(octal) = slice.strip_prefix("0o") {
-            i64::from_str_radix(octal, 8)
-        } else if let Some(binary) = slice.strip_prefix("0b") {
-            i64::from_str_radix(binary, 2)
+        let maybe_integer = if let Some(hex) = number.strip_prefix("0x") {
+            u64::from_str_radix(hex, 16)
+        } else if let Some(octal) = number.strip_prefix("0o") {
+            u64::from_str_radix(octal, 8)
+        } else if let Some(binary) = number.strip_prefix("0b") {
+            u64::from_str_radix(binary, 2)
         } else {
-            i64::from_str(slice)
+            u64::from_str(&number)
         };
 
-        let number_node = if let Ok(n) = maybe_integer {
+        // The negation is applied before checking the integer's range,
+        // so that i64::MIN can be represented.
+        let maybe_integer = maybe_integer.ok().and_then(|n| {
+            if negate {
+                if n <= i64::MIN.unsigned_abs() {
+                    Some((n as i64).wrapping_neg())
+                } else {
+                    None
+                }
+            } else {
+                i64::try_from(n).ok()
+            }
+        });
+
+        let number_node = if let Some(n) = maybe_integer {
             if n == 0 {
                 self.push_node(Number0)?
-            } else if n == 1 && !negate {
+            } else if n == 1 {
                 self.push_node(Number1)?
             } else {
-                let n = if negate { -n } else { n };
                 match self.constants.add_i64(n) {
                     Ok(constant_index) => self.push_node(Int(constant_index))?,
                     Err(_) => return internal_error!(ConstantPoolCapacityOverflow, self),
                 }
             }
         } else {
-            match f64::from_str(slice) {
+            match f64::from_str(&number) {
                 Ok(n) => {
                     let n = if negate { -n } else { n };
                     match self.constants.add_f64(n) {
@@ -1599,12 +2491,13 @@ impl<'source> Parser<'source> {
     ) -> Result<Option<AstIndex>, ParserError> {
         let mut list_context = *context;
         let start_indent = self.current_indent();
-        let start_span = self.current_span();
 
         if self.consume_next_token(&mut list_context) != Some(Token::SquareOpen) {
             return internal_error!(UnexpectedToken, self);
         }
 
+        let start_span = self.current_span();
+
         // The end brace should have the same indentation as the start brace.
         if matches!(list_context.expected_indentation, Indentation::Greater) {
             list_context.expected_indentation = Indentation::Equal(start_indent);
@@ -1622,7 +2515,9 @@ impl<'source> Parser<'source> {
         ) {
             self.consume_until_next_token(&mut entry_context);
 
-            if let Some(entry) = self.parse_expression(&mut ExpressionContext::inline())? {
+            if let Some(entry) =
+                self.parse_expression_with_context(&mut ExpressionContext::inline())?
+            {
                 entries.push(entry);
             }
 
@@ -1664,27 +2559,28 @@ impl<'source> Parser<'source> {
             return internal_error!(ExpectedMapColon, self);
         }
 
-        let entries =
-            if let Some(value) = self.parse_expression(&mut ExpressionContext::permissive())? {
-                if let Some(Token::Comma) = self.peek_next_token_on_same_line() {
-                    self.consume_next_token_on_same_line();
-                    let mut entries = vec![(first_key, Some(value))];
-                    entries.extend(self.parse_comma_separated_map_entries(context, false)?);
-                    entries
-                } else if context.allow_map_block {
-                    let mut block_context = ExpressionContext::permissive();
-                    block_context.expected_indentation = Indentation::Equal(start_indent);
-                    return self.parse_map_block(
-                        (first_key, Some(value)),
-                        start_span,
-                        &mut block_context,
-                    );
-                } else {
-                    vec![(first_key, Some(value))]
-                }
+        let entries = if let Some(value) =
+            self.parse_expression_with_context(&mut ExpressionContext::permissive())?
+        {
+            if let Some(Token::Comma) = self.peek_next_token_on_same_line() {
+                self.consume_next_token_on_same_line();
+                let mut entries = vec![(first_key, Some(value))];
+                entries.extend(self.parse_comma_separated_map_entries(context, false)?);
+                entries
+            } else if context.allow_map_block {
+                let mut block_context = ExpressionContext::permissive();
+                block_context.expected_indentation = Indentation::Equal(start_indent);
+                return self.parse_map_block(
+                    (first_key, Some(value)),
+                    start_span,
+                    &mut block_context,
+                );
             } else {
-                return syntax_error!(ExpectedMapValue, self);
-            };
+                vec![(first_key, Some(value))]
+            }
+        } else {
+            return syntax_error!(ExpectedMapValue, self);
+        };
 
         let result = self.push_node_with_start_span(Node::Map(entries), start_span)?;
         Ok(Some(result))
@@ -1705,7 +2601,9 @@ impl<'source> Parser<'source> {
                 if self.peek_next_token_on_same_line() == Some(Token::Colon) {
                     self.consume_next_token_on_same_line();
 
-                    if let Some(value) = self.parse_expression(&mut ExpressionContext::inline())? {
+                    if let Some(value) =
+                        self.parse_expression_with_context(&mut ExpressionContext::inline())?
+                    {
                         entries.push((key, Some(value)));
                     } else {
                         // If a value wasn't found on the same line as the key,
@@ -1779,7 +2677,7 @@ impl<'source> Parser<'source> {
                     }
                     self.consume_until_next_token(&mut value_context);
 
-                    if let Some(value) = self.parse_expression(&mut value_context)? {
+                    if let Some(value) = self.parse_expression_with_context(&mut value_context)? {
                         entries.push((key, Some(value)));
                     } else {
                         return syntax_error!(ExpectedMapValue, self);
@@ -1820,7 +2718,7 @@ impl<'source> Parser<'source> {
             match id_or_wildcard {
                 ConstantIndexOrWildcard::Index(id_index) => {
                     args.push(Some(id_index));
-                    self.frame_mut()?.ids_assigned_in_scope.insert(id_index);
+                    self.frame_mut()?.add_local(id_index, None);
                 }
                 ConstantIndexOrWildcard::Wildcard => args.push(None),
             }
@@ -1840,13 +2738,14 @@ impl<'source> Parser<'source> {
             return syntax_error!(ExpectedForArgs, self);
         }
 
-        let iterable = match self.parse_expression(&mut ExpressionContext::inline())? {
+        let iterable = match self.parse_expression_with_context(&mut ExpressionContext::inline())? {
             Some(iterable) => iterable,
             None => return syntax_error!(ExpectedForIterable, self),
         };
 
-        match self.parse_indented_block()? {
+        match self.with_nested_depth(|parser| parser.parse_indented_block())? {
             Some(body) => {
+                let arg_ids = args.iter().flatten().copied().collect::<Vec<_>>();
                 let result = self.push_node_with_start_span(
                     Node::For(AstFor {
                         args,
@@ -1855,6 +2754,7 @@ impl<'source> Parser<'source> {
                     }),
                     start_span,
                 )?;
+                self.frame_mut()?.set_definitions(&arg_ids, result);
 
                 Ok(Some(result))
             }
@@ -1869,7 +2769,7 @@ impl<'source> Parser<'source> {
 
         self.consume_next_token_on_same_line();
 
-        if let Some(body) = self.parse_indented_block()? {
+        if let Some(body) = self.with_nested_depth(|parser| parser.parse_indented_block())? {
             let result = self.push_node(Node::Loop { body })?;
             Ok(Some(result))
         } else {
@@ -1884,14 +2784,15 @@ impl<'source> Parser<'source> {
 
         self.consume_next_token_on_same_line();
 
-        let condition =
-            if let Some(condition) = self.parse_expression(&mut ExpressionContext::inline())? {
-                condition
-            } else {
-                return syntax_error!(ExpectedWhileCondition, self);
-            };
+        let condition = if let Some(condition) =
+            self.parse_expression_with_context(&mut ExpressionContext::inline())?
+        {
+            condition
+        } else {
+            return syntax_error!(ExpectedWhileCondition, self);
+        };
 
-        match self.parse_indented_block()? {
+        match self.with_nested_depth(|parser| parser.parse_indented_block())? {
             Some(body) => {
                 let result = self.push_node(Node::While { condition, body })?;
                 Ok(Some(result))
@@ -1907,14 +2808,15 @@ impl<'source> Parser<'source> {
 
         self.consume_next_token_on_same_line();
 
-        let condition =
-            if let Some(condition) = self.parse_expression(&mut ExpressionContext::inline())? {
-                condition
-            } else {
-                return syntax_error!(ExpectedUntilCondition, self);
-            };
+        let condition = if let Some(condition) =
+            self.parse_expression_with_context(&mut ExpressionContext::inline())?
+        {
+            condition
+        } else {
+            return syntax_error!(ExpectedUntilCondition, self);
+        };
 
-        match self.parse_indented_block()? {
+        match self.with_nested_depth(|parser| parser.parse_indented_block())? {
             Some(body) => {
                 let result = self.push_node(Node::Until { condition, body })?;
                 Ok(Some(result))
@@ -1923,6 +2825,81 @@ impl<'source> Parser<'source> {
         }
     }
 
+    // Parses a guard expression, which is compiled as an if expression with an inverted condition
+    //
+    // e.g.
+    //   guard x > 0 else return
+    // is equivalent to:
+    //   if not (x > 0) then return
+    //
+    // The else branch must end with return, throw, break, or continue.
+    fn parse_guard_expression(
+        &mut self,
+        context: &mut ExpressionContext,
+    ) -> Result<Option<AstIndex>, ParserError> {
+        if self.consume_next_token(context) != Some(Token::Guard) {
+            return internal_error!(UnexpectedToken, self);
+        }
+
+        let start_span = self.current_span();
+
+        let condition = match self.parse_expression_with_context(&mut ExpressionContext::inline())?
+        {
+            Some(condition) => condition,
+            None => return syntax_error!(ExpectedGuardCondition, self),
+        };
+
+        if self.peek_next_token_on_same_line() != Some(Token::Else) {
+            return syntax_error!(ExpectedGuardElse, self);
+        }
+        self.consume_next_token_on_same_line();
+
+        let else_node = if let Some(else_node) =
+            self.parse_expressions(&mut ExpressionContext::inline(), TempResult::No)?
+        {
+            else_node
+        } else if !context.allow_linebreaks {
+            return syntax_error!(ExpectedElseExpression, self);
+        } else if let Some(else_block) = self.parse_indented_block()? {
+            else_block
+        } else {
+            return indentation_error!(ElseBlock, self);
+        };
+
+        // The else branch needs to exit from the guard's scope
+        let mut last_expression = else_node;
+        while let Node::Block(expressions) = &self.ast.node(last_expression).node {
+            match expressions.last() {
+                Some(expression) => last_expression = *expression,
+                None => break,
+            }
+        }
+        if !matches!(
+            self.ast.node(last_expression).node,
+            Node::Return(_) | Node::Throw(_) | Node::Break | Node::Continue
+        ) {
+            let span = *self.ast.span(self.ast.node(last_expression).span);
+            return syntax_error_with_span!(ExpectedGuardExit, span);
+        }
+
+        let inverted_condition = self.push_node(Node::UnaryOp {
+            op: AstUnaryOp::Not,
+            value: condition,
+        })?;
+
+        let result = self.push_node_with_start_span(
+            Node::If(AstIf {
+                condition: inverted_condition,
+                then_node: else_node,
+                else_if_blocks: vec![],
+                else_node: None,
+            }),
+            start_span,
+        )?;
+
+        Ok(Some(result))
+    }
+
     fn parse_if_expression(
         &mut self,
         context: &mut ExpressionContext,
@@ -1934,7 +2911,8 @@ impl<'source> Parser<'source> {
             return internal_error!(UnexpectedToken, self);
         }
 
-        let condition = match self.parse_expression(&mut ExpressionContext::inline())? {
+        let condition = match self.parse_expression_with_context(&mut ExpressionContext::inline())?
+        {
             Some(condition) => condition,
             None => return syntax_error!(ExpectedIfCondition, self),
         };
@@ -1982,7 +2960,7 @@ impl<'source> Parser<'source> {
                     }
 
                     if let Some(else_if_condition) =
-                        self.parse_expression(&mut ExpressionContext::inline())?
+                        self.parse_expression_with_context(&mut ExpressionContext::inline())?
                     {
                         if let Some(else_if_block) = self.parse_indented_block()? {
                             else_if_blocks.push((else_if_condition, else_if_block));
@@ -2045,7 +3023,7 @@ impl<'source> Parser<'source> {
         let mut arms = Vec::new();
 
         while self.peek_token().is_some() {
-            let condition = self.parse_expression(&mut ExpressionContext::inline())?;
+            let condition = self.parse_expression_with_context(&mut ExpressionContext::inline())?;
 
             let arm_body = match self.peek_next_token_on_same_line() {
                 Some(Token::Else) => {
@@ -2177,7 +3155,7 @@ impl<'source> Parser<'source> {
                 if self.peek_next_token_on_same_line() == Some(Token::If) {
                     self.consume_next_token_on_same_line();
 
-                    match self.parse_expression(&mut ExpressionContext::inline())? {
+                    match self.parse_expression_with_context(&mut ExpressionContext::inline())? {
                         Some(expression) => Some(expression),
                         None => return syntax_error!(ExpectedMatchCondition, self),
                     }
@@ -2284,8 +3262,9 @@ impl<'source> Parser<'source> {
                         let result = if self.peek_token() == Some(Ellipsis) {
                             self.consume_token();
                             if in_nested_patterns {
-                                self.frame_mut()?.ids_assigned_in_scope.insert(id);
-                                self.push_node(Node::Ellipsis(Some(id)))?
+                                let ellipsis_node = self.push_node(Node::Ellipsis(Some(id)))?;
+                                self.frame_mut()?.add_local_node(ellipsis_node, id);
+                                ellipsis_node
                             } else {
                                 return syntax_error!(MatchEllipsisOutsideOfNestedPatterns, self);
                             }
@@ -2293,9 +3272,10 @@ impl<'source> Parser<'source> {
                             let id_node = self.push_node(Node::Id(id))?;
                             if self.next_token_is_lookup_start(&pattern_context) {
                                 self.frame_mut()?.add_id_access(id);
+                                self.frame_mut()?.add_access_node(id_node, id);
                                 self.parse_lookup(id_node, &mut pattern_context)?
                             } else {
-                                self.frame_mut()?.ids_assigned_in_scope.insert(id);
+                                self.frame_mut()?.add_local_node(id_node, id);
                                 id_node
                             }
                         };
@@ -2347,18 +3327,20 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_nested_match_patterns(&mut self) -> Result<Vec<AstIndex>, ParserError> {
-        let mut result = vec![];
+        self.with_nested_depth(|parser| {
+            let mut result = vec![];
 
-        while let Some(pattern) = self.parse_match_pattern(true)? {
-            result.push(pattern);
+            while let Some(pattern) = parser.parse_match_pattern(true)? {
+                result.push(pattern);
 
-            if self.peek_next_token_on_same_line() != Some(Token::Comma) {
-                break;
+                if parser.peek_next_token_on_same_line() != Some(Token::Comma) {
+                    break;
+                }
+                parser.consume_next_token_on_same_line();
             }
-            self.consume_next_token_on_same_line();
-        }
 
-        Ok(result)
+            Ok(result)
+        })
     }
 
     fn parse_import_expression(
@@ -2399,20 +3381,21 @@ impl<'source> Parser<'source> {
         }
 
         // Mark any imported ids as locally assigned
+        let mut imported_ids = Vec::with_capacity(items.len());
         for item in items.iter() {
             match item.last() {
                 Some(ImportItemNode::Id(id)) => {
-                    self.frame_mut()?.ids_assigned_in_scope.insert(*id);
+                    self.frame_mut()?.add_local(*id, None);
+                    imported_ids.push(*id);
                 }
                 Some(ImportItemNode::Str(_)) => {}
                 None => return internal_error!(ExpectedIdInImportItem, self),
             };
         }
 
-        Ok(Some(self.push_node_with_start_span(
-            Node::Import { from, items },
-            start_span,
-        )?))
+        let result = self.push_node_with_start_span(Node::Import { from, items }, start_span)?;
+        self.frame_mut()?.set_definitions(&imported_ids, result);
+        Ok(Some(result))
     }
 
     fn parse_try_expression(
@@ -2445,7 +3428,7 @@ impl<'source> Parser<'source> {
         {
             match catch_arg {
                 ConstantIndexOrWildcard::Index(id_index) => {
-                    self.frame_mut()?.ids_assigned_in_scope.insert(id_index);
+                    self.frame_mut()?.add_local(id_index, None);
                     Some(id_index)
                 }
 
@@ -2483,6 +3466,10 @@ impl<'source> Parser<'source> {
             start_span,
         )?;
 
+        if let Some(catch_arg) = catch_arg {
+            self.frame_mut()?.set_definitions(&[catch_arg], result);
+        }
+
         Ok(Some(result))
     }
 
@@ -2594,13 +3581,17 @@ impl<'source> Parser<'source> {
             return internal_error!(UnexpectedToken, self);
         }
 
+        let start_span = self.current_span();
+
         let expression_context = ExpressionContext {
             allow_space_separated_call: true,
             ..*context
         };
         let mut expressions = vec![];
         let mut encountered_comma = false;
-        while let Some(expression) = self.parse_expression(&mut expression_context.clone())? {
+        while let Some(expression) =
+            self.parse_expression_with_context(&mut expression_context.clone())?
+        {
             expressions.push(expression);
 
             if self.peek_next_token_on_same_line() == Some(Token::Comma) {
@@ -2611,16 +3602,17 @@ impl<'source> Parser<'source> {
             }
         }
 
-        let expressions_node = match expressions.as_slice() {
-            [] => self.push_node(Node::Empty)?,
-            [single_expression] if !encountered_comma => {
-                self.push_node(Node::Nested(*single_expression))?
-            }
-            _ => self.push_node(Node::Tuple(expressions))?,
-        };
-
         if let Some(RoundClose) = self.peek_token() {
             self.consume_token();
+
+            let expressions_node = match expressions.as_slice() {
+                [] => self.push_node_with_start_span(Node::Empty, start_span)?,
+                [single_expression] if !encountered_comma => {
+                    self.push_node_with_start_span(Node::Nested(*single_expression), start_span)?
+                }
+                _ => self.push_node_with_start_span(Node::Tuple(expressions), start_span)?,
+            };
+
             let result = self.check_for_lookup_after_node(expressions_node, context)?;
             Ok(Some(result))
         } else {
@@ -2644,138 +3636,38 @@ impl<'source> Parser<'source> {
 
         let string_quote = self.consume_next_token(context).unwrap();
 
+        // Strings delimited with triple quotes have their common indentation removed
+        let dedent = self.lexer.slice().len() == 3;
+
         let start_span = self.current_span();
 
-        let mut nodes = Vec::new();
+        let mut segments = Vec::new();
 
         while let Some(next_token) = self.consume_token() {
             match next_token {
                 StringLiteral => {
                     let string_literal = self.lexer.slice();
-
-                    let mut literal = String::with_capacity(string_literal.len());
-                    let mut chars = string_literal.chars().peekable();
-
-                    while let Some(c) = chars.next() {
-                        match c {
-                            '\\' => match chars.next() {
-                                Some('\n') | Some('\r') => {
-                                    while let Some(c) = chars.peek() {
-                                        if c.is_whitespace() {
-                                            chars.next();
-                                        } else {
-                                            break;
-                                        }
-                                    }
-                                }
-                                Some('\\') => literal.push('\\'),
-                                Some('\'') => literal.push('\''),
-                                Some('"') => literal.push('"'),
-                                Some('n') => literal.push('\n'),
-                                Some('r') => literal.push('\r'),
-                                Some('t') => literal.push('\t'),
-                                Some('x') => match chars.next() {
-                                    Some(c1) if c1.is_ascii_hexdigit() => match chars.next() {
-                                        Some(c2) if c2.is_ascii_hexdigit() => {
-                                            // is_ascii_hexdigit already checked
-                                            let d1 = c1.to_digit(16).unwrap();
-                                            let d2 = c2.to_digit(16).unwrap();
-                                            let d = d1 * 16 + d2;
-                                            if d <= 0x7f {
-                                                literal.push(char::from_u32(d).unwrap());
-                                            } else {
-                                                return syntax_error!(
-                                                    AsciiEscapeCodeOutOfRange,
-                                                    self
-                                                );
-                                            }
-                                        }
-                                        Some(_) => {
-                                            return syntax_error!(
-                                                UnexpectedCharInNumericEscapeCode,
-                                                self
-                                            )
-                                        }
-                                        None => {
-                                            return syntax_error!(
-                                                UnterminatedNumericEscapeCode,
-                                                self
-                                            )
-                                        }
-                                    },
-                                    Some(_) => {
-                                        return syntax_error!(
-                                            UnexpectedCharInNumericEscapeCode,
-                                            self
-                                        )
-                                    }
-                                    None => {
-                                        return syntax_error!(UnterminatedNumericEscapeCode, self)
-                                    }
-                                },
-                                Some('u') => match chars.next() {
-                                    Some('{') => {
-                                        let mut code = 0;
-
-                                        while let Some(c) = chars.peek().cloned() {
-                                            if c.is_ascii_hexdigit() {
-                                                chars.next();
-                                                code *= 16;
-                                                code += c.to_digit(16).unwrap();
-                                            } else {
-                                                break;
-                                            }
-                                        }
-
-                                        match chars.next() {
-                                            Some('}') => match char::from_u32(code) {
-                                                Some(c) => literal.push(c),
-                                                None => {
-                                                    return syntax_error!(
-                                                        UnicodeEscapeCodeOutOfRange,
-                                                        self
-                                                    );
-                                                }
-                                            },
-                                            Some(_) => {
-                                                return syntax_error!(
-                                                    UnexpectedCharInNumericEscapeCode,
-                                                    self
-                                                );
-                                            }
-                                            None => {
-                                                return syntax_error!(
-                                                    UnterminatedNumericEscapeCode,
-                                                    self
-                                                )
-                                            }
-                                        }
-                                    }
-                                    Some(_) => {
-                                        return syntax_error!(
-                                            UnexpectedCharInNumericEscapeCode,
-                                            self
-                                        )
-                                    }
-                                    None => {
-                                        return syntax_error!(UnterminatedNumericEscapeCode, self)
-                                    }
-                                },
-                                _ => return syntax_error!(UnexpectedEscapeInString, self),
-                            },
-                            _ => literal.push(c),
-                        }
+                    let literal_start = self.current_span().start;
+                    if dedent {
+                        // The literal is processed once the string's indentation is known,
+                        // but any errors in escape codes are reported with their positions in
+                        // the original source.
+                        self.parse_string_literal(string_literal, literal_start)?;
+                        segments.push(StringSegment::Raw(string_literal.to_string()));
+                    } else {
+                        let literal = self.parse_string_literal(string_literal, literal_start)?;
+                        let node = StringNode::Literal(self.add_string_constant(&literal)?);
+                        segments.push(StringSegment::Node(node));
                     }
-
-                    nodes.push(StringNode::Literal(self.add_string_constant(&literal)?));
                 }
                 Dollar => match self.peek_token() {
                     Some(Id) => {
                         self.consume_token();
-                        let id = self.add_string_constant(self.lexer.slice())?;
+                        let id = self.add_string_constant(self.lexer.id_name())?;
                         self.frame_mut()?.add_id_access(id);
                         let id_node = self.push_node(Node::Id(id))?;
-                        nodes.push(StringNode::Expr(id_node));
+                        self.frame_mut()?.add_access_node(id_node, id);
+                        segments.push(StringSegment::Node(StringNode::Expr(id_node)));
                     }
                     Some(CurlyOpen) => {
                         self.consume_token();
@@ -2783,7 +3675,7 @@ impl<'source> Parser<'source> {
                         if let Some(expression) = self
                             .parse_expressions(&mut ExpressionContext::inline(), TempResult::No)?
                         {
-                            nodes.push(StringNode::Expr(expression));
+                            segments.push(StringSegment::Node(StringNode::Expr(expression)));
                         } else {
                             return syntax_error!(ExpectedExpression, self);
                         }
@@ -2804,6 +3696,27 @@ impl<'source> Parser<'source> {
                         QuotationMark::Double
                     };
 
+                    let segments = if dedent {
+                        dedent_string_segments(segments)
+                    } else {
+                        segments
+                    };
+
+                    let mut nodes = Vec::with_capacity(segments.len());
+                    for segment in segments {
+                        match segment {
+                            StringSegment::Raw(string_literal) => {
+                                let literal = self
+                                    .parse_string_literal(&string_literal, Position::default())?;
+                                if !literal.is_empty() {
+                                    let constant = self.add_string_constant(&literal)?;
+                                    nodes.push(StringNode::Literal(constant));
+                                }
+                            }
+                            StringSegment::Node(node) => nodes.push(node),
+                        }
+                    }
+
                     if nodes.is_empty() {
                         nodes.push(StringNode::Literal(self.add_string_constant("")?));
                     }
@@ -2823,6 +3736,129 @@ impl<'source> Parser<'source> {
         syntax_error!(UnterminatedString, self)
     }
 
+    // Processes the escape codes in a string literal
+    //
+    // The literal's start position is used to provide precise spans for errors in escape codes.
+    fn parse_string_literal(
+        &self,
+        string_literal: &str,
+        start: Position,
+    ) -> Result<String, ParserError> {
+        let mut literal = String::with_capacity(string_literal.len());
+        let mut chars = StringLiteralChars::new(string_literal, start);
+
+        loop {
+            let escape_start = chars.position;
+
+            match chars.next() {
+                Some('\\') => {}
+                // CRLF line endings are normalized to LF
+                Some('\r') if chars.peek() == Some('\n') => continue,
+                Some(c) => {
+                    literal.push(c);
+                    continue;
+                }
+                None => break,
+            }
+
+            // The span from the start of the escape code up to the last consumed character
+            macro_rules! escape_error {
+                ($error:ident) => {
+                    syntax_error_with_span!(
+                        $error,
+                        Span {
+                            start: escape_start,
+                            end: chars.position,
+                        }
+                    )
+                };
+            }
+
+            match chars.next() {
+                Some('\n') | Some('\r') => {
+                    while let Some(c) = chars.peek() {
+                        if c.is_whitespace() {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                Some('\\') => literal.push('\\'),
+                Some('\'') => literal.push('\''),
+                Some('"') => literal.push('"'),
+                Some('$') => literal.push('$'),
+                Some('0') => literal.push('\0'),
+                Some('e') => literal.push('\x1b'),
+                Some('n') => literal.push('\n'),
+                Some('r') => literal.push('\r'),
+                Some('t') => literal.push('\t'),
+                Some('x') => match chars.next() {
+                    Some(c1) if c1.is_ascii_hexdigit() => match chars.next() {
+                        Some(c2) if c2.is_ascii_hexdigit() => {
+                            // is_ascii_hexdigit already checked
+                            let d1 = c1.to_digit(16).unwrap();
+                            let d2 = c2.to_digit(16).unwrap();
+                            let d = d1 * 16 + d2;
+                            if d <= 0x7f {
+                                literal.push(char::from_u32(d).unwrap());
+                            } else {
+                                return escape_error!(AsciiEscapeCodeOutOfRange);
+                            }
+                        }
+                        Some(_) => return escape_error!(UnexpectedCharInNumericEscapeCode),
+                        None => return escape_error!(UnterminatedNumericEscapeCode),
+                    },
+                    Some(_) => return escape_error!(UnexpectedCharInNumericEscapeCode),
+                    None => return escape_error!(UnterminatedNumericEscapeCode),
+                },
+                Some('u') => match chars.next() {
+                    Some('{') => {
+                        let mut contents = String::new();
+                        let terminated = loop {
+                            match chars.next() {
+                                Some('}') => break true,
+                                Some(c) => contents.push(c),
+                                None => break false,
+                            }
+                        };
+
+                        if !terminated {
+                            return escape_error!(UnterminatedNumericEscapeCode);
+                        }
+
+                        if contents.chars().all(|c| c.is_ascii_hexdigit()) {
+                            let code = if contents.is_empty() {
+                                Some(0)
+                            } else {
+                                u32::from_str_radix(&contents, 16).ok()
+                            };
+                            match code.and_then(char::from_u32) {
+                                Some(c) => literal.push(c),
+                                None => return escape_error!(UnicodeEscapeCodeOutOfRange),
+                            }
+                        } else if contents
+                            .chars()
+                            .all(|c| matches!(c, 'A'..='Z' | '0'..='9' | ' ' | '-'))
+                        {
+                            match named_unicode_char(&contents) {
+                                Some(c) => literal.push(c),
+                                None => return escape_error!(UnknownUnicodeEscapeName),
+                            }
+                        } else {
+                            return escape_error!(UnexpectedCharInNumericEscapeCode);
+                        }
+                    }
+                    Some(_) => return escape_error!(UnexpectedCharInNumericEscapeCode),
+                    None => return escape_error!(UnterminatedNumericEscapeCode),
+                },
+                _ => return escape_error!(UnexpectedEscapeInString),
+            }
+        }
+
+        Ok(literal)
+    }
+
     fn push_ast_op(
         &mut self,
         op: Token,
@@ -2848,6 +3884,8 @@ impl<'source> Parser<'source> {
             And => AstBinaryOp::And,
             Or => AstBinaryOp::Or,
 
+            In => AstBinaryOp::In,
+
             Pipe => AstBinaryOp::Pipe,
 
             _ => unreachable!(),
@@ -2924,6 +3962,17 @@ impl<'source> Parser<'source> {
     }
 
     fn push_node_with_span(&mut self, node: Node, span: Span) -> Result<AstIndex, ParserError> {
+        let kind = node.kind();
+        if !self.settings.node_filter.allows(kind) {
+            let error = ParserError::new(SyntaxError::DisabledNode(kind).into(), span);
+
+            #[cfg(feature = "panic_on_parser_error")]
+            panic!("{}", error);
+
+            #[cfg(not(feature = "panic_on_parser_error"))]
+            return Err(error);
+        }
+
         self.ast.push(node, span)
     }
 
@@ -3112,9 +4161,10 @@ fn operator_precedence(op: Token) -> Option<(u8, u8)> {
         And => (5, 6),
         // Chained comparisons require right-associativity
         Equal | NotEqual => (8, 7),
-        Greater | GreaterOrEqual | Less | LessOrEqual => (10, 9),
-        Add | Subtract => (11, 12),
-        Multiply | Divide | Modulo => (13, 14),
+        In => (9, 10),
+        Greater | GreaterOrEqual | Less | LessOrEqual => (12, 11),
+        Add | Subtract => (13, 14),
+        Multiply | Divide | Modulo => (15, 16),
         _ => return None,
     };
     Some(priority)
@@ -3127,3 +4177,202 @@ struct PeekInfo {
     indent: usize,
     peek_count: usize,
 }
+
+// Iterates over the characters in a string literal, keeping track of their positions
+//
+// `position` refers to the end of the most recently consumed character, following the
+// lexer's conventions, i.e. columns are counted using each character's display width.
+struct StringLiteralChars<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+}
+
+impl<'a> StringLiteralChars<'a> {
+    fn new(string_literal: &'a str, start: Position) -> Self {
+        Self {
+            chars: string_literal.chars().peekable(),
+            position: start,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+}
+
+impl<'a> Iterator for StringLiteralChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        self.position.offset += c.len_utf8() as u32;
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += c.width().unwrap_or(0) as u32;
+        }
+
+        Some(c)
+    }
+}
+
+// Characters that can be referred to by name in unicode escape codes, e.g. `\u{ESCAPE}`
+//
+// Names are taken from the Unicode standard, along with their common abbreviations.
+// Abbreviations that could be confused with hex codes (e.g. `FF` for form feed) are excluded.
+const NAMED_UNICODE_CHARS: &[(&str, char)] = &[
+    ("NULL", '\u{0}'),
+    ("NUL", '\u{0}'),
+    ("ALERT", '\u{7}'),
+    ("BEL", '\u{7}'),
+    ("BACKSPACE", '\u{8}'),
+    ("BS", '\u{8}'),
+    ("CHARACTER TABULATION", '\u{9}'),
+    ("TAB", '\u{9}'),
+    ("LINE FEED", '\u{a}'),
+    ("LF", '\u{a}'),
+    ("LINE TABULATION", '\u{b}'),
+    ("VT", '\u{b}'),
+    ("FORM FEED", '\u{c}'),
+    ("CARRIAGE RETURN", '\u{d}'),
+    ("CR", '\u{d}'),
+    ("ESCAPE", '\u{1b}'),
+    ("ESC", '\u{1b}'),
+    ("SPACE", '\u{20}'),
+    ("SP", '\u{20}'),
+    ("DELETE", '\u{7f}'),
+    ("DEL", '\u{7f}'),
+    ("NO-BREAK SPACE", '\u{a0}'),
+    ("NBSP", '\u{a0}'),
+    ("SOFT HYPHEN", '\u{ad}'),
+    ("SHY", '\u{ad}'),
+    ("ZERO WIDTH SPACE", '\u{200b}'),
+    ("ZWSP", '\u{200b}'),
+    ("ZERO WIDTH NON-JOINER", '\u{200c}'),
+    ("ZWNJ", '\u{200c}'),
+    ("ZERO WIDTH JOINER", '\u{200d}'),
+    ("ZWJ", '\u{200d}'),
+    ("LEFT-TO-RIGHT MARK", '\u{200e}'),
+    ("LRM", '\u{200e}'),
+    ("RIGHT-TO-LEFT MARK", '\u{200f}'),
+    ("RLM", '\u{200f}'),
+    ("LINE SEPARATOR", '\u{2028}'),
+    ("PARAGRAPH SEPARATOR", '\u{2029}'),
+    ("WORD JOINER", '\u{2060}'),
+    ("WJ", '\u{2060}'),
+    ("ZERO WIDTH NO-BREAK SPACE", '\u{feff}'),
+    ("BYTE ORDER MARK", '\u{feff}'),
+    ("BOM", '\u{feff}'),
+    ("REPLACEMENT CHARACTER", '\u{fffd}'),
+];
+
+fn named_unicode_char(name: &str) -> Option<char> {
+    NAMED_UNICODE_CHARS
+        .iter()
+        .find(|(char_name, _)| *char_name == name)
+        .map(|(_, c)| *c)
+}
+
+// A segment of a string that's being parsed
+enum StringSegment {
+    // A string literal that hasn't had its escape codes processed yet
+    Raw(String),
+    // A string node that's ready to be added to the string
+    Node(StringNode),
+}
+
+// Removes the common indentation from the lines of a triple-quoted string
+//
+// A line break directly following the opening quotes is removed, as is any indentation preceding
+// the closing quotes. Lines that only contain whitespace don't affect the common indentation.
+fn dedent_string_segments(mut segments: Vec<StringSegment>) -> Vec<StringSegment> {
+    use StringSegment::*;
+
+    let is_indent = |c: char| c == ' ' || c == '\t';
+
+    if let Some(Raw(first)) = segments.first_mut() {
+        if first.starts_with('\n') {
+            first.drain(..1);
+        } else if first.starts_with("\r\n") {
+            first.drain(..2);
+        }
+    }
+
+    if let Some(Raw(last)) = segments.last_mut() {
+        let trimmed_len = last.trim_end_matches(is_indent).len();
+        if last[..trimmed_len].ends_with('\n') {
+            last.truncate(trimmed_len);
+        }
+    }
+
+    // Find the smallest indentation of the lines that contain more than whitespace.
+    // line_indent is Some while the current line's indentation is being counted.
+    let mut common_indent: Option<usize> = None;
+    let mut line_indent = Some(0);
+    let mut update_common_indent = |indent: usize| {
+        common_indent = Some(common_indent.map_or(indent, |common| common.min(indent)));
+    };
+
+    for segment in segments.iter() {
+        match segment {
+            Raw(literal) => {
+                for c in literal.chars() {
+                    match line_indent {
+                        Some(indent) if is_indent(c) => line_indent = Some(indent + 1),
+                        Some(_) if c == '\n' || c == '\r' => line_indent = Some(0),
+                        Some(indent) => {
+                            update_common_indent(indent);
+                            line_indent = None;
+                        }
+                        None if c == '\n' => line_indent = Some(0),
+                        None => {}
+                    }
+                }
+            }
+            Node(_) => {
+                if let Some(indent) = line_indent.take() {
+                    update_common_indent(indent);
+                }
+            }
+        }
+    }
+
+    let common_indent = match common_indent {
+        Some(indent) if indent > 0 => indent,
+        _ => return segments,
+    };
+
+    // Remove the common indentation from each line.
+    // removed_indent is Some while indentation is being removed from the start of a line.
+    let mut removed_indent = Some(0);
+
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            Raw(literal) => {
+                let mut result = String::with_capacity(literal.len());
+
+                for c in literal.chars() {
+                    match removed_indent {
+                        Some(removed) if removed < common_indent && is_indent(c) => {
+                            removed_indent = Some(removed + 1);
+                            continue;
+                        }
+                        _ if c == '\n' => removed_indent = Some(0),
+                        Some(_) if c == '\r' => {}
+                        _ => removed_indent = None,
+                    }
+                    result.push(c);
+                }
+
+                Raw(result)
+            }
+            node => {
+                removed_indent = None;
+                node
+            }
+        })
+        .collect()
+}
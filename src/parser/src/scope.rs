@@ -0,0 +1,118 @@
+//! Scope analysis, see [Parser::parse_with_scopes](crate::Parser::parse_with_scopes)
+
+use {
+    crate::{AstIndex, ConstantIndex},
+    std::collections::HashMap,
+};
+
+/// A local value, identified by its frame and slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LocalId {
+    /// The node that contains the local's frame, either a [Function](crate::Function) or the
+    /// script's entry point
+    pub frame: AstIndex,
+    /// The index of the local in its frame
+    ///
+    /// Slots are allocated in the order in which locals are first assigned, starting with the
+    /// function's arguments.
+    pub slot: usize,
+    /// The node where the local is first assigned
+    ///
+    /// IDs that don't have their own node refer to the node that contains them,
+    /// e.g. the arguments of a `for` loop refer to the loop's node.
+    pub definition: AstIndex,
+}
+
+/// The result of resolving an ID that's referenced by a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IdResolution {
+    /// The ID refers to a local value in the same frame as the node
+    Local(LocalId),
+    /// The ID refers to a local value in an enclosing frame, which is captured by the node's
+    /// function
+    Capture(LocalId),
+    /// The ID isn't assigned in any of the node's frames
+    ///
+    /// Globals are looked up at runtime in the module's exports, and then in the prelude.
+    /// Exported assignments (e.g. `export x = 1`) also resolve to globals.
+    Global,
+}
+
+/// The results of scope analysis, mapping nodes that reference IDs to their resolutions
+///
+/// Resolutions are provided for [Id](crate::Node::Id), [NamedCall](crate::Node::NamedCall),
+/// and [Ellipsis](crate::Node::Ellipsis) nodes.
+#[derive(Clone, Debug, Default)]
+pub struct Scopes {
+    resolutions: HashMap<AstIndex, IdResolution>,
+}
+
+impl Scopes {
+    /// Returns the resolution of the ID that's referenced by the node
+    ///
+    /// `None` is returned if the node doesn't reference an ID.
+    pub fn resolve(&self, node: AstIndex) -> Option<IdResolution> {
+        self.resolutions.get(&node).copied()
+    }
+
+    /// Returns the nodes that refer to the given local, in the order that they appear in the Ast
+    ///
+    /// Captures of the local in nested functions are included.
+    pub fn references(&self, local: &LocalId) -> Vec<AstIndex> {
+        let mut result = self
+            .resolutions
+            .iter()
+            .filter_map(|(node, resolution)| match resolution {
+                IdResolution::Local(id) | IdResolution::Capture(id) if id == local => Some(*node),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        result.sort_unstable();
+        result
+    }
+
+    /// Returns an iterator over all of the resolved nodes, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (AstIndex, IdResolution)> + '_ {
+        self.resolutions
+            .iter()
+            .map(|(node, resolution)| (*node, *resolution))
+    }
+}
+
+// The resolution of a node that's recorded in the parser's frames, with locals referred to by
+// their slot in the frame
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum FrameResolution {
+    Local(usize),
+    Capture(usize),
+    Global,
+}
+
+impl Scopes {
+    // Adds the resolutions that were recorded in a frame, once the frame's node is known
+    pub(crate) fn add_frame(
+        &mut self,
+        frame: AstIndex,
+        locals: &[(ConstantIndex, Option<AstIndex>)],
+        resolutions: Vec<(AstIndex, FrameResolution)>,
+    ) {
+        let local_id = |slot: usize| -> Option<LocalId> {
+            Some(LocalId {
+                frame,
+                slot,
+                definition: locals.get(slot)?.1?,
+            })
+        };
+
+        for (node, resolution) in resolutions {
+            let resolution = match resolution {
+                FrameResolution::Local(slot) => local_id(slot).map(IdResolution::Local),
+                FrameResolution::Capture(slot) => local_id(slot).map(IdResolution::Capture),
+                FrameResolution::Global => Some(IdResolution::Global),
+            };
+            if let Some(resolution) = resolution {
+                self.resolutions.insert(node, resolution);
+            }
+        }
+    }
+}
@@ -0,0 +1,631 @@
+//! A lossless syntax tree for Koto scripts
+//!
+//! The [Ast] produced by the [Parser] discards information that isn't needed by the compiler,
+//! like whitespace, comments, and the exact positions of tokens. Tools like formatters and editors
+//! need a representation of the source that preserves every byte, which is provided by
+//! [SyntaxTree].
+//!
+//! The tree follows the 'green/red' design used by the rowan crate:
+//!   - Green nodes are immutable, don't know their position in the source, and are shared via
+//!     reference counting, so unchanged parts of a tree can be reused after an edit.
+//!   - [SyntaxNode]s are lightweight views onto the green nodes that provide source offsets.
+//!
+//! The syntax tree captures the structure of the script's layout, grouping tokens into lines,
+//! indented blocks, bracketed groups, and strings.
+//!
+//! The syntax tree is built alongside the [Ast] rather than replacing it: the [Parser] produces
+//! the Ast directly from the script's source, and the Ast isn't lowered from the syntax tree.
+//! Both are produced from the same text, so the byte range of a node's span in the Ast
+//! (see [Span::byte_range]) can be used to find the corresponding part of the syntax tree.
+//!
+//! [Ast]: crate::Ast
+//! [Parser]: crate::Parser
+//! [Span::byte_range]: crate::Span::byte_range
+
+use {
+    koto_lexer::{Token, TokenStream},
+    std::{ops::Range, sync::Arc},
+};
+
+/// The kinds of node that can appear in a [SyntaxTree]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+    /// The root of the tree, containing the script's top-level lines
+    Root,
+    /// A line of code, along with any indented block that follows it
+    Line,
+    /// A series of lines that are indented further than the line that precedes them
+    Block,
+    /// Tokens enclosed by `(` and `)`
+    Parentheses,
+    /// Tokens enclosed by `[` and `]`
+    SquareBrackets,
+    /// Tokens enclosed by `{` and `}`
+    CurlyBraces,
+    /// A string, including its quotation marks and any template expressions
+    Str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct GreenNode {
+    kind: SyntaxKind,
+    // The length of the node's text in bytes
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(GreenToken),
+}
+
+impl GreenElement {
+    fn len(&self) -> usize {
+        match self {
+            Self::Node(node) => node.len,
+            Self::Token(token) => token.text.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct GreenToken {
+    kind: Token,
+    text: String,
+}
+
+/// A lossless syntax tree for a Koto script
+///
+/// Every token produced by the lexer is included in the tree, including whitespace, newlines,
+/// and comments, so the tree's text is always identical to the source it was parsed from.
+///
+/// Trivia (whitespace, newlines, and comments) is attached to the tree's lines:
+///   - Comments and blank lines that precede a line are included at the start of the line,
+///     and are available via [SyntaxNode::leading_trivia].
+///   - Trivia that follows a line's tokens up to the end of the line, e.g. a trailing comment,
+///     is included at the end of the line, and is available via [SyntaxNode::trailing_trivia].
+///   - Trivia at the end of the script that isn't followed by a line is attached to the root.
+///
+/// [SyntaxNode::line_containing] can be used to find the line that contains a node in an
+/// [Ast](crate::Ast), allowing the node's comments to be preserved when rewriting code.
+///
+/// Scripts that contain errors still produce a complete tree.
+///
+/// # Example
+///
+/// ```
+/// use koto_parser::{SyntaxKind, SyntaxTree};
+///
+/// let source = "\
+/// f = |x|
+///   x * x # square
+/// ";
+/// let tree = SyntaxTree::parse(source);
+/// assert_eq!(tree.text(), source);
+///
+/// let line = tree.root().child_nodes().next().unwrap();
+/// assert_eq!(line.kind(), SyntaxKind::Line);
+///
+/// let edited = tree.edit(14..15, "2");
+/// assert_eq!(edited.text(), "f = |x|\n  x * 2 # square\n");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxTree {
+    root: Arc<GreenNode>,
+}
+
+impl SyntaxTree {
+    /// Produces a syntax tree for the given script
+    pub fn parse(source: &str) -> Self {
+        let (root, _) = build_tree(source);
+        Self {
+            root: Arc::new(root),
+        }
+    }
+
+    /// Returns the root node of the tree
+    pub fn root(&self) -> SyntaxNode<'_> {
+        SyntaxNode {
+            green: &self.root,
+            offset: 0,
+        }
+    }
+
+    /// Returns the text of the script represented by the tree
+    pub fn text(&self) -> String {
+        self.root().text()
+    }
+
+    /// Produces a new tree with the given byte range of the script replaced
+    ///
+    /// Only the top-level lines that are affected by the edit are reparsed,
+    /// the rest of the tree is shared with the original.
+    ///
+    /// Panics if the range is out of bounds or doesn't fall on a char boundary.
+    pub fn edit(&self, range: Range<usize>, replacement: &str) -> Self {
+        let children = &self.root.children;
+
+        // The offsets of each of the root's children
+        let mut offsets = Vec::with_capacity(children.len() + 1);
+        let mut offset = 0;
+        for child in children.iter() {
+            offsets.push(offset);
+            offset += child.len();
+        }
+        offsets.push(offset);
+
+        assert!(range.start <= range.end && range.end <= self.root.len);
+
+        if children.is_empty() {
+            let mut text = self.text();
+            text.replace_range(range, replacement);
+            return Self::parse(&text);
+        }
+
+        // The child that contains the edit's start position needs to be reparsed,
+        // along with the preceding child if the edit is at the start of a line or in its leading
+        // trivia, given that the edit might change the indentation that follows the preceding line.
+        let child_containing = |position: usize| {
+            offsets[1..]
+                .iter()
+                .position(|end| position < *end)
+                .unwrap_or(children.len() - 1)
+        };
+        let mut first = child_containing(range.start.saturating_sub(1));
+        if first > 0 && range.start <= offsets[first] + leading_trivia_len(&children[first]) {
+            first -= 1;
+        }
+        let mut last = child_containing(range.end);
+
+        let region_start = offsets[first];
+
+        loop {
+            let mut region_text = String::new();
+            for child in children[first..=last].iter() {
+                write_text(child, &mut region_text);
+            }
+            region_text.replace_range(
+                range.start - region_start..range.end - region_start,
+                replacement,
+            );
+
+            let (region, ends_cleanly) = build_tree(&region_text);
+
+            // If the reparsed region doesn't end at the start of a new top-level line, then the
+            // following child needs to be included in the region. Trivia at the start of the
+            // script are children of the root node, and might now need to be attached to a line.
+            let is_followed_by_line = matches!(children.get(last + 1), Some(GreenElement::Node(_)));
+            if (ends_cleanly && is_followed_by_line) || last == children.len() - 1 {
+                let mut new_children = Vec::with_capacity(children.len());
+                new_children.extend(children[..first].iter().cloned());
+                new_children.extend(region.children);
+                new_children.extend(children[last + 1..].iter().cloned());
+
+                return Self {
+                    root: Arc::new(GreenNode {
+                        kind: SyntaxKind::Root,
+                        len: new_children.iter().map(|child| child.len()).sum(),
+                        children: new_children,
+                    }),
+                };
+            }
+
+            last += 1;
+        }
+    }
+}
+
+/// A node in a [SyntaxTree]
+#[derive(Clone, Copy, Debug)]
+pub struct SyntaxNode<'a> {
+    green: &'a GreenNode,
+    offset: usize,
+}
+
+impl<'a> SyntaxNode<'a> {
+    /// The node's kind
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    /// The byte range of the script that's covered by the node
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.len
+    }
+
+    /// The node's text
+    pub fn text(&self) -> String {
+        let mut result = String::with_capacity(self.green.len);
+        for child in self.green.children.iter() {
+            write_text(child, &mut result);
+        }
+        result
+    }
+
+    /// Returns an iterator over the node's child nodes and tokens
+    pub fn children(&self) -> impl Iterator<Item = SyntaxElement<'a>> {
+        let mut offset = self.offset;
+        self.green.children.iter().map(move |child| {
+            let result = SyntaxElement::new(child, offset);
+            offset += child.len();
+            result
+        })
+    }
+
+    /// Returns an iterator over the node's child nodes, skipping tokens
+    pub fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode<'a>> {
+        self.children().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Returns all of the tokens contained in the node and its descendants, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken<'a>> {
+        let mut result = Vec::new();
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => result.extend(node.tokens()),
+                SyntaxElement::Token(token) => result.push(token),
+            }
+        }
+        result
+    }
+
+    /// Returns the token that contains the given byte offset
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken<'a>> {
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) if node.range().contains(&offset) => {
+                    return node.token_at_offset(offset)
+                }
+                SyntaxElement::Token(token) if token.range.contains(&offset) => return Some(token),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Returns the trivia at the start of the node
+    ///
+    /// For lines, this includes any comments and blank lines that precede the line.
+    pub fn leading_trivia(&self) -> Vec<SyntaxToken<'a>> {
+        let mut result = Vec::new();
+        for child in self.children() {
+            match child {
+                SyntaxElement::Token(token) if token.kind.is_trivia() => result.push(token),
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// Returns the trivia at the end of the node, preceding any nested [SyntaxKind::Block]
+    ///
+    /// For lines, this includes any comment that follows the line's tokens, along with the
+    /// newline that ends the line.
+    ///
+    /// Nodes that only contain trivia return their trivia from [Self::leading_trivia],
+    /// with no trailing trivia.
+    pub fn trailing_trivia(&self) -> Vec<SyntaxToken<'a>> {
+        let children: Vec<_> = self
+            .children()
+            .filter(|child| !matches!(child, SyntaxElement::Node(node) if node.kind() == SyntaxKind::Block))
+            .collect();
+        let leading_count = self.leading_trivia().len();
+
+        let mut result = Vec::new();
+        for child in children[leading_count..].iter().rev() {
+            match child {
+                SyntaxElement::Token(token) if token.kind.is_trivia() => result.push(token.clone()),
+                _ => break,
+            }
+        }
+        result.reverse();
+        result
+    }
+
+    /// Returns the innermost [SyntaxKind::Line] that contains the given byte range
+    ///
+    /// The byte range of a node in an [Ast](crate::Ast) can be found with
+    /// [Span::byte_range](crate::Span::byte_range).
+    pub fn line_containing(&self, range: Range<usize>) -> Option<SyntaxNode<'a>> {
+        let mut result = None;
+        let mut node = *self;
+
+        loop {
+            if node.kind() == SyntaxKind::Line {
+                result = Some(node);
+            }
+
+            let child = node.child_nodes().find(|child| {
+                let child_range = child.range();
+                child_range.start <= range.start
+                    && range.end <= child_range.end
+                    && range.start < child_range.end
+            });
+
+            match child {
+                Some(child) => node = child,
+                None => return result,
+            }
+        }
+    }
+
+    /// Returns true if both nodes share the same underlying data
+    ///
+    /// Nodes in unchanged regions of a tree are shared after calling [SyntaxTree::edit].
+    pub fn ptr_eq(&self, other: &SyntaxNode) -> bool {
+        std::ptr::eq(self.green, other.green)
+    }
+}
+
+/// A child of a [SyntaxNode], either a node or a token
+#[derive(Clone, Debug)]
+pub enum SyntaxElement<'a> {
+    /// A node containing further children
+    Node(SyntaxNode<'a>),
+    /// A token
+    Token(SyntaxToken<'a>),
+}
+
+impl<'a> SyntaxElement<'a> {
+    fn new(green: &'a GreenElement, offset: usize) -> Self {
+        match green {
+            GreenElement::Node(node) => Self::Node(SyntaxNode {
+                green: node,
+                offset,
+            }),
+            GreenElement::Token(token) => Self::Token(SyntaxToken {
+                kind: token.kind,
+                text: &token.text,
+                range: offset..offset + token.text.len(),
+            }),
+        }
+    }
+}
+
+/// A token in a [SyntaxTree]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxToken<'a> {
+    /// The token's kind
+    pub kind: Token,
+    /// The token's text
+    pub text: &'a str,
+    /// The byte range of the script that's covered by the token
+    pub range: Range<usize>,
+}
+
+// Returns the length in bytes of the trivia at the start of an element
+fn leading_trivia_len(element: &GreenElement) -> usize {
+    match element {
+        GreenElement::Node(node) => node
+            .children
+            .iter()
+            .take_while(
+                |child| matches!(child, GreenElement::Token(token) if token.kind.is_trivia()),
+            )
+            .map(|child| child.len())
+            .sum(),
+        GreenElement::Token(token) if token.kind.is_trivia() => token.text.len(),
+        GreenElement::Token(_) => 0,
+    }
+}
+
+fn write_text(element: &GreenElement, result: &mut String) {
+    match element {
+        GreenElement::Node(node) => {
+            for child in node.children.iter() {
+                write_text(child, result);
+            }
+        }
+        GreenElement::Token(token) => result.push_str(&token.text),
+    }
+}
+
+// A node that's in the process of being built
+struct OpenNode {
+    kind: SyntaxKind,
+    // The indentation of the lines in a Root or Block node
+    indent: usize,
+    children: Vec<GreenElement>,
+}
+
+// Builds the tree from a script, returning the root node
+//
+// A flag is also returned that indicates if the script ended cleanly, i.e. without any open
+// brackets or strings, and with a trailing newline that isn't followed by indentation or trivia.
+fn build_tree(source: &str) -> (GreenNode, bool) {
+    use {SyntaxKind::*, Token::*};
+
+    let mut builder = TreeBuilder {
+        stack: vec![OpenNode {
+            kind: Root,
+            indent: 0,
+            children: Vec::new(),
+        }],
+        pending_newline_indent: Some(0),
+        pending_trivia: Vec::new(),
+    };
+
+    for lexed in TokenStream::new(source) {
+        let token = lexed.token;
+
+        if token.is_trivia() {
+            if builder.pending_newline_indent.is_some() {
+                // Trivia that follows a newline is attached to the next line
+                if token.is_newline() {
+                    builder.pending_newline_indent = Some(lexed.indent);
+                }
+                builder.pending_trivia.push(GreenElement::Token(GreenToken {
+                    kind: token,
+                    text: lexed.slice.into(),
+                }));
+            } else {
+                if token.is_newline() && matches!(builder.top_kind(), Root | Line | Block) {
+                    builder.pending_newline_indent = Some(lexed.indent);
+                }
+                builder.push_token(token, lexed.slice);
+            }
+            continue;
+        }
+
+        if let Some(indent) = builder.pending_newline_indent.take() {
+            builder.start_new_line(indent);
+        }
+
+        if matches!(builder.top_kind(), Root | Block) {
+            builder.open(Line, 0);
+        }
+
+        let leading_trivia = std::mem::take(&mut builder.pending_trivia);
+        builder
+            .stack
+            .last_mut()
+            .unwrap()
+            .children
+            .extend(leading_trivia);
+
+        match token {
+            SingleQuote | DoubleQuote if builder.top_kind() == Str => {
+                builder.push_token(token, lexed.slice);
+                builder.close();
+            }
+            SingleQuote | DoubleQuote => {
+                builder.open(Str, 0);
+                builder.push_token(token, lexed.slice);
+            }
+            RoundOpen | SquareOpen | CurlyOpen => {
+                let kind = match token {
+                    RoundOpen => Parentheses,
+                    SquareOpen => SquareBrackets,
+                    _ => CurlyBraces,
+                };
+                builder.open(kind, 0);
+                builder.push_token(token, lexed.slice);
+            }
+            RoundClose | SquareClose | CurlyClose => {
+                let kind = match token {
+                    RoundClose => Parentheses,
+                    SquareClose => SquareBrackets,
+                    _ => CurlyBraces,
+                };
+                // Unmatched closing brackets are left in place as tokens
+                match builder.stack.iter().rposition(|node| node.kind == kind) {
+                    Some(position) => {
+                        while builder.stack.len() > position + 1 {
+                            builder.close();
+                        }
+                        builder.push_token(token, lexed.slice);
+                        builder.close();
+                    }
+                    None => builder.push_token(token, lexed.slice),
+                }
+            }
+            _ => builder.push_token(token, lexed.slice),
+        }
+    }
+
+    let ends_cleanly = builder.pending_newline_indent == Some(0)
+        && builder.pending_trivia.is_empty()
+        && builder
+            .stack
+            .iter()
+            .all(|node| matches!(node.kind, Root | Line | Block));
+
+    while builder.stack.len() > 1 {
+        builder.close();
+    }
+
+    // Trivia at the end of the script that isn't followed by a line is attached to the root
+    let mut root = builder.stack.pop().unwrap();
+    root.children.append(&mut builder.pending_trivia);
+    let root = GreenNode {
+        kind: root.kind,
+        len: root.children.iter().map(|child| child.len()).sum(),
+        children: root.children,
+    };
+
+    (root, ends_cleanly)
+}
+
+struct TreeBuilder {
+    // The nodes that are currently being built, with the root node at the bottom of the stack
+    stack: Vec<OpenNode>,
+    // The indentation following the most recent newline, if no other tokens have followed it yet
+    pending_newline_indent: Option<usize>,
+    // Trivia following the most recent newline, to be attached to the next line
+    pending_trivia: Vec<GreenElement>,
+}
+
+impl TreeBuilder {
+    fn top_kind(&self) -> SyntaxKind {
+        self.stack.last().unwrap().kind
+    }
+
+    fn push_token(&mut self, kind: Token, text: &str) {
+        self.stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(GreenElement::Token(GreenToken {
+                kind,
+                text: text.into(),
+            }));
+    }
+
+    fn open(&mut self, kind: SyntaxKind, indent: usize) {
+        self.stack.push(OpenNode {
+            kind,
+            indent,
+            children: Vec::new(),
+        });
+    }
+
+    fn close(&mut self) {
+        let node = self.stack.pop().unwrap();
+        let green = GreenNode {
+            kind: node.kind,
+            len: node.children.iter().map(|child| child.len()).sum(),
+            children: node.children,
+        };
+        self.stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(GreenElement::Node(Arc::new(green)));
+    }
+
+    // Called when a line starts with the given indentation
+    fn start_new_line(&mut self, indent: usize) {
+        if self.top_kind() != SyntaxKind::Line {
+            return;
+        }
+
+        // The indentation of the innermost block (the line's parent)
+        let block_indent = self.stack[self.stack.len() - 2].indent;
+
+        if indent > block_indent {
+            // The new line is the start of a block that belongs to the current line
+            self.open(SyntaxKind::Block, indent);
+        } else {
+            self.close();
+
+            // Close any blocks that are indented further than the new line,
+            // along with the lines that they belong to.
+            while let Some(OpenNode {
+                kind: SyntaxKind::Block,
+                indent: current_indent,
+                ..
+            }) = self.stack.last()
+            {
+                if *current_indent <= indent {
+                    break;
+                }
+                self.close();
+                self.close();
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Helpers for traversing and rewriting an [Ast]
+
+use crate::{Ast, AstIndex, Node, ParserError, Span};
+
+/// Callbacks that are used when walking through an [Ast], see [walk]
+///
+/// Both callbacks have default implementations that do nothing, so a visitor only needs to
+/// implement the callbacks that it's interested in.
+pub trait Visitor {
+    /// Called when a node is reached, before any of its children are visited
+    ///
+    /// Returning `false` will skip the node's children, `exit` will still be called for the node.
+    fn enter(&mut self, _ast: &Ast, _index: AstIndex, _node: &Node, _span: &Span) -> bool {
+        true
+    }
+
+    /// Called after all of the node's children have been visited
+    fn exit(&mut self, _ast: &Ast, _index: AstIndex, _node: &Node, _span: &Span) {}
+}
+
+/// Walks through the [Ast], starting from its entry point
+///
+/// The visitor's callbacks are called for each node in the tree, with child nodes being visited
+/// in the order that they appear in the source, see [Node::children].
+pub fn walk(ast: &Ast, visitor: &mut impl Visitor) {
+    if ast.entry_point().is_some() {
+        walk_node(ast, ast.entry_point_index(), visitor);
+    }
+}
+
+/// Walks through the [Ast], starting from the node with the given index
+pub fn walk_node(ast: &Ast, index: AstIndex, visitor: &mut impl Visitor) {
+    let ast_node = ast.node(index);
+    let span = ast.span(ast_node.span);
+
+    if visitor.enter(ast, index, &ast_node.node, span) {
+        for child in ast_node.node.children() {
+            walk_node(ast, child, visitor);
+        }
+    }
+
+    visitor.exit(ast, index, &ast_node.node, span);
+}
+
+/// Produces a new [Ast] by transforming each of the nodes in an existing Ast
+///
+/// The tree is rewritten starting from the leaf nodes, so by the time `transform` is called for a
+/// node, its child indices have already been updated to refer to nodes in the new Ast.
+///
+/// `transform` receives the new Ast, allowing additional nodes to be pushed to it, along with the
+/// node to be transformed and its span. The node returned by `transform` is then added to the new
+/// Ast in place of the original node.
+///
+/// Only nodes that are reachable from the entry point are included in the new Ast.
+/// The constant pool is carried over to the new Ast unchanged.
+///
+/// Note that the compiler relies on information that the parser caches in some nodes,
+/// e.g. [Function::local_count](crate::Function::local_count), which needs to be kept up to
+/// date by the transform if the rewritten Ast is going to be compiled.
+pub fn rewrite<F>(ast: &Ast, mut transform: F) -> Result<Ast, ParserError>
+where
+    F: FnMut(&mut Ast, Node, Span) -> Result<Node, ParserError>,
+{
+    let mut result = Ast::with_capacity(ast.nodes().len());
+    result.set_constants(ast.constants().clone());
+
+    if ast.entry_point().is_some() {
+        let entry_point = rewrite_node(ast, ast.entry_point_index(), &mut result, &mut transform)?;
+        result.set_entry_point(entry_point);
+    }
+
+    Ok(result)
+}
+
+fn rewrite_node<F>(
+    ast: &Ast,
+    index: AstIndex,
+    result: &mut Ast,
+    transform: &mut F,
+) -> Result<AstIndex, ParserError>
+where
+    F: FnMut(&mut Ast, Node, Span) -> Result<Node, ParserError>,
+{
+    let ast_node = ast.node(index);
+    let span = *ast.span(ast_node.span);
+
+    let mut node = ast_node.node.clone();
+    let mut children = Vec::new();
+    for child in node.children() {
+        children.push(rewrite_node(ast, child, result, transform)?);
+    }
+    // map_children visits the children in the same order as children(), so the rewritten
+    // indices can be applied in sequence.
+    let mut children = children.into_iter();
+    node.map_children(|_| children.next().unwrap());
+
+    let node = transform(result, node, span)?;
+    result.push(node, span)
+}
@@ -0,0 +1,161 @@
+mod ast_builder {
+    use koto_parser::*;
+
+    fn nodes(ast: &Ast) -> Vec<Node> {
+        ast.nodes().iter().map(|node| node.node.clone()).collect()
+    }
+
+    fn check_push_fails(node: Node, expected_message: &str) {
+        let mut builder = AstBuilder::new();
+        builder.add_string("x").unwrap();
+        builder.add_i64(42).unwrap();
+        builder.push(Node::Empty).unwrap();
+
+        match builder.push(node) {
+            Ok(_) => panic!("Unexpected success"),
+            Err(error) => {
+                assert_eq!(error.message, expected_message);
+                assert_eq!(error.node, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_parsed_ast() {
+        let source = "
+x = 1 + 2.5
+'$x!'
+";
+        let mut builder = AstBuilder::new();
+        let x = builder.add_string("x").unwrap();
+        let two_and_a_half = builder.add_f64(2.5).unwrap();
+        let exclamation = builder.add_string("!").unwrap();
+
+        let target = builder.push(Node::Id(x)).unwrap();
+        let lhs = builder.push(Node::Number1).unwrap();
+        let rhs = builder.push(Node::Float(two_and_a_half)).unwrap();
+        let expression = builder
+            .push(Node::BinaryOp {
+                op: AstBinaryOp::Add,
+                lhs,
+                rhs,
+            })
+            .unwrap();
+        let assign = builder
+            .push(Node::Assign {
+                target: AssignTarget {
+                    target_index: target,
+                    scope: Scope::Local,
+                },
+                op: AssignOp::Equal,
+                expression,
+            })
+            .unwrap();
+        let x_in_string = builder.push(Node::Id(x)).unwrap();
+        let string = builder
+            .push(Node::Str(AstString {
+                quotation_mark: QuotationMark::Single,
+                nodes: vec![
+                    StringNode::Expr(x_in_string),
+                    StringNode::Literal(exclamation),
+                ],
+            }))
+            .unwrap();
+        builder
+            .push(Node::MainBlock {
+                body: vec![assign, string],
+                local_count: 1,
+            })
+            .unwrap();
+
+        let built = builder.build().unwrap();
+        let parsed = Parser::parse(source).unwrap();
+
+        assert_eq!(nodes(&built), nodes(&parsed));
+        assert_eq!(built.constants(), parsed.constants());
+        assert_eq!(built.entry_point_index(), parsed.entry_point_index());
+    }
+
+    #[test]
+    fn spans() {
+        let span = |line, column| Span {
+            start: Position {
+                line,
+                column,
+                ..Default::default()
+            },
+            end: Position {
+                line,
+                column: column + 1,
+                ..Default::default()
+            },
+        };
+
+        let mut builder = AstBuilder::new();
+        let first = builder.push(Node::Number0).unwrap();
+        builder.set_span(span(2, 3));
+        let second = builder.push(Node::Number1).unwrap();
+        builder
+            .push(Node::MainBlock {
+                body: vec![first, second],
+                local_count: 0,
+            })
+            .unwrap();
+        let ast = builder.build().unwrap();
+
+        assert_eq!(*ast.span(ast.node(first).span), Span::default());
+        assert_eq!(*ast.span(ast.node(second).span), span(2, 3));
+        assert_eq!(*ast.span(ast.entry_point().unwrap().span), span(2, 3));
+    }
+
+    #[test]
+    fn child_that_hasnt_been_pushed() {
+        check_push_fails(
+            Node::Block(vec![0, 1]),
+            "Child node 1 hasn't been added to the tree",
+        );
+    }
+
+    #[test]
+    fn node_that_refers_to_itself() {
+        check_push_fails(
+            Node::Nested(1),
+            "Child node 1 hasn't been added to the tree",
+        );
+    }
+
+    #[test]
+    fn missing_constant() {
+        check_push_fails(Node::Id(2_u32.into()), "Expected constant 2 to be a string");
+    }
+
+    #[test]
+    fn constants_with_unexpected_types() {
+        check_push_fails(Node::Id(1_u32.into()), "Expected constant 1 to be a string");
+        check_push_fails(Node::Int(0_u32.into()), "Expected constant 0 to be an i64");
+        check_push_fails(
+            Node::Float(1_u32.into()),
+            "Expected constant 1 to be an f64",
+        );
+        check_push_fails(
+            Node::Lookup((LookupNode::Id(1_u32.into()), None)),
+            "Expected constant 1 to be a string",
+        );
+        check_push_fails(
+            Node::For(AstFor {
+                args: vec![Some(0_u32.into()), Some(1_u32.into())],
+                iterable: 0,
+                body: 0,
+            }),
+            "Expected constant 1 to be a string",
+        );
+    }
+
+    #[test]
+    fn empty_tree() {
+        match AstBuilder::new().build() {
+            Ok(_) => panic!("Unexpected success"),
+            Err(error) => assert_eq!(error.message, "The tree doesn't contain any nodes"),
+        }
+    }
+}
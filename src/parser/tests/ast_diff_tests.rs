@@ -0,0 +1,160 @@
+mod ast_diff {
+    use koto_parser::*;
+
+    fn parse(source: &str) -> Ast {
+        match Parser::parse(source) {
+            Ok(ast) => ast,
+            Err(error) => panic!("Error while parsing script: {}", error),
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Change<'a> {
+        Inserted(NodeKind, &'a str),
+        Removed(NodeKind, &'a str),
+        Changed(NodeKind, &'a str, &'a str),
+    }
+
+    fn slice(source: &str, span: Span) -> &str {
+        let offset = |position: Position| {
+            source
+                .lines()
+                .take(position.line as usize - 1)
+                .map(|line| line.len() + 1)
+                .sum::<usize>()
+                + position.column as usize
+                - 1
+        };
+        &source[offset(span.start)..offset(span.end)]
+    }
+
+    fn check_diff<'a>(before: &'a str, after: &'a str, expected: &[Change<'a>]) {
+        let previous = parse(before);
+        let updated = parse(after);
+
+        let changes = previous
+            .diff(&updated)
+            .into_iter()
+            .map(|change| match change {
+                AstChange::Inserted { node, span } => {
+                    Change::Inserted(updated.node(node).node.kind(), slice(after, span))
+                }
+                AstChange::Removed { node, span } => {
+                    Change::Removed(previous.node(node).node.kind(), slice(before, span))
+                }
+                AstChange::Changed {
+                    previous: previous_node,
+                    previous_span,
+                    node,
+                    span,
+                } => {
+                    assert_eq!(
+                        previous.node(previous_node).node.kind(),
+                        updated.node(node).node.kind()
+                    );
+                    Change::Changed(
+                        updated.node(node).node.kind(),
+                        slice(before, previous_span),
+                        slice(after, span),
+                    )
+                }
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn unchanged() {
+        let source = "\
+f = |x| x * x
+y = f 42
+";
+        check_diff(source, source, &[]);
+    }
+
+    #[test]
+    fn changed_literal_in_function() {
+        check_diff(
+            "\
+f = |x|
+  x * 2
+y = f 3
+",
+            "\
+f = |x|
+  x * 3
+y = f 3
+",
+            &[Change::Changed(NodeKind::Int, "2", "3")],
+        );
+    }
+
+    #[test]
+    fn moved_nodes_are_unchanged() {
+        check_diff(
+            "\
+a = [1, 2, 3]
+",
+            "\
+z = 99
+a = [1, 2, 3]
+",
+            &[
+                Change::Changed(
+                    NodeKind::MainBlock,
+                    "a = [1, 2, 3]\n",
+                    "z = 99\na = [1, 2, 3]\n",
+                ),
+                Change::Inserted(NodeKind::Assign, "z = 99"),
+            ],
+        );
+    }
+
+    #[test]
+    fn removed_line() {
+        check_diff(
+            "\
+x = foo 1
+y = foo 2
+z = foo 3
+",
+            "\
+x = foo 1
+z = foo 3
+",
+            &[
+                Change::Changed(
+                    NodeKind::MainBlock,
+                    "x = foo 1\ny = foo 2\nz = foo 3\n",
+                    "x = foo 1\nz = foo 3\n",
+                ),
+                Change::Removed(NodeKind::Assign, "y = foo 2"),
+            ],
+        );
+    }
+
+    #[test]
+    fn changed_kind() {
+        check_diff(
+            "x = [1, 2]",
+            "x = (1, 2)",
+            &[
+                Change::Removed(NodeKind::List, "[1, 2]"),
+                Change::Inserted(NodeKind::Tuple, "(1, 2)"),
+            ],
+        );
+    }
+
+    #[test]
+    fn changed_list() {
+        check_diff(
+            "x = [1, 2]",
+            "x = [1, 2, 3]",
+            &[
+                Change::Changed(NodeKind::List, "[1, 2]", "[1, 2, 3]"),
+                Change::Inserted(NodeKind::Int, "3"),
+            ],
+        );
+    }
+}
@@ -0,0 +1,178 @@
+mod error_excerpts {
+    use koto_parser::*;
+
+    const SOURCE: &str = "\
+a = 1
+b = 2
+c = foo + bar
+d = 4
+e = 5
+";
+
+    fn position(line: u32, column: u32) -> Position {
+        Position {
+            line,
+            column,
+            ..Default::default()
+        }
+    }
+
+    fn check_excerpt(
+        start: Position,
+        end: Position,
+        options: ExcerptOptions,
+        expected_lines: &[&str],
+    ) {
+        let excerpt = format_error_with_excerpt(Some("Oops"), &None, SOURCE, start, end, &options);
+        assert_eq!(excerpt, expected_lines.join("\n"));
+    }
+
+    #[test]
+    fn default_options() {
+        check_excerpt(
+            position(3, 5),
+            position(3, 8),
+            ExcerptOptions::default(),
+            &[
+                "Oops",
+                " --- 3:5",
+                "   |",
+                " 3 | c = foo + bar",
+                "   |     ^^^",
+            ],
+        );
+    }
+
+    #[test]
+    fn multi_line_span() {
+        check_excerpt(
+            position(2, 1),
+            position(3, 4),
+            ExcerptOptions::default(),
+            &[
+                "Oops",
+                " --- 2:1",
+                "   |",
+                " 2 | b = 2",
+                " 3 | c = foo + bar",
+            ],
+        );
+    }
+
+    #[test]
+    fn context_lines() {
+        check_excerpt(
+            position(3, 5),
+            position(3, 8),
+            ExcerptOptions {
+                context_lines: 1,
+                ..Default::default()
+            },
+            &[
+                "Oops",
+                " --- 3:5",
+                "   |",
+                " 2 | b = 2",
+                " 3 | c = foo + bar",
+                "   |     ^^^",
+                " 4 | d = 4",
+            ],
+        );
+    }
+
+    #[test]
+    fn context_lines_are_clamped_to_the_source() {
+        check_excerpt(
+            position(1, 1),
+            position(1, 2),
+            ExcerptOptions {
+                context_lines: 10,
+                ..Default::default()
+            },
+            &[
+                "Oops",
+                " --- 1:1",
+                "   |",
+                " 1 | a = 1",
+                "   | ^",
+                " 2 | b = 2",
+                " 3 | c = foo + bar",
+                " 4 | d = 4",
+                " 5 | e = 5",
+            ],
+        );
+    }
+
+    #[test]
+    fn unicode_gutters() {
+        check_excerpt(
+            position(3, 5),
+            position(3, 8),
+            ExcerptOptions {
+                unicode: true,
+                ..Default::default()
+            },
+            &[
+                "Oops",
+                " ─── 3:5",
+                "   │",
+                " 3 │ c = foo + bar",
+                "   │     ^^^",
+            ],
+        );
+    }
+
+    #[test]
+    fn max_width() {
+        check_excerpt(
+            position(3, 7),
+            position(3, 14),
+            ExcerptOptions {
+                max_width: Some(8),
+                ..Default::default()
+            },
+            &["Oops", " --- 3:7", "   |", " 3 | c = f...", "   |       ^^"],
+        );
+    }
+
+    #[test]
+    fn color() {
+        let excerpt = format_error_with_excerpt(
+            Some("Oops"),
+            &None,
+            SOURCE,
+            position(3, 5),
+            position(3, 8),
+            &ExcerptOptions {
+                color: true,
+                ..Default::default()
+            },
+        );
+
+        assert!(excerpt.starts_with("\x1b[1;31mOops\x1b[0m\n"));
+        assert!(excerpt.contains("\x1b[31m     ^^^\x1b[0m"));
+
+        // Removing the color codes produces the default output
+        let mut stripped = String::new();
+        let mut in_escape_code = false;
+        for c in excerpt.chars() {
+            match c {
+                '\x1b' => in_escape_code = true,
+                'm' if in_escape_code => in_escape_code = false,
+                _ if in_escape_code => {}
+                _ => stripped.push(c),
+            }
+        }
+        assert_eq!(
+            stripped,
+            format_error_with_excerpt(
+                Some("Oops"),
+                &None,
+                SOURCE,
+                position(3, 5),
+                position(3, 8),
+                &ExcerptOptions::default(),
+            )
+        );
+    }
+}
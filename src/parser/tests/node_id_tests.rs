@@ -0,0 +1,140 @@
+mod node_ids {
+    use {koto_parser::*, std::collections::HashSet};
+
+    fn parse(source: &str) -> Ast {
+        match Parser::parse(source) {
+            Ok(ast) => ast,
+            Err(error) => panic!("Error while parsing script: {}", error),
+        }
+    }
+
+    fn reparse(previous: &Ast, source: &str) -> Ast {
+        let mut ast = parse(source);
+        ast.preserve_node_ids(previous);
+        ast
+    }
+
+    fn all_ids(ast: &Ast) -> Vec<NodeId> {
+        (0..ast.nodes().len())
+            .map(|index| ast.node_id(index as AstIndex))
+            .collect()
+    }
+
+    // Returns the ID of the first node of the given kind that produces the given source slice
+    fn find_id(ast: &Ast, source: &str, kind: NodeKind, slice: &str) -> NodeId {
+        let index = (0..ast.nodes().len() as AstIndex)
+            .find(|index| {
+                ast.node(*index).node.kind() == kind
+                    && ast.source_slice(*index, source) == Some(slice)
+            })
+            .unwrap_or_else(|| panic!("Missing {} node for '{}'", kind, slice));
+        ast.node_id(index)
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        let ast = parse("x = 1 + 2\ny = x * 3\n");
+        let ids = all_ids(&ast);
+        let unique = ids.iter().collect::<HashSet<_>>();
+        assert_eq!(ids.len(), unique.len());
+    }
+
+    #[test]
+    fn unchanged_nodes_keep_their_ids() {
+        let before = "\
+f = |x|
+  x * x
+y = f 2
+";
+        let after = "\
+f = |x|
+  x * x
+y = f 3
+";
+        let previous = parse(before);
+        let ast = reparse(&previous, after);
+
+        assert_eq!(
+            find_id(&previous, before, NodeKind::Function, "|x|\n  x * x"),
+            find_id(&ast, after, NodeKind::Function, "|x|\n  x * x"),
+        );
+
+        // The edited call has a new ID that isn't used in the previous tree
+        let call = find_id(&ast, after, NodeKind::NamedCall, "f 3");
+        assert!(!all_ids(&previous).contains(&call));
+        assert_eq!(previous.find_node(call), None);
+    }
+
+    #[test]
+    fn moved_nodes_keep_their_ids() {
+        let before = "\
+a = [1, 2, 3]
+";
+        let after = "\
+z = 'new line'
+a = [1, 2, 3]
+";
+        let previous = parse(before);
+        let ast = reparse(&previous, after);
+
+        // The list has moved and its constants have new indices, but its contents are unchanged
+        let list = find_id(&previous, before, NodeKind::List, "[1, 2, 3]");
+        assert_eq!(find_id(&ast, after, NodeKind::List, "[1, 2, 3]"), list);
+        assert!(ast.find_node(list).is_some());
+    }
+
+    #[test]
+    fn identical_nodes_are_matched_in_order() {
+        let before = "\
+x = foo 42
+y = foo 42
+";
+        let after = "\
+x = foo 42
+z = 99
+y = foo 42
+";
+        let previous = parse(before);
+        let ast = reparse(&previous, after);
+
+        let previous_ids = all_ids(&previous);
+        let calls = (0..ast.nodes().len() as AstIndex)
+            .filter(|index| ast.node(*index).node.kind() == NodeKind::NamedCall)
+            .map(|index| ast.node_id(index))
+            .collect::<Vec<_>>();
+        let previous_calls = (0..previous.nodes().len() as AstIndex)
+            .filter(|index| previous.node(*index).node.kind() == NodeKind::NamedCall)
+            .map(|index| previous.node_id(index))
+            .collect::<Vec<_>>();
+
+        assert_eq!(calls, previous_calls);
+        assert!(!previous_ids.contains(&find_id(&ast, after, NodeKind::Int, "99")));
+    }
+
+    #[test]
+    fn changed_values_produce_new_ids() {
+        let previous = parse("x = 'hello'");
+        let ast = reparse(&previous, "x = 'goodbye'");
+
+        let previous_ids = all_ids(&previous).into_iter().collect::<HashSet<_>>();
+        let string = find_id(&ast, "x = 'goodbye'", NodeKind::Str, "'goodbye'");
+        assert!(!previous_ids.contains(&string));
+
+        // The assignment target is unchanged
+        assert_eq!(
+            find_id(&ast, "x = 'goodbye'", NodeKind::Id, "x"),
+            find_id(&previous, "x = 'hello'", NodeKind::Id, "x")
+        );
+    }
+
+    #[test]
+    fn ids_stay_unique_across_repeated_edits() {
+        let mut previous = parse("a = 1");
+        for source in &["a = 1\nb = 2", "a = 1\nb = 3", "c = 4\na = 1\nb = 3"] {
+            let ast = reparse(&previous, source);
+            let ids = all_ids(&ast);
+            assert_eq!(ids.len(), ids.iter().collect::<HashSet<_>>().len());
+            previous = ast;
+        }
+    }
+}
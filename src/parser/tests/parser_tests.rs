@@ -128,6 +128,90 @@ a
             )
         }
 
+        #[test]
+        fn number_separators() {
+            let source = "
+1_000_000
+0xFF_FF
+0b1010_1010
+1_000.000_5
+";
+            check_ast(
+                source,
+                &[
+                    Int(constant(0)),
+                    Int(constant(1)),
+                    Int(constant(2)),
+                    Float(constant(3)),
+                    MainBlock {
+                        body: vec![0, 1, 2, 3],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::I64(1_000_000),
+                    Constant::I64(0xffff),
+                    Constant::I64(0b1010_1010),
+                    Constant::F64(1000.0005),
+                ]),
+            )
+        }
+
+        #[test]
+        fn scientific_notation() {
+            let source = "
+1e-3
+2.5E6
+-1e3
+1E+2
+";
+            check_ast(
+                source,
+                &[
+                    Float(constant(0)),
+                    Float(constant(1)),
+                    Float(constant(2)),
+                    Float(constant(3)),
+                    MainBlock {
+                        body: vec![0, 1, 2, 3],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::F64(1e-3),
+                    Constant::F64(2.5e6),
+                    Constant::F64(-1e3),
+                    Constant::F64(1e2),
+                ]),
+            )
+        }
+
+        #[test]
+        fn integer_limits() {
+            let source = "
+-9_223_372_036_854_775_808
+9223372036854775807
+-1
+";
+            check_ast(
+                source,
+                &[
+                    Int(constant(0)),
+                    Int(constant(1)),
+                    Int(constant(2)),
+                    MainBlock {
+                        body: vec![0, 1, 2],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::I64(i64::MIN),
+                    Constant::I64(i64::MAX),
+                    Constant::I64(-1),
+                ]),
+            )
+        }
+
         #[test]
         fn multiline_strings() {
             let source = r#"
@@ -155,6 +239,49 @@ a
             )
         }
 
+        #[test]
+        fn dedented_strings() {
+            let source = r#"
+x = """
+    foo
+      $x
+    bar\t
+    """
+"#;
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Id(constant(0)),
+                    Str(AstString {
+                        quotation_mark: QuotationMark::Double,
+                        nodes: vec![
+                            StringNode::Literal(constant(1)),
+                            StringNode::Expr(1),
+                            StringNode::Literal(constant(2)),
+                        ],
+                    }),
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Local,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 2,
+                    },
+                    MainBlock {
+                        body: vec![3],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("foo\n  "),
+                    Constant::Str("\nbar\t\n"),
+                ]),
+            )
+        }
+
         #[test]
         fn strings_with_escape_codes() {
             let source = r#"
@@ -175,6 +302,29 @@ a
             )
         }
 
+        #[test]
+        fn strings_with_additional_escape_codes() {
+            let source = r#"
+"\$x \e[1m\0"
+'\u{ESCAPE}\u{ZWJ}\u{NO-BREAK SPACE}\u{}'
+"#;
+            check_ast(
+                source,
+                &[
+                    string_literal(0, QuotationMark::Double),
+                    string_literal(1, QuotationMark::Single),
+                    MainBlock {
+                        body: vec![0, 1],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("$x \x1b[1m\0"),
+                    Constant::Str("\x1b\u{200d}\u{a0}\0"),
+                ]),
+            )
+        }
+
         #[test]
         fn strings_with_interpolated_ids() {
             let source = r#"
@@ -680,6 +830,119 @@ export @tests =
             )
         }
 
+        #[test]
+        fn map_block_test_annotations() {
+            let source = r#"
+x =
+  @test_expected_to_fail foo: 0
+  @test_skip bar: 1
+  @test_timeout bar: 0
+"#;
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Number0,
+                    Number1,
+                    Number0,
+                    Map(vec![
+                        (
+                            MapKey::Meta(MetaKeyId::TestExpectedToFail, Some(constant(1))),
+                            Some(1),
+                        ),
+                        (
+                            MapKey::Meta(MetaKeyId::TestSkip, Some(constant(2))),
+                            Some(2),
+                        ),
+                        (
+                            MapKey::Meta(MetaKeyId::TestTimeout, Some(constant(2))),
+                            Some(3),
+                        ),
+                    ]),
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Local,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 4,
+                    }, // 5
+                    MainBlock {
+                        body: vec![5],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("foo"),
+                    Constant::Str("bar"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn module_version_meta_entries() {
+            let source = r#"
+export @version = "1.0"
+export @requires = "koto >= 0.9"
+"#;
+            check_ast(
+                source,
+                &[
+                    Meta(MetaKeyId::Version, None),
+                    string_literal(0, QuotationMark::Double),
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Export,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 1,
+                    },
+                    Meta(MetaKeyId::Requires, None),
+                    string_literal(1, QuotationMark::Double),
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 3,
+                            scope: Scope::Export,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 4,
+                    }, // 5
+                    MainBlock {
+                        body: vec![2, 5],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[Constant::Str("1.0"), Constant::Str("koto >= 0.9")]),
+            )
+        }
+
+        #[test]
+        fn reload_meta_entry() {
+            let source = "export @reload = 0";
+            check_ast(
+                source,
+                &[
+                    Meta(MetaKeyId::Reload, None),
+                    Number0,
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Export,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 1,
+                    },
+                    MainBlock {
+                        body: vec![2],
+                        local_count: 0,
+                    },
+                ],
+                None,
+            )
+        }
+
         #[test]
         fn ranges_from_literals() {
             let source = "
@@ -1035,6 +1298,86 @@ num4(
             )
         }
 
+        #[test]
+        fn multi_export() {
+            let source = "export a, b = 1, 0";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Id(constant(1)),
+                    Number1,
+                    Number0,
+                    TempTuple(vec![2, 3]),
+                    MultiAssign {
+                        targets: vec![
+                            AssignTarget {
+                                target_index: 0,
+                                scope: Scope::Export,
+                            },
+                            AssignTarget {
+                                target_index: 1,
+                                scope: Scope::Export,
+                            },
+                        ],
+                        expression: 4,
+                    }, // 5
+                    MainBlock {
+                        body: vec![5],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[Constant::Str("a"), Constant::Str("b")]),
+            )
+        }
+
+        #[test]
+        fn export_existing_values() {
+            let source = "
+export a
+export
+  b, c
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Id(constant(0)),
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Export,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 1,
+                    },
+                    Id(constant(1)),
+                    Id(constant(2)),
+                    Id(constant(1)), // 5
+                    Id(constant(2)),
+                    TempTuple(vec![5, 6]),
+                    MultiAssign {
+                        targets: vec![
+                            AssignTarget {
+                                target_index: 3,
+                                scope: Scope::Export,
+                            },
+                            AssignTarget {
+                                target_index: 4,
+                                scope: Scope::Export,
+                            },
+                        ],
+                        expression: 7,
+                    },
+                    MainBlock {
+                        body: vec![2, 8],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[Constant::Str("a"), Constant::Str("b"), Constant::Str("c")]),
+            )
+        }
+
         #[test]
         fn tuple() {
             let source = "x = 1, 0";
@@ -1211,6 +1554,41 @@ x";
             )
         }
 
+        #[test]
+        fn multi_with_ellipsis() {
+            let source = "x, rest... = y";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Ellipsis(Some(constant(1))),
+                    Id(constant(2)),
+                    MultiAssign {
+                        targets: vec![
+                            AssignTarget {
+                                target_index: 0,
+                                scope: Scope::Local,
+                            },
+                            AssignTarget {
+                                target_index: 1,
+                                scope: Scope::Local,
+                            },
+                        ],
+                        expression: 2,
+                    },
+                    MainBlock {
+                        body: vec![3],
+                        local_count: 2,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("rest"),
+                    Constant::Str("y"),
+                ]),
+            )
+        }
+
         #[test]
         fn modify_assign() {
             let source = "\
@@ -1626,11 +2004,124 @@ a = 1
                 None,
             )
         }
+        #[test]
+        fn in_operator() {
+            let source = "1 in [0, 1] == true";
+            check_ast(
+                source,
+                &[
+                    Number1,
+                    Number0,
+                    Number1,
+                    List(vec![1, 2]),
+                    BinaryOp {
+                        op: AstBinaryOp::In,
+                        lhs: 0,
+                        rhs: 3,
+                    },
+                    BoolTrue,
+                    BinaryOp {
+                        op: AstBinaryOp::Equal,
+                        lhs: 4,
+                        rhs: 5,
+                    },
+                    MainBlock {
+                        body: vec![6],
+                        local_count: 0,
+                    },
+                ],
+                None,
+            )
+        }
+
+        #[test]
+        fn in_operator_with_range() {
+            let source = "x in 0..n + 1 and y";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Number0,
+                    Id(constant(1)),
+                    Number1,
+                    BinaryOp {
+                        op: AstBinaryOp::Add,
+                        lhs: 2,
+                        rhs: 3,
+                    },
+                    Range {
+                        start: 1,
+                        end: 4,
+                        inclusive: false,
+                    }, // 5
+                    BinaryOp {
+                        op: AstBinaryOp::In,
+                        lhs: 0,
+                        rhs: 5,
+                    },
+                    Id(constant(2)),
+                    BinaryOp {
+                        op: AstBinaryOp::And,
+                        lhs: 6,
+                        rhs: 7,
+                    },
+                    MainBlock {
+                        body: vec![8],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[Constant::Str("x"), Constant::Str("n"), Constant::Str("y")]),
+            )
+        }
     }
 
     mod control_flow {
         use super::*;
 
+        #[test]
+        fn guard() {
+            let source = "\
+guard x else return
+guard y else
+  throw y
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Return(None),
+                    UnaryOp {
+                        op: AstUnaryOp::Not,
+                        value: 0,
+                    },
+                    If(AstIf {
+                        condition: 2,
+                        then_node: 1,
+                        else_if_blocks: vec![],
+                        else_node: None,
+                    }),
+                    Id(constant(1)),
+                    Id(constant(1)), // 5
+                    Throw(5),
+                    UnaryOp {
+                        op: AstUnaryOp::Not,
+                        value: 4,
+                    },
+                    If(AstIf {
+                        condition: 7,
+                        then_node: 6,
+                        else_if_blocks: vec![],
+                        else_node: None,
+                    }),
+                    MainBlock {
+                        body: vec![3, 8],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[Constant::Str("x"), Constant::Str("y")]),
+            )
+        }
+
         #[test]
         fn if_inline() {
             let source = "1 + if true then 0 else 1";
@@ -1781,6 +2272,8 @@ a";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 5
                     MainBlock {
                         body: vec![5],
@@ -1979,6 +2472,8 @@ a()";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2029,6 +2524,8 @@ a()";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 5
                     MainBlock {
                         body: vec![5],
@@ -2071,6 +2568,8 @@ a()";
                         is_instance_function: false,
                         is_variadic: true,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![8],
@@ -2117,6 +2616,8 @@ f 42";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2169,6 +2670,8 @@ f 42";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 5
                     Assign {
                         target: AssignTarget {
@@ -2192,6 +2695,8 @@ f 42";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 10
                     Assign {
                         target: AssignTarget {
@@ -2375,6 +2880,8 @@ f x";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2415,6 +2922,8 @@ f x";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 5
                     Nested(5),
                     Id(constant(2)), // x
@@ -2431,6 +2940,8 @@ f x";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 10
                     Nested(10),
                     TempTuple(vec![6, 11]),
@@ -2570,6 +3081,8 @@ foo.bar x
                         is_instance_function: true,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Map(vec![
                         (MapKey::Id(constant(0)), Some(0)),
@@ -2615,6 +3128,8 @@ f = ||
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2667,6 +3182,8 @@ f = ||
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }), // 5
                     Assign {
                         target: AssignTarget {
@@ -2725,6 +3242,8 @@ f()";
                         is_instance_function: true,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Map(vec![
                         (MapKey::Id(constant(1)), Some(1)),
@@ -2738,6 +3257,8 @@ f()";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2824,6 +3345,8 @@ f = |n|
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2843,6 +3366,8 @@ f = |n|
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     Assign {
                         target: AssignTarget {
@@ -2902,6 +3427,8 @@ f = |n|
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![7],
@@ -2938,6 +3465,8 @@ f = |n|
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![3],
@@ -2981,6 +3510,8 @@ y z";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     NamedCall {
                         id: constant(1), // y
@@ -3029,6 +3560,8 @@ y z";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: true,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![2],
@@ -3057,6 +3590,8 @@ y z";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: true,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![4],
@@ -3088,6 +3623,8 @@ y z";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: true,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![3],
@@ -3123,6 +3660,8 @@ y z";
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
                     }),
                     MainBlock {
                         body: vec![8],
@@ -3156,24 +3695,108 @@ y z";
                     Id(constant(3)),  // e
                     Id(constant(0)),
                     Function(koto_parser::Function {
-                        args: vec![0, 5, 6],
-                        local_count: 4,
+                        args: vec![0, 5, 6],
+                        local_count: 4,
+                        accessed_non_locals: vec![],
+                        body: 7,
+                        is_instance_function: false,
+                        is_variadic: false,
+                        is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
+                    }),
+                    MainBlock {
+                        body: vec![8],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("a"),
+                    Constant::Str("c"),
+                    Constant::Str("d"),
+                    Constant::Str("e"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn type_annotations() {
+            let source = "|x: Number, y| -> String y";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Id(constant(2)),
+                    Id(constant(2)),
+                    Function(koto_parser::Function {
+                        args: vec![0, 1],
+                        local_count: 2,
+                        accessed_non_locals: vec![],
+                        body: 2,
+                        is_instance_function: false,
+                        is_variadic: false,
+                        is_generator: false,
+                        arg_types: vec![Some(constant(1)), None],
+                        return_type: Some(constant(3)),
+                    }),
+                    MainBlock {
+                        body: vec![3],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("Number"),
+                    Constant::Str("y"),
+                    Constant::Str("String"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn decorated_function() {
+            let source = "f = @foo @bar |x| x";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Id(constant(1)),
+                    Id(constant(2)),
+                    Id(constant(3)),
+                    Id(constant(3)),
+                    Function(koto_parser::Function {
+                        args: vec![3],
+                        local_count: 1,
                         accessed_non_locals: vec![],
-                        body: 7,
+                        body: 4,
                         is_instance_function: false,
                         is_variadic: false,
                         is_generator: false,
-                    }),
+                        arg_types: vec![],
+                        return_type: None,
+                    }), // 5
+                    Decorated {
+                        decorators: vec![1, 2],
+                        function: 5,
+                    },
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Local,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 6,
+                    },
                     MainBlock {
-                        body: vec![8],
-                        local_count: 0,
+                        body: vec![7],
+                        local_count: 1,
                     },
                 ],
                 Some(&[
-                    Constant::Str("a"),
-                    Constant::Str("c"),
-                    Constant::Str("d"),
-                    Constant::Str("e"),
+                    Constant::Str("f"),
+                    Constant::Str("foo"),
+                    Constant::Str("bar"),
+                    Constant::Str("x"),
                 ]),
             )
         }
@@ -3979,6 +4602,80 @@ assert_eq x, "hello"
                 ]),
             )
         }
+
+        #[test]
+        fn assert() {
+            let source = r#"
+assert x == 1
+assert not x
+"#;
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)),
+                    Number1,
+                    BinaryOp {
+                        op: AstBinaryOp::Equal,
+                        lhs: 0,
+                        rhs: 1,
+                    },
+                    Assert {
+                        expression_string: constant(1),
+                        expression: 2,
+                    },
+                    Id(constant(0)),
+                    UnaryOp {
+                        op: AstUnaryOp::Not,
+                        value: 4,
+                    }, // 5
+                    Assert {
+                        expression_string: constant(2),
+                        expression: 5,
+                    },
+                    MainBlock {
+                        body: vec![3, 6],
+                        local_count: 0,
+                    },
+                ],
+                Some(&[
+                    Constant::Str("x"),
+                    Constant::Str("x == 1"),
+                    Constant::Str("not x"),
+                ]),
+            )
+        }
+
+        #[test]
+        fn raw_ids() {
+            let source = "
+`if` = {`match`: 1}
+`if`.`match`
+";
+            check_ast(
+                source,
+                &[
+                    Id(constant(0)), // if
+                    Number1,
+                    Map(vec![(MapKey::Id(constant(1)), Some(1))]),
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 0,
+                            scope: Scope::Local,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 2,
+                    },
+                    Id(constant(0)),
+                    Lookup((LookupNode::Id(constant(1)), None)), // 5
+                    Lookup((LookupNode::Root(4), Some(5))),
+                    MainBlock {
+                        body: vec![3, 6],
+                        local_count: 1,
+                    },
+                ],
+                Some(&[Constant::Str("if"), Constant::Str("match")]),
+            )
+        }
     }
 
     mod import {
@@ -4911,4 +5608,341 @@ switch
             )
         }
     }
+
+    mod expression_entry_point {
+        use super::*;
+
+        fn check_expression(source: &str, expected_ast: &[Node]) {
+            match Parser::parse_expression(source) {
+                Ok(ast) => {
+                    assert_eq!(
+                        ast.nodes()
+                            .iter()
+                            .map(|ast_node| ast_node.node.clone())
+                            .collect::<Vec<_>>(),
+                        expected_ast
+                    );
+                    assert_eq!(ast.entry_point_index() as usize, expected_ast.len() - 1);
+                }
+                Err(error) => panic!("{} - {}", error, error.span.start),
+            }
+        }
+
+        #[test]
+        fn arithmetic() {
+            check_expression(
+                "x + 1",
+                &[
+                    Id(constant(0)),
+                    Number1,
+                    BinaryOp {
+                        op: AstBinaryOp::Add,
+                        lhs: 0,
+                        rhs: 1,
+                    },
+                ],
+            )
+        }
+
+        #[test]
+        fn surrounded_by_whitespace_and_comments() {
+            check_expression(
+                "
+# A comment
+  foo.bar 42 # Another comment
+
+",
+                &[
+                    Id(constant(0)),
+                    Int(constant(2)),
+                    Lookup((
+                        LookupNode::Call {
+                            args: vec![1],
+                            with_parens: false,
+                        },
+                        None,
+                    )),
+                    Lookup((LookupNode::Id(constant(1)), Some(2))),
+                    Lookup((LookupNode::Root(0), Some(3))),
+                ],
+            )
+        }
+
+        #[test]
+        fn function_with_locals() {
+            check_expression(
+                "|x| y = x * 2",
+                &[
+                    Id(constant(0)),
+                    Id(constant(1)),
+                    Id(constant(0)),
+                    Int(constant(2)),
+                    BinaryOp {
+                        op: AstBinaryOp::Multiply,
+                        lhs: 2,
+                        rhs: 3,
+                    },
+                    Assign {
+                        target: AssignTarget {
+                            target_index: 1,
+                            scope: Scope::Local,
+                        },
+                        op: AssignOp::Equal,
+                        expression: 4,
+                    },
+                    Function(koto_parser::Function {
+                        args: vec![0],
+                        local_count: 2,
+                        accessed_non_locals: vec![],
+                        body: 5,
+                        is_instance_function: false,
+                        is_variadic: false,
+                        is_generator: false,
+                        arg_types: vec![],
+                        return_type: None,
+                    }),
+                ],
+            )
+        }
+    }
+
+    mod source_slice {
+        use super::*;
+
+        // Checks the source slice of the last node in the AST that matches the predicate
+        fn check_slice(source: &str, predicate: impl Fn(&Node) -> bool, expected: &str) {
+            let ast = Parser::parse(source).unwrap();
+            let index = ast
+                .nodes()
+                .iter()
+                .rposition(|ast_node| predicate(&ast_node.node))
+                .expect("Missing node");
+            assert_eq!(ast.source_slice(index as AstIndex, source), Some(expected));
+        }
+
+        #[test]
+        fn assignment_with_lookup() {
+            let source = "x = foo.bar 1, 2";
+            check_slice(source, |node| matches!(node, Assign { .. }), source);
+            check_slice(source, |node| matches!(node, Lookup(_)), "foo.bar 1, 2");
+            check_slice(source, |node| matches!(node, Int(_)), "2");
+        }
+
+        #[test]
+        fn binary_op() {
+            check_slice(
+                "a = (x + y) * z",
+                |node| matches!(node, BinaryOp { .. }),
+                "(x + y) * z",
+            );
+        }
+
+        #[test]
+        fn multiline_function() {
+            let source = "
+# A comment
+f = |a, b|
+  a + b
+f 1, 2
+";
+            check_slice(
+                source,
+                |node| matches!(node, Function(_)),
+                "|a, b|\n  a + b",
+            );
+            check_slice(source, |node| matches!(node, NamedCall { .. }), "f 1, 2");
+        }
+
+        #[test]
+        fn list() {
+            check_slice("x = [1, 2, 3]", |node| matches!(node, List(_)), "[1, 2, 3]");
+        }
+
+        #[test]
+        fn strings_with_wide_characters_and_tabs() {
+            let source = "s = 'héllo 👋 $x' + '\t' + y";
+            check_slice(source, |node| matches!(node, Id(_)), "y");
+            check_slice(
+                source,
+                |node| matches!(node, BinaryOp { .. }),
+                "'héllo 👋 $x' + '\t' + y",
+            );
+        }
+
+        #[test]
+        fn out_of_range_index() {
+            let ast = Parser::parse("x = 1").unwrap();
+            assert_eq!(ast.source_slice(100, "x = 1"), None);
+        }
+    }
+
+    mod node_at {
+        use super::*;
+
+        // Checks the source slices of the chain of nodes at the given position,
+        // starting with the innermost node
+        fn check_nodes_at(source: &str, line: u32, column: u32, expected: &[&str]) {
+            let ast = Parser::parse(source).unwrap();
+            let position = Position {
+                line,
+                column,
+                ..Default::default()
+            };
+
+            let slices = ast
+                .nodes_at(position)
+                .into_iter()
+                .rev()
+                .map(|index| ast.source_slice(index, source).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(slices, expected);
+
+            let innermost = ast.node_at(position);
+            assert_eq!(
+                innermost.map(|index| ast.source_slice(index, source).unwrap()),
+                expected.first().copied()
+            );
+        }
+
+        #[test]
+        fn binary_op_in_assignment() {
+            let source = "x = foo + 42";
+            check_nodes_at(source, 1, 6, &["foo", "foo + 42", source, source]);
+            check_nodes_at(source, 1, 11, &["42", "foo + 42", source, source]);
+        }
+
+        #[test]
+        fn position_at_the_end_of_a_node() {
+            let source = "x = foo.bar";
+            check_nodes_at(source, 1, 12, &["bar", "foo.bar", source, source]);
+            check_nodes_at("a+b", 1, 2, &["a", "a+b", "a+b"]);
+        }
+
+        #[test]
+        fn nested_function() {
+            let source = "
+f = |x|
+  y = [x, 2]
+  y
+";
+            check_nodes_at(
+                source,
+                3,
+                8,
+                &[
+                    "x",
+                    "[x, 2]",
+                    "y = [x, 2]",
+                    "\n  y = [x, 2]\n  y",
+                    "|x|\n  y = [x, 2]\n  y",
+                    "f = |x|\n  y = [x, 2]\n  y",
+                    source,
+                ],
+            );
+        }
+
+        #[test]
+        fn position_outside_of_the_ast() {
+            let ast = Parser::parse("x = 1").unwrap();
+            let position = Position {
+                line: 10,
+                column: 1,
+                ..Default::default()
+            };
+            assert_eq!(ast.node_at(position), None);
+            assert!(ast.nodes_at(position).is_empty());
+        }
+    }
+
+    mod byte_offsets {
+        use super::*;
+
+        #[test]
+        fn node_spans_match_source_slices() {
+            let source = "\
+x = 'héllö 👋'
+f = |n|
+  # ✨
+  n + x.size()
+";
+            let ast = Parser::parse(source).unwrap();
+
+            for (index, node) in ast.nodes().iter().enumerate() {
+                let span = ast.span(node.span);
+                assert_eq!(
+                    source.get(span.byte_range()),
+                    span.source_slice(source),
+                    "Mismatched slices for node {}",
+                    index
+                );
+            }
+        }
+
+        #[test]
+        fn error_span() {
+            let source = "x = 'ü'\ny = 1 +\n";
+            let error = Parser::parse(source).unwrap_err();
+
+            assert_eq!(error.span.start.line, 2);
+            assert_eq!(error.span.start.offset, 15);
+            assert_eq!(&source[error.span.byte_range()], "+");
+        }
+    }
+
+    mod line_endings {
+        use super::*;
+
+        const SOURCE: &str = "\
+x = 'multi
+line'
+f = |n|
+  y = '''
+    ${n}
+      $x
+    '''
+  \"${{a: y}.a}\\
+    continued\"
+";
+
+        fn nodes(ast: &Ast) -> Vec<Node> {
+            ast.nodes().iter().map(|node| node.node.clone()).collect()
+        }
+
+        fn check_same_ast(source: &str) {
+            let expected = Parser::parse(SOURCE).unwrap();
+            let ast = Parser::parse(source).unwrap();
+
+            assert_eq!(nodes(&ast), nodes(&expected));
+            assert_eq!(ast.constants(), expected.constants());
+            for (node, expected_node) in ast.nodes().iter().zip(expected.nodes().iter()) {
+                let span = ast.span(node.span);
+                let expected_span = expected.span(expected_node.span);
+                assert_eq!(
+                    (
+                        span.start.line,
+                        span.start.column,
+                        span.end.line,
+                        span.end.column
+                    ),
+                    (
+                        expected_span.start.line,
+                        expected_span.start.column,
+                        expected_span.end.line,
+                        expected_span.end.column
+                    )
+                );
+            }
+        }
+
+        #[test]
+        fn crlf() {
+            check_same_ast(&SOURCE.replace('\n', "\r\n"));
+        }
+
+        #[test]
+        fn byte_order_mark() {
+            check_same_ast(&format!("\u{feff}{}", SOURCE));
+            check_same_ast(&format!("\u{feff}{}", SOURCE.replace('\n', "\r\n")));
+        }
+    }
 }
@@ -16,12 +16,23 @@ mod parser {
 
     #[cfg(not(feature = "panic_on_parser_error"))]
     fn check_parsing_fails(source: &str) {
-        if let Ok(ast) = Parser::parse(source) {
-            panic!(
+        let error = match Parser::parse(source) {
+            Ok(ast) => panic!(
                 "Unexpected success while parsing:\n{}\n{:#?}",
                 source,
                 ast.nodes()
-            );
+            ),
+            Err(error) => error,
+        };
+
+        // The first error found when recovering from errors should match the parser's error
+        let (_, recovered_errors) = Parser::parse_with_recovery(source);
+        match recovered_errors.first() {
+            Some(recovered) => {
+                assert_eq!(recovered.to_string(), error.to_string());
+                assert_eq!(recovered.span, error.span);
+            }
+            None => panic!("Missing errors while parsing with recovery:\n{}", source),
         }
     }
 
@@ -43,6 +54,38 @@ mod parser {
             check_parsing_fails("import foo bar");
         }
 
+        #[test]
+        fn guard_without_else() {
+            check_parsing_fails("guard x return");
+        }
+
+        #[test]
+        fn guard_without_else_expression() {
+            check_parsing_fails("guard x else");
+        }
+
+        #[test]
+        fn guard_else_without_exit() {
+            check_parsing_fails("guard x else 42");
+            check_parsing_fails(
+                "\
+guard x else
+  return 1
+  y = 2
+",
+            );
+        }
+
+        #[test]
+        fn missing_id_in_export_list() {
+            check_parsing_fails("export foo, = 1, 2");
+        }
+
+        #[test]
+        fn export_without_ids() {
+            check_parsing_fails("export\nfoo");
+        }
+
         mod indentation {
             use super::*;
 
@@ -110,6 +153,25 @@ if f x
             }
         }
 
+        mod assignment {
+            use super::*;
+
+            #[test]
+            fn ellipsis_with_single_target() {
+                check_parsing_fails("rest... = x");
+            }
+
+            #[test]
+            fn ellipsis_in_middle_position() {
+                check_parsing_fails("a, rest..., b = x");
+            }
+
+            #[test]
+            fn multiple_ellipses() {
+                check_parsing_fails("a..., b... = x");
+            }
+        }
+
         mod loops {
             use super::*;
 
@@ -169,6 +231,26 @@ f = ||
             fn missing_commas_in_lookup_call() {
                 check_parsing_fails("f.bar 1 2 3");
             }
+
+            #[test]
+            fn missing_arg_type() {
+                check_parsing_fails("f = |x: | x");
+            }
+
+            #[test]
+            fn missing_return_type() {
+                check_parsing_fails("f = |x| -> 42");
+            }
+
+            #[test]
+            fn decorator_without_function() {
+                check_parsing_fails("x = @foo @bar 42");
+            }
+
+            #[test]
+            fn decorator_without_id() {
+                check_parsing_fails("f = @foo @ |x| x");
+            }
         }
 
         mod lookups {
@@ -294,9 +376,84 @@ switch
             }
         }
 
+        mod numbers {
+            use super::*;
+
+            #[test]
+            fn exponent_without_digits() {
+                check_parsing_fails("x = 1e");
+            }
+
+            #[test]
+            fn trailing_separator() {
+                check_parsing_fails("x = 1_");
+            }
+
+            #[test]
+            fn separator_after_prefix() {
+                check_parsing_fails("x = 0x_ff");
+            }
+        }
+
         mod strings {
             use super::*;
 
+            // Checks that an error is reported with the expected columns on the first line
+            #[cfg(not(feature = "panic_on_parser_error"))]
+            fn check_error_columns(source: &str, start: u32, end: u32) {
+                match Parser::parse(source) {
+                    Ok(_) => panic!("Unexpected success while parsing:\n{}", source),
+                    Err(error) => {
+                        assert_eq!(error.span.start.line, 1);
+                        assert_eq!(error.span.end.line, 1);
+                        assert_eq!(
+                            (error.span.start.column, error.span.end.column),
+                            (start, end)
+                        );
+                    }
+                }
+            }
+
+            #[cfg(not(feature = "panic_on_parser_error"))]
+            mod escape_codes {
+                use super::*;
+
+                #[test]
+                fn unexpected_escape() {
+                    check_error_columns(r"'abc \q def'", 6, 8);
+                }
+
+                #[test]
+                fn ascii_out_of_range() {
+                    check_error_columns(r"'\x80'", 2, 6);
+                }
+
+                #[test]
+                fn unexpected_char_in_ascii_escape() {
+                    check_error_columns(r"'x \x9z'", 4, 8);
+                }
+
+                #[test]
+                fn unicode_out_of_range() {
+                    check_error_columns(r"'\u{110000}'", 2, 12);
+                }
+
+                #[test]
+                fn unknown_unicode_name() {
+                    check_error_columns(r"'🦋 \u{NOT A NAME}'", 5, 19);
+                }
+
+                #[test]
+                fn unexpected_char_in_unicode_escape() {
+                    check_error_columns(r"'\u{1f98b!}'", 2, 12);
+                }
+
+                #[test]
+                fn escape_error_in_template_string() {
+                    check_error_columns(r"'$x \u{xyz}'", 5, 12);
+                }
+            }
+
             #[test]
             fn unterminated_string() {
                 check_parsing_fails("'hello");
@@ -333,4 +490,485 @@ switch
             }
         }
     }
+
+    mod expressions {
+        use koto_parser::Parser;
+
+        fn check_expression_parsing_fails(source: &str) {
+            if let Ok(ast) = Parser::parse_expression(source) {
+                panic!(
+                    "Unexpected success while parsing:\n{}\n{:#?}",
+                    source,
+                    ast.nodes()
+                );
+            }
+        }
+
+        #[test]
+        fn empty_source() {
+            check_expression_parsing_fails("");
+            check_expression_parsing_fails("  # Nothing to see here");
+        }
+
+        #[test]
+        fn export() {
+            check_expression_parsing_fails("export x = 1");
+        }
+
+        #[test]
+        fn top_level_assignment() {
+            check_expression_parsing_fails("x = 1");
+        }
+
+        #[test]
+        fn multiple_expressions() {
+            check_expression_parsing_fails("1 + 1\n2 + 2");
+        }
+    }
+
+    #[cfg(not(feature = "panic_on_parser_error"))]
+    mod structured_errors {
+        use koto_parser::{ErrorType, Parser, SyntaxError, Token};
+
+        fn parse_error(source: &str) -> koto_parser::ParserError {
+            match Parser::parse(source) {
+                Ok(ast) => panic!(
+                    "Unexpected success while parsing:\n{}\n{:#?}",
+                    source,
+                    ast.nodes()
+                ),
+                Err(error) => error,
+            }
+        }
+
+        #[test]
+        fn unexpected_token() {
+            let error = parse_error("x = 1 )");
+
+            assert_eq!(
+                error.error,
+                ErrorType::SyntaxError(SyntaxError::UnexpectedToken)
+            );
+            assert_eq!(error.code(), "SyntaxError::UnexpectedToken");
+            assert_eq!(error.token, Some(Token::RoundClose));
+            assert!(error.expected_tokens().is_empty());
+        }
+
+        #[test]
+        fn missing_for_in_keyword() {
+            let error = parse_error("for x y");
+
+            assert_eq!(error.code(), "SyntaxError::ExpectedForInKeyword");
+            assert_eq!(error.expected_tokens(), &[Token::In]);
+        }
+
+        #[test]
+        fn missing_indented_block() {
+            let error = parse_error("while true\nx = 1");
+
+            assert!(error.is_indentation_error());
+            assert_eq!(error.code(), "ExpectedIndentation::WhileBody");
+            assert_eq!(error.expected_tokens(), &[Token::NewLineIndented]);
+        }
+
+        #[test]
+        fn lexer_errors() {
+            let check_lexer_error = |source: &str, expected: SyntaxError, expected_slice: &str| {
+                let error = parse_error(source);
+                assert_eq!(error.error, ErrorType::SyntaxError(expected), "{}", source);
+                assert_eq!(error.token, Some(Token::Error));
+                assert_eq!(
+                    &source[error.span.byte_range()],
+                    expected_slice,
+                    "{}",
+                    source
+                );
+            };
+
+            check_lexer_error("x = 'hello", SyntaxError::UnterminatedString, "'");
+            check_lexer_error(
+                "x = 1\ny = \"\"\"\nabc",
+                SyntaxError::UnterminatedString,
+                "\"\"\"",
+            );
+            check_lexer_error("x = \"${'}\"", SyntaxError::UnterminatedString, "'");
+            check_lexer_error("#- abc\n  x = 1", SyntaxError::UnterminatedComment, "#-");
+            check_lexer_error("x = 1e", SyntaxError::InvalidNumber, "1e");
+            check_lexer_error("x = 0x", SyntaxError::InvalidNumber, "0x");
+            check_lexer_error("x = 0b102 + 1", SyntaxError::InvalidNumber, "0b102");
+            check_lexer_error("x = 42abc", SyntaxError::InvalidNumber, "42abc");
+            check_lexer_error("x = 1 ~ 2", SyntaxError::UnexpectedCharacter, "~");
+            check_lexer_error("f = ||\n  ¬", SyntaxError::UnexpectedCharacter, "¬");
+        }
+    }
+
+    #[cfg(not(feature = "panic_on_parser_error"))]
+    mod max_depth {
+        use koto_parser::{ErrorType, Parser, ParserSettings, SyntaxError};
+
+        fn check_max_depth_exceeded(source: &str) {
+            match Parser::parse(source) {
+                Ok(_) => panic!("Unexpected success while parsing deeply nested expressions"),
+                Err(error) => assert_eq!(
+                    error.error,
+                    ErrorType::SyntaxError(SyntaxError::MaxDepthExceeded)
+                ),
+            }
+        }
+
+        fn indented_lines(line: &str, depth: usize) -> String {
+            let mut result = (0..depth)
+                .map(|i| format!("{}{}\n", " ".repeat(i * 2), line))
+                .collect::<String>();
+            result.push_str(&" ".repeat(depth * 2));
+            result.push_str("1\n");
+            result
+        }
+
+        const DEPTH: usize = 10_000;
+
+        #[test]
+        fn nested_parentheses() {
+            check_max_depth_exceeded(&format!("{}1{}", "(".repeat(DEPTH), ")".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_lists() {
+            check_max_depth_exceeded(&format!("{}1{}", "[".repeat(DEPTH), "]".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_maps() {
+            check_max_depth_exceeded(&format!("{}1{}", "{x: ".repeat(DEPTH), "}".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_calls() {
+            check_max_depth_exceeded(&format!("{}x{}", "f(".repeat(DEPTH), ")".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_unary_ops() {
+            check_max_depth_exceeded(&format!("{}x", "-".repeat(DEPTH)));
+            check_max_depth_exceeded(&format!("{}x", "not ".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_ranges() {
+            check_max_depth_exceeded(&format!("{}x", "0..".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_string_templates() {
+            check_max_depth_exceeded(&format!("{}x{}", "'${".repeat(DEPTH), "}'".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_functions() {
+            check_max_depth_exceeded(&format!("{}x", "|| ".repeat(DEPTH)));
+        }
+
+        #[test]
+        fn nested_blocks() {
+            check_max_depth_exceeded(&indented_lines("if x", DEPTH));
+            check_max_depth_exceeded(&indented_lines("for x in y", DEPTH));
+            check_max_depth_exceeded(&indented_lines("while x", DEPTH));
+        }
+
+        #[test]
+        fn nested_match_patterns() {
+            check_max_depth_exceeded(&format!(
+                "match x\n  {}a{} then 1\n",
+                "(".repeat(DEPTH),
+                ")".repeat(DEPTH)
+            ));
+        }
+
+        #[test]
+        fn custom_max_depth() {
+            let source = "f [(1, {x: 2})]";
+
+            assert!(Parser::parse(source).is_ok());
+
+            let result = Parser::parse_with_settings(
+                source,
+                ParserSettings {
+                    max_depth: 3,
+                    ..Default::default()
+                },
+            );
+            match result {
+                Ok(_) => panic!("Unexpected success while parsing with a small max depth"),
+                Err(error) => assert_eq!(
+                    error.error,
+                    ErrorType::SyntaxError(SyntaxError::MaxDepthExceeded)
+                ),
+            }
+
+            // Each level of nesting counts towards the depth as both an expression and a term
+            let source = "((((((((((1))))))))))";
+            let result = Parser::parse_with_settings(
+                source,
+                ParserSettings {
+                    max_depth: 22,
+                    ..Default::default()
+                },
+            );
+            assert!(result.is_ok());
+        }
+
+        #[test]
+        fn depth_is_restored_after_nested_expressions() {
+            // Sequential expressions don't accumulate depth
+            let source = "(((1)))\n".repeat(100);
+            let result = Parser::parse_with_settings(
+                &source,
+                ParserSettings {
+                    max_depth: 8,
+                    ..Default::default()
+                },
+            );
+            assert!(result.is_ok());
+        }
+    }
+
+    #[cfg(not(feature = "panic_on_parser_error"))]
+    mod node_filter {
+        use koto_parser::{
+            ErrorType, NodeFilter, NodeKind, Parser, ParserSettings, Position, SyntaxError,
+        };
+
+        fn parse_with_filter(
+            source: &str,
+            node_filter: NodeFilter,
+        ) -> Result<koto_parser::Ast, koto_parser::ParserError> {
+            Parser::parse_with_settings(
+                source,
+                ParserSettings {
+                    node_filter,
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn check_disabled(source: &str, node_filter: NodeFilter, expected: NodeKind) {
+            match parse_with_filter(source, node_filter) {
+                Ok(_) => panic!("Unexpected success while parsing:\n{}", source),
+                Err(error) => assert_eq!(
+                    error.error,
+                    ErrorType::SyntaxError(SyntaxError::DisabledNode(expected))
+                ),
+            }
+        }
+
+        #[test]
+        fn denied_nodes() {
+            let filter =
+                || NodeFilter::Deny(vec![NodeKind::Import, NodeKind::Debug, NodeKind::Throw]);
+
+            check_disabled("import foo", filter(), NodeKind::Import);
+            check_disabled("from foo import bar", filter(), NodeKind::Import);
+            check_disabled("debug x", filter(), NodeKind::Debug);
+            check_disabled("f = || throw 'error'", filter(), NodeKind::Throw);
+
+            assert!(parse_with_filter("x = foo.bar 1 + 2", filter()).is_ok());
+        }
+
+        #[test]
+        fn allowed_nodes() {
+            let filter = || {
+                NodeFilter::Allow(vec![
+                    NodeKind::Id,
+                    NodeKind::Number1,
+                    NodeKind::Int,
+                    NodeKind::Assign,
+                    NodeKind::BinaryOp,
+                ])
+            };
+
+            assert!(parse_with_filter("x = 1 + 2\ny = x * 3", filter()).is_ok());
+
+            check_disabled("x = [1, 2]", filter(), NodeKind::List);
+            check_disabled("f = |x| x", filter(), NodeKind::Function);
+            check_disabled("x = y.z", filter(), NodeKind::Lookup);
+        }
+
+        #[test]
+        fn error_span_and_message() {
+            let source = "
+x = 1
+import foo
+";
+            let filter = NodeFilter::Deny(vec![NodeKind::Import]);
+            let error = parse_with_filter(source, filter).unwrap_err();
+
+            assert_eq!(
+                error.span.start,
+                Position {
+                    line: 3,
+                    column: 1,
+                    offset: 7
+                }
+            );
+            assert_eq!(
+                error.to_string(),
+                "'Import' has been disabled in the parser's settings"
+            );
+        }
+    }
+
+    mod recovery {
+        use koto_parser::{Node, Parser};
+
+        // Checks the lines of the recovered errors, along with the nodes in the main block
+        fn check_recovery(source: &str, expected_error_lines: &[u32], expected_body: &[&str]) {
+            let (ast, errors) = Parser::parse_with_recovery(source);
+
+            let error_lines = errors
+                .iter()
+                .map(|error| error.span.start.line)
+                .collect::<Vec<_>>();
+            assert_eq!(error_lines, expected_error_lines, "{:#?}", errors);
+
+            let body = match &ast.entry_point().unwrap().node {
+                Node::MainBlock { body, .. } => body
+                    .iter()
+                    .map(|index| ast.node(*index).node.to_string())
+                    .collect::<Vec<_>>(),
+                other => panic!("Expected main block, found {}", other),
+            };
+            assert_eq!(body, expected_body);
+        }
+
+        #[test]
+        fn multiple_errors() {
+            let source = "
+x = 1 +
+y = 2
+z = (3
+w = 4
+";
+            check_recovery(source, &[2, 4], &["Error", "Assign", "Error", "Assign"]);
+        }
+
+        #[test]
+        fn error_in_indented_block() {
+            let source = "
+f = |x|
+  y = x +
+  y * 2
+g = |x| x * 2
+if
+";
+            check_recovery(source, &[3, 6], &["Error", "Assign", "Error"]);
+        }
+
+        #[test]
+        fn unexpected_indentation() {
+            let source = "
+x = 1
+    y = 2
+z = 3
+";
+            check_recovery(source, &[3], &["Assign", "Error", "Assign"]);
+        }
+
+        #[test]
+        fn valid_script_matches_parse() {
+            let source = "
+f = |x|
+  for y in 0..x
+    debug y
+x = f 42
+";
+            let (recovered_ast, errors) = Parser::parse_with_recovery(source);
+            assert!(errors.is_empty());
+
+            let ast = Parser::parse(source).unwrap();
+            assert_eq!(
+                format!("{:?}", recovered_ast.nodes()),
+                format!("{:?}", ast.nodes())
+            );
+        }
+    }
+
+    #[cfg(not(feature = "panic_on_parser_error"))]
+    mod indentation_settings {
+        use koto_parser::{
+            ErrorType, LexerSettings, Parser, ParserSettings, Position, Span, SyntaxError,
+        };
+
+        fn parse(
+            source: &str,
+            lexer: LexerSettings,
+        ) -> Result<koto_parser::Ast, koto_parser::ParserError> {
+            Parser::parse_with_settings(
+                source,
+                ParserSettings {
+                    lexer,
+                    ..Default::default()
+                },
+            )
+        }
+
+        #[test]
+        fn tab_width() {
+            let source = "\
+f = ||
+\tx = 1
+    x
+";
+            assert!(parse(source, LexerSettings::default()).is_err());
+            assert!(parse(
+                source,
+                LexerSettings {
+                    tab_width: 4,
+                    ..Default::default()
+                }
+            )
+            .is_ok());
+        }
+
+        #[test]
+        fn mixed_indentation() {
+            let strict = LexerSettings {
+                reject_mixed_indentation: true,
+                ..Default::default()
+            };
+
+            assert!(parse("f = ||\n\tx = 1\n\tx\n", strict).is_ok());
+            assert!(parse("f = ||\n  x = 1\n  x\n", strict).is_ok());
+
+            let source = "\
+f = ||
+  x = 1
+\t x
+";
+            assert!(parse(source, LexerSettings::default()).is_ok());
+            match parse(source, strict) {
+                Ok(_) => panic!("Unexpected success while parsing with mixed indentation"),
+                Err(error) => {
+                    assert_eq!(
+                        error.error,
+                        ErrorType::SyntaxError(SyntaxError::MixedIndentation)
+                    );
+                    assert_eq!(
+                        error.span,
+                        Span {
+                            start: Position {
+                                line: 3,
+                                column: 1,
+                                offset: 15,
+                            },
+                            end: Position {
+                                line: 3,
+                                column: 3,
+                                offset: 17,
+                            },
+                        }
+                    );
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,302 @@
+mod scopes {
+    use koto_parser::*;
+
+    fn position(ast: &Ast, node: AstIndex) -> String {
+        let span = ast.span(ast.node(node).span);
+        format!("{}:{}", span.start.line, span.start.column)
+    }
+
+    // Describes each resolved node, ordered by the node's position in the source,
+    // and then by the order in which the nodes were added to the Ast
+    fn describe_scopes(source: &str) -> Vec<String> {
+        let (ast, scopes) = match Parser::parse_with_scopes(source) {
+            Ok(result) => result,
+            Err(error) => panic!("Error while parsing script: {}", error),
+        };
+
+        let mut resolved = scopes.iter().collect::<Vec<_>>();
+        resolved.sort_by_key(|(node, _)| {
+            let span = ast.span(ast.node(*node).span);
+            (span.start.line, span.start.column, *node)
+        });
+
+        resolved
+            .into_iter()
+            .map(|(node, resolution)| {
+                let id = match &ast.node(node).node {
+                    Node::Id(id) | Node::NamedCall { id, .. } | Node::Ellipsis(Some(id)) => *id,
+                    other => panic!("Unexpected resolved node: {}", other),
+                };
+                let name = ast.constants().get_str(id);
+                let position = position(&ast, node);
+
+                match resolution {
+                    IdResolution::Local(local) => format!(
+                        "{} {} local {} {}",
+                        name,
+                        position,
+                        local.slot,
+                        self::position(&ast, local.definition)
+                    ),
+                    IdResolution::Capture(local) => format!(
+                        "{} {} capture {} {}",
+                        name,
+                        position,
+                        local.slot,
+                        self::position(&ast, local.definition)
+                    ),
+                    IdResolution::Global => format!("{} {} global", name, position),
+                }
+            })
+            .collect()
+    }
+
+    fn check_scopes(source: &str, expected: &[&str]) {
+        assert_eq!(describe_scopes(source), expected);
+    }
+
+    #[test]
+    fn locals_and_globals() {
+        let source = "
+x = 1
+y = x + z
+";
+        check_scopes(
+            source,
+            &[
+                "x 2:1 local 0 2:1",
+                "y 3:1 local 1 3:1",
+                "x 3:5 local 0 2:1",
+                "z 3:9 global",
+            ],
+        );
+    }
+
+    #[test]
+    fn access_before_assignment() {
+        // The value of x on the rhs is looked up before the local is assigned
+        check_scopes("x = x + 1", &["x 1:1 local 0 1:1", "x 1:5 global"]);
+    }
+
+    #[test]
+    fn reassignment() {
+        let source = "
+x = 1
+x = 2
+x += 3
+";
+        check_scopes(
+            source,
+            &[
+                "x 2:1 local 0 2:1",
+                "x 3:1 local 0 2:1",
+                "x 4:1 local 0 2:1",
+            ],
+        );
+    }
+
+    #[test]
+    fn function_args_and_captures() {
+        let source = "
+a = 1
+f = |b|
+  c = a + b
+  c
+f 2
+";
+        check_scopes(
+            source,
+            &[
+                "a 2:1 local 0 2:1",
+                "f 3:1 local 1 3:1",
+                "b 3:6 local 0 3:6",
+                "c 4:3 local 1 4:3",
+                "a 4:7 capture 0 2:1",
+                "b 4:11 local 0 3:6",
+                "c 5:3 local 1 4:3",
+                "f 6:1 local 1 3:1",
+            ],
+        );
+    }
+
+    #[test]
+    fn recursive_function() {
+        check_scopes(
+            "f = |n| if n > 0 then f n - 1 else n",
+            &[
+                "f 1:1 local 0 1:1",
+                "n 1:6 local 0 1:6",
+                "n 1:12 local 0 1:6",
+                "f 1:23 capture 0 1:1",
+                "n 1:25 local 0 1:6",
+                "n 1:36 local 0 1:6",
+            ],
+        );
+    }
+
+    #[test]
+    fn capture_through_nested_functions() {
+        let source = "
+x = 1
+f = ||
+  g = || x + y
+";
+        check_scopes(
+            source,
+            &[
+                "x 2:1 local 0 2:1",
+                "f 3:1 local 1 3:1",
+                "g 4:3 local 0 4:3",
+                "x 4:10 capture 0 2:1",
+                "y 4:14 global",
+            ],
+        );
+    }
+
+    #[test]
+    fn definitions_without_id_nodes() {
+        let source = "
+import foo
+for i in foo
+  try
+    i
+  catch e
+    e
+";
+        check_scopes(
+            source,
+            &[
+                "foo 3:10 local 0 2:1",
+                "i 5:5 local 1 3:1",
+                "e 7:5 local 2 4:3",
+            ],
+        );
+    }
+
+    #[test]
+    fn exports() {
+        let source = "
+export x = 1
+y = 2
+export y
+";
+        check_scopes(
+            source,
+            &[
+                "x 2:8 global",
+                "y 3:1 local 0 3:1",
+                "y 4:8 global",
+                "y 4:8 local 0 3:1",
+            ],
+        );
+    }
+
+    #[test]
+    fn match_patterns() {
+        let source = "
+match x
+  (a, rest...) then a + rest.size()
+  b then b
+";
+        check_scopes(
+            source,
+            &[
+                "x 2:7 global",
+                "a 3:4 local 0 3:4",
+                "rest 3:11 local 1 3:11",
+                "a 3:21 local 0 3:4",
+                "rest 3:25 local 1 3:11",
+                "b 4:3 local 2 4:3",
+                "b 4:10 local 2 4:3",
+            ],
+        );
+    }
+
+    #[test]
+    fn string_templates_and_decorators() {
+        let source = "
+x = 1
+f = @memoize || '$x ${x + 1}'
+";
+        check_scopes(
+            source,
+            &[
+                "x 2:1 local 0 2:1",
+                "f 3:1 local 1 3:1",
+                "memoize 3:6 global",
+                "x 3:19 capture 0 2:1",
+                "x 3:23 capture 0 2:1",
+            ],
+        );
+    }
+
+    #[test]
+    fn local_frames() {
+        let source = "
+x = 1
+f = |x| x
+";
+        let (ast, scopes) = Parser::parse_with_scopes(source).unwrap();
+
+        let function = ast
+            .nodes()
+            .iter()
+            .position(|node| matches!(node.node, Node::Function(_)))
+            .unwrap() as AstIndex;
+
+        let frames = scopes
+            .iter()
+            .filter_map(|(_, resolution)| match resolution {
+                IdResolution::Local(local) => Some(local.frame),
+                _ => None,
+            })
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames.contains(&function));
+        assert!(frames.contains(&ast.entry_point_index()));
+    }
+
+    #[test]
+    fn references() {
+        let source = "
+count = 0
+increment = ||
+  count + 1
+count = increment()
+";
+        let (ast, scopes) = Parser::parse_with_scopes(source).unwrap();
+
+        let definition = ast
+            .nodes()
+            .iter()
+            .position(|node| matches!(node.node, Node::Id(_)))
+            .unwrap() as AstIndex;
+        let local = match scopes.resolve(definition) {
+            Some(IdResolution::Local(local)) => local,
+            other => panic!("Unexpected resolution: {:?}", other),
+        };
+
+        let positions = scopes
+            .references(&local)
+            .into_iter()
+            .map(|node| position(&ast, node))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, ["2:1", "4:3", "5:1"]);
+    }
+
+    #[test]
+    fn nodes_without_ids_are_unresolved() {
+        let (ast, scopes) = Parser::parse_with_scopes("x = 1 + 2").unwrap();
+        let ints = ast
+            .nodes()
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node.node, Node::Int(_)))
+            .map(|(index, _)| index as AstIndex)
+            .collect::<Vec<_>>();
+
+        assert!(!ints.is_empty());
+        assert!(ints.iter().all(|node| scopes.resolve(*node).is_none()));
+    }
+}
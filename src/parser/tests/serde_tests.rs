@@ -0,0 +1,74 @@
+#![cfg(feature = "serde")]
+
+mod serde {
+    use koto_parser::*;
+
+    fn check_round_trip(source: &str) -> Ast {
+        let ast = Parser::parse(source).unwrap();
+
+        let json = serde_json::to_string(&ast).unwrap();
+        let deserialized: Ast = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(ast.nodes().len(), deserialized.nodes().len());
+        for (index, (expected, actual)) in ast.nodes().iter().zip(deserialized.nodes()).enumerate()
+        {
+            assert_eq!(expected.node, actual.node, "Mismatch at node {}", index);
+            assert_eq!(ast.span(expected.span), deserialized.span(actual.span));
+        }
+        assert_eq!(ast.entry_point_index(), deserialized.entry_point_index());
+        assert_eq!(ast.constants(), deserialized.constants());
+        assert_eq!(
+            ast.constants().iter().collect::<Vec<_>>(),
+            deserialized.constants().iter().collect::<Vec<_>>()
+        );
+
+        deserialized
+    }
+
+    #[test]
+    fn round_trip() {
+        check_round_trip(
+            "
+import foo.bar
+x = [1, 2.5, 'hello $foo']
+f = |a, b...|
+  match a
+    (0, y) or (y, 0) then y
+    else b.size()
+for i in 0..10
+  try
+    x.push f i
+  catch e
+    debug e
+",
+        );
+    }
+
+    #[test]
+    fn constants_are_serialized_in_order() {
+        let ast = Parser::parse("x = 'abc', 42, -1.5").unwrap();
+        let json = serde_json::to_value(ast.constants()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{"Str": "x"}, {"Str": "abc"}, {"I64": 42}, {"F64": -1.5}])
+        );
+    }
+
+    #[test]
+    fn duplicate_constants_are_preserved() {
+        let json = r#"[{"Str": "a"}, {"I64": 1}, {"Str": "a"}]"#;
+        let constants: ConstantPool = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            constants.iter().collect::<Vec<_>>(),
+            &[Constant::Str("a"), Constant::I64(1), Constant::Str("a")]
+        );
+    }
+
+    #[test]
+    fn out_of_range_constant_index() {
+        let result = serde_json::from_str::<ConstantIndex>("4294967296");
+        assert!(result.is_err());
+        let index = serde_json::from_str::<ConstantIndex>("4294967295").unwrap();
+        assert_eq!(usize::from(index), 4294967295);
+    }
+}
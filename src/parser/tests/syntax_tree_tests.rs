@@ -0,0 +1,378 @@
+mod syntax_tree {
+    use koto_parser::*;
+
+    // Produces an indented summary of the tree, with one node or token per line
+    fn tree_summary(tree: &SyntaxTree) -> String {
+        fn summarize_node(node: SyntaxNode, depth: usize, result: &mut String) {
+            result.push_str(&format!("{}{:?}\n", "  ".repeat(depth), node.kind()));
+            for child in node.children() {
+                match child {
+                    SyntaxElement::Node(node) => summarize_node(node, depth + 1, result),
+                    SyntaxElement::Token(token) => result.push_str(&format!(
+                        "{}{:?} {:?}\n",
+                        "  ".repeat(depth + 1),
+                        token.kind,
+                        token.text
+                    )),
+                }
+            }
+        }
+
+        let mut result = String::new();
+        summarize_node(tree.root(), 0, &mut result);
+        result
+    }
+
+    fn check_tree(source: &str, expected: &str) {
+        let tree = SyntaxTree::parse(source);
+        assert_eq!(tree.text(), source);
+        assert_eq!(tree_summary(&tree), expected);
+    }
+
+    // Describes the comments and newlines that are attached to each line in the tree
+    fn line_trivia(tree: &SyntaxTree) -> Vec<String> {
+        fn describe(trivia: &[SyntaxToken]) -> String {
+            trivia
+                .iter()
+                .filter(|token| token.kind != Token::Whitespace)
+                .map(|token| {
+                    if token.kind.is_newline() {
+                        "\\n"
+                    } else {
+                        token.text
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+
+        fn describe_node(node: SyntaxNode, result: &mut Vec<String>) {
+            if node.kind() == SyntaxKind::Line {
+                let first_token = node
+                    .tokens()
+                    .into_iter()
+                    .find(|token| !token.kind.is_trivia())
+                    .unwrap();
+                let leading = describe(&node.leading_trivia());
+                if !leading.is_empty() {
+                    result.push(format!("{} leading: {}", first_token.text, leading));
+                }
+                let trailing = describe(&node.trailing_trivia());
+                if !trailing.is_empty() {
+                    result.push(format!("{} trailing: {}", first_token.text, trailing));
+                }
+            }
+
+            for child in node.child_nodes() {
+                describe_node(child, result);
+            }
+        }
+
+        let mut result = Vec::new();
+        describe_node(tree.root(), &mut result);
+        result
+    }
+
+    fn check_line_trivia(source: &str, expected: &[&str]) {
+        let tree = SyntaxTree::parse(source);
+        assert_eq!(line_trivia(&tree), expected);
+    }
+
+    #[test]
+    fn lines_and_blocks() {
+        let source = "\
+# foo
+f = |x|
+  x
+
+  x
+f()
+";
+        check_tree(
+            source,
+            r##"Root
+  Line
+    CommentSingle "# foo"
+    NewLine "\n"
+    Id "f"
+    Whitespace " "
+    Assign "="
+    Whitespace " "
+    Function "|"
+    Id "x"
+    Function "|"
+    NewLineIndented "\n  "
+    Block
+      Line
+        Id "x"
+        NewLine "\n"
+      Line
+        NewLineIndented "\n  "
+        Id "x"
+        NewLine "\n"
+  Line
+    Id "f"
+    Parentheses
+      RoundOpen "("
+      RoundClose ")"
+    NewLine "\n"
+"##,
+        );
+    }
+
+    #[test]
+    fn brackets_and_strings() {
+        let source = "[{a: 1}, '$x ${(y)}']";
+        check_tree(
+            source,
+            r#"Root
+  Line
+    SquareBrackets
+      SquareOpen "["
+      CurlyBraces
+        CurlyOpen "{"
+        Id "a"
+        Colon ":"
+        Whitespace " "
+        Number "1"
+        CurlyClose "}"
+      Comma ","
+      Whitespace " "
+      Str
+        SingleQuote "'"
+        Dollar "$"
+        Id "x"
+        StringLiteral " "
+        Dollar "$"
+        CurlyBraces
+          CurlyOpen "{"
+          Parentheses
+            RoundOpen "("
+            Id "y"
+            RoundClose ")"
+          CurlyClose "}"
+        SingleQuote "'"
+      SquareClose "]"
+"#,
+        );
+    }
+
+    #[test]
+    fn newlines_in_brackets_dont_start_new_lines() {
+        let source = "\
+x = [
+  1,
+]
+";
+        check_tree(
+            source,
+            r#"Root
+  Line
+    Id "x"
+    Whitespace " "
+    Assign "="
+    Whitespace " "
+    SquareBrackets
+      SquareOpen "["
+      NewLineIndented "\n  "
+      Number "1"
+      Comma ","
+      NewLine "\n"
+      SquareClose "]"
+    NewLine "\n"
+"#,
+        );
+    }
+
+    #[test]
+    fn scripts_with_errors() {
+        check_tree(
+            "x)\n'abc",
+            r#"Root
+  Line
+    Id "x"
+    RoundClose ")"
+    NewLine "\n"
+  Line
+    Str
+      SingleQuote "'"
+      Error "abc"
+"#,
+        );
+    }
+
+    #[test]
+    fn leading_and_trailing_comments() {
+        let source = "\
+# x is one
+x = 1 # one
+
+# y is two
+y = 2
+";
+        check_line_trivia(
+            source,
+            &[
+                "x leading: # x is one \\n",
+                "x trailing: # one \\n",
+                "y leading: \\n # y is two \\n",
+                "y trailing: \\n",
+            ],
+        );
+    }
+
+    #[test]
+    fn comments_in_function_body() {
+        let source = "\
+f = |x|
+  # Double x
+  y = x * 2 #- twice -#
+  y
+";
+        check_line_trivia(
+            source,
+            &[
+                "f trailing: \\n",
+                "y leading: # Double x \\n",
+                "y trailing: #- twice -# \\n",
+                "y trailing: \\n",
+            ],
+        );
+    }
+
+    #[test]
+    fn trivia_at_the_end_of_the_script() {
+        let tree = SyntaxTree::parse("x = 1\n\n# The end\n");
+        let root = tree.root();
+        assert!(root.leading_trivia().is_empty());
+        let trailing: Vec<_> = root
+            .trailing_trivia()
+            .iter()
+            .map(|token| token.text)
+            .collect();
+        assert_eq!(trailing, &["\n", "# The end", "\n"]);
+    }
+
+    #[test]
+    fn finding_the_line_for_an_ast_node() {
+        let source = "\
+f = ||
+  # Exported
+  export x = 1 # one
+";
+        let tree = SyntaxTree::parse(source);
+        let ast = Parser::parse(source).unwrap();
+
+        let export = ast
+            .nodes()
+            .iter()
+            .find(|node| match &node.node {
+                Node::Assign { target, .. } => target.scope == Scope::Export,
+                _ => false,
+            })
+            .unwrap();
+        let range = ast.span(export.span).byte_range();
+
+        let line = tree.root().line_containing(range).unwrap();
+        assert_eq!(line.text(), "# Exported\n  export x = 1 # one\n");
+        assert_eq!(line.leading_trivia()[0].text, "# Exported");
+        assert_eq!(line.trailing_trivia()[1].text, "# one");
+    }
+
+    #[test]
+    fn node_ranges_and_tokens() {
+        let source = "f = |x|\n  x + 1\n";
+        let tree = SyntaxTree::parse(source);
+
+        let line = tree.root().child_nodes().next().unwrap();
+        assert_eq!(line.range(), 0..source.len());
+
+        let block = line.child_nodes().next().unwrap();
+        assert_eq!(block.kind(), SyntaxKind::Block);
+        assert_eq!(block.range(), 10..source.len());
+        assert_eq!(block.text(), "x + 1\n");
+
+        let tokens = tree.root().tokens();
+        assert_eq!(tokens.len(), 14);
+        for token in tokens.iter() {
+            assert_eq!(&source[token.range.clone()], token.text);
+        }
+
+        let token = tree.root().token_at_offset(14).unwrap();
+        assert_eq!(token.kind, Token::Number);
+        assert_eq!(token.range, 14..15);
+        assert!(tree.root().token_at_offset(source.len()).is_none());
+    }
+
+    #[test]
+    fn edits_match_a_full_reparse() {
+        let source = r#"
+# A script for testing edits
+import foo
+
+f = |x, y|
+  z = x + y
+
+  match z
+    0 then 'zero'
+    n if n > 10 then "big: ${n}"
+    else
+      [
+        1, 2
+      ]
+
+#-
+multi
+-#
+f 1, 2
+"#;
+        let tree = SyntaxTree::parse(source);
+        let insertions = ["\n", "  ", "x", "'", "\"", "(", "]", "#", "#-", "$", "\n  "];
+
+        for position in 0..=source.len() {
+            for insertion in insertions.iter() {
+                let mut expected = source.to_string();
+                expected.insert_str(position, insertion);
+                assert_eq!(
+                    tree.edit(position..position, insertion),
+                    SyntaxTree::parse(&expected),
+                    "Mismatch when inserting {:?} at {}",
+                    insertion,
+                    position
+                );
+            }
+
+            for end in position + 1..(position + 4).min(source.len() + 1) {
+                let mut expected = source.to_string();
+                expected.replace_range(position..end, "");
+                assert_eq!(
+                    tree.edit(position..end, ""),
+                    SyntaxTree::parse(&expected),
+                    "Mismatch when removing {}..{}",
+                    position,
+                    end
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn edits_reuse_unchanged_lines() {
+        let source = "\
+a = 1
+b = 2
+c = 3
+";
+        let tree = SyntaxTree::parse(source);
+        let edited = tree.edit(10..11, "42");
+        assert_eq!(edited.text(), "a = 1\nb = 42\nc = 3\n");
+
+        let old_lines: Vec<_> = tree.root().child_nodes().collect();
+        let new_lines: Vec<_> = edited.root().child_nodes().collect();
+        assert_eq!(new_lines.len(), 3);
+        assert!(new_lines[0].ptr_eq(&old_lines[0]));
+        assert!(!new_lines[1].ptr_eq(&old_lines[1]));
+        assert!(new_lines[2].ptr_eq(&old_lines[2]));
+        assert_eq!(new_lines[2].range(), 13..19);
+    }
+}
@@ -0,0 +1,236 @@
+mod visitor {
+    use koto_parser::*;
+
+    // Records the order in which nodes are entered and exited
+    #[derive(Default)]
+    struct NodeRecorder {
+        events: Vec<String>,
+        skip_function_bodies: bool,
+    }
+
+    impl Visitor for NodeRecorder {
+        fn enter(&mut self, _ast: &Ast, _index: AstIndex, node: &Node, span: &Span) -> bool {
+            self.events
+                .push(format!("enter {} {}", node, span.start.line));
+            !(self.skip_function_bodies && matches!(node, Node::Function(_)))
+        }
+
+        fn exit(&mut self, _ast: &Ast, _index: AstIndex, node: &Node, _span: &Span) {
+            self.events.push(format!("exit {}", node));
+        }
+    }
+
+    fn check_walk(source: &str, skip_function_bodies: bool, expected: &[&str]) {
+        let ast = Parser::parse(source).unwrap();
+        let mut recorder = NodeRecorder {
+            skip_function_bodies,
+            ..Default::default()
+        };
+        walk(&ast, &mut recorder);
+        assert_eq!(recorder.events, expected);
+    }
+
+    #[test]
+    fn walk_assignment() {
+        let source = "
+x = 1 + y
+'$x!'
+";
+        check_walk(
+            source,
+            false,
+            &[
+                "enter MainBlock 1",
+                "enter Assign 2",
+                "enter Id 2",
+                "exit Id",
+                "enter BinaryOp 2",
+                "enter Number1 2",
+                "exit Number1",
+                "enter Id 2",
+                "exit Id",
+                "exit BinaryOp",
+                "exit Assign",
+                "enter Str 3",
+                "enter Id 3",
+                "exit Id",
+                "exit Str",
+                "exit MainBlock",
+            ],
+        );
+    }
+
+    #[test]
+    fn walk_skipping_children() {
+        let source = "
+f = |x|
+  x * x
+f 2
+";
+        check_walk(
+            source,
+            true,
+            &[
+                "enter MainBlock 1",
+                "enter Assign 3",
+                "enter Id 2",
+                "exit Id",
+                "enter Function 2",
+                "exit Function",
+                "exit Assign",
+                "enter NamedCall 4",
+                "enter Int 4",
+                "exit Int",
+                "exit NamedCall",
+                "exit MainBlock",
+            ],
+        );
+    }
+
+    #[test]
+    fn walk_visits_every_node() {
+        let source = r#"
+import foo.bar, "baz"
+f = @memoize |a, b...|
+  match a, b
+    (0, _), [] then "$a"
+    x if x > 1 then x
+    else
+      for i in 0..=10
+        while false
+          continue
+  switch
+    a then {a, "b": [1, 2], @meta c}
+    else
+      try
+        debug a.b."c"[0](1) >> g
+      catch e
+        throw e
+      finally
+        yield assert not a
+x, y = f 1
+"#;
+        struct Counter(Vec<usize>);
+
+        impl Visitor for Counter {
+            fn enter(&mut self, _: &Ast, index: AstIndex, _: &Node, _: &Span) -> bool {
+                self.0[index as usize] += 1;
+                true
+            }
+        }
+
+        let ast = Parser::parse(source).unwrap();
+        let mut counter = Counter(vec![0; ast.nodes().len()]);
+        walk(&ast, &mut counter);
+
+        for (index, count) in counter.0.iter().enumerate() {
+            assert_eq!(
+                *count,
+                1,
+                "Node {} ({}) visited {} times",
+                index,
+                ast.node(index as AstIndex).node,
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn rewrite_nodes() {
+        let source = "
+a = 1 + 2
+b = a - 1
+";
+        let ast = Parser::parse(source).unwrap();
+
+        // Swap addition and subtraction, and replace `1` with `x + 0`
+        let rewritten = rewrite(&ast, |ast, node, span| {
+            let result = match node {
+                Node::BinaryOp { op, lhs, rhs } => {
+                    let op = match op {
+                        AstBinaryOp::Add => AstBinaryOp::Subtract,
+                        AstBinaryOp::Subtract => AstBinaryOp::Add,
+                        other => other,
+                    };
+                    Node::BinaryOp { op, lhs, rhs }
+                }
+                Node::Number1 => {
+                    let x = ast.push(Node::Id(ConstantIndex::from(0u8)), span)?;
+                    let zero = ast.push(Node::Number0, span)?;
+                    Node::BinaryOp {
+                        op: AstBinaryOp::Add,
+                        lhs: x,
+                        rhs: zero,
+                    }
+                }
+                other => other,
+            };
+            Ok(result)
+        })
+        .unwrap();
+
+        let a = ConstantIndex::from(0u8);
+        let two = ConstantIndex::from(1u8);
+        let b = ConstantIndex::from(2u8);
+
+        let expected = &[
+            Node::Id(a),
+            Node::Id(a), // x + 0
+            Node::Number0,
+            Node::BinaryOp {
+                op: AstBinaryOp::Add,
+                lhs: 1,
+                rhs: 2,
+            },
+            Node::Int(two),
+            Node::BinaryOp {
+                op: AstBinaryOp::Subtract,
+                lhs: 3,
+                rhs: 4,
+            },
+            Node::Assign {
+                target: AssignTarget {
+                    target_index: 0,
+                    scope: Scope::Local,
+                },
+                op: AssignOp::Equal,
+                expression: 5,
+            },
+            Node::Id(b),
+            Node::Id(a),
+            Node::Id(a), // x + 0
+            Node::Number0,
+            Node::BinaryOp {
+                op: AstBinaryOp::Add,
+                lhs: 9,
+                rhs: 10,
+            },
+            Node::BinaryOp {
+                op: AstBinaryOp::Add,
+                lhs: 8,
+                rhs: 11,
+            },
+            Node::Assign {
+                target: AssignTarget {
+                    target_index: 7,
+                    scope: Scope::Local,
+                },
+                op: AssignOp::Equal,
+                expression: 12,
+            },
+            Node::MainBlock {
+                body: vec![6, 13],
+                local_count: 2,
+            },
+        ];
+
+        for (i, (ast_node, expected_node)) in
+            rewritten.nodes().iter().zip(expected.iter()).enumerate()
+        {
+            assert_eq!(ast_node.node, *expected_node, "Mismatch at position {}", i);
+        }
+        assert_eq!(rewritten.nodes().len(), expected.len());
+        assert_eq!(rewritten.entry_point_index(), 14);
+        assert_eq!(rewritten.constants().size(), ast.constants().size());
+    }
+}
@@ -0,0 +1,123 @@
+use {
+    crate::{DataMap, Value},
+    koto_bytecode::Chunk,
+    std::{
+        convert::TryFrom,
+        num::NonZeroU32,
+        sync::{Arc, Weak},
+    },
+};
+
+/// Inline caches for `.` accesses with constant keys
+///
+/// Each `Access` instruction that finds its key in a map's data caches the index of the map entry
+/// that it found. When the instruction runs again, the entry at the cached index is checked first,
+/// which avoids hashing the key when the accessed maps share a layout, e.g. when a loop repeatedly
+/// accesses the same map, or when maps are produced by the same constructor function.
+///
+/// Removing entries from a map changes the indices of its remaining entries, so a cached index is
+/// only used if the entry at the index still has the expected key. Otherwise the cache is
+/// invalidated, and the result of a regular lookup is cached in its place.
+///
+/// The cached indices for each chunk are stored in a list that's indexed by instruction position.
+/// Chunks are held with weak references, and the lists for chunks that have been dropped are
+/// removed when a new chunk is added to the cache.
+#[derive(Clone, Default)]
+pub(crate) struct AccessCache {
+    // The cached indices for each chunk, with the most recently accessed chunk first
+    chunks: Vec<ChunkSlots>,
+}
+
+#[derive(Clone)]
+struct ChunkSlots {
+    chunk: Weak<Chunk>,
+    // The cached entry index for each instruction position, stored with 1 added
+    slots: Vec<Option<NonZeroU32>>,
+}
+
+impl AccessCache {
+    /// Looks up `key` in the map data, using the cached index for the instruction if available
+    pub fn get(
+        &mut self,
+        chunk: &Arc<Chunk>,
+        ip: usize,
+        data: &DataMap,
+        key: &str,
+    ) -> Option<Value> {
+        let slot = &mut self.chunk_slots(chunk)[ip];
+
+        if let Some(cached) = *slot {
+            match data.get_index(cached.get() as usize - 1) {
+                Some((entry_key, value)) if key_matches(entry_key.value(), key) => {
+                    return Some(value.clone());
+                }
+                _ => *slot = None,
+            }
+        }
+
+        let (index, value) = data.get_full_with_string(key)?;
+        *slot = u32::try_from(index + 1).ok().and_then(NonZeroU32::new);
+        Some(value.clone())
+    }
+
+    // Returns the cached indices for the chunk, moving the chunk to the front of the cache
+    fn chunk_slots(&mut self, chunk: &Arc<Chunk>) -> &mut [Option<NonZeroU32>] {
+        let position = self
+            .chunks
+            .iter()
+            .position(|entry| entry.chunk.as_ptr() == Arc::as_ptr(chunk));
+
+        match position {
+            Some(0) => {}
+            Some(position) => self.chunks.swap(0, position),
+            None => {
+                self.chunks.retain(|entry| entry.chunk.strong_count() > 0);
+                self.chunks.insert(
+                    0,
+                    ChunkSlots {
+                        chunk: Arc::downgrade(chunk),
+                        slots: vec![None; chunk.bytes.len()],
+                    },
+                );
+            }
+        }
+
+        &mut self.chunks[0].slots
+    }
+}
+
+fn key_matches(entry_key: &Value, key: &str) -> bool {
+    matches!(entry_key, Value::Str(entry_key) if entry_key.as_str() == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::ValueString};
+
+    #[test]
+    fn dropped_chunks_are_removed() {
+        let mut data = DataMap::new();
+        data.insert(ValueString::from("x").into(), 42.into());
+
+        let mut cache = AccessCache::default();
+        let chunk_a = Arc::new(Chunk::new(
+            vec![0; 4],
+            Default::default(),
+            None,
+            Default::default(),
+        ));
+        assert!(cache.get(&chunk_a, 2, &data, "x").is_some());
+        assert_eq!(cache.chunks.len(), 1);
+        drop(chunk_a);
+
+        let chunk_b = Arc::new(Chunk::new(
+            vec![0; 4],
+            Default::default(),
+            None,
+            Default::default(),
+        ));
+        assert!(cache.get(&chunk_b, 2, &data, "x").is_some());
+        assert_eq!(cache.chunks.len(), 1);
+        assert_eq!(cache.chunks[0].slots[2], NonZeroU32::new(1));
+    }
+}
@@ -0,0 +1,40 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A trait for the source of time that's used by the core library, see [VmSettings::clock]
+///
+/// Replacing the default clock allows scripts to follow a simulated time (e.g. a game's clock),
+/// or to be run deterministically in tests.
+///
+/// [VmSettings::clock]: crate::VmSettings::clock
+pub trait KotoClock: Send + Sync {
+    /// Returns the current time, as the duration since the Unix epoch
+    ///
+    /// Clocks that follow a simulated time are free to choose their own epoch.
+    fn now(&self) -> Duration;
+
+    /// Suspends the current thread for the provided duration
+    ///
+    /// Clocks that follow a simulated time can advance their time rather than suspending
+    /// the thread.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The default clock used in Koto, following the system time
+#[derive(Default)]
+pub struct DefaultClock {}
+
+impl KotoClock for DefaultClock {
+    fn now(&self) -> Duration {
+        // A system time that's earlier than the epoch is treated as the epoch
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
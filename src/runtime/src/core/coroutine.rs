@@ -0,0 +1,231 @@
+use {
+    crate::{
+        runtime_error, CallArgs, ExternalData, ExternalValue, MetaKey, MetaMap, Mutex,
+        RuntimeError, RuntimeResult, RwLock, Value, ValueMap, Vm,
+    },
+    lazy_static::lazy_static,
+    std::{fmt, mem, slice, sync::Arc},
+};
+
+pub fn make_module() -> ValueMap {
+    let mut result = ValueMap::new();
+
+    result.add_fn("create", |vm, args| match vm.get_args(args) {
+        [f] if Coroutine::can_run(f) => {
+            let f = f.clone();
+            Ok(Coroutine::new(vm, f)?.into())
+        }
+        [unexpected] => runtime_error!(
+            "coroutine.create: Expected function as argument, found '{}'",
+            unexpected.type_as_string(),
+        ),
+        _ => runtime_error!("coroutine.create: Expected function as argument"),
+    });
+
+    result
+}
+
+lazy_static! {
+    static ref COROUTINE_META: Arc<RwLock<MetaMap>> = {
+        let mut meta = MetaMap::with_type_name("Coroutine");
+
+        // The coroutine is cloned out of the external value before being resumed,
+        // so that the value's data isn't locked while the coroutine is running.
+        meta.add_instance_fn(MetaKey::Named("resume".into()), |vm, args| {
+            let (coroutine, value) = match vm.get_args(args) {
+                [instance] => (Coroutine::from_value(instance), Value::Empty),
+                [instance, value] => (Coroutine::from_value(instance), value.clone()),
+                _ => return runtime_error!("Coroutine.resume: Expected an optional value"),
+            };
+
+            match coroutine {
+                Some(coroutine) => coroutine.resume(value),
+                None => runtime_error!("Coroutine.resume: Expected a Coroutine as argument"),
+            }
+        });
+
+        meta.add_named_instance_fn("status", |coroutine: &Coroutine, _, _| {
+            Ok(coroutine.status().as_str().into())
+        });
+
+        Arc::new(RwLock::new(meta))
+    };
+}
+
+/// The status of a [Coroutine]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoroutineStatus {
+    /// The coroutine is waiting to be started, or to be resumed after yielding a value
+    Suspended,
+    /// The coroutine is running
+    Running,
+    /// The coroutine's function has returned, or an error was thrown
+    Finished,
+}
+
+impl CoroutineStatus {
+    /// The status as a string, as returned by `Coroutine.status` in Koto
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Suspended => "suspended",
+            Self::Running => "running",
+            Self::Finished => "finished",
+        }
+    }
+}
+
+impl fmt::Display for CoroutineStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A function that can be suspended and resumed, see [Coroutine::resume]
+///
+/// Coroutines generalize the VM's generators: a coroutine that's made from a generator function
+/// (i.e. a function that contains `yield`) runs until it yields a value, and then waits to be
+/// resumed. The value that's passed to the next call to `resume` becomes the result of the
+/// `yield` expression.
+///
+/// Coroutines can be made from any callable value, with functions that don't yield running to
+/// completion when the coroutine is first resumed.
+///
+/// Clones of a coroutine refer to the same underlying coroutine.
+#[derive(Clone)]
+pub struct Coroutine(Arc<Mutex<CoroutineState>>);
+
+enum CoroutineState {
+    // The coroutine hasn't been started,
+    // the function will be called with the value that's passed to the first resume call
+    Created { vm: Vm, function: Value },
+    // The coroutine's generator VM is waiting to be resumed
+    Suspended(Vm),
+    // The coroutine's VM has been taken while it's running
+    Running,
+    Finished,
+}
+
+impl Coroutine {
+    /// Makes a new coroutine that will call the provided function
+    ///
+    /// The function will be run in a VM that shares the provided VM's context.
+    pub fn new(vm: &Vm, function: Value) -> Result<Self, RuntimeError> {
+        if !Self::can_run(&function) {
+            return runtime_error!(
+                "Expected a function for the coroutine, found '{}'",
+                function.type_as_string()
+            );
+        }
+
+        Ok(Self(Arc::new(Mutex::new(CoroutineState::Created {
+            vm: vm.spawn_shared_vm(),
+            function,
+        }))))
+    }
+
+    /// Returns true if the value is a function that can be run by a coroutine
+    ///
+    /// Along with callable values, generator functions are accepted.
+    pub fn can_run(value: &Value) -> bool {
+        value.is_callable() || matches!(value, Value::Generator(_))
+    }
+
+    /// Returns the coroutine contained in the value, if it's a coroutine
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::ExternalValue(value) => value.data().downcast_ref::<Self>().cloned(),
+            _ => None,
+        }
+    }
+
+    /// The coroutine's current status
+    pub fn status(&self) -> CoroutineStatus {
+        match &*self.0.lock() {
+            CoroutineState::Created { .. } | CoroutineState::Suspended(_) => {
+                CoroutineStatus::Suspended
+            }
+            CoroutineState::Running => CoroutineStatus::Running,
+            CoroutineState::Finished => CoroutineStatus::Finished,
+        }
+    }
+
+    /// Runs the coroutine until it yields a value or finishes
+    ///
+    /// When the coroutine is started, a non-empty value is passed as an argument to the function.
+    /// Otherwise, the value is used as the result of the `yield` expression that suspended the
+    /// coroutine.
+    ///
+    /// The yielded value is returned, or the function's result once it finishes. An error is
+    /// returned if the coroutine is already running, or if it has finished.
+    pub fn resume(&self, value: Value) -> RuntimeResult {
+        use CoroutineState::*;
+
+        let state = mem::replace(&mut *self.0.lock(), Running);
+
+        let (result, suspended_vm) = match state {
+            Created { mut vm, function } => {
+                let args = match &value {
+                    Value::Empty => &[],
+                    _ => slice::from_ref(&value),
+                };
+
+                match function {
+                    Value::Generator(function) => {
+                        match vm.make_generator_vm(function, None, args, None) {
+                            Ok(mut generator_vm) => {
+                                let result = generator_vm.continue_running();
+                                (result, Some(generator_vm))
+                            }
+                            Err(error) => (Err(error), None),
+                        }
+                    }
+                    _ => (vm.run_function(function, CallArgs::Separate(args)), None),
+                }
+            }
+            Suspended(mut vm) => {
+                let result = vm.resume_generator(value);
+                (result, Some(vm))
+            }
+            Running => return runtime_error!("The coroutine is already running"),
+            Finished => {
+                *self.0.lock() = Finished;
+                return runtime_error!("The coroutine has finished");
+            }
+        };
+
+        // The coroutine can be resumed again if its generator VM is still running
+        *self.0.lock() = match suspended_vm {
+            Some(vm) if result.is_ok() && !vm.call_stack().is_empty() => Suspended(vm),
+            _ => Finished,
+        };
+
+        result
+    }
+}
+
+impl From<Coroutine> for Value {
+    fn from(coroutine: Coroutine) -> Self {
+        Value::ExternalValue(ExternalValue::with_shared_meta_map(
+            coroutine,
+            COROUTINE_META.clone(),
+        ))
+    }
+}
+
+impl ExternalData for Coroutine {
+    fn value_type(&self) -> String {
+        "Coroutine".to_string()
+    }
+}
+
+impl fmt::Display for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Coroutine")
+    }
+}
+
+impl fmt::Debug for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Coroutine ({})", self.status())
+    }
+}
@@ -408,6 +408,22 @@ pub fn make_module() -> ValueMap {
             .map_err(|e| e.with_prefix("iterator.product"))
     });
 
+    result.add_fn("resume", |vm, args| {
+        let (mut iterator, value) = match vm.get_args(args) {
+            [Iterator(i)] => (i.clone(), Value::Empty),
+            [Iterator(i), value] => (i.clone(), value.clone()),
+            _ => return runtime_error!("iterator.resume: Expected iterator as argument"),
+        };
+
+        match iterator.resume(value) {
+            Ok(Some(Output::Value(value))) => Ok(value),
+            Ok(Some(Output::Error(error))) => Err(error),
+            Ok(None) => Ok(Value::Empty),
+            Ok(Some(Output::ValuePair(_, _))) => unreachable!(),
+            Err(error) => Err(error.with_prefix("iterator.resume")),
+        }
+    });
+
     result.add_fn("skip", |vm, args| match vm.get_args(args) {
         [iterable, Number(n)] if iterable.is_iterable() && *n >= 0.0 => {
             let mut iter = make_iterator(iterable).unwrap();
@@ -1,18 +1,72 @@
-use crate::{runtime_error, Value, ValueMap, ValueTuple};
+use {
+    crate::{
+        runtime_error, ExternalData, ExternalValue, MetaKey, MetaMap, UnaryOp, Value, ValueMap,
+        ValueString, ValueTuple,
+    },
+    std::fmt,
+};
 
 pub fn make_module() -> ValueMap {
     use Value::*;
 
     let mut result = ValueMap::new();
 
+    // koto.current_module is an alias for koto.exports
+    let exports = ExternalFunction(crate::ExternalFunction::new(
+        |vm, _| Ok(Value::Map(vm.context_mut().exports.clone())),
+        false,
+    ));
+
     result.add_value("args", Tuple(ValueTuple::default()));
 
-    result.add_fn("exports", |vm, _| {
-        Ok(Value::Map(vm.context_mut().exports.clone()))
+    result.add_value("current_module", exports.clone());
+
+    result.add_fn("deep_copy", |vm, args| match vm.get_args(args) {
+        [value] => Ok(value.deep_copy()),
+        _ => runtime_error!("koto.deep_copy: Expected a value as argument"),
+    });
+
+    result.add_fn("error", |vm, args| {
+        let (message, cause) = match vm.get_args(args) {
+            [Str(message)] => (message.clone(), Empty),
+            [Str(message), Map(options)] => (
+                message.clone(),
+                options
+                    .data()
+                    .get_with_string("cause")
+                    .cloned()
+                    .unwrap_or(Empty),
+            ),
+            _ => {
+                return runtime_error!(
+                    "koto.error: Expected a message as a string, and optional map with a cause"
+                )
+            }
+        };
+
+        if !(matches!(cause, Empty | Str(_)) || is_throwable_map(&cause)) {
+            return runtime_error!(
+                "koto.error: Expected a string or a map with @display as cause, found '{}'",
+                cause.type_as_string()
+            );
+        }
+
+        Ok(make_error(message, cause))
+    });
+
+    result.add_value("exports", exports);
+
+    result.add_fn("import_all_in", |vm, args| match vm.get_args(args) {
+        [Str(dir)] => {
+            let dir = dir.clone();
+            vm.import_all_in(&dir)
+        }
+        _ => runtime_error!("koto.import_all_in: Expected a directory path as a string"),
     });
 
     result.add_value("script_dir", Empty);
     result.add_value("script_path", Empty);
+    result.add_value("settings", make_settings(&[]));
 
     result.add_fn("type", |vm, args| match vm.get_args(args) {
         [value] => Ok(Str(value.type_as_string().into())),
@@ -21,3 +75,75 @@ pub fn make_module() -> ValueMap {
 
     result
 }
+
+/// Makes an error map, as returned by `koto.error`
+///
+/// The map contains the error's `message` and `cause`, and uses the message for its `@display`
+/// function. When the map is thrown, the cause is included in the [RuntimeError]'s chain of
+/// causes.
+///
+/// [RuntimeError]: crate::RuntimeError
+pub fn make_error(message: ValueString, cause: Value) -> Value {
+    let mut meta = MetaMap::with_type_name("Error");
+    meta.add_instance_fn(MetaKey::UnaryOp(UnaryOp::Display), |vm, args| {
+        match vm.get_args(args) {
+            [Value::Map(error)] => Ok(error
+                .data()
+                .get_with_string("message")
+                .cloned()
+                .unwrap_or(Value::Empty)),
+            _ => runtime_error!("Error.@display: Expected an error map as argument"),
+        }
+    });
+
+    let mut map = ValueMap::with_contents(Default::default(), meta);
+    map.add_value("message", Value::Str(message));
+    map.add_value("cause", cause);
+
+    Value::Map(map)
+}
+
+// Returns true if the value is a map that can be thrown as an error
+fn is_throwable_map(value: &Value) -> bool {
+    match value {
+        Value::Map(map) => map.meta().contains_key(&MetaKey::UnaryOp(UnaryOp::Display)),
+        _ => false,
+    }
+}
+
+/// Makes the read-only value that's exposed to scripts as `koto.settings`
+///
+/// Each setting is available via `.` access, e.g. `koto.settings.run_tests`, but can't be
+/// modified by the script. `koto.settings.to_map()` returns a copy of the settings as a Map.
+///
+/// The settings are provided by the host, e.g. `Koto` updates them before running a script.
+pub fn make_settings(settings: &[(&str, Value)]) -> Value {
+    let mut meta = MetaMap::with_type_name("Settings");
+    let mut map = ValueMap::with_capacity(settings.len());
+
+    for (key, value) in settings.iter() {
+        meta.insert(MetaKey::Named((*key).into()), value.clone());
+        map.add_value(key, value.clone());
+    }
+
+    meta.add_named_instance_fn("to_map", |settings: &Settings, _, _| {
+        Ok(Value::Map(ValueMap::with_data(settings.0.data().clone())))
+    });
+
+    Value::ExternalValue(ExternalValue::new(Settings(map), meta))
+}
+
+#[derive(Debug)]
+struct Settings(ValueMap);
+
+impl ExternalData for Settings {
+    fn value_type(&self) -> String {
+        "Settings".to_string()
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Settings")
+    }
+}
@@ -2,7 +2,7 @@ use {
     crate::{
         runtime_error,
         value_sort::{compare_values, sort_values},
-        BinaryOp, CallArgs, Value, ValueIterator, ValueList, ValueMap,
+        BinaryOp, CallArgs, Value, ValueIterator, ValueList, ValueMap, ValueVec,
     },
     std::cmp::Ordering,
     std::ops::DerefMut,
@@ -25,8 +25,16 @@ pub fn make_module() -> ValueMap {
         [List(l), value] => {
             let l = l.clone();
             let value = value.clone();
-            for candidate in l.data().iter() {
-                match vm.run_binary_op(BinaryOp::Equal, value.clone(), candidate.clone()) {
+            // The list isn't locked while the comparisons are made,
+            // so any changes made to the list by overloaded operators are seen by the loop.
+            let mut index = 0;
+            loop {
+                let candidate = match l.data().get(index) {
+                    Some(candidate) => candidate.clone(),
+                    None => break,
+                };
+                index += 1;
+                match vm.run_binary_op(BinaryOp::Equal, value.clone(), candidate) {
                     Ok(Bool(false)) => {}
                     Ok(Bool(true)) => return Ok(true.into()),
                     Ok(unexpected) => {
@@ -174,16 +182,14 @@ pub fn make_module() -> ValueMap {
                 let l = l.clone();
                 let f = f.clone();
 
-                let mut write_index = 0;
-                for read_index in 0..l.len() {
-                    let value = l.data()[read_index].clone();
-                    match vm.run_function(f.clone(), CallArgs::Single(value.clone())) {
-                        Ok(Bool(result)) => {
-                            if result {
-                                l.data_mut()[write_index] = value;
-                                write_index += 1;
-                            }
-                        }
+                // The predicate is called with values from a copy of the list's data,
+                // so that the predicate is free to access and modify the list.
+                let data = l.data().clone();
+                let mut retained = ValueVec::with_capacity(data.len());
+                for x in data {
+                    match vm.run_function(f.clone(), CallArgs::Single(x.clone())) {
+                        Ok(Bool(true)) => retained.push(x),
+                        Ok(Bool(false)) => {}
                         Ok(unexpected) => {
                             return runtime_error!(
                                 "list.retain expects a Bool to be returned from the \
@@ -194,36 +200,30 @@ pub fn make_module() -> ValueMap {
                         Err(error) => return Err(error.with_prefix("list.retain")),
                     }
                 }
-                l.data_mut().resize(write_index, Empty);
+                *l.data_mut() = retained;
             }
             [List(l), value] => {
                 let l = l.clone();
                 let value = value.clone();
 
-                let mut error = None;
-                l.data_mut().retain(|x| {
-                    if error.is_some() {
-                        return true;
-                    }
+                // The comparisons are made against a copy of the list's data,
+                // so that overloaded operators are free to access the list.
+                let data = l.data().clone();
+                let mut retained = ValueVec::with_capacity(data.len());
+                for x in data {
                     match vm.run_binary_op(BinaryOp::Equal, x.clone(), value.clone()) {
-                        Ok(Bool(true)) => true,
-                        Ok(Bool(false)) => false,
+                        Ok(Bool(true)) => retained.push(x),
+                        Ok(Bool(false)) => {}
                         Ok(unexpected) => {
-                            error = Some(runtime_error!(
+                            return runtime_error!(
                                 "list.retain:: Expected Bool from == comparison, found '{}'",
                                 unexpected.type_as_string()
-                            ));
-                            true
-                        }
-                        Err(e) => {
-                            error = Some(Err(e.with_prefix("list.retain")));
-                            true
+                            );
                         }
+                        Err(e) => return Err(e.with_prefix("list.retain")),
                     }
-                });
-                if let Some(error) = error {
-                    return error;
                 }
+                *l.data_mut() = retained;
             }
             _ => {
                 return runtime_error!(
@@ -251,8 +251,10 @@ pub fn make_module() -> ValueMap {
     result.add_fn("sort", |vm, args| match vm.get_args(args) {
         [List(l)] => {
             let l = l.clone();
-            let mut data = l.data_mut();
+            // The list is sorted as a copy, so that overloaded operators are free to access it
+            let mut data = l.data().clone();
             sort_values(vm, &mut data)?;
+            *l.data_mut() = data;
             Ok(Empty)
         }
         [List(l), f] if f.is_callable() => {
@@ -260,8 +262,8 @@ pub fn make_module() -> ValueMap {
             let f = f.clone();
 
             // apply function and construct a vec of (key, value)
-            let mut pairs = l
-                .data()
+            let data = l.data().clone();
+            let mut pairs = data
                 .iter()
                 .map(
                     |value| match vm.run_function(f.clone(), CallArgs::Single(value.clone())) {
@@ -331,10 +333,19 @@ pub fn make_module() -> ValueMap {
             let l = l.clone();
             let f = f.clone();
 
-            for value in l.data_mut().iter_mut() {
-                *value = match vm.run_function(f.clone(), CallArgs::Single(value.clone())) {
+            // The list isn't locked while the function is called, so each value is taken from
+            // the list before the call, and then the result is written back to the list.
+            for index in 0..l.len() {
+                let value = match l.data().get(index) {
+                    Some(value) => value.clone(),
+                    None => break,
+                };
+                let result = match vm.run_function(f.clone(), CallArgs::Single(value)) {
                     Ok(result) => result,
                     Err(error) => return Err(error.with_prefix("list.transform")),
+                };
+                if let Some(entry) = l.data_mut().get_mut(index) {
+                    *entry = result;
                 }
             }
 
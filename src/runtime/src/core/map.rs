@@ -173,8 +173,10 @@ pub fn make_module() -> ValueMap {
                     Ok(value)
                 };
 
-            let mut cache = DataMap::with_capacity(m.len());
-            m.data_mut().sort_by(|key_a, value_a, key_b, value_b| {
+            // The map is sorted as a copy, so that the key function is free to access the map
+            let mut data = m.data().clone();
+            let mut cache = DataMap::with_capacity(data.len());
+            data.sort_by(|key_a, value_a, key_b, value_b| {
                 if error.is_some() {
                     return Ordering::Equal;
                 }
@@ -212,6 +214,7 @@ pub fn make_module() -> ValueMap {
             if let Some(error) = error {
                 error
             } else {
+                *m.data_mut() = data;
                 Ok(Empty)
             }
         }
@@ -1,5 +1,6 @@
 //! The core library for the Koto language
 
+pub mod coroutine;
 pub mod io;
 pub mod iterator;
 pub mod koto;
@@ -19,6 +20,7 @@ use crate::ValueMap;
 
 #[derive(Clone)]
 pub struct CoreLib {
+    pub coroutine: ValueMap,
     pub io: ValueMap,
     pub iterator: ValueMap,
     pub koto: ValueMap,
@@ -38,6 +40,7 @@ pub struct CoreLib {
 impl Default for CoreLib {
     fn default() -> Self {
         Self {
+            coroutine: coroutine::make_module(),
             io: io::make_module(),
             iterator: iterator::make_module(),
             koto: koto::make_module(),
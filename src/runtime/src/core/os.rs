@@ -13,5 +13,9 @@ pub fn make_module() -> ValueMap {
         Ok(Number(num_cpus::get_physical().into()))
     });
 
+    result.add_fn("time", |vm, _args| {
+        Ok(Number(vm.clock().now().as_secs_f64().into()))
+    });
+
     result
 }
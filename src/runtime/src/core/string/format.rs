@@ -314,10 +314,15 @@ pub fn format_string(
                 None => return runtime_error!("Missing argument for index {}", n),
             },
             FormatToken::Identifier(id, format_spec) => match format_args.first() {
-                Some(Value::Map(map)) => match map.data().get_with_string(id) {
-                    Some(value) => result.push_str(&value_to_string(vm, value, format_spec)?),
-                    None => return runtime_error!("Key '{}' not found in map", id),
-                },
+                Some(Value::Map(map)) => {
+                    // The map's lock is released before the value is converted to a string,
+                    // which might call an overloaded @display function.
+                    let value = map.data().get_with_string(id).cloned();
+                    match value {
+                        Some(value) => result.push_str(&value_to_string(vm, &value, format_spec)?),
+                        None => return runtime_error!("Key '{}' not found in map", id),
+                    }
+                }
                 Some(other) => {
                     return runtime_error!(
                         "Expected map as first argument, found '{}'",
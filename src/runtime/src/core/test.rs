@@ -1,4 +1,10 @@
-use crate::{runtime_error, BinaryOp, Value, ValueMap, ValueNumber};
+use {
+    crate::{
+        runtime_error, BinaryOp, CallArgs, ExternalFunction, RuntimeResult, UnaryOp, Value,
+        ValueKey, ValueList, ValueMap, ValueNumber, Vm,
+    },
+    std::{fs, path::PathBuf},
+};
 
 pub fn make_module() -> ValueMap {
     use Value::*;
@@ -65,55 +71,60 @@ pub fn make_module() -> ValueMap {
     });
 
     result.add_fn("assert_near", |vm, args| match vm.get_args(args) {
-        [Number(a), Number(b), Number(allowed_diff)] => {
-            if number_near(*a, *b, *allowed_diff) {
-                Ok(Empty)
-            } else {
-                runtime_error!(
-                    "Assertion failed, '{}' and '{}' are not within {} of each other",
-                    a,
-                    b,
-                    allowed_diff,
-                )
-            }
-        }
-        [Num2(a), Num2(b), Number(allowed_diff)] => {
-            let allowed_diff: f64 = allowed_diff.into();
-            if f64_near(a.0, b.0, allowed_diff) && f64_near(a.1, b.1, allowed_diff) {
-                Ok(Empty)
-            } else {
-                runtime_error!(
-                    "Assertion failed, '{}' and '{}' are not within {} of each other",
-                    a,
-                    b,
-                    allowed_diff,
-                )
-            }
-        }
-        [Num4(a), Num4(b), Number(allowed_diff)] => {
-            let allowed_diff: f32 = allowed_diff.into();
-            if f32_near(a.0, b.0, allowed_diff)
-                && f32_near(a.1, b.1, allowed_diff)
-                && f32_near(a.2, b.2, allowed_diff)
-                && f32_near(a.3, b.3, allowed_diff)
-            {
-                Ok(Empty)
-            } else {
-                runtime_error!(
-                    "Assertion failed, '{}' and '{}' are not within {} of each other",
-                    a,
-                    b,
-                    allowed_diff,
-                )
-            }
+        [a, b] => {
+            let allowed_diff = vm.float_epsilon();
+            check_near(a, b, allowed_diff)
         }
+        [a, b, Number(allowed_diff)] => check_near(a, b, allowed_diff.into()),
         [a, b, c] => runtime_error!(
             "assert_near expects Numbers as arguments, found '{}', '{}', and '{}'",
             a.type_as_string(),
             b.type_as_string(),
             c.type_as_string(),
         ),
-        _ => runtime_error!("assert_eq expects three arguments"),
+        _ => runtime_error!("assert_near expects two or three arguments"),
+    });
+
+    result.add_fn("snapshot", |vm, args| match vm.get_args(args) {
+        [Str(name), value] => {
+            let name = name.clone();
+            let value = value.clone();
+            check_snapshot(vm, &name, value)
+        }
+        _ => runtime_error!("snapshot expects a String and a value as arguments"),
+    });
+
+    result.add_fn("stub", |vm, args| match vm.get_args(args) {
+        [] => Ok(make_stub(Empty)),
+        [implementation] => Ok(make_stub(implementation.clone())),
+        _ => runtime_error!("stub expects zero or one arguments"),
+    });
+
+    result.add_fn("with_replaced", |vm, args| match vm.get_args(args) {
+        [Map(target), key, replacement, f] if key.is_immutable() && f.is_callable() => {
+            let target = target.clone();
+            let key = ValueKey::from(key.clone());
+            let replacement = replacement.clone();
+            let f = f.clone();
+
+            let previous = target.data_mut().insert(key.clone(), replacement.clone());
+            let result = vm.run_function(f, CallArgs::Single(replacement));
+
+            // The original entry is restored even if the function returned an error
+            match previous {
+                Some(previous) => {
+                    target.data_mut().insert(key, previous);
+                }
+                None => {
+                    target.data_mut().shift_remove(&key);
+                }
+            }
+
+            result
+        }
+        _ => runtime_error!(
+            "with_replaced expects a Map, a key, a replacement value, and a function as arguments"
+        ),
     });
 
     result.add_fn("run_tests", |vm, args| match vm.get_args(args) {
@@ -128,14 +139,172 @@ pub fn make_module() -> ValueMap {
     result
 }
 
-fn f32_near(a: f32, b: f32, allowed_diff: f32) -> bool {
-    (a - b).abs() <= allowed_diff
+// Makes a function that records its calls, returning a tuple containing the function and a list
+// of the calls that have been made, with the arguments for each call stored in a tuple
+//
+// If the implementation is callable then it gets called with the stub's arguments,
+// otherwise it's returned as the stub's result.
+fn make_stub(implementation: Value) -> Value {
+    let calls = ValueList::default();
+
+    let stub = {
+        let calls = calls.clone();
+        ExternalFunction::new(
+            move |vm, args| {
+                let args = vm.get_args(args).to_vec();
+                calls.data_mut().push(Value::Tuple(args.as_slice().into()));
+
+                if implementation.is_callable() {
+                    vm.run_function(implementation.clone(), CallArgs::Separate(&args))
+                } else {
+                    Ok(implementation.clone())
+                }
+            },
+            false,
+        )
+    };
+
+    Value::Tuple(vec![Value::ExternalFunction(stub), Value::List(calls)].into())
+}
+
+fn check_near(a: &Value, b: &Value, allowed_diff: f64) -> RuntimeResult {
+    use Value::*;
+
+    let is_near = match (a, b) {
+        (Number(a), Number(b)) => a.is_near(*b, allowed_diff),
+        (Num2(a), Num2(b)) => a.is_near(b, allowed_diff),
+        (Num4(a), Num4(b)) => a.is_near(b, allowed_diff as f32),
+        _ => {
+            return runtime_error!(
+                "assert_near expects Numbers as arguments, found '{}' and '{}'",
+                a.type_as_string(),
+                b.type_as_string(),
+            )
+        }
+    };
+
+    if is_near {
+        Ok(Empty)
+    } else {
+        runtime_error!(
+            "Assertion failed, '{}' and '{}' are not within {} of each other",
+            a,
+            b,
+            ValueNumber::from(allowed_diff),
+        )
+    }
+}
+
+fn check_snapshot(vm: &mut Vm, name: &str, value: Value) -> RuntimeResult {
+    use Value::{Empty, Str};
+
+    let is_valid_name = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !is_valid_name {
+        return runtime_error!(
+            "snapshot: '{}' isn't a valid snapshot name, \
+             names can contain alphanumeric characters, '_', '-', and '.'",
+            name
+        );
+    }
+
+    let path = match snapshot_path(vm, name) {
+        Some(path) => path,
+        None => return runtime_error!("snapshot: the script doesn't have a path"),
+    };
+
+    // Strings are stored as-is, other values are stored using their display string
+    let actual = match value {
+        Str(s) => s.to_string(),
+        value => match vm.run_unary_op(UnaryOp::Display, value)? {
+            Str(s) => s.to_string(),
+            unexpected => {
+                return runtime_error!(
+                    "snapshot: expected String from display, found '{}'",
+                    unexpected.type_as_string()
+                )
+            }
+        },
+    };
+
+    let stored = if vm.update_snapshots() {
+        None
+    } else {
+        match fs::read_to_string(&path) {
+            Ok(stored) => Some(stored.replace("\r\n", "\n")),
+            Err(_) => None,
+        }
+    };
+
+    match stored {
+        Some(stored) => {
+            // A trailing newline is appended when writing snapshots
+            let stored = stored.strip_suffix('\n').unwrap_or(&stored);
+            if stored == actual {
+                Ok(Empty)
+            } else {
+                runtime_error!(
+                    "Snapshot '{}' doesn't match '{}'\n{}",
+                    name,
+                    path.to_string_lossy(),
+                    describe_snapshot_mismatch(stored, &actual)
+                )
+            }
+        }
+        None => {
+            let write_result = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, format!("{}\n", actual)));
+            match write_result {
+                Ok(_) => Ok(Empty),
+                Err(error) => runtime_error!(
+                    "snapshot: failed to write '{}': {}",
+                    path.to_string_lossy(),
+                    error
+                ),
+            }
+        }
+    }
 }
 
-fn f64_near(a: f64, b: f64, allowed_diff: f64) -> bool {
-    (a - b).abs() <= allowed_diff
+// Snapshots are stored next to the script, in `snapshots/<script name>/<snapshot name>.snap`
+fn snapshot_path(vm: &Vm, name: &str) -> Option<PathBuf> {
+    let chunk = vm.chunk();
+    let script_path = chunk.source_path.as_ref()?;
+    let script_name = script_path.file_stem()?;
+
+    let mut result = script_path.parent()?.to_path_buf();
+    result.push("snapshots");
+    result.push(script_name);
+    result.push(format!("{}.snap", name));
+    Some(result)
 }
 
-fn number_near(a: ValueNumber, b: ValueNumber, allowed_diff: ValueNumber) -> bool {
-    (a - b).abs() <= allowed_diff
+// Describes the first line that differs between the stored snapshot and the actual value
+fn describe_snapshot_mismatch(stored: &str, actual: &str) -> String {
+    let mut stored_lines = stored.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+
+    loop {
+        match (stored_lines.next(), actual_lines.next()) {
+            (Some(expected), Some(found)) if expected == found => line += 1,
+            (expected, found) => {
+                let describe = |s: Option<&str>| match s {
+                    Some(s) => format!("'{}'", s),
+                    None => "end of snapshot".to_string(),
+                };
+                return format!(
+                    "  line {}:\n    expected: {}\n    actual:   {}",
+                    line,
+                    describe(expected),
+                    describe(found)
+                );
+            }
+        }
+    }
 }
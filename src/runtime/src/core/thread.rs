@@ -1,7 +1,9 @@
 use {
     crate::{
-        runtime_error, CallArgs, ExternalData, MetaMap, RuntimeError, RwLock, Value, ValueMap,
+        runtime_error, CallArgs, ExternalData, ExternalValue, MetaMap, RuntimeError, RwLock, Value,
+        ValueMap,
     },
+    crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError},
     lazy_static::lazy_static,
     std::{fmt, sync::Arc, thread, thread::JoinHandle, time::Duration},
 };
@@ -11,6 +13,20 @@ pub fn make_module() -> ValueMap {
 
     let mut result = ValueMap::new();
 
+    result.add_fn("channel", |vm, args| {
+        let (sender, receiver) = match vm.get_args(args) {
+            [] => channel(None),
+            [Number(capacity)] if *capacity >= 0 => channel(Some(capacity.into())),
+            _ => {
+                return runtime_error!(
+                    "thread.channel: Expected an optional non-negative Number as argument"
+                )
+            }
+        };
+
+        Ok(Value::Tuple(vec![sender.into(), receiver.into()].into()))
+    });
+
     #[cfg(not(target_arch = "wasm32"))]
     result.add_fn("create", |vm, args| match vm.get_args(args) {
         [f] if f.is_callable() => {
@@ -47,7 +63,7 @@ pub fn make_module() -> ValueMap {
                 return runtime_error!("thread.sleep: the duration must be finite");
             }
 
-            thread::sleep(Duration::from_secs_f64(seconds));
+            vm.clock().sleep(Duration::from_secs_f64(seconds));
 
             Ok(Empty)
         }
@@ -72,6 +88,159 @@ lazy_static! {
 
         Arc::new(RwLock::new(meta))
     };
+    static ref SENDER_META: Arc<RwLock<MetaMap>> = {
+        let mut meta = MetaMap::with_type_name("Sender");
+
+        meta.add_named_instance_fn("send", |sender: &ChannelSender, _, args| match args {
+            [value] => {
+                sender.send(value)?;
+                Ok(Value::Empty)
+            }
+            _ => runtime_error!("Sender.send: Expected a value as argument"),
+        });
+
+        Arc::new(RwLock::new(meta))
+    };
+    static ref RECEIVER_META: Arc<RwLock<MetaMap>> = {
+        use Value::Number;
+
+        let mut meta = MetaMap::with_type_name("Receiver");
+
+        meta.add_named_instance_fn("receive", |receiver: &ChannelReceiver, _, args| {
+            let result = match args {
+                [] => receiver.receive(),
+                [Number(seconds)] => {
+                    let seconds: f64 = seconds.into();
+                    if seconds < 0.0 || !seconds.is_finite() {
+                        return runtime_error!(
+                            "Receiver.receive: the timeout must be positive and finite"
+                        );
+                    }
+                    receiver.receive_timeout(Duration::from_secs_f64(seconds))
+                }
+                _ => {
+                    return runtime_error!(
+                        "Receiver.receive: Expected an optional timeout in seconds as argument"
+                    )
+                }
+            };
+
+            Ok(result.unwrap_or_default())
+        });
+
+        meta.add_named_instance_fn("try_receive", |receiver: &ChannelReceiver, _, _| {
+            Ok(receiver.try_receive().unwrap_or_default())
+        });
+
+        Arc::new(RwLock::new(meta))
+    };
+}
+
+/// Makes a channel for sending values between VMs that are running on different threads
+///
+/// Channels can have multiple senders and receivers, with each sent value being received once.
+/// If a capacity is provided then sending blocks while the channel is full.
+///
+/// See [ChannelSender] and [ChannelReceiver].
+pub fn channel(capacity: Option<usize>) -> (ChannelSender, ChannelReceiver) {
+    let (sender, receiver) = match capacity {
+        Some(capacity) => crossbeam_channel::bounded(capacity),
+        None => crossbeam_channel::unbounded(),
+    };
+    (ChannelSender(sender), ChannelReceiver(receiver))
+}
+
+/// The sending half of a channel, see [channel]
+#[derive(Clone, Debug)]
+pub struct ChannelSender(Sender<Value>);
+
+impl ChannelSender {
+    /// Sends a value through the channel
+    ///
+    /// Lists, maps, and tuples are deep-copied before being sent, so that threads don't share
+    /// mutable data via the channel. Other values, like strings, functions and external values,
+    /// are sent as they are.
+    ///
+    /// An error is returned if all of the channel's receivers have been dropped.
+    pub fn send(&self, value: &Value) -> Result<(), RuntimeError> {
+        match self.0.send(value.deep_copy()) {
+            Ok(_) => Ok(()),
+            Err(_) => runtime_error!("Sender.send: The channel has been closed"),
+        }
+    }
+}
+
+/// The receiving half of a channel, see [channel]
+#[derive(Clone, Debug)]
+pub struct ChannelReceiver(Receiver<Value>);
+
+impl ChannelReceiver {
+    /// Waits for a value to be sent through the channel
+    ///
+    /// `None` is returned once the channel is empty and all of its senders have been dropped.
+    pub fn receive(&self) -> Option<Value> {
+        self.0.recv().ok()
+    }
+
+    /// Waits for a value to be sent through the channel, up to the provided timeout
+    ///
+    /// `None` is returned if the timeout elapses, or if the channel has been closed.
+    pub fn receive_timeout(&self, timeout: Duration) -> Option<Value> {
+        match self.0.recv_timeout(timeout) {
+            Ok(value) => Some(value),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Returns a value from the channel if one is available, without waiting
+    pub fn try_receive(&self) -> Option<Value> {
+        match self.0.try_recv() {
+            Ok(value) => Some(value),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+}
+
+impl From<ChannelSender> for Value {
+    fn from(sender: ChannelSender) -> Self {
+        Value::ExternalValue(ExternalValue::with_shared_meta_map(
+            sender,
+            SENDER_META.clone(),
+        ))
+    }
+}
+
+impl From<ChannelReceiver> for Value {
+    fn from(receiver: ChannelReceiver) -> Self {
+        Value::ExternalValue(ExternalValue::with_shared_meta_map(
+            receiver,
+            RECEIVER_META.clone(),
+        ))
+    }
+}
+
+impl ExternalData for ChannelSender {
+    fn value_type(&self) -> String {
+        "Sender".to_string()
+    }
+}
+
+impl fmt::Display for ChannelSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sender")
+    }
+}
+
+impl ExternalData for ChannelReceiver {
+    fn value_type(&self) -> String {
+        "Receiver".to_string()
+    }
+}
+
+impl fmt::Display for ChannelReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Receiver")
+    }
 }
 
 #[derive(Debug)]
@@ -82,7 +251,7 @@ struct Thread {
 impl Thread {
     #[cfg(not(target_arch = "wasm32"))]
     fn make_external_value(join_handle: JoinHandle<Result<Value, RuntimeError>>) -> Value {
-        let result = crate::ExternalValue::with_shared_meta_map(
+        let result = ExternalValue::with_shared_meta_map(
             Thread {
                 join_handle: Some(join_handle),
             },
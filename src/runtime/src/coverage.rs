@@ -0,0 +1,74 @@
+use {
+    koto_bytecode::Chunk,
+    koto_parser::Span,
+    std::{collections::HashMap, sync::Arc},
+};
+
+/// The coverage of a chunk that was compiled with coverage points, see [Vm::take_coverage]
+///
+/// [Vm::take_coverage]: crate::Vm::take_coverage
+#[derive(Clone, Debug)]
+pub struct ChunkCoverage {
+    /// The chunk that the coverage refers to
+    pub chunk: Arc<Chunk>,
+    /// The number of times that each of the chunk's coverage points was reached
+    ///
+    /// The counts are in the same order as the chunk's
+    /// [coverage points](koto_bytecode::DebugInfo::coverage_points).
+    pub hits: Vec<u64>,
+}
+
+impl ChunkCoverage {
+    fn new(chunk: Arc<Chunk>) -> Self {
+        let hits = vec![0; chunk.debug_info.coverage_points().len()];
+        Self { chunk, hits }
+    }
+
+    /// Returns an iterator over the chunk's coverage points, along with their hit counts
+    pub fn points(&self) -> impl Iterator<Item = (Span, u64)> + '_ {
+        self.chunk
+            .debug_info
+            .coverage_points()
+            .iter()
+            .copied()
+            .zip(self.hits.iter().copied())
+    }
+
+    /// The number of coverage points that were reached at least once
+    pub fn covered_count(&self) -> usize {
+        self.hits.iter().filter(|hits| **hits > 0).count()
+    }
+}
+
+// Counts the coverage points that are reached while running instrumented chunks
+//
+// Chunks are identified by their address, with the recorded chunks being kept alive until the
+// coverage is taken, so addresses can't be reused by other chunks in the meantime.
+#[derive(Default)]
+pub(crate) struct CoverageRecorder {
+    chunks: HashMap<usize, ChunkCoverage>,
+}
+
+impl CoverageRecorder {
+    pub fn record(&mut self, chunk: &Arc<Chunk>, index: u32) {
+        let coverage = self
+            .chunks
+            .entry(Arc::as_ptr(chunk) as usize)
+            .or_insert_with(|| ChunkCoverage::new(chunk.clone()));
+
+        if let Some(hits) = coverage.hits.get_mut(index as usize) {
+            *hits += 1;
+        }
+    }
+
+    // Returns the recorded coverage, ordered by the chunks' source paths
+    pub fn take(&mut self) -> Vec<ChunkCoverage> {
+        let mut result = self
+            .chunks
+            .drain()
+            .map(|(_, coverage)| coverage)
+            .collect::<Vec<_>>();
+        result.sort_by(|a, b| a.chunk.source_path.cmp(&b.chunk.source_path));
+        result
+    }
+}
@@ -0,0 +1,188 @@
+use {
+    crate::{Value, ValueKey, ValueList, ValueMap},
+    std::{collections::HashSet, fmt},
+};
+
+/// A reference cycle that was found in a VM's values, see [Vm::find_reference_cycles]
+///
+/// Values in a cycle keep each other alive, so they won't be dropped until the cycle is broken.
+///
+/// [Vm::find_reference_cycles]: crate::Vm::find_reference_cycles
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReferenceCycle {
+    /// The path to the first value in the cycle that was reached, e.g. `exports.foo`
+    pub start: String,
+    /// The path from the start value back to itself, e.g. `.bar[0]`
+    ///
+    /// Values that are captured by functions are referred to with `<captures>`,
+    /// e.g. `.f<captures>[0]`.
+    pub path: String,
+}
+
+impl fmt::Display for ReferenceCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{} -> {}", self.start, self.path, self.start)
+    }
+}
+
+// Finds reference cycles with a depth-first search through lists, maps, tuples, and captures
+//
+// A container that's reached while it's already on the search path closes a cycle. When cycles
+// are being broken, the reference that closes the cycle is replaced with Empty, or if the
+// reference is in a tuple, then the reference to the tuple is replaced.
+pub(crate) struct CycleFinder {
+    // The ids of the containers on the current search path,
+    // along with the length of the path when they were reached
+    stack: Vec<(usize, usize)>,
+    // The ids of containers that have been searched
+    visited: HashSet<usize>,
+    path: String,
+    break_cycles: bool,
+    cycles: Vec<ReferenceCycle>,
+}
+
+impl CycleFinder {
+    pub fn new(break_cycles: bool) -> Self {
+        Self {
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            path: String::new(),
+            break_cycles,
+            cycles: Vec::new(),
+        }
+    }
+
+    pub fn search(&mut self, root: &str, value: &Value) {
+        self.path.clear();
+        self.path.push_str(root);
+        self.visit(value);
+    }
+
+    pub fn finish(self) -> Vec<ReferenceCycle> {
+        self.cycles
+    }
+
+    // Returns true if the reference to the value should be replaced to break a cycle
+    fn visit(&mut self, value: &Value) -> bool {
+        match value {
+            Value::List(list) => self.visit_list(list),
+            Value::Map(map) => self.visit_map(map),
+            Value::Tuple(tuple) => {
+                let id = tuple.instance_id();
+                if let Some(cycle_closed) = self.enter(id) {
+                    return cycle_closed;
+                }
+
+                // Tuples can't be modified, so cycles that pass through them are broken by
+                // replacing the reference to the tuple.
+                let mut break_reference = false;
+                for (i, value) in tuple.data().iter().enumerate() {
+                    let path_len = self.path.len();
+                    self.path.push_str(&format!("[{}]", i));
+                    break_reference |= self.visit(value);
+                    self.path.truncate(path_len);
+                }
+
+                self.exit(id);
+                break_reference
+            }
+            Value::Function(f) | Value::Generator(f) => match &f.captures {
+                Some(captures) => {
+                    let path_len = self.path.len();
+                    self.path.push_str("<captures>");
+                    let break_reference = self.visit_list(captures);
+                    self.path.truncate(path_len);
+                    break_reference
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn visit_list(&mut self, list: &ValueList) -> bool {
+        let id = list.instance_id();
+        if let Some(cycle_closed) = self.enter(id) {
+            return cycle_closed;
+        }
+
+        // The list's values are cloned so that the list isn't locked during the search
+        let values = list.data().to_vec();
+        for (i, value) in values.iter().enumerate() {
+            let path_len = self.path.len();
+            self.path.push_str(&format!("[{}]", i));
+            if self.visit(value) {
+                if let Some(entry) = list.data_mut().get_mut(i) {
+                    *entry = Value::Empty;
+                }
+            }
+            self.path.truncate(path_len);
+        }
+
+        self.exit(id);
+        false
+    }
+
+    fn visit_map(&mut self, map: &ValueMap) -> bool {
+        let id = map.instance_id();
+        if let Some(cycle_closed) = self.enter(id) {
+            return cycle_closed;
+        }
+
+        let entries = map
+            .data()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        for (key, value) in entries.iter() {
+            let path_len = self.path.len();
+            push_key(&mut self.path, key);
+            if self.visit(value) {
+                if let Some(entry) = map.data_mut().get_mut(key) {
+                    *entry = Value::Empty;
+                }
+            }
+            self.path.truncate(path_len);
+        }
+
+        self.exit(id);
+        false
+    }
+
+    // Adds a container to the search path
+    //
+    // If the container has already been searched then Some is returned, containing true if the
+    // container closes a cycle that should be broken.
+    fn enter(&mut self, id: usize) -> Option<bool> {
+        if let Some((_, start_len)) = self.stack.iter().find(|(stack_id, _)| *stack_id == id) {
+            self.cycles.push(ReferenceCycle {
+                start: self.path[..*start_len].to_string(),
+                path: self.path[*start_len..].to_string(),
+            });
+            return Some(self.break_cycles);
+        }
+
+        if !self.visited.insert(id) {
+            return Some(false);
+        }
+
+        self.stack.push((id, self.path.len()));
+        None
+    }
+
+    fn exit(&mut self, id: usize) {
+        debug_assert_eq!(self.stack.last().map(|(stack_id, _)| *stack_id), Some(id));
+        self.stack.pop();
+    }
+}
+
+// Appends a map key to the path, e.g. `.foo` or `[42]`
+fn push_key(path: &mut String, key: &ValueKey) {
+    match key.value() {
+        Value::Str(s) => {
+            path.push('.');
+            path.push_str(s);
+        }
+        other => path.push_str(&format!("[{}]", other)),
+    }
+}
@@ -0,0 +1,146 @@
+use {
+    crate::{RuntimeError, Value, Vm},
+    koto_bytecode::Chunk,
+    koto_parser::Span,
+    std::{path::PathBuf, sync::Arc},
+};
+
+/// Callbacks that allow a debugger to follow a VM's execution, see [VmSettings::debug_hook]
+///
+/// The callbacks are called on the thread that's running the VM, before execution continues.
+/// A debugger can pause execution by blocking in a callback, e.g. while waiting for a command
+/// from the user.
+///
+/// Each callback has a default implementation that does nothing.
+///
+/// [VmSettings::debug_hook]: crate::VmSettings::debug_hook
+pub trait DebugHook: Send + Sync {
+    /// Called before the first instruction of each statement is executed
+    ///
+    /// Statements are identified by the line in the source where their instructions start,
+    /// with a new statement also starting when a function is called or returns.
+    fn on_statement(&self, _frame: &DebugFrame) {}
+
+    /// Called when execution reaches a statement on a line that has a breakpoint
+    ///
+    /// This is called after [DebugHook::on_statement], see [Vm::add_breakpoint].
+    fn on_breakpoint(&self, _frame: &DebugFrame, _breakpoint: &Breakpoint) {}
+
+    /// Called when a Koto function is called, after its arguments have been prepared
+    ///
+    /// This is also called when a script's top-level code starts running.
+    fn on_function_entry(&self, _frame: &DebugFrame) {}
+
+    /// Called when a Koto function returns, before its frame is removed from the call stack
+    ///
+    /// This is also called when a script's top-level code finishes running.
+    fn on_function_exit(&self, _frame: &DebugFrame, _return_value: &Value) {}
+
+    /// Called when an error is thrown, before the VM looks for a `catch` block to handle it
+    ///
+    /// The frame refers to the instruction that produced the error.
+    fn on_error(&self, _frame: &DebugFrame, _error: &RuntimeError) {}
+}
+
+/// A position in a script where execution should be reported to a [DebugHook]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Breakpoint {
+    /// The path of the script or module, matching the compiled chunk's `source_path`
+    ///
+    /// Scripts that were compiled without a path are matched by `None`.
+    pub path: Option<PathBuf>,
+    /// The breakpoint's line in the source, starting from 1
+    pub line: u32,
+}
+
+/// A view of one of a VM's call frames, provided to [DebugHook] callbacks
+///
+/// The frame's values can be inspected along with its position in the source, and the frames
+/// of the calling functions can be reached via [DebugFrame::caller].
+pub struct DebugFrame<'a> {
+    vm: &'a Vm,
+    index: usize,
+    ip: Option<usize>,
+}
+
+impl<'a> DebugFrame<'a> {
+    // Returns the VM's innermost frame, with the ip of the frame's current instruction
+    pub(crate) fn new(vm: &'a Vm, ip: usize) -> Self {
+        Self {
+            vm,
+            index: vm.call_stack().len().saturating_sub(1),
+            ip: Some(ip),
+        }
+    }
+
+    /// The chunk that's being executed in the frame
+    pub fn chunk(&self) -> Arc<Chunk> {
+        match self.vm.call_stack().get(self.index) {
+            Some(frame) => frame.chunk.clone(),
+            None => self.vm.chunk(),
+        }
+    }
+
+    /// The position in the chunk of the frame's current instruction
+    ///
+    /// For frames that have called other functions, this is the position where execution will
+    /// resume once the call returns. `None` is returned if the position isn't known,
+    /// e.g. when the frame was called by an external function.
+    pub fn ip(&self) -> Option<usize> {
+        self.ip
+    }
+
+    /// The span in the source of the frame's current instruction, if available
+    ///
+    /// For frames that have called other functions, this is the span of the call.
+    pub fn span(&self) -> Option<Span> {
+        let ip = if self.index + 1 < self.vm.call_stack().len() {
+            // The ip where execution will resume follows the call instruction
+            self.ip?.checked_sub(1)?
+        } else {
+            self.ip?
+        };
+        self.chunk().source_span(ip)
+    }
+
+    /// The number of frames that precede the frame in the call stack
+    ///
+    /// The frame for the script's top-level code has a depth of 0.
+    pub fn depth(&self) -> usize {
+        self.index
+    }
+
+    /// The values in the frame's registers
+    ///
+    /// The function's arguments come first, followed by its captured values and then its local
+    /// values and temporary values.
+    pub fn registers(&self) -> &'a [Value] {
+        let call_stack = self.vm.call_stack();
+        let value_stack = self.vm.value_stack();
+
+        let start = match call_stack.get(self.index) {
+            Some(frame) => frame.register_base,
+            None => return &[],
+        };
+        let end = match call_stack.get(self.index + 1) {
+            Some(callee) => callee.call_base,
+            None => value_stack.len(),
+        };
+
+        value_stack.get(start..end).unwrap_or_default()
+    }
+
+    /// Returns the frame of the function that called this frame's function
+    pub fn caller(&self) -> Option<DebugFrame<'a>> {
+        let index = self.index.checked_sub(1)?;
+        let ip = self.vm.call_stack()[index]
+            .return_register_and_ip
+            .map(|(_, ip)| ip);
+
+        Some(Self {
+            vm: self.vm,
+            index,
+            ip,
+        })
+    }
+}
@@ -1,19 +1,54 @@
 use {
     crate::{Mutex, UnaryOp, Value, Vm},
     koto_bytecode::Chunk,
-    koto_parser::format_error_with_excerpt,
+    koto_parser::{format_frame_with_excerpt, ExcerptOptions, Span},
     std::{
         sync::Arc,
+        time::Duration,
         {error, fmt},
     },
 };
 
+/// A frame in a [RuntimeError]'s trace
+///
+/// The first frame in the trace refers to the instruction where the error occurred, with the
+/// following frames referring to the calls that led to the error.
 #[derive(Clone, Debug)]
 pub struct ErrorFrame {
     chunk: Arc<Chunk>,
     instruction: usize,
 }
 
+impl ErrorFrame {
+    /// The chunk that was being executed in the frame
+    pub fn chunk(&self) -> &Arc<Chunk> {
+        &self.chunk
+    }
+
+    /// The position of the frame's instruction in the chunk's bytecode
+    ///
+    /// For frames following the first frame, this is the position where execution would have
+    /// resumed after the call returned.
+    pub fn instruction(&self) -> usize {
+        self.instruction
+    }
+
+    /// The span of the source that produced the frame's instruction, if available
+    ///
+    /// See [Chunk::source_span].
+    pub fn span(&self) -> Option<Span> {
+        self.chunk.debug_info.get_source_span(self.instruction)
+    }
+
+    /// The name of the function that was being executed in the frame, if known
+    ///
+    /// `None` is returned for anonymous functions and for a script's top-level code,
+    /// see [DebugInfo::function_name](koto_bytecode::DebugInfo::function_name).
+    pub fn function_name(&self) -> Option<&str> {
+        self.chunk.debug_info.function_name(self.instruction)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum RuntimeErrorType {
     /// A runtime error message
@@ -27,12 +62,62 @@ pub enum RuntimeErrorType {
         thrown_value: Value,
         vm: Option<Arc<Mutex<Vm>>>,
     },
+    /// The VM was interrupted via its [InterruptHandle](crate::InterruptHandle)
+    ///
+    /// Errors of this type can't be caught by `try` expressions.
+    Interrupted,
+    /// The VM's instruction budget has been exhausted, see [VmSettings::instruction_budget]
+    ///
+    /// Errors of this type can't be caught by `try` expressions.
+    ///
+    /// [VmSettings::instruction_budget]: crate::VmSettings::instruction_budget
+    InstructionBudgetExhausted,
+    /// Execution didn't finish within the timeout provided to [Vm::run_with_timeout]
+    ///
+    /// Errors of this type can't be caught by `try` expressions.
+    TimedOut(Duration),
+    /// The VM's memory limit has been exceeded, see [VmSettings::memory_limit]
+    ///
+    /// The error contains the limit in bytes. Errors of this type can't be caught by `try`
+    /// expressions.
+    ///
+    /// [VmSettings::memory_limit]: crate::VmSettings::memory_limit
+    MemoryLimitExceeded(usize),
+    /// The VM's maximum call depth has been exceeded, see [VmSettings::max_call_depth]
+    ///
+    /// The error contains the maximum depth. Only the deepest frames of the call stack are
+    /// included in the error's trace, see [RuntimeError::omitted_frames].
+    ///
+    /// [VmSettings::max_call_depth]: crate::VmSettings::max_call_depth
+    CallDepthExceeded(usize),
+    /// The VM's maximum depth of nested calls has been exceeded,
+    /// see [VmSettings::max_nested_call_depth]
+    ///
+    /// The error contains the maximum depth. As with [RuntimeErrorType::CallDepthExceeded], only
+    /// the deepest frames of the call stack are included in the error's trace.
+    ///
+    /// [VmSettings::max_nested_call_depth]: crate::VmSettings::max_nested_call_depth
+    NestedCallDepthExceeded(usize),
 }
 
+// The number of frames that are included in the traces of call depth errors
+const CALL_DEPTH_TRACE_FRAMES: usize = 20;
+
 #[derive(Clone, Debug)]
 pub struct RuntimeError {
     pub error: RuntimeErrorType,
     pub trace: Vec<ErrorFrame>,
+    /// The error that caused this error, if any
+    ///
+    /// Errors thrown by scripts can refer to their cause, e.g. with
+    /// `throw koto.error 'parse failed', {cause: e}`, in which case the cause is also available
+    /// from the error's [source](error::Error::source). Causes don't include traces.
+    pub cause: Option<Box<RuntimeError>>,
+    /// The number of frames that were left out of the error's trace
+    ///
+    /// Traces are limited to the deepest frames of the call stack when the VM's maximum call
+    /// depth has been exceeded, with the remaining frames being omitted.
+    pub omitted_frames: usize,
 }
 
 impl RuntimeError {
@@ -40,6 +125,8 @@ impl RuntimeError {
         Self {
             error,
             trace: Vec::new(),
+            cause: None,
+            omitted_frames: 0,
         }
     }
 
@@ -61,8 +148,21 @@ impl RuntimeError {
         self
     }
 
+    /// Returns an iterator over the error's chain of causes, starting with the error itself
+    pub fn chain(&self) -> impl Iterator<Item = &RuntimeError> {
+        std::iter::successors(Some(self), |error| error.cause.as_deref())
+    }
+
     pub fn extend_trace(&mut self, chunk: Arc<Chunk>, instruction: usize) {
-        self.trace.push(ErrorFrame { chunk, instruction });
+        if matches!(
+            self.error,
+            RuntimeErrorType::CallDepthExceeded(_) | RuntimeErrorType::NestedCallDepthExceeded(_)
+        ) && self.trace.len() >= CALL_DEPTH_TRACE_FRAMES
+        {
+            self.omitted_frames += 1;
+        } else {
+            self.trace.push(ErrorFrame { chunk, instruction });
+        }
     }
 }
 
@@ -78,11 +178,64 @@ impl From<&str> for RuntimeError {
     }
 }
 
-impl fmt::Display for RuntimeError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl RuntimeError {
+    /// Formats the error along with source excerpts for each frame in the error's trace,
+    /// using the provided formatting options
+    ///
+    /// The messages of the error's causes follow the trace.
+    /// The error's `Display` implementation uses the default options.
+    pub fn format_with_options(&self, options: &ExcerptOptions) -> String {
+        let message = self.message();
+
+        let mut result = String::new();
+        if self.trace.is_empty() {
+            result.push_str(&message);
+        }
+
+        let mut first_frame = true;
+        for frame in self.trace.iter() {
+            let frame_message = if first_frame {
+                first_frame = false;
+                Some(message.as_str())
+            } else {
+                None
+            };
+
+            match frame.span() {
+                Some(span) => result.push_str(&format_frame_with_excerpt(
+                    frame_message,
+                    frame.function_name(),
+                    &frame.chunk.source_path,
+                    &frame.chunk.debug_info.source,
+                    span.start,
+                    span.end,
+                    options,
+                )),
+                None => result.push_str(&format!(
+                    "Runtime error at instruction {}: {}",
+                    frame.instruction, message
+                )),
+            };
+        }
+
+        if self.omitted_frames > 0 {
+            result.push_str(&format!(
+                "\n... {} more frames omitted",
+                self.omitted_frames
+            ));
+        }
+
+        for cause in self.chain().skip(1) {
+            result.push_str(&format!("\nCaused by: {}", cause.message()));
+        }
+
+        result
+    }
+
+    fn message(&self) -> String {
         use {RuntimeErrorType::*, Value::*};
 
-        let message = match &self.error {
+        match &self.error {
             StringError(s) => s.clone(),
             KotoError { thrown_value, vm } => match (&thrown_value, vm) {
                 (Str(message), _) => message.to_string(),
@@ -99,41 +252,44 @@ impl fmt::Display for RuntimeError {
                 },
                 _ => "Unable to get error message".to_string(),
             },
-        };
+            Interrupted => "Execution was interrupted".to_string(),
+            InstructionBudgetExhausted => "The instruction budget has been exhausted".to_string(),
+            TimedOut(timeout) => {
+                format!("Execution timed out after {}s", timeout.as_secs_f64())
+            }
+            MemoryLimitExceeded(limit) => {
+                format!("The memory limit of {} bytes has been exceeded", limit)
+            }
+            CallDepthExceeded(limit) => {
+                format!("The maximum call depth of {} has been exceeded", limit)
+            }
+            NestedCallDepthExceeded(limit) => {
+                format!(
+                    "The maximum nested call depth of {} has been exceeded",
+                    limit
+                )
+            }
+        }
+    }
+}
 
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if f.alternate() {
-            f.write_str(&message)
+            f.write_str(&self.message())
         } else {
-            let mut first_frame = true;
-            for frame in self.trace.iter() {
-                let frame_message = if first_frame {
-                    first_frame = false;
-                    Some(message.as_str())
-                } else {
-                    None
-                };
-
-                match frame.chunk.debug_info.get_source_span(frame.instruction) {
-                    Some(span) => f.write_str(&format_error_with_excerpt(
-                        frame_message,
-                        &frame.chunk.source_path,
-                        &frame.chunk.debug_info.source,
-                        span.start,
-                        span.end,
-                    ))?,
-                    None => write!(
-                        f,
-                        "Runtime error at instruction {}: {}",
-                        frame.instruction, message
-                    )?,
-                };
-            }
-            Ok(())
+            f.write_str(&self.format_with_options(&ExcerptOptions::default()))
         }
     }
 }
 
-impl error::Error for RuntimeError {}
+impl error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.cause
+            .as_deref()
+            .map(|cause| cause as &(dyn error::Error + 'static))
+    }
+}
 
 pub type RuntimeResult = Result<Value, RuntimeError>;
 
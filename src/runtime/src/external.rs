@@ -63,6 +63,11 @@ impl ExternalValue {
     }
 }
 
+/// A function that's implemented in Rust, and that can be called from Koto
+///
+/// Koto functions that are passed to an external function as arguments can be called from Rust,
+/// see [Vm::run_function].
+//
 // Once Trait aliases are stabilized this can be simplified a bit,
 // see: https://github.com/rust-lang/rust/issues/55628
 #[allow(clippy::type_complexity)]
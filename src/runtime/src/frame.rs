@@ -1,4 +1,4 @@
-use {koto_bytecode::Chunk, std::sync::Arc};
+use {crate::profiler::FrameProfile, koto_bytecode::Chunk, std::sync::Arc};
 
 #[derive(Clone, Debug)]
 pub(crate) struct Frame {
@@ -7,6 +7,10 @@ pub(crate) struct Frame {
     // The index in the VM value stack of the first argument register,
     // or the first local register if there are no arguments.
     pub register_base: usize,
+    // The index in the VM value stack of the call's frame base register.
+    // This precedes the register base when the function isn't an instance function,
+    // and is used as the base for calls that replace the frame, see Vm::run_tail_call.
+    pub call_base: usize,
     // When returning to this frame, the register for the return value and the ip to resume from.
     pub return_register_and_ip: Option<(u8, usize)>,
     // A stack of catch points for handling errors
@@ -15,6 +19,11 @@ pub(crate) struct Frame {
     // e.g. when an overloaded operator is being executed as a result of a regular instruction,
     //      or when an external function is calling back into the VM with a functor,
     pub execution_barrier: bool,
+    // The frame's profiling state, set when the VM is profiling function calls
+    pub profile: Option<FrameProfile>,
+    // The span of the call when it's being traced, see VmSettings::trace_sample_interval
+    #[cfg(feature = "tracing")]
+    pub trace_span: Option<tracing::Span>,
 }
 
 impl Frame {
@@ -22,9 +31,13 @@ impl Frame {
         Self {
             chunk,
             register_base,
+            call_base: register_base,
             return_register_and_ip: None,
             catch_stack: vec![],
             execution_barrier: false,
+            profile: None,
+            #[cfg(feature = "tracing")]
+            trace_span: None,
         }
     }
 }
@@ -0,0 +1,224 @@
+use {
+    crate::{DataMap, Mutex, RwLock, RwLockWriteGuard, Value, ValueVec},
+    rustc_hash::FxHashMap,
+    std::{
+        mem,
+        sync::{Arc, Weak},
+    },
+};
+
+// A garbage collector that frees reference cycles between lists and maps, see
+// VmSettings::gc_interval
+//
+// Values are still reference counted, the collector only tracks the lists, maps, and function
+// captures that are created by the VM, so that cycles between them can be found and freed.
+//
+// Collections use trial deletion: the references between tracked containers are subtracted from
+// their reference counts, and any container that still has references remaining is referred to
+// from outside of the tracked containers (e.g. from a VM's registers, or by the embedder).
+// Containers that can't be reached from those externally referenced containers are garbage,
+// and their contents are cleared, which frees the cycles that they're part of.
+pub(crate) struct GarbageCollector {
+    interval: usize,
+    state: Mutex<GcState>,
+}
+
+#[derive(Default)]
+struct GcState {
+    tracked: Vec<WeakContainer>,
+    // The number of containers that have been tracked since the last collection
+    tracked_since_collection: usize,
+}
+
+enum WeakContainer {
+    List(Weak<RwLock<ValueVec>>),
+    Map(Weak<RwLock<DataMap>>),
+}
+
+enum Container {
+    List(Arc<RwLock<ValueVec>>),
+    Map(Arc<RwLock<DataMap>>),
+}
+
+impl Container {
+    fn id(&self) -> usize {
+        match self {
+            Self::List(list) => Arc::as_ptr(list) as *const () as usize,
+            Self::Map(map) => Arc::as_ptr(map) as *const () as usize,
+        }
+    }
+
+    fn ref_count(&self) -> usize {
+        match self {
+            Self::List(list) => Arc::strong_count(list),
+            Self::Map(map) => Arc::strong_count(map),
+        }
+    }
+}
+
+enum ContainerGuard<'a> {
+    List(RwLockWriteGuard<'a, ValueVec>),
+    Map(RwLockWriteGuard<'a, DataMap>),
+}
+
+impl GarbageCollector {
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval,
+            state: Mutex::default(),
+        }
+    }
+
+    // Starts tracking the value if it's a list, map, or a function with captures
+    //
+    // Returns true if a collection is due.
+    pub fn track(&self, value: &Value) -> bool {
+        let container = match value {
+            Value::List(list) => WeakContainer::List(list.downgrade()),
+            Value::Map(map) => WeakContainer::Map(map.downgrade()),
+            Value::Function(f) | Value::Generator(f) => match &f.captures {
+                Some(captures) => WeakContainer::List(captures.downgrade()),
+                None => return false,
+            },
+            _ => return false,
+        };
+
+        let mut state = self.state.lock();
+        state.tracked.push(container);
+        state.tracked_since_collection += 1;
+        state.tracked_since_collection >= self.interval
+    }
+
+    // Frees unreachable cycles between the tracked containers
+    //
+    // Returns the number of containers that were freed.
+    pub fn collect(&self) -> usize {
+        let mut state = self.state.lock();
+        state.tracked_since_collection = 0;
+
+        // Containers that have been dropped are no longer tracked,
+        // along with containers that have been tracked more than once
+        let mut containers = Vec::with_capacity(state.tracked.len());
+        let mut indices = FxHashMap::default();
+        state.tracked.retain(|container| {
+            let container = match container {
+                WeakContainer::List(list) => list.upgrade().map(Container::List),
+                WeakContainer::Map(map) => map.upgrade().map(Container::Map),
+            };
+            match container {
+                Some(container) if !indices.contains_key(&container.id()) => {
+                    indices.insert(container.id(), containers.len());
+                    containers.push(container);
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        // Each container is locked for the duration of the collection so that references can't
+        // be taken from them while their reference counts are being checked. If a container is
+        // already locked then the collection is abandoned, it will be retried next time.
+        let mut guards = Vec::with_capacity(containers.len());
+        for container in containers.iter() {
+            let guard = match container {
+                Container::List(list) => list.try_write().map(ContainerGuard::List),
+                Container::Map(map) => map.try_write().map(ContainerGuard::Map),
+            };
+            match guard {
+                Some(guard) => guards.push(guard),
+                None => return 0,
+            }
+        }
+
+        // The references to tracked containers from each container
+        let children: Vec<Vec<usize>> = guards
+            .iter()
+            .map(|guard| {
+                let mut result = Vec::new();
+                match guard {
+                    ContainerGuard::List(list) => {
+                        for value in list.iter() {
+                            find_references(value, &indices, &mut result);
+                        }
+                    }
+                    ContainerGuard::Map(map) => {
+                        for (key, value) in map.iter() {
+                            find_references(key.value(), &indices, &mut result);
+                            find_references(value, &indices, &mut result);
+                        }
+                    }
+                }
+                result
+            })
+            .collect();
+
+        // The reference held in `containers` is excluded from the external reference counts
+        let mut external_refs: Vec<usize> = containers
+            .iter()
+            .map(|container| container.ref_count() - 1)
+            .collect();
+        for child in children.iter().flatten() {
+            external_refs[*child] = external_refs[*child].saturating_sub(1);
+        }
+
+        // Mark the containers that can be reached from externally referenced containers
+        let mut reachable = vec![false; containers.len()];
+        let mut stack: Vec<usize> = (0..containers.len())
+            .filter(|i| external_refs[*i] > 0)
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !reachable[i] {
+                reachable[i] = true;
+                stack.extend(children[i].iter().filter(|child| !reachable[**child]));
+            }
+        }
+
+        // The contents of unreachable containers are dropped once the locks have been released
+        let mut garbage_lists = Vec::new();
+        let mut garbage_maps = Vec::new();
+        for (guard, _) in guards
+            .iter_mut()
+            .zip(reachable.iter())
+            .filter(|(_, reachable)| !**reachable)
+        {
+            match guard {
+                ContainerGuard::List(list) => garbage_lists.push(mem::take(&mut **list)),
+                ContainerGuard::Map(map) => garbage_maps.push(mem::take(&mut **map)),
+            }
+        }
+        let freed = garbage_lists.len() + garbage_maps.len();
+
+        drop(guards);
+        drop(state);
+        drop(garbage_lists);
+        drop(garbage_maps);
+
+        freed
+    }
+}
+
+// Finds the tracked containers that are referred to by a value
+//
+// Tuples that are only referred to by the value's container are searched for references,
+// other tuples are treated as external references to the containers that they contain.
+fn find_references(value: &Value, indices: &FxHashMap<usize, usize>, result: &mut Vec<usize>) {
+    let id = match value {
+        Value::List(list) => list.instance_id(),
+        Value::Map(map) => map.instance_id(),
+        Value::Function(f) | Value::Generator(f) => match &f.captures {
+            Some(captures) => captures.instance_id(),
+            None => return,
+        },
+        Value::Tuple(tuple) if tuple.ref_count() == 1 => {
+            for value in tuple.data().iter() {
+                find_references(value, indices, result);
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    if let Some(index) = indices.get(&id) {
+        result.push(*index);
+    }
+}
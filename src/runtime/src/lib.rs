@@ -1,11 +1,26 @@
 //! Contains the runtime and core library for the Koto language
 
+mod access_cache;
+mod clock;
+mod coverage;
+#[cfg(feature = "cycle_detection")]
+mod cycles;
+mod debugger;
 mod error;
 mod external;
 mod file;
 mod frame;
+#[cfg(feature = "gc")]
+mod gc;
+mod memory;
 mod meta_map;
+mod module_version;
+mod pending;
+mod profiler;
+mod sandbox;
 mod stdio;
+mod test_result;
+mod value_conversion;
 mod value_iterator;
 mod value_key;
 mod value_list;
@@ -22,17 +37,32 @@ pub mod num4;
 pub mod value;
 
 pub use {
+    crate::core::{
+        coroutine::{Coroutine, CoroutineStatus},
+        thread::{channel, ChannelReceiver, ChannelSender},
+    },
+    clock::{DefaultClock, KotoClock},
+    coverage::ChunkCoverage,
+    debugger::{Breakpoint, DebugFrame, DebugHook},
     error::*,
     external::{ExternalData, ExternalFunction, ExternalValue},
     file::{KotoFile, KotoRead, KotoWrite},
-    koto_bytecode::{CompilerError, Loader, LoaderError},
+    koto_bytecode::{
+        CompilerError, CompilerSettings, Loader, LoaderError, LoaderWarning, ModuleResolver,
+        ResolvedModule,
+    },
     koto_parser::ParserError,
     meta_map::{BinaryOp, MetaKey, MetaMap, UnaryOp},
     num2::Num2,
     num4::Num4,
     parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    pending::{KotoFuture, Pending},
+    profiler::{FunctionProfile, Profile},
+    sandbox::{ImportPolicy, SandboxSettings},
     stdio::{DefaultStderr, DefaultStdin, DefaultStdout},
-    value::{FunctionInfo, Value},
+    test_result::{TestOutcome, TestResult},
+    value::{FunctionInfo, FunctionIp, Value},
+    value_conversion::{expect_arg, expect_entry, FromValue},
     value_iterator::{ExternalIterator, IntRange, ValueIterator, ValueIteratorOutput},
     value_key::ValueKey,
     value_list::{ValueList, ValueVec},
@@ -40,5 +70,8 @@ pub use {
     value_number::ValueNumber,
     value_string::ValueString,
     value_tuple::ValueTuple,
-    vm::{CallArgs, Vm, VmSettings},
+    vm::{CallArgs, InterruptHandle, StepResult, Vm, VmSettings},
 };
+
+#[cfg(feature = "cycle_detection")]
+pub use cycles::ReferenceCycle;
@@ -0,0 +1,189 @@
+use {
+    crate::{DataMap, Mutex, RwLock, Value, ValueKey, ValueVec},
+    rustc_hash::FxHashSet,
+    std::{
+        mem::size_of,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Weak,
+        },
+    },
+};
+
+/// The approximate number of bytes used by each element of a list or tuple
+pub(crate) const VALUE_SIZE: usize = size_of::<Value>();
+/// The approximate number of bytes used by each entry in a map
+pub(crate) const MAP_ENTRY_SIZE: usize = size_of::<ValueKey>() + size_of::<Value>();
+
+// The number of bytes that are counted for each tracked value, in addition to the value's size
+//
+// Tracked values are held by weak references, which keep the allocation that holds the value's
+// reference counts alive until the usage is recalculated, so the overhead is included in the usage
+// to keep the tracker's own memory usage bounded by the limit.
+const TRACKING_OVERHEAD: usize = size_of::<WeakAllocation>() + 2 * size_of::<usize>();
+
+// Tracks the approximate amount of memory that's used by values, see VmSettings::memory_limit
+//
+// New allocations are added to the usage as they're made, and the values that hold them are
+// tracked with weak references. When the limit would be exceeded, the usage is recalculated from
+// the tracked values that are still alive, which releases the memory of values that have been
+// dropped. This follows the approach of the GarbageCollector, which tracks containers without
+// needing to hook into the values' drops.
+pub(crate) struct MemoryTracker {
+    limit: usize,
+    usage: AtomicUsize,
+    allocations: Mutex<Vec<WeakAllocation>>,
+}
+
+// A weak reference to a value's data, see MemoryTracker::track_value
+enum WeakAllocation {
+    Str(Weak<str>),
+    List(Weak<RwLock<ValueVec>>),
+    Tuple(Weak<[Value]>),
+    Map(Weak<RwLock<DataMap>>),
+}
+
+impl WeakAllocation {
+    // Returns the address and size of the value's data if it's still alive
+    fn live_size(&self) -> Option<(usize, usize)> {
+        let result = match self {
+            Self::Str(s) => {
+                let s = s.upgrade()?;
+                (Arc::as_ptr(&s) as *const () as usize, s.len())
+            }
+            // Containers that are locked while the usage is recalculated (e.g. by an external
+            // function that's modifying a list) are counted without their contents.
+            Self::List(l) => {
+                let l = l.upgrade()?;
+                let len = l.try_read().map_or(0, |data| data.len());
+                (Arc::as_ptr(&l) as *const () as usize, len * VALUE_SIZE)
+            }
+            Self::Tuple(t) => {
+                let t = t.upgrade()?;
+                (Arc::as_ptr(&t) as *const () as usize, t.len() * VALUE_SIZE)
+            }
+            Self::Map(m) => {
+                let m = m.upgrade()?;
+                let len = m.try_read().map_or(0, |data| data.len());
+                (Arc::as_ptr(&m) as *const () as usize, len * MAP_ENTRY_SIZE)
+            }
+        };
+        Some(result)
+    }
+}
+
+impl MemoryTracker {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            usage: AtomicUsize::new(0),
+            allocations: Mutex::default(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn usage(&self) -> usize {
+        self.usage.load(Ordering::Relaxed)
+    }
+
+    // Sets the usage, with previously tracked values no longer being counted
+    pub fn set_usage(&self, usage: usize) {
+        self.allocations.lock().clear();
+        self.usage.store(usage, Ordering::Relaxed);
+    }
+
+    // Adds an allocation to the tracked usage, returning false if the limit would be exceeded
+    //
+    // If the limit would be exceeded then the usage is first recalculated from the tracked values
+    // that are still alive.
+    pub fn allocate(&self, size: usize) -> bool {
+        if self.try_allocate(size) {
+            return true;
+        }
+
+        self.release_dropped_values();
+        self.try_allocate(size)
+    }
+
+    // Starts tracking a value whose size has been added to the usage with `allocate`,
+    // so that its size can be released once it has been dropped
+    //
+    // Returns false if the limit would be exceeded by the tracking overhead.
+    pub fn track_value(&self, value: &Value) -> bool {
+        let allocation = match value {
+            Value::Str(s) if !s.is_empty() => WeakAllocation::Str(s.downgrade()),
+            Value::List(l) => WeakAllocation::List(l.downgrade()),
+            Value::Tuple(t) if !t.data().is_empty() => WeakAllocation::Tuple(t.downgrade()),
+            Value::Map(m) => WeakAllocation::Map(m.downgrade()),
+            _ => return true,
+        };
+
+        self.allocations.lock().push(allocation);
+        self.allocate(TRACKING_OVERHEAD)
+    }
+
+    fn try_allocate(&self, size: usize) -> bool {
+        self.usage
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |usage| {
+                usage.checked_add(size).filter(|usage| *usage <= self.limit)
+            })
+            .is_ok()
+    }
+
+    // Recalculates the usage from the tracked values that are still alive
+    //
+    // Allocations that weren't made for a tracked value (e.g. with Vm::track_allocation) are
+    // released, along with values that have been dropped.
+    fn release_dropped_values(&self) {
+        let mut allocations = self.allocations.lock();
+        let mut counted = FxHashSet::default();
+        let mut usage = 0;
+
+        allocations.retain(|allocation| match allocation.live_size() {
+            Some((id, size)) if counted.insert(id) => {
+                usage += size + TRACKING_OVERHEAD;
+                true
+            }
+            _ => false,
+        });
+
+        self.usage.store(usage, Ordering::Relaxed);
+    }
+}
+
+// Returns the approximate number of bytes that are allocated for a value's contents
+//
+// Values contained in lists, tuples, and maps aren't included, they're accounted for
+// separately when they're created.
+pub(crate) fn allocation_size(value: &Value) -> usize {
+    match value {
+        Value::Str(s) => s.len(),
+        Value::List(l) => l.len() * VALUE_SIZE,
+        Value::Tuple(t) => t.data().len() * VALUE_SIZE,
+        Value::Map(m) => m.len() * MAP_ENTRY_SIZE,
+        _ => 0,
+    }
+}
+
+// Returns the allocation size of a value that has been returned from an external function
+//
+// Values that share their contents with other values were allocated before the call,
+// so only values with uniquely referenced contents are included.
+pub(crate) fn new_allocation_size(value: &Value) -> usize {
+    let is_new = match value {
+        Value::Str(s) => s.ref_count() == 1,
+        Value::List(l) => l.ref_count() == 1,
+        Value::Tuple(t) => t.ref_count() == 1,
+        Value::Map(m) => m.ref_count() == 1,
+        _ => false,
+    };
+
+    if is_new {
+        allocation_size(value)
+    } else {
+        0
+    }
+}
@@ -362,10 +362,16 @@ pub enum MetaKey {
     UnaryOp(UnaryOp),
     Named(ValueString),
     Test(ValueString),
+    TestExpectedToFail(ValueString),
+    TestSkip(ValueString),
+    TestTimeout(ValueString),
     Tests,
     PreTest,
     PostTest,
     Type,
+    Version,
+    Requires,
+    Reload,
 }
 
 impl MetaKey {
@@ -375,10 +381,16 @@ impl MetaKey {
             MetaKey::UnaryOp(op) => MetaKeyRef::UnaryOp(*op),
             MetaKey::Named(name) => MetaKeyRef::Named(name),
             MetaKey::Test(name) => MetaKeyRef::Test(name),
+            MetaKey::TestExpectedToFail(name) => MetaKeyRef::TestExpectedToFail(name),
+            MetaKey::TestSkip(name) => MetaKeyRef::TestSkip(name),
+            MetaKey::TestTimeout(name) => MetaKeyRef::TestTimeout(name),
             MetaKey::Tests => MetaKeyRef::Tests,
             MetaKey::PreTest => MetaKeyRef::PreTest,
             MetaKey::PostTest => MetaKeyRef::PostTest,
             MetaKey::Type => MetaKeyRef::Type,
+            MetaKey::Version => MetaKeyRef::Version,
+            MetaKey::Requires => MetaKeyRef::Requires,
+            MetaKey::Reload => MetaKeyRef::Reload,
         }
     }
 }
@@ -483,9 +495,21 @@ pub fn meta_id_to_key(id: MetaKeyId, name: Option<ValueString>) -> Result<MetaKe
         }
         MetaKeyId::Tests => MetaKey::Tests,
         MetaKeyId::Test => MetaKey::Test(name.ok_or_else(|| "Missing name for test".to_string())?),
+        MetaKeyId::TestExpectedToFail => {
+            MetaKey::TestExpectedToFail(name.ok_or_else(|| "Missing name for test".to_string())?)
+        }
+        MetaKeyId::TestSkip => {
+            MetaKey::TestSkip(name.ok_or_else(|| "Missing name for test".to_string())?)
+        }
+        MetaKeyId::TestTimeout => {
+            MetaKey::TestTimeout(name.ok_or_else(|| "Missing name for test".to_string())?)
+        }
         MetaKeyId::PreTest => MetaKey::PreTest,
         MetaKeyId::PostTest => MetaKey::PostTest,
         MetaKeyId::Type => MetaKey::Type,
+        MetaKeyId::Version => MetaKey::Version,
+        MetaKeyId::Requires => MetaKey::Requires,
+        MetaKeyId::Reload => MetaKey::Reload,
         MetaKeyId::Invalid => return Err("Invalid MetaKeyId".to_string()),
     };
 
@@ -499,10 +523,16 @@ enum MetaKeyRef<'a> {
     UnaryOp(UnaryOp),
     Named(&'a str),
     Test(&'a str),
+    TestExpectedToFail(&'a str),
+    TestSkip(&'a str),
+    TestTimeout(&'a str),
     Tests,
     PreTest,
     PostTest,
     Type,
+    Version,
+    Requires,
+    Reload,
 }
 
 // A trait that allows for allocation-free map accesses with &str
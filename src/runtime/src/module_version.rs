@@ -0,0 +1,163 @@
+//! Helpers for validating the version metadata that modules can declare with `@version` and
+//! `@requires`.
+
+use std::fmt;
+
+/// A version number in the form `major.minor.patch`
+///
+/// The minor and patch numbers are optional, and default to 0.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Version {
+    major: u32,
+    minor: u32,
+    patch: u32,
+}
+
+impl Version {
+    /// Parses a version from a string, e.g. "1.2.3"
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().split('.');
+
+        let mut parse_part = |required| match parts.next() {
+            Some(part) if !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) => {
+                part.parse::<u32>().ok()
+            }
+            Some(_) => None,
+            None if required => None,
+            None => Some(0),
+        };
+
+        let major = parse_part(true)?;
+        let minor = parse_part(false)?;
+        let patch = parse_part(false)?;
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            major,
+            minor,
+            patch,
+        })
+    }
+
+    /// The version of the Koto runtime
+    pub fn runtime() -> Self {
+        // The crate's version is always a valid version number
+        Self::parse(env!("CARGO_PKG_VERSION")).unwrap()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A requirement on the version of the Koto runtime, e.g. "koto >= 0.9"
+pub struct VersionRequirement {
+    comparison: VersionComparison,
+    version: Version,
+}
+
+#[derive(Clone, Copy)]
+enum VersionComparison {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl VersionRequirement {
+    /// Parses a requirement from a string, e.g. "koto >= 0.9"
+    pub fn parse(requirement: &str) -> Option<Self> {
+        let requirement = requirement.trim().strip_prefix("koto")?.trim_start();
+
+        // The longer comparison operators need to be checked first
+        let (comparison, version) = [
+            (">=", VersionComparison::GreaterOrEqual),
+            ("<=", VersionComparison::LessOrEqual),
+            ("==", VersionComparison::Equal),
+            (">", VersionComparison::Greater),
+            ("<", VersionComparison::Less),
+        ]
+        .iter()
+        .find_map(|(op, comparison)| {
+            requirement
+                .strip_prefix(op)
+                .map(|version| (*comparison, version))
+        })?;
+
+        Some(Self {
+            comparison,
+            version: Version::parse(version)?,
+        })
+    }
+
+    /// Returns true if the provided version satisfies the requirement
+    pub fn is_satisfied_by(&self, version: Version) -> bool {
+        use VersionComparison::*;
+
+        match self.comparison {
+            Equal => version == self.version,
+            Greater => version > self.version,
+            GreaterOrEqual => version >= self.version,
+            Less => version < self.version,
+            LessOrEqual => version <= self.version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(Version::parse("1"), Some(version(1, 0, 0)));
+        assert_eq!(Version::parse("1.2"), Some(version(1, 2, 0)));
+        assert_eq!(Version::parse(" 1.2.3 "), Some(version(1, 2, 3)));
+        assert_eq!(Version::parse(""), None);
+        assert_eq!(Version::parse("1."), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert_eq!(Version::parse("1.x"), None);
+        assert_eq!(Version::parse("-1"), None);
+    }
+
+    #[test]
+    fn version_requirements() {
+        let check = |requirement: &str, version: Version| {
+            VersionRequirement::parse(requirement)
+                .unwrap()
+                .is_satisfied_by(version)
+        };
+
+        assert!(check("koto >= 0.9", version(0, 9, 0)));
+        assert!(check("koto>=0.9", version(0, 10, 0)));
+        assert!(!check("koto >= 0.9", version(0, 8, 9)));
+        assert!(check("koto > 0.9", version(0, 9, 1)));
+        assert!(!check("koto > 0.9", version(0, 9, 0)));
+        assert!(check("koto < 1", version(0, 9, 0)));
+        assert!(check("koto <= 0.9.1", version(0, 9, 1)));
+        assert!(check("koto == 0.9", version(0, 9, 0)));
+        assert!(!check("koto == 0.9", version(0, 9, 1)));
+    }
+
+    #[test]
+    fn invalid_version_requirements() {
+        assert!(VersionRequirement::parse("0.9").is_none());
+        assert!(VersionRequirement::parse("koto 0.9").is_none());
+        assert!(VersionRequirement::parse("koto >= ").is_none());
+        assert!(VersionRequirement::parse("python >= 3").is_none());
+    }
+}
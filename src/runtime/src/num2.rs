@@ -1,5 +1,5 @@
 use {
-    crate::ValueNumber,
+    crate::{value_number::f64_near, ValueNumber},
     std::{
         fmt,
         hash::{Hash, Hasher},
@@ -15,6 +15,11 @@ impl Num2 {
         Num2(self.0.abs(), self.1.abs())
     }
 
+    /// Returns true if each component is within `allowed_diff` of the other's component
+    pub fn is_near(&self, other: &Self, allowed_diff: f64) -> bool {
+        f64_near(self.0, other.0, allowed_diff) && f64_near(self.1, other.1, allowed_diff)
+    }
+
     pub fn length(&self) -> f64 {
         (self.0 * self.0 + self.1 * self.1).sqrt()
     }
@@ -1,5 +1,5 @@
 use {
-    crate::ValueNumber,
+    crate::{value_number::f32_near, ValueNumber},
     std::{
         fmt,
         hash::{Hash, Hasher},
@@ -15,6 +15,14 @@ impl Num4 {
         Self(self.0.abs(), self.1.abs(), self.2.abs(), self.3.abs())
     }
 
+    /// Returns true if each component is within `allowed_diff` of the other's component
+    pub fn is_near(&self, other: &Self, allowed_diff: f32) -> bool {
+        f32_near(self.0, other.0, allowed_diff)
+            && f32_near(self.1, other.1, allowed_diff)
+            && f32_near(self.2, other.2, allowed_diff)
+            && f32_near(self.3, other.3, allowed_diff)
+    }
+
     pub fn length(&self) -> f64 {
         let x = self.0 as f64;
         let y = self.1 as f64;
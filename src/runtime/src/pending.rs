@@ -0,0 +1,83 @@
+use {
+    crate::{ExternalData, ExternalValue, MetaMap, Mutex, RuntimeResult, Value},
+    std::{fmt, future::Future, pin::Pin},
+};
+
+/// The future of an async external function call, see [Pending]
+pub type KotoFuture = Pin<Box<dyn Future<Output = RuntimeResult> + Send>>;
+
+/// The result of an async external function, which is available once its future has completed
+///
+/// When an external function returns a pending value while a VM is running with
+/// [Vm::run_async](crate::Vm::run_async), the calling frame is suspended until the future
+/// completes, with the future's output used as the result of the call.
+///
+/// # Example
+///
+/// ```
+/// use koto_runtime::{Pending, Value, ValueMap, ValueNumber};
+///
+/// let mut module = ValueMap::new();
+/// module.add_fn("answer", |vm, args| {
+///     let offset = match vm.get_args(args) {
+///         [Value::Number(n)] => *n,
+///         _ => ValueNumber::from(0),
+///     };
+///     Ok(Pending::new(async move {
+///         // e.g. await a network request
+///         Ok(Value::Number(offset + ValueNumber::from(42)))
+///     }))
+/// });
+/// ```
+pub struct Pending(Mutex<Option<KotoFuture>>);
+
+impl Pending {
+    /// Makes a pending value that will resolve with the future's output
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(future: impl Future<Output = RuntimeResult> + Send + 'static) -> Value {
+        let pending = Self(Mutex::new(Some(Box::pin(future))));
+        Value::ExternalValue(ExternalValue::new(
+            pending,
+            MetaMap::with_type_name("Pending"),
+        ))
+    }
+
+    /// Returns true if the value was made with [Pending::new]
+    pub fn is_pending(value: &Value) -> bool {
+        match value {
+            Value::ExternalValue(value) => value.data().downcast_ref::<Self>().is_some(),
+            _ => false,
+        }
+    }
+
+    // Takes the future out of a pending value
+    //
+    // None is returned if the value isn't pending, or if its future has already been taken.
+    pub(crate) fn take_future(value: &Value) -> Option<KotoFuture> {
+        match value {
+            Value::ExternalValue(value) => value
+                .data()
+                .downcast_ref::<Self>()
+                .and_then(|pending| pending.0.lock().take()),
+            _ => None,
+        }
+    }
+}
+
+impl ExternalData for Pending {
+    fn value_type(&self) -> String {
+        "Pending".to_string()
+    }
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pending")
+    }
+}
+
+impl fmt::Debug for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pending")
+    }
+}
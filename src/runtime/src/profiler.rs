@@ -0,0 +1,249 @@
+use {
+    crate::{Value, ValueMap},
+    koto_bytecode::Chunk,
+    koto_parser::Span,
+    std::{collections::HashMap, fmt, sync::Arc, time::Duration},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// Statistics that were recorded for a function while profiling, see [Vm::take_profile]
+///
+/// A chunk's top-level code is profiled as a function starting at ip 0.
+///
+/// [Vm::take_profile]: crate::Vm::take_profile
+#[derive(Clone, Debug)]
+pub struct FunctionProfile {
+    /// The chunk that contains the function
+    pub chunk: Arc<Chunk>,
+    /// The position in the chunk's bytecode of the function's first instruction
+    pub ip: usize,
+    /// The number of calls to the function that have finished
+    pub calls: u64,
+    /// The time spent in the function, including the time spent in the functions it called
+    ///
+    /// Recursive calls are included in the time of the outermost call.
+    pub inclusive_time: Duration,
+    /// The time spent in the function, excluding the time spent in the Koto functions it called
+    ///
+    /// Time spent in calls to external functions is included.
+    pub exclusive_time: Duration,
+    /// The number of values that were allocated while the function was running
+    ///
+    /// Allocations made by the Koto functions that it called aren't included,
+    /// see [VmSettings::memory_limit](crate::VmSettings::memory_limit) for the allocations that
+    /// are tracked.
+    pub allocations: u64,
+    /// The approximate number of bytes that were allocated while the function was running
+    pub allocated_bytes: u64,
+}
+
+impl FunctionProfile {
+    fn new(chunk: Arc<Chunk>, ip: usize) -> Self {
+        Self {
+            chunk,
+            ip,
+            calls: 0,
+            inclusive_time: Duration::default(),
+            exclusive_time: Duration::default(),
+            allocations: 0,
+            allocated_bytes: 0,
+        }
+    }
+
+    /// The function's name, if known
+    ///
+    /// See [DebugInfo::function_name](koto_bytecode::DebugInfo::function_name).
+    pub fn name(&self) -> Option<&str> {
+        self.chunk.debug_info.function_name(self.ip)
+    }
+
+    /// The span in the source of the function's first instruction, if available
+    pub fn span(&self) -> Option<Span> {
+        self.chunk.source_span(self.ip)
+    }
+
+    /// Returns the profile as a Koto map
+    ///
+    /// Times are provided in seconds.
+    pub fn to_map(&self) -> ValueMap {
+        let optional_value = |value: Option<Value>| value.unwrap_or(Value::Empty);
+
+        let mut map = ValueMap::with_capacity(9);
+        map.add_value("name", optional_value(self.name().map(Value::from)));
+        map.add_value(
+            "path",
+            optional_value(
+                self.chunk
+                    .source_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().as_ref().into()),
+            ),
+        );
+        map.add_value(
+            "line",
+            optional_value(self.span().map(|span| span.start.line.into())),
+        );
+        map.add_value("calls", self.calls.into());
+        map.add_value("inclusive_time", self.inclusive_time.as_secs_f64().into());
+        map.add_value("exclusive_time", self.exclusive_time.as_secs_f64().into());
+        map.add_value("allocations", self.allocations.into());
+        map.add_value("allocated_bytes", self.allocated_bytes.into());
+        map
+    }
+}
+
+impl fmt::Display for FunctionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name(), self.ip) {
+            (Some(name), _) => write!(f, "{}", name)?,
+            (None, 0) => write!(f, "<top level>")?,
+            (None, _) => write!(f, "<anonymous>")?,
+        }
+
+        match &self.chunk.source_path {
+            Some(path) => write!(f, " ({}", path.to_string_lossy())?,
+            None => write!(f, " (<script>")?,
+        }
+
+        match self.span() {
+            // The top-level code's position is left out, it always starts with the chunk
+            Some(span) if self.ip > 0 => {
+                write!(f, ":{}:{})", span.start.line, span.start.column)
+            }
+            _ => write!(f, ")"),
+        }
+    }
+}
+
+/// The statistics recorded for each of the functions that were called while profiling
+///
+/// See [VmSettings::profile](crate::VmSettings::profile) and [Vm::take_profile].
+///
+/// [Vm::take_profile]: crate::Vm::take_profile
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// The profiled functions, sorted by their exclusive time, with the slowest first
+    pub functions: Vec<FunctionProfile>,
+}
+
+impl Profile {
+    /// Returns the profile as a Koto map
+    ///
+    /// Each function is keyed by its description, e.g. `f (script.koto:3:5)`,
+    /// see [FunctionProfile::to_map].
+    pub fn to_map(&self) -> ValueMap {
+        let mut map = ValueMap::with_capacity(self.functions.len());
+        for function in self.functions.iter() {
+            map.add_map(&function.to_string(), function.to_map());
+        }
+        map
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>12} {:>12} {:>12}  function",
+            "calls", "inclusive", "exclusive", "allocations"
+        )?;
+
+        for function in self.functions.iter() {
+            writeln!(
+                f,
+                "{:>8} {:>11.3}ms {:>11.3}ms {:>12}  {}",
+                function.calls,
+                function.inclusive_time.as_secs_f64() * 1000.0,
+                function.exclusive_time.as_secs_f64() * 1000.0,
+                function.allocations,
+                function
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// The profiling state of a call frame, see Vm::push_frame
+#[derive(Clone, Debug)]
+pub(crate) struct FrameProfile {
+    // The ip of the function's first instruction, used to identify the function
+    pub function_ip: usize,
+    // The time when the frame was pushed, Instant isn't available on wasm32
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+    // True if the function was already running further up the call stack,
+    // in which case the frame's time is included in the outer call's inclusive time
+    pub is_recursive: bool,
+    // The time spent in the Koto functions that were called from the frame
+    pub child_time: Duration,
+}
+
+impl FrameProfile {
+    pub fn new(function_ip: usize, is_recursive: bool) -> Self {
+        Self {
+            function_ip,
+            is_recursive,
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+            child_time: Duration::default(),
+        }
+    }
+
+    // The time since the frame was pushed, which is zero on wasm32
+    pub fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.start.elapsed()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Duration::default()
+        }
+    }
+}
+
+// Accumulates the statistics of the functions that are called while profiling
+//
+// Functions are identified by their chunk's address and their ip, with the recorded chunks being
+// kept alive until the profile is taken, see CoverageRecorder.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    functions: HashMap<(usize, usize), FunctionProfile>,
+}
+
+impl Profiler {
+    fn function(&mut self, chunk: &Arc<Chunk>, ip: usize) -> &mut FunctionProfile {
+        self.functions
+            .entry((Arc::as_ptr(chunk) as usize, ip))
+            .or_insert_with(|| FunctionProfile::new(chunk.clone(), ip))
+    }
+
+    pub fn record_call(&mut self, chunk: &Arc<Chunk>, frame: &FrameProfile, inclusive: Duration) {
+        let function = self.function(chunk, frame.function_ip);
+        function.calls += 1;
+        if !frame.is_recursive {
+            function.inclusive_time += inclusive;
+        }
+        function.exclusive_time += inclusive.saturating_sub(frame.child_time);
+    }
+
+    pub fn record_allocation(&mut self, chunk: &Arc<Chunk>, ip: usize, size: usize) {
+        let function = self.function(chunk, ip);
+        function.allocations += 1;
+        function.allocated_bytes += size as u64;
+    }
+
+    // Returns the recorded profile, sorted by exclusive time
+    pub fn take(&mut self) -> Profile {
+        let mut functions = self
+            .functions
+            .drain()
+            .map(|(_, function)| function)
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|function| std::cmp::Reverse(function.exclusive_time));
+        Profile { functions }
+    }
+}
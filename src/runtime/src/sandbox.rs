@@ -0,0 +1,126 @@
+use crate::{runtime_error, ExternalFunction, Value, ValueKey, ValueMap};
+
+/// Restrictions on the capabilities that are available to scripts, see [VmSettings::sandbox]
+///
+/// Core modules and functions are referred to by name, e.g. `os` for the whole `os` module,
+/// or `io.open` for a single function. Names that don't match an entry in the core library are
+/// ignored.
+///
+/// [VmSettings::sandbox]: crate::VmSettings::sandbox
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SandboxSettings {
+    /// Core modules and functions that are removed
+    ///
+    /// Excluded modules are removed from the prelude. The functions of modules that provide
+    /// methods for values, like `string`, remain available as methods (e.g. `'abc'.size()`),
+    /// unless they're also excluded individually.
+    pub excluded: Vec<String>,
+    /// Core modules and functions that are replaced with stubs
+    ///
+    /// Stubbed functions throw an error when they're called, allowing scripts that refer to them
+    /// to run as long as the functions aren't called. Values other than functions are replaced
+    /// with `()`. Stubbing a module stubs each of its entries.
+    pub stubbed: Vec<String>,
+    /// The modules that can be imported by scripts
+    pub imports: ImportPolicy,
+}
+
+/// The modules that can be imported by scripts, see [SandboxSettings::imports]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImportPolicy {
+    /// Modules can be imported from the module resolver and from the filesystem
+    #[default]
+    Unrestricted,
+    /// Modules can only be imported from the module resolver, see [VmSettings::module_resolver]
+    ///
+    /// Imports of modules that aren't provided by the resolver fail without accessing the
+    /// filesystem. When no resolver has been set then no modules can be imported.
+    ///
+    /// [VmSettings::module_resolver]: crate::VmSettings::module_resolver
+    ResolverOnly,
+}
+
+impl SandboxSettings {
+    /// A profile for running untrusted scripts
+    ///
+    /// The `os` module is excluded, and functions that access the filesystem or the process's
+    /// standard input are stubbed. Output with `io.print` remains available, and is written to
+    /// the VM's `stdout`.
+    ///
+    /// Modules can only be imported from the VM's module resolver, allowing the embedder to
+    /// control the set of modules that are available.
+    pub fn untrusted() -> Self {
+        Self {
+            excluded: vec!["os".into()],
+            stubbed: [
+                "io.create",
+                "io.current_dir",
+                "io.exists",
+                "io.open",
+                "io.read_to_string",
+                "io.remove_file",
+                "io.stdin",
+                "io.temp_dir",
+                "koto.import_all_in",
+                "test.snapshot",
+            ]
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+            imports: ImportPolicy::ResolverOnly,
+        }
+    }
+
+    // Applies the restrictions to the prelude
+    //
+    // The prelude's core modules are shared with the VM's core library, so the restrictions also
+    // apply to the functions that are available as methods.
+    pub(crate) fn apply(&self, prelude: &ValueMap) {
+        for name in self.stubbed.iter() {
+            match name.split_once('.') {
+                Some((module, function)) => {
+                    if let Some(Value::Map(module)) = prelude.data().get_with_string(module) {
+                        if let Some(entry) = module.data_mut().get_with_string_mut(function) {
+                            *entry = make_stub(name, entry);
+                        }
+                    }
+                }
+                None => {
+                    if let Some(Value::Map(module)) = prelude.data().get_with_string(name) {
+                        for (key, entry) in module.data_mut().iter_mut() {
+                            *entry = make_stub(&format!("{}.{}", name, key.value()), entry);
+                        }
+                    }
+                }
+            }
+        }
+
+        for name in self.excluded.iter() {
+            match name.split_once('.') {
+                Some((module, function)) => {
+                    if let Some(Value::Map(module)) = prelude.data().get_with_string(module) {
+                        module.data_mut().shift_remove(&ValueKey::from(function));
+                    }
+                }
+                None => {
+                    prelude
+                        .data_mut()
+                        .shift_remove(&ValueKey::from(name.as_str()));
+                }
+            }
+        }
+    }
+}
+
+fn make_stub(name: &str, entry: &Value) -> Value {
+    match entry {
+        Value::ExternalFunction(f) => {
+            let name = name.to_string();
+            Value::ExternalFunction(ExternalFunction::new(
+                move |_, _| runtime_error!("'{}' is disabled by the sandbox settings", name),
+                f.is_instance_function,
+            ))
+        }
+        _ => Value::Empty,
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fmt, path::PathBuf, time::Duration};
+
+/// The outcome of running a test, see [TestResult]
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestOutcome {
+    /// The test passed
+    Passed,
+    /// The test failed, with the resulting error message
+    Failed(String),
+    /// The test was expected to fail, and failed with the resulting error message
+    ExpectedFailure(String),
+    /// The test was expected to fail, but it passed
+    UnexpectedPass,
+    /// The test was skipped, with the provided reason
+    Skipped(String),
+    /// The test didn't finish within its timeout
+    TimedOut(Duration),
+}
+
+impl TestOutcome {
+    /// Returns true if the outcome should cause the test run to fail
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::Failed(_) | Self::UnexpectedPass | Self::TimedOut(_)
+        )
+    }
+
+    /// Returns a short name for the outcome, e.g. `passed`, or `expected_failure`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed(_) => "failed",
+            Self::ExpectedFailure(_) => "expected_failure",
+            Self::UnexpectedPass => "unexpected_pass",
+            Self::Skipped(_) => "skipped",
+            Self::TimedOut(_) => "timed_out",
+        }
+    }
+}
+
+impl fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed => write!(f, "passed"),
+            Self::Failed(error) => write!(f, "failed: {}", error),
+            Self::ExpectedFailure(error) => write!(f, "failed as expected: {}", error),
+            Self::UnexpectedPass => write!(f, "passed, but was expected to fail"),
+            Self::Skipped(reason) => write!(f, "skipped: {}", reason),
+            Self::TimedOut(timeout) => {
+                write!(f, "timed out after {}s", timeout.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// The result of running a test, see [Vm::take_test_results](crate::Vm::take_test_results)
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    /// The path of the module that contains the test
+    pub module_path: Option<PathBuf>,
+    /// The test's name
+    pub name: String,
+    /// The test's outcome
+    pub outcome: TestOutcome,
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.module_path {
+            write!(f, "{} - ", path.to_string_lossy())?;
+        }
+        write!(f, "'{}' {}", self.name, self.outcome)
+    }
+}
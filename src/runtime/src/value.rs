@@ -1,14 +1,17 @@
 use {
     crate::{
-        num2, num4, value_key::ValueRef, value_map::ValueMap, ExternalData, ExternalFunction,
-        ExternalValue, IntRange, MetaKey, RwLock, ValueIterator, ValueList, ValueNumber,
-        ValueString, ValueTuple, ValueVec,
+        num2, num4, value_key::ValueRef, value_map::ValueMap, DataMap, ExternalData,
+        ExternalFunction, ExternalValue, IntRange, MetaKey, RwLock, ValueIterator, ValueList,
+        ValueNumber, ValueString, ValueTuple, ValueVec,
     },
     koto_bytecode::Chunk,
+    rustc_hash::FxHashMap,
     std::{fmt, sync::Arc},
 };
 
 /// The core Value type for Koto
+///
+/// Values are 40 bytes in size, or 32 bytes with the runtime's `compact_value` feature enabled.
 #[derive(Clone, Debug)]
 pub enum Value {
     /// The default type representing the absence of a value
@@ -104,26 +107,75 @@ impl Value {
         }
     }
 
+    /// Returns a deep copy of the value
+    ///
+    /// Lists, maps, and tuples are copied recursively, and iterators are copied as they would be
+    /// with `iterator.copy`. Other values are cloned.
+    ///
+    /// Containers that appear more than once in the value are only copied once, so the copy has
+    /// the same shared structure as the original. Cycles (e.g. a list that contains itself) are
+    /// reproduced in the copy.
     pub fn deep_copy(&self) -> Value {
+        self.deep_copy_with_cache(&mut FxHashMap::default())
+    }
+
+    // Makes a deep copy of the value, with the copies of lists, maps, and tuples cached by the
+    // address of the original's data.
+    //
+    // Lists and maps are cached before their contents are copied so that cycles can be detected.
+    fn deep_copy_with_cache(&self, copies: &mut FxHashMap<usize, Value>) -> Value {
         use Value::*;
 
         match &self {
             List(l) => {
-                let result = l.data().iter().map(|v| v.deep_copy()).collect::<ValueVec>();
-                List(ValueList::with_data(result))
+                if let Some(copy) = copies.get(&l.instance_id()) {
+                    return copy.clone();
+                }
+
+                let result = ValueList::default();
+                copies.insert(l.instance_id(), List(result.clone()));
+
+                let data = l
+                    .data()
+                    .iter()
+                    .map(|v| v.deep_copy_with_cache(copies))
+                    .collect::<ValueVec>();
+                *result.data_mut() = data;
+
+                List(result)
             }
             Tuple(t) => {
-                let result = t.data().iter().map(|v| v.deep_copy()).collect::<Vec<_>>();
-                Tuple(result.into())
+                if let Some(copy) = copies.get(&t.instance_id()) {
+                    return copy.clone();
+                }
+
+                let data = t
+                    .data()
+                    .iter()
+                    .map(|v| v.deep_copy_with_cache(copies))
+                    .collect::<Vec<_>>();
+                let result = Tuple(data.into());
+                copies.insert(t.instance_id(), result.clone());
+
+                result
             }
             Map(m) => {
+                if let Some(copy) = copies.get(&m.instance_id()) {
+                    return copy.clone();
+                }
+
+                let meta = m.meta().clone();
+                let result = ValueMap::with_contents(DataMap::default(), meta);
+                copies.insert(m.instance_id(), Map(result.clone()));
+
                 let data = m
                     .data()
                     .iter()
-                    .map(|(k, v)| (k.clone(), v.deep_copy()))
+                    .map(|(k, v)| (k.clone(), v.deep_copy_with_cache(copies)))
                     .collect();
-                let meta = m.meta().clone();
-                Map(ValueMap::with_contents(data, meta))
+                *result.data_mut() = data;
+
+                Map(result)
             }
             Iterator(i) => Iterator(i.make_copy()),
             _ => self.clone(),
@@ -298,12 +350,23 @@ pub struct SimpleFunctionInfo {
     pub arg_count: u8,
 }
 
+#[cfg(feature = "compact_value")]
+type FunctionIpOffset = u32;
+#[cfg(not(feature = "compact_value"))]
+type FunctionIpOffset = usize;
+
+/// The type used for the start ip of a [FunctionInfo]
+///
+/// With the runtime's `compact_value` feature enabled, the ip is stored as a 32 bit offset,
+/// which reduces the size of [Value].
+pub type FunctionIp = FunctionIpOffset;
+
 #[derive(Clone, Debug)]
 pub struct FunctionInfo {
     /// The [Chunk] in which the function can be found.
     pub chunk: Arc<Chunk>,
     /// The start ip of the function.
-    pub ip: usize,
+    pub ip: FunctionIp,
     /// The expected number of arguments for the function.
     pub arg_count: u8,
     /// If the function is an instance function, then the first argument will be `self`.
@@ -356,4 +419,12 @@ mod tests {
         // total size of Value should not be greater than 40 bytes.
         assert!(std::mem::size_of::<Value>() <= 40);
     }
+
+    #[test]
+    #[cfg(feature = "compact_value")]
+    fn test_compact_value_mem_size() {
+        // With compact values, all Value variants should have a size of <= 24 bytes,
+        // which allows the total size of Value to be reduced to 32 bytes.
+        assert!(std::mem::size_of::<Value>() <= 32);
+    }
 }
@@ -0,0 +1,212 @@
+//! Helpers for converting Koto values into Rust types
+//!
+//! These are useful when implementing external functions, where each of the function's arguments
+//! needs to be checked and converted before it can be used.
+
+use {
+    crate::{
+        runtime_error, Num2, Num4, RuntimeError, Value, ValueList, ValueMap, ValueNumber,
+        ValueString, ValueTuple,
+    },
+    std::convert::TryFrom,
+};
+
+/// A Rust type that can be converted from a Koto [Value]
+pub trait FromValue: Sized {
+    /// The name of the expected value type, used in error messages
+    fn expected_type() -> &'static str;
+
+    /// Converts the value, returning `None` if the value can't be converted
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    fn expected_type() -> &'static str {
+        "Value"
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for bool {
+    fn expected_type() -> &'static str {
+        "Bool"
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for ValueNumber {
+    fn expected_type() -> &'static str {
+        "Number"
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! from_value_float {
+    ($type:ident) => {
+        impl FromValue for $type {
+            fn expected_type() -> &'static str {
+                "Number"
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::Number(n) => Some(n.into()),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_value_float!(f32);
+from_value_float!(f64);
+
+// Integers are only converted from numbers without a fractional part that fit in the target type
+macro_rules! from_value_int {
+    ($type:ident) => {
+        impl FromValue for $type {
+            fn expected_type() -> &'static str {
+                "Int"
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::Number(ValueNumber::I64(n)) => $type::try_from(*n).ok(),
+                    Value::Number(ValueNumber::F64(n))
+                        if n.fract() == 0.0
+                            && *n >= $type::MIN as f64
+                            && *n <= $type::MAX as f64 =>
+                    {
+                        Some(*n as $type)
+                    }
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_value_int!(i32);
+from_value_int!(u32);
+from_value_int!(i64);
+from_value_int!(u64);
+from_value_int!(isize);
+from_value_int!(usize);
+
+impl FromValue for String {
+    fn expected_type() -> &'static str {
+        "String"
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Str(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! from_value_variant {
+    ($type:ident, $variant:ident, $name:expr) => {
+        impl FromValue for $type {
+            fn expected_type() -> &'static str {
+                $name
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::$variant(inner) => Some(inner.clone()),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_value_variant!(ValueString, Str, "String");
+from_value_variant!(ValueList, List, "List");
+from_value_variant!(ValueMap, Map, "Map");
+from_value_variant!(ValueTuple, Tuple, "Tuple");
+from_value_variant!(Num2, Num2, "Num2");
+from_value_variant!(Num4, Num4, "Num4");
+
+/// Optional values are converted from `()`, allowing arguments and map entries to be omitted
+impl<T: FromValue> FromValue for Option<T> {
+    fn expected_type() -> &'static str {
+        T::expected_type()
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Empty => Some(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// Converts a function's argument into the requested type
+///
+/// `fn_name` is used in the error message if the argument is missing or has an unexpected type,
+/// e.g. `slider - Expected Number as argument 2, found String`.
+///
+/// Missing arguments are treated as `()`, so optional arguments can be requested as `Option<T>`.
+pub fn expect_arg<T: FromValue>(
+    fn_name: &str,
+    args: &[Value],
+    index: usize,
+) -> Result<T, RuntimeError> {
+    let value = args.get(index).unwrap_or(&Value::Empty);
+    match T::from_value(value) {
+        Some(result) => Ok(result),
+        None if index >= args.len() => runtime_error!(
+            "{} - Expected {} as argument {}",
+            fn_name,
+            T::expected_type(),
+            index + 1,
+        ),
+        None => runtime_error!(
+            "{} - Expected {} as argument {}, found {}",
+            fn_name,
+            T::expected_type(),
+            index + 1,
+            value.type_as_string(),
+        ),
+    }
+}
+
+/// Converts a map's entry into the requested type
+///
+/// Missing entries are treated as `()`, so optional entries can be requested as `Option<T>`.
+pub fn expect_entry<T: FromValue>(
+    fn_name: &str,
+    map: &ValueMap,
+    key: &str,
+) -> Result<T, RuntimeError> {
+    let value = map.data().get_with_string(key).cloned();
+    let value = value.unwrap_or(Value::Empty);
+    match T::from_value(&value) {
+        Some(result) => Ok(result),
+        None => runtime_error!(
+            "{} - Expected {} for '{}', found {}",
+            fn_name,
+            T::expected_type(),
+            key,
+            value.type_as_string(),
+        ),
+    }
+}
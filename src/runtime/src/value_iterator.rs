@@ -1,6 +1,7 @@
 use {
     crate::{
-        Mutex, Num2, Num4, RuntimeError, Value, ValueList, ValueMap, ValueString, ValueTuple, Vm,
+        Mutex, Num2, Num4, RuntimeError, RuntimeResult, Value, ValueList, ValueMap, ValueNumber,
+        ValueString, ValueTuple, Vm,
     },
     std::{fmt, sync::Arc},
     unicode_segmentation::GraphemeCursor,
@@ -17,6 +18,24 @@ impl IntRange {
         self.start <= self.end
     }
 
+    /// Returns true if the number is one of the values produced by the range
+    ///
+    /// Descending ranges contain the values from `start` down to (but not including) `end`,
+    /// and non-integer numbers aren't contained in any range.
+    pub fn contains(&self, n: ValueNumber) -> bool {
+        let n = f64::from(n);
+        if n.fract() != 0.0 {
+            return false;
+        }
+
+        let (start, end) = (self.start as f64, self.end as f64);
+        if self.is_ascending() {
+            start <= n && n < end
+        } else {
+            end < n && n <= start
+        }
+    }
+
     fn len(&self) -> usize {
         if self.is_ascending() {
             (self.end - self.start) as usize
@@ -146,14 +165,7 @@ impl Iterator for ValueIteratorInternals {
                     None => None,
                 }
             }
-            Iterable::Generator(vm) => match vm.lock().continue_running() {
-                Ok(Value::Empty) => None,
-                Ok(Value::TemporaryTuple(_)) => {
-                    unreachable!("Yield shouldn't produce temporary tuples")
-                }
-                Ok(result) => Some(ValueIteratorOutput::Value(result)),
-                Err(error) => Some(ValueIteratorOutput::Error(error)),
-            },
+            Iterable::Generator(vm) => generator_output(vm.lock().continue_running()),
             Iterable::External(external_iterator) => external_iterator.lock().next(),
         }
     }
@@ -215,6 +227,19 @@ impl ValueIterator {
         Self::new(Iterable::External(Arc::new(Mutex::new(external))))
     }
 
+    /// Resumes a generator, with the value used as the result of the suspended `yield` expression
+    ///
+    /// The generator's next output is returned, with `None` returned if the generator has
+    /// finished. If the generator hasn't started yet, then the value is discarded.
+    ///
+    /// An error is returned if the iterator isn't a generator.
+    pub fn resume(&mut self, value: Value) -> Result<Option<ValueIteratorOutput>, RuntimeError> {
+        match &self.0.lock().iterable {
+            Iterable::Generator(vm) => Ok(generator_output(vm.lock().resume_generator(value))),
+            _ => crate::runtime_error!("Only generators can be resumed with a value"),
+        }
+    }
+
     // For internal functions that want to perform repeated iterations with a single lock
     pub fn lock_internals(
         &mut self,
@@ -224,6 +249,18 @@ impl ValueIterator {
     }
 }
 
+// Converts the result of running a generator VM into an iterator output
+fn generator_output(result: RuntimeResult) -> Option<ValueIteratorOutput> {
+    match result {
+        Ok(Value::Empty) => None,
+        Ok(Value::TemporaryTuple(_)) => {
+            unreachable!("Yield shouldn't produce temporary tuples")
+        }
+        Ok(result) => Some(ValueIteratorOutput::Value(result)),
+        Err(error) => Some(ValueIteratorOutput::Error(error)),
+    }
+}
+
 impl Iterator for ValueIterator {
     type Item = ValueIteratorOutput;
 
@@ -18,6 +18,14 @@ impl ValueKey {
     pub fn value(&self) -> &Value {
         &self.0
     }
+
+    // Returns the key with its string interned, see ValueString::interned
+    pub(crate) fn interned(self) -> Self {
+        match self.0 {
+            Value::Str(s) => Self(Value::Str(s.interned())),
+            _ => self,
+        }
+    }
 }
 
 impl PartialEq for ValueKey {
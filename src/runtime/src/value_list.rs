@@ -45,6 +45,21 @@ impl ValueList {
     pub fn data_mut(&self) -> RwLockWriteGuard<ValueVec> {
         self.0.write()
     }
+
+    // The number of lists that share the list's data
+    pub(crate) fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    // The address of the list's data, shared by lists that share the same data
+    pub(crate) fn instance_id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+
+    // A weak reference to the list's data, used by the garbage collector and the memory tracker
+    pub(crate) fn downgrade(&self) -> std::sync::Weak<RwLock<ValueVec>> {
+        Arc::downgrade(&self.0)
+    }
 }
 
 impl fmt::Display for ValueList {
@@ -79,6 +79,22 @@ impl DataMap {
         self.insert(id.into(), value)
     }
 
+    /// Inserts a key-value pair into the map, returning the previous value for the key
+    ///
+    /// String keys are interned, so that maps with repeated keys share the keys' storage.
+    #[inline]
+    pub fn insert(&mut self, key: ValueKey, value: Value) -> Option<Value> {
+        self.0.insert(key.interned(), value)
+    }
+
+    /// Inserts a key-value pair into the map without interning the key
+    ///
+    /// This is used for keys that are already shared, e.g. strings from a chunk's constants.
+    #[inline]
+    pub(crate) fn insert_shared(&mut self, key: ValueKey, value: Value) -> Option<Value> {
+        self.0.insert(key, value)
+    }
+
     #[inline]
     pub fn extend(&mut self, other: &DataMap) {
         self.0.extend(other.0.clone().into_iter());
@@ -90,6 +106,14 @@ impl DataMap {
         self.0.get(&key as &dyn ValueKeyRef)
     }
 
+    /// Returns the index and value of an entry without having to create a ValueString
+    #[inline]
+    pub fn get_full_with_string(&self, key: &str) -> Option<(usize, &Value)> {
+        self.0
+            .get_full(&key as &dyn ValueKeyRef)
+            .map(|(index, _, value)| (index, value))
+    }
+
     /// Allows access to map entries without having to create a ValueString
     #[inline]
     pub fn get_with_string_mut(&mut self, key: &str) -> Option<&mut Value> {
@@ -114,7 +138,9 @@ impl DerefMut for DataMap {
 impl FromIterator<(ValueKey, Value)> for DataMap {
     #[inline]
     fn from_iter<T: IntoIterator<Item = (ValueKey, Value)>>(iter: T) -> DataMap {
-        Self(DataMapType::from_iter(iter))
+        Self(DataMapType::from_iter(
+            iter.into_iter().map(|(key, value)| (key.interned(), value)),
+        ))
     }
 }
 
@@ -169,6 +195,26 @@ impl ValueMap {
         self.meta.write()
     }
 
+    // The number of maps that share the map's data
+    pub(crate) fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.data)
+    }
+
+    // Returns true if the two maps share the same data
+    pub(crate) fn is_same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+
+    // The address of the map's data, shared by maps that share the same data
+    pub(crate) fn instance_id(&self) -> usize {
+        Arc::as_ptr(&self.data) as *const () as usize
+    }
+
+    // A weak reference to the map's data, used by the garbage collector and the memory tracker
+    pub(crate) fn downgrade(&self) -> std::sync::Weak<RwLock<DataMap>> {
+        Arc::downgrade(&self.data)
+    }
+
     #[inline]
     pub fn insert(&mut self, key: ValueKey, value: Value) {
         self.data_mut().insert(key, value);
@@ -46,6 +46,11 @@ impl ValueNumber {
         }
     }
 
+    /// Returns true if the two numbers are within `allowed_diff` of each other
+    pub fn is_near(self, other: Self, allowed_diff: f64) -> bool {
+        f64_near(self.into(), other.into(), allowed_diff)
+    }
+
     pub fn is_nan(self) -> bool {
         match self {
             Self::F64(n) => n.is_nan(),
@@ -85,7 +90,9 @@ impl fmt::Display for ValueNumber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ValueNumber::F64(n) => {
-                if n.fract() > 0.0 {
+                // Floats are displayed using their shortest round-trippable representation,
+                // with integral values keeping a trailing `.0` to distinguish them from integers.
+                if n.fract() != 0.0 || !n.is_finite() {
                     write!(f, "{}", n)
                 } else {
                     write!(f, "{:.1}", n)
@@ -96,6 +103,16 @@ impl fmt::Display for ValueNumber {
     }
 }
 
+/// Returns true if the two f32 values are within `allowed_diff` of each other
+pub(crate) fn f32_near(a: f32, b: f32, allowed_diff: f32) -> bool {
+    (a - b).abs() <= allowed_diff
+}
+
+/// Returns true if the two f64 values are within `allowed_diff` of each other
+pub(crate) fn f64_near(a: f64, b: f64, allowed_diff: f64) -> bool {
+    (a - b).abs() <= allowed_diff
+}
+
 impl Hash for ValueNumber {
     fn hash<H: Hasher>(&self, state: &mut H) {
         state.write_u64(self.to_bits())
@@ -1,24 +1,87 @@
 use {
+    crate::Mutex,
     lazy_static::lazy_static,
+    rustc_hash::{FxHashSet, FxHasher},
     std::{
+        convert::TryFrom,
         fmt,
         hash::{Hash, Hasher},
+        num::NonZeroUsize,
         ops::{Deref, Range},
         sync::Arc,
     },
     unicode_segmentation::UnicodeSegmentation,
 };
 
+// The type used to store the offsets of a substring's bounds
+//
+// The offsets are stored with 1 added, so that the unused zero value allows StringBounds to be
+// stored without a separate flag.
+//
+// With the `compact_value` feature enabled the offsets are 32 bit, which reduces the size of
+// Value. Substrings with bounds that don't fit in 32 bits are copied into a new string.
+#[cfg(feature = "compact_value")]
+type StringOffset = std::num::NonZeroU32;
+#[cfg(not(feature = "compact_value"))]
+type StringOffset = NonZeroUsize;
+
+// Converts bounds within a string's data into offsets
+//
+// None is returned if the bounds don't fit in a StringOffset.
+#[allow(clippy::useless_conversion)]
+fn to_offsets(bounds: Range<usize>) -> Option<Range<StringOffset>> {
+    let to_offset = |index: usize| {
+        let offset = NonZeroUsize::new(index.checked_add(1)?)?;
+        StringOffset::try_from(offset).ok()
+    };
+    Some(to_offset(bounds.start)?..to_offset(bounds.end)?)
+}
+
+// The bounds of a ValueString within its data
+#[derive(Clone, Debug, PartialEq)]
+enum StringBounds {
+    // The string covers all of its data
+    //
+    // Interned strings are flagged so that they can be used as map keys without
+    // needing to be looked up in the interner again.
+    Full { interned: bool },
+    // The string is a substring of its data
+    Offsets(Range<StringOffset>),
+}
+
 #[derive(Clone)]
 pub struct ValueString {
     string: Arc<str>,
-    bounds: Range<usize>,
+    bounds: StringBounds,
 }
 
 impl ValueString {
     fn new(string: Arc<str>) -> Self {
-        let bounds = 0..string.len();
-        Self { string, bounds }
+        Self {
+            string,
+            bounds: StringBounds::Full { interned: false },
+        }
+    }
+
+    fn new_interned(string: Arc<str>) -> Self {
+        Self {
+            string,
+            bounds: StringBounds::Full { interned: true },
+        }
+    }
+
+    // Makes a string from validated bounds within the string's data
+    fn from_valid_bounds(string: Arc<str>, bounds: Range<usize>) -> Self {
+        if bounds == (0..string.len()) {
+            Self::new(string)
+        } else if let Some(offsets) = to_offsets(bounds.clone()) {
+            Self {
+                string,
+                bounds: StringBounds::Offsets(offsets),
+            }
+        } else {
+            Self::new(string[bounds].into())
+        }
     }
 
     pub fn empty() -> Self {
@@ -27,21 +90,19 @@ impl ValueString {
 
     pub fn new_with_bounds(string: Arc<str>, bounds: Range<usize>) -> Option<Self> {
         if string.get(bounds.clone()).is_some() {
-            Some(Self { string, bounds })
+            Some(Self::from_valid_bounds(string, bounds))
         } else {
             None
         }
     }
 
     pub fn with_bounds(&self, mut new_bounds: Range<usize>) -> Option<Self> {
-        new_bounds.end += self.bounds.start;
-        new_bounds.start += self.bounds.start;
-
-        if new_bounds.end <= self.bounds.end && self.string.get(new_bounds.clone()).is_some() {
-            Some(Self {
-                string: self.string.clone(),
-                bounds: new_bounds,
-            })
+        let bounds = self.bounds();
+        new_bounds.end += bounds.start;
+        new_bounds.start += bounds.start;
+
+        if new_bounds.end <= bounds.end && self.string.get(new_bounds.clone()).is_some() {
+            Some(Self::from_valid_bounds(self.string.clone(), new_bounds))
         } else {
             None
         }
@@ -100,13 +161,69 @@ impl ValueString {
     #[inline]
     pub fn as_str(&self) -> &str {
         // Safety: bounds have already been checked in new_with_bounds / with_bounds
-        unsafe { self.string.get_unchecked(self.bounds.clone()) }
+        unsafe { self.string.get_unchecked(self.bounds()) }
+    }
+
+    // The string's bounds within its data
+    #[inline]
+    #[allow(clippy::unnecessary_cast)]
+    fn bounds(&self) -> Range<usize> {
+        match &self.bounds {
+            StringBounds::Full { .. } => 0..self.string.len(),
+            StringBounds::Offsets(offsets) => {
+                offsets.start.get() as usize - 1..offsets.end.get() as usize - 1
+            }
+        }
+    }
+
+    // The number of strings that share the string's data
+    pub(crate) fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.string)
+    }
+
+    // A weak reference to the string's data, used by the memory tracker
+    pub(crate) fn downgrade(&self) -> std::sync::Weak<str> {
+        Arc::downgrade(&self.string)
+    }
+
+    // True if the string's data is the given string data, e.g. a chunk's constant string data
+    pub(crate) fn shares_data_with(&self, data: &Arc<str>) -> bool {
+        Arc::ptr_eq(&self.string, data)
+    }
+
+    // Returns a string with the same contents that shares its data with other interned strings
+    //
+    // Interning is used for map keys, so that maps with repeated keys don't store duplicate
+    // strings, and so that comparisons between keys can be made by pointer.
+    // Strings that have already been interned are returned without accessing the interner.
+    pub(crate) fn interned(self) -> Self {
+        if self.bounds == (StringBounds::Full { interned: true }) {
+            return self;
+        }
+
+        let mut interner = interner_shard(self.as_str()).lock();
+
+        if let Some(interned) = interner.strings.get(self.as_str()) {
+            return Self::new_interned(interned.clone());
+        }
+
+        let string: Arc<str> = match self.bounds {
+            StringBounds::Full { .. } => self.string,
+            StringBounds::Offsets(_) => self.as_str().into(),
+        };
+        interner.insert(string.clone());
+        Self::new_interned(string)
+    }
+
+    // True if the strings share the same data
+    fn is_same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.string, &other.string) && self.bounds() == other.bounds()
     }
 }
 
 impl PartialEq for ValueString {
     fn eq(&self, other: &Self) -> bool {
-        self.as_str() == other.as_str()
+        self.is_same_instance(other) || self.as_str() == other.as_str()
     }
 }
 impl Eq for ValueString {}
@@ -158,6 +275,128 @@ impl fmt::Display for ValueString {
     }
 }
 
+// The number of shards in the string interner
+//
+// Each shard has its own lock, which reduces contention when strings are interned by
+// multiple threads.
+const INTERNER_SHARD_COUNT: usize = 16;
+
 lazy_static! {
     static ref EMPTY_STRING: Arc<str> = Arc::from("");
+    static ref STRING_INTERNER: [Mutex<StringInterner>; INTERNER_SHARD_COUNT] = Default::default();
+}
+
+// Returns the interner shard that's used for the given string
+fn interner_shard(s: &str) -> &'static Mutex<StringInterner> {
+    let mut hasher = FxHasher::default();
+    s.hash(&mut hasher);
+    &STRING_INTERNER[hasher.finish() as usize % INTERNER_SHARD_COUNT]
+}
+
+// The minimum number of strings in an interner shard before unused strings are removed
+const MIN_INTERNER_PRUNE_LEN: usize = 128;
+
+// The strings that have been interned in a shard of the interner, see ValueString::interned
+//
+// Strings that are only referred to by the interner are removed each time the number of
+// interned strings reaches the prune threshold, which is then set to double the remaining count.
+struct StringInterner {
+    strings: FxHashSet<Arc<str>>,
+    prune_len: usize,
+}
+
+impl Default for StringInterner {
+    fn default() -> Self {
+        Self {
+            strings: FxHashSet::default(),
+            prune_len: MIN_INTERNER_PRUNE_LEN,
+        }
+    }
+}
+
+impl StringInterner {
+    fn insert(&mut self, string: Arc<str>) {
+        if self.strings.len() >= self.prune_len {
+            self.strings.retain(|s| Arc::strong_count(s) > 1);
+            self.prune_len = (self.strings.len() * 2).max(MIN_INTERNER_PRUNE_LEN);
+        }
+
+        self.strings.insert(string);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::DataMap};
+
+    #[test]
+    fn map_keys_share_interned_strings() {
+        let mut a = DataMap::new();
+        let mut b = DataMap::new();
+        a.insert(
+            ValueString::from("interned_key".to_string()).into(),
+            1.into(),
+        );
+        b.insert(
+            ValueString::from("interned_key".to_string()).into(),
+            2.into(),
+        );
+
+        let key_a = match a.keys().next().map(|key| key.value()) {
+            Some(crate::Value::Str(s)) => s.clone(),
+            _ => unreachable!(),
+        };
+        let key_b = match b.keys().next().map(|key| key.value()) {
+            Some(crate::Value::Str(s)) => s.clone(),
+            _ => unreachable!(),
+        };
+        assert!(key_a.is_same_instance(&key_b));
+    }
+
+    #[test]
+    fn interned_substrings_are_copied() {
+        let s = ValueString::from("abcdef_interned")
+            .with_bounds(1..4)
+            .unwrap();
+        let interned = s.clone().interned();
+        assert_eq!(interned.as_str(), "bcd");
+        assert_eq!(interned.string.len(), 3);
+        assert!(interned.is_same_instance(&ValueString::from("bcd").interned()));
+    }
+
+    #[test]
+    fn interned_strings_are_flagged() {
+        let interned = ValueString::from("flagged_interned".to_string()).interned();
+        assert_eq!(interned.bounds, StringBounds::Full { interned: true });
+        assert!(interned.clone().interned().is_same_instance(&interned));
+    }
+
+    #[test]
+    fn full_bounds_are_independent_of_offsets() {
+        let s = ValueString::from("abc");
+        assert_eq!(s.bounds, StringBounds::Full { interned: false });
+        assert_eq!(s.as_str(), "abc");
+
+        let full = s.with_bounds(0..3).unwrap();
+        assert_eq!(full.bounds, StringBounds::Full { interned: false });
+        assert!(full.is_same_instance(&s));
+
+        let substring = s.with_bounds(1..3).unwrap();
+        assert_eq!(substring.as_str(), "bc");
+        assert_eq!(substring.with_bounds(0..2).unwrap().as_str(), "bc");
+
+        let empty = s.with_bounds(3..3).unwrap();
+        assert_eq!(empty.as_str(), "");
+        assert!(matches!(empty.bounds, StringBounds::Offsets(_)));
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_cast)]
+    fn offsets_are_available_up_to_the_maximum() {
+        let max = StringOffset::MAX.get() as usize - 1;
+        let offsets = to_offsets(max - 1..max).unwrap();
+        assert_eq!(offsets.start.get() as usize, max);
+        assert_eq!(offsets.end.get() as usize, max + 1);
+        assert!(to_offsets(0..max + 1).is_none());
+    }
 }
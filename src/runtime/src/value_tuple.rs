@@ -11,6 +11,21 @@ impl ValueTuple {
     pub fn data(&self) -> &[Value] {
         &self.0
     }
+
+    // The number of tuples that share the tuple's data
+    pub(crate) fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    // The address of the tuple's data, shared by tuples that share the same data
+    pub(crate) fn instance_id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+
+    // A weak reference to the tuple's data, used by the memory tracker
+    pub(crate) fn downgrade(&self) -> std::sync::Weak<[Value]> {
+        Arc::downgrade(&self.0)
+    }
 }
 
 impl Default for ValueTuple {
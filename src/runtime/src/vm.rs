@@ -1,30 +1,78 @@
 use {
     crate::{
+        access_cache::AccessCache,
         core::CoreLib,
+        coverage::CoverageRecorder,
+        debugger::{Breakpoint, DebugFrame, DebugHook},
         external::{self, Args, ExternalFunction},
         frame::Frame,
+        memory::{self, MemoryTracker, MAP_ENTRY_SIZE, VALUE_SIZE},
         meta_map::meta_id_to_key,
-        num2, num4, runtime_error,
-        value::{self, FunctionInfo, RegisterSlice, SimpleFunctionInfo},
+        module_version::{Version, VersionRequirement},
+        num2, num4,
+        profiler::{FrameProfile, Profiler},
+        runtime_error,
+        value::{self, FunctionInfo, FunctionIp, RegisterSlice, SimpleFunctionInfo},
         value_iterator::{IntRange, ValueIterator, ValueIteratorOutput},
-        BinaryOp, DefaultStderr, DefaultStdin, DefaultStdout, KotoFile, Loader, MetaKey,
-        RuntimeError, RuntimeErrorType, RuntimeResult, RwLock, RwLockReadGuard, RwLockWriteGuard,
-        UnaryOp, Value, ValueKey, ValueList, ValueMap, ValueNumber, ValueString, ValueTuple,
-        ValueVec,
+        BinaryOp, ChunkCoverage, DefaultClock, DefaultStderr, DefaultStdin, DefaultStdout,
+        ImportPolicy, KotoClock, KotoFile, Loader, LoaderWarning, MetaKey, ModuleResolver, Mutex,
+        Pending, Profile, RuntimeError, RuntimeErrorType, RuntimeResult, RwLock, RwLockReadGuard,
+        RwLockWriteGuard, SandboxSettings, TestOutcome, TestResult, UnaryOp, Value, ValueKey,
+        ValueList, ValueMap, ValueNumber, ValueString, ValueTuple, ValueVec,
     },
-    koto_bytecode::{Chunk, Instruction, InstructionReader, TypeId},
-    koto_parser::{ConstantIndex, MetaKeyId},
+    koto_bytecode::{
+        Chunk, CompilerSettings, Instruction, InstructionReader, JumpTableKey, TypeId,
+        MAX_SIZE_HINT,
+    },
+    koto_parser::{ConstantIndex, MetaKeyId, Span},
     std::{
-        collections::HashMap,
+        cell::Cell,
+        collections::{HashMap, HashSet},
         fmt,
         path::PathBuf,
         sync::{
-            atomic::{AtomicBool, Ordering},
-            Arc,
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            mpsc, Arc,
         },
+        time::Duration,
     },
 };
 
+#[cfg(feature = "cycle_detection")]
+use crate::cycles::{CycleFinder, ReferenceCycle};
+#[cfg(feature = "gc")]
+use crate::gc::GarbageCollector;
+
+// The default values for VmSettings::max_call_depth and VmSettings::max_nested_call_depth
+const DEFAULT_MAX_CALL_DEPTH: usize = 200_000;
+const DEFAULT_MAX_NESTED_CALL_DEPTH: usize = 200;
+
+thread_local! {
+    // The number of nested calls to Vm::execute_instructions on the current thread,
+    // see VmSettings::max_nested_call_depth
+    static NESTED_CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// Tracks a nested call to Vm::execute_instructions, decrementing the depth when dropped
+struct NestedCall;
+
+impl NestedCall {
+    fn enter() -> Self {
+        NESTED_CALL_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        Self
+    }
+
+    fn depth() -> usize {
+        NESTED_CALL_DEPTH.with(|depth| depth.get())
+    }
+}
+
+impl Drop for NestedCall {
+    fn drop(&mut self) {
+        NESTED_CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
 macro_rules! call_binary_op_or_else {
     ($vm:expr,
      $result_register:expr,
@@ -56,6 +104,69 @@ pub enum ControlFlow {
 // Instructions will place their results in registers, there's no Ok type
 pub type InstructionResult = Result<(), RuntimeError>;
 
+/// The result of running a VM until execution is suspended, see [Vm::step] and [Vm::run_until]
+#[derive(Clone, Debug)]
+pub enum StepResult {
+    /// Execution was suspended, and continues with the next call to [Vm::step] or [Vm::run_until]
+    Continue,
+    /// A value was yielded, execution continues after the yield with the next call to [Vm::step]
+    Yielded(Value),
+    /// Execution has finished, with the resulting value
+    Finished(Value),
+    /// A runtime error was thrown that wasn't caught, execution can't be continued
+    Error(RuntimeError),
+}
+
+/// A handle that allows a VM to be interrupted from another thread, see [Vm::interrupt_handle]
+#[derive(Clone, Debug, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+    // VMs using the handle are also interrupted by the parent, see Vm::call_with_timeout
+    parent: Option<Box<InterruptHandle>>,
+}
+
+impl InterruptHandle {
+    /// Interrupts VMs that are using the handle
+    ///
+    /// Execution stops before the next instruction with a [RuntimeErrorType::Interrupted] error.
+    /// VMs will continue to be interrupted until [InterruptHandle::reset] is called.
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Allows VMs that are using the handle to run again after being interrupted
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    /// True if [InterruptHandle::interrupt] has been called since the handle was last reset
+    pub fn is_interrupted(&self) -> bool {
+        self.is_interrupted_directly()
+            || matches!(&self.parent, Some(parent) if parent.is_interrupted())
+    }
+
+    // Returns a new handle that's also interrupted when this handle is interrupted
+    fn child(&self) -> Self {
+        Self {
+            flag: Default::default(),
+            parent: Some(Box::new(self.clone())),
+        }
+    }
+
+    // True if the handle itself has been interrupted, ignoring the parent
+    fn is_interrupted_directly(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
+
+// The reason for execute_instructions_until to stop executing instructions
+enum ExecutionEnd {
+    Returned(Value),
+    Yielded(Value),
+    // The suspension condition was met
+    Suspended,
+}
+
 /// Context shared by all VMs across modules
 struct SharedContext {
     pub prelude: ValueMap,
@@ -63,7 +174,42 @@ struct SharedContext {
     stdin: Arc<dyn KotoFile>,
     stdout: Arc<dyn KotoFile>,
     stderr: Arc<dyn KotoFile>,
+    clock: Arc<dyn KotoClock>,
     run_import_tests: bool,
+    isolate_tests: bool,
+    parallel_tests: bool,
+    update_snapshots: bool,
+    float_epsilon: f64,
+    approximate_num_equality: bool,
+    check_type_annotations: bool,
+    eliminate_tail_calls: bool,
+    test_results: Mutex<Vec<TestResult>>,
+    coverage: Mutex<CoverageRecorder>,
+    // The number of instructions that can still be executed, see VmSettings::instruction_budget
+    instruction_budget: Option<AtomicU64>,
+    // The memory that has been allocated for values, see VmSettings::memory_limit
+    memory: Option<MemoryTracker>,
+    // see VmSettings::max_call_depth and VmSettings::max_nested_call_depth
+    max_call_depth: Option<usize>,
+    max_nested_call_depth: Option<usize>,
+    // Callbacks for following execution, see VmSettings::debug_hook
+    debug_hook: Option<Arc<dyn DebugHook>>,
+    // The breakpoints that are reported to the debug hook, see Vm::add_breakpoint
+    breakpoints: RwLock<HashSet<Breakpoint>>,
+    // The statistics of called functions, see VmSettings::profile
+    profiler: Option<Mutex<Profiler>>,
+    // The interval between traced function calls, see VmSettings::trace_sample_interval
+    #[cfg(feature = "tracing")]
+    trace_sample_interval: u64,
+    // The number of function calls that have been made, used to sample the traced calls
+    #[cfg(feature = "tracing")]
+    call_count: AtomicU64,
+    // see VmSettings::break_cycles_on_drop
+    #[cfg(feature = "cycle_detection")]
+    break_cycles_on_drop: bool,
+    // Frees reference cycles, see VmSettings::gc_interval
+    #[cfg(feature = "gc")]
+    gc: Option<GarbageCollector>,
 }
 
 impl Default for SharedContext {
@@ -77,6 +223,7 @@ impl SharedContext {
         let core_lib = CoreLib::default();
 
         let mut prelude = ValueMap::default();
+        prelude.add_map("coroutine", core_lib.coroutine.clone());
         prelude.add_map("io", core_lib.io.clone());
         prelude.add_map("iterator", core_lib.iterator.clone());
         prelude.add_map("koto", core_lib.koto.clone());
@@ -89,6 +236,7 @@ impl SharedContext {
         prelude.add_map("test", core_lib.test.clone());
         prelude.add_map("thread", core_lib.thread.clone());
         prelude.add_map("tuple", core_lib.tuple.clone());
+        settings.sandbox.apply(&prelude);
 
         Self {
             prelude,
@@ -96,7 +244,34 @@ impl SharedContext {
             stdin: settings.stdin,
             stdout: settings.stdout,
             stderr: settings.stderr,
+            clock: settings.clock,
             run_import_tests: settings.run_import_tests,
+            isolate_tests: settings.isolate_tests,
+            parallel_tests: settings.parallel_tests,
+            update_snapshots: settings.update_snapshots,
+            float_epsilon: settings.float_epsilon,
+            approximate_num_equality: settings.approximate_num_equality,
+            check_type_annotations: settings.check_type_annotations,
+            eliminate_tail_calls: settings.eliminate_tail_calls,
+            test_results: Mutex::default(),
+            coverage: Mutex::default(),
+            instruction_budget: settings.instruction_budget.map(AtomicU64::new),
+            memory: settings.memory_limit.map(MemoryTracker::new),
+            max_call_depth: settings.max_call_depth,
+            max_nested_call_depth: settings.max_nested_call_depth,
+            debug_hook: settings.debug_hook,
+            breakpoints: RwLock::default(),
+            profiler: settings.profile.then(Mutex::default),
+            #[cfg(feature = "tracing")]
+            trace_sample_interval: settings.trace_sample_interval,
+            #[cfg(feature = "tracing")]
+            call_count: AtomicU64::new(0),
+            #[cfg(feature = "cycle_detection")]
+            break_cycles_on_drop: settings.break_cycles_on_drop,
+            #[cfg(feature = "gc")]
+            gc: settings
+                .gc_interval
+                .map(|interval| GarbageCollector::new(interval.max(1))),
         }
     }
 }
@@ -121,8 +296,21 @@ impl ModuleContext {
         }
     }
 
+    /// Returns the compiler warnings that were produced while importing modules
+    pub fn take_warnings(&mut self) -> Vec<LoaderWarning> {
+        self.loader.take_warnings()
+    }
+
     fn reset(&mut self) {
+        let cache_dir = self.loader.cache_dir().cloned();
+        let compiler_settings = self.loader.compiler_settings();
+        let module_resolver = self.loader.module_resolver().cloned();
+        let filesystem_imports = self.loader.filesystem_imports();
         self.loader = Default::default();
+        self.loader.set_cache_dir(cache_dir);
+        self.loader.set_compiler_settings(compiler_settings);
+        self.loader.set_module_resolver(module_resolver);
+        self.loader.set_filesystem_imports(filesystem_imports);
         self.stop_spawned_vms();
     }
 
@@ -144,7 +332,127 @@ pub struct VmSettings {
     pub stdin: Arc<dyn KotoFile>,
     pub stdout: Arc<dyn KotoFile>,
     pub stderr: Arc<dyn KotoFile>,
+    /// The source of time used by the core library, e.g. by `os.time` and `thread.sleep`
+    ///
+    /// The clock is shared with VMs that are spawned from the VM. Timeouts (see
+    /// [Vm::run_with_timeout]) and profiling always use the system time.
+    pub clock: Arc<dyn KotoClock>,
     pub run_import_tests: bool,
+    /// When enabled, each of a module's tests is run with a fresh instance of the module
+    ///
+    /// See [Vm::run_module_tests].
+    pub isolate_tests: bool,
+    /// When enabled along with `isolate_tests`, a module's tests are run in parallel
+    pub parallel_tests: bool,
+    /// When enabled, snapshots checked with `test.snapshot` are updated rather than compared
+    pub update_snapshots: bool,
+    /// The allowed difference used when comparing floats approximately
+    ///
+    /// This is used by `assert_near` when no allowed difference is provided,
+    /// and by num2/num4 equality checks when `approximate_num_equality` is enabled.
+    pub float_epsilon: f64,
+    /// When enabled, num2 and num4 components are compared using `float_epsilon`
+    pub approximate_num_equality: bool,
+    /// When enabled, the annotated types of function arguments and return values are checked
+    pub check_type_annotations: bool,
+    /// When enabled, calls in return position replace the calling function's frame
+    ///
+    /// This allows recursive functions to run in constant stack space, with the tradeoff that
+    /// errors thrown from the called function won't include the replaced frames in their traces.
+    pub eliminate_tail_calls: bool,
+    /// The directory used to cache compiled modules, see [Loader::set_cache_dir]
+    pub module_cache_dir: Option<PathBuf>,
+    /// The settings used when compiling imported modules
+    pub compiler_settings: CompilerSettings,
+    /// The resolver used to find imported modules, see [Loader::set_module_resolver]
+    ///
+    /// When no resolver is set, modules are loaded from the filesystem.
+    pub module_resolver: Option<Arc<dyn ModuleResolver>>,
+    /// Restrictions on the core library that's available to scripts
+    ///
+    /// Core modules and functions can be excluded or replaced with stubs, allowing untrusted
+    /// scripts to be run with a limited set of capabilities, see [SandboxSettings::untrusted].
+    pub sandbox: SandboxSettings,
+    /// The maximum number of instructions that can be executed, or `None` for no limit
+    ///
+    /// Once the budget has been exhausted, execution stops with a
+    /// [RuntimeErrorType::InstructionBudgetExhausted] error, which can't be caught by scripts.
+    /// The budget is shared with VMs that are spawned from the VM, e.g. by `thread.create`.
+    /// The remaining budget can be updated with [Vm::set_instruction_budget].
+    pub instruction_budget: Option<u64>,
+    /// The approximate number of bytes that can be allocated for values, or `None` for no limit
+    ///
+    /// The sizes of new strings, lists, tuples, and maps are tracked as they're created, along
+    /// with the growth of lists and maps that are passed to external functions. Contained values
+    /// and most bookkeeping overhead aren't included, so the actual memory usage will be higher.
+    ///
+    /// Dropped values are released from the usage when the limit would otherwise be exceeded,
+    /// at which point the usage is recalculated from the tracked values that are still alive,
+    /// so the limit bounds the memory that's in use rather than the total amount of memory that's
+    /// allocated while running scripts.
+    ///
+    /// Once the limit has been exceeded, execution stops with a
+    /// [RuntimeErrorType::MemoryLimitExceeded] error, which can't be caught by scripts.
+    /// The limit is shared with VMs that are spawned from the VM.
+    pub memory_limit: Option<usize>,
+    /// The maximum number of frames that can be on the call stack, or `None` for no limit
+    ///
+    /// Calling a function when the limit has been reached produces a
+    /// [RuntimeErrorType::CallDepthExceeded] error, which can be caught by scripts. The error's
+    /// trace only includes the deepest frames of the call stack, so that scripts with infinite
+    /// recursion fail with a concise error rather than exhausting the available memory.
+    ///
+    /// Functions that are run by VMs spawned from the VM (e.g. by iterator adaptors, or by
+    /// imported modules) count towards the depth of the VM that spawned them, with the
+    /// exception of VMs that run on other threads.
+    ///
+    /// The default limit is 200,000 frames.
+    pub max_call_depth: Option<usize>,
+    /// The maximum number of nested calls to Koto functions from external functions,
+    /// or `None` for no limit
+    ///
+    /// Koto functions that are called from external functions (e.g. the function passed to
+    /// `list.each`, or an overloaded operator) are run with a nested call to the VM, which uses
+    /// space on the thread's native stack. Calling a function when the limit has been reached
+    /// produces a [RuntimeErrorType::NestedCallDepthExceeded] error, preventing recursion through
+    /// external functions from overflowing the native stack.
+    ///
+    /// The depth is counted for each thread, including calls made by other VMs on the same
+    /// thread. The default limit is 200, which might need to be lowered for threads with
+    /// small stacks.
+    pub max_nested_call_depth: Option<usize>,
+    /// Callbacks that are used to follow execution, e.g. by a debugger
+    ///
+    /// The hook is shared with VMs that are spawned from the VM, and has a small cost for each
+    /// executed instruction, so it should only be set while debugging.
+    pub debug_hook: Option<Arc<dyn DebugHook>>,
+    /// When enabled, the calls, timings, and allocations of each Koto function are recorded
+    ///
+    /// The recorded profile is shared with VMs that are spawned from the VM, and can be
+    /// retrieved with [Vm::take_profile]. Profiling adds overhead to each function call,
+    /// so it should only be enabled while investigating a script's performance.
+    pub profile: bool,
+    /// The interval between the calls to Koto functions that are traced with `tracing` spans
+    ///
+    /// A `koto.call` span is emitted for one in every `n` calls, with `0` disabling call spans.
+    /// This is only used when the runtime's `tracing` feature is enabled.
+    pub trace_sample_interval: u64,
+    /// When enabled, reference cycles are broken when the last VM that shares the settings is
+    /// dropped, see [Vm::break_reference_cycles]
+    ///
+    /// This is only used when the runtime's `cycle_detection` feature is enabled.
+    pub break_cycles_on_drop: bool,
+    /// When set, reference cycles are freed by a garbage collector
+    ///
+    /// Values are still reference counted, with the lists, maps, and function captures that are
+    /// created by the VM being tracked by the collector. A collection is run each time the given
+    /// number of values have been tracked, and collections can also be run with
+    /// [Vm::collect_garbage]. Cycles are also collected when the last VM that shares the settings
+    /// is dropped.
+    ///
+    /// The collector is shared with VMs that are spawned from the VM.
+    /// This is only used when the runtime's `gc` feature is enabled.
+    pub gc_interval: Option<usize>,
 }
 
 impl Default for VmSettings {
@@ -153,7 +461,28 @@ impl Default for VmSettings {
             stdin: Arc::new(DefaultStdin::default()),
             stdout: Arc::new(DefaultStdout::default()),
             stderr: Arc::new(DefaultStderr::default()),
+            clock: Arc::new(DefaultClock::default()),
             run_import_tests: true,
+            isolate_tests: true,
+            parallel_tests: false,
+            update_snapshots: false,
+            float_epsilon: 1.0e-12,
+            approximate_num_equality: false,
+            check_type_annotations: false,
+            eliminate_tail_calls: false,
+            module_cache_dir: None,
+            compiler_settings: CompilerSettings::default(),
+            module_resolver: None,
+            sandbox: SandboxSettings::default(),
+            instruction_budget: None,
+            memory_limit: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_nested_call_depth: Some(DEFAULT_MAX_NESTED_CALL_DEPTH),
+            debug_hook: None,
+            profile: false,
+            trace_sample_interval: 100,
+            break_cycles_on_drop: false,
+            gc_interval: None,
         }
     }
 }
@@ -166,6 +495,35 @@ pub struct Vm {
     value_stack: Vec<Value>,
     call_stack: Vec<Frame>,
     stop_flag: Option<Arc<AtomicBool>>,
+    // Allows execution to be interrupted with an error that can't be caught,
+    // see Vm::interrupt_handle and call_with_timeout
+    interrupt_handle: InterruptHandle,
+    // The position of the most recent statement that was reported to the debug hook,
+    // as the chunk's address, the statement's line, and the call depth
+    debug_position: Option<(usize, u32, usize)>,
+    // Cached map entry positions for Access instructions
+    access_cache: AccessCache,
+    // True while instructions are being executed by Vm::run_async,
+    // allowing async external functions to suspend execution
+    running_async: bool,
+    // The number of external function calls that are in progress
+    external_call_depth: usize,
+    // The call depth of the VM that spawned this VM, see VmSettings::max_call_depth
+    base_call_depth: usize,
+    // The result register of an async call that has suspended execution, see Vm::run_async
+    suspended_call: Option<u8>,
+    // An error returned by an async call, which is thrown when execution continues
+    async_call_error: Option<RuntimeError>,
+    // The register of the most recently yielded value, which receives a coroutine's resumed value
+    yield_register: Option<u8>,
+    // Replaces the shared stdout and stderr during a call, see Vm::run_function_with_output
+    redirected_output: Option<RedirectedOutput>,
+}
+
+#[derive(Clone)]
+struct RedirectedOutput {
+    stdout: Arc<dyn KotoFile>,
+    stderr: Arc<dyn KotoFile>,
 }
 
 impl Default for Vm {
@@ -176,13 +534,37 @@ impl Default for Vm {
 
 impl Vm {
     pub fn with_settings(settings: VmSettings) -> Self {
+        let mut context = ModuleContext::default();
+        context
+            .loader
+            .set_cache_dir(settings.module_cache_dir.clone());
+        context
+            .loader
+            .set_compiler_settings(settings.compiler_settings);
+        context
+            .loader
+            .set_module_resolver(settings.module_resolver.clone());
+        context
+            .loader
+            .set_filesystem_imports(settings.sandbox.imports == ImportPolicy::Unrestricted);
+
         Self {
-            context: Arc::new(RwLock::new(ModuleContext::default())),
+            context: Arc::new(RwLock::new(context)),
             context_shared: Arc::new(SharedContext::with_settings(settings)),
             reader: InstructionReader::default(),
             value_stack: Vec::with_capacity(32),
             call_stack: vec![],
             stop_flag: None,
+            interrupt_handle: InterruptHandle::default(),
+            debug_position: None,
+            access_cache: AccessCache::default(),
+            running_async: false,
+            external_call_depth: 0,
+            base_call_depth: 0,
+            suspended_call: None,
+            async_call_error: None,
+            yield_register: None,
+            redirected_output: None,
         }
     }
 
@@ -194,9 +576,28 @@ impl Vm {
             value_stack: Vec::with_capacity(32),
             call_stack: vec![],
             stop_flag: None,
+            interrupt_handle: self.interrupt_handle.clone(),
+            debug_position: None,
+            access_cache: AccessCache::default(),
+            running_async: false,
+            external_call_depth: 0,
+            base_call_depth: self.total_call_depth(),
+            suspended_call: None,
+            async_call_error: None,
+            yield_register: None,
+            redirected_output: self.redirected_output.clone(),
         }
     }
 
+    /// Spawns a VM that shares this VM's context
+    ///
+    /// The spawned VM has its own value and call stacks, and shares its exports, prelude, and
+    /// settings with this VM. This makes it useful for calling Koto functions outside of the
+    /// external function that received them, e.g. when a callback is stored by an external
+    /// function to be called later by the host application.
+    ///
+    /// Spawned VMs can be sent to other threads. See [Vm::run_function] for information about
+    /// calling functions.
     pub fn spawn_shared_vm(&self) -> Self {
         Self {
             context: self.context.clone(),
@@ -205,6 +606,16 @@ impl Vm {
             value_stack: Vec::with_capacity(8),
             call_stack: vec![],
             stop_flag: None,
+            interrupt_handle: self.interrupt_handle.clone(),
+            debug_position: None,
+            access_cache: AccessCache::default(),
+            running_async: false,
+            external_call_depth: 0,
+            base_call_depth: self.total_call_depth(),
+            suspended_call: None,
+            async_call_error: None,
+            yield_register: None,
+            redirected_output: self.redirected_output.clone(),
         }
     }
 
@@ -221,6 +632,16 @@ impl Vm {
             value_stack: Vec::with_capacity(8),
             call_stack: vec![],
             stop_flag: Some(stop_flag),
+            interrupt_handle: self.interrupt_handle.clone(),
+            debug_position: None,
+            access_cache: AccessCache::default(),
+            running_async: false,
+            external_call_depth: 0,
+            base_call_depth: 0,
+            suspended_call: None,
+            async_call_error: None,
+            yield_register: None,
+            redirected_output: self.redirected_output.clone(),
         }
     }
 
@@ -244,13 +665,209 @@ impl Vm {
     }
 
     /// The stdout wrapper used by the VM
+    ///
+    /// While a call is being made with [Vm::run_function_with_output], the call's stdout is
+    /// returned.
     pub fn stdout(&self) -> &Arc<dyn KotoFile> {
-        &self.context_shared.stdout
+        match &self.redirected_output {
+            Some(output) => &output.stdout,
+            None => &self.context_shared.stdout,
+        }
     }
 
     /// The stderr wrapper used by the VM
+    ///
+    /// While a call is being made with [Vm::run_function_with_output], the call's stderr is
+    /// returned.
     pub fn stderr(&self) -> &Arc<dyn KotoFile> {
-        &self.context_shared.stderr
+        match &self.redirected_output {
+            Some(output) => &output.stderr,
+            None => &self.context_shared.stderr,
+        }
+    }
+
+    /// The clock used by the VM, see [VmSettings::clock]
+    pub fn clock(&self) -> &Arc<dyn KotoClock> {
+        &self.context_shared.clock
+    }
+
+    /// The allowed difference used when comparing floats approximately
+    pub fn float_epsilon(&self) -> f64 {
+        self.context_shared.float_epsilon
+    }
+
+    /// Returns the results of the tests that have been run since the last call
+    ///
+    /// Results are collected from all of the VMs that share this VM's settings,
+    /// so the results for tests in imported modules are included.
+    pub fn take_test_results(&self) -> Vec<TestResult> {
+        std::mem::take(&mut *self.context_shared.test_results.lock())
+    }
+
+    /// Returns the coverage that was recorded while running chunks compiled with coverage points
+    ///
+    /// See [CompilerSettings::coverage]. The recorded coverage is cleared, and is shared between
+    /// VMs that were spawned from the same VM.
+    pub fn take_coverage(&self) -> Vec<ChunkCoverage> {
+        self.context_shared.coverage.lock().take()
+    }
+
+    /// Returns the profile that was recorded while running functions
+    ///
+    /// An empty profile is returned if profiling isn't enabled, see [VmSettings::profile].
+    /// The recorded profile is cleared, and is shared between VMs that were spawned from the
+    /// same VM.
+    pub fn take_profile(&self) -> Profile {
+        match &self.context_shared.profiler {
+            Some(profiler) => profiler.lock().take(),
+            None => Profile::default(),
+        }
+    }
+
+    /// Returns a handle that can be used to interrupt the VM from another thread
+    ///
+    /// Once [InterruptHandle::interrupt] has been called, execution stops before the next
+    /// instruction with a [RuntimeErrorType::Interrupted] error, which can't be caught by scripts.
+    /// The handle is shared with VMs that are spawned from the VM, e.g. by `thread.create`.
+    ///
+    /// Calls to external functions aren't interrupted, so a long-running call will delay the
+    /// interruption until it returns.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt_handle.clone()
+    }
+
+    /// Adds a breakpoint that will be reported to the VM's debug hook
+    ///
+    /// Breakpoints are shared with VMs that are spawned from the VM. Breakpoints only have an
+    /// effect when a debug hook has been provided, see [VmSettings::debug_hook].
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) {
+        self.context_shared.breakpoints.write().insert(breakpoint);
+    }
+
+    /// Removes a breakpoint, returning true if the breakpoint had been added
+    pub fn remove_breakpoint(&self, breakpoint: &Breakpoint) -> bool {
+        self.context_shared.breakpoints.write().remove(breakpoint)
+    }
+
+    /// Returns the number of instructions that can still be executed
+    ///
+    /// `None` is returned if the VM was created without an instruction budget,
+    /// see [VmSettings::instruction_budget].
+    pub fn instruction_budget(&self) -> Option<u64> {
+        self.context_shared
+            .instruction_budget
+            .as_ref()
+            .map(|remaining| remaining.load(Ordering::Relaxed))
+    }
+
+    /// Sets the number of instructions that can still be executed
+    ///
+    /// This allows execution to continue after the budget has been exhausted.
+    /// The budget can only be set if the VM was created with an instruction budget,
+    /// see [VmSettings::instruction_budget], otherwise this has no effect.
+    pub fn set_instruction_budget(&self, budget: u64) {
+        if let Some(remaining) = &self.context_shared.instruction_budget {
+            remaining.store(budget, Ordering::Relaxed);
+        }
+    }
+
+    // Consumes one instruction from the instruction budget,
+    // returning false if the budget has been exhausted
+    fn consume_instruction_budget(&self) -> bool {
+        match &self.context_shared.instruction_budget {
+            Some(remaining) => remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok(),
+            None => true,
+        }
+    }
+
+    /// Returns the approximate number of bytes that have been allocated for values
+    ///
+    /// `None` is returned if the VM was created without a memory limit,
+    /// see [VmSettings::memory_limit].
+    pub fn memory_usage(&self) -> Option<usize> {
+        self.context_shared
+            .memory
+            .as_ref()
+            .map(MemoryTracker::usage)
+    }
+
+    /// Sets the number of bytes that are considered to have been allocated for values
+    ///
+    /// This can be used to reset the usage between runs, with the values that were previously
+    /// tracked no longer being counted. The usage can only be set if the VM was created with a
+    /// memory limit, see [VmSettings::memory_limit], otherwise this has no effect.
+    pub fn set_memory_usage(&self, usage: usize) {
+        if let Some(memory) = &self.context_shared.memory {
+            memory.set_usage(usage);
+        }
+    }
+
+    /// Adds an allocation to the VM's memory usage
+    ///
+    /// External functions that allocate memory for their own use can call this to have the
+    /// allocation counted against the VM's memory limit, see [VmSettings::memory_limit].
+    /// An error is returned if the limit would be exceeded.
+    ///
+    /// The allocation is counted until the usage is next recalculated from the values that are
+    /// still alive, which happens when the limit would otherwise be exceeded.
+    pub fn track_allocation(&self, size: usize) -> Result<(), RuntimeError> {
+        if let (Some(profiler), Some(frame)) =
+            (&self.context_shared.profiler, self.call_stack.last())
+        {
+            if let Some(profile) = frame.profile.as_ref().filter(|_| size > 0) {
+                profiler
+                    .lock()
+                    .record_allocation(&frame.chunk, profile.function_ip, size);
+            }
+        }
+
+        match &self.context_shared.memory {
+            Some(memory) if !memory.allocate(size) => Err(RuntimeError::new(
+                RuntimeErrorType::MemoryLimitExceeded(memory.limit()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Starts tracking a new value with the memory tracker, see VmSettings::memory_limit
+    //
+    // The value's size should already have been added with track_allocation, tracking the value
+    // allows its size to be released once it has been dropped.
+    #[inline]
+    fn track_value_memory(&self, value: &Value) -> Result<(), RuntimeError> {
+        match &self.context_shared.memory {
+            Some(memory) if !memory.track_value(value) => Err(RuntimeError::new(
+                RuntimeErrorType::MemoryLimitExceeded(memory.limit()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // The combined allocation sizes of the arguments for an external call,
+    // used to find the growth of containers during the call
+    fn args_allocation_size(&self, args: &Args) -> usize {
+        self.register_slice(args.register, args.count)
+            .iter()
+            .map(memory::allocation_size)
+            .sum()
+    }
+
+    // Starts tracking a newly created value with the garbage collector, see VmSettings::gc_interval
+    #[inline]
+    fn track_with_gc(&self, _value: &Value) {
+        #[cfg(feature = "gc")]
+        if let Some(gc) = &self.context_shared.gc {
+            if gc.track(_value) {
+                gc.collect();
+            }
+        }
+    }
+
+    /// True if snapshots should be updated rather than compared, see [VmSettings::update_snapshots]
+    pub fn update_snapshots(&self) -> bool {
+        self.context_shared.update_snapshots
     }
 
     pub fn get_exported_value(&self, id: &str) -> Option<Value> {
@@ -275,6 +892,156 @@ impl Vm {
         self.execute_instructions()
     }
 
+    /// Runs a chunk, suspending execution while async external functions are pending
+    ///
+    /// External functions can return a [Pending] value to complete asynchronously, in which case
+    /// the calling frame is suspended until the pending future completes, with the future's output
+    /// used as the result of the call. Errors returned by the future are thrown from the call, and
+    /// can be caught by the script.
+    ///
+    /// Execution can only be suspended while the script's own instructions are being executed,
+    /// so a runtime error is thrown if an async function is called by an external function,
+    /// e.g. in a function that's passed to `list.each`. Async functions also can't be called
+    /// when the VM is run synchronously, e.g. with [Vm::run] or [Vm::step].
+    pub async fn run_async(&mut self, chunk: Arc<Chunk>) -> RuntimeResult {
+        self.push_frame(chunk, 0, 0);
+        self.execute_instructions_async().await
+    }
+
+    /// Runs a chunk, interrupting execution if it doesn't finish within the timeout
+    ///
+    /// Once the timeout has elapsed, execution is stopped before the next instruction with a
+    /// [RuntimeErrorType::TimedOut] error, which can't be caught by scripts. Calls to external
+    /// functions aren't interrupted, so a long-running call will delay the timeout until it
+    /// returns.
+    ///
+    /// The timeout is measured by a watchdog thread, so on wasm32 the chunk is run without a
+    /// timeout, see [VmSettings::instruction_budget] for an alternative.
+    pub fn run_with_timeout(&mut self, chunk: Arc<Chunk>, timeout: Duration) -> RuntimeResult {
+        match self.call_with_timeout(Some(timeout), |vm| vm.run(chunk)) {
+            (Err(error), true) => Err(RuntimeError {
+                error: RuntimeErrorType::TimedOut(timeout),
+                trace: error.trace,
+                cause: None,
+                omitted_frames: error.omitted_frames,
+            }),
+            (result, _) => result,
+        }
+    }
+
+    /// Prepares the VM to run a chunk, with execution proceeding via calls to [Vm::step]
+    pub fn start(&mut self, chunk: Arc<Chunk>) {
+        self.push_frame(chunk, 0, 0);
+    }
+
+    /// Executes up to `instruction_count` instructions, and then suspends execution
+    ///
+    /// This allows scripts to be run in bounded time slices, e.g. a game loop can give each
+    /// entity's VM a fixed number of instructions per frame. Execution can be suspended at any
+    /// point in the script, including inside function calls, and resumes with the next call to
+    /// `step`.
+    ///
+    /// Note that functions that are called by external functions (e.g. the function passed to
+    /// `list.each`) are run to completion, with their instructions not counted towards the limit.
+    ///
+    /// Once execution has finished, further calls to `step` return `Finished(Empty)`.
+    pub fn step(&mut self, instruction_count: usize) -> StepResult {
+        let mut remaining = instruction_count;
+        self.step_until(|_| {
+            if remaining == 0 {
+                true
+            } else {
+                remaining -= 1;
+                false
+            }
+        })
+    }
+
+    /// Executes instructions until the condition returns true, and then suspends execution
+    ///
+    /// The condition is checked before each instruction after the first, so execution always
+    /// makes progress, e.g. when continuing from a breakpoint. When execution is suspended,
+    /// [Vm::current_span] and [Vm::call_depth] describe the next instruction to be executed.
+    ///
+    /// As with [Vm::step], functions that are called by external functions are run to
+    /// completion without the condition being checked.
+    pub fn run_until(&mut self, mut condition: impl FnMut(&Vm) -> bool) -> StepResult {
+        let mut first_instruction = true;
+        self.step_until(|vm| {
+            if first_instruction {
+                first_instruction = false;
+                false
+            } else {
+                condition(vm)
+            }
+        })
+    }
+
+    /// Executes instructions until execution reaches a different statement
+    ///
+    /// Statements are identified by the line in the source where their instructions start,
+    /// with execution also being suspended when a function is called or returns, so that
+    /// stepping follows execution into called functions.
+    ///
+    /// Instructions without source spans (e.g. when debug info has been stripped) are treated as
+    /// belonging to the current statement.
+    pub fn step_statement(&mut self) -> StepResult {
+        let start_line = self.current_span().map(|span| span.start.line);
+        let start_depth = self.call_depth();
+        self.run_until(|vm| {
+            vm.call_depth() != start_depth
+                || matches!(vm.current_span(), Some(span) if Some(span.start.line) != start_line)
+        })
+    }
+
+    /// Returns the span in the source of the next instruction to be executed, if available
+    ///
+    /// This is useful when execution has been suspended, e.g. after a call to [Vm::step].
+    pub fn current_span(&self) -> Option<Span> {
+        self.reader.chunk.source_span(self.ip())
+    }
+
+    /// Returns the number of active call frames
+    ///
+    /// The script's top-level code counts as a frame, so the depth is 1 while it's being run.
+    /// The depth is 0 once execution has finished.
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    // The call depth including the depth of the VM that spawned this VM
+    fn total_call_depth(&self) -> usize {
+        self.base_call_depth + self.call_stack.len()
+    }
+
+    // Returns an error if a new frame would exceed the maximum call depth,
+    // or if the maximum depth of nested calls has been reached
+    fn check_call_depth(&self) -> Result<(), RuntimeError> {
+        let settings = &self.context_shared;
+        match (settings.max_call_depth, settings.max_nested_call_depth) {
+            (Some(max_depth), _) if self.total_call_depth() >= max_depth => Err(RuntimeError::new(
+                RuntimeErrorType::CallDepthExceeded(max_depth),
+            )),
+            (_, Some(max_depth)) if NestedCall::depth() >= max_depth => Err(RuntimeError::new(
+                RuntimeErrorType::NestedCallDepthExceeded(max_depth),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn step_until(&mut self, should_suspend: impl FnMut(&Vm) -> bool) -> StepResult {
+        if self.call_stack.is_empty() {
+            return StepResult::Finished(Value::Empty);
+        }
+
+        match self.execute_instructions_until(should_suspend) {
+            Ok(ExecutionEnd::Returned(value)) => StepResult::Finished(value),
+            Ok(ExecutionEnd::Yielded(value)) => StepResult::Yielded(value),
+            Ok(ExecutionEnd::Suspended) => StepResult::Continue,
+            Err(error) => StepResult::Error(error),
+        }
+    }
+
     pub fn continue_running(&mut self) -> RuntimeResult {
         if self.call_stack.is_empty() {
             Ok(Value::Empty)
@@ -283,10 +1050,55 @@ impl Vm {
         }
     }
 
+    /// Runs a function with the provided arguments, returning the function's result
+    ///
+    /// The function can be any callable value, e.g. a Koto function, an external function, or a
+    /// map that implements `@||`.
+    ///
+    /// Calls can be made re-entrantly by external functions, with the VM that was passed to the
+    /// external function being used to call any callbacks that it received as arguments.
+    /// Callbacks that are stored by an external function to be called after it has returned
+    /// should be called with a VM from [Vm::spawn_shared_vm].
+    ///
+    /// The called function is free to access and modify any values that are visible to it,
+    /// so locks on values (e.g. from [ValueList::data] or [ExternalValue::data_mut]) should be
+    /// released before making a call, otherwise the call may deadlock.
+    /// [Vm::context] and [Vm::context_mut] also need to be released before making a call.
+    ///
+    /// [ExternalValue::data_mut]: crate::ExternalValue::data_mut
     pub fn run_function(&mut self, function: Value, args: CallArgs) -> RuntimeResult {
         self.call_and_run_function(None, function, args)
     }
 
+    /// Runs a function with its output redirected to the provided stdout and stderr
+    ///
+    /// The VM's stdout and stderr are replaced for the duration of the call, allowing the output
+    /// of individual calls to be captured, e.g. by test runners. The redirect also applies to VMs
+    /// that are spawned during the call, e.g. by `thread.create`, while generators and iterator
+    /// adaptors that were created before the call continue to use the VM's previous output.
+    ///
+    /// Once the call has finished the VM's previous stdout and stderr are restored, so redirected
+    /// calls can be nested.
+    pub fn run_function_with_output(
+        &mut self,
+        function: Value,
+        args: CallArgs,
+        stdout: Arc<dyn KotoFile>,
+        stderr: Arc<dyn KotoFile>,
+    ) -> RuntimeResult {
+        let previous_output = self
+            .redirected_output
+            .replace(RedirectedOutput { stdout, stderr });
+        let result = self.run_function(function, args);
+        self.redirected_output = previous_output;
+        result
+    }
+
+    /// Runs a function with the provided instance and arguments
+    ///
+    /// The instance is made available to Koto functions as `self`.
+    ///
+    /// See [Vm::run_function] for information about making re-entrant calls.
     pub fn run_instance_function(
         &mut self,
         instance: Value,
@@ -296,12 +1108,80 @@ impl Vm {
         self.call_and_run_function(Some(instance), function, args)
     }
 
+    /// Runs a function, suspending execution while async external functions are pending
+    ///
+    /// See [Vm::run_async].
+    pub async fn run_function_async(
+        &mut self,
+        function: Value,
+        args: CallArgs<'_>,
+    ) -> RuntimeResult {
+        let old_frame_count = self.call_stack.len();
+
+        self.running_async = true;
+        let call_result = self.call_function_with_args(None, function, args);
+        self.running_async = false;
+        let result_register = call_result?;
+
+        let result = if self.call_stack.len() == old_frame_count {
+            // An external function was called, which may have returned a pending value
+            if self.suspended_call.take().is_some() {
+                self.resolve_suspended_call(result_register).await;
+            }
+            match self.async_call_error.take() {
+                Some(error) => Err(error),
+                None => Ok(self.clone_register(result_register)),
+            }
+        } else {
+            self.frame_mut().execution_barrier = true;
+            let result = self.execute_instructions_async().await;
+            if result.is_err() {
+                self.pop_frame(Value::Empty)?;
+            }
+            result
+        };
+
+        self.truncate_registers(result_register);
+        result
+    }
+
     fn call_and_run_function(
         &mut self,
         instance: Option<Value>,
         function: Value,
         args: CallArgs,
     ) -> RuntimeResult {
+        let old_frame_count = self.call_stack.len();
+        let result_register = self.call_function_with_args(instance, function, args)?;
+
+        let result = if self.call_stack.len() == old_frame_count {
+            // If the call stack is the same size as before calling the function,
+            // then an external function was called and the result should be in the frame base.
+            let result = self.clone_register(result_register);
+            Ok(result)
+        } else {
+            self.frame_mut().execution_barrier = true;
+            let result = self.execute_instructions();
+            if result.is_err() {
+                self.pop_frame(Value::Empty)?;
+            }
+            result
+        };
+
+        self.truncate_registers(result_register);
+        result
+    }
+
+    // Places a function's args in the registers following the current frame, and then calls it
+    //
+    // The call's result register is returned. If a Koto function was called, then its frame will
+    // have been pushed onto the call stack, ready for its instructions to be executed.
+    fn call_function_with_args(
+        &mut self,
+        instance: Option<Value>,
+        function: Value,
+        args: CallArgs,
+    ) -> Result<u8, RuntimeError> {
         if !function.is_callable() {
             return runtime_error!("run_function: the provided value isn't a function");
         }
@@ -370,8 +1250,6 @@ impl Vm {
             }
         };
 
-        let old_frame_count = self.call_stack.len();
-
         self.call_callable(
             result_register,
             function,
@@ -381,22 +1259,7 @@ impl Vm {
             temp_tuple_values,
         )?;
 
-        let result = if self.call_stack.len() == old_frame_count {
-            // If the call stack is the same size as before calling the function,
-            // then an external function was called and the result should be in the frame base.
-            let result = self.clone_register(result_register);
-            Ok(result)
-        } else {
-            self.frame_mut().execution_barrier = true;
-            let result = self.execute_instructions();
-            if result.is_err() {
-                self.pop_frame(Value::Empty)?;
-            }
-            result
-        };
-
-        self.truncate_registers(result_register);
-        result
+        Ok(result_register)
     }
 
     pub fn run_unary_op(&mut self, op: UnaryOp, value: Value) -> RuntimeResult {
@@ -478,8 +1341,119 @@ impl Vm {
         result
     }
 
+    /// Runs the tests of a module
+    ///
+    /// If `isolate_tests` is enabled in the VM's settings, then each test is run with a fresh
+    /// instance of the module, which is produced by running the module's chunk in a new VM.
+    /// This prevents tests from depending on changes to the module's state that were made by
+    /// other tests, although note that the module's top-level expressions will be evaluated
+    /// again for each test. Imported modules are shared between the module's instances.
+    ///
+    /// If `parallel_tests` is also enabled then the instances are distributed between a pool of
+    /// threads, with a thread for each of the available CPU cores. All of the threads are joined
+    /// before returning, with failures reported in the order that the tests are defined.
+    ///
+    /// If `isolate_tests` is disabled then the tests are run with [Vm::run_tests].
+    pub fn run_module_tests(&mut self, module: Arc<Chunk>, tests: ValueMap) -> RuntimeResult {
+        if !self.context_shared.isolate_tests {
+            return self.run_tests(tests);
+        }
+
+        let test_names = tests
+            .meta()
+            .iter()
+            .filter_map(|(key, test)| match key {
+                MetaKey::Test(name) | MetaKey::TestExpectedToFail(name) if test.is_callable() => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // The module's type (e.g. 'Module' for imported modules) is preserved in each instance
+        let module_type = self.context().exports.meta().get(&MetaKey::Type).cloned();
+        let make_test_vm = || {
+            let mut vm = self.spawn_new_vm();
+            if let Some(module_type) = &module_type {
+                vm.context_mut()
+                    .exports
+                    .meta_mut()
+                    .insert(MetaKey::Type, module_type.clone());
+            }
+            vm
+        };
+
+        if self.context_shared.parallel_tests && cfg!(not(target_arch = "wasm32")) {
+            let thread_count = num_cpus::get().min(test_names.len());
+
+            // Each thread runs its tests in turn, stopping after the first failure
+            let mut thread_tests = (0..thread_count).map(|_| Vec::new()).collect::<Vec<_>>();
+            for (index, test_name) in test_names.into_iter().enumerate() {
+                thread_tests[index % thread_count].push((index, make_test_vm(), test_name));
+            }
+
+            let threads = thread_tests
+                .into_iter()
+                .map(|tests| {
+                    let module = module.clone();
+                    std::thread::spawn(move || {
+                        let mut results = Vec::with_capacity(tests.len());
+                        for (index, vm, test_name) in tests {
+                            let result = run_isolated_test(vm, module.clone(), &test_name);
+                            let failed = result.is_err();
+                            results.push((index, result));
+                            if failed {
+                                break;
+                            }
+                        }
+                        results
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            // Every thread is joined before the results are checked, so that no test threads are
+            // left running after returning
+            let mut results = Vec::new();
+            let mut thread_panicked = false;
+            for thread in threads {
+                match thread.join() {
+                    Ok(thread_results) => results.extend(thread_results),
+                    Err(_) => thread_panicked = true,
+                }
+            }
+
+            results.sort_by_key(|(index, _)| *index);
+            for (_, result) in results {
+                result?;
+            }
+
+            if thread_panicked {
+                return runtime_error!("A test thread panicked");
+            }
+        } else {
+            for test_name in test_names.iter() {
+                run_isolated_test(make_test_vm(), module.clone(), test_name)?;
+            }
+        }
+
+        Ok(Value::Empty)
+    }
+
+    /// Runs the tests contained in the map
+    ///
+    /// The tests all share the same module state, see [Vm::run_module_tests] for running a
+    /// module's tests with isolated module instances.
     pub fn run_tests(&mut self, tests: ValueMap) -> RuntimeResult {
-        use Value::{Empty, Function, Map};
+        self.run_tests_with_filter(tests, None)
+    }
+
+    // Runs the tests contained in the map, optionally only running the test with the given name
+    fn run_tests_with_filter(
+        &mut self,
+        tests: ValueMap,
+        test_filter: Option<&ValueString>,
+    ) -> RuntimeResult {
+        use Value::{Empty, Function, Map, Number, Str};
 
         // It's important throughout this function to make sure we don't hang on to any references
         // to the internal test map data while calling the test functions, otherwise we'll end up in
@@ -510,98 +1484,315 @@ impl Vm {
                 .get_index(i)
                 .map(|(key, value)| (key.clone(), value.clone()));
 
-            match meta_entry {
+            let (test_name, test, expected_to_fail) = match meta_entry {
                 Some((MetaKey::Test(test_name), test)) if test.is_callable() => {
-                    let make_test_error = |error: RuntimeError, message: &str| {
-                        Err(error.with_prefix(&format!("{} '{}'", message, test_name)))
+                    (test_name, test, false)
+                }
+                Some((MetaKey::TestExpectedToFail(test_name), test)) if test.is_callable() => {
+                    (test_name, test, true)
+                }
+                _ => continue,
+            };
+
+            if !test_filter.iter().all(|filter| **filter == test_name) {
+                continue;
+            }
+
+            let module_path = self.chunk().source_path.clone();
+            let record_outcome = |vm: &Vm, outcome: TestOutcome| {
+                vm.context_shared.test_results.lock().push(TestResult {
+                    module_path: module_path.clone(),
+                    name: test_name.to_string(),
+                    outcome,
+                });
+            };
+            let make_test_error = |vm: &Vm, error: RuntimeError, message: &str| {
+                record_outcome(vm, TestOutcome::Failed(error.to_string()));
+                Err(error.with_prefix(&format!("{} '{}'", message, test_name)))
+            };
+
+            let (skip_reason, timeout) = {
+                let meta = tests.meta();
+                (
+                    meta.get(&MetaKey::TestSkip(test_name.clone())).cloned(),
+                    meta.get(&MetaKey::TestTimeout(test_name.clone())).cloned(),
+                )
+            };
+
+            match skip_reason {
+                None | Some(Empty) => {}
+                Some(Str(reason)) => {
+                    record_outcome(self, TestOutcome::Skipped(reason.to_string()));
+                    continue;
+                }
+                Some(unexpected) => {
+                    return runtime_error!(
+                        "Expected a String as the reason for skipping test '{}', found '{}'",
+                        test_name,
+                        unexpected.type_as_string()
+                    )
+                }
+            }
+
+            let timeout = match timeout {
+                None => None,
+                Some(Number(n)) if n > 0.0 => Some(Duration::from_secs_f64(n.into())),
+                Some(unexpected) => {
+                    return runtime_error!(
+                        "Expected a positive Number of seconds as the timeout for test '{}', \
+                         found '{}'",
+                        test_name,
+                        unexpected
+                    )
+                }
+            };
+
+            if let Some(pre_test) = &pre_test {
+                if pre_test.is_callable() {
+                    let pre_test_result = if pass_self_to_pre_test {
+                        self.run_instance_function(
+                            self_arg.clone(),
+                            pre_test.clone(),
+                            CallArgs::None,
+                        )
+                    } else {
+                        self.run_function(pre_test.clone(), CallArgs::None)
                     };
 
-                    if let Some(pre_test) = &pre_test {
-                        if pre_test.is_callable() {
-                            let pre_test_result = if pass_self_to_pre_test {
-                                self.run_instance_function(
-                                    self_arg.clone(),
-                                    pre_test.clone(),
-                                    CallArgs::None,
-                                )
-                            } else {
-                                self.run_function(pre_test.clone(), CallArgs::None)
-                            };
-
-                            if let Err(error) = pre_test_result {
-                                return make_test_error(error, "Error while preparing to run test");
-                            }
-                        }
+                    if let Err(error) = pre_test_result {
+                        return make_test_error(self, error, "Error while preparing to run test");
                     }
+                }
+            }
 
-                    let pass_self_to_test = match &test {
-                        Function(f) => f.arg_count == 1,
-                        _ => false,
+            let pass_self_to_test = match &test {
+                Function(f) => f.arg_count == 1,
+                _ => false,
+            };
+
+            let (test_result, timed_out) = self.call_with_timeout(timeout, |vm| {
+                if pass_self_to_test {
+                    vm.run_instance_function(self_arg.clone(), test, CallArgs::None)
+                } else {
+                    vm.run_function(test, CallArgs::None)
+                }
+            });
+
+            let outcome = match (test_result, timeout) {
+                (Err(error), Some(timeout)) if timed_out => {
+                    record_outcome(self, TestOutcome::TimedOut(timeout));
+                    return Err(error.with_prefix(&format!(
+                        "Test '{}' timed out after {}s",
+                        test_name,
+                        timeout.as_secs_f64()
+                    )));
+                }
+                (Err(error), _) if expected_to_fail => {
+                    TestOutcome::ExpectedFailure(error.to_string())
+                }
+                (Err(error), _) => return make_test_error(self, error, "Error while running test"),
+                (Ok(_), _) if expected_to_fail => {
+                    record_outcome(self, TestOutcome::UnexpectedPass);
+                    return runtime_error!("Test '{}' passed, but was expected to fail", test_name);
+                }
+                (Ok(_), _) => TestOutcome::Passed,
+            };
+
+            if let Some(post_test) = &post_test {
+                if post_test.is_callable() {
+                    let post_test_result = if pass_self_to_post_test {
+                        self.run_instance_function(
+                            self_arg.clone(),
+                            post_test.clone(),
+                            CallArgs::None,
+                        )
+                    } else {
+                        self.run_function(post_test.clone(), CallArgs::None)
                     };
 
-                    let test_result = if pass_self_to_test {
-                        self.run_instance_function(self_arg.clone(), test, CallArgs::None)
-                    } else {
-                        self.run_function(test, CallArgs::None)
-                    };
+                    if let Err(error) = post_test_result {
+                        return make_test_error(self, error, "Error after running test");
+                    }
+                }
+            }
+
+            record_outcome(self, outcome);
+        }
+
+        Ok(Empty)
+    }
+
+    // Runs the function, interrupting execution if it doesn't finish within the timeout
+    //
+    // The function's result is returned along with a flag that's set to true if execution was
+    // interrupted. Timeouts rely on threads, so on wasm32 the function is run without a timeout.
+    fn call_with_timeout(
+        &mut self,
+        timeout: Option<Duration>,
+        f: impl FnOnce(&mut Self) -> RuntimeResult,
+    ) -> (RuntimeResult, bool) {
+        let timeout = match timeout {
+            Some(timeout) if cfg!(not(target_arch = "wasm32")) => timeout,
+            _ => return (f(self), false),
+        };
+
+        // The watchdog interrupts a child of the VM's handle, so that other VMs sharing the
+        // handle aren't interrupted, while interruptions from the embedder still apply.
+        let timeout_handle = self.interrupt_handle.child();
+        let previous_handle = std::mem::replace(&mut self.interrupt_handle, timeout_handle.clone());
+
+        let (finished_sender, finished_receiver) = mpsc::channel::<()>();
+        let watchdog = {
+            let timeout_handle = timeout_handle.clone();
+            std::thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) =
+                    finished_receiver.recv_timeout(timeout)
+                {
+                    timeout_handle.interrupt();
+                }
+            })
+        };
+
+        let result = f(self);
+
+        // Dropping the sender disconnects the channel, allowing the watchdog to finish early
+        drop(finished_sender);
+        let _ = watchdog.join();
+        self.interrupt_handle = previous_handle;
 
-                    if let Err(error) = test_result {
-                        return make_test_error(error, "Error while running test");
-                    }
+        let interrupted = result.is_err() && timeout_handle.is_interrupted_directly();
+        (result, interrupted)
+    }
 
-                    if let Some(post_test) = &post_test {
-                        if post_test.is_callable() {
-                            let post_test_result = if pass_self_to_post_test {
-                                self.run_instance_function(
-                                    self_arg.clone(),
-                                    post_test.clone(),
-                                    CallArgs::None,
-                                )
-                            } else {
-                                self.run_function(post_test.clone(), CallArgs::None)
-                            };
-
-                            if let Err(error) = post_test_result {
-                                return make_test_error(error, "Error after running test");
-                            }
-                        }
+    // Executes instructions until the current frame returns,
+    // awaiting the futures of any async calls that suspend execution
+    async fn execute_instructions_async(&mut self) -> RuntimeResult {
+        loop {
+            self.running_async = true;
+            let result = self.execute_instructions_until(|vm| vm.suspended_call.is_some());
+            self.running_async = false;
+
+            match result? {
+                ExecutionEnd::Returned(value) | ExecutionEnd::Yielded(value) => return Ok(value),
+                ExecutionEnd::Suspended => {
+                    if let Some(register) = self.suspended_call.take() {
+                        self.resolve_suspended_call(register).await;
                     }
                 }
-                _ => {}
             }
         }
+    }
 
-        Ok(Empty)
+    // Awaits the future of the pending value in the register, replacing the value with the
+    // future's output
+    //
+    // If the future returns an error, then it's thrown when execution continues.
+    async fn resolve_suspended_call(&mut self, register: u8) {
+        let result = match Pending::take_future(self.get_register(register)) {
+            Some(future) => future.await,
+            None => runtime_error!("Expected a pending value from an async call"),
+        };
+
+        match result {
+            Ok(value) => self.set_register(register, value),
+            Err(error) => {
+                self.set_register(register, Value::Empty);
+                self.async_call_error = Some(error);
+            }
+        }
     }
 
     fn execute_instructions(&mut self) -> RuntimeResult {
-        let mut result = Value::Empty;
+        let _nested_call = NestedCall::enter();
+
+        match self.execute_instructions_until(|_| false)? {
+            ExecutionEnd::Returned(value) | ExecutionEnd::Yielded(value) => Ok(value),
+            // Execution is only suspended when a suspension condition is provided
+            ExecutionEnd::Suspended => Ok(Value::Empty),
+        }
+    }
 
+    // Executes instructions until the current frame returns or yields
+    //
+    // The suspension condition is checked before each instruction, and if it returns true then
+    // execution is suspended. The IP will then point to the next instruction to be executed.
+    fn execute_instructions_until(
+        &mut self,
+        mut should_suspend: impl FnMut(&Vm) -> bool,
+    ) -> Result<ExecutionEnd, RuntimeError> {
         let mut instruction_ip = self.ip();
 
-        while let Some(instruction) = self.reader.next() {
-            if let Some(stop_flag) = &self.stop_flag {
-                if stop_flag.load(Ordering::Relaxed) {
-                    break;
-                }
+        loop {
+            if should_suspend(self) {
+                return Ok(ExecutionEnd::Suspended);
             }
-            match self.execute_instruction(instruction, instruction_ip) {
-                Ok(ControlFlow::Continue) => {}
-                Ok(ControlFlow::Return(value)) => {
-                    result = value;
-                    break;
+
+            if let Some(hook) = &self.context_shared.debug_hook {
+                self.report_statement(hook.clone());
+            }
+
+            let result = if let Some(error) = self.async_call_error.take() {
+                // The error is thrown from the async call, which precedes the current ip
+                instruction_ip = self.ip() - 1;
+                Err(error)
+            } else {
+                let instruction = match self.reader.next() {
+                    Some(instruction) => instruction,
+                    None => break,
+                };
+
+                if let Some(stop_flag) = &self.stop_flag {
+                    if stop_flag.load(Ordering::Relaxed) {
+                        break;
+                    }
                 }
-                Ok(ControlFlow::Yield(value)) => {
-                    result = value;
-                    break;
+
+                if self.interrupt_handle.is_interrupted() {
+                    Err(RuntimeError::new(RuntimeErrorType::Interrupted))
+                } else if !self.consume_instruction_budget() {
+                    Err(RuntimeError::new(
+                        RuntimeErrorType::InstructionBudgetExhausted,
+                    ))
+                } else {
+                    self.execute_instruction(instruction, instruction_ip)
                 }
+            };
+
+            match result {
+                Ok(ControlFlow::Continue) => {}
+                Ok(ControlFlow::Return(value)) => return Ok(ExecutionEnd::Returned(value)),
+                Ok(ControlFlow::Yield(value)) => return Ok(ExecutionEnd::Yielded(value)),
                 Err(mut error) => {
                     let mut recover_register_and_ip = None;
 
                     error.extend_trace(self.chunk(), instruction_ip);
 
+                    // Errors that were thrown in nested calls to execute_instructions_until
+                    // will have already been reported, and will have longer traces.
+                    if error.trace.len() == 1 {
+                        if let Some(hook) = &self.context_shared.debug_hook {
+                            hook.on_error(&DebugFrame::new(self, instruction_ip), &error);
+                        }
+                    }
+
+                    // Errors can't be caught while execution is being interrupted,
+                    // or once the instruction budget or memory limit have been exhausted.
+                    let interrupted = self.interrupt_handle.is_interrupted()
+                        || matches!(
+                            error.error,
+                            RuntimeErrorType::Interrupted
+                                | RuntimeErrorType::InstructionBudgetExhausted
+                                | RuntimeErrorType::MemoryLimitExceeded(_)
+                        );
+
                     while let Some(frame) = self.call_stack.last() {
-                        if let Some((error_register, catch_ip)) = frame.catch_stack.last() {
+                        let catch = if interrupted {
+                            None
+                        } else {
+                            frame.catch_stack.last()
+                        };
+
+                        if let Some((error_register, catch_ip)) = catch {
                             recover_register_and_ip = Some((*error_register, *catch_ip));
                             break;
                         } else {
@@ -633,7 +1824,7 @@ impl Vm {
             instruction_ip = self.ip();
         }
 
-        Ok(result)
+        Ok(ExecutionEnd::Returned(Value::Empty))
     }
 
     fn execute_instruction(
@@ -700,7 +1891,13 @@ impl Vm {
                 register,
                 size_hint,
             } => {
-                self.set_register(register, Map(ValueMap::with_capacity(size_hint)));
+                // The size hint is clamped in case the chunk hasn't been verified
+                let size_hint = size_hint.min(MAX_SIZE_HINT);
+                self.track_allocation(size_hint * MAP_ENTRY_SIZE)?;
+                let map = Map(ValueMap::with_capacity(size_hint));
+                self.track_value_memory(&map)?;
+                self.track_with_gc(&map);
+                self.set_register(register, map);
                 Ok(())
             }
             Instruction::MakeNum2 {
@@ -717,7 +1914,10 @@ impl Vm {
                 register,
                 size_hint,
             } => {
-                self.set_register(register, SequenceBuilder(Vec::with_capacity(size_hint)));
+                self.set_register(
+                    register,
+                    SequenceBuilder(Vec::with_capacity(size_hint.min(MAX_SIZE_HINT))),
+                );
                 Ok(())
             }
             Instruction::SequencePush { sequence, value } => {
@@ -739,7 +1939,10 @@ impl Vm {
                 register,
                 size_hint,
             } => {
-                self.set_register(register, StringBuilder(String::with_capacity(size_hint)));
+                self.set_register(
+                    register,
+                    StringBuilder(String::with_capacity(size_hint.min(MAX_SIZE_HINT))),
+                );
                 Ok(())
             }
             Instruction::StringPush { register, value } => self.run_string_push(register, value),
@@ -807,6 +2010,29 @@ impl Vm {
             }
             Instruction::Equal { register, lhs, rhs } => self.run_equal(register, lhs, rhs),
             Instruction::NotEqual { register, lhs, rhs } => self.run_not_equal(register, lhs, rhs),
+            Instruction::In { register, lhs, rhs } => self.run_in(register, lhs, rhs),
+            Instruction::AddU8 { register, lhs, rhs } => self.run_add_u8(register, lhs, rhs),
+            Instruction::SubtractU8 { register, lhs, rhs } => {
+                self.run_subtract_u8(register, lhs, rhs)
+            }
+            Instruction::LessU8 { register, lhs, rhs } => {
+                self.run_comparison_u8(BinaryOp::Less, register, lhs, rhs)
+            }
+            Instruction::LessOrEqualU8 { register, lhs, rhs } => {
+                self.run_comparison_u8(BinaryOp::LessOrEqual, register, lhs, rhs)
+            }
+            Instruction::GreaterU8 { register, lhs, rhs } => {
+                self.run_comparison_u8(BinaryOp::Greater, register, lhs, rhs)
+            }
+            Instruction::GreaterOrEqualU8 { register, lhs, rhs } => {
+                self.run_comparison_u8(BinaryOp::GreaterOrEqual, register, lhs, rhs)
+            }
+            Instruction::EqualU8 { register, lhs, rhs } => {
+                self.run_comparison_u8(BinaryOp::Equal, register, lhs, rhs)
+            }
+            Instruction::NotEqualU8 { register, lhs, rhs } => {
+                self.run_comparison_u8(BinaryOp::NotEqual, register, lhs, rhs)
+            }
             Instruction::Jump { offset } => {
                 self.jump_ip(offset);
                 Ok(())
@@ -847,7 +2073,24 @@ impl Vm {
                 Some(instance),
                 None,
             ),
+            Instruction::TailCall {
+                result,
+                function,
+                frame_base,
+                arg_count,
+            } => self.run_tail_call(result, function, frame_base, arg_count, None),
+            Instruction::TailCallInstance {
+                result,
+                function,
+                frame_base,
+                arg_count,
+                instance,
+            } => self.run_tail_call(result, function, frame_base, arg_count, Some(instance)),
             Instruction::Return { register } => {
+                if let Some(hook) = &self.context_shared.debug_hook {
+                    let frame = DebugFrame::new(self, instruction_ip);
+                    hook.on_function_exit(&frame, self.get_register(register));
+                }
                 if let Some(return_value) = self.pop_frame(self.clone_register(register))? {
                     // If pop_frame returns a new return_value, then execution should stop.
                     control_flow = ControlFlow::Return(return_value);
@@ -855,28 +2098,22 @@ impl Vm {
                 Ok(())
             }
             Instruction::Yield { register } => {
+                self.yield_register = Some(register);
                 control_flow = ControlFlow::Yield(self.clone_register(register));
                 Ok(())
             }
             Instruction::Throw { register } => {
                 let thrown_value = self.clone_register(register);
 
-                let display_op = MetaKey::UnaryOp(UnaryOp::Display);
-                use RuntimeErrorType::KotoError;
-                match &thrown_value {
-                    Str(_) => Err(RuntimeError::new(KotoError {
-                        thrown_value,
-                        vm: None,
-                    })),
-                    Map(m) if m.meta().contains_key(&display_op) => Err(
-                        RuntimeError::from_koto_value(thrown_value, self.spawn_shared_vm()),
-                    ),
-                    other => {
-                        runtime_error!(
-                            "throw: expected string or map with @display function, found '{}'",
-                            other.type_as_string()
-                        )
+                match self.make_thrown_error(&thrown_value) {
+                    Some(mut error) => {
+                        error.cause = self.make_thrown_error_cause(&thrown_value, &mut Vec::new());
+                        Err(error)
                     }
+                    None => runtime_error!(
+                        "throw: expected string or map with @display function, found '{}'",
+                        thrown_value.type_as_string()
+                    ),
                 }
             }
             Instruction::Size { register, value } => {
@@ -956,7 +2193,7 @@ impl Vm {
                 register,
                 value,
                 key,
-            } => self.run_access(register, value, self.value_string_from_constant(key)),
+            } => self.run_access_constant(register, value, key, instruction_ip),
             Instruction::AccessString {
                 register,
                 value,
@@ -976,6 +2213,13 @@ impl Vm {
                 self.frame_mut().catch_stack.push((arg_register, catch_ip));
                 Ok(())
             }
+            Instruction::Coverage { index } => {
+                self.context_shared
+                    .coverage
+                    .lock()
+                    .record(&self.reader.chunk, index);
+                Ok(())
+            }
             Instruction::TryEnd => {
                 self.frame_mut().catch_stack.pop();
                 Ok(())
@@ -983,8 +2227,29 @@ impl Vm {
             Instruction::Debug { register, constant } => {
                 self.run_debug(register, constant, instruction_ip)
             }
+            Instruction::Assert { register, constant } => self.run_assert(register, None, constant),
+            Instruction::AssertComparison {
+                register,
+                lhs,
+                rhs,
+                constant,
+            } => self.run_assert(register, Some((lhs, rhs)), constant),
             Instruction::CheckType { register, type_id } => self.run_check_type(register, type_id),
+            Instruction::CheckTypeAnnotation {
+                register,
+                type_name,
+            } => self.run_check_type_annotation(register, type_name),
             Instruction::CheckSize { register, size } => self.run_check_size(register, size),
+            Instruction::JumpTable {
+                register,
+                key_type,
+                entry_count,
+                default_offset,
+                table_ip,
+            } => {
+                self.run_jump_table(register, key_type, entry_count, default_offset, table_ip);
+                Ok(())
+            }
         }?;
 
         Ok(control_flow)
@@ -1020,9 +2285,16 @@ impl Vm {
     }
 
     fn run_value_export(&mut self, name_register: u8, value_register: u8) {
-        let name = ValueKey::from(self.clone_register(name_register));
+        let name = self.clone_register(name_register);
         let value = self.clone_register(value_register);
-        self.context_mut().exports.data_mut().insert(name, value);
+        let is_constant = self.is_constant_string(&name);
+        let context = self.context_mut();
+        let mut exports = context.exports.data_mut();
+        if is_constant {
+            exports.insert_shared(name.into(), value);
+        } else {
+            exports.insert(name.into(), value);
+        }
     }
 
     fn run_make_range(
@@ -1184,39 +2456,29 @@ impl Vm {
         use Value::*;
 
         let result = match self.get_register(value) {
-            List(list) => {
-                let index = signed_index_to_unsigned(index, list.data().len());
-                list.data().get(index).cloned().unwrap_or(Empty)
-            }
-            Tuple(tuple) => {
-                let index = signed_index_to_unsigned(index, tuple.data().len());
-                tuple.data().get(index).cloned().unwrap_or(Empty)
-            }
+            List(list) => signed_index_to_unsigned(index, list.data().len())
+                .and_then(|index| list.data().get(index).cloned())
+                .unwrap_or(Empty),
+            Tuple(tuple) => signed_index_to_unsigned(index, tuple.data().len())
+                .and_then(|index| tuple.data().get(index).cloned())
+                .unwrap_or(Empty),
             TemporaryTuple(RegisterSlice { start, count }) => {
                 let count = *count;
-                if (index.abs() as u8) < count {
-                    let index = signed_index_to_unsigned(index, count as usize);
-                    self.clone_register(start + index as u8)
-                } else {
-                    Empty
-                }
-            }
-            Num2(n) => {
-                let index = signed_index_to_unsigned(index, 2);
-                if index < 2 {
-                    Number(n[index].into())
-                } else {
-                    Empty
-                }
-            }
-            Num4(n) => {
-                let index = signed_index_to_unsigned(index, 4);
-                if index < 4 {
-                    Number(n[index].into())
-                } else {
-                    Empty
+                match signed_index_to_unsigned(index, count as usize) {
+                    Some(index) if index < count as usize => {
+                        self.clone_register(start + index as u8)
+                    }
+                    _ => Empty,
                 }
             }
+            Num2(n) => match signed_index_to_unsigned(index, 2) {
+                Some(index) if index < 2 => Number(n[index].into()),
+                _ => Empty,
+            },
+            Num4(n) => match signed_index_to_unsigned(index, 4) {
+                Some(index) if index < 4 => Number(n[index].into()),
+                _ => Empty,
+            },
             unexpected => {
                 return self
                     .unexpected_type_error("ValueIndex: Expected indexable value", unexpected);
@@ -1237,9 +2499,10 @@ impl Vm {
     ) -> InstructionResult {
         use Value::*;
 
+        // Negative indices that are before the start of the sequence are clamped to the start
         let result = match self.get_register(value) {
             List(list) => {
-                let index = signed_index_to_unsigned(index, list.data().len());
+                let index = signed_index_to_unsigned(index, list.data().len()).unwrap_or(0);
                 if is_slice_to {
                     list.data()
                         .get(..index)
@@ -1251,7 +2514,7 @@ impl Vm {
                 }
             }
             Tuple(tuple) => {
-                let index = signed_index_to_unsigned(index, tuple.data().len());
+                let index = signed_index_to_unsigned(index, tuple.data().len()).unwrap_or(0);
                 if is_slice_to {
                     tuple
                         .data()
@@ -1264,6 +2527,22 @@ impl Vm {
                         .map_or(Empty, |entries| Tuple(entries.into()))
                 }
             }
+            TemporaryTuple(RegisterSlice { start, count }) => {
+                // Temporary tuples are produced by multi-assignments with ellipses,
+                // e.g. `first, rest... = 1, 2, 3`
+                let (start, count) = (*start, *count);
+                let index = signed_index_to_unsigned(index, count as usize).unwrap_or(0) as u8;
+                if index <= count {
+                    let entries = if is_slice_to {
+                        self.register_slice(start, index)
+                    } else {
+                        self.register_slice(start + index, count - index)
+                    };
+                    Tuple(entries.into())
+                } else {
+                    Empty
+                }
+            }
             unexpected => {
                 return self.unexpected_type_error("SliceFrom: expected List or Tuple", unexpected);
             }
@@ -1299,7 +2578,7 @@ impl Vm {
 
                 let function = FunctionInfo {
                     chunk: self.chunk(),
-                    ip: self.ip(),
+                    ip: self.ip() as FunctionIp,
                     arg_count,
                     instance_function,
                     variadic,
@@ -1313,6 +2592,7 @@ impl Vm {
                     Function(function)
                 };
 
+                self.track_with_gc(&value);
                 self.jump_ip(size);
                 self.set_register(register, value);
             }
@@ -1390,6 +2670,44 @@ impl Vm {
         Ok(())
     }
 
+    // Makes the error for a thrown value, returning None if the value can't be thrown
+    fn make_thrown_error(&self, thrown_value: &Value) -> Option<RuntimeError> {
+        use RuntimeErrorType::KotoError;
+
+        match thrown_value {
+            Value::Str(_) => Some(RuntimeError::new(KotoError {
+                thrown_value: thrown_value.clone(),
+                vm: None,
+            })),
+            Value::Map(m) if m.meta().contains_key(&MetaKey::UnaryOp(UnaryOp::Display)) => Some(
+                RuntimeError::from_koto_value(thrown_value.clone(), self.spawn_shared_vm()),
+            ),
+            _ => None,
+        }
+    }
+
+    // Makes the chain of errors that caused a thrown error
+    //
+    // A thrown map refers to its cause with a `cause` entry, see `koto.error`.
+    // Maps that have already been visited are skipped to avoid cycles in the chain.
+    fn make_thrown_error_cause(
+        &self,
+        thrown_value: &Value,
+        visited: &mut Vec<ValueMap>,
+    ) -> Option<Box<RuntimeError>> {
+        let map = match thrown_value {
+            Value::Map(map) if !visited.iter().any(|m| m.is_same_instance(map)) => map,
+            _ => return None,
+        };
+        visited.push(map.clone());
+
+        let cause_value = map.data().get_with_string("cause").cloned()?;
+        let mut cause = self.make_thrown_error(&cause_value)?;
+        cause.cause = self.make_thrown_error_cause(&cause_value, visited);
+
+        Some(Box::new(cause))
+    }
+
     fn run_display(&mut self, result: u8, value: u8) -> InstructionResult {
         use {UnaryOp::Display, Value::*};
 
@@ -1457,6 +2775,9 @@ impl Vm {
             _ => return self.binary_op_error(lhs_value, rhs_value, "+"),
         };
 
+        self.track_allocation(memory::allocation_size(&result_value))?;
+        self.track_value_memory(&result_value)?;
+        self.track_with_gc(&result_value);
         self.set_register(result, result_value);
         Ok(())
     }
@@ -1582,6 +2903,69 @@ impl Vm {
         Ok(())
     }
 
+    fn run_add_u8(&mut self, result: u8, lhs: u8, n: u8) -> InstructionResult {
+        use {Value::Number, ValueNumber::I64};
+
+        let result_value = match self.get_register(lhs) {
+            Number(I64(a)) => Number(I64(a + n as i64)),
+            Number(a) => Number(*a + ValueNumber::from(n)),
+            _ => return self.run_binary_op_with_u8_rhs(BinaryOp::Add, result, lhs, n),
+        };
+
+        self.set_register(result, result_value);
+        Ok(())
+    }
+
+    fn run_subtract_u8(&mut self, result: u8, lhs: u8, n: u8) -> InstructionResult {
+        use {Value::Number, ValueNumber::I64};
+
+        let result_value = match self.get_register(lhs) {
+            Number(I64(a)) => Number(I64(a - n as i64)),
+            Number(a) => Number(*a - ValueNumber::from(n)),
+            _ => return self.run_binary_op_with_u8_rhs(BinaryOp::Subtract, result, lhs, n),
+        };
+
+        self.set_register(result, result_value);
+        Ok(())
+    }
+
+    fn run_comparison_u8(&mut self, op: BinaryOp, result: u8, lhs: u8, n: u8) -> InstructionResult {
+        use {Value::Number, ValueNumber::I64};
+
+        let (a, b) = match self.get_register(lhs) {
+            Number(I64(a)) => (I64(*a), I64(n as i64)),
+            Number(a) => (*a, ValueNumber::from(n)),
+            _ => return self.run_binary_op_with_u8_rhs(op, result, lhs, n),
+        };
+
+        let comparison_result = match op {
+            BinaryOp::Less => a < b,
+            BinaryOp::LessOrEqual => a <= b,
+            BinaryOp::Greater => a > b,
+            BinaryOp::GreaterOrEqual => a >= b,
+            BinaryOp::Equal => a == b,
+            BinaryOp::NotEqual => a != b,
+            _ => unreachable!(),
+        };
+
+        self.set_register(result, comparison_result.into());
+        Ok(())
+    }
+
+    // The generic fallback for ops with a small integer rhs, used when the lhs isn't a Number
+    fn run_binary_op_with_u8_rhs(
+        &mut self,
+        op: BinaryOp,
+        result: u8,
+        lhs: u8,
+        n: u8,
+    ) -> InstructionResult {
+        let lhs_value = self.clone_register(lhs);
+        let result_value = self.run_binary_op(op, lhs_value, n.into())?;
+        self.set_register(result, result_value);
+        Ok(())
+    }
+
     fn run_less(&mut self, result: u8, lhs: u8, rhs: u8) -> InstructionResult {
         use {BinaryOp::Less, Value::*};
 
@@ -1689,8 +3073,8 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
         let result_value = match (lhs_value, rhs_value) {
             (Number(a), Number(b)) => a == b,
-            (Num2(a), Num2(b)) => a == b,
-            (Num4(a), Num4(b)) => a == b,
+            (Num2(a), Num2(b)) => self.num2_equal(a, b),
+            (Num4(a), Num4(b)) => self.num4_equal(a, b),
             (Bool(a), Bool(b)) => a == b,
             (Str(a), Str(b)) => a == b,
             (Range(a), Range(b)) => a == b,
@@ -1752,6 +3136,58 @@ impl Vm {
         Ok(())
     }
 
+    fn run_in(&mut self, result: u8, lhs: u8, rhs: u8) -> InstructionResult {
+        use Value::*;
+
+        let value = self.clone_register(lhs);
+        let container = self.clone_register(rhs);
+
+        let result_value = match (&value, &container) {
+            (key, Map(m)) if key.is_immutable() => {
+                m.data().contains_key(&ValueKey::from(key.clone()))
+            }
+            (_, Map(_)) => false,
+            (Str(s), Str(container)) => container.contains(s.as_str()),
+            (Number(n), Range(r)) => r.contains(*n),
+            (_, List(l)) => {
+                let values = l.data().clone();
+                self.contains_value(&values, &value)?
+            }
+            (_, Tuple(t)) => {
+                let t = t.clone();
+                self.contains_value(t.data(), &value)?
+            }
+            _ => {
+                return runtime_error!(
+                    "Unable to check if '{}' is in '{}'",
+                    value.type_as_string(),
+                    container.type_as_string()
+                );
+            }
+        };
+
+        self.set_register(result, Bool(result_value));
+        Ok(())
+    }
+
+    // Returns true if any of the values is equal to the given value
+    fn contains_value(&mut self, values: &[Value], value: &Value) -> Result<bool, RuntimeError> {
+        for candidate in values.iter() {
+            match self.run_binary_op(BinaryOp::Equal, value.clone(), candidate.clone())? {
+                Value::Bool(false) => {}
+                Value::Bool(true) => return Ok(true),
+                unexpected => {
+                    return self.unexpected_type_error(
+                        "Expected Bool from equality comparison",
+                        &unexpected,
+                    )
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
     fn run_not_equal(&mut self, result: u8, lhs: u8, rhs: u8) -> InstructionResult {
         use {BinaryOp::NotEqual, Value::*};
 
@@ -1759,8 +3195,8 @@ impl Vm {
         let rhs_value = self.get_register(rhs);
         let result_value = match (lhs_value, rhs_value) {
             (Number(a), Number(b)) => a != b,
-            (Num2(a), Num2(b)) => a != b,
-            (Num4(a), Num4(b)) => a != b,
+            (Num2(a), Num2(b)) => !self.num2_equal(a, b),
+            (Num4(a), Num4(b)) => !self.num4_equal(a, b),
             (Bool(a), Bool(b)) => a != b,
             (Str(a), Str(b)) => a != b,
             (Range(a), Range(b)) => a != b,
@@ -1818,6 +3254,25 @@ impl Vm {
         Ok(())
     }
 
+    // Called from run_equal / run_not_equal to compare num2 values,
+    // taking the approximate equality setting into account
+    fn num2_equal(&self, a: &num2::Num2, b: &num2::Num2) -> bool {
+        if self.context_shared.approximate_num_equality {
+            a.is_near(b, self.context_shared.float_epsilon)
+        } else {
+            a == b
+        }
+    }
+
+    // See num2_equal
+    fn num4_equal(&self, a: &num4::Num4, b: &num4::Num4) -> bool {
+        if self.context_shared.approximate_num_equality {
+            a.is_near(b, self.context_shared.float_epsilon as f32)
+        } else {
+            a == b
+        }
+    }
+
     // Called from run_equal / run_not_equal to compare the contents of lists and tuples
     fn compare_value_ranges(
         &mut self,
@@ -1934,6 +3389,74 @@ impl Vm {
         Ok(())
     }
 
+    fn run_jump_table(
+        &mut self,
+        register: u8,
+        key_type: JumpTableKey,
+        entry_count: usize,
+        default_offset: usize,
+        table_ip: usize,
+    ) {
+        use std::{cmp::Ordering as CmpOrdering, convert::TryInto};
+
+        let chunk = self.reader.chunk.clone();
+        let entry_size = key_type.entry_size();
+        let entry_bytes = |index: usize| {
+            let start = table_ip + index * entry_size;
+            &chunk.bytes[start..start + entry_size]
+        };
+        let entry_offset = |bytes: &[u8]| {
+            u16::from_le_bytes([bytes[entry_size - 2], bytes[entry_size - 1]]) as usize
+        };
+
+        // Binary search through the table's sorted entries,
+        // returning the offset of the matching entry if one is found.
+        let search = |compare: &dyn Fn(&[u8]) -> CmpOrdering| {
+            let (mut low, mut high) = (0, entry_count);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                let bytes = entry_bytes(mid);
+                match compare(bytes) {
+                    CmpOrdering::Less => low = mid + 1,
+                    CmpOrdering::Greater => high = mid,
+                    CmpOrdering::Equal => return Some(entry_offset(bytes)),
+                }
+            }
+            None
+        };
+
+        let offset = match (key_type, self.get_register(register)) {
+            (JumpTableKey::Int, Value::Number(n)) => {
+                let key = match n {
+                    ValueNumber::I64(i) => Some(*i),
+                    ValueNumber::F64(f) if f.fract() == 0.0 && (*f as i64) as f64 == *f => {
+                        Some(*f as i64)
+                    }
+                    _ => None,
+                };
+                key.and_then(|key| {
+                    search(&|bytes| {
+                        let entry_key = i64::from_le_bytes(bytes[..8].try_into().unwrap());
+                        entry_key.cmp(&key)
+                    })
+                })
+                .unwrap_or(default_offset)
+            }
+            (JumpTableKey::Str, Value::Str(s)) => {
+                let key = s.as_str();
+                search(&|bytes| {
+                    let constant = ConstantIndex::from([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    chunk.constants.get_str(constant).cmp(key)
+                })
+                .unwrap_or(default_offset)
+            }
+            // Other values fall through to the match arms' pattern checks
+            _ => return,
+        };
+
+        self.jump_ip(offset);
+    }
+
     fn run_size(&mut self, register: u8, value: u8) {
         let result = self.get_register(value).size();
         self.set_register(register, Value::Number(result.into()));
@@ -1987,76 +3510,219 @@ impl Vm {
             Err(e) => return runtime_error!("Failed to import '{}': {}", import_name, e),
         };
 
+        let module = self.import_module(&import_name, module_chunk, module_path)?;
+        self.set_register(import_register, module);
+
+        Ok(())
+    }
+
+    /// Imports all of the modules contained in a directory
+    ///
+    /// The directory is found relative to the location of the current script, and each `.koto`
+    /// file in the directory is imported as a module.
+    ///
+    /// A map is returned containing each module's exports, keyed by the module's name.
+    pub fn import_all_in(&mut self, dir: &str) -> RuntimeResult {
+        let source_path = self.reader.chunk.source_path.clone();
+        let modules = match self
+            .context_mut()
+            .loader
+            .compile_modules_in_dir(dir, source_path)
+        {
+            Ok(modules) => modules,
+            Err(e) => return runtime_error!("Failed to import modules in '{}': {}", dir, e),
+        };
+
+        let mut result = ValueMap::with_capacity(modules.len());
+
+        for (name, module_chunk, module_path) in modules {
+            let module = self.import_module(&name, module_chunk, module_path)?;
+            result.add_value(&name, module);
+        }
+
+        Ok(Value::Map(result))
+    }
+
+    // Runs a compiled module and returns its exports
+    //
+    // Modules are cached after they've been run, so subsequent imports of the same module will
+    // return the cached exports.
+    fn import_module(
+        &mut self,
+        import_name: &str,
+        module_chunk: Arc<Chunk>,
+        module_path: PathBuf,
+    ) -> RuntimeResult {
         // Has the module been loaded previously?
         let maybe_in_cache = self.context().modules.get(&module_path).cloned();
         match maybe_in_cache {
-            Some(Some(cached_exports)) => {
-                self.set_register(import_register, Value::Map(cached_exports));
-                return Ok(());
-            }
+            Some(Some(cached_exports)) => Ok(Value::Map(cached_exports)),
             Some(None) => {
                 // If the cache contains a None entry for the module path,
                 // then we're in a recursive import (see below).
-                return runtime_error!("Recursive import of module '{}'", import_name);
+                runtime_error!("Recursive import of module '{}'", import_name)
             }
             None => {
                 // The module is new to the runtime, so it needs to be loaded
+                #[cfg(feature = "tracing")]
+                let _trace_span = tracing::info_span!(
+                    parent: &self.parent_trace_span(),
+                    "koto.import",
+                    module = import_name,
+                    path = %module_path.display()
+                )
+                .entered();
 
                 // Insert a placeholder for the new module, preventing recursive imports
                 self.context_mut().modules.insert(module_path.clone(), None);
 
-                // Run the module chunk in a new vm
-                let mut vm = self.spawn_new_vm();
-                match vm.run(module_chunk) {
-                    Ok(_) => {
-                        if self.context_shared.run_import_tests {
-                            let maybe_tests =
-                                vm.context().exports.meta().get(&MetaKey::Tests).cloned();
-                            match maybe_tests {
-                                Some(Value::Map(tests)) => {
-                                    if let Err(error) = vm.run_tests(tests) {
-                                        return runtime_error!(
-                                            "Module '{}' - {}",
-                                            import_name,
-                                            error
-                                        );
-                                    }
-                                }
-                                Some(other) => {
-                                    return runtime_error!(
-                                        "Expected map for tests in module '{}', found '{}'",
-                                        import_name,
-                                        other.type_as_string()
-                                    );
-                                }
-                                None => {}
-                            }
-                        }
+                let module_exports = match self.run_module(import_name, module_chunk) {
+                    Ok(module_vm) => module_vm.context().exports.clone(),
+                    Err(error) => {
+                        self.context_mut().modules.remove(&module_path);
+                        return Err(error);
+                    }
+                };
+
+                // Cache the module's resulting exports map
+                self.context_mut()
+                    .modules
+                    .insert(module_path, Some(module_exports.clone()));
+
+                Ok(Value::Map(module_exports))
+            }
+        }
+    }
+
+    // Runs a module chunk in a new VM, followed by its tests and its main function
+    //
+    // The module's VM is returned, with the module's exports available in its context.
+    fn run_module(
+        &mut self,
+        import_name: &str,
+        module_chunk: Arc<Chunk>,
+    ) -> Result<Vm, RuntimeError> {
+        let mut vm = self.spawn_new_vm();
+
+        // Imported modules are identified with the 'Module' type
+        vm.context_mut()
+            .exports
+            .meta_mut()
+            .insert(MetaKey::Type, Value::Str("Module".into()));
+
+        vm.run(module_chunk.clone())?;
+
+        if self.context_shared.run_import_tests {
+            let maybe_tests = vm.context().exports.meta().get(&MetaKey::Tests).cloned();
+            match maybe_tests {
+                Some(Value::Map(tests)) => {
+                    if let Err(error) = vm.run_module_tests(module_chunk, tests) {
+                        return runtime_error!("Module '{}' - {}", import_name, error);
+                    }
+                }
+                Some(other) => {
+                    return runtime_error!(
+                        "Expected map for tests in module '{}', found '{}'",
+                        import_name,
+                        other.type_as_string()
+                    );
+                }
+                None => {}
+            }
+        }
+
+        if let Some(main) = vm.get_exported_function("main") {
+            vm.run_function(main, CallArgs::None)?;
+        }
+
+        Ok(vm)
+    }
+
+    /// Reloads a module, updating its exports in place
+    ///
+    /// The module is found in the same way as with `import`, relative to the script that the VM
+    /// is running, and is then recompiled from its source file and run.
+    ///
+    /// If the module exports an `@reload` function, then it's called with the module's previous
+    /// exports, allowing state to be migrated to the reloaded module. The hook is run in the
+    /// context of the reloaded module, so the module's new exports can be modified directly.
+    ///
+    /// The module's previous exports map is then updated with the new exports, so that existing
+    /// references to the module (e.g. from `import` expressions in other modules) see the reloaded
+    /// version. Values that were copied out of the module (e.g. with `from module import foo`)
+    /// aren't updated.
+    ///
+    /// If the module hasn't been imported yet then it's imported for the first time.
+    /// If an error occurs while reloading then the module's previous exports are left unchanged.
+    pub fn reload_module(&mut self, name: &str) -> RuntimeResult {
+        let source_path = self.reader.chunk.source_path.clone();
+        let (module_chunk, module_path) = match self
+            .context_mut()
+            .loader
+            .recompile_module(name, source_path)
+        {
+            Ok((chunk, path)) => (chunk, path),
+            Err(e) => return runtime_error!("Failed to reload '{}': {}", name, e),
+        };
+
+        let maybe_in_cache = self.context().modules.get(&module_path).cloned();
+        let previous_exports = match maybe_in_cache {
+            Some(Some(previous_exports)) => previous_exports,
+            Some(None) => {
+                return runtime_error!("Unable to reload '{}' while it's being imported", name)
+            }
+            None => return self.import_module(name, module_chunk, module_path),
+        };
 
-                        if let Some(main) = vm.get_exported_function("main") {
-                            if let Err(error) = vm.run_function(main, CallArgs::None) {
-                                self.context_mut().modules.remove(&module_path);
-                                return Err(error);
-                            }
-                        }
+        #[cfg(feature = "tracing")]
+        let _trace_span = tracing::info_span!(
+            parent: &self.parent_trace_span(),
+            "koto.reload",
+            module = name,
+            path = %module_path.display()
+        )
+        .entered();
+
+        // A placeholder is inserted while the module is running, preventing recursive imports
+        self.context_mut().modules.insert(module_path.clone(), None);
+
+        let result = self
+            .run_module(name, module_chunk)
+            .and_then(|mut module_vm| {
+                let reload_hook = module_vm
+                    .context()
+                    .exports
+                    .meta()
+                    .get(&MetaKey::Reload)
+                    .cloned();
+                match reload_hook {
+                    Some(hook) if hook.is_callable() => {
+                        module_vm.run_function(
+                            hook,
+                            CallArgs::Single(Value::Map(previous_exports.clone())),
+                        )?;
                     }
-                    Err(error) => {
-                        self.context_mut().modules.remove(&module_path);
-                        return Err(error);
+                    Some(unexpected) => {
+                        return self
+                            .unexpected_type_error("@reload: Expected Function", &unexpected);
                     }
+                    None => {}
                 }
 
-                // Cache the module's resulting exports map
-                let module_exports = vm.context().exports.clone();
-                self.context_mut()
-                    .modules
-                    .insert(module_path, Some(module_exports.clone()));
+                let new_exports = module_vm.context().exports.clone();
+                Ok(new_exports)
+            });
 
-                self.set_register(import_register, Value::Map(module_exports));
-            }
-        }
+        // The previous exports are cached again, and are left unchanged if an error occurred
+        self.context_mut()
+            .modules
+            .insert(module_path, Some(previous_exports.clone()));
+        let new_exports = result?;
 
-        Ok(())
+        *previous_exports.data_mut() = new_exports.data().clone();
+        *previous_exports.meta_mut() = new_exports.meta().clone();
+
+        Ok(Value::Map(previous_exports))
     }
 
     fn run_make_num2(
@@ -2503,10 +4169,21 @@ impl Vm {
     ) -> InstructionResult {
         let key = self.clone_register(key_register);
         let value = self.clone_register(value_register);
+        let is_constant_key = self.is_constant_string(&key);
 
         match self.get_register_mut(map_register) {
             Value::Map(map) => {
-                map.data_mut().insert(key.into(), value);
+                let mut data = map.data_mut();
+                let previous = if is_constant_key {
+                    data.insert_shared(key.into(), value)
+                } else {
+                    data.insert(key.into(), value)
+                };
+                drop(data);
+                let is_new_entry = previous.is_none();
+                if is_new_entry {
+                    self.track_allocation(MAP_ENTRY_SIZE)?;
+                }
                 Ok(())
             }
             unexpected => runtime_error!(
@@ -2576,6 +4253,47 @@ impl Vm {
             Err(error) => return runtime_error!("MetaExport: {}", error),
         };
 
+        // Version metadata is validated as soon as it's exported,
+        // so that an unsupported module fails before running any further.
+        match meta_key {
+            MetaKey::Version => match &value {
+                Value::Str(version) if Version::parse(version).is_some() => {}
+                Value::Str(version) => {
+                    return runtime_error!(
+                        "Invalid module version '{}', expected a version like '1.2.3'",
+                        version
+                    )
+                }
+                unexpected => {
+                    return self.unexpected_type_error("@version: Expected String", unexpected)
+                }
+            },
+            MetaKey::Requires => match &value {
+                Value::Str(requirement) => match VersionRequirement::parse(requirement) {
+                    Some(parsed) => {
+                        let runtime_version = Version::runtime();
+                        if !parsed.is_satisfied_by(runtime_version) {
+                            return runtime_error!(
+                                "The module requires '{}', but the runtime version is {}",
+                                requirement.trim(),
+                                runtime_version
+                            );
+                        }
+                    }
+                    None => {
+                        return runtime_error!(
+                            "Invalid version requirement '{}', expected a requirement like 'koto >= 0.9'",
+                            requirement
+                        )
+                    }
+                },
+                unexpected => {
+                    return self.unexpected_type_error("@requires: Expected String", unexpected)
+                }
+            },
+            _ => {}
+        }
+
         self.context_mut()
             .exports
             .meta_mut()
@@ -2606,6 +4324,36 @@ impl Vm {
         Ok(())
     }
 
+    fn run_access_constant(
+        &mut self,
+        result_register: u8,
+        value_register: u8,
+        key: ConstantIndex,
+        instruction_ip: usize,
+    ) -> InstructionResult {
+        // Map data lookups go through the access cache,
+        // other accesses fall back to run_access.
+        if let Value::Map(map) = &self.value_stack[self.register_index(value_register)] {
+            let chunk = &self.reader.chunk;
+            let key_string = chunk.constants.get_str(key);
+            let data = map.data();
+            if let Some(value) = self
+                .access_cache
+                .get(chunk, instruction_ip, &data, key_string)
+            {
+                drop(data);
+                self.set_register(result_register, value);
+                return Ok(());
+            }
+        }
+
+        self.run_access(
+            result_register,
+            value_register,
+            self.value_string_from_constant(key),
+        )
+    }
+
     fn run_access(
         &mut self,
         result_register: u8,
@@ -2712,7 +4460,7 @@ impl Vm {
                 SimpleFunction(f) => {
                     let f_as_instance_function = FunctionInfo {
                         chunk: f.chunk,
-                        ip: f.ip,
+                        ip: f.ip as FunctionIp,
                         arg_count: f.arg_count,
                         instance_function: true,
                         variadic: false,
@@ -2773,16 +4521,56 @@ impl Vm {
             frame_base + 1
         };
 
-        let result = (&*function)(
-            self,
-            &Args {
-                register: adjusted_frame_base,
-                count: call_arg_count,
-            },
-        );
+        let args = Args {
+            register: adjusted_frame_base,
+            count: call_arg_count,
+        };
+
+        // When allocations are being tracked, lists and maps that grow during the call are
+        // accounted for
+        let track_allocations =
+            self.context_shared.memory.is_some() || self.context_shared.profiler.is_some();
+        let args_size = if track_allocations {
+            self.args_allocation_size(&args)
+        } else {
+            0
+        };
+
+        #[cfg(feature = "tracing")]
+        let trace_span =
+            tracing::trace_span!(parent: &self.parent_trace_span(), "koto.external_call");
+        #[cfg(feature = "tracing")]
+        let _trace_guard = trace_span.enter();
+
+        self.external_call_depth += 1;
+        let result = function(self, &args);
+        self.external_call_depth -= 1;
 
         match result {
             Ok(value) => {
+                if Pending::is_pending(&value) {
+                    // Execution can only be suspended when the call was made by the script's own
+                    // instructions while running with run_async.
+                    if self.running_async && self.external_call_depth == 0 {
+                        self.suspended_call = Some(result_register);
+                    } else {
+                        return runtime_error!(
+                            "Async functions can only be called by scripts that are run \
+                             asynchronously, and not by other external functions"
+                        );
+                    }
+                }
+
+                if track_allocations {
+                    let growth = self.args_allocation_size(&args).saturating_sub(args_size);
+                    let new_size = memory::new_allocation_size(&value);
+                    self.track_allocation(growth + new_size)?;
+                    if new_size > 0 {
+                        self.track_value_memory(&value)?;
+                    }
+                }
+
+                self.track_with_gc(&value);
                 self.set_register(result_register, value);
                 // External function calls don't use the push/pop frame mechanism,
                 // so drop the function args here now that the call has been completed.
@@ -2803,6 +4591,34 @@ impl Vm {
         instance_register: Option<u8>,
         temp_tuple_values: Option<&[Value]>,
     ) -> InstructionResult {
+        let instance = instance_register.map(|register| self.clone_register(register));
+        let generator_vm = self.make_generator_vm(
+            function,
+            instance,
+            self.register_slice(frame_base + 1, call_arg_count),
+            temp_tuple_values,
+        )?;
+
+        // The args have been cloned into the generator vm, so at this point they can be removed
+        self.truncate_registers(frame_base);
+
+        // Wrap the generator vm in an iterator and place it in the result register
+        self.set_register(result_register, ValueIterator::with_vm(generator_vm).into());
+
+        Ok(())
+    }
+
+    // Spawns a VM that's ready to run a generator function
+    //
+    // The function's frame is pushed in the new VM, with the args, captures, and any temp tuple
+    // values placed in its registers. Used for generator calls, and for starting coroutines.
+    pub(crate) fn make_generator_vm(
+        &self,
+        function: FunctionInfo,
+        instance: Option<Value>,
+        args: &[Value],
+        temp_tuple_values: Option<&[Value]>,
+    ) -> Result<Vm, RuntimeError> {
         let FunctionInfo {
             chunk,
             ip: function_ip,
@@ -2813,6 +4629,12 @@ impl Vm {
             arg_is_unpacked_tuple: _unused,
         } = function;
 
+        // FunctionIp is narrower than usize when the compact_value feature is enabled
+        #[allow(clippy::unnecessary_cast)]
+        let function_ip = function_ip as usize;
+
+        let call_arg_count = args.len() as u8;
+
         // Spawn a VM for the generator
         let mut generator_vm = self.spawn_shared_vm();
         // Push a frame for running the generator function
@@ -2830,8 +4652,7 @@ impl Vm {
 
         // Copy the instance value into the generator vm
         let arg_offset = if instance_function {
-            if let Some(instance_register) = instance_register {
-                let instance = self.clone_register(instance_register);
+            if let Some(instance) = instance {
                 // Place the instance in the first register of the generator vm
                 generator_vm.set_register(0, instance);
                 1
@@ -2843,9 +4664,9 @@ impl Vm {
         };
 
         // Copy any regular (non-instance, non-variadic) arguments into the generator vm
-        for (arg_index, arg) in self
-            .register_slice(frame_base + 1, expected_arg_count.min(call_arg_count))
+        for (arg_index, arg) in args
             .iter()
+            .take(expected_arg_count as usize)
             .cloned()
             .enumerate()
         {
@@ -2865,10 +4686,7 @@ impl Vm {
             if call_arg_count >= expected_arg_count {
                 // Capture the varargs into a tuple and place them in the
                 // generator vm's last arg register
-                let varargs_start = frame_base + 1 + expected_arg_count;
-                let varargs_count = call_arg_count - expected_arg_count;
-                let varargs =
-                    Value::Tuple(self.register_slice(varargs_start, varargs_count).into());
+                let varargs = Value::Tuple(args[expected_arg_count as usize..].into());
                 generator_vm.set_register(variadic_register, varargs);
             } else {
                 generator_vm.set_register(variadic_register, Value::Empty);
@@ -2888,13 +4706,16 @@ impl Vm {
                 .extend_from_slice(temp_tuple_values);
         }
 
-        // The args have been cloned into the generator vm, so at this point they can be removed
-        self.truncate_registers(frame_base);
-
-        // Wrap the generator vm in an iterator and place it in the result register
-        self.set_register(result_register, ValueIterator::with_vm(generator_vm).into());
+        Ok(generator_vm)
+    }
 
-        Ok(())
+    // Continues running a suspended generator VM, with the value used as the result of the
+    // yield expression that suspended it
+    pub(crate) fn resume_generator(&mut self, value: Value) -> RuntimeResult {
+        if let Some(register) = self.yield_register.take() {
+            self.set_register(register, value);
+        }
+        self.continue_running()
     }
 
     fn call_function(
@@ -2906,6 +4727,8 @@ impl Vm {
         instance_register: Option<u8>,
         temp_tuple_values: Option<&[Value]>,
     ) -> InstructionResult {
+        self.check_call_depth()?;
+
         let FunctionInfo {
             chunk,
             ip: function_ip,
@@ -2916,6 +4739,10 @@ impl Vm {
             arg_is_unpacked_tuple: _unused,
         } = function;
 
+        // FunctionIp is narrower than usize when the compact_value feature is enabled
+        #[allow(clippy::unnecessary_cast)]
+        let function_ip = function_ip as usize;
+
         let expected_arg_count = match (instance_function, variadic) {
             (true, true) => function_arg_count - 2,
             (true, false) | (false, true) => function_arg_count - 1,
@@ -2973,7 +4800,94 @@ impl Vm {
         }
 
         // Set up a new frame for the called function
+        let call_base = self.register_index(frame_base);
         self.push_frame(chunk, function_ip, adjusted_frame_base);
+        self.frame_mut().call_base = call_base;
+
+        Ok(())
+    }
+
+    // Calls a function in return position
+    //
+    // If tail call elimination is enabled, and the function is a Koto function, then the current
+    // frame is replaced by the called function's frame. Otherwise a regular call is made, with the
+    // result then being returned by the following Return instruction.
+    fn run_tail_call(
+        &mut self,
+        result_register: u8,
+        function_register: u8,
+        frame_base: u8,
+        arg_count: u8,
+        instance_register: Option<u8>,
+    ) -> InstructionResult {
+        use Value::*;
+
+        let function = self.clone_register(function_register);
+
+        let frame_can_be_replaced = self.context_shared.eliminate_tail_calls
+            // Catch points in the current frame need to remain active while the call is running
+            && self.frame().catch_stack.is_empty()
+            && match &function {
+                SimpleFunction(_) => true,
+                // Instance functions called without an instance produce an error in call_function,
+                // which should be thrown from the current frame.
+                Function(info) => !info.instance_function || instance_register.is_some(),
+                _ => false,
+            };
+
+        if !frame_can_be_replaced {
+            return self.call_callable(
+                result_register,
+                function,
+                frame_base,
+                arg_count,
+                instance_register,
+                None,
+            );
+        }
+
+        // Place the instance in the call's frame base, so that it gets moved along with the args
+        if let Some(instance_register) = instance_register {
+            if instance_register != frame_base {
+                let instance = self.clone_register(instance_register);
+                self.set_register(frame_base, instance);
+            }
+        }
+
+        // Move the call's frame base and args down to the replaced frame's call base,
+        // discarding the replaced frame's registers.
+        // The frame base register is only written to for instance calls, so the stack is resized
+        // rather than truncated to ensure that it's included.
+        let call_start = self.register_index(frame_base);
+        let call_end = call_start + 1 + arg_count as usize;
+        let replaced_frame = self.call_stack.pop().expect("Empty call stack");
+        self.finish_frame_profile(&replaced_frame);
+        self.value_stack.resize(call_end, Value::Empty);
+        self.value_stack.drain(replaced_frame.call_base..call_start);
+
+        // Make the call on behalf of the caller, keeping the caller's return register and ip
+        let caller_frame_base = (replaced_frame.call_base - self.register_base()) as u8;
+        let caller_return = self
+            .call_stack
+            .last()
+            .and_then(|caller| caller.return_register_and_ip);
+        let caller_result_register =
+            caller_return.map_or(caller_frame_base, |(register, _)| register);
+
+        self.call_callable(
+            caller_result_register,
+            function,
+            caller_frame_base,
+            arg_count,
+            instance_register.map(|_| caller_frame_base),
+            None,
+        )?;
+
+        let frame_count = self.call_stack.len();
+        if frame_count > 1 {
+            self.call_stack[frame_count - 2].return_register_and_ip = caller_return;
+        }
+        self.frame_mut().execution_barrier = replaced_frame.execution_barrier;
 
         Ok(())
     }
@@ -2995,6 +4909,10 @@ impl Vm {
                 ip: function_ip,
                 arg_count,
             }) => {
+                self.check_call_depth()?;
+
+                let call_base = self.register_index(frame_base);
+
                 // The frame base is offset by one since the frame's instance register is unused.
                 let frame_base = frame_base + 1;
 
@@ -3011,6 +4929,7 @@ impl Vm {
 
                 // Set up a new frame for the called function
                 self.push_frame(chunk, function_ip, frame_base);
+                self.frame_mut().call_base = call_base;
 
                 Ok(())
             }
@@ -3070,10 +4989,47 @@ impl Vm {
 
         let expression_string = self.get_constant_str(expression_constant);
 
-        self.stdout().write_line(&format!(
-            "{}{}: {}",
-            prefix, expression_string, value_string
-        ))
+        // The expression string is empty when the chunk was compiled without debug info
+        if expression_string.is_empty() {
+            self.stdout()
+                .write_line(&format!("{}{}", prefix, value_string))
+        } else {
+            self.stdout().write_line(&format!(
+                "{}{}: {}",
+                prefix, expression_string, value_string
+            ))
+        }
+    }
+
+    fn run_assert(
+        &self,
+        register: u8,
+        compared_registers: Option<(u8, u8)>,
+        expression_constant: ConstantIndex,
+    ) -> InstructionResult {
+        match self.get_register(register) {
+            Value::Bool(true) => Ok(()),
+            Value::Bool(false) => {
+                let expression_string = self.get_constant_str(expression_constant);
+                // The expression string is empty when the chunk was compiled without debug info
+                let separator = if expression_string.is_empty() {
+                    ""
+                } else {
+                    ": "
+                };
+                match compared_registers {
+                    Some((lhs, rhs)) => runtime_error!(
+                        "assertion failed{}{} (lhs: {}, rhs: {})",
+                        separator,
+                        expression_string,
+                        self.get_register(lhs),
+                        self.get_register(rhs)
+                    ),
+                    None => runtime_error!("assertion failed{}{}", separator, expression_string),
+                }
+            }
+            unexpected => self.unexpected_type_error("assert: Expected Bool", unexpected),
+        }
     }
 
     fn run_check_type(&self, register: u8, type_id: TypeId) -> InstructionResult {
@@ -3093,6 +5049,35 @@ impl Vm {
         Ok(())
     }
 
+    fn run_check_type_annotation(
+        &self,
+        register: u8,
+        type_name: ConstantIndex,
+    ) -> InstructionResult {
+        use Value::*;
+
+        if !self.context_shared.check_type_annotations {
+            return Ok(());
+        }
+
+        let value = self.get_register(register);
+        let type_name = self.get_constant_str(type_name);
+
+        let type_matches = match type_name {
+            "Any" => true,
+            "Number" => matches!(value, Number(_)),
+            "Function" => matches!(value, SimpleFunction(_) | Function(_) | ExternalFunction(_)),
+            "Tuple" => matches!(value, Tuple(_) | TemporaryTuple(_)),
+            _ => value.type_as_string() == type_name,
+        };
+
+        if type_matches {
+            Ok(())
+        } else {
+            self.unexpected_type_error(&format!("Expected '{}'", type_name), value)
+        }
+    }
+
     fn run_check_size(&self, register: u8, expected_size: usize) -> InstructionResult {
         let value_size = self.get_register(register).size();
 
@@ -3112,6 +5097,7 @@ impl Vm {
         sequence_register: u8,
         value_register: u8,
     ) -> InstructionResult {
+        self.track_allocation(VALUE_SIZE)?;
         let value = self.clone_register(value_register);
         match self.get_register_mut(sequence_register) {
             Value::SequenceBuilder(builder) => {
@@ -3131,8 +5117,10 @@ impl Vm {
         // Move the sequence builder out of its register to avoid cloning the Vec
         match self.remove_register(register) {
             Value::SequenceBuilder(result) => {
-                let list = ValueList::with_data(ValueVec::from_vec(result));
-                self.set_register(register, Value::List(list));
+                let list = Value::List(ValueList::with_data(ValueVec::from_vec(result)));
+                self.track_value_memory(&list)?;
+                self.track_with_gc(&list);
+                self.set_register(register, list);
                 Ok(())
             }
             other => self.unexpected_type_error("SequenceToList: Expected SequenceBuilder", &other),
@@ -3143,7 +5131,9 @@ impl Vm {
         // Move the sequence builder out of its register to avoid cloning the Vec
         match self.remove_register(register) {
             Value::SequenceBuilder(result) => {
-                self.set_register(register, Value::Tuple(ValueTuple::from(result)));
+                let tuple = Value::Tuple(ValueTuple::from(result));
+                self.track_value_memory(&tuple)?;
+                self.set_register(register, tuple);
                 Ok(())
             }
             other => self.unexpected_type_error("SequenceToList: Expected SequenceBuilder", &other),
@@ -3157,6 +5147,7 @@ impl Vm {
         // Add the resulting string to the string builder
         match display_result {
             Value::Str(string) => {
+                self.track_allocation(string.len())?;
                 match self.get_register_mut(register) {
                     Value::StringBuilder(builder) => {
                         builder.push_str(&string);
@@ -3180,7 +5171,9 @@ impl Vm {
         match self.remove_register(register) {
             Value::StringBuilder(result) => {
                 // Make a ValueString out of the string builder's contents
-                self.set_register(register, Value::Str(ValueString::from(result)));
+                let string = Value::Str(ValueString::from(result));
+                self.track_value_memory(&string)?;
+                self.set_register(register, string);
                 Ok(())
             }
             other => self.unexpected_type_error("StringFinish: Expected StringBuilder", &other),
@@ -3195,7 +5188,11 @@ impl Vm {
         self.reader = InstructionReader { chunk, ip };
     }
 
-    fn ip(&self) -> usize {
+    /// Returns the position of the next instruction to be executed in the current chunk
+    ///
+    /// This can be used along with [Vm::chunk] and [Chunk::source_span] to find the position
+    /// in the source that's currently being executed, e.g. after a call to [Vm::step].
+    pub fn ip(&self) -> usize {
         self.reader.ip
     }
 
@@ -3227,9 +5224,133 @@ impl Vm {
         };
         let new_frame_base = previous_frame_base + frame_base as usize;
 
-        self.call_stack
-            .push(Frame::new(chunk.clone(), new_frame_base));
+        if self.call_stack.is_empty() {
+            // Make sure that the first statement is reported to the debug hook
+            self.debug_position = None;
+        }
+
+        let mut frame = Frame::new(chunk.clone(), new_frame_base);
+        if self.context_shared.profiler.is_some() {
+            let is_recursive = self.call_stack.iter().any(|caller| {
+                Arc::ptr_eq(&caller.chunk, &chunk)
+                    && matches!(&caller.profile, Some(profile) if profile.function_ip == ip)
+            });
+            frame.profile = Some(FrameProfile::new(ip, is_recursive));
+        }
+        #[cfg(feature = "tracing")]
+        {
+            frame.trace_span = self.make_call_trace_span(&chunk, ip);
+        }
+
+        self.call_stack.push(frame);
         self.set_chunk_and_ip(chunk, ip);
+
+        if let Some(hook) = &self.context_shared.debug_hook {
+            hook.on_function_entry(&DebugFrame::new(self, ip));
+        }
+    }
+
+    // Reports the start of a new statement to the debug hook, along with any matching breakpoint
+    fn report_statement(&mut self, hook: Arc<dyn DebugHook>) {
+        let ip = self.ip();
+        let chunk = self.chunk();
+        let line = match chunk.source_span(ip) {
+            Some(span) => span.start.line,
+            None => return,
+        };
+
+        let position = (Arc::as_ptr(&chunk) as usize, line, self.call_stack.len());
+        if self.debug_position == Some(position) {
+            return;
+        }
+        self.debug_position = Some(position);
+
+        let frame = DebugFrame::new(self, ip);
+        hook.on_statement(&frame);
+
+        let breakpoint = Breakpoint {
+            path: chunk.source_path.clone(),
+            line,
+        };
+        if self.context_shared.breakpoints.read().contains(&breakpoint) {
+            hook.on_breakpoint(&frame, &breakpoint);
+        }
+    }
+
+    pub(crate) fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    pub(crate) fn value_stack(&self) -> &[Value] {
+        &self.value_stack
+    }
+
+    // Returns the span of the innermost traced call,
+    // or the current span if none of the frames are being traced
+    #[cfg(feature = "tracing")]
+    fn parent_trace_span(&self) -> tracing::Span {
+        self.call_stack
+            .iter()
+            .rev()
+            .find_map(|frame| frame.trace_span.clone())
+            .unwrap_or_else(tracing::Span::current)
+    }
+
+    // Makes a span for a call to a Koto function if the call is sampled,
+    // see VmSettings::trace_sample_interval
+    //
+    // The span isn't entered, it's closed when the call's frame is dropped.
+    #[cfg(feature = "tracing")]
+    fn make_call_trace_span(&self, chunk: &Chunk, ip: usize) -> Option<tracing::Span> {
+        let interval = self.context_shared.trace_sample_interval;
+        if interval == 0
+            || self
+                .context_shared
+                .call_count
+                .fetch_add(1, Ordering::Relaxed)
+                % interval
+                != 0
+        {
+            return None;
+        }
+
+        let function = match chunk.debug_info.function_name(ip) {
+            Some(name) => name,
+            None if ip == 0 => "<top level>",
+            None => "<anonymous>",
+        };
+        let path = match &chunk.source_path {
+            Some(path) => path.to_string_lossy(),
+            None => "<script>".into(),
+        };
+
+        Some(tracing::debug_span!(
+            parent: &self.parent_trace_span(),
+            "koto.call",
+            function,
+            path = %path,
+            line = chunk.source_span(ip).map(|span| span.start.line),
+        ))
+    }
+
+    // Records the statistics of a frame that has been removed from the call stack,
+    // see VmSettings::profile
+    fn finish_frame_profile(&mut self, frame: &Frame) {
+        let (profiler, profile) = match (&self.context_shared.profiler, &frame.profile) {
+            (Some(profiler), Some(profile)) => (profiler, profile),
+            _ => return,
+        };
+
+        let elapsed = profile.elapsed();
+        profiler.lock().record_call(&frame.chunk, profile, elapsed);
+
+        if let Some(caller_profile) = self
+            .call_stack
+            .last_mut()
+            .and_then(|caller| caller.profile.as_mut())
+        {
+            caller_profile.child_time += elapsed;
+        }
     }
 
     fn pop_frame(&mut self, return_value: Value) -> Result<Option<Value>, RuntimeError> {
@@ -3237,6 +5358,8 @@ impl Vm {
 
         match self.call_stack.pop() {
             Some(popped_frame) => {
+                self.finish_frame_profile(&popped_frame);
+
                 if self.call_stack.is_empty() {
                     Ok(Some(return_value))
                 } else {
@@ -3337,6 +5460,17 @@ impl Vm {
         self.reader.chunk.constants.get_str(constant_index)
     }
 
+    // True if the value is a string from the current chunk's constants
+    //
+    // Constant strings are shared by each use of the constant, so they're used as map keys
+    // without being interned.
+    fn is_constant_string(&self, value: &Value) -> bool {
+        matches!(
+            value,
+            Value::Str(s) if s.shares_data_with(self.reader.chunk.constants.string_data())
+        )
+    }
+
     fn value_string_from_constant(&self, constant_index: ConstantIndex) -> ValueString {
         let constants = &self.reader.chunk.constants;
         let bounds = constants.get_str_bounds(constant_index);
@@ -3360,17 +5494,128 @@ impl Vm {
     }
 }
 
+// Runs a single test with a fresh instance of the module, see Vm::run_module_tests
+fn run_isolated_test(mut vm: Vm, module: Arc<Chunk>, test_name: &ValueString) -> RuntimeResult {
+    if let Err(error) = vm.run(module) {
+        return Err(error.with_prefix(&format!(
+            "Error while preparing the module for test '{}'",
+            test_name
+        )));
+    }
+
+    let tests = vm.context().exports.meta().get(&MetaKey::Tests).cloned();
+    match tests {
+        Some(Value::Map(tests)) => vm.run_tests_with_filter(tests, Some(test_name)),
+        _ => runtime_error!(
+            "Missing tests in the module instance for test '{}'",
+            test_name
+        ),
+    }
+}
+
+#[cfg(feature = "cycle_detection")]
+impl Vm {
+    /// Returns the reference cycles that can be reached from the VM's values
+    ///
+    /// The VM's registers, exports, prelude, and loaded modules are searched, along with the
+    /// lists, maps, tuples, and function captures that they contain. Each cycle is reported once,
+    /// with the path that was taken to reach it.
+    pub fn find_reference_cycles(&self) -> Vec<ReferenceCycle> {
+        self.search_for_reference_cycles(false)
+    }
+
+    /// Breaks the reference cycles that can be reached from the VM's values
+    ///
+    /// The reference that closes each cycle is replaced with `()`, allowing the values in the
+    /// cycle to be dropped. The broken cycles are returned, see [Vm::find_reference_cycles].
+    pub fn break_reference_cycles(&self) -> Vec<ReferenceCycle> {
+        self.search_for_reference_cycles(true)
+    }
+
+    fn search_for_reference_cycles(&self, break_cycles: bool) -> Vec<ReferenceCycle> {
+        let mut finder = CycleFinder::new(break_cycles);
+
+        for (i, value) in self.value_stack.iter().enumerate() {
+            finder.search(&format!("registers[{}]", i), value);
+        }
+
+        // The context's values are cloned so that it isn't locked during the search
+        let (exports, mut modules) = {
+            let context = self.context.read();
+            let modules = context
+                .modules
+                .iter()
+                .filter_map(|(path, module)| module.clone().map(|module| (path.clone(), module)))
+                .collect::<Vec<_>>();
+            (context.exports.clone(), modules)
+        };
+        modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        finder.search("exports", &Value::Map(exports));
+        finder.search("prelude", &Value::Map(self.prelude()));
+        for (path, module) in modules {
+            finder.search(&format!("modules[{}]", path.display()), &Value::Map(module));
+        }
+
+        finder.finish()
+    }
+}
+
+#[cfg(feature = "gc")]
+impl Vm {
+    /// Runs the garbage collector, freeing any unreachable reference cycles
+    ///
+    /// Returns the number of lists, maps, and function captures that were freed.
+    /// Nothing is collected if the VM was created without a garbage collector,
+    /// see [VmSettings::gc_interval].
+    pub fn collect_garbage(&self) -> usize {
+        self.context_shared
+            .gc
+            .as_ref()
+            .map_or(0, GarbageCollector::collect)
+    }
+}
+
+#[cfg(any(feature = "cycle_detection", feature = "gc"))]
+impl Drop for Vm {
+    fn drop(&mut self) {
+        // Cycles are only freed when the last VM that shares the context is dropped,
+        // otherwise the values might still be in use.
+        if Arc::strong_count(&self.context_shared) != 1 {
+            return;
+        }
+
+        #[cfg(feature = "cycle_detection")]
+        if self.context_shared.break_cycles_on_drop {
+            self.break_reference_cycles();
+        }
+
+        #[cfg(feature = "gc")]
+        if self.context_shared.gc.is_some() {
+            // The VM's registers and module context are released first,
+            // so that only cycles that are referred to from elsewhere are kept alive.
+            self.value_stack.clear();
+            let context = std::mem::take(&mut *self.context.write());
+            drop(context);
+            self.collect_garbage();
+        }
+    }
+}
+
 impl fmt::Debug for Vm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("Vm")
     }
 }
 
-fn signed_index_to_unsigned(index: i8, size: usize) -> usize {
+// Converts an index that counts back from the end when negative into an unsigned index
+//
+// None is returned when a negative index is before the start of the sequence.
+fn signed_index_to_unsigned(index: i8, size: usize) -> Option<usize> {
     if index < 0 {
-        size - (index.abs() as usize).min(size)
+        size.checked_sub(index.unsigned_abs() as usize)
     } else {
-        index as usize
+        Some(index as usize)
     }
 }
 
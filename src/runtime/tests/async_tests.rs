@@ -0,0 +1,180 @@
+mod async_calls {
+    use {
+        koto_runtime::{Loader, Pending, Value, ValueMap, ValueNumber, Vm},
+        std::{
+            future::Future,
+            pin::Pin,
+            sync::Arc,
+            task::{Context, Poll, Wake, Waker},
+            thread::{self, Thread},
+        },
+    };
+
+    // A minimal executor that polls a future to completion on the current thread
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    // A future that returns Pending once before completing
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                context.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    fn make_vm() -> Vm {
+        let vm = Vm::default();
+        let mut async_module = ValueMap::new();
+
+        async_module.add_fn("double", |vm, args| match vm.get_args(args) {
+            [Value::Number(n)] => {
+                let n = *n;
+                Ok(Pending::new(async move {
+                    YieldOnce(false).await;
+                    Ok(Value::Number(n * ValueNumber::from(2)))
+                }))
+            }
+            _ => koto_runtime::runtime_error!("async.double: Expected a number"),
+        });
+
+        async_module.add_fn("fail", |_, _| {
+            Ok(Pending::new(async {
+                YieldOnce(false).await;
+                koto_runtime::runtime_error!("async failure")
+            }))
+        });
+
+        vm.prelude().add_map("async", async_module);
+        vm
+    }
+
+    fn run_script_async(script: &str) -> Result<Value, String> {
+        let mut vm = make_vm();
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+        block_on(vm.run_async(chunk)).map_err(|e| e.to_string())
+    }
+
+    fn check_number(result: Result<Value, String>, expected: f64) {
+        match result {
+            Ok(Value::Number(n)) if n == expected => {}
+            other => panic!("Expected {}, found {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn async_call_result() {
+        let script = "
+f = |x| (async.double x) + 1
+f 20
+";
+        check_number(run_script_async(script), 41.0);
+    }
+
+    #[test]
+    fn async_calls_in_a_loop() {
+        let script = "
+result = 0
+for i in 1..=3
+  result += async.double i
+result
+";
+        check_number(run_script_async(script), 12.0);
+    }
+
+    #[test]
+    fn async_errors_can_be_caught() {
+        let script = "
+x = try
+  async.fail()
+catch error
+  'caught: $error'
+x
+";
+        match run_script_async(script) {
+            Ok(Value::Str(s)) => assert!(s.contains("caught: async failure"), "{}", s),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn uncaught_async_errors() {
+        let result = run_script_async("async.fail()");
+        match result {
+            Err(error) => assert!(error.contains("async failure"), "{}", error),
+            Ok(value) => panic!("Unexpected success: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn run_function_async() {
+        let mut vm = make_vm();
+        let chunk = Loader::default()
+            .compile_script("export f = |x| async.double x", &None)
+            .unwrap();
+        block_on(vm.run_async(chunk)).unwrap();
+
+        let f = vm.get_exported_function("f").unwrap();
+        let result = block_on(vm.run_function_async(f, koto_runtime::CallArgs::Single(4.into())));
+        check_number(result.map_err(|e| e.to_string()), 8.0);
+    }
+
+    #[test]
+    fn async_calls_fail_when_running_synchronously() {
+        let mut vm = make_vm();
+        let chunk = Loader::default()
+            .compile_script("async.double 1", &None)
+            .unwrap();
+        match vm.run(chunk) {
+            Err(error) => assert!(error.to_string().contains("asynchronously")),
+            Ok(value) => panic!("Unexpected success: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn async_calls_fail_when_called_by_external_functions() {
+        let script = "
+[1, 2].each(|x| async.double x).to_list()
+";
+        match run_script_async(script) {
+            Err(error) => assert!(error.contains("asynchronously"), "{}", error),
+            Ok(value) => panic!("Unexpected success: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn run_async_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let mut vm = make_vm();
+        let chunk = Loader::default().compile_script("1", &None).unwrap();
+        let future = vm.run_async(chunk);
+        assert_send(&future);
+        block_on(future).unwrap();
+    }
+}
@@ -0,0 +1,281 @@
+mod runtime_test_utils;
+
+mod callbacks {
+    use {
+        crate::runtime_test_utils::{number, number_list, run_script, test_script_with_vm},
+        koto_runtime::{runtime_error, CallArgs, Loader, Value, Vm},
+        std::{
+            sync::{Arc, Mutex},
+            thread,
+        },
+    };
+
+    // Callbacks that have been stored by `on_event`, along with the VMs that will call them
+    type Handlers = Arc<Mutex<Vec<(Vm, Value)>>>;
+
+    fn make_vm() -> (Vm, Handlers) {
+        let vm = Vm::default();
+        let handlers = Handlers::default();
+        let mut prelude = vm.prelude();
+
+        prelude.add_fn("call_with", |vm, args| match vm.get_args(args) {
+            [f, value] if f.is_callable() => {
+                let f = f.clone();
+                let value = value.clone();
+                vm.run_function(f, CallArgs::Single(value))
+            }
+            _ => runtime_error!("call_with: Expected function and value as arguments"),
+        });
+
+        prelude.add_fn("on_event", {
+            let handlers = handlers.clone();
+            move |vm, args| match vm.get_args(args) {
+                [f] if f.is_callable() => {
+                    let f = f.clone();
+                    handlers.lock().unwrap().push((vm.spawn_shared_vm(), f));
+                    Ok(Value::Empty)
+                }
+                _ => runtime_error!("on_event: Expected function as argument"),
+            }
+        });
+
+        prelude.add_fn("emit", {
+            let handlers = handlers.clone();
+            move |vm, args| match vm.get_args(args) {
+                [value] => {
+                    let value = value.clone();
+                    emit(&handlers, value)?;
+                    Ok(Value::Empty)
+                }
+                _ => runtime_error!("emit: Expected a value as argument"),
+            }
+        });
+
+        (vm, handlers)
+    }
+
+    // Calls each of the stored handlers
+    //
+    // The handlers are taken out of the mutex while they're being called,
+    // so that handlers are free to register new handlers.
+    fn emit(handlers: &Handlers, value: Value) -> Result<(), koto_runtime::RuntimeError> {
+        let mut called = std::mem::take(&mut *handlers.lock().unwrap());
+        let mut result = Ok(());
+        for (vm, f) in called.iter_mut() {
+            if let Err(error) = vm.run_function(f.clone(), CallArgs::Single(value.clone())) {
+                result = Err(error);
+                break;
+            }
+        }
+        let mut handlers = handlers.lock().unwrap();
+        let added = std::mem::replace(&mut *handlers, called);
+        handlers.extend(added);
+        result
+    }
+
+    #[test]
+    fn calling_a_callback() {
+        let (vm, _) = make_vm();
+        test_script_with_vm(vm, "call_with (|x| x * 2), 21", number(42));
+    }
+
+    #[test]
+    fn nested_callbacks() {
+        let (vm, _) = make_vm();
+        let script = "
+f = |n|
+  if n == 0
+    0
+  else
+    n + call_with f, n - 1
+call_with f, 10
+";
+        test_script_with_vm(vm, script, number(55));
+    }
+
+    #[test]
+    fn callback_modifies_its_captured_list() {
+        let (vm, _) = make_vm();
+        let script = "
+x = [1, 2, 3]
+call_with (|n| x.push n), 4
+x
+";
+        test_script_with_vm(vm, script, number_list(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn stored_callbacks_called_by_the_script() {
+        let (vm, _) = make_vm();
+        let script = "
+received = []
+on_event |x| received.push x
+on_event |x| received.push x * 10
+emit 1
+emit 2
+received
+";
+        test_script_with_vm(vm, script, number_list(&[1, 10, 2, 20]));
+    }
+
+    #[test]
+    fn stored_callbacks_can_register_handlers() {
+        let (vm, handlers) = make_vm();
+        let script = "
+on_event |_| on_event |x| x
+emit 1
+";
+        test_script_with_vm(vm, script, Value::Empty);
+        assert_eq!(handlers.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn stored_callbacks_called_by_the_host() {
+        let (mut vm, handlers) = make_vm();
+        run_script(
+            &mut vm,
+            "
+export received = []
+on_event |x| received.push x
+",
+        );
+
+        // The script has finished, the callbacks can still be called with the spawned VMs
+        emit(&handlers, number(1)).unwrap();
+
+        // ...and from other threads
+        thread::spawn({
+            let handlers = handlers.clone();
+            move || emit(&handlers, number(2)).unwrap()
+        })
+        .join()
+        .unwrap();
+
+        assert!(matches!(
+            run_script(&mut vm, "received == [1, 2]"),
+            Value::Bool(true)
+        ));
+    }
+
+    #[test]
+    fn errors_are_propagated_from_callbacks() {
+        let (mut vm, _) = make_vm();
+        let chunk = Loader::default()
+            .compile_script("call_with (|x| throw 'oops: {}'.format x), 42", &None)
+            .unwrap();
+        match vm.run(chunk) {
+            Err(error) => assert!(error.to_string().starts_with("oops: 42")),
+            Ok(value) => panic!("Expected an error, found: {}", value),
+        }
+    }
+
+    mod core_functions_calling_back_into_their_container {
+        use super::*;
+
+        fn test_script(script: &str, expected_output: Value) {
+            test_script_with_vm(Vm::default(), script, expected_output);
+        }
+
+        #[test]
+        fn list_contains() {
+            let script = "
+x = [1, 2]
+foo =
+  @==: |self, other|
+    x[0] = 99
+    false
+x.contains foo
+x
+";
+            test_script(script, number_list(&[99, 2]));
+        }
+
+        #[test]
+        fn list_retain_with_predicate() {
+            let script = "
+x = [1, 2, 3, 4]
+x.retain |n|
+  x.remove x.size() - 1
+  true
+x
+";
+            // The predicate is called with each of the initial values
+            test_script(script, number_list(&[1, 2, 3, 4]));
+        }
+
+        #[test]
+        fn list_retain_with_value() {
+            let script = "
+x = []
+foo = {@==: |self, other| x.size() > 2}
+x.resize 3, foo
+x.retain 1
+x.size()
+";
+            test_script(script, number(3));
+        }
+
+        #[test]
+        fn list_sort() {
+            let script = "
+foo = |n|
+  n: n
+  @<: |self, other|
+    x[0] = x[0]
+    self.n < other.n
+  @>: |self, other| self.n > other.n
+export x = [(foo 3), (foo 1), (foo 2)]
+x.sort()
+x.transform |foo| foo.n
+x
+";
+            test_script(script, number_list(&[1, 2, 3]));
+        }
+
+        #[test]
+        fn list_sort_with_key() {
+            let script = "
+x = [3, 1, 2]
+x.sort |n| n + x.size()
+x
+";
+            test_script(script, number_list(&[1, 2, 3]));
+        }
+
+        #[test]
+        fn list_transform() {
+            let script = "
+x = [1, 2, 3]
+x.transform |n| n + x.size()
+x
+";
+            test_script(script, number_list(&[4, 5, 6]));
+        }
+
+        #[test]
+        fn map_sort_with_key() {
+            let script = "
+x = {c: 3, a: 1, b: 2}
+x.sort |key, value| value + x.size()
+x.keys().to_tuple()
+";
+            test_script(
+                script,
+                Value::Tuple(vec!["a".into(), "b".into(), "c".into()].into()),
+            );
+        }
+
+        #[test]
+        fn string_format_with_map_argument() {
+            let script = "
+x = {}
+x.foo =
+  @display: |self|
+    x.bar = 99
+    'foo'
+'{foo}'.format x
+";
+            test_script(script, "foo".into());
+        }
+    }
+}
@@ -0,0 +1,57 @@
+mod runtime_test_utils;
+
+mod channels {
+    use {
+        crate::runtime_test_utils::run_script,
+        koto_runtime::{channel, Value, Vm},
+        std::thread,
+    };
+
+    #[test]
+    fn values_can_be_sent_between_vms_on_different_threads() {
+        let (jobs_tx, jobs_rx) = channel(None);
+        let (results_tx, results_rx) = channel(Some(1));
+
+        let worker = thread::spawn({
+            let mut vm = Vm::default();
+            vm.prelude().add_value("jobs", jobs_rx.into());
+            vm.prelude().add_value("results", results_tx.into());
+            move || {
+                let script = "
+while true
+  match jobs.receive()
+    () then break
+    job then results.send {input: job.input, output: job.input.to_uppercase()}
+";
+                run_script(&mut vm, script);
+            }
+        });
+
+        let mut vm = Vm::default();
+        vm.prelude().add_value("jobs", jobs_tx.clone().into());
+        run_script(&mut vm, "jobs.send {input: 'hello'}");
+
+        match results_rx.receive() {
+            Some(Value::Map(result)) => {
+                let data = result.data();
+                assert!(
+                    matches!(data.get_with_string("output"), Some(Value::Str(s)) if s.as_str() == "HELLO")
+                );
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        jobs_tx.send(&Value::Empty).unwrap();
+        worker.join().unwrap();
+
+        // The worker's VM has been dropped along with its sender
+        assert!(results_rx.receive().is_none());
+    }
+
+    #[test]
+    fn sending_to_a_closed_channel_is_an_error() {
+        let (tx, rx) = channel(None);
+        drop(rx);
+        assert!(tx.send(&Value::Number(1.into())).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+mod runtime_test_utils;
+
+mod clock {
+    use {
+        crate::runtime_test_utils::{number, test_script_with_vm},
+        koto_runtime::{KotoClock, Mutex, Vm, VmSettings},
+        std::{sync::Arc, time::Duration},
+    };
+
+    // A clock that only advances when sleep is called
+    #[derive(Default)]
+    struct SimulatedClock {
+        time: Mutex<Duration>,
+    }
+
+    impl KotoClock for SimulatedClock {
+        fn now(&self) -> Duration {
+            *self.time.lock()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            *self.time.lock() += duration;
+        }
+    }
+
+    fn make_vm(start: Duration) -> (Vm, Arc<SimulatedClock>) {
+        let clock = Arc::new(SimulatedClock {
+            time: Mutex::new(start),
+        });
+        let vm = Vm::with_settings(VmSettings {
+            clock: clock.clone(),
+            ..Default::default()
+        });
+        (vm, clock)
+    }
+
+    #[test]
+    fn os_time() {
+        let (vm, _) = make_vm(Duration::from_secs_f64(42.5));
+        test_script_with_vm(vm, "os.time()", number(42.5));
+    }
+
+    #[test]
+    fn thread_sleep_advances_the_clock() {
+        let (vm, clock) = make_vm(Duration::from_secs(10));
+        // A simulated clock allows long sleeps to complete immediately
+        let script = "
+start = os.time()
+thread.sleep 3600
+thread.sleep 0.5
+os.time() - start
+";
+        test_script_with_vm(vm, script, number(3600.5));
+        assert_eq!(clock.now(), Duration::from_secs_f64(3610.5));
+    }
+
+    #[test]
+    fn clock_is_shared_with_spawned_vms() {
+        let (vm, _) = make_vm(Duration::from_secs(100));
+        let script = "
+t = thread.create ||
+  thread.sleep 10
+  os.time()
+t.join()
+";
+        test_script_with_vm(vm, script, number(110));
+    }
+
+    #[test]
+    fn clock_is_available_to_external_functions() {
+        let (vm, clock) = make_vm(Duration::from_secs(1));
+        vm.prelude().add_fn("elapsed_ms", |vm, _| {
+            Ok(number(vm.clock().now().as_millis() as f64))
+        });
+        clock.sleep(Duration::from_millis(250));
+        test_script_with_vm(vm, "elapsed_ms()", number(1250));
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "cycle_detection")]
+
+mod cycles {
+    use koto_runtime::{Loader, Value, Vm, VmSettings};
+
+    fn run_script(vm: &mut Vm, script: &str) {
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+        if let Err(error) = vm.run(chunk) {
+            panic!("Error while running script: {}", error);
+        }
+    }
+
+    fn cycle_paths(vm: &Vm) -> Vec<String> {
+        vm.find_reference_cycles()
+            .iter()
+            .map(|cycle| cycle.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn no_cycles() {
+        let mut vm = Vm::default();
+        run_script(
+            &mut vm,
+            "
+x = [1, 2, 3]
+export foo = {bar: x, baz: x}
+",
+        );
+        assert!(vm.find_reference_cycles().is_empty());
+    }
+
+    #[test]
+    fn list_cycle() {
+        let mut vm = Vm::default();
+        run_script(
+            &mut vm,
+            "
+export foo = [1, 2]
+foo.push foo
+",
+        );
+        assert_eq!(cycle_paths(&vm), vec!["exports.foo[2] -> exports.foo"]);
+    }
+
+    #[test]
+    fn map_cycle_through_a_list() {
+        let mut vm = Vm::default();
+        run_script(
+            &mut vm,
+            "
+export foo = {}
+foo.bar = [foo]
+",
+        );
+        assert_eq!(cycle_paths(&vm), vec!["exports.foo.bar[0] -> exports.foo"]);
+    }
+
+    #[test]
+    fn captured_cycle() {
+        let mut vm = Vm::default();
+        run_script(
+            &mut vm,
+            "
+make_foo = ||
+  x = {}
+  x.f = || x
+  x
+export foo = make_foo()
+",
+        );
+        assert_eq!(
+            cycle_paths(&vm),
+            vec!["exports.foo.f<captures>[0] -> exports.foo"]
+        );
+    }
+
+    #[test]
+    fn break_cycles() {
+        let mut vm = Vm::default();
+        run_script(
+            &mut vm,
+            "
+export foo = {}
+foo.bar = [foo, 42]
+",
+        );
+        assert_eq!(vm.break_reference_cycles().len(), 1);
+        assert!(vm.find_reference_cycles().is_empty());
+
+        let bar = vm
+            .context()
+            .exports
+            .data()
+            .get_with_string("foo")
+            .and_then(|foo| match foo {
+                Value::Map(foo) => foo.data().get_with_string("bar").cloned(),
+                _ => None,
+            });
+        match bar {
+            Some(Value::List(bar)) => {
+                assert!(matches!(bar.data()[0], Value::Empty));
+                assert!(matches!(&bar.data()[1], Value::Number(n) if *n == 42));
+            }
+            other => panic!("Unexpected value: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn break_cycles_on_drop() {
+        let mut vm = Vm::with_settings(VmSettings {
+            break_cycles_on_drop: true,
+            ..Default::default()
+        });
+        run_script(
+            &mut vm,
+            "
+export foo = [1]
+foo.push foo
+",
+        );
+
+        let foo = vm.context().exports.data().get_with_string("foo").cloned();
+        drop(vm);
+
+        match foo {
+            Some(Value::List(foo)) => assert!(matches!(foo.data()[1], Value::Empty)),
+            other => panic!("Unexpected value: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+mod debug_hook {
+    use {
+        koto_bytecode::Chunk,
+        koto_runtime::{
+            Breakpoint, DebugFrame, DebugHook, Loader, Mutex, RuntimeError, Value, Vm, VmSettings,
+        },
+        std::sync::Arc,
+    };
+
+    // Records the events that are reported by the VM
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn record(&self, event: String) {
+            self.events.lock().push(event);
+        }
+    }
+
+    fn line(frame: &DebugFrame) -> u32 {
+        frame.span().unwrap().start.line
+    }
+
+    fn numbers(values: &[Value]) -> Vec<i64> {
+        values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Number(n) => Some(i64::from(n)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    impl DebugHook for Recorder {
+        fn on_statement(&self, frame: &DebugFrame) {
+            self.record(format!("statement {}", line(frame)));
+        }
+
+        fn on_breakpoint(&self, frame: &DebugFrame, breakpoint: &Breakpoint) {
+            assert_eq!(line(frame), breakpoint.line);
+            let caller_line = frame.caller().map(|caller| line(&caller));
+            self.record(format!(
+                "breakpoint {} {:?} {:?}",
+                breakpoint.line,
+                numbers(frame.registers()),
+                caller_line
+            ));
+        }
+
+        fn on_function_entry(&self, frame: &DebugFrame) {
+            self.record(format!("entry {}", frame.depth()));
+        }
+
+        fn on_function_exit(&self, frame: &DebugFrame, return_value: &Value) {
+            self.record(format!("exit {} {}", frame.depth(), return_value));
+        }
+
+        fn on_error(&self, frame: &DebugFrame, error: &RuntimeError) {
+            let message = error.to_string();
+            self.record(format!(
+                "error {} {}",
+                line(frame),
+                message.lines().next().unwrap()
+            ));
+        }
+    }
+
+    fn compile(script: &str) -> Arc<Chunk> {
+        match Loader::default().compile_script(script, &None) {
+            Ok(chunk) => chunk,
+            Err(error) => panic!("Error while compiling script: {}", error),
+        }
+    }
+
+    fn run_with_hook(script: &str, breakpoints: &[u32]) -> (Value, Vec<String>) {
+        let recorder = Arc::new(Recorder::default());
+        let mut vm = Vm::with_settings(VmSettings {
+            debug_hook: Some(recorder.clone()),
+            ..Default::default()
+        });
+        for line in breakpoints {
+            vm.add_breakpoint(Breakpoint {
+                path: None,
+                line: *line,
+            });
+        }
+
+        let result = vm.run(compile(script)).unwrap();
+        let events = recorder.events.lock().clone();
+        (result, events)
+    }
+
+    #[test]
+    fn statements_and_function_calls() {
+        let script = "
+f = |n|
+  x = n * 2
+  x + 1
+y = f 10
+y * 2
+";
+        let (result, events) = run_with_hook(script, &[]);
+        assert!(matches!(result, Value::Number(n) if n == 42));
+        assert_eq!(
+            events,
+            &[
+                "entry 0",
+                "statement 2",
+                "statement 5",
+                "entry 1",
+                "statement 3",
+                "statement 4",
+                "exit 1 21",
+                "statement 6",
+                "exit 0 42",
+            ]
+        );
+    }
+
+    #[test]
+    fn breakpoints_with_frame_inspection() {
+        let script = "
+f = |a, b|
+  c = a + b
+  c * 2
+x = f 1, 2
+y = f 3, 4
+x + y
+";
+        let (_, events) = run_with_hook(script, &[4]);
+        let breakpoints = events
+            .iter()
+            .filter(|event| event.starts_with("breakpoint"))
+            .collect::<Vec<_>>();
+        // The function's args and local value are visible,
+        // along with the line of the call in the caller's frame
+        assert_eq!(
+            breakpoints,
+            &[
+                "breakpoint 4 [1, 2, 3] Some(5)",
+                "breakpoint 4 [3, 4, 7] Some(6)"
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_breakpoints_are_ignored() {
+        let vm = Vm::default();
+        let breakpoint = Breakpoint {
+            path: None,
+            line: 1,
+        };
+        vm.add_breakpoint(breakpoint.clone());
+        assert!(vm.remove_breakpoint(&breakpoint));
+        assert!(!vm.remove_breakpoint(&breakpoint));
+    }
+
+    #[test]
+    fn errors() {
+        let script = "
+f = || throw 'oops'
+x = try
+  f()
+catch _
+  99
+x
+";
+        let (_, events) = run_with_hook(script, &[]);
+        assert!(events.contains(&"error 2 oops".to_string()));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.starts_with("error"))
+                .count(),
+            1
+        );
+    }
+}
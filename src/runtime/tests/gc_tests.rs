@@ -0,0 +1,155 @@
+#![cfg(feature = "gc")]
+
+mod gc {
+    use {
+        koto_runtime::{ExternalData, ExternalValue, Loader, MetaMap, Value, Vm, VmSettings},
+        std::{
+            fmt,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+        },
+    };
+
+    // Counts the number of probes that have been dropped
+    #[derive(Debug)]
+    struct Probe(Arc<AtomicUsize>);
+
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl ExternalData for Probe {}
+
+    impl fmt::Display for Probe {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Probe")
+        }
+    }
+
+    fn make_vm(gc_interval: Option<usize>) -> (Vm, Arc<AtomicUsize>) {
+        let vm = Vm::with_settings(VmSettings {
+            gc_interval,
+            ..Default::default()
+        });
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        vm.prelude().add_fn("make_probe", {
+            let dropped = dropped.clone();
+            move |_, _| {
+                Ok(Value::ExternalValue(ExternalValue::new(
+                    Probe(dropped.clone()),
+                    MetaMap::default(),
+                )))
+            }
+        });
+
+        (vm, dropped)
+    }
+
+    fn run_script(vm: &mut Vm, script: &str) {
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+        if let Err(error) = vm.run(chunk) {
+            panic!("Error while running script: {}", error);
+        }
+    }
+
+    #[test]
+    fn unreachable_cycles_are_collected() {
+        let (mut vm, dropped) = make_vm(Some(1000));
+        run_script(
+            &mut vm,
+            "
+make_cycle = ||
+  x = {probe: make_probe()}
+  x.self = [x]
+  ()
+make_cycle()
+",
+        );
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        assert_eq!(vm.collect_garbage(), 2);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn captured_cycles_are_collected() {
+        let (mut vm, dropped) = make_vm(Some(1000));
+        run_script(
+            &mut vm,
+            "
+make_cycle = ||
+  x = {probe: make_probe()}
+  x.f = || x
+  ()
+make_cycle()
+",
+        );
+        assert!(vm.collect_garbage() > 0);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reachable_cycles_are_kept() {
+        let (mut vm, dropped) = make_vm(Some(1000));
+        run_script(
+            &mut vm,
+            "
+export foo = [make_probe()]
+foo.push foo
+",
+        );
+        assert_eq!(vm.collect_garbage(), 0);
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        match vm.get_exported_value("foo") {
+            Some(Value::List(foo)) => assert_eq!(foo.len(), 2),
+            other => panic!("Unexpected value: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycles_are_collected_at_the_gc_interval() {
+        let (mut vm, dropped) = make_vm(Some(10));
+        run_script(
+            &mut vm,
+            "
+for _ in 0..100
+  x = [make_probe()]
+  x.push x
+",
+        );
+        assert!(dropped.load(Ordering::SeqCst) >= 90);
+    }
+
+    #[test]
+    fn cycles_are_collected_when_the_vm_is_dropped() {
+        let (mut vm, dropped) = make_vm(Some(1000));
+        run_script(
+            &mut vm,
+            "
+export foo = [make_probe()]
+foo.push foo
+",
+        );
+        drop(vm);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn cycles_are_leaked_without_the_gc() {
+        let (mut vm, dropped) = make_vm(None);
+        run_script(
+            &mut vm,
+            "
+x = [make_probe()]
+x.push x
+",
+        );
+        assert_eq!(vm.collect_garbage(), 0);
+        drop(vm);
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+    }
+}
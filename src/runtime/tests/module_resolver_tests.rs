@@ -0,0 +1,182 @@
+mod module_resolver {
+    use {
+        koto_runtime::{Loader, ModuleResolver, ResolvedModule, Value, Vm, VmSettings},
+        std::{
+            collections::HashMap,
+            path::{Path, PathBuf},
+            sync::{Arc, Mutex},
+        },
+    };
+
+    // Provides modules from a map of names to sources, recording the resolved imports
+    #[derive(Default)]
+    struct MapResolver {
+        modules: HashMap<&'static str, &'static str>,
+        resolved: Mutex<Vec<(String, Option<PathBuf>)>>,
+    }
+
+    impl ModuleResolver for MapResolver {
+        fn resolve(
+            &self,
+            name: &str,
+            importing_path: Option<&Path>,
+        ) -> Result<Option<ResolvedModule>, String> {
+            self.resolved
+                .lock()
+                .unwrap()
+                .push((name.to_string(), importing_path.map(Path::to_path_buf)));
+
+            match name {
+                "broken" => Err("The database is unavailable".into()),
+                _ => Ok(self.modules.get(name).map(|source| ResolvedModule {
+                    path: PathBuf::from(format!("db/{}.koto", name)),
+                    source: source.as_bytes().to_vec(),
+                })),
+            }
+        }
+    }
+
+    fn make_vm(modules: &[(&'static str, &'static str)]) -> (Vm, Arc<MapResolver>) {
+        let resolver = Arc::new(MapResolver {
+            modules: modules.iter().cloned().collect(),
+            ..Default::default()
+        });
+        let vm = Vm::with_settings(VmSettings {
+            module_resolver: Some(resolver.clone()),
+            ..Default::default()
+        });
+        (vm, resolver)
+    }
+
+    fn run_script(vm: &mut Vm, script: &str) -> Result<Value, String> {
+        let chunk = Loader::default()
+            .compile_script(script, &Some(PathBuf::from("main.koto")))
+            .unwrap();
+        vm.run(chunk).map_err(|error| error.to_string())
+    }
+
+    fn check_number(result: Result<Value, String>, expected: f64) {
+        match result {
+            Ok(Value::Number(n)) if n == expected => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    fn check_string(result: Result<Value, String>, expected: &str) {
+        match result {
+            Ok(Value::Str(s)) if s.as_str() == expected => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn import() {
+        let (mut vm, _) = make_vm(&[("foo", "export x = 42")]);
+        check_number(
+            run_script(
+                &mut vm,
+                "
+import foo
+foo.x
+",
+            ),
+            42.0,
+        );
+    }
+
+    #[test]
+    fn from_import() {
+        let (mut vm, _) = make_vm(&[("foo", "export x = 42\nexport y = 99")]);
+        check_number(
+            run_script(
+                &mut vm,
+                "
+from foo import x, y
+x + y
+",
+            ),
+            141.0,
+        );
+    }
+
+    #[test]
+    fn modules_have_the_module_type() {
+        let (mut vm, _) = make_vm(&[("foo", "export x = 42")]);
+        check_string(run_script(&mut vm, "import foo\nkoto.type foo"), "Module");
+    }
+
+    #[test]
+    fn modules_can_override_their_type() {
+        let (mut vm, _) = make_vm(&[("foo", "export @type = 'Foo'")]);
+        check_string(run_script(&mut vm, "import foo\nkoto.type foo"), "Foo");
+    }
+
+    #[test]
+    fn nested_imports_are_resolved_from_the_importing_module() {
+        let (mut vm, resolver) = make_vm(&[
+            ("foo", "import bar\nexport x = bar.y + 1"),
+            ("bar", "export y = 41"),
+        ]);
+        check_number(
+            run_script(
+                &mut vm,
+                "
+import foo
+foo.x
+",
+            ),
+            42.0,
+        );
+
+        let resolved = resolver.resolved.lock().unwrap();
+        assert_eq!(
+            *resolved,
+            vec![
+                ("foo".to_string(), Some(PathBuf::from("main.koto"))),
+                ("bar".to_string(), Some(PathBuf::from("db/foo.koto"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn modules_are_only_run_once() {
+        let (mut vm, _) = make_vm(&[
+            ("counter", "export state = {count: 0}"),
+            (
+                "reader",
+                "import counter\nexport count = counter.state.count",
+            ),
+        ]);
+        check_number(
+            run_script(
+                &mut vm,
+                "
+import counter
+counter.state.count += 1
+import reader
+reader.count
+",
+            ),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn resolver_errors_are_reported() {
+        let (mut vm, _) = make_vm(&[]);
+        let error = run_script(&mut vm, "import broken").unwrap_err();
+        assert!(
+            error.contains("The database is unavailable"),
+            "Unexpected error: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn unresolved_modules_are_searched_for_in_the_filesystem() {
+        let (mut vm, resolver) = make_vm(&[]);
+        let error = run_script(&mut vm, "import missing").unwrap_err();
+        assert!(error.starts_with("Failed to import 'missing'"));
+        assert_eq!(resolver.resolved.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,164 @@
+mod profiler {
+    use koto_runtime::{Loader, Profile, Value, Vm, VmSettings};
+
+    fn run_with_profile(script: &str) -> Profile {
+        let mut vm = Vm::with_settings(VmSettings {
+            profile: true,
+            ..Default::default()
+        });
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+        if let Err(error) = vm.run(chunk) {
+            panic!("Error while running script: {}", error);
+        }
+        vm.take_profile()
+    }
+
+    fn function_calls(profile: &Profile) -> Vec<(Option<&str>, u64)> {
+        let mut result = profile
+            .functions
+            .iter()
+            .map(|function| (function.name(), function.calls))
+            .collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut vm = Vm::default();
+        let chunk = Loader::default()
+            .compile_script("f = |x| x + 1\nf 1", &None)
+            .unwrap();
+        vm.run(chunk).unwrap();
+
+        assert!(vm.take_profile().functions.is_empty());
+    }
+
+    #[test]
+    fn call_counts() {
+        let script = "
+f = |x| x + 1
+g = |x| (f x) + (f x)
+(0..3).each(|x| g x).consume()
+f 1
+";
+        let profile = run_with_profile(script);
+
+        assert_eq!(
+            function_calls(&profile),
+            &[(None, 1), (None, 3), (Some("f"), 7), (Some("g"), 3)]
+        );
+    }
+
+    #[test]
+    fn inclusive_and_exclusive_times() {
+        let script = "
+fib = |n|
+  if n < 2
+    n
+  else
+    (fib n - 1) + (fib n - 2)
+outer = || fib 10
+outer()
+";
+        let profile = run_with_profile(script);
+        let function = |name| {
+            profile
+                .functions
+                .iter()
+                .find(|function| function.name() == name)
+                .unwrap()
+        };
+
+        let top_level = function(None);
+        let outer = function(Some("outer"));
+        let fib = function(Some("fib"));
+
+        assert_eq!(fib.calls, 177);
+
+        // Recursive calls are included in the outermost call's inclusive time
+        assert!(fib.inclusive_time <= outer.inclusive_time);
+        assert!(outer.inclusive_time <= top_level.inclusive_time);
+
+        // Exclusive times don't include the time spent in called functions
+        assert!(fib.exclusive_time <= fib.inclusive_time);
+        assert_eq!(
+            outer.exclusive_time,
+            outer.inclusive_time - fib.inclusive_time
+        );
+    }
+
+    #[test]
+    fn allocations() {
+        let script = "
+make_list = |n| (0..n).to_list()
+make_string = |s| '$s!'
+x = make_list 10
+y = make_string 'hello'
+";
+        let profile = run_with_profile(script);
+        let function = |name| {
+            profile
+                .functions
+                .iter()
+                .find(|function| function.name() == Some(name))
+                .unwrap()
+        };
+
+        let make_list = function("make_list");
+        assert_eq!(make_list.allocations, 1);
+        assert!(make_list.allocated_bytes >= 10);
+
+        let make_string = function("make_string");
+        assert!(make_string.allocations > 0);
+        assert!(make_string.allocated_bytes >= 6);
+
+        // The allocations made by the called functions aren't included in the top-level's stats
+        let top_level = profile
+            .functions
+            .iter()
+            .find(|function| function.name().is_none())
+            .unwrap();
+        assert_eq!(top_level.allocations, 0);
+    }
+
+    #[test]
+    fn profile_as_map() {
+        let profile = run_with_profile("f = |x| x * 2\nf 1");
+        let map = profile.to_map();
+
+        let f = map
+            .data()
+            .iter()
+            .find(|(key, _)| key.to_string().starts_with("f (<script>:1:"))
+            .map(|(_, value)| value.clone());
+        match f {
+            Some(Value::Map(f)) => {
+                let data = f.data();
+                assert!(matches!(data.get_with_string("calls"), Some(Value::Number(n)) if *n == 1));
+                assert!(matches!(data.get_with_string("line"), Some(Value::Number(n)) if *n == 1));
+                assert!(matches!(data.get_with_string("path"), Some(Value::Empty)));
+            }
+            other => panic!("Expected a map for f, found {:?}", other),
+        }
+        assert!(map
+            .data()
+            .get_with_string("<top level> (<script>)")
+            .is_some());
+    }
+
+    #[test]
+    fn taking_the_profile_clears_it() {
+        let mut vm = Vm::with_settings(VmSettings {
+            profile: true,
+            ..Default::default()
+        });
+        let chunk = Loader::default()
+            .compile_script("f = |x| x + 1\nf 1", &None)
+            .unwrap();
+        vm.run(chunk).unwrap();
+
+        assert_eq!(vm.take_profile().functions.len(), 2);
+        assert!(vm.take_profile().functions.is_empty());
+    }
+}
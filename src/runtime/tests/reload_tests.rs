@@ -0,0 +1,171 @@
+mod reload {
+    use {
+        koto_runtime::{Loader, Value, Vm},
+        std::{fs, path::PathBuf},
+    };
+
+    // Each test uses its own script directory, which is removed when the test ends
+    struct ScriptDir(PathBuf);
+
+    impl ScriptDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("koto_reload_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, file_name: &str, contents: &str) {
+            fs::write(self.0.join(file_name), contents).unwrap();
+        }
+
+        fn run_main(&self, vm: &mut Vm, script: &str) {
+            self.write("main.koto", script);
+            let main_path = self.0.join("main.koto");
+            let chunk = Loader::default()
+                .compile_script(script, &Some(main_path))
+                .unwrap();
+            if let Err(error) = vm.run(chunk) {
+                panic!("Error while running script: {}", error);
+            }
+        }
+    }
+
+    impl Drop for ScriptDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn get_number(vm: &Vm, path: &[&str]) -> f64 {
+        let mut value = Value::Map(vm.context().exports.clone());
+        for key in path {
+            value = match value {
+                Value::Map(map) => map.data().get_with_string(key).cloned().unwrap(),
+                other => panic!("Unexpected value: {:?}", other),
+            };
+        }
+        match value {
+            Value::Number(n) => n.into(),
+            other => panic!("Unexpected value: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reloaded_exports_are_updated_in_place() {
+        let dir = ScriptDir::new("in_place");
+        dir.write("foo.koto", "export value = 1");
+
+        let mut vm = Vm::default();
+        dir.run_main(
+            &mut vm,
+            "
+import foo
+export foo_module = foo
+",
+        );
+        assert_eq!(get_number(&vm, &["foo_module", "value"]), 1.0);
+
+        dir.write("foo.koto", "export value = 2");
+        vm.reload_module("foo").unwrap();
+        assert_eq!(get_number(&vm, &["foo_module", "value"]), 2.0);
+    }
+
+    #[test]
+    fn reload_hook_migrates_state() {
+        let dir = ScriptDir::new("hook");
+        dir.write(
+            "foo.koto",
+            "
+state = {count: 0}
+export state = state
+export increment = || state.count += 1
+",
+        );
+
+        let mut vm = Vm::default();
+        dir.run_main(
+            &mut vm,
+            "
+import foo
+foo.increment()
+foo.increment()
+export foo_module = foo
+",
+        );
+        assert_eq!(get_number(&vm, &["foo_module", "state", "count"]), 2.0);
+
+        dir.write(
+            "foo.koto",
+            "
+export state = {count: 0, reloaded: 1}
+export @reload = |previous|
+  state.count = previous.state.count
+",
+        );
+        vm.reload_module("foo").unwrap();
+        assert_eq!(get_number(&vm, &["foo_module", "state", "count"]), 2.0);
+        assert_eq!(get_number(&vm, &["foo_module", "state", "reloaded"]), 1.0);
+    }
+
+    #[test]
+    fn failed_reloads_leave_the_module_unchanged() {
+        let dir = ScriptDir::new("failed");
+        dir.write("foo.koto", "export value = 1");
+
+        let mut vm = Vm::default();
+        dir.run_main(
+            &mut vm,
+            "
+import foo
+export foo_module = foo
+",
+        );
+
+        // A compilation error
+        dir.write("foo.koto", "export value = (");
+        assert!(vm.reload_module("foo").is_err());
+        assert_eq!(get_number(&vm, &["foo_module", "value"]), 1.0);
+
+        // A runtime error
+        dir.write("foo.koto", "export value = 2\nthrow 'oops'");
+        assert!(vm.reload_module("foo").is_err());
+        assert_eq!(get_number(&vm, &["foo_module", "value"]), 1.0);
+
+        // An error in the reload hook
+        dir.write(
+            "foo.koto",
+            "
+export value = 3
+export @reload = |previous| previous.missing.value
+",
+        );
+        assert!(vm.reload_module("foo").is_err());
+        assert_eq!(get_number(&vm, &["foo_module", "value"]), 1.0);
+
+        // The module can be reloaded once the error has been fixed
+        dir.write("foo.koto", "export value = 4");
+        vm.reload_module("foo").unwrap();
+        assert_eq!(get_number(&vm, &["foo_module", "value"]), 4.0);
+    }
+
+    #[test]
+    fn reloading_a_module_that_hasnt_been_imported() {
+        let dir = ScriptDir::new("not_imported");
+        dir.write("foo.koto", "export value = 42");
+
+        let mut vm = Vm::default();
+        dir.run_main(&mut vm, "x = 1");
+
+        match vm.reload_module("foo") {
+            Ok(Value::Map(foo)) => {
+                assert!(matches!(
+                    foo.data().get_with_string("value"),
+                    Some(Value::Number(n)) if *n == 42
+                ));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
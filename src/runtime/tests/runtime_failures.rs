@@ -1,12 +1,14 @@
 mod runtime {
     use {
         koto_bytecode::Chunk,
-        koto_runtime::{Loader, Vm},
+        koto_runtime::{Loader, RuntimeError, Vm, VmSettings},
     };
 
     fn check_script_fails(script: &str) {
-        let mut vm = Vm::default();
+        check_script_fails_with_vm(Vm::default(), script);
+    }
 
+    fn check_script_fails_with_vm(mut vm: Vm, script: &str) {
         let print_chunk = |script: &str, chunk| {
             println!("{}\n", script);
             let script_lines = script.lines().collect::<Vec<_>>();
@@ -58,6 +60,62 @@ import test.assert
             }
         }
 
+        mod assert {
+            use super::*;
+
+            #[test]
+            fn failed_assertion() {
+                check_script_fails("assert 1 + 1 == 3");
+            }
+
+            #[test]
+            fn failed_chained_comparison() {
+                check_script_fails("assert 1 < 2 < 2");
+            }
+
+            #[test]
+            fn non_bool_value() {
+                check_script_fails("assert 42");
+            }
+        }
+
+        mod import {
+            use super::*;
+
+            #[test]
+            fn import_all_in_missing_directory() {
+                check_script_fails("koto.import_all_in 'this_directory_does_not_exist'");
+            }
+        }
+
+        mod module_versions {
+            use super::*;
+
+            #[test]
+            fn unsatisfied_requirement() {
+                check_script_fails("export @requires = 'koto >= 999.0'");
+            }
+
+            #[test]
+            fn invalid_requirement() {
+                check_script_fails("export @requires = '>= 0.9'");
+            }
+
+            #[test]
+            fn invalid_version() {
+                check_script_fails("export @version = 'latest'");
+            }
+        }
+
+        mod operators {
+            use super::*;
+
+            #[test]
+            fn in_with_unsupported_container() {
+                check_script_fails("1 in 42");
+            }
+        }
+
         mod function_calls {
             use super::*;
 
@@ -97,5 +155,642 @@ f [1, 2, 3]
                 check_script_fails(script);
             }
         }
+
+        mod type_annotations {
+            use super::*;
+
+            fn check_script_fails_with_type_checks(script: &str) {
+                let vm = Vm::with_settings(VmSettings {
+                    check_type_annotations: true,
+                    ..Default::default()
+                });
+                check_script_fails_with_vm(vm, script);
+            }
+
+            #[test]
+            fn arg_with_wrong_type() {
+                let script = r#"
+f = |x: Number| x
+f "O_o"
+"#;
+                check_script_fails_with_type_checks(script);
+            }
+
+            #[test]
+            fn implicit_return_with_wrong_type() {
+                let script = "
+f = |x| -> Int
+  x / 2
+f 3
+";
+                check_script_fails_with_type_checks(script);
+            }
+
+            #[test]
+            fn early_return_with_wrong_type() {
+                let script = "
+f = |x| -> String
+  if x > 0
+    return x
+  'negative'
+f 1
+";
+                check_script_fails_with_type_checks(script);
+            }
+        }
+    }
+
+    mod error_trace {
+        use super::*;
+
+        fn run_script_with_error(script: &str) -> RuntimeError {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            match Vm::default().run(chunk) {
+                Ok(result) => panic!("Script didn't fail as expected, result: {}", result),
+                Err(error) => error,
+            }
+        }
+
+        #[test]
+        fn frames_refer_to_source_spans() {
+            let script = "
+f = |x|
+  throw 'oops'
+g = |x|
+  f x
+g 1
+";
+            let error = run_script_with_error(script);
+
+            let lines = error
+                .trace
+                .iter()
+                .map(|frame| frame.span().unwrap().start.line)
+                .collect::<Vec<_>>();
+            assert_eq!(lines, &[3, 5, 6]);
+
+            let first_frame = &error.trace[0];
+            assert_eq!(
+                first_frame.chunk().source_span(first_frame.instruction()),
+                first_frame.span()
+            );
+        }
+
+        #[test]
+        fn frames_refer_to_function_names() {
+            let script = "
+m =
+  check: |x|
+    throw 'oops'
+f = |x|
+  (1..3).each(|n| m.check n).to_tuple()
+f 1
+";
+            let error = run_script_with_error(script);
+
+            let names = error
+                .trace
+                .iter()
+                .map(|frame| frame.function_name())
+                .collect::<Vec<_>>();
+            // The anonymous function passed to `each` has no name
+            assert_eq!(names, &[Some("check"), None, Some("f"), None]);
+
+            assert!(error.to_string().contains(" --- 4:5 in 'check'"));
+            assert!(error.to_string().contains(" --- 6:30 in 'f'"));
+        }
+
+        #[test]
+        fn errors_preserve_their_causes() {
+            let script = "
+from koto import error
+parse = |s|
+  try
+    s.to_number()
+  catch e
+    throw error 'parse failed', {cause: e}
+try
+  parse 'x'
+catch e
+  throw error 'load failed', {cause: e}
+";
+            let error = run_script_with_error(script);
+
+            let messages = error
+                .chain()
+                .map(|error| format!("{:#}", error))
+                .collect::<Vec<_>>();
+            assert_eq!(messages.len(), 3);
+            assert_eq!(messages[0], "load failed");
+            assert_eq!(messages[1], "parse failed");
+            assert!(messages[2].starts_with("string.to_number: Failed to convert 'x'"));
+
+            let source = std::error::Error::source(&error).unwrap();
+            assert_eq!(format!("{:#}", source), "parse failed");
+
+            // Only the error itself has a trace
+            assert_eq!(error.trace.len(), 1);
+            assert!(error.cause.as_ref().unwrap().trace.is_empty());
+
+            assert!(error.to_string().contains("\nCaused by: parse failed\n"));
+        }
+
+        #[test]
+        fn cyclic_causes_are_only_included_once() {
+            let script = "
+from koto import error
+e = error 'oops'
+e.cause = e
+throw e
+";
+            let error = run_script_with_error(script);
+
+            assert_eq!(error.chain().count(), 2);
+        }
+    }
+
+    mod instruction_budget {
+        use {
+            super::*,
+            koto_runtime::{RuntimeErrorType, Value},
+        };
+
+        fn vm_with_budget(budget: u64) -> Vm {
+            Vm::with_settings(VmSettings {
+                instruction_budget: Some(budget),
+                ..Default::default()
+            })
+        }
+
+        fn run(vm: &mut Vm, script: &str) -> Result<Value, RuntimeError> {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            vm.run(chunk)
+        }
+
+        fn is_budget_error(error: &RuntimeError) -> bool {
+            matches!(error.error, RuntimeErrorType::InstructionBudgetExhausted)
+        }
+
+        #[test]
+        fn infinite_loop_is_stopped() {
+            let mut vm = vm_with_budget(1000);
+            let error = run(&mut vm, "loop\n  x = 1").unwrap_err();
+            assert!(is_budget_error(&error));
+            assert_eq!(vm.instruction_budget(), Some(0));
+        }
+
+        #[test]
+        fn budget_exhaustion_cant_be_caught() {
+            let script = "
+try
+  while true
+    x = 1
+catch _
+  42
+";
+            let error = run(&mut vm_with_budget(1000), script).unwrap_err();
+            assert!(is_budget_error(&error));
+        }
+
+        #[test]
+        fn budget_is_shared_with_functions_called_by_the_core_library() {
+            let script = "
+f = |x|
+  loop
+    x += 1
+(1..10).each(f).to_list()
+";
+            let error = run(&mut vm_with_budget(1000), script).unwrap_err();
+            assert!(is_budget_error(&error));
+        }
+
+        #[test]
+        fn scripts_within_budget_complete() {
+            let mut vm = vm_with_budget(1000);
+            let result = run(&mut vm, "x = 0\nfor i in 0..10\n  x += i\nx").unwrap();
+            assert!(matches!(result, Value::Number(n) if n == 45));
+
+            let remaining = vm.instruction_budget().unwrap();
+            assert!(remaining > 0 && remaining < 1000);
+        }
+
+        #[test]
+        fn budget_can_be_refilled() {
+            let mut vm = vm_with_budget(5);
+            let script = "x = 0\nfor i in 0..10\n  x += i\nx";
+            assert!(run(&mut vm, script).is_err());
+
+            vm.set_instruction_budget(1000);
+            assert!(run(&mut vm, script).is_ok());
+        }
+
+        #[test]
+        fn no_budget_by_default() {
+            let vm = Vm::default();
+            assert_eq!(vm.instruction_budget(), None);
+            vm.set_instruction_budget(100);
+            assert_eq!(vm.instruction_budget(), None);
+        }
+    }
+
+    mod timeouts {
+        use {
+            super::*,
+            koto_runtime::{RuntimeErrorType, Value},
+            std::time::Duration,
+        };
+
+        fn run_with_timeout(vm: &mut Vm, script: &str) -> Result<Value, RuntimeError> {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            vm.run_with_timeout(chunk, Duration::from_millis(50))
+        }
+
+        fn is_timeout_error(error: &RuntimeError) -> bool {
+            matches!(error.error, RuntimeErrorType::TimedOut(timeout)
+                if timeout == Duration::from_millis(50))
+        }
+
+        #[test]
+        fn infinite_loop_times_out() {
+            let error = run_with_timeout(&mut Vm::default(), "loop\n  x = 1").unwrap_err();
+            assert!(is_timeout_error(&error));
+            assert!(error
+                .to_string()
+                .starts_with("Execution timed out after 0.05s"));
+        }
+
+        #[test]
+        fn timeouts_cant_be_caught() {
+            let script = "
+try
+  while true
+    x = 1
+catch _
+  42
+";
+            let error = run_with_timeout(&mut Vm::default(), script).unwrap_err();
+            assert!(is_timeout_error(&error));
+        }
+
+        #[test]
+        fn functions_called_by_the_core_library_are_interrupted() {
+            let script = "
+f = |x|
+  loop
+    x += 1
+(1..10).each(f).to_list()
+";
+            let error = run_with_timeout(&mut Vm::default(), script).unwrap_err();
+            assert!(is_timeout_error(&error));
+        }
+
+        #[test]
+        fn vm_can_be_reused_after_a_timeout() {
+            let mut vm = Vm::default();
+            assert!(run_with_timeout(&mut vm, "loop\n  x = 1").is_err());
+
+            let result = run_with_timeout(&mut vm, "1 + 2").unwrap();
+            assert!(matches!(result, Value::Number(n) if n == 3));
+        }
+
+        #[test]
+        fn errors_before_the_timeout_are_unchanged() {
+            let error = run_with_timeout(&mut Vm::default(), "throw 'oops'").unwrap_err();
+            assert!(!is_timeout_error(&error));
+            assert!(error.to_string().starts_with("oops"));
+        }
+    }
+
+    mod memory_limit {
+        use {
+            super::*,
+            koto_runtime::{RuntimeErrorType, Value},
+        };
+
+        fn vm_with_limit(limit: usize) -> Vm {
+            Vm::with_settings(VmSettings {
+                memory_limit: Some(limit),
+                ..Default::default()
+            })
+        }
+
+        fn run(vm: &mut Vm, script: &str) -> Result<Value, RuntimeError> {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            vm.run(chunk)
+        }
+
+        fn check_limit_exceeded(script: &str) {
+            match run(&mut vm_with_limit(10_000), script) {
+                Ok(_) => panic!("Expected the memory limit to be exceeded in:\n{}", script),
+                Err(error) => assert!(
+                    matches!(error.error, RuntimeErrorType::MemoryLimitExceeded(10_000)),
+                    "Unexpected error: {}",
+                    error
+                ),
+            }
+        }
+
+        #[test]
+        fn list_concatenation() {
+            check_limit_exceeded(
+                "
+x = []
+for i in 0..1000
+  x = x + [i]
+x
+",
+            );
+        }
+
+        #[test]
+        fn string_interpolation() {
+            check_limit_exceeded(
+                "
+s = ''
+for i in 0..10000
+  t = '$s$i'
+  s = t
+s
+",
+            );
+        }
+
+        #[test]
+        fn list_growth_in_external_functions() {
+            check_limit_exceeded(
+                "
+x = []
+for i in 0..100000
+  x.push i
+x
+",
+            );
+        }
+
+        #[test]
+        fn new_values_from_external_functions() {
+            check_limit_exceeded("(0..100000).to_list()");
+            check_limit_exceeded("(0..100000).to_tuple()");
+        }
+
+        #[test]
+        fn limit_errors_cant_be_caught() {
+            check_limit_exceeded(
+                "
+try
+  (0..100000).to_list()
+catch _
+  42
+",
+            );
+        }
+
+        #[test]
+        fn usage_is_tracked_and_can_be_reset() {
+            let mut vm = vm_with_limit(10_000);
+            let script = "(0..10).to_list()";
+            assert!(run(&mut vm, script).is_ok());
+
+            let usage = vm.memory_usage().unwrap();
+            assert!(usage > 0 && usage < 10_000);
+
+            vm.set_memory_usage(0);
+            assert_eq!(vm.memory_usage(), Some(0));
+            assert!(run(&mut vm, script).is_ok());
+        }
+
+        #[test]
+        fn dropped_values_are_released() {
+            let mut vm = vm_with_limit(10_000);
+
+            // Each iteration's string is dropped when the next one is assigned
+            let script = "
+for i in 0..100000
+  s = 'x$i'
+s
+";
+            assert!(run(&mut vm, script).is_ok());
+
+            // The values from previous runs are also released
+            let script = "(0..100).to_list()";
+            for _ in 0..1000 {
+                assert!(run(&mut vm, script).is_ok());
+            }
+            assert!(vm.memory_usage().unwrap() <= 10_000);
+        }
+
+        #[test]
+        fn live_values_are_still_counted() {
+            // The strings are kept alive in the list, so their memory isn't released
+            check_limit_exceeded(
+                "
+x = []
+for i in 0..10000
+  x.push 'x$i'
+x
+",
+            );
+        }
+
+        #[test]
+        fn shared_values_arent_counted_again() {
+            let mut vm = vm_with_limit(10_000);
+            let script = "
+m = {foo: (0..100).to_list()}
+for _ in 0..1000
+  x = m.get 'foo'
+x
+";
+            assert!(run(&mut vm, script).is_ok());
+        }
+
+        #[test]
+        fn no_limit_by_default() {
+            let vm = Vm::default();
+            assert_eq!(vm.memory_usage(), None);
+            assert!(vm.track_allocation(usize::MAX).is_ok());
+        }
+    }
+
+    mod call_depth {
+        use {
+            super::*,
+            koto_runtime::{RuntimeErrorType, Value},
+        };
+
+        fn vm_with_limits(max_call_depth: usize, max_nested_call_depth: usize) -> Vm {
+            Vm::with_settings(VmSettings {
+                max_call_depth: Some(max_call_depth),
+                max_nested_call_depth: Some(max_nested_call_depth),
+                ..Default::default()
+            })
+        }
+
+        fn run(vm: &mut Vm, script: &str) -> Result<Value, RuntimeError> {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            vm.run(chunk)
+        }
+
+        #[test]
+        fn infinite_recursion() {
+            let script = "
+f = |n| 1 + f n + 1
+f 0
+";
+            let error = run(&mut vm_with_limits(1000, 100), script).unwrap_err();
+            assert!(
+                matches!(error.error, RuntimeErrorType::CallDepthExceeded(1000)),
+                "Unexpected error: {}",
+                error
+            );
+
+            // Only the deepest frames are included in the trace
+            assert_eq!(error.trace.len(), 20);
+            assert_eq!(error.trace.len() + error.omitted_frames, 1000);
+            assert!(error
+                .to_string()
+                .starts_with("The maximum call depth of 1000 has been exceeded"));
+            assert!(error.to_string().ends_with("... 980 more frames omitted"));
+        }
+
+        #[test]
+        fn recursion_through_external_functions() {
+            let script = "
+f = |n| [n].each(|x| f x + 1).to_tuple()
+f 0
+";
+            let error = run(&mut vm_with_limits(1000, 50), script).unwrap_err();
+            assert!(
+                matches!(error.error, RuntimeErrorType::NestedCallDepthExceeded(50)),
+                "Unexpected error: {}",
+                error
+            );
+            assert_eq!(error.trace.len(), 20);
+        }
+
+        #[test]
+        fn call_depth_errors_can_be_caught() {
+            let script = "
+f = |n| 1 + f n + 1
+try
+  f 0
+catch error
+  'caught'
+";
+            let result = run(&mut vm_with_limits(1000, 100), script).unwrap();
+            assert!(matches!(result, Value::Str(s) if s.as_str() == "caught"));
+        }
+
+        #[test]
+        fn recursion_within_the_limit() {
+            let script = "
+f = |n| if n == 0 then 0 else 1 + f n - 1
+f 998
+";
+            let result = run(&mut vm_with_limits(1000, 100), script).unwrap();
+            assert!(matches!(result, Value::Number(n) if n == 998));
+        }
+
+        #[test]
+        fn the_vm_can_run_again_after_the_limit_is_exceeded() {
+            let mut vm = vm_with_limits(1000, 100);
+            assert!(run(&mut vm, "f = || f()\nf()").is_err());
+            let result = run(&mut vm, "f = |n| if n == 0 then 0 else 1 + f n - 1\nf 500");
+            assert!(matches!(result, Ok(Value::Number(n)) if n == 500));
+        }
+    }
+
+    mod interrupt_handle {
+        use {
+            super::*,
+            koto_runtime::{RuntimeErrorType, Value},
+            std::{thread, time::Duration},
+        };
+
+        fn run(vm: &mut Vm, script: &str) -> Result<Value, RuntimeError> {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            vm.run(chunk)
+        }
+
+        // Runs the script, interrupting it from another thread after a short delay
+        fn run_and_interrupt(vm: &mut Vm, script: &str) -> Result<Value, RuntimeError> {
+            let handle = vm.interrupt_handle();
+            let interrupter = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                handle.interrupt();
+            });
+            let result = run(vm, script);
+            interrupter.join().unwrap();
+            result
+        }
+
+        fn is_interrupted_error(error: &RuntimeError) -> bool {
+            matches!(error.error, RuntimeErrorType::Interrupted)
+        }
+
+        #[test]
+        fn infinite_loop_is_interrupted() {
+            let error = run_and_interrupt(&mut Vm::default(), "loop\n  x = 1").unwrap_err();
+            assert!(is_interrupted_error(&error));
+            assert!(error.to_string().starts_with("Execution was interrupted"));
+        }
+
+        #[test]
+        fn interruptions_cant_be_caught() {
+            let script = "
+try
+  while true
+    x = 1
+catch _
+  42
+";
+            let error = run_and_interrupt(&mut Vm::default(), script).unwrap_err();
+            assert!(is_interrupted_error(&error));
+        }
+
+        #[test]
+        fn functions_called_by_the_core_library_are_interrupted() {
+            let script = "
+f = |x|
+  loop
+    x += 1
+(1..10).each(f).to_list()
+";
+            let error = run_and_interrupt(&mut Vm::default(), script).unwrap_err();
+            assert!(is_interrupted_error(&error));
+        }
+
+        #[test]
+        fn vm_can_run_again_after_reset() {
+            let mut vm = Vm::default();
+            let handle = vm.interrupt_handle();
+            assert!(run_and_interrupt(&mut vm, "loop\n  x = 1").is_err());
+
+            // The VM stays interrupted until the handle is reset
+            assert!(handle.is_interrupted());
+            assert!(is_interrupted_error(&run(&mut vm, "1 + 2").unwrap_err()));
+
+            handle.reset();
+            let result = run(&mut vm, "1 + 2").unwrap();
+            assert!(matches!(result, Value::Number(n) if n == 3));
+        }
+
+        #[test]
+        fn vms_running_with_a_timeout_are_interrupted() {
+            let mut vm = Vm::default();
+            let handle = vm.interrupt_handle();
+            let interrupter = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                handle.interrupt();
+            });
+            let chunk = Loader::default()
+                .compile_script("loop\n  x = 1", &None)
+                .unwrap();
+            let error = vm
+                .run_with_timeout(chunk, Duration::from_secs(60))
+                .unwrap_err();
+            interrupter.join().unwrap();
+            assert!(is_interrupted_error(&error));
+        }
     }
 }
@@ -48,6 +48,25 @@ pub fn test_script_with_vm(mut vm: Vm, script: &str, expected_output: Value) {
     }
 }
 
+pub fn run_script(vm: &mut Vm, script: &str) -> Value {
+    let mut loader = Loader::default();
+    let chunk = match loader.compile_script(script, &None) {
+        Ok(chunk) => chunk,
+        Err(error) => {
+            print_chunk(script, vm.chunk());
+            panic!("Error while compiling script: {}", error);
+        }
+    };
+
+    match vm.run(chunk) {
+        Ok(result) => result,
+        Err(e) => {
+            print_chunk(script, vm.chunk());
+            panic!("Error while running script: {}", e);
+        }
+    }
+}
+
 pub fn print_chunk(script: &str, chunk: Arc<Chunk>) {
     println!("{}\n", script);
     let script_lines = script.lines().collect::<Vec<_>>();
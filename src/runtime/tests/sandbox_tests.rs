@@ -0,0 +1,253 @@
+mod sandbox {
+    use {
+        koto_runtime::{
+            ImportPolicy, Loader, ModuleResolver, ResolvedModule, SandboxSettings, Value, Vm,
+            VmSettings,
+        },
+        std::{
+            fs,
+            path::{Path, PathBuf},
+            sync::Arc,
+        },
+    };
+
+    fn make_vm(sandbox: SandboxSettings) -> Vm {
+        Vm::with_settings(VmSettings {
+            sandbox,
+            ..Default::default()
+        })
+    }
+
+    fn run_script(vm: &mut Vm, script: &str) -> Result<Value, String> {
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+        vm.run(chunk).map_err(|error| error.to_string())
+    }
+
+    fn check_error(result: Result<Value, String>, expected: &str) {
+        match result {
+            Err(error) => assert!(
+                error.starts_with(expected),
+                "Unexpected error: {}, expected: {}",
+                error,
+                expected
+            ),
+            Ok(value) => panic!("Expected an error, found: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn excluded_modules_are_removed_from_the_prelude() {
+        let mut vm = make_vm(SandboxSettings {
+            excluded: vec!["os".into()],
+            ..Default::default()
+        });
+        check_error(run_script(&mut vm, "os.name()"), "'os' not found");
+        assert!(vm.prelude().data().get_with_string("os").is_none());
+        assert!(vm.prelude().data().get_with_string("io").is_some());
+    }
+
+    #[test]
+    fn excluded_functions_are_removed_from_their_module() {
+        let mut vm = make_vm(SandboxSettings {
+            excluded: vec!["string.to_uppercase".into()],
+            ..Default::default()
+        });
+        check_error(
+            run_script(&mut vm, "string.to_uppercase 'abc'"),
+            "'to_uppercase' not found",
+        );
+        // Excluded functions are also unavailable as methods
+        check_error(
+            run_script(&mut vm, "'abc'.to_uppercase()"),
+            "'to_uppercase' not found",
+        );
+        assert!(matches!(
+            run_script(&mut vm, "'abc'.to_lowercase()"),
+            Ok(Value::Str(s)) if s.as_str() == "abc"
+        ));
+    }
+
+    #[test]
+    fn stubbed_functions_throw_errors_when_called() {
+        let mut vm = make_vm(SandboxSettings {
+            stubbed: vec!["io.open".into()],
+            ..Default::default()
+        });
+        check_error(
+            run_script(&mut vm, "io.open 'foo.txt'"),
+            "'io.open' is disabled by the sandbox settings",
+        );
+        // Stubbed functions can still be referred to
+        assert!(matches!(
+            run_script(&mut vm, "koto.type io.open"),
+            Ok(Value::Str(s)) if s.as_str() == "ExternalFunction"
+        ));
+    }
+
+    #[test]
+    fn stubbed_modules_stub_each_function() {
+        let mut vm = make_vm(SandboxSettings {
+            stubbed: vec!["os".into()],
+            ..Default::default()
+        });
+        check_error(
+            run_script(&mut vm, "os.name()"),
+            "'os.name' is disabled by the sandbox settings",
+        );
+        check_error(
+            run_script(&mut vm, "os.cpu_count()"),
+            "'os.cpu_count' is disabled by the sandbox settings",
+        );
+    }
+
+    #[test]
+    fn stubbed_errors_can_be_caught() {
+        let mut vm = make_vm(SandboxSettings {
+            stubbed: vec!["io.read_to_string".into()],
+            ..Default::default()
+        });
+        assert!(matches!(
+            run_script(
+                &mut vm,
+                "
+try
+  io.read_to_string 'secrets.txt'
+catch _
+  'unavailable'
+"
+            ),
+            Ok(Value::Str(s)) if s.as_str() == "unavailable"
+        ));
+    }
+
+    #[test]
+    fn untrusted_profile() {
+        let mut vm = make_vm(SandboxSettings::untrusted());
+        check_error(run_script(&mut vm, "os.name()"), "'os' not found");
+        check_error(
+            run_script(&mut vm, "io.create 'foo.txt'"),
+            "'io.create' is disabled by the sandbox settings",
+        );
+        check_error(
+            run_script(&mut vm, "io.remove_file 'foo.txt'"),
+            "'io.remove_file' is disabled by the sandbox settings",
+        );
+        assert!(run_script(&mut vm, "koto.type io.print").is_ok());
+        assert!(run_script(&mut vm, "[1, 2, 3].size()").is_ok());
+    }
+
+    #[test]
+    fn unknown_names_are_ignored() {
+        let mut vm = make_vm(SandboxSettings {
+            excluded: vec!["foo".into(), "io.foo".into()],
+            stubbed: vec!["bar".into(), "io.bar".into()],
+            ..Default::default()
+        });
+        assert!(run_script(&mut vm, "koto.type io.print").is_ok());
+    }
+
+    // Provides a single module named 'allowed'
+    struct AllowedResolver;
+
+    impl ModuleResolver for AllowedResolver {
+        fn resolve(
+            &self,
+            name: &str,
+            _importing_path: Option<&Path>,
+        ) -> Result<Option<ResolvedModule>, String> {
+            Ok((name == "allowed").then(|| ResolvedModule {
+                path: PathBuf::from("allowed.koto"),
+                source: b"export x = 42".to_vec(),
+            }))
+        }
+    }
+
+    // A directory containing a script and a module that mustn't be readable when sandboxed,
+    // the module contains a syntax error so that its contents would be shown in an error excerpt
+    struct ScriptDir(PathBuf);
+
+    impl ScriptDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("koto_sandbox_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.koto"), "").unwrap();
+            fs::write(dir.join("secret.koto"), "api_key = \"hunter2\" (").unwrap();
+            Self(dir)
+        }
+
+        fn run(&self, vm: &mut Vm, script: &str) -> Result<Value, String> {
+            let chunk = Loader::default()
+                .compile_script(script, &Some(self.0.join("main.koto")))
+                .unwrap();
+            vm.run(chunk).map_err(|error| error.to_string())
+        }
+    }
+
+    impl Drop for ScriptDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn check_refused_import(result: Result<Value, String>) {
+        match result {
+            Err(error) => {
+                assert!(
+                    error.contains("importing modules from the filesystem is disabled"),
+                    "Unexpected error: {}",
+                    error
+                );
+                assert!(!error.contains("hunter2"), "Unexpected error: {}", error);
+            }
+            Ok(value) => panic!("Expected an error, found: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn filesystem_imports_are_refused_by_the_untrusted_profile() {
+        let dir = ScriptDir::new("untrusted");
+        let mut vm = make_vm(SandboxSettings::untrusted());
+
+        check_refused_import(dir.run(&mut vm, "import secret"));
+        let absolute_path = dir.0.join("secret");
+        check_refused_import(dir.run(
+            &mut vm,
+            &format!("import '{}'", absolute_path.to_string_lossy()),
+        ));
+    }
+
+    #[test]
+    fn resolver_only_imports() {
+        let dir = ScriptDir::new("resolver_only");
+        let mut vm = Vm::with_settings(VmSettings {
+            module_resolver: Some(Arc::new(AllowedResolver)),
+            sandbox: SandboxSettings {
+                imports: ImportPolicy::ResolverOnly,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert!(matches!(
+            dir.run(&mut vm, "import allowed\nallowed.x"),
+            Ok(Value::Number(n)) if n == 42
+        ));
+        check_refused_import(dir.run(&mut vm, "import secret"));
+        check_refused_import(dir.run(&mut vm, "from secret import api_key"));
+    }
+
+    #[test]
+    fn filesystem_imports_are_allowed_by_default() {
+        let dir = ScriptDir::new("unrestricted");
+        let mut vm = make_vm(SandboxSettings::default());
+        // The module is found and loaded, producing a syntax error
+        let error = dir.run(&mut vm, "import secret").unwrap_err();
+        assert!(
+            !error.contains("is disabled"),
+            "Unexpected error: {}",
+            error
+        );
+    }
+}
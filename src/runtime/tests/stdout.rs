@@ -1,6 +1,9 @@
 use {
     koto_bytecode::Chunk,
-    koto_runtime::{KotoFile, KotoRead, KotoWrite, Loader, Mutex, RuntimeError, Vm, VmSettings},
+    koto_runtime::{
+        runtime_error, CallArgs, KotoFile, KotoRead, KotoWrite, Loader, Mutex, RuntimeError, Value,
+        Vm, VmSettings,
+    },
     std::{fmt, sync::Arc},
 };
 
@@ -110,6 +113,26 @@ foo 4
         check_logged_output(script, "[1] 2 + 2: 4\n");
     }
 
+    #[test]
+    fn float_display() {
+        let script = "
+import io.print
+print 1.0
+print -1.25
+print 0.1 + 0.2
+print (num2 -1.25, 2)
+";
+        check_logged_output(
+            script,
+            "\
+1.0
+-1.25
+0.30000000000000004
+num2(-1.25, 2)
+",
+        );
+    }
+
     #[test]
     fn write_via_stdout() {
         let script = "
@@ -134,3 +157,135 @@ stderr.write_line '789'
         check_logged_output(script, "123456789\n");
     }
 }
+
+mod redirected_output {
+    use super::*;
+
+    fn make_output() -> (Arc<dyn KotoFile>, Arc<Mutex<String>>) {
+        let output = Arc::new(Mutex::new(String::new()));
+        let file = Arc::new(TestStdout {
+            output: output.clone(),
+        });
+        (file, output)
+    }
+
+    // Makes a VM with captured output, along with a `capture` function that returns the output
+    // of the function that it's called with
+    fn make_vm() -> (Vm, Arc<Mutex<String>>) {
+        let (stdout, output) = make_output();
+        let vm = Vm::with_settings(VmSettings {
+            stdout: stdout.clone(),
+            stderr: stdout,
+            ..Default::default()
+        });
+
+        vm.prelude()
+            .add_fn("capture", |vm, args| match vm.get_args(args) {
+                [f] if f.is_callable() => {
+                    let f = f.clone();
+                    let (file, output) = make_output();
+                    vm.run_function_with_output(f, CallArgs::None, file.clone(), file)?;
+                    let result = output.lock().clone();
+                    Ok(result.into())
+                }
+                _ => runtime_error!("capture: Expected a function as argument"),
+            });
+
+        (vm, output)
+    }
+
+    fn run_script(vm: &mut Vm, script: &str) -> Value {
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+        match vm.run(chunk) {
+            Ok(result) => result,
+            Err(error) => panic!("Error while running script: {}", error),
+        }
+    }
+
+    fn check_string(value: Value, expected: &str) {
+        match value {
+            Value::Str(s) => assert_eq!(s.as_str(), expected),
+            other => panic!("Expected string, found: {}", other),
+        }
+    }
+
+    #[test]
+    fn output_is_captured_for_a_single_call() {
+        let (mut vm, vm_output) = make_vm();
+        let f = run_script(
+            &mut vm,
+            "
+io.print 'before'
+||
+  io.print 'during'
+  io.stderr().write_line 'error'
+",
+        );
+
+        let (stdout, call_stdout) = make_output();
+        let (stderr, call_stderr) = make_output();
+        vm.run_function_with_output(f, CallArgs::None, stdout, stderr)
+            .unwrap();
+        run_script(&mut vm, "io.print 'after'");
+
+        assert_eq!(call_stdout.lock().as_str(), "during\n");
+        assert_eq!(call_stderr.lock().as_str(), "error\n");
+        assert_eq!(vm_output.lock().as_str(), "before\nafter\n");
+    }
+
+    #[test]
+    fn output_is_restored_after_an_error() {
+        let (mut vm, vm_output) = make_vm();
+        let f = run_script(
+            &mut vm,
+            "
+||
+  io.print 'during'
+  throw 'oops'
+",
+        );
+
+        let (stdout, call_stdout) = make_output();
+        assert!(vm
+            .run_function_with_output(f, CallArgs::None, stdout.clone(), stdout)
+            .is_err());
+        run_script(&mut vm, "io.print 'after'");
+
+        assert_eq!(call_stdout.lock().as_str(), "during\n");
+        assert_eq!(vm_output.lock().as_str(), "after\n");
+    }
+
+    #[test]
+    fn nested_redirects() {
+        let (mut vm, vm_output) = make_vm();
+        let result = run_script(
+            &mut vm,
+            "
+capture ||
+  io.print 'a'
+  io.print capture || io.print 'b'
+  io.print 'c'
+",
+        );
+
+        check_string(result, "a\nb\n\nc\n");
+        assert!(vm_output.lock().is_empty());
+    }
+
+    #[test]
+    fn spawned_vms_use_the_redirected_output() {
+        let (mut vm, vm_output) = make_vm();
+        let result = run_script(
+            &mut vm,
+            "
+capture ||
+  (1..=2).each(|n| io.print 'each {}', n).consume()
+  t = thread.create || io.print 'thread'
+  t.join()
+",
+        );
+
+        check_string(result, "each 1\neach 2\nthread\n");
+        assert!(vm_output.lock().is_empty());
+    }
+}
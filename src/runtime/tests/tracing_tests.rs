@@ -0,0 +1,160 @@
+#![cfg(feature = "tracing")]
+
+mod tracing_spans {
+    use {
+        koto_runtime::{Loader, Mutex, Vm, VmSettings},
+        std::{
+            fmt,
+            sync::{
+                atomic::{AtomicU64, Ordering},
+                Arc,
+            },
+        },
+        tracing::{
+            field::{Field, Visit},
+            span, Event, Id, Metadata, Subscriber,
+        },
+    };
+
+    // A span that was created while running a script
+    #[derive(Debug, PartialEq)]
+    struct RecordedSpan {
+        name: &'static str,
+        function: Option<String>,
+        parent: Option<u64>,
+    }
+
+    // A subscriber that records the spans that are created
+    #[derive(Default)]
+    struct Recorder {
+        next_id: AtomicU64,
+        spans: Mutex<Vec<RecordedSpan>>,
+        entered: Mutex<Vec<u64>>,
+    }
+
+    struct FunctionVisitor(Option<String>);
+
+    impl Visit for FunctionVisitor {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "function" {
+                self.0 = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &span::Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+
+            let parent = if attributes.is_contextual() {
+                self.entered.lock().last().copied()
+            } else {
+                attributes.parent().map(|parent| parent.into_u64())
+            };
+
+            let mut visitor = FunctionVisitor(None);
+            attributes.record(&mut visitor);
+
+            self.spans.lock().push(RecordedSpan {
+                name: attributes.metadata().name(),
+                function: visitor.0,
+                parent,
+            });
+
+            Id::from_u64(id)
+        }
+
+        fn record(&self, _span: &Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.entered.lock().pop();
+        }
+    }
+
+    fn run_with_spans(script: &str, trace_sample_interval: u64) -> Vec<RecordedSpan> {
+        let recorder = Arc::new(Recorder::default());
+        let mut vm = Vm::with_settings(VmSettings {
+            trace_sample_interval,
+            ..Default::default()
+        });
+        let chunk = Loader::default().compile_script(script, &None).unwrap();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            if let Err(error) = vm.run(chunk) {
+                panic!("Error while running script: {}", error);
+            }
+        });
+
+        let spans = std::mem::take(&mut *recorder.spans.lock());
+        spans
+    }
+
+    fn call_span(function: &str, parent: Option<u64>) -> RecordedSpan {
+        RecordedSpan {
+            name: "koto.call",
+            function: Some(function.to_string()),
+            parent,
+        }
+    }
+
+    #[test]
+    fn calls_and_external_calls() {
+        let script = "
+f = |x| x.size()
+f [1, 2]
+";
+        let spans = run_with_spans(script, 1);
+
+        assert_eq!(
+            spans,
+            &[
+                call_span("<top level>", None),
+                call_span("f", Some(1)),
+                RecordedSpan {
+                    name: "koto.external_call",
+                    function: None,
+                    parent: Some(2),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_are_sampled() {
+        let script = "
+f = |x| x
+for i in 0..4
+  f i
+";
+        let spans = run_with_spans(script, 2);
+
+        assert_eq!(
+            spans,
+            &[
+                call_span("<top level>", None),
+                call_span("f", Some(1)),
+                call_span("f", Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn call_spans_can_be_disabled() {
+        let spans = run_with_spans("f = |x| x\nf 1", 0);
+        assert!(spans.is_empty());
+    }
+}
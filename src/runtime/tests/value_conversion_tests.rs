@@ -0,0 +1,70 @@
+mod value_conversion {
+    use koto_runtime::{expect_arg, expect_entry, FromValue, Value, ValueMap};
+
+    fn number(n: f64) -> Value {
+        Value::Number(n.into())
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(f64::from_value(&number(1.5)), Some(1.5));
+        assert_eq!(f32::from_value(&Value::Number(2.into())), Some(2.0));
+        assert_eq!(i64::from_value(&Value::Number((-3).into())), Some(-3));
+        assert_eq!(f64::from_value(&Value::Bool(true)), None);
+    }
+
+    #[test]
+    fn integers_from_floats() {
+        assert_eq!(i32::from_value(&number(4.0)), Some(4));
+        assert_eq!(i32::from_value(&number(4.5)), None);
+        assert_eq!(usize::from_value(&number(-1.0)), None);
+        assert_eq!(usize::from_value(&Value::Number((-1).into())), None);
+        assert_eq!(u32::from_value(&Value::Number(i64::MAX.into())), None);
+    }
+
+    #[test]
+    fn optional_values() {
+        assert_eq!(Option::<f64>::from_value(&Value::Empty), Some(None));
+        assert_eq!(Option::<f64>::from_value(&number(1.0)), Some(Some(1.0)));
+        assert_eq!(Option::<f64>::from_value(&Value::Str("x".into())), None);
+    }
+
+    #[test]
+    fn args() {
+        let args = [Value::Str("hello".into()), number(42.0)];
+
+        assert_eq!(expect_arg::<String>("f", &args, 0).unwrap(), "hello");
+        assert_eq!(expect_arg::<i64>("f", &args, 1).unwrap(), 42);
+        assert_eq!(expect_arg::<Option<bool>>("f", &args, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn arg_errors() {
+        let args = [Value::Str("hello".into())];
+
+        let error = expect_arg::<f64>("f", &args, 0).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "f - Expected Number as argument 1, found String"
+        );
+
+        let error = expect_arg::<bool>("f", &args, 1).unwrap_err();
+        assert_eq!(format!("{:#}", error), "f - Expected Bool as argument 2");
+    }
+
+    #[test]
+    fn map_entries() {
+        let mut map = ValueMap::new();
+        map.add_value("x", number(1.0));
+        map.add_value("name", Value::Str("abc".into()));
+
+        assert_eq!(expect_entry::<f64>("f", &map, "x").unwrap(), 1.0);
+        assert_eq!(expect_entry::<Option<f64>>("f", &map, "y").unwrap(), None);
+
+        let error = expect_entry::<f64>("f", &map, "name").unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "f - Expected Number for 'name', found String"
+        );
+    }
+}
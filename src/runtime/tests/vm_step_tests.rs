@@ -0,0 +1,267 @@
+mod vm_step {
+    use {
+        koto_bytecode::Chunk,
+        koto_runtime::{Loader, StepResult, Value, Vm},
+        std::sync::Arc,
+    };
+
+    fn compile(script: &str) -> Arc<Chunk> {
+        match Loader::default().compile_script(script, &None) {
+            Ok(chunk) => chunk,
+            Err(error) => panic!("Error while compiling script: {}", error),
+        }
+    }
+
+    fn expect_number(value: Value, expected: i64) {
+        match value {
+            Value::Number(n) => assert_eq!(n, expected),
+            other => panic!("Expected {}, found {}", expected, other),
+        }
+    }
+
+    // Steps through the script until it's finished, returning the result and the number of steps
+    fn step_to_end(vm: &mut Vm, instructions_per_step: usize) -> (Value, usize) {
+        let mut step_count = 0;
+        loop {
+            step_count += 1;
+            match vm.step(instructions_per_step) {
+                StepResult::Continue => {}
+                StepResult::Finished(result) => return (result, step_count),
+                StepResult::Yielded(value) => panic!("Unexpected yield: {}", value),
+                StepResult::Error(error) => panic!("Error while stepping: {}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn step_through_loop() {
+        let script = "
+x = 0
+for i in 0..100
+  x += i
+x
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        let (result, step_count) = step_to_end(&mut vm, 10);
+        expect_number(result, 4950);
+        assert!(step_count > 10);
+    }
+
+    #[test]
+    fn suspension_across_call_frames() {
+        let script = "
+fib = |n|
+  if n < 2
+    n
+  else
+    (fib n - 1) + (fib n - 2)
+fib 10
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        let (result, _) = step_to_end(&mut vm, 1);
+        expect_number(result, 55);
+    }
+
+    #[test]
+    fn zero_instructions() {
+        let mut vm = Vm::default();
+        vm.start(compile("42"));
+
+        assert!(matches!(vm.step(0), StepResult::Continue));
+        let (result, _) = step_to_end(&mut vm, 100);
+        expect_number(result, 42);
+    }
+
+    #[test]
+    fn finished_vm_returns_empty() {
+        let mut vm = Vm::default();
+        vm.start(compile("99"));
+
+        let (result, _) = step_to_end(&mut vm, 100);
+        expect_number(result, 99);
+        assert!(matches!(vm.step(100), StepResult::Finished(Value::Empty)));
+    }
+
+    #[test]
+    fn yielded_values() {
+        let script = "
+yield 1
+yield 2
+3
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        let mut yielded = Vec::new();
+        let result = loop {
+            match vm.step(1) {
+                StepResult::Continue => {}
+                StepResult::Yielded(Value::Number(n)) => yielded.push(i64::from(n)),
+                StepResult::Finished(result) => break result,
+                other => panic!("Unexpected step result: {:?}", other),
+            }
+        };
+
+        assert_eq!(yielded, [1, 2]);
+        expect_number(result, 3);
+    }
+
+    #[test]
+    fn caught_error() {
+        let script = "
+x = try
+  throw 'oops'
+catch _
+  -1
+x
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        let (result, _) = step_to_end(&mut vm, 1);
+        expect_number(result, -1);
+    }
+
+    #[test]
+    fn uncaught_error() {
+        let script = "
+f = || throw 'oops'
+f()
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        let error = loop {
+            match vm.step(1) {
+                StepResult::Continue => {}
+                StepResult::Error(error) => break error,
+                other => panic!("Unexpected step result: {:?}", other),
+            }
+        };
+
+        assert!(error.to_string().contains("oops"));
+        assert!(matches!(vm.step(1), StepResult::Finished(Value::Empty)));
+    }
+
+    #[test]
+    fn many_interleaved_vms() {
+        let script = "
+count = 0
+for i in 0..limit
+  count += 1
+  export progress = count
+count
+";
+
+        let vm_count = 20;
+        let mut vms = (0..vm_count)
+            .map(|i| {
+                let mut vm = Vm::default();
+                vm.context_mut()
+                    .exports
+                    .add_value("limit", Value::Number((i * 10).into()));
+                vm.start(compile(script));
+                (vm, None)
+            })
+            .collect::<Vec<_>>();
+
+        // Round-robin scheduling, with each VM given a small time slice in turn
+        let mut rounds = 0;
+        while vms.iter().any(|(_, result)| result.is_none()) {
+            rounds += 1;
+            for (vm, result) in vms.iter_mut().filter(|(_, result)| result.is_none()) {
+                match vm.step(7) {
+                    StepResult::Continue => {}
+                    StepResult::Finished(value) => *result = Some(value),
+                    other => panic!("Unexpected step result: {:?}", other),
+                }
+            }
+
+            // While the scheduler is running, each VM's progress is visible in its exports
+            if rounds == 10 {
+                let (vm, _) = &vms[vm_count - 1];
+                match vm.get_exported_value("progress") {
+                    Some(Value::Number(n)) => assert!(n > 0 && n < ((vm_count - 1) * 10)),
+                    other => panic!("Unexpected progress: {:?}", other),
+                }
+            }
+        }
+
+        for (i, (_, result)) in vms.into_iter().enumerate() {
+            expect_number(result.unwrap(), i as i64 * 10);
+        }
+        assert!(rounds > 10);
+    }
+
+    // Returns the line of the next instruction to be executed
+    fn current_line(vm: &Vm) -> u32 {
+        vm.current_span().unwrap().start.line
+    }
+
+    #[test]
+    fn run_until_breakpoint() {
+        let script = "
+x = 0
+for i in 0..5
+  x += i
+x * 2
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        // The breakpoint is hit when execution arrives at line 4 from another line,
+        // with execution continuing past it each time it's hit
+        let mut hits = 0;
+        let mut previous_line = 0;
+        let result = loop {
+            let breakpoint = |vm: &Vm| {
+                let line = current_line(vm);
+                let hit = line == 4 && previous_line != 4;
+                previous_line = line;
+                hit
+            };
+            match vm.run_until(breakpoint) {
+                StepResult::Continue => {
+                    assert_eq!(current_line(&vm), 4);
+                    hits += 1;
+                }
+                StepResult::Finished(result) => break result,
+                other => panic!("Unexpected step result: {:?}", other),
+            }
+        };
+
+        assert_eq!(hits, 5);
+        expect_number(result, 20);
+    }
+
+    #[test]
+    fn step_through_statements() {
+        let script = "
+f = |n|
+  x = n * 2
+  x + 1
+y = f 10
+y * 2
+";
+        let mut vm = Vm::default();
+        vm.start(compile(script));
+
+        let mut positions = vec![(current_line(&vm), vm.call_depth())];
+        let result = loop {
+            match vm.step_statement() {
+                StepResult::Continue => positions.push((current_line(&vm), vm.call_depth())),
+                StepResult::Finished(result) => break result,
+                other => panic!("Unexpected step result: {:?}", other),
+            }
+        };
+
+        expect_number(result, 42);
+        // Stepping follows execution into the function, and then on to the next statement
+        assert_eq!(positions, &[(2, 1), (5, 1), (3, 2), (4, 2), (6, 1)]);
+        assert_eq!(vm.call_depth(), 0);
+    }
+}
@@ -4,10 +4,10 @@ mod vm {
     use {
         crate::runtime_test_utils::{
             num2, num4, number, number_list, number_tuple, string, test_script,
-            test_script_with_vm, value_tuple,
+            test_script_with_vm, value_list, value_tuple,
         },
         koto_runtime::{
-            runtime_error, DataMap, IntRange, Value, Value::*, ValueList, ValueMap, Vm,
+            runtime_error, DataMap, IntRange, Value, Value::*, ValueList, ValueMap, Vm, VmSettings,
         },
     };
 
@@ -71,6 +71,55 @@ mod vm {
             test_script("1 + 1 == 2 and 2 + 2 != 5", true.into());
         }
 
+        #[test]
+        fn small_int_rhs() {
+            let script = "
+x = 10
+y = 1.5
+x + 1, x - 255, x + 256, y + 2, y - 2, -x + 0";
+            test_script(
+                script,
+                value_tuple(&[
+                    11.into(),
+                    number(-245),
+                    266.into(),
+                    3.5.into(),
+                    number(-0.5),
+                    number(-10),
+                ]),
+            );
+        }
+
+        #[test]
+        fn small_int_rhs_comparisons() {
+            let script = "
+x = 10
+y = 10.5
+(x < 11, x <= 9, x > 9, x >= 11, x == 10, x != 10,
+ y < 10, y > 10, y == 10, 'ten' == 10, 'ten' != 10)";
+            test_script(
+                script,
+                value_tuple(&[
+                    true.into(),
+                    false.into(),
+                    true.into(),
+                    false.into(),
+                    true.into(),
+                    false.into(),
+                    false.into(),
+                    true.into(),
+                    false.into(),
+                    false.into(),
+                    true.into(),
+                ]),
+            );
+        }
+
+        #[test]
+        fn small_int_rhs_with_num2() {
+            test_script("x = num2 1, 2\nx + 1", num2(2.0, 3.0));
+        }
+
         #[test]
         fn not_bool() {
             test_script("not false", true.into());
@@ -96,6 +145,65 @@ a = 99
 -a";
             test_script(script, number(-99));
         }
+
+        #[test]
+        fn module_level_constant_expressions() {
+            // The module-level values are evaluated by the compiler,
+            // and should match the results of the same expressions evaluated at runtime.
+            let script = "
+x = 1 + 2 * 3, 7 / 2, -7 % 3, 2.5 * -2, 'foo' + 'bar', [0.5 - 1, 10 % 4]
+f = || (1 + 2 * 3, 7 / 2, -7 % 3, 2.5 * -2, 'foo' + 'bar', [0.5 - 1, 10 % 4])
+x == f()";
+            test_script(script, true.into());
+        }
+
+        #[test]
+        fn in_list_and_tuple() {
+            let script = "
+x = [1, 2, 3]
+y = ('a', 'b')
+2 in x and not 4 in x and 'b' in y and not 'c' in y";
+            test_script(script, true.into());
+        }
+
+        #[test]
+        fn in_map() {
+            let script = "
+m = {foo: 42, bar: 99}
+'foo' in m and not 'baz' in m";
+            test_script(script, true.into());
+        }
+
+        #[test]
+        fn in_string() {
+            test_script("'ell' in 'hello' and not 'xyz' in 'hello'", true.into());
+        }
+
+        #[test]
+        fn in_range() {
+            test_script(
+                "2 in (0..3) and not 3 in (0..3) and 3 in (0..=3)",
+                true.into(),
+            );
+        }
+
+        #[test]
+        fn in_range_without_parentheses() {
+            test_script("x = 5\nx in 0..10 and not x in 0..5", true.into());
+        }
+
+        #[test]
+        fn in_descending_range() {
+            test_script(
+                "3 in 5..0 and 5 in 5..0 and not 0 in 5..0 and 0 in 5..=0",
+                true.into(),
+            );
+        }
+
+        #[test]
+        fn in_range_with_non_integer() {
+            test_script("not 1.5 in 0..3 and not 2.5 in 3..0", true.into());
+        }
     }
 
     mod ranges {
@@ -367,11 +475,91 @@ a, b = a+b, a%b
 b";
             test_script(script, 3.into());
         }
+
+        #[test]
+        fn unpack_list_with_ellipsis_in_last_position() {
+            let script = "
+a, rest... = [1, 2, 3]
+a, rest";
+            test_script(script, value_tuple(&[1.into(), number_list(&[2, 3])]));
+        }
+
+        #[test]
+        fn unpack_list_with_ellipsis_in_first_position() {
+            let script = "
+rest..., a = [1, 2, 3]
+rest, a";
+            test_script(script, value_tuple(&[number_list(&[1, 2]), 3.into()]));
+        }
+
+        #[test]
+        fn unpack_temp_tuple_with_ellipsis() {
+            let script = "
+a, b, rest... = 1, 2, 3, 4
+a, b, rest";
+            test_script(
+                script,
+                value_tuple(&[1.into(), 2.into(), number_tuple(&[3, 4])]),
+            );
+        }
+
+        #[test]
+        fn unpack_temp_tuple_with_ellipsis_in_first_position() {
+            let script = "
+rest..., a, b = 1, 2
+rest, a, b";
+            test_script(script, value_tuple(&[value_tuple(&[]), 1.into(), 2.into()]));
+        }
+
+        #[test]
+        fn unpack_list_with_ellipsis_in_first_position_and_missing_values() {
+            let script = "
+rest..., a, b, c = [1, 2]
+rest, a, b, c";
+            test_script(
+                script,
+                value_tuple(&[value_list(&[]), Value::Empty, 1.into(), 2.into()]),
+            );
+        }
+
+        #[test]
+        fn unpack_temp_tuple_with_ellipsis_in_first_position_and_missing_values() {
+            let script = "
+rest..., a, b, c = 1, 2
+rest, a, b, c";
+            test_script(
+                script,
+                value_tuple(&[value_tuple(&[]), Value::Empty, 1.into(), 2.into()]),
+            );
+        }
     }
 
     mod if_expressions {
         use super::*;
 
+        #[test]
+        fn guard_inline() {
+            let script = "
+f = |x|
+  guard x > 0 else return -1
+  x * 2
+(f -10), (f 21)";
+            test_script(script, number_tuple(&[-1, 42]));
+        }
+
+        #[test]
+        fn guard_block() {
+            let script = "
+count = 0
+for i in 0..10
+  guard i % 2 == 0 else
+    count += 100
+    continue
+  count += 1
+count";
+            test_script(script, 505.into());
+        }
+
         #[test]
         fn if_else_if_result_from_if() {
             let script = "
@@ -538,6 +726,18 @@ match 42
             test_script(script, 33.into());
         }
 
+        #[test]
+        fn match_on_alternative_with_shared_bindings() {
+            let script = "
+f = |x|
+  match x
+    (n, 0) or (0, n) if n > 1 then n * 10
+    (a, b) then a + b
+f((5, 0)) + f((0, 7)) + f((0, 1))
+";
+            test_script(script, 121.into());
+        }
+
         #[test]
         fn match_tuple() {
             let script = "
@@ -567,7 +767,7 @@ match [1, [2, 3], [4, 5, 6]]
 x = [0]
 match x
   [0] or [1] then 123
-  [x, y] or [x, y, z] then 99
+  [x, y] or [x, y, _] then 99
   else -1
 ";
             test_script(script, 123.into());
@@ -706,6 +906,126 @@ m
 "#;
             test_script(script, number_tuple(&[3, 4]));
         }
+
+        #[test]
+        fn jump_table_with_int_patterns() {
+            let script = r#"
+f = |x|
+  match x
+    0 then "zero"
+    1 or 2 then "small"
+    100 then "big"
+    -5 then "negative"
+    2 then "unreachable"
+    else "other"
+(0, 1, 2, 2.0, 2.5, 100, -5, 99, "x").each(f).to_tuple()
+"#;
+            test_script(
+                script,
+                value_tuple(&[
+                    string("zero"),
+                    string("small"),
+                    string("small"),
+                    string("small"),
+                    string("other"),
+                    string("big"),
+                    string("negative"),
+                    string("other"),
+                    string("other"),
+                ]),
+            );
+        }
+
+        #[test]
+        fn jump_table_with_string_patterns() {
+            let script = r#"
+f = |x|
+  match x
+    "a" then 1
+    "bb" then 2
+    "c" or "d" then 3
+    "e" then 4
+    y then y
+(f "a"), (f "bb"), (f "d"), (f "e"), (f "zz"), (f 42)
+"#;
+            test_script(
+                script,
+                value_tuple(&[
+                    1.into(),
+                    2.into(),
+                    3.into(),
+                    4.into(),
+                    string("zz"),
+                    42.into(),
+                ]),
+            );
+        }
+
+        #[test]
+        fn jump_table_without_catch_all_arm() {
+            let script = r#"
+f = |x|
+  match x
+    1 then "one"
+    2 then "two"
+    3 then "three"
+    4 then "four"
+(f 4), (f 5)
+"#;
+            test_script(script, value_tuple(&[string("four"), Empty]));
+        }
+
+        #[test]
+        fn jump_table_with_conditions() {
+            let script = r#"
+f = |x, flag|
+  match x
+    1 if flag then "one"
+    2 then "two"
+    1 or 3 then "one or three"
+    4 if flag then "four"
+    else "other"
+(f 1, true), (f 1, false), (f 3, true), (f 4, true), (f 4, false)
+"#;
+            test_script(
+                script,
+                value_tuple(&[
+                    string("one"),
+                    string("one or three"),
+                    string("one or three"),
+                    string("four"),
+                    string("other"),
+                ]),
+            );
+        }
+
+        #[test]
+        fn jump_table_followed_by_other_arms() {
+            let script = r#"
+f = |x|
+  match x
+    0 then "zero"
+    1 then "one"
+    2 then "two"
+    3 then "three"
+    (a, b) then a + b
+    n if n == -1 then "minus one"
+    4 then "four"
+    else "other"
+(f 2), (f -1), (f (10, 20)), (f 4), (f 5), (f "x")
+"#;
+            test_script(
+                script,
+                value_tuple(&[
+                    string("two"),
+                    string("minus one"),
+                    30.into(),
+                    string("four"),
+                    string("other"),
+                    string("other"),
+                ]),
+            );
+        }
     }
 
     mod switch_expressions {
@@ -744,17 +1064,17 @@ x
             let mut prelude = vm.prelude();
 
             prelude.add_value("test_value", 42.into());
-            prelude.add_fn("assert", |vm, args| {
+            prelude.add_fn("check", |vm, args| {
                 for value in vm.get_args(args).iter() {
                     match value {
                         Bool(b) => {
                             if !b {
-                                return runtime_error!("Assertion failed");
+                                return runtime_error!("Check failed");
                             }
                         }
                         unexpected => {
                             return runtime_error!(
-                                "assert expects booleans as arguments, found '{}'",
+                                "check expects booleans as arguments, found '{}'",
                                 unexpected.type_as_string(),
                             )
                         }
@@ -774,17 +1094,51 @@ x
 
         #[test]
         fn function() {
-            let script = "assert 1 + 1 == 2";
+            let script = "check 1 + 1 == 2";
             test_script_with_prelude(script, Empty);
         }
 
         #[test]
         fn function_two_args() {
-            let script = "assert 1 + 1 == 2, 2 < 3";
+            let script = "check 1 + 1 == 2, 2 < 3";
             test_script_with_prelude(script, Empty);
         }
     }
 
+    mod assert {
+        use super::*;
+
+        #[test]
+        fn passing_assertion() {
+            test_script("assert 1 + 1 == 2", Empty);
+        }
+
+        #[test]
+        fn passing_chained_comparison() {
+            test_script("assert 1 < 2 <= 2", Empty);
+        }
+
+        #[test]
+        fn assert_in_function() {
+            let script = "
+f = |x|
+  assert x > 0
+  x * 2
+f 21";
+            test_script(script, 42.into());
+        }
+
+        #[test]
+        fn assert_as_a_quoted_map_key() {
+            let script = r#"
+x = {"assert": 42}
+test.assert x.assert == 42
+x.assert
+"#;
+            test_script(script, 42.into());
+        }
+    }
+
     mod functions {
         use super::*;
 
@@ -1131,6 +1485,29 @@ x";
             test_script(script, 42.into());
         }
 
+        #[test]
+        fn multi_export_assignment() {
+            let script = "
+f = ||
+  export x, y = 1, 2
+f()
+x, y";
+            test_script(script, number_tuple(&[1, 2]));
+        }
+
+        #[test]
+        fn export_existing_values() {
+            let script = "
+f = ||
+  a, b, c = 1, 2, 3
+  export a
+  export
+    b, c
+f()
+a, b, c";
+            test_script(script, number_tuple(&[1, 2, 3]));
+        }
+
         #[test]
         fn multi_assignment_of_function_results() {
             let script = "
@@ -1248,6 +1625,339 @@ calls
                 test_script(script, number_list(&[1, 110, 2, 120, 3, 130, 4, 140]));
             }
         }
+
+        mod type_annotations {
+            use super::*;
+
+            fn type_checking_vm() -> Vm {
+                Vm::with_settings(VmSettings {
+                    check_type_annotations: true,
+                    ..Default::default()
+                })
+            }
+
+            #[test]
+            fn annotations_are_ignored_by_default() {
+                let script = r#"
+f = |x: Number, y: String| -> Bool
+  x
+f "hello", 42
+"#;
+                test_script(script, string("hello"));
+            }
+
+            #[test]
+            fn arg_types() {
+                let script = r#"
+f = |n: Int, x: Float, s: String, xs: List, t: Tuple, m: Map, g: Function, a: Any| s
+f 1, 1.5, "foo", [], (1, 2), {}, (|| 42), 99
+"#;
+                test_script_with_vm(type_checking_vm(), script, string("foo"));
+            }
+
+            #[test]
+            fn number_matches_ints_and_floats() {
+                let script = "
+f = |x: Number| -> Number
+  x * 2
+(f 1) + (f 1.5)
+";
+                test_script_with_vm(type_checking_vm(), script, 5.into());
+            }
+
+            #[test]
+            fn map_with_overloaded_type() {
+                let script = "
+make_foo = || -> Foo
+  {@type: 'Foo'}
+f = |foo: Foo| koto.type foo
+f make_foo()
+";
+                test_script_with_vm(type_checking_vm(), script, string("Foo"));
+            }
+
+            #[test]
+            fn instance_function_with_annotations() {
+                let script = "
+m =
+  x: 42
+  get: |self, y: Int| -> Int
+    self.x + y
+m.get 1
+";
+                test_script_with_vm(type_checking_vm(), script, 43.into());
+            }
+        }
+
+        mod forward_definitions {
+            use super::*;
+
+            #[test]
+            fn mutual_recursion() {
+                let script = "
+is_even = |n| if n == 0 then true else is_odd n - 1
+is_odd = |n| if n == 0 then false else is_even n - 1
+is_even 10
+";
+                test_script(script, true.into());
+            }
+
+            #[test]
+            fn mutual_recursion_in_function_body() {
+                let script = "
+f = |x|
+  a = |n| if n > 100 then n else b n + 1
+  y = 42
+  b = |n| a n * 2
+  a x
+f 1
+";
+                test_script(script, 190.into());
+            }
+
+            #[test]
+            fn forward_definition_referred_to_by_several_functions() {
+                let script = "
+a = || c() + 1
+b = || c() + 2
+c = || 10
+a() + b()
+";
+                test_script(script, 23.into());
+            }
+
+            #[test]
+            fn decorated_forward_definition() {
+                let script = "
+double = |f| |x| (f x) * 2
+a = |x| b x
+b = @double |x| x + 1
+a 1
+";
+                test_script(script, 4.into());
+            }
+        }
+
+        mod decorators {
+            use super::*;
+
+            #[test]
+            fn single_decorator() {
+                let script = "
+double = |f| |x| (f x) * 2
+f = @double |x| x + 1
+f 10
+";
+                test_script(script, 22.into());
+            }
+
+            #[test]
+            fn decorators_are_applied_in_reverse_order() {
+                let script = "
+add_one = |f| |x| (f x) + 1
+double = |f| |x| (f x) * 2
+f = @add_one @double |x| x
+f 10
+";
+                test_script(script, 21.into());
+            }
+
+            #[test]
+            fn recursive_decorated_function() {
+                let script = "
+calls = [0]
+counted = |f|
+  |n|
+    calls[0] += 1
+    f n
+memoize = |f|
+  cache = {}
+  |n|
+    match cache.get n
+      ()
+        result = f n
+        cache.insert n, result
+        result
+      cached then cached
+fib = @memoize @counted |n|
+  if n < 2 then n else (fib n - 1) + (fib n - 2)
+(fib 20), calls[0]
+";
+                test_script(script, number_tuple(&[6765, 21]));
+            }
+
+            #[test]
+            fn decorated_map_entry() {
+                let script = "
+double = |f| |x| (f x) * 2
+m =
+  foo: @double |x| x + 1
+m.foo 1
+";
+                test_script(script, 4.into());
+            }
+        }
+
+        #[test]
+        fn captures_with_unreachable_accesses() {
+            let script = "
+a, b, c = 1, 2, 3
+f = |x|
+  if false then return a
+  result = match x
+    0 then b
+    other then other + c
+    _ then a
+  return result
+  a
+(f 0), (f 10)
+";
+            test_script(script, number_tuple(&[2, 13]));
+        }
+    }
+
+    mod tail_calls {
+        use {super::*, koto_runtime::Loader};
+
+        fn tail_calls_vm() -> Vm {
+            Vm::with_settings(VmSettings {
+                eliminate_tail_calls: true,
+                ..Default::default()
+            })
+        }
+
+        fn test_tail_calls(script: &str, expected_output: Value) {
+            test_script_with_vm(tail_calls_vm(), script, expected_output.clone());
+            test_script(script, expected_output);
+        }
+
+        // Runs the script and returns the number of frames in the thrown error's trace
+        fn error_trace_length(mut vm: Vm, script: &str) -> usize {
+            let chunk = Loader::default().compile_script(script, &None).unwrap();
+            match vm.run(chunk) {
+                Ok(_) => panic!("Expected an error"),
+                Err(error) => error.trace.len(),
+            }
+        }
+
+        #[test]
+        fn self_recursion() {
+            let script = "
+f = |n, total|
+  if n == 0
+    total
+  else
+    f n - 1, total + n
+f 100000, 0
+";
+            test_tail_calls(script, 5000050000_i64.into());
+        }
+
+        #[test]
+        fn mutual_recursion() {
+            let script = "
+is_even = |n| if n == 0 then true else is_odd n - 1
+is_odd = |n| if n == 0 then false else is_even n - 1
+is_even(10001), is_odd(10001)
+";
+            test_tail_calls(script, value_tuple(&[false.into(), true.into()]));
+        }
+
+        #[test]
+        fn explicit_return() {
+            let script = "
+f = |n|
+  if n > 0
+    return f n - 1
+  debug_value = 99
+f 10
+";
+            test_tail_calls(script, 99.into());
+        }
+
+        #[test]
+        fn instance_function() {
+            let script = "
+m =
+  count: 0
+  count_down: |self, n|
+    if n == 0
+      return self.count
+    self.count += 1
+    self.count_down n - 1
+m.count_down 1000
+";
+            test_tail_calls(script, 1000.into());
+        }
+
+        #[test]
+        fn variadic_function() {
+            let script = "
+f = |n, rest...|
+  match n
+    0 then rest.size()
+    else f n - 1, 1, 2, 3
+f 10, 1
+";
+            test_tail_calls(script, 3.into());
+        }
+
+        #[test]
+        fn captured_values() {
+            let script = "
+x = 42
+f = |n| if n == 0 then x else f n - 1
+f 100
+";
+            test_tail_calls(script, 42.into());
+        }
+
+        #[test]
+        fn external_function() {
+            let script = "
+f = |xs| xs.size()
+g = |n| f (1..n).to_list()
+g 10
+";
+            test_tail_calls(script, 9.into());
+        }
+
+        #[test]
+        fn call_in_try_block() {
+            let script = "
+f = |n|
+  if n == 0
+    throw 'done'
+  try
+    return f n - 1
+  catch e
+    'caught at $n'
+f 3
+";
+            test_tail_calls(script, string("caught at 1"));
+        }
+
+        #[test]
+        fn tail_call_from_function_called_by_external_function() {
+            let script = "
+f = |n| if n == 0 then 'done' else f n - 1
+(1, 2, 3).each(|n| f n * 100).to_tuple()
+";
+            test_tail_calls(
+                script,
+                value_tuple(&[string("done"), string("done"), string("done")]),
+            );
+        }
+
+        #[test]
+        fn replaced_frames_are_removed_from_error_traces() {
+            let script = "
+f = |n| if n == 0 then throw 'error' else f n - 1
+f 10
+";
+            assert_eq!(error_trace_length(tail_calls_vm(), script), 2);
+            assert_eq!(error_trace_length(Vm::default(), script), 12);
+        }
     }
 
     mod loops {
@@ -1492,6 +2202,38 @@ o.foo";
             test_script(script, 30.into());
         }
 
+        #[test]
+        fn access_with_different_layouts() {
+            let script = "
+get_foo = |m| m.foo
+a = {foo: 1, bar: 2}
+b = {bar: 3, foo: 4}
+c = {bar: 5, baz: 6, foo: 7}
+(get_foo a), (get_foo b), (get_foo a), (get_foo c), (get_foo b)";
+            test_script(
+                script,
+                value_tuple(&[1.into(), 4.into(), 1.into(), 7.into(), 4.into()]),
+            );
+        }
+
+        #[test]
+        fn access_after_removing_entries() {
+            let script = "
+m = {foo: 1, bar: 2, baz: 3}
+result = []
+for key in ('foo', 'bar')
+  result.push m.baz
+  m.remove key
+m.foo = 4
+result.push m.baz
+result.push m.foo
+result";
+            test_script(
+                script,
+                value_list(&[3.into(), 3.into(), 3.into(), 4.into()]),
+            );
+        }
+
         #[test]
         fn addition() {
             let script = "
@@ -1926,6 +2668,22 @@ num2(1, -1).keep(|n| n < 0).count()
 ";
             test_script(script, 1.into());
         }
+
+        #[test]
+        fn exact_equality_by_default() {
+            let script = "(num2 0.1 + 0.2) == (num2 0.3)";
+            test_script(script, false.into());
+        }
+
+        #[test]
+        fn approximate_equality() {
+            let vm = Vm::with_settings(VmSettings {
+                approximate_num_equality: true,
+                ..Default::default()
+            });
+            let script = "(num2 0.1 + 0.2) == (num2 0.3)";
+            test_script_with_vm(vm, script, true.into());
+        }
     }
 
     mod num4 {
@@ -2375,6 +3133,22 @@ z.x
             test_script(script, 1.into());
         }
 
+        #[test]
+        fn arithmetic_with_small_int_rhs() {
+            let script = "
+foo = |x|
+  x: x
+  @+: |self, other| foo self.x + other
+  @-: |self, other| foo self.x - other
+  @<: |self, other| self.x < other
+  @==: |self, other| self.x == other
+
+z = (foo 10) + 5 - 1
+z.x, z < 15, z == 14
+";
+            test_script(script, value_tuple(&[14.into(), true.into(), true.into()]));
+        }
+
         #[test]
         fn less() {
             let script = "
@@ -2608,4 +3382,124 @@ a.x + a.y # The meta map's y entry is hidden by the data entry
             test_script(script, 110.into());
         }
     }
+
+    mod chunk_builder {
+        use {
+            super::*,
+            koto_bytecode::{ChunkBuilder, Instruction},
+            std::sync::Arc,
+        };
+
+        #[test]
+        fn function_call_in_a_loop() {
+            // Equivalent to:
+            //   f = |x| x * x
+            //   sum = 0
+            //   for i in 0..4
+            //     sum += f i
+            //   sum
+            let mut builder = ChunkBuilder::new();
+            let function_end = builder.new_label();
+            let loop_start = builder.new_label();
+            let loop_end = builder.new_label();
+            let four = builder.add_i64(4).unwrap();
+
+            builder
+                .push_with_target(
+                    Instruction::SimpleFunction {
+                        register: 0,
+                        arg_count: 1,
+                        size: 0,
+                    },
+                    function_end,
+                )
+                .unwrap();
+            builder
+                .push(Instruction::Multiply {
+                    register: 1,
+                    lhs: 0,
+                    rhs: 0,
+                })
+                .unwrap();
+            builder.push(Instruction::Return { register: 1 }).unwrap();
+            builder.place_label(function_end).unwrap();
+
+            builder
+                .push(Instruction::SetNumber {
+                    register: 1,
+                    value: 0,
+                })
+                .unwrap();
+            builder
+                .push(Instruction::SetNumber {
+                    register: 5,
+                    value: 0,
+                })
+                .unwrap();
+            builder
+                .push(Instruction::LoadInt {
+                    register: 6,
+                    constant: four,
+                })
+                .unwrap();
+            builder
+                .push(Instruction::Range {
+                    register: 4,
+                    start: 5,
+                    end: 6,
+                })
+                .unwrap();
+            builder
+                .push(Instruction::MakeIterator {
+                    register: 3,
+                    iterable: 4,
+                })
+                .unwrap();
+
+            builder.place_label(loop_start).unwrap();
+            builder
+                .push_with_target(
+                    Instruction::IterNext {
+                        register: 2,
+                        iterator: 3,
+                        jump_offset: 0,
+                    },
+                    loop_end,
+                )
+                .unwrap();
+            builder
+                .push(Instruction::Copy {
+                    target: 6,
+                    source: 2,
+                })
+                .unwrap();
+            builder
+                .push(Instruction::Call {
+                    result: 4,
+                    function: 0,
+                    frame_base: 5,
+                    arg_count: 1,
+                })
+                .unwrap();
+            builder
+                .push(Instruction::Add {
+                    register: 1,
+                    lhs: 1,
+                    rhs: 4,
+                })
+                .unwrap();
+            builder
+                .push_with_target(Instruction::JumpBack { offset: 0 }, loop_start)
+                .unwrap();
+            builder.place_label(loop_end).unwrap();
+            builder.push(Instruction::Return { register: 1 }).unwrap();
+
+            let chunk = Arc::new(builder.build().unwrap());
+            match Vm::default().run(chunk) {
+                Ok(Number(n)) => assert_eq!(n, 14),
+                Ok(other) => panic!("Unexpected result: {}", other),
+                Err(error) => panic!("Error while running the chunk: {}", error),
+            }
+        }
+    }
 }